@@ -1,5 +1,18 @@
-use rnes_common::{Byte, Word, AudioSample, RnesResult};
-use std::collections::VecDeque;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use core::cell::Cell;
+use alloc::collections::VecDeque;
+use rnes_common::{Byte, Word, AudioSample, ExpansionChip, Region, RnesError, RnesResult};
+use serde::{Deserialize, Serialize};
+
+mod expansion;
+mod visualizer;
+
+pub use visualizer::{ApuChannel, ApuSnapshot, ChannelLevels, ChannelSnapshot, CHANNEL_COUNT, VISUALIZER_CAPACITY};
+use visualizer::Visualizer;
+use expansion::ExpansionMixer;
 
 // APU registers
 const APU_PULSE1_VOLUME: u16 = 0x4000;
@@ -41,10 +54,13 @@ const PULSE_DUTY_CYCLES: [[u8; 8]; 4] = [
     [1, 1, 1, 1, 1, 1, 0, 0], // 75%
 ];
 
-// Noise period table
+// Noise period tables (CPU cycles)
 const NOISE_PERIODS: [u16; 16] = [
     4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
 ];
+const NOISE_PERIODS_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
 
 // DMC period table
 const DMC_PERIODS: [u16; 16] = [
@@ -58,7 +74,7 @@ const LENGTH_COUNTER_TABLE: [u8; 32] = [
 ];
 
 /// Pulse wave channel
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct PulseChannel {
     enabled: bool,
     duty_cycle: u8,
@@ -163,18 +179,21 @@ impl PulseChannel {
             return 0.0;
         }
 
-        let envelope_output = if self.constant_volume {
+        (self.envelope_volume() as f32) / 15.0
+    }
+
+    /// Constant volume or the envelope's current level (0-15)
+    fn envelope_volume(&self) -> u8 {
+        if self.constant_volume {
             self.volume & 0x0F
         } else {
             self.envelope_counter
-        };
-
-        (envelope_output as f32) / 15.0
+        }
     }
 }
 
 /// Triangle wave channel
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct TriangleChannel {
     enabled: bool,
     timer: u16,
@@ -183,7 +202,9 @@ struct TriangleChannel {
     linear_counter: u8,
     linear_reload: u8,
     linear_reload_flag: bool,
+    control: bool,
     step: u8,
+    silence_ultrasonic: bool,
 }
 
 impl TriangleChannel {
@@ -196,7 +217,9 @@ impl TriangleChannel {
             linear_counter: 0,
             linear_reload: 0,
             linear_reload_flag: false,
+            control: false,
             step: 0,
+            silence_ultrasonic: true,
         }
     }
 
@@ -205,7 +228,10 @@ impl TriangleChannel {
             self.timer -= 1;
         } else {
             self.timer = self.timer_value;
-            if self.length_counter > 0 && self.linear_counter > 0 {
+            // Periods 0 and 1 run the sequencer at ~55 kHz; holding it
+            // keeps the output level instead of aliasing or popping
+            let ultrasonic = self.silence_ultrasonic && self.timer_value < 2;
+            if self.length_counter > 0 && self.linear_counter > 0 && !ultrasonic {
                 self.step = (self.step + 1) % 32;
             }
         }
@@ -217,19 +243,21 @@ impl TriangleChannel {
         } else if self.linear_counter > 0 {
             self.linear_counter -= 1;
         }
-        if !self.linear_reload_flag {
+        if !self.control {
             self.linear_reload_flag = false;
         }
     }
 
     fn step_length(&mut self) {
-        if self.length_counter > 0 {
+        if !self.control && self.length_counter > 0 {
             self.length_counter -= 1;
         }
     }
 
     fn output(&self) -> f32 {
-        if !self.enabled || self.length_counter == 0 || self.linear_counter == 0 {
+        // A halted sequencer keeps driving its last step; only a channel
+        // disabled through $4015 is treated as silent
+        if !self.enabled {
             return 0.0;
         }
 
@@ -244,7 +272,7 @@ impl TriangleChannel {
 }
 
 /// Noise channel
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct NoiseChannel {
     enabled: bool,
     timer: u16,
@@ -257,6 +285,14 @@ struct NoiseChannel {
     envelope_counter: u8,
     shift_register: u16,
     mode: bool,
+    period_index: u8,
+    /// Region setting, kept from the running APU when a save state loads
+    #[serde(skip, default = "ntsc_noise_periods")]
+    periods: &'static [u16; 16],
+}
+
+fn ntsc_noise_periods() -> &'static [u16; 16] {
+    &NOISE_PERIODS
 }
 
 impl NoiseChannel {
@@ -273,6 +309,8 @@ impl NoiseChannel {
             envelope_counter: 0,
             shift_register: 1,
             mode: false,
+            period_index: 0,
+            periods: &NOISE_PERIODS,
         }
     }
 
@@ -281,18 +319,25 @@ impl NoiseChannel {
             self.timer -= 1;
         } else {
             self.timer = self.timer_value;
-            
-            let feedback = if self.mode {
-                (self.shift_register >> 6) & 1
-            } else {
-                (self.shift_register >> 1) & 1
-            } ^ (self.shift_register & 1);
-            
-            self.shift_register >>= 1;
-            self.shift_register |= feedback << 14;
+            self.clock_shift_register();
         }
     }
 
+    /// Advance the 15-bit LFSR one step
+    ///
+    /// Feedback is bit 0 XOR bit 1, or bit 6 in mode 1, which gives the
+    /// 93-step short sequence from the power-up state.
+    fn clock_shift_register(&mut self) {
+        let tap = if self.mode { 6 } else { 1 };
+        let feedback = ((self.shift_register >> tap) ^ self.shift_register) & 1;
+        self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+    }
+
+    fn set_period(&mut self, index: u8) {
+        self.period_index = index & 0x0F;
+        self.timer_value = self.periods[self.period_index as usize];
+    }
+
     fn step_envelope(&mut self) {
         if self.envelope_start {
             self.envelope_start = false;
@@ -321,26 +366,33 @@ impl NoiseChannel {
             return 0.0;
         }
 
-        let envelope_output = if self.constant_volume {
+        (self.envelope_volume() as f32) / 15.0
+    }
+
+    /// Constant volume or the envelope's current level (0-15)
+    fn envelope_volume(&self) -> u8 {
+        if self.constant_volume {
             self.volume & 0x0F
         } else {
             self.envelope_counter
-        };
-
-        (envelope_output as f32) / 15.0
+        }
     }
 }
 
 /// DMC (Delta Modulation Channel)
-#[derive(Debug, Clone)]
+///
+/// The channel is playing while `bytes_remaining` is non-zero; there is no
+/// separate enable flag. The output level keeps sounding when it stops, so
+/// $4011 writes work as raw PCM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct DmcChannel {
-    enabled: bool,
     timer: u16,
     timer_value: u16,
     sample_buffer: u8,
     sample_buffer_empty: bool,
     shift_register: u8,
     bits_remaining: u8,
+    silence: bool,
     sample_address: u16,
     sample_length: u16,
     current_address: u16,
@@ -348,96 +400,127 @@ struct DmcChannel {
     loop_flag: bool,
     irq_enabled: bool,
     irq_pending: bool,
+    sample_finished: bool,
     output_level: u8,
+    /// Sample bytes fetched since power-on
+    fetches: u64,
 }
 
 impl DmcChannel {
     fn new() -> Self {
         Self {
-            enabled: false,
             timer: 0,
-            timer_value: 0,
+            timer_value: DMC_PERIODS[0],
             sample_buffer: 0,
             sample_buffer_empty: true,
             shift_register: 0,
-            bits_remaining: 0,
-            sample_address: 0,
-            sample_length: 0,
-            current_address: 0,
+            bits_remaining: 8,
+            silence: true,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
             bytes_remaining: 0,
             loop_flag: false,
             irq_enabled: false,
             irq_pending: false,
+            sample_finished: false,
             output_level: 0,
+            fetches: 0,
         }
     }
 
     fn step(&mut self) {
+        // The memory reader refills the sample buffer as soon as it empties
+        if self.sample_buffer_empty && self.bytes_remaining > 0 {
+            self.fetch_sample_byte();
+        }
+
         if self.timer > 0 {
             self.timer -= 1;
-        } else {
-            self.timer = self.timer_value;
-            
-            if self.bits_remaining == 0 {
-                if self.sample_buffer_empty {
-                    // Start new sample fetch
-                    if self.bytes_remaining > 0 {
-                        // TODO: Implement memory reading
-                        self.sample_buffer = 0;
-                        self.sample_buffer_empty = false;
-                        self.current_address = self.current_address.wrapping_add(1);
-                        self.bytes_remaining -= 1;
-                    } else if self.loop_flag {
-                        // Restart sample
-                        self.current_address = self.sample_address;
-                        self.bytes_remaining = self.sample_length;
-                    } else {
-                        self.enabled = false;
-                        if self.irq_enabled {
-                            self.irq_pending = true;
-                        }
-                    }
-                }
-                
-                if !self.sample_buffer_empty {
-                    self.shift_register = self.sample_buffer;
-                    self.bits_remaining = 8;
-                    self.sample_buffer_empty = true;
+            return;
+        }
+        self.timer = self.timer_value;
+
+        // A new output cycle takes the buffered byte, or stays silent if
+        // there is none. This is what lets the current byte finish after
+        // the channel is stopped.
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            self.silence = self.sample_buffer_empty;
+            if !self.sample_buffer_empty {
+                self.shift_register = self.sample_buffer;
+                self.sample_buffer_empty = true;
+            }
+        }
+
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
                 }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
             }
-            
-            if self.bits_remaining > 0 {
-                let bit = (self.shift_register >> 7) & 1;
-                self.shift_register <<= 1;
-                self.bits_remaining -= 1;
-                
-                if bit != 0 {
-                    if self.output_level < 126 {
-                        self.output_level += 2;
-                    }
-                } else {
-                    if self.output_level > 1 {
-                        self.output_level -= 2;
-                    }
+        }
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+    }
+
+    fn fetch_sample_byte(&mut self) {
+        // TODO: Implement memory reading
+        self.sample_buffer = 0;
+        self.sample_buffer_empty = false;
+        self.fetches += 1;
+        // The address wraps from $FFFF to $8000
+        self.current_address = self.current_address.wrapping_add(1) | 0x8000;
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else {
+                self.sample_finished = true;
+                if self.irq_enabled {
+                    self.irq_pending = true;
                 }
             }
         }
     }
 
-    fn output(&self) -> f32 {
-        if !self.enabled {
-            return 0.0;
+    /// Start the sample again from $4012/$4013
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /// Handle the DMC bit of a $4015 write
+    ///
+    /// Setting it starts the sample only if it has finished; clearing it
+    /// stops fetching, but bytes already fetched still play out.
+    fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
         }
+    }
+
+    fn output(&self) -> f32 {
         (self.output_level as f32) / 127.0
     }
 }
 
 /// Frame counter for timing audio updates
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct FrameCounter {
     mode: u8,
     step: u8,
     cycles: u32,
+    /// $4017 bit 6: never raise the frame IRQ
+    irq_inhibit: bool,
+    /// Set at the end of each 4-step sequence, read back through $4015 bit 6
+    /// and cleared by that read
+    irq_pending: Cell<bool>,
 }
 
 impl FrameCounter {
@@ -446,6 +529,8 @@ impl FrameCounter {
             mode: 0,
             step: 0,
             cycles: 0,
+            irq_inhibit: false,
+            irq_pending: Cell::new(false),
         }
     }
 
@@ -466,6 +551,9 @@ impl FrameCounter {
         
         if quarter_frame || half_frame {
             self.step = (self.step + 1) % 4;
+            if self.step == 0 && self.mode == 0 && !self.irq_inhibit {
+                self.irq_pending.set(true);
+            }
             true
         } else {
             false
@@ -474,6 +562,40 @@ impl FrameCounter {
 }
 
 /// APU implementation
+/// Channel, DMC and frame counter state for save states
+///
+/// Mixer settings, the region and queued samples are not included.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApuState {
+    pulse1: PulseChannel,
+    pulse2: PulseChannel,
+    triangle: TriangleChannel,
+    noise: NoiseChannel,
+    dmc: DmcChannel,
+    frame_counter: FrameCounter,
+    cycles_since_sample: f32,
+}
+
+impl ApuState {
+    /// Check the sequencer positions the channels index tables with
+    pub fn validate(&self) -> RnesResult<()> {
+        let pulse_valid = |pulse: &PulseChannel| {
+            pulse.duty_cycle < 4 && pulse.duty_step < 8 && pulse.sweep_shift <= 7
+        };
+        let valid = pulse_valid(&self.pulse1)
+            && pulse_valid(&self.pulse2)
+            && self.triangle.step < 32
+            && self.noise.period_index < 16
+            && self.dmc.bits_remaining <= 8;
+        if valid {
+            Ok(())
+        } else {
+            Err(RnesError::CorruptSaveState("APU state out of range".into()))
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Apu {
     pulse1: PulseChannel,
     pulse2: PulseChannel,
@@ -483,6 +605,10 @@ pub struct Apu {
     frame_counter: FrameCounter,
     sample_buffer: VecDeque<AudioSample>,
     cycles_since_sample: f32,
+    cycles_per_sample: f32,
+    master_volume: f32,
+    expansion: ExpansionMixer,
+    visualizer: Visualizer,
 }
 
 impl Apu {
@@ -496,6 +622,10 @@ impl Apu {
             frame_counter: FrameCounter::new(),
             sample_buffer: VecDeque::new(),
             cycles_since_sample: 0.0,
+            cycles_per_sample: CYCLES_PER_SAMPLE,
+            master_volume: 1.0,
+            expansion: ExpansionMixer::new(),
+            visualizer: Visualizer::default(),
         }
     }
 
@@ -531,8 +661,8 @@ impl Apu {
 
         // Generate audio samples
         self.cycles_since_sample += 1.0;
-        if self.cycles_since_sample >= CYCLES_PER_SAMPLE {
-            self.cycles_since_sample -= CYCLES_PER_SAMPLE;
+        if self.cycles_since_sample >= self.cycles_per_sample {
+            self.cycles_since_sample -= self.cycles_per_sample;
             self.generate_sample();
         }
 
@@ -546,6 +676,7 @@ impl Apu {
         let triangle_output = self.triangle.output();
         let noise_output = self.noise.output();
         let dmc_output = self.dmc.output();
+        self.visualizer.record([pulse1_output, pulse2_output, triangle_output, noise_output, dmc_output]);
 
         // Mix audio channels
         let mixed = self.mix_audio(pulse1_output, pulse2_output, triangle_output, noise_output, dmc_output);
@@ -564,7 +695,7 @@ impl Apu {
         let pulse_out = 95.88 / (8128.0 / (pulse1 + pulse2) + 100.0);
         let tnd_out = 159.79 / (1.0 / (triangle / 8227.0 + noise / 12241.0 + dmc / 22638.0) + 100.0);
         
-        (pulse_out + tnd_out) / 2.0
+        ((pulse_out + tnd_out) / 2.0 + self.expansion.level()) * self.master_volume
     }
 
     /// Read APU register
@@ -577,6 +708,7 @@ impl Apu {
                 if self.triangle.length_counter > 0 { status |= 0x04; }
                 if self.noise.length_counter > 0 { status |= 0x08; }
                 if self.dmc.bytes_remaining > 0 { status |= 0x10; }
+                if self.frame_counter.irq_pending.get() { status |= 0x40; }
                 if self.dmc.irq_pending { status |= 0x80; }
                 Ok(status)
             }
@@ -584,6 +716,13 @@ impl Apu {
         }
     }
 
+    /// Read $4015 as the CPU does, acknowledging the frame IRQ
+    pub fn read_status(&self) -> Byte {
+        let status = self.read_register(APU_STATUS).unwrap_or(0);
+        self.frame_counter.irq_pending.set(false);
+        status
+    }
+
     /// Write APU register
     pub fn write_register(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
         match addr {
@@ -637,8 +776,8 @@ impl Apu {
 
             // Triangle
             APU_TRIANGLE_LINEAR => {
+                self.triangle.control = (value & 0x80) != 0;
                 self.triangle.linear_reload = value & 0x7F;
-                self.triangle.linear_reload_flag = true;
             }
             APU_TRIANGLE_FREQ_LOW => {
                 self.triangle.timer_value = (self.triangle.timer_value & 0xFF00) | value as u16;
@@ -647,6 +786,8 @@ impl Apu {
                 self.triangle.timer_value = (self.triangle.timer_value & 0x00FF) | (((value & 0x07) as u16) << 8);
                 let length_index = (value >> 3) & 0x1F;
                 self.triangle.length_counter = LENGTH_COUNTER_TABLE[length_index as usize];
+                // The sequencer phase is left alone
+                self.triangle.linear_reload_flag = true;
             }
 
             // Noise
@@ -657,8 +798,7 @@ impl Apu {
             }
             APU_NOISE_FREQ => {
                 self.noise.mode = (value & 0x80) != 0;
-                let period_index = (value & 0x0F) as usize;
-                self.noise.timer_value = NOISE_PERIODS[period_index];
+                self.noise.set_period(value & 0x0F);
             }
             APU_NOISE_LENGTH => {
                 let length_index = (value >> 3) & 0x1F;
@@ -670,6 +810,9 @@ impl Apu {
             APU_DMC_FREQ => {
                 self.dmc.irq_enabled = (value & 0x80) != 0;
                 self.dmc.loop_flag = (value & 0x40) != 0;
+                if !self.dmc.irq_enabled {
+                    self.dmc.irq_pending = false;
+                }
                 let period_index = (value & 0x0F) as usize;
                 self.dmc.timer_value = DMC_PERIODS[period_index];
             }
@@ -689,13 +832,12 @@ impl Apu {
                 self.pulse2.enabled = (value & 0x02) != 0;
                 self.triangle.enabled = (value & 0x04) != 0;
                 self.noise.enabled = (value & 0x08) != 0;
-                self.dmc.enabled = (value & 0x10) != 0;
                 
                 if !self.pulse1.enabled { self.pulse1.length_counter = 0; }
                 if !self.pulse2.enabled { self.pulse2.length_counter = 0; }
                 if !self.triangle.enabled { self.triangle.length_counter = 0; }
                 if !self.noise.enabled { self.noise.length_counter = 0; }
-                if !self.dmc.enabled { self.dmc.bytes_remaining = 0; }
+                self.dmc.set_enabled((value & 0x10) != 0);
                 
                 self.dmc.irq_pending = false;
             }
@@ -703,6 +845,10 @@ impl Apu {
             // Frame counter
             APU_FRAME_COUNTER => {
                 self.frame_counter.mode = value & 0x80;
+                self.frame_counter.irq_inhibit = (value & 0x40) != 0;
+                if self.frame_counter.irq_inhibit {
+                    self.frame_counter.irq_pending.set(false);
+                }
                 // TODO: Handle 5-step sequence
             }
 
@@ -714,10 +860,136 @@ impl Apu {
         Ok(())
     }
 
-    /// Get audio samples
-    pub fn get_samples(&mut self) -> Vec<AudioSample> {
-        let samples: Vec<AudioSample> = self.sample_buffer.drain(..).collect();
-        samples
+    /// Move up to `out.len()` of the oldest samples into `out`, returning
+    /// how many were written
+    pub fn fill_samples(&mut self, out: &mut [AudioSample]) -> usize {
+        let count = out.len().min(self.sample_buffer.len());
+        for (slot, sample) in out.iter_mut().zip(self.sample_buffer.drain(..count)) {
+            *slot = sample;
+        }
+        count
+    }
+
+    /// Take all buffered samples, oldest first
+    pub fn drain_samples(&mut self) -> impl Iterator<Item = AudioSample> + '_ {
+        self.sample_buffer.drain(..)
+    }
+
+    /// Number of buffered samples
+    pub fn samples_available(&self) -> usize {
+        self.sample_buffer.len()
+    }
+
+    /// Discard all buffered samples
+    pub fn clear_samples(&mut self) {
+        self.sample_buffer.clear();
+    }
+
+    /// Set master output volume (0.0 - 1.0)
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Set the output of the cartridge's sound chip (-1.0 to 1.0 of its full
+    /// scale), mixed into following samples; `None` silences expansion audio
+    pub fn set_expansion_audio(&mut self, output: Option<(ExpansionChip, f32)>) {
+        match output {
+            Some((chip, output)) => self.expansion.set_output(chip, output),
+            None => self.expansion.clear(),
+        }
+    }
+
+    /// Set the volume of a sound chip relative to its hardware level
+    pub fn set_expansion_gain(&mut self, chip: ExpansionChip, gain: f32) {
+        self.expansion.set_gain(chip, gain);
+    }
+
+    /// Get the volume of a sound chip relative to its hardware level
+    pub fn expansion_gain(&self, chip: ExpansionChip) -> f32 {
+        self.expansion.gain(chip)
+    }
+
+    /// Mute or unmute a sound chip
+    pub fn set_expansion_enabled(&mut self, chip: ExpansionChip, enabled: bool) {
+        self.expansion.set_enabled(chip, enabled);
+    }
+
+    /// Whether a sound chip is mixed in
+    pub fn expansion_enabled(&self, chip: ExpansionChip) -> bool {
+        self.expansion.enabled(chip)
+    }
+
+    /// Get master output volume
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    /// Reset APU as the console's reset button does
+    ///
+    /// All channels are silenced as if $4015 were written with 0 and the
+    /// frame counter restarts in its current mode. Other channel registers
+    /// keep their values.
+    pub fn reset(&mut self) {
+        let _ = self.write_register(APU_STATUS, 0);
+        self.frame_counter.step = 0;
+        self.frame_counter.cycles = 0;
+        self.frame_counter.irq_pending.set(false);
+        self.dmc.output_level &= 0x01;
+    }
+
+    /// Capture channel and frame counter state for a save state
+    pub fn save_state(&self) -> ApuState {
+        ApuState {
+            pulse1: self.pulse1.clone(),
+            pulse2: self.pulse2.clone(),
+            triangle: self.triangle.clone(),
+            noise: self.noise.clone(),
+            dmc: self.dmc.clone(),
+            frame_counter: self.frame_counter.clone(),
+            cycles_since_sample: self.cycles_since_sample,
+        }
+    }
+
+    /// Restore state taken with `save_state`, keeping the region and settings
+    pub fn load_state(&mut self, state: ApuState) -> RnesResult<()> {
+        state.validate()?;
+        let periods = self.noise.periods;
+        let silence_ultrasonic = self.triangle.silence_ultrasonic;
+        self.pulse1 = state.pulse1;
+        self.pulse2 = state.pulse2;
+        self.triangle = state.triangle;
+        self.noise = state.noise;
+        self.dmc = state.dmc;
+        self.frame_counter = state.frame_counter;
+        self.cycles_since_sample = state.cycles_since_sample;
+        self.noise.periods = periods;
+        self.triangle.silence_ultrasonic = silence_ultrasonic;
+        Ok(())
+    }
+
+    /// Set CPU clock rate (Hz) used to resample output to 44.1 kHz
+    pub fn set_cpu_clock_rate(&mut self, clock_rate: u32) {
+        self.cycles_per_sample = clock_rate as f32 / SAMPLE_RATE as f32;
+    }
+
+    /// Select the region's noise period table and clock rate
+    pub fn set_region(&mut self, region: Region) {
+        self.noise.periods = match region {
+            Region::Ntsc => &NOISE_PERIODS,
+            Region::Pal | Region::Dendy => &NOISE_PERIODS_PAL,
+        };
+        self.noise.set_period(self.noise.period_index);
+        self.set_cpu_clock_rate(region.cpu_clock_rate());
+    }
+
+    /// Hold the triangle's sequencer at timer periods 0 and 1
+    pub fn set_silence_ultrasonic_triangle(&mut self, silence: bool) {
+        self.triangle.silence_ultrasonic = silence;
+    }
+
+    /// Check if the frame counter IRQ is pending
+    pub fn frame_irq_pending(&self) -> bool {
+        self.frame_counter.irq_pending.get()
     }
 
     /// Check if DMC IRQ is pending
@@ -725,10 +997,23 @@ impl Apu {
         self.dmc.irq_pending
     }
 
+    /// Number of sample bytes the DMC has fetched
+    pub fn dmc_fetches(&self) -> u64 {
+        self.dmc.fetches
+    }
+
     /// Clear DMC IRQ
     pub fn clear_dmc_irq(&mut self) {
         self.dmc.irq_pending = false;
     }
+
+    /// Check and clear whether a non-looping DMC sample has finished
+    ///
+    /// Set whether or not the DMC IRQ is enabled, for frontends and tests
+    /// that want to know when a sample ends.
+    pub fn take_dmc_sample_finished(&mut self) -> bool {
+        core::mem::take(&mut self.dmc.sample_finished)
+    }
 }
 
 impl Default for Apu {
@@ -736,3 +1021,139 @@ impl Default for Apu {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lfsr_sequence(mode: bool, steps: usize) -> Vec<u16> {
+        let mut noise = NoiseChannel::new();
+        noise.mode = mode;
+        (0..steps)
+            .map(|_| {
+                let value = noise.shift_register;
+                noise.clock_shift_register();
+                value
+            })
+            .collect()
+    }
+
+    fn sequence_length(mode: bool) -> usize {
+        let mut noise = NoiseChannel::new();
+        noise.mode = mode;
+        let start = noise.shift_register;
+        (1..=32767)
+            .find(|_| {
+                noise.clock_shift_register();
+                noise.shift_register == start
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_noise_sequence_lengths() {
+        assert_eq!(sequence_length(false), 32767);
+        assert_eq!(sequence_length(true), 93);
+    }
+
+    #[test]
+    fn test_noise_short_sequence_matches_dump() {
+        // Shift register values from power-up in mode 1
+        assert_eq!(lfsr_sequence(true, 16), [
+            0x0001, 0x4000, 0x2000, 0x1000, 0x0800, 0x0400, 0x0200, 0x0100,
+            0x0080, 0x0040, 0x4020, 0x2010, 0x1008, 0x0804, 0x0402, 0x0201,
+        ]);
+
+        // Bit 0 over the full 93-step period; the channel is muted while it is set
+        let expected = "100000000000000100000000100000100100000000100100100100100000000000100100000100100100000100000";
+        let bits: String = lfsr_sequence(true, 93).iter().map(|value| if value & 1 != 0 { '1' } else { '0' }).collect();
+        assert_eq!(bits, expected);
+    }
+
+    #[test]
+    fn test_noise_long_sequence_matches_dump() {
+        let expected = "100000000000000100000000000001100000000000010100000000000111100000000001000100000000011001100";
+        let bits: String = lfsr_sequence(false, 93).iter().map(|value| if value & 1 != 0 { '1' } else { '0' }).collect();
+        assert_eq!(bits, expected);
+    }
+
+    #[test]
+    fn test_noise_period_table_follows_region() {
+        let mut apu = Apu::new();
+        apu.write_register(APU_NOISE_FREQ, 0x0D).unwrap();
+        assert_eq!(apu.noise.timer_value, 1016);
+
+        // Switching region re-reads the period already written
+        apu.set_region(Region::Pal);
+        assert_eq!(apu.noise.timer_value, 944);
+        apu.write_register(APU_NOISE_FREQ, 0x8F).unwrap();
+        assert_eq!((apu.noise.timer_value, apu.noise.mode), (3778, true));
+
+        apu.set_region(Region::Ntsc);
+        assert_eq!(apu.noise.timer_value, 4068);
+    }
+
+    #[test]
+    fn test_frame_irq_flag() {
+        let mut apu = Apu::new();
+        for _ in 0..4 * 7457 {
+            apu.step().unwrap();
+        }
+        assert!(apu.frame_irq_pending());
+        assert_eq!(apu.read_register(APU_STATUS).unwrap() & 0x40, 0x40);
+
+        // Reading $4015 acknowledges it
+        assert_eq!(apu.read_status() & 0x40, 0x40);
+        assert!(!apu.frame_irq_pending());
+
+        // The inhibit bit clears it and keeps it from being raised
+        for _ in 0..4 * 7457 {
+            apu.step().unwrap();
+        }
+        apu.write_register(APU_FRAME_COUNTER, 0x40).unwrap();
+        assert!(!apu.frame_irq_pending());
+        for _ in 0..4 * 7457 {
+            apu.step().unwrap();
+        }
+        assert!(!apu.frame_irq_pending());
+    }
+
+    #[test]
+    fn test_expansion_audio_mixing() {
+        let mut apu = Apu::new();
+        let silent = |apu: &Apu| apu.mix_audio(0.0, 0.0, 0.0, 0.0, 0.0);
+        assert_eq!(silent(&apu), 0.0);
+        let pulse = apu.mix_audio(15.0, 0.0, 0.0, 0.0, 0.0);
+
+        // A VRC7 channel at full volume sits a little below an APU pulse
+        apu.set_expansion_audio(Some((ExpansionChip::Vrc7, 1.0 / 6.0)));
+        let vrc7_channel = silent(&apu);
+        assert!(vrc7_channel > 0.7 * pulse && vrc7_channel < pulse, "{} vs {}", vrc7_channel, pulse);
+
+        apu.set_expansion_gain(ExpansionChip::Vrc7, 0.5);
+        apu.set_expansion_audio(Some((ExpansionChip::Vrc7, 1.0 / 6.0)));
+        assert!((silent(&apu) - vrc7_channel * 0.5).abs() < 1e-6);
+        assert_eq!(apu.expansion_gain(ExpansionChip::Fds), 1.0);
+
+        // Muting one chip leaves the others alone
+        apu.set_expansion_enabled(ExpansionChip::Vrc7, false);
+        apu.set_expansion_audio(Some((ExpansionChip::Vrc7, 1.0)));
+        assert_eq!(silent(&apu), 0.0);
+        apu.set_expansion_audio(Some((ExpansionChip::Fds, 1.0)));
+        assert!(silent(&apu) > 0.0);
+        apu.set_expansion_audio(None);
+        assert_eq!(silent(&apu), 0.0);
+    }
+
+    #[test]
+    fn test_load_state_rejects_out_of_range_values() {
+        let mut apu = Apu::new();
+        let mut state = apu.save_state();
+        state.pulse1.duty_step = 8;
+        assert!(apu.load_state(state).is_err());
+        let mut state = apu.save_state();
+        state.triangle.step = 32;
+        assert!(apu.load_state(state).is_err());
+        apu.load_state(apu.save_state()).unwrap();
+    }
+}
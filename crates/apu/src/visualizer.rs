@@ -0,0 +1,181 @@
+//! Per-channel data for oscilloscope and piano-roll views
+//!
+//! While enabled, every output sample also records each channel's raw level.
+//! Frontends drain those levels into their own buffer once per frame and take
+//! a `ApuSnapshot` of the decoded channel registers alongside. Neither call
+//! allocates.
+
+use alloc::collections::VecDeque;
+use super::Apu;
+
+/// Number of channels in a visualizer sample
+pub const CHANNEL_COUNT: usize = 5;
+
+/// Samples kept while nobody drains them, about 90 ms at 44.1 kHz
+pub const VISUALIZER_CAPACITY: usize = 4096;
+
+/// Raw level (0.0-1.0) of pulse 1, pulse 2, triangle, noise and DMC
+pub type ChannelLevels = [f32; CHANNEL_COUNT];
+
+/// APU channel, in the order used by `ChannelLevels`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApuChannel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+/// Decoded state of one channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelSnapshot {
+    pub channel: ApuChannel,
+    /// Enabled through $4015; for the DMC, a sample is playing
+    pub enabled: bool,
+    /// Timer period in CPU cycles (the raw 11-bit value for pulse and triangle)
+    pub period: u16,
+    /// Current volume, 0-15; the DMC reports its output level, 0-127
+    pub volume: u8,
+    /// Pulse duty cycle (0-3), or the noise mode bit
+    pub duty: u8,
+    /// Length counter; for the DMC, bytes left in the sample
+    pub length_remaining: u16,
+    /// Raw output level (0.0-1.0)
+    pub output: f32,
+}
+
+impl ChannelSnapshot {
+    /// Pitch in Hz at the given CPU clock rate
+    ///
+    /// Only pulse and triangle are tonal; `None` for the others and for
+    /// periods too short to sound.
+    pub fn frequency(&self, cpu_clock_rate: u32) -> Option<f32> {
+        let steps = match self.channel {
+            ApuChannel::Pulse1 | ApuChannel::Pulse2 if self.period >= 8 => 16.0,
+            ApuChannel::Triangle if self.period >= 2 => 32.0,
+            _ => return None,
+        };
+        Some(cpu_clock_rate as f32 / (steps * (self.period as f32 + 1.0)))
+    }
+}
+
+/// Decoded state of every channel at one moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApuSnapshot {
+    pub pulse1: ChannelSnapshot,
+    pub pulse2: ChannelSnapshot,
+    pub triangle: ChannelSnapshot,
+    pub noise: ChannelSnapshot,
+    pub dmc: ChannelSnapshot,
+}
+
+impl ApuSnapshot {
+    /// Channels in `ChannelLevels` order
+    pub fn channels(&self) -> [ChannelSnapshot; CHANNEL_COUNT] {
+        [self.pulse1, self.pulse2, self.triangle, self.noise, self.dmc]
+    }
+}
+
+/// Buffer of per-channel levels, one entry per output sample
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Visualizer {
+    enabled: bool,
+    levels: VecDeque<ChannelLevels>,
+}
+
+impl Visualizer {
+    pub(crate) fn record(&mut self, levels: ChannelLevels) {
+        if !self.enabled {
+            return;
+        }
+        if self.levels.len() == VISUALIZER_CAPACITY {
+            self.levels.pop_front();
+        }
+        self.levels.push_back(levels);
+    }
+}
+
+impl Apu {
+    /// Start or stop recording per-channel levels
+    ///
+    /// Storage is reserved once when first enabled.
+    pub fn set_visualizer_enabled(&mut self, enabled: bool) {
+        let visualizer = &mut self.visualizer;
+        visualizer.enabled = enabled;
+        if enabled {
+            visualizer.levels.reserve_exact(VISUALIZER_CAPACITY.saturating_sub(visualizer.levels.len()));
+        } else {
+            visualizer.levels.clear();
+        }
+    }
+
+    /// Check if per-channel levels are being recorded
+    pub fn visualizer_enabled(&self) -> bool {
+        self.visualizer.enabled
+    }
+
+    /// Copy recorded channel levels into `out`, oldest first
+    ///
+    /// Returns how many entries were written. Levels that don't fit stay
+    /// queued for the next call.
+    pub fn drain_channel_levels(&mut self, out: &mut [ChannelLevels]) -> usize {
+        let count = out.len().min(self.visualizer.levels.len());
+        for (slot, levels) in out.iter_mut().zip(self.visualizer.levels.drain(..count)) {
+            *slot = levels;
+        }
+        count
+    }
+
+    /// Decode every channel's registers and current output
+    pub fn snapshot(&self) -> ApuSnapshot {
+        ApuSnapshot {
+            pulse1: ChannelSnapshot {
+                channel: ApuChannel::Pulse1,
+                enabled: self.pulse1.enabled,
+                period: self.pulse1.timer_value,
+                volume: self.pulse1.envelope_volume(),
+                duty: self.pulse1.duty_cycle,
+                length_remaining: self.pulse1.length_counter as u16,
+                output: self.pulse1.output(),
+            },
+            pulse2: ChannelSnapshot {
+                channel: ApuChannel::Pulse2,
+                enabled: self.pulse2.enabled,
+                period: self.pulse2.timer_value,
+                volume: self.pulse2.envelope_volume(),
+                duty: self.pulse2.duty_cycle,
+                length_remaining: self.pulse2.length_counter as u16,
+                output: self.pulse2.output(),
+            },
+            triangle: ChannelSnapshot {
+                channel: ApuChannel::Triangle,
+                enabled: self.triangle.enabled,
+                period: self.triangle.timer_value,
+                // The triangle has no volume control, only on and off
+                volume: if self.triangle.length_counter > 0 && self.triangle.linear_counter > 0 { 15 } else { 0 },
+                duty: 0,
+                length_remaining: self.triangle.length_counter as u16,
+                output: self.triangle.output(),
+            },
+            noise: ChannelSnapshot {
+                channel: ApuChannel::Noise,
+                enabled: self.noise.enabled,
+                period: self.noise.timer_value,
+                volume: self.noise.envelope_volume(),
+                duty: self.noise.mode as u8,
+                length_remaining: self.noise.length_counter as u16,
+                output: self.noise.output(),
+            },
+            dmc: ChannelSnapshot {
+                channel: ApuChannel::Dmc,
+                enabled: self.dmc.bytes_remaining > 0,
+                period: self.dmc.timer_value,
+                volume: self.dmc.output_level,
+                duty: 0,
+                length_remaining: self.dmc.bytes_remaining,
+                output: self.dmc.output(),
+            },
+        }
+    }
+}
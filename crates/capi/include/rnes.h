@@ -0,0 +1,125 @@
+#ifndef RNES_H
+#define RNES_H
+
+/* This file is generated by cbindgen. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Call succeeded
+#define RNES_OK 0
+
+// A required pointer argument was null
+#define RNES_ERR_NULL_POINTER -1
+
+// An argument was out of range
+#define RNES_ERR_INVALID_ARGUMENT -2
+
+// The ROM image could not be loaded
+#define RNES_ERR_ROM -3
+
+// Emulation stopped with an error
+#define RNES_ERR_EMULATION -4
+
+// A save state could not be created or restored
+#define RNES_ERR_STATE -5
+
+// The output buffer is too small; the required size is reported
+#define RNES_ERR_BUFFER_TOO_SMALL -6
+
+// The operation needs a ROM to be loaded first
+#define RNES_ERR_NO_ROM -7
+
+// Size of the RGBA8 frame buffer in bytes
+#define RNES_FRAMEBUFFER_SIZE ((256 * 240) * 4)
+
+// Opaque emulator handle
+typedef struct RnesEmulator RnesEmulator;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Get the library version as a NUL-terminated string
+const char *rnes_version(void);
+
+// Create a new emulator instance
+//
+// The returned handle must be released with `rnes_emulator_destroy`.
+RnesEmulator *rnes_emulator_create(void);
+
+// Destroy an emulator instance
+void rnes_emulator_destroy(RnesEmulator *emu);
+
+// Load an iNES ROM image from memory and reset the emulator
+int32_t rnes_load_rom(RnesEmulator *emu, const uint8_t *data, size_t len);
+
+// Run the emulator until the next frame is complete
+int32_t rnes_run_frame(RnesEmulator *emu);
+
+// Press the console's reset button
+//
+// RAM and cartridge state are kept, as on hardware.
+int32_t rnes_soft_reset(RnesEmulator *emu);
+
+// Turn the console off and on again
+//
+// RAM is refilled with its power-on contents; battery-backed RAM survives.
+int32_t rnes_power_cycle(RnesEmulator *emu);
+
+// Get the current frame as 256x240 RGBA8 pixels
+//
+// The pointer stays valid until the next call on this handle. If `len` is not
+// null it receives the buffer size in bytes.
+const uint8_t *rnes_get_framebuffer(RnesEmulator *emu, size_t *len);
+
+// Get the rows of the current frame that changed since the previous frame
+//
+// Rows `first` up to (not including) `end` differ; both are 0 when the frame
+// is unchanged and its texture upload can be skipped.
+int32_t rnes_get_dirty_lines(const RnesEmulator *emu, uint32_t *first, uint32_t *end);
+
+// Drain audio samples produced since the last call
+//
+// Samples are mono `f32` values at the APU output rate. The pointer stays
+// valid until the next call on this handle.
+const float *rnes_get_audio_samples(RnesEmulator *emu, size_t *count);
+
+// Copy up to `capacity` of the oldest audio samples into `out`
+//
+// Returns the number of samples written, so an audio callback can request
+// exactly one device buffer. Samples left over stay queued for the next call.
+size_t rnes_fill_audio(RnesEmulator *emu, float *out, size_t capacity);
+
+// Set the button state for a controller port (0 or 1)
+//
+// `buttons` uses the standard bit layout: A, B, Select, Start, Up, Down,
+// Left, Right from bit 0 to bit 7.
+// The new state is latched when the next frame starts.
+int32_t rnes_set_input(RnesEmulator *emu, uint32_t port, uint8_t buttons);
+
+// Serialize the machine state into a caller-provided buffer
+//
+// `written` receives the number of bytes written. If the buffer is too small
+// nothing is written, `written` receives the required size and
+// `RNES_ERR_BUFFER_TOO_SMALL` is returned, so passing a null buffer with a
+// capacity of zero can be used to query the size.
+int32_t rnes_save_state(RnesEmulator *emu, uint8_t *buffer, size_t capacity, size_t *written);
+
+// Restore the machine state from a buffer produced by `rnes_save_state`
+int32_t rnes_load_state(RnesEmulator *emu, const uint8_t *data, size_t len);
+
+// Get the message for the most recent error on this handle
+//
+// Returns null if no error has occurred. The string stays valid until the
+// next failing call on this handle.
+const char *rnes_last_error(const RnesEmulator *emu);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // RNES_H
@@ -0,0 +1,506 @@
+//! C API for embedding the RNES core
+//!
+//! All functions take an opaque `RnesEmulator` handle created with
+//! `rnes_emulator_create` and released with `rnes_emulator_destroy`.
+//! Functions returning `i32` use the `RNES_*` status codes below; on failure
+//! a human-readable message is available from `rnes_last_error`.
+//!
+//! The C header lives in `include/rnes.h` and is generated with cbindgen
+//! (see `cbindgen.toml`).
+
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::slice;
+
+use rnes_cartridge::Cartridge;
+use rnes_common::{Config, RnesError, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rnes_core::Emulator;
+
+/// Call succeeded
+pub const RNES_OK: i32 = 0;
+/// A required pointer argument was null
+pub const RNES_ERR_NULL_POINTER: i32 = -1;
+/// An argument was out of range
+pub const RNES_ERR_INVALID_ARGUMENT: i32 = -2;
+/// The ROM image could not be loaded
+pub const RNES_ERR_ROM: i32 = -3;
+/// Emulation stopped with an error
+pub const RNES_ERR_EMULATION: i32 = -4;
+/// A save state could not be created or restored
+pub const RNES_ERR_STATE: i32 = -5;
+/// The output buffer is too small; the required size is reported
+pub const RNES_ERR_BUFFER_TOO_SMALL: i32 = -6;
+/// The operation needs a ROM to be loaded first
+pub const RNES_ERR_NO_ROM: i32 = -7;
+
+/// Size of the RGBA8 frame buffer in bytes
+pub const RNES_FRAMEBUFFER_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 4;
+
+/// Opaque emulator handle
+pub struct RnesEmulator {
+    emulator: Emulator,
+    rom_loaded: bool,
+    frame: Vec<u8>,
+    audio: Vec<f32>,
+    last_error: Option<CString>,
+}
+
+impl RnesEmulator {
+    fn new() -> Self {
+        Self {
+            emulator: Emulator::with_config(Config::default()),
+            rom_loaded: false,
+            frame: vec![0; RNES_FRAMEBUFFER_SIZE],
+            audio: Vec::new(),
+            last_error: None,
+        }
+    }
+
+    /// Record an error message and return the given status code
+    fn fail(&mut self, code: i32, message: impl Into<String>) -> i32 {
+        let message = message.into();
+        tracing::warn!("rnes-capi: {}", message);
+        // Interior NUL bytes cannot be represented in a C string
+        self.last_error = CString::new(message.replace('\0', " ")).ok();
+        code
+    }
+
+    fn fail_with(&mut self, code: i32, error: RnesError) -> i32 {
+        self.fail(code, error.to_string())
+    }
+
+    /// Copy the PPU frame buffer into the RGBA8 output buffer
+    fn update_frame(&mut self) {
+        self.emulator.write_frame_rgba(&mut self.frame);
+    }
+}
+
+/// Get the library version as a NUL-terminated string
+#[no_mangle]
+pub extern "C" fn rnes_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Create a new emulator instance
+///
+/// The returned handle must be released with `rnes_emulator_destroy`.
+#[no_mangle]
+pub extern "C" fn rnes_emulator_create() -> *mut RnesEmulator {
+    Box::into_raw(Box::new(RnesEmulator::new()))
+}
+
+/// Destroy an emulator instance
+///
+/// # Safety
+///
+/// `emu` must be null or a handle returned by `rnes_emulator_create` that has
+/// not already been destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_emulator_destroy(emu: *mut RnesEmulator) {
+    if !emu.is_null() {
+        drop(Box::from_raw(emu));
+    }
+}
+
+/// Load an iNES ROM image from memory and reset the emulator
+///
+/// # Safety
+///
+/// `emu` must be a valid handle and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_load_rom(emu: *mut RnesEmulator, data: *const u8, len: usize) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    if data.is_null() {
+        return emu.fail(RNES_ERR_NULL_POINTER, "ROM data pointer is null");
+    }
+
+    let rom = slice::from_raw_parts(data, len);
+    let cartridge = match Cartridge::from_bytes(rom) {
+        Ok(cartridge) => cartridge,
+        Err(e) => return emu.fail_with(RNES_ERR_ROM, e),
+    };
+
+    if let Err(e) = emu.emulator.load_rom(cartridge) {
+        emu.rom_loaded = false;
+        return emu.fail_with(RNES_ERR_ROM, e);
+    }
+
+    emu.rom_loaded = true;
+    emu.emulator.start();
+    emu.frame.fill(0);
+    emu.audio.clear();
+    RNES_OK
+}
+
+/// Run the emulator until the next frame is complete
+///
+/// # Safety
+///
+/// `emu` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_run_frame(emu: *mut RnesEmulator) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    if !emu.rom_loaded {
+        return emu.fail(RNES_ERR_NO_ROM, "No ROM loaded");
+    }
+
+    if let Err(e) = emu.emulator.run_frame() {
+        return emu.fail_with(RNES_ERR_EMULATION, e);
+    }
+
+    emu.update_frame();
+    RNES_OK
+}
+
+/// Press the console's reset button
+///
+/// RAM and cartridge state are kept, as on hardware.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_soft_reset(emu: *mut RnesEmulator) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    if !emu.rom_loaded {
+        return emu.fail(RNES_ERR_NO_ROM, "No ROM loaded");
+    }
+
+    if let Err(e) = emu.emulator.soft_reset() {
+        return emu.fail_with(RNES_ERR_EMULATION, e);
+    }
+    RNES_OK
+}
+
+/// Turn the console off and on again
+///
+/// RAM is refilled with its power-on contents; battery-backed RAM survives.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_power_cycle(emu: *mut RnesEmulator) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    if !emu.rom_loaded {
+        return emu.fail(RNES_ERR_NO_ROM, "No ROM loaded");
+    }
+
+    if let Err(e) = emu.emulator.power_cycle() {
+        return emu.fail_with(RNES_ERR_EMULATION, e);
+    }
+    emu.audio.clear();
+    RNES_OK
+}
+
+/// Get the current frame as 256x240 RGBA8 pixels
+///
+/// The pointer stays valid until the next call on this handle. If `len` is not
+/// null it receives the buffer size in bytes.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle and `len` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_get_framebuffer(emu: *mut RnesEmulator, len: *mut usize) -> *const u8 {
+    let Some(emu) = emu.as_mut() else {
+        return ptr::null();
+    };
+    if let Some(len) = len.as_mut() {
+        *len = emu.frame.len();
+    }
+    emu.frame.as_ptr()
+}
+
+/// Get the rows of the current frame that changed since the previous frame
+///
+/// Rows `first` up to (not including) `end` differ; both are 0 when the frame
+/// is unchanged and its texture upload can be skipped.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle and `first` and `end` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_get_dirty_lines(emu: *const RnesEmulator, first: *mut u32, end: *mut u32) -> i32 {
+    let (Some(emu), Some(first), Some(end)) = (emu.as_ref(), first.as_mut(), end.as_mut()) else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    let lines = emu.emulator.dirty_lines().unwrap_or(0..0);
+    *first = lines.start as u32;
+    *end = lines.end as u32;
+    RNES_OK
+}
+
+/// Drain audio samples produced since the last call
+///
+/// Samples are mono `f32` values at the APU output rate. The pointer stays
+/// valid until the next call on this handle.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle and `count` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_get_audio_samples(emu: *mut RnesEmulator, count: *mut usize) -> *const f32 {
+    let Some(emu) = emu.as_mut() else {
+        return ptr::null();
+    };
+    let Some(count) = count.as_mut() else {
+        return ptr::null();
+    };
+
+    emu.audio.clear();
+    if emu.rom_loaded {
+        emu.audio.extend(emu.emulator.drain_audio());
+    }
+    *count = emu.audio.len();
+    emu.audio.as_ptr()
+}
+
+/// Copy up to `capacity` of the oldest audio samples into `out`
+///
+/// Returns the number of samples written, so an audio callback can request
+/// exactly one device buffer. Samples left over stay queued for the next call.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle and `out` must point to `capacity` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_fill_audio(emu: *mut RnesEmulator, out: *mut f32, capacity: usize) -> usize {
+    let Some(emu) = emu.as_mut() else {
+        return 0;
+    };
+    if out.is_null() || !emu.rom_loaded {
+        return 0;
+    }
+
+    let out = slice::from_raw_parts_mut(out, capacity);
+    emu.emulator.fill_audio(out)
+}
+
+/// Set the button state for a controller port (0 or 1)
+///
+/// `buttons` uses the standard bit layout: A, B, Select, Start, Up, Down,
+/// Left, Right from bit 0 to bit 7.
+/// The new state is latched when the next frame starts.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_set_input(emu: *mut RnesEmulator, port: u32, buttons: u8) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+
+    if port > 1 {
+        return emu.fail(RNES_ERR_INVALID_ARGUMENT, format!("Invalid controller port: {}", port));
+    }
+    emu.emulator.set_input(port as usize, buttons);
+    RNES_OK
+}
+
+/// Serialize the machine state into a caller-provided buffer
+///
+/// `written` receives the number of bytes written. If the buffer is too small
+/// nothing is written, `written` receives the required size and
+/// `RNES_ERR_BUFFER_TOO_SMALL` is returned, so passing a null buffer with a
+/// capacity of zero can be used to query the size.
+///
+/// # Safety
+///
+/// `emu` must be a valid handle, `buffer` must be null or point to `capacity`
+/// writable bytes and `written` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_save_state(
+    emu: *mut RnesEmulator,
+    buffer: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    let Some(written) = written.as_mut() else {
+        return emu.fail(RNES_ERR_NULL_POINTER, "Output size pointer is null");
+    };
+    if !emu.rom_loaded {
+        return emu.fail(RNES_ERR_NO_ROM, "No ROM loaded");
+    }
+
+    let data = match emu.emulator.save_state_to_bytes() {
+        Ok(data) => data,
+        Err(e) => return emu.fail_with(RNES_ERR_STATE, e),
+    };
+
+    *written = data.len();
+    if buffer.is_null() || capacity < data.len() {
+        return emu.fail(
+            RNES_ERR_BUFFER_TOO_SMALL,
+            format!("Save state needs {} bytes, buffer has {}", data.len(), capacity),
+        );
+    }
+
+    ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+    RNES_OK
+}
+
+/// Restore the machine state from a buffer produced by `rnes_save_state`
+///
+/// # Safety
+///
+/// `emu` must be a valid handle and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_load_state(emu: *mut RnesEmulator, data: *const u8, len: usize) -> i32 {
+    let Some(emu) = emu.as_mut() else {
+        return RNES_ERR_NULL_POINTER;
+    };
+    if data.is_null() {
+        return emu.fail(RNES_ERR_NULL_POINTER, "State data pointer is null");
+    }
+    if !emu.rom_loaded {
+        return emu.fail(RNES_ERR_NO_ROM, "No ROM loaded");
+    }
+
+    let state = slice::from_raw_parts(data, len);
+    if let Err(e) = emu.emulator.load_state_from_bytes(state) {
+        return emu.fail_with(RNES_ERR_STATE, e);
+    }
+
+    emu.update_frame();
+    RNES_OK
+}
+
+/// Get the message for the most recent error on this handle
+///
+/// Returns null if no error has occurred. The string stays valid until the
+/// next failing call on this handle.
+///
+/// # Safety
+///
+/// `emu` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn rnes_last_error(emu: *const RnesEmulator) -> *const c_char {
+    match emu.as_ref().and_then(|emu| emu.last_error.as_ref()) {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a minimal NROM image whose reset vector points at an infinite loop
+    fn create_test_rom() -> Vec<u8> {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut prg = vec![0xEA; 16 * 1024];
+        // JMP $8000
+        prg[0] = 0x4C;
+        prg[1] = 0x00;
+        prg[2] = 0x80;
+        // Reset vector -> $8000
+        prg[0x3FFC] = 0x00;
+        prg[0x3FFD] = 0x80;
+        rom.extend_from_slice(&prg);
+        rom.extend_from_slice(&[0; 8 * 1024]);
+        rom
+    }
+
+    #[test]
+    fn test_null_handle() {
+        unsafe {
+            assert_eq!(rnes_run_frame(ptr::null_mut()), RNES_ERR_NULL_POINTER);
+            assert_eq!(rnes_set_input(ptr::null_mut(), 0, 0), RNES_ERR_NULL_POINTER);
+            assert!(rnes_last_error(ptr::null()).is_null());
+            rnes_emulator_destroy(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_invalid_rom() {
+        unsafe {
+            let emu = rnes_emulator_create();
+            let data = [0u8; 4];
+            assert_eq!(rnes_load_rom(emu, data.as_ptr(), data.len()), RNES_ERR_ROM);
+            assert!(!rnes_last_error(emu).is_null());
+            assert_eq!(rnes_run_frame(emu), RNES_ERR_NO_ROM);
+            assert_eq!(rnes_soft_reset(emu), RNES_ERR_NO_ROM);
+            assert_eq!(rnes_power_cycle(emu), RNES_ERR_NO_ROM);
+            rnes_emulator_destroy(emu);
+        }
+    }
+
+    #[test]
+    fn test_run_frame_and_framebuffer() {
+        unsafe {
+            let emu = rnes_emulator_create();
+            let rom = create_test_rom();
+            assert_eq!(rnes_load_rom(emu, rom.as_ptr(), rom.len()), RNES_OK);
+            assert_eq!(rnes_set_input(emu, 0, 0x09), RNES_OK);
+            assert_eq!(rnes_set_input(emu, 2, 0), RNES_ERR_INVALID_ARGUMENT);
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+
+            let mut len = 0;
+            let frame = rnes_get_framebuffer(emu, &mut len);
+            assert!(!frame.is_null());
+            assert_eq!(len, RNES_FRAMEBUFFER_SIZE);
+
+            // The first frame is all new; the next is identical
+            let (mut first, mut end) = (0, 0);
+            assert_eq!(rnes_get_dirty_lines(emu, &mut first, &mut end), RNES_OK);
+            assert_eq!((first, end), (0, 240));
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+            assert_eq!(rnes_get_dirty_lines(emu, &mut first, &mut end), RNES_OK);
+            assert_eq!((first, end), (0, 0));
+
+            let mut count = 0;
+            assert!(!rnes_get_audio_samples(emu, &mut count).is_null());
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+            let mut buffer = [0.0f32; 256];
+            assert_eq!(rnes_fill_audio(emu, buffer.as_mut_ptr(), buffer.len()), buffer.len());
+            assert_eq!(rnes_fill_audio(ptr::null_mut(), buffer.as_mut_ptr(), buffer.len()), 0);
+
+            assert_eq!(rnes_soft_reset(emu), RNES_OK);
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+            assert_eq!(rnes_power_cycle(emu), RNES_OK);
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+            rnes_emulator_destroy(emu);
+        }
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        unsafe {
+            let emu = rnes_emulator_create();
+            let rom = create_test_rom();
+            assert_eq!(rnes_load_rom(emu, rom.as_ptr(), rom.len()), RNES_OK);
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+
+            // Query the required size first
+            let mut size = 0;
+            assert_eq!(rnes_save_state(emu, ptr::null_mut(), 0, &mut size), RNES_ERR_BUFFER_TOO_SMALL);
+            assert!(size > 0);
+
+            let mut buffer = vec![0u8; size];
+            let mut written = 0;
+            assert_eq!(rnes_save_state(emu, buffer.as_mut_ptr(), buffer.len(), &mut written), RNES_OK);
+            assert_eq!(written, size);
+
+            assert_eq!(rnes_run_frame(emu), RNES_OK);
+            assert_eq!(rnes_load_state(emu, buffer.as_ptr(), written), RNES_OK);
+            assert_eq!((*emu).emulator.cpu.pc, 0x8000);
+
+            let garbage = [0xFFu8; 8];
+            assert_eq!(rnes_load_state(emu, garbage.as_ptr(), garbage.len()), RNES_ERR_STATE);
+            rnes_emulator_destroy(emu);
+        }
+    }
+}
@@ -1,26 +1,60 @@
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::Read;
-use std::path::Path;
-use rnes_common::{Byte, Word, RnesResult};
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+use rnes_common::{Byte, Word, Region, RnesResult, RomId};
 use crate::header::{InesHeader, Mirroring};
 
 /// Cartridge implementation
-#[derive(Debug, Clone)]
+///
+/// The default is an empty cartridge, used as a placeholder while mapper
+/// state is restored from a save state.
+#[derive(Debug, Clone, Default)]
 pub struct Cartridge {
     pub header: InesHeader,
     pub prg_rom: Vec<Byte>,
+    /// CHR ROM, or CHR RAM when `chr_is_ram` is set
     pub chr_rom: Vec<Byte>,
+    pub chr_is_ram: bool,
     pub prg_ram: Vec<Byte>,
     pub mirroring: Mirroring,
+    /// CRC-32 of the PRG and CHR ROM data as loaded (header excluded)
+    pub crc32: u32,
+    /// Content hash identifying the game, independent of the file name
+    pub rom_id: RomId,
+    /// Timing region (from the header, else a file name hint, else NTSC)
+    pub region: Region,
+    /// File the ROM was loaded from, if any
+    #[cfg(feature = "std")]
+    pub file_path: Option<PathBuf>,
 }
 
 impl Cartridge {
     /// Load ROM from file
+    #[cfg(feature = "std")]
     pub fn from_file<P: AsRef<Path>>(path: P) -> RnesResult<Self> {
+        let path = path.as_ref();
         let mut file = File::open(path)?;
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
-        Self::from_bytes(&data)
+        let mut cartridge = Self::from_bytes(&data)?;
+        
+        // The header wins; file name tags only fill in unknown regions
+        if cartridge.header.region().is_none() {
+            if let Some(region) = path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(Region::from_filename)
+            {
+                cartridge.region = region;
+            }
+        }
+        cartridge.file_path = Some(path.to_path_buf());
+        Ok(cartridge)
     }
     
     /// Load ROM from byte array
@@ -36,6 +70,9 @@ impl Cartridge {
         
         // Read PRG ROM
         let prg_rom_size = header.prg_rom_bytes();
+        if prg_rom_size == 0 {
+            return Err(rnes_common::RnesError::RomFormat("ROM has no PRG ROM".to_string()));
+        }
         if offset + prg_rom_size > data.len() {
             return Err(rnes_common::RnesError::RomFormat("Insufficient PRG ROM data".to_string()));
         }
@@ -48,31 +85,42 @@ impl Cartridge {
             if offset + chr_rom_size > data.len() {
                 return Err(rnes_common::RnesError::RomFormat("Insufficient CHR ROM data".to_string()));
             }
-            let rom = data[offset..offset + chr_rom_size].to_vec();
-            rom
+            data[offset..offset + chr_rom_size].to_vec()
         } else {
-            // No CHR ROM, create 8KB CHR RAM
-            vec![0; 8192]
+            // No CHR ROM: CHR RAM, 8KB unless NES 2.0 asks for more
+            vec![0; header.chr_ram_bytes().max(8192)]
         };
+        let chr_is_ram = chr_rom_size == 0;
+        let rom_id = RomId::from_rom_data(&prg_rom, if chr_is_ram { &[] } else { &chr_rom });
         
         // Create PRG RAM
-        let prg_ram_size = if header.flags8 > 0 {
-            header.flags8 as usize * 8192
-        } else {
-            8192 // Default 8KB
-        };
-        let prg_ram = vec![0; prg_ram_size];
+        let prg_ram = vec![0; header.prg_ram_bytes().max(8192)];
         
         let mirroring = header.mirroring();
+        let region = header.region().unwrap_or_default();
         Ok(Self {
             header,
             prg_rom,
             chr_rom,
+            chr_is_ram,
             prg_ram,
             mirroring,
+            crc32: rom_id.crc32,
+            rom_id,
+            region,
+            #[cfg(feature = "std")]
+            file_path: None,
         })
     }
     
+    /// Get the ROM file name without its extension, if loaded from a file
+    #[cfg(feature = "std")]
+    pub fn file_stem(&self) -> Option<String> {
+        self.file_path.as_ref()?
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+    }
+    
     /// Read PRG ROM
     pub fn read_prg_rom(&self, addr: Word) -> RnesResult<Byte> {
         let offset = addr as usize;
@@ -107,22 +155,31 @@ impl Cartridge {
     
     /// Write CHR RAM
     pub fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
-        let offset = addr as usize;
-        if offset < self.chr_rom.len() {
-            // If CHR ROM exists, it's usually read-only
-            // But some games may use CHR RAM
+        if !self.chr_is_ram {
+            // CHR ROM is read-only
             tracing::warn!("Attempting to write to CHR ROM: 0x{:04X} = 0x{:02X}", addr, value);
-        } else {
-            // For nametable writes, we need to support mirroring
-            // This is a simplified approach - in a real implementation,
-            // we might need separate nametable RAM
-            let mirrored_offset = offset % self.chr_rom.len();
-            tracing::warn!("Writing to mirrored CHR address: 0x{:04X} -> 0x{:04X} = 0x{:02X}", 
-                          addr, mirrored_offset as u16, value);
+            return Ok(());
         }
+        
+        let offset = addr as usize % self.chr_rom.len();
+        self.chr_rom[offset] = value;
         Ok(())
     }
     
+    /// Get CHR RAM contents, or `None` for CHR ROM cartridges
+    pub fn chr_ram(&self) -> Option<&[Byte]> {
+        self.chr_is_ram.then_some(&self.chr_rom[..])
+    }
+    
+    /// Get mutable CHR RAM contents, or `None` for CHR ROM cartridges
+    pub fn chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        if self.chr_is_ram {
+            Some(&mut self.chr_rom[..])
+        } else {
+            None
+        }
+    }
+    
     /// Read PRG RAM
     pub fn read_prg_ram(&self, addr: Word) -> RnesResult<Byte> {
         let offset = (addr & 0x1FFF) as usize;
@@ -149,6 +206,11 @@ impl Cartridge {
         self.header.mapper_number()
     }
     
+    /// Get NES 2.0 submapper number
+    pub fn submapper(&self) -> u8 {
+        self.header.submapper()
+    }
+    
     /// Check if battery backup is present
     pub fn has_battery(&self) -> bool {
         self.header.has_battery()
@@ -158,4 +220,9 @@ impl Cartridge {
     pub fn mirroring(&self) -> Mirroring {
         self.mirroring
     }
+    
+    /// Get timing region
+    pub fn region(&self) -> Region {
+        self.region
+    }
 }
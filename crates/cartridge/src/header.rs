@@ -1,7 +1,9 @@
-use rnes_common::{Byte, RnesResult, RnesError};
+use alloc::string::ToString;
+use rnes_common::{Byte, Region, RnesResult, RnesError};
+use serde::{Deserialize, Serialize};
 
 /// iNES ROM header
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct InesHeader {
     pub magic: [u8; 4],      // "NES\x1A"
     pub prg_rom_size: Byte,  // PRG ROM size (16KB units)
@@ -44,6 +46,15 @@ impl InesHeader {
         (self.flags6 >> 4) | (self.flags7 & 0xF0)
     }
     
+    /// Get NES 2.0 submapper number (0 for plain iNES)
+    pub fn submapper(&self) -> u8 {
+        if self.is_nes2() {
+            self.flags8 >> 4
+        } else {
+            0
+        }
+    }
+    
     /// Check if battery backup is present
     pub fn has_battery(&self) -> bool {
         (self.flags6 & 0x02) != 0
@@ -69,6 +80,32 @@ impl InesHeader {
         (self.flags7 & 0x0C) == 0x08
     }
     
+    /// Get TV system declared by the header, if any
+    ///
+    /// NES 2.0 stores the timing mode in byte 12. Plain iNES only has the
+    /// rarely-set PAL bits in flags 9/10, which are ignored when the unused
+    /// bytes contain junk (e.g. "DiskDude!"), so `None` means "unknown".
+    pub fn region(&self) -> Option<Region> {
+        if self.is_nes2() {
+            return match self.padding[1] & 0x03 {
+                1 => Some(Region::Pal),
+                3 => Some(Region::Dendy),
+                // 0 = NTSC, 2 = multi-region (runs on NTSC hardware too)
+                _ => Some(Region::Ntsc),
+            };
+        }
+
+        if self.padding.iter().any(|&b| b != 0) {
+            return None;
+        }
+
+        if self.flags9 & 0x01 != 0 || self.flags10 & 0x03 == 0x02 {
+            Some(Region::Pal)
+        } else {
+            None
+        }
+    }
+    
     /// Get PRG ROM size in bytes
     pub fn prg_rom_bytes(&self) -> usize {
         self.prg_rom_size as usize * 16384
@@ -79,6 +116,36 @@ impl InesHeader {
         self.chr_rom_size as usize * 8192
     }
     
+    /// Get CHR RAM size in bytes
+    ///
+    /// NES 2.0 declares it in byte 11 (same shift encoding as PRG RAM);
+    /// plain iNES implies 8KB of CHR RAM when there is no CHR ROM.
+    pub fn chr_ram_bytes(&self) -> usize {
+        if self.is_nes2() {
+            let shift_size = |shift: u8| if shift == 0 { 0 } else { 64usize << shift };
+            shift_size(self.padding[0] & 0x0F) + shift_size(self.padding[0] >> 4)
+        } else if self.chr_rom_size == 0 {
+            8192
+        } else {
+            0
+        }
+    }
+    
+    /// Get PRG RAM size in bytes (volatile plus battery-backed)
+    ///
+    /// NES 2.0 encodes each size as a shift count (64 << n); plain iNES
+    /// gives 8KB units in flags 8, where 0 means 8KB for compatibility.
+    pub fn prg_ram_bytes(&self) -> usize {
+        if self.is_nes2() {
+            let shift_size = |shift: u8| if shift == 0 { 0 } else { 64usize << shift };
+            shift_size(self.flags10 & 0x0F) + shift_size(self.flags10 >> 4)
+        } else if self.flags8 > 0 {
+            self.flags8 as usize * 8192
+        } else {
+            8192
+        }
+    }
+    
     /// Get trainer size in bytes
     pub fn trainer_bytes(&self) -> usize {
         if self.has_trainer() { 512 } else { 0 }
@@ -86,8 +153,9 @@ impl InesHeader {
 }
 
 /// Mirroring type
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum Mirroring {
+    #[default]
     Horizontal,
     Vertical,
     FourScreen,
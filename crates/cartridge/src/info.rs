@@ -0,0 +1,186 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use rnes_common::{crc32, Region};
+use crate::cartridge::Cartridge;
+use crate::header::Mirroring;
+
+/// Common name of an iNES mapper number, if it has one
+pub fn mapper_name(mapper: u8) -> Option<&'static str> {
+    Some(match mapper {
+        0 => "NROM",
+        1 => "MMC1",
+        2 => "UxROM",
+        3 => "CNROM",
+        4 => "MMC3",
+        5 => "MMC5",
+        7 => "AxROM",
+        9 => "MMC2",
+        10 => "MMC4",
+        11 => "Color Dreams",
+        13 => "CPROM",
+        16 => "Bandai FCG",
+        18 => "Jaleco SS88006",
+        19 => "Namco 163",
+        21 => "VRC4a/VRC4c",
+        22 => "VRC2a",
+        23 => "VRC2b/VRC4e",
+        24 => "VRC6a",
+        25 => "VRC4b/VRC4d",
+        26 => "VRC6b",
+        34 => "BNROM/NINA-001",
+        64 => "RAMBO-1",
+        66 => "GxROM",
+        69 => "Sunsoft FME-7",
+        71 => "Camerica",
+        73 => "VRC3",
+        75 => "VRC1",
+        79 => "NINA-03/06",
+        85 => "VRC7",
+        88 => "NAMCOT-3443",
+        94 => "UN1ROM",
+        95 => "NAMCOT-3425",
+        105 => "NES-EVENT",
+        118 => "TxSROM",
+        119 => "TQROM",
+        154 => "NAMCOT-3453",
+        180 => "UNROM (Crazy Climber)",
+        185 => "CNROM with copy protection",
+        206 => "Namco 108",
+        210 => "Namco 175/340",
+        228 => "Action 52",
+        232 => "Camerica Quattro",
+        _ => return None,
+    })
+}
+
+/// Fields only present in NES 2.0 headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nes2Info {
+    pub submapper: u8,
+    /// Volatile PRG RAM in bytes
+    pub prg_ram_size: usize,
+    /// Battery-backed PRG RAM in bytes
+    pub prg_nvram_size: usize,
+    /// Volatile CHR RAM in bytes
+    pub chr_ram_size: usize,
+    /// Battery-backed CHR RAM in bytes
+    pub chr_nvram_size: usize,
+    /// Console type (0 = NES/Famicom, 1 = Vs. System, 2 = PlayChoice-10, 3 = extended)
+    pub console_type: u8,
+    /// Number of miscellaneous ROMs after CHR ROM
+    pub misc_roms: u8,
+    /// Default expansion port device
+    pub expansion_device: u8,
+}
+
+/// Summary of a cartridge for `rnes info` and "ROM properties" dialogs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub mapper: u8,
+    pub mapper_name: Option<&'static str>,
+    /// PRG ROM size in bytes
+    pub prg_rom_size: usize,
+    /// CHR ROM size in bytes, 0 for CHR RAM cartridges
+    pub chr_rom_size: usize,
+    /// CHR RAM size in bytes, 0 for CHR ROM cartridges
+    pub chr_ram_size: usize,
+    /// PRG RAM size in bytes (volatile plus battery-backed)
+    pub prg_ram_size: usize,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub trainer: bool,
+    pub region: Region,
+    pub nes2: Option<Nes2Info>,
+    /// CRC-32 of PRG and CHR ROM together (the `RomId`)
+    pub crc32: u32,
+    pub prg_crc32: u32,
+    pub chr_crc32: u32,
+    /// SHA-1 of PRG and CHR ROM together, lowercase hex
+    pub sha1: String,
+}
+
+impl Cartridge {
+    /// Describe the cartridge's header, sizes and hashes
+    pub fn info(&self) -> RomInfo {
+        let header = &self.header;
+        let shift_size = |shift: u8| if shift == 0 { 0 } else { 64usize << shift };
+        let nes2 = header.is_nes2().then(|| Nes2Info {
+            submapper: header.submapper(),
+            prg_ram_size: shift_size(header.flags10 & 0x0F),
+            prg_nvram_size: shift_size(header.flags10 >> 4),
+            chr_ram_size: shift_size(header.padding[0] & 0x0F),
+            chr_nvram_size: shift_size(header.padding[0] >> 4),
+            console_type: header.flags7 & 0x03,
+            misc_roms: header.padding[3] & 0x03,
+            expansion_device: header.padding[4] & 0x3F,
+        });
+        let chr_rom: &[u8] = if self.chr_is_ram { &[] } else { &self.chr_rom };
+
+        RomInfo {
+            mapper: self.mapper_number(),
+            mapper_name: mapper_name(self.mapper_number()),
+            prg_rom_size: self.prg_rom.len(),
+            chr_rom_size: chr_rom.len(),
+            chr_ram_size: if self.chr_is_ram { self.chr_rom.len() } else { 0 },
+            prg_ram_size: self.prg_ram.len(),
+            mirroring: self.mirroring,
+            battery: self.has_battery(),
+            trainer: header.has_trainer(),
+            region: self.region,
+            nes2,
+            crc32: self.rom_id.crc32,
+            prg_crc32: crc32(&self.prg_rom),
+            chr_crc32: crc32(chr_rom),
+            sha1: self.rom_id.sha1_hex(),
+        }
+    }
+}
+
+/// Format a byte count as KB
+fn kb(bytes: usize) -> String {
+    if bytes.is_multiple_of(1024) {
+        format!("{} KB", bytes / 1024)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mapper_name {
+            Some(name) => write!(f, "Mapper:     {} ({})", self.mapper, name)?,
+            None => write!(f, "Mapper:     {}", self.mapper)?,
+        }
+        match self.nes2 {
+            Some(nes2) => writeln!(f, ", submapper {}", nes2.submapper)?,
+            None => writeln!(f)?,
+        }
+        writeln!(f, "Format:     {}", if self.nes2.is_some() { "NES 2.0" } else { "iNES" })?;
+        writeln!(f, "PRG ROM:    {}", kb(self.prg_rom_size))?;
+        if self.chr_rom_size > 0 {
+            writeln!(f, "CHR ROM:    {}", kb(self.chr_rom_size))?;
+        } else {
+            writeln!(f, "CHR RAM:    {}", kb(self.chr_ram_size))?;
+        }
+        writeln!(f, "PRG RAM:    {}{}", kb(self.prg_ram_size), if self.battery { " (battery)" } else { "" })?;
+        writeln!(f, "Mirroring:  {:?}", self.mirroring)?;
+        writeln!(f, "Trainer:    {}", if self.trainer { "yes" } else { "no" })?;
+        writeln!(f, "Region:     {}", self.region)?;
+        if let Some(nes2) = self.nes2 {
+            writeln!(
+                f,
+                "NES 2.0:    PRG RAM {} + NVRAM {}, CHR RAM {} + NVRAM {}, console {}, misc ROMs {}, expansion {}",
+                kb(nes2.prg_ram_size),
+                kb(nes2.prg_nvram_size),
+                kb(nes2.chr_ram_size),
+                kb(nes2.chr_nvram_size),
+                nes2.console_type,
+                nes2.misc_roms,
+                nes2.expansion_device,
+            )?;
+        }
+        writeln!(f, "CRC32:      {:08X} (PRG {:08X}, CHR {:08X})", self.crc32, self.prg_crc32, self.chr_crc32)?;
+        write!(f, "SHA-1:      {}", self.sha1)
+    }
+}
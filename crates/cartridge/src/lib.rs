@@ -1,5 +1,11 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod cartridge;
 pub mod header;
+pub mod info;
 
 pub use cartridge::*;
 pub use header::*;
+pub use info::*;
@@ -0,0 +1,193 @@
+//! Per-game RAM maps naming the variables a game keeps in memory
+//!
+//! The file format follows the RAM map tables on Data Crystal, one entry per
+//! line:
+//!
+//! ```text
+//! # Super Mario Bros.
+//! $075A        lives     Lives remaining, minus one
+//! $071C-$071D  scroll_x  Horizontal scroll position
+//! ```
+//!
+//! An address is hex with an optional `$` or `0x`, optionally followed by
+//! `-end` for a multi-byte variable. The name must be a valid identifier so
+//! watch expressions can refer to it; the rest of the line is the
+//! description. Blank lines and lines starting with `#` are ignored.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::{Word, RnesError, RnesResult};
+
+/// Named RAM variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamAnnotation {
+    /// First address of the variable
+    pub address: Word,
+    /// Size in bytes
+    pub length: u32,
+    pub name: String,
+    pub description: String,
+}
+
+impl RamAnnotation {
+    /// Check if `addr` falls inside the variable
+    pub fn contains(&self, addr: Word) -> bool {
+        addr >= self.address && ((addr - self.address) as u32) < self.length
+    }
+}
+
+/// RAM map of one game, by address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RamAnnotations {
+    entries: BTreeMap<Word, RamAnnotation>,
+}
+
+impl RamAnnotations {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a RAM map
+    pub fn parse(text: &str) -> RnesResult<Self> {
+        let mut annotations = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| RnesError::Config(format!("RAM map line {}: {}", index + 1, message));
+
+            let (range, rest) = split_field(line);
+            let (name, description) = split_field(rest);
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (parse_address(start), parse_address(end)),
+                None => (parse_address(range), parse_address(range)),
+            };
+            let (Some(address), Some(end)) = (start, end) else {
+                return Err(error(&format!("invalid address `{}`", range)));
+            };
+            if end < address {
+                return Err(error(&format!("address range `{}` ends before it starts", range)));
+            }
+
+            if !is_identifier(name) {
+                return Err(error(&format!("invalid name `{}`", name)));
+            }
+            if annotations.by_name(name).is_some() {
+                return Err(error(&format!("duplicate name `{}`", name)));
+            }
+
+            annotations.insert(RamAnnotation {
+                address,
+                length: (end - address) as u32 + 1,
+                name: name.to_string(),
+                description: description.trim().to_string(),
+            });
+        }
+        Ok(annotations)
+    }
+
+    /// Load a RAM map file
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> RnesResult<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Add or replace the annotation starting at its address
+    pub fn insert(&mut self, annotation: RamAnnotation) {
+        self.entries.insert(annotation.address, annotation);
+    }
+
+    /// Annotation of the variable containing `addr`, if any
+    pub fn get(&self, addr: Word) -> Option<&RamAnnotation> {
+        self.entries.range(..=addr)
+            .next_back()
+            .map(|(_, annotation)| annotation)
+            .filter(|annotation| annotation.contains(addr))
+    }
+
+    /// Annotation with the given name
+    pub fn by_name(&self, name: &str) -> Option<&RamAnnotation> {
+        self.entries.values().find(|annotation| annotation.name == name)
+    }
+
+    /// All annotations in address order
+    pub fn iter(&self) -> impl Iterator<Item = &RamAnnotation> {
+        self.entries.values()
+    }
+
+    /// Number of annotations
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the map is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Label for an address, e.g. `$075A (lives)` or `$071D (scroll_x+1)`
+    pub fn label(&self, addr: Word) -> String {
+        match self.get(addr) {
+            Some(annotation) if annotation.address == addr => format!("${:04X} ({})", addr, annotation.name),
+            Some(annotation) => format!("${:04X} ({}+{})", addr, annotation.name, addr - annotation.address),
+            None => format!("${:04X}", addr),
+        }
+    }
+}
+
+/// Split off the first whitespace-separated field
+fn split_field(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    text.split_once(char::is_whitespace).unwrap_or((text, ""))
+}
+
+fn parse_address(text: &str) -> Option<Word> {
+    let digits = text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    Word::from_str_radix(digits, 16).ok()
+}
+
+/// Check if `text` is a name watch expressions can use
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ram_map_parsing_and_lookup() {
+        let annotations = RamAnnotations::parse(
+            "# Super Mario Bros.\n\n$075A        lives     Lives remaining, minus one\n0x071C-0x071D\tscroll_x\n00FF flag\n",
+        ).unwrap();
+        assert_eq!(annotations.len(), 3);
+
+        let lives = annotations.get(0x075A).unwrap();
+        assert_eq!((lives.name.as_str(), lives.length), ("lives", 1));
+        assert_eq!(lives.description, "Lives remaining, minus one");
+        assert_eq!(annotations.get(0x071D).unwrap().name, "scroll_x");
+        assert_eq!(annotations.by_name("scroll_x").unwrap().length, 2);
+        assert_eq!(annotations.by_name("flag").unwrap().address, 0x00FF);
+        assert!(annotations.get(0x071E).is_none());
+
+        assert_eq!(annotations.label(0x075A), "$075A (lives)");
+        assert_eq!(annotations.label(0x071D), "$071D (scroll_x+1)");
+        assert_eq!(annotations.label(0x0300), "$0300");
+    }
+
+    #[test]
+    fn test_ram_map_errors() {
+        let message = |text: &str| RamAnnotations::parse(text).unwrap_err().to_string();
+        assert!(message("# header\nzz lives").contains("line 2: invalid address `zz`"));
+        assert!(message("$0010-$0008 x").contains("ends before it starts"));
+        assert!(message("$0010 2lives").contains("invalid name `2lives`"));
+        assert!(message("$0010").contains("invalid name ``"));
+        assert!(message("$0010 a\n$0020 a").contains("duplicate name `a`"));
+    }
+}
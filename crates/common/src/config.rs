@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use crate::{RnesResult, RnesError, Button};
+use crate::{RnesResult, RnesError, BackgroundBehavior, Button, ExpansionChip, CrtPreset, InputDevice, InterruptKind, Mmc3IrqRevision, RamInitPattern, ScaleMode};
 
 /// Emulator configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +20,9 @@ pub struct Config {
     pub debug: DebugConfig,
     /// Save state settings
     pub save_states: SaveStateConfig,
+    /// Save storage settings
+    #[serde(default)]
+    pub storage: StorageConfig,
 }
 
 /// General emulator configuration
@@ -35,8 +38,32 @@ pub struct GeneralConfig {
     pub turbo_multiplier: f32,
     /// Auto-save battery backup
     pub auto_save_battery: bool,
-    /// Auto-save interval (seconds, 0 = disabled)
+    /// Longest time battery RAM may stay unsaved while the game keeps writing it (seconds, 0 = no limit)
     pub auto_save_interval: u32,
+    /// Quiet time after the last battery RAM write before it is saved (milliseconds)
+    #[serde(default = "default_battery_flush_delay_ms")]
+    pub battery_flush_delay_ms: u32,
+    /// Extra idle scanlines inserted after VBlank (0 = no overclock)
+    #[serde(default)]
+    pub overclock_scanlines: u16,
+    /// Per-game overclock overrides keyed by ROM id (CRC-32 or SHA-1 hex)
+    #[serde(default)]
+    pub overclock_overrides: HashMap<String, u16>,
+    /// Power-on contents of work RAM, cartridge RAM, OAM and palette RAM
+    #[serde(default)]
+    pub ram_init: RamInitPattern,
+    /// Seed for `RamInitPattern::Random`
+    #[serde(default)]
+    pub ram_init_seed: u64,
+    /// Force an MMC3 IRQ revision (`None` = pick from the NES 2.0 submapper)
+    #[serde(default)]
+    pub mmc3_irq_revision: Option<Mmc3IrqRevision>,
+    /// What to do while the window doesn't have focus
+    #[serde(default)]
+    pub on_focus_loss: BackgroundBehavior,
+    /// What to do while the window is minimized or the browser tab hidden
+    #[serde(default = "default_on_hidden")]
+    pub on_hidden: BackgroundBehavior,
 }
 
 /// Video configuration
@@ -50,6 +77,13 @@ pub struct VideoConfig {
     pub fullscreen: bool,
     /// Scale factor
     pub scale_factor: f32,
+    /// How the picture fills the window
+    #[serde(default)]
+    pub scale_mode: ScaleMode,
+    /// Upscale by a whole number with nearest-neighbour, then bilinear to
+    /// the final size, so fractional scales stay sharp without shimmering
+    #[serde(default)]
+    pub sharp_bilinear: bool,
     /// Enable scanlines
     pub scanlines: bool,
     /// Scanline intensity (0.0 - 1.0)
@@ -58,6 +92,17 @@ pub struct VideoConfig {
     pub ntsc_filter: bool,
     /// NTSC filter strength (0.0 - 1.0)
     pub ntsc_strength: f32,
+    /// CRT shader pass; `scanlines` alone picks the scanline preset
+    #[serde(default)]
+    pub crt_preset: CrtPreset,
+    /// WGSL file used instead of the built-in CRT shader, with the same
+    /// entry points and uniforms
+    #[serde(default)]
+    pub crt_shader: Option<PathBuf>,
+    /// Draw every sprite on a line instead of dropping those past the
+    /// eighth, which removes flicker some games rely on
+    #[serde(default)]
+    pub remove_sprite_limit: bool,
 }
 
 /// Audio configuration
@@ -73,6 +118,31 @@ pub struct AudioConfig {
     pub enabled: bool,
     /// Audio device name (empty = default)
     pub device_name: String,
+    /// Hold the triangle still at timer periods 0 and 1, whose ultrasonic
+    /// output aliases into audible noise
+    #[serde(default = "default_silence_ultrasonic_triangle")]
+    pub silence_ultrasonic_triangle: bool,
+    /// Cartridge sound chip settings; chips not listed play at hardware level
+    #[serde(default)]
+    pub expansion: HashMap<ExpansionChip, ExpansionAudioConfig>,
+}
+
+/// Volume settings for one cartridge sound chip
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExpansionAudioConfig {
+    /// Mix the chip into the output
+    #[serde(default = "default_expansion_enabled")]
+    pub enabled: bool,
+    /// Volume relative to the chip's hardware level (1.0 = as on a Famicom)
+    #[serde(default = "default_expansion_gain")]
+    pub gain: f32,
+}
+
+impl AudioConfig {
+    /// Settings for a sound chip, falling back to the defaults
+    pub fn expansion(&self, chip: ExpansionChip) -> ExpansionAudioConfig {
+        self.expansion.get(&chip).copied().unwrap_or_default()
+    }
 }
 
 /// Input configuration
@@ -86,6 +156,12 @@ pub struct InputConfig {
     pub enable_gamepad: bool,
     /// Gamepad deadzone (0.0 - 1.0)
     pub gamepad_deadzone: f32,
+    /// Devices plugged into ports 1 and 2
+    #[serde(default)]
+    pub port_devices: [InputDevice; 2],
+    /// Connect controller 1 to port 2 and controller 2 to port 1
+    #[serde(default)]
+    pub swap_ports: bool,
 }
 
 /// Controller configuration
@@ -95,6 +171,21 @@ pub struct ControllerConfig {
     pub buttons: HashMap<Button, String>,
 }
 
+impl ControllerConfig {
+    /// Find the button bound to a key name (case-insensitive)
+    pub fn button_for_key(&self, key: &str) -> Option<Button> {
+        self.buttons.iter()
+            .find(|(_, bound)| bound.eq_ignore_ascii_case(key))
+            .map(|(&button, _)| button)
+    }
+    
+    /// Bind a key to a button, replacing any previous binding of that key
+    pub fn bind(&mut self, button: Button, key: &str) {
+        self.buttons.retain(|_, bound| !bound.eq_ignore_ascii_case(key));
+        self.buttons.insert(button, key.to_string());
+    }
+}
+
 /// Debug configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DebugConfig {
@@ -114,6 +205,12 @@ pub struct DebugConfig {
     pub step_execution: bool,
     /// Log level (trace, debug, info, warn, error)
     pub log_level: String,
+    /// Halt at the reset vector before the first instruction runs
+    #[serde(default)]
+    pub pause_on_start: bool,
+    /// Interrupts that halt execution when taken
+    #[serde(default)]
+    pub break_on_interrupts: Vec<InterruptKind>,
 }
 
 /// Save state configuration
@@ -129,6 +226,30 @@ pub struct SaveStateConfig {
     pub quick_save_slot: u8,
     /// Quick load slot
     pub quick_load_slot: u8,
+    /// Save an exit state on shutdown and resume from it on next launch
+    #[serde(default)]
+    pub auto_resume: bool,
+    /// Skip boot delays by restoring a cached post-boot state
+    ///
+    /// Runs then start from the cache instead of power-on, so leave this off
+    /// for movies and netplay.
+    #[serde(default)]
+    pub fast_boot: bool,
+}
+
+/// Save storage configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// Directory for battery backups and save states
+    pub save_dir: PathBuf,
+    /// Keep each ROM's saves in its own subdirectory
+    pub per_rom_directory: bool,
+    /// Directory for `.sav` battery files (defaults to `save_dir`)
+    #[serde(default)]
+    pub battery_dir: Option<PathBuf>,
+    /// Write `.sav` battery files next to the ROM file, like FCEUX and Mesen can
+    #[serde(default)]
+    pub sav_beside_rom: bool,
 }
 
 impl Config {
@@ -141,6 +262,7 @@ impl Config {
             input: InputConfig::default(),
             debug: DebugConfig::default(),
             save_states: SaveStateConfig::default(),
+            storage: StorageConfig::default(),
         }
     }
     
@@ -178,13 +300,7 @@ impl Config {
         let contents = toml::to_string_pretty(self)
             .map_err(|e| RnesError::Serialization(format!("Failed to serialize config: {}", e)))?;
         
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
-        
-        file.write_all(contents.as_bytes())?;
+        crate::write_atomic(path, contents.as_bytes())?;
         tracing::info!("Configuration saved to: {:?}", path);
         Ok(())
     }
@@ -204,6 +320,26 @@ impl Config {
     }
 }
 
+fn default_battery_flush_delay_ms() -> u32 {
+    1000
+}
+
+fn default_on_hidden() -> BackgroundBehavior {
+    BackgroundBehavior::Pause
+}
+
+fn default_silence_ultrasonic_triangle() -> bool {
+    true
+}
+
+fn default_expansion_enabled() -> bool {
+    true
+}
+
+fn default_expansion_gain() -> f32 {
+    1.0
+}
+
 impl Default for GeneralConfig {
     fn default() -> Self {
         Self {
@@ -213,6 +349,14 @@ impl Default for GeneralConfig {
             turbo_multiplier: 2.0,
             auto_save_battery: true,
             auto_save_interval: 30,
+            battery_flush_delay_ms: default_battery_flush_delay_ms(),
+            overclock_scanlines: 0,
+            overclock_overrides: HashMap::new(),
+            ram_init: RamInitPattern::default(),
+            ram_init_seed: 0,
+            mmc3_irq_revision: None,
+            on_focus_loss: BackgroundBehavior::KeepRunning,
+            on_hidden: default_on_hidden(),
         }
     }
 }
@@ -224,10 +368,15 @@ impl Default for VideoConfig {
             window_height: 240 * 3,
             fullscreen: false,
             scale_factor: 3.0,
+            scale_mode: ScaleMode::Fit,
+            sharp_bilinear: false,
             scanlines: false,
             scanline_intensity: 0.3,
             ntsc_filter: false,
             ntsc_strength: 0.5,
+            crt_preset: CrtPreset::None,
+            crt_shader: None,
+            remove_sprite_limit: false,
         }
     }
 }
@@ -240,6 +389,17 @@ impl Default for AudioConfig {
             master_volume: 1.0,
             enabled: true,
             device_name: String::new(),
+            silence_ultrasonic_triangle: true,
+            expansion: HashMap::new(),
+        }
+    }
+}
+
+impl Default for ExpansionAudioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            gain: 1.0,
         }
     }
 }
@@ -271,6 +431,8 @@ impl Default for InputConfig {
             controller2: ControllerConfig { buttons: controller2 },
             enable_gamepad: true,
             gamepad_deadzone: 0.2,
+            port_devices: [InputDevice::Controller; 2],
+            swap_ports: false,
         }
     }
 }
@@ -286,6 +448,8 @@ impl Default for DebugConfig {
             enable_breakpoints: false,
             step_execution: false,
             log_level: "info".to_string(),
+            pause_on_start: false,
+            break_on_interrupts: Vec::new(),
         }
     }
 }
@@ -298,6 +462,19 @@ impl Default for SaveStateConfig {
             quick_save_enabled: true,
             quick_save_slot: 9,
             quick_load_slot: 8,
+            auto_resume: false,
+            fast_boot: false,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            save_dir: PathBuf::from("saves"),
+            per_rom_directory: false,
+            battery_dir: None,
+            sav_beside_rom: false,
         }
     }
 }
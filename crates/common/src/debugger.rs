@@ -1,8 +1,14 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, HashSet};
 use crate::{Byte, Word};
+#[cfg(feature = "std")]
+use crate::{DebugConfig, RamAnnotations, RnesError, RnesResult, WatchExpression};
 
 /// Debugger for NES emulator
+#[cfg(feature = "std")]
 #[derive(Debug, Clone)]
 pub struct Debugger {
     /// Breakpoints
@@ -21,6 +27,24 @@ pub struct Debugger {
     pub instruction_history: Vec<InstructionInfo>,
     /// Maximum history size
     pub max_history: usize,
+    /// Mapper events that halt execution
+    pub mapper_breaks: HashSet<MapperEventKind>,
+    /// Mapper event that last halted execution
+    pub last_mapper_event: Option<MapperEvent>,
+    /// Halt at the reset vector before the first instruction runs
+    pub pause_on_start: bool,
+    /// Interrupts that halt execution when taken
+    pub interrupt_breaks: HashSet<InterruptKind>,
+    /// Interrupt that last halted execution
+    pub last_interrupt: Option<InterruptEvent>,
+    /// Layers the PPU draws, for inspecting a frame's contents
+    pub layers: LayerVisibility,
+    /// Named watch expressions, in the order they were added
+    pub watches: Vec<(String, WatchExpression)>,
+    /// Value of each watch expression after the last completed frame
+    pub watch_values: BTreeMap<String, i64>,
+    /// Names of the loaded game's RAM variables
+    pub annotations: RamAnnotations,
 }
 
 /// Debug information
@@ -40,6 +64,9 @@ pub struct DebugInfo {
     pub cpu_registers: CpuRegisters,
     /// PPU state
     pub ppu_state: PpuDebugState,
+    /// Pending interrupts
+    #[serde(default)]
+    pub interrupts: InterruptDebugState,
     /// Memory access count
     pub memory_access_count: u64,
     /// Total cycles executed
@@ -70,6 +97,34 @@ pub struct StatusFlagsDebug {
     pub negative: bool,
 }
 
+/// Pending interrupt state for debugging
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InterruptDebugState {
+    /// NMI waiting to be taken before the next instruction
+    pub nmi_pending: bool,
+    /// IRQ line level; taken while the I flag is clear
+    pub irq_line: bool,
+    /// Devices holding the IRQ line asserted
+    pub irq_sources: Vec<IrqSource>,
+}
+
+/// Device that can assert the CPU's IRQ line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IrqSource {
+    /// APU frame counter, at the end of each 4-step sequence
+    ApuFrame,
+    /// DMC, at the end of a non-looping sample
+    Dmc,
+    /// Cartridge mapper
+    Mapper,
+}
+
+impl IrqSource {
+    /// Every source, in bit order
+    pub const ALL: [IrqSource; 3] = [IrqSource::ApuFrame, IrqSource::Dmc, IrqSource::Mapper];
+}
+
 /// PPU debug state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PpuDebugState {
@@ -105,6 +160,116 @@ pub struct MemoryAccess {
     pub pc: Word,
 }
 
+/// Kind of mapper event the debugger can break on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MapperEventKind {
+    /// A PRG ROM bank mapped into $8000-$FFFF changed
+    PrgBankSwitch,
+    /// A CHR bank mapped into $0000-$1FFF changed
+    ChrBankSwitch,
+    /// The mapper asserted its IRQ
+    Irq,
+    /// Nametable mirroring changed
+    MirroringChange,
+}
+
+/// Mapper event captured by the debugger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapperEvent {
+    pub kind: MapperEventKind,
+    /// CPU write that caused the event; `None` for IRQs raised by the mapper's counter
+    pub write: Option<MemoryAccess>,
+    /// PPU position when the event happened
+    pub scanline: i32,
+    pub dot: u32,
+}
+
+/// Interrupt the debugger can break on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptKind {
+    /// Non-maskable interrupt, raised by the PPU at the start of vblank
+    Nmi,
+    /// Maskable interrupt from the APU or mapper, taken while I is clear
+    Irq,
+    /// BRK instruction
+    Brk,
+}
+
+impl InterruptKind {
+    /// Address of the vector the CPU jumps through
+    pub fn vector(self) -> Word {
+        match self {
+            InterruptKind::Nmi => 0xFFFA,
+            InterruptKind::Irq | InterruptKind::Brk => 0xFFFE,
+        }
+    }
+}
+
+/// Interrupt captured by the debugger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptEvent {
+    pub kind: InterruptKind,
+    /// Address of the interrupted instruction, or of the BRK
+    pub pc: Word,
+    /// Handler address read from the vector
+    pub target: Word,
+    /// PPU position when the interrupt was taken
+    pub scanline: i32,
+    pub dot: u32,
+}
+
+/// Tile grid drawn over the picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TileGrid {
+    /// Lines every 8 pixels, one cell per tile
+    Tile8,
+    /// Lines every 16 pixels, one cell per attribute quadrant or 8x16 sprite
+    Tile16,
+}
+
+impl TileGrid {
+    /// Cell size in pixels
+    pub fn size(self) -> usize {
+        match self {
+            TileGrid::Tile8 => 8,
+            TileGrid::Tile16 => 16,
+        }
+    }
+}
+
+/// Which parts of the picture the PPU draws
+///
+/// Hidden layers only change the output; sprite-0 hits, fetches and
+/// everything else the CPU can observe behave as if all were visible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerVisibility {
+    pub background: bool,
+    pub sprites: bool,
+    /// Background tiles fetched from $2000, $2400, $2800 and $2C00
+    pub nametables: [bool; 4],
+    /// Grid overlay, aligned to the screen rather than the scroll
+    pub grid: Option<TileGrid>,
+}
+
+impl LayerVisibility {
+    /// Check if the picture is drawn normally
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for LayerVisibility {
+    fn default() -> Self {
+        Self {
+            background: true,
+            sprites: true,
+            nametables: [true; 4],
+            grid: None,
+        }
+    }
+}
+
 /// Instruction information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstructionInfo {
@@ -117,6 +282,7 @@ pub struct InstructionInfo {
     pub cycle: u64,
 }
 
+#[cfg(feature = "std")]
 impl Debugger {
     /// Create new debugger
     pub fn new() -> Self {
@@ -129,9 +295,26 @@ impl Debugger {
             memory_history: Vec::new(),
             instruction_history: Vec::new(),
             max_history: 1000,
+            mapper_breaks: HashSet::new(),
+            last_mapper_event: None,
+            pause_on_start: false,
+            interrupt_breaks: HashSet::new(),
+            last_interrupt: None,
+            layers: LayerVisibility::default(),
+            watches: Vec::new(),
+            watch_values: BTreeMap::new(),
+            annotations: RamAnnotations::new(),
         }
     }
     
+    /// Create a debugger with the start and interrupt breaks from `config`
+    pub fn from_config(config: &DebugConfig) -> Self {
+        let mut debugger = Self::new();
+        debugger.pause_on_start = config.pause_on_start;
+        debugger.interrupt_breaks.extend(config.break_on_interrupts.iter().copied());
+        debugger
+    }
+    
     /// Add breakpoint
     pub fn add_breakpoint(&mut self, address: Word) {
         self.breakpoints.insert(address);
@@ -155,14 +338,14 @@ impl Debugger {
     /// Add watchpoint
     pub fn add_watchpoint(&mut self, address: Word) {
         self.watchpoints.insert(address);
-        tracing::info!("Watchpoint added at 0x{:04X}", address);
+        tracing::info!("Watchpoint added at {}", self.annotations.label(address));
     }
     
     /// Remove watchpoint
     pub fn remove_watchpoint(&mut self, address: Word) -> bool {
         let removed = self.watchpoints.remove(&address);
         if removed {
-            tracing::info!("Watchpoint removed at 0x{:04X}", address);
+            tracing::info!("Watchpoint removed at {}", self.annotations.label(address));
         }
         removed
     }
@@ -172,6 +355,46 @@ impl Debugger {
         self.watchpoints.contains(&address)
     }
     
+    /// Break when the mapper raises an event of this kind
+    pub fn add_mapper_break(&mut self, kind: MapperEventKind) {
+        self.mapper_breaks.insert(kind);
+        tracing::info!("Mapper breakpoint added on {:?}", kind);
+    }
+    
+    /// Remove mapper event breakpoint
+    pub fn remove_mapper_break(&mut self, kind: MapperEventKind) -> bool {
+        let removed = self.mapper_breaks.remove(&kind);
+        if removed {
+            tracing::info!("Mapper breakpoint removed on {:?}", kind);
+        }
+        removed
+    }
+    
+    /// Check if mapper event breakpoint exists
+    pub fn has_mapper_break(&self, kind: MapperEventKind) -> bool {
+        self.mapper_breaks.contains(&kind)
+    }
+    
+    /// Break when the CPU takes an interrupt of this kind
+    pub fn add_interrupt_break(&mut self, kind: InterruptKind) {
+        self.interrupt_breaks.insert(kind);
+        tracing::info!("Interrupt breakpoint added on {:?}", kind);
+    }
+    
+    /// Remove interrupt breakpoint
+    pub fn remove_interrupt_break(&mut self, kind: InterruptKind) -> bool {
+        let removed = self.interrupt_breaks.remove(&kind);
+        if removed {
+            tracing::info!("Interrupt breakpoint removed on {:?}", kind);
+        }
+        removed
+    }
+    
+    /// Check if interrupt breakpoint exists
+    pub fn has_interrupt_break(&self, kind: InterruptKind) -> bool {
+        self.interrupt_breaks.contains(&kind)
+    }
+    
     /// Enable step mode
     pub fn enable_step_mode(&mut self) {
         self.step_mode = true;
@@ -246,6 +469,8 @@ impl Debugger {
     /// Clear breakpoints
     pub fn clear_breakpoints(&mut self) {
         self.breakpoints.clear();
+        self.mapper_breaks.clear();
+        self.interrupt_breaks.clear();
         tracing::info!("All breakpoints cleared");
     }
     
@@ -255,6 +480,51 @@ impl Debugger {
         tracing::info!("All watchpoints cleared");
     }
     
+    /// Add a watch from a definition such as `lives = [0x075A]`
+    ///
+    /// Names from the RAM map stand for their address. A watch with the same
+    /// name is replaced. Its value appears in `watch_values` once the watches
+    /// are next evaluated.
+    pub fn add_watch(&mut self, definition: &str) -> RnesResult<()> {
+        let (name, expression) = definition.split_once('=')
+            .ok_or_else(|| RnesError::Config(format!("Watch needs the form `name = expression`: {}", definition)))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(RnesError::Config(format!("Watch has no name: {}", definition)));
+        }
+        // Report error columns within the whole definition
+        let offset = definition.len() - expression.len();
+        let names = |name: &str| self.annotations.by_name(name).map(|annotation| annotation.address);
+        let expression = WatchExpression::parse_with_names(expression, &names).map_err(|error| match error {
+            RnesError::Expression { column, message } => RnesError::Expression { column: column + offset, message },
+            error => error,
+        })?;
+        self.remove_watch(name);
+        self.watches.push((name.to_string(), expression));
+        Ok(())
+    }
+    
+    /// Remove a watch by name
+    pub fn remove_watch(&mut self, name: &str) -> bool {
+        self.watch_values.remove(name);
+        let count = self.watches.len();
+        self.watches.retain(|(watch, _)| watch != name);
+        self.watches.len() != count
+    }
+    
+    /// Remove all watches
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+        self.watch_values.clear();
+    }
+    
+    /// Evaluate every watch with `peek` reading CPU memory
+    pub fn update_watches(&mut self, peek: &dyn Fn(Word) -> Byte) {
+        for (name, expression) in &self.watches {
+            self.watch_values.insert(name.clone(), expression.evaluate(peek));
+        }
+    }
+    
     /// Clear history
     pub fn clear_history(&mut self) {
         self.memory_history.clear();
@@ -272,6 +542,13 @@ impl Debugger {
         self.watchpoints.iter().cloned().collect()
     }
     
+    /// Get watchpoints labelled with their RAM map names, in address order
+    pub fn get_watchpoint_labels(&self) -> Vec<String> {
+        let mut addresses = self.get_watchpoints();
+        addresses.sort_unstable();
+        addresses.into_iter().map(|address| self.annotations.label(address)).collect()
+    }
+    
     /// Get recent memory accesses
     pub fn get_recent_memory_accesses(&self, count: usize) -> Vec<&MemoryAccess> {
         let start = if self.memory_history.len() > count {
@@ -313,6 +590,7 @@ impl Default for DebugInfo {
             current_cycles: 0,
             cpu_registers: CpuRegisters::default(),
             ppu_state: PpuDebugState::default(),
+            interrupts: InterruptDebugState::default(),
             memory_access_count: 0,
             total_cycles: 0,
         }
@@ -1,8 +1,11 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use thiserror::Error;
 
 /// RNES emulator error types
 #[derive(Error, Debug)]
 pub enum RnesError {
+    #[cfg(feature = "std")]
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
@@ -15,9 +18,24 @@ pub enum RnesError {
     #[error("Memory access error: address 0x{address:04X}")]
     MemoryAccess { address: u16 },
     
+    #[error("Memory range out of bounds: {region} offset 0x{offset:X} length {len}")]
+    MemoryRange { region: String, offset: usize, len: usize },
+    
+    #[error("Memory is read-only: {0}")]
+    ReadOnlyMemory(String),
+    
     #[error("CPU error: {0}")]
     Cpu(String),
     
+    #[error("Invalid opcode 0x{opcode:02X} at 0x{pc:04X}")]
+    InvalidOpcode { opcode: u8, pc: u16 },
+    
+    #[error("Assembly error on line {line}: {message}")]
+    Assembly { line: usize, message: String },
+    
+    #[error("Invalid expression at column {column}: {message}")]
+    Expression { column: usize, message: String },
+    
     #[error("PPU error: {0}")]
     Ppu(String),
     
@@ -27,9 +45,133 @@ pub enum RnesError {
     #[error("Serialization error: {0}")]
     Serialization(String),
     
+    #[error("Incompatible save state: {0}")]
+    IncompatibleSaveState(String),
+    
+    #[error("Save state version {found} is not supported (expected {expected})")]
+    SaveStateVersionMismatch { found: u32, expected: u32 },
+    
+    #[error("Save state was made with a different ROM (CRC32 {state_crc32:08X}, loaded {rom_crc32:08X})")]
+    SaveStateRomMismatch { state_crc32: u32, rom_crc32: u32 },
+    
+    #[error("Save state is corrupted: {0}")]
+    CorruptSaveState(String),
+    
+    #[error("PRG RAM size mismatch: {actual} bytes of data for {expected} bytes of RAM")]
+    PrgRamSizeMismatch { expected: usize, actual: usize },
+    
+    #[error("{component} does not support {feature}")]
+    UnsupportedFeature { component: &'static str, feature: String },
+    
+    #[error("No ROM loaded")]
+    NoRomLoaded,
+    
+    #[error("Not found: {0}")]
+    NotFound(String),
+    
+    #[error("Netplay error: {0}")]
+    Netplay(String),
+    
     #[error("Configuration error: {0}")]
     Config(String),
 }
 
+impl RnesError {
+    /// Stable snake_case name of the error variant
+    ///
+    /// Unlike the message, this never changes wording, so bindings can
+    /// expose it as an error code (e.g. a `kind` field on a JS exception).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "std")]
+            RnesError::Io(_) => "io",
+            RnesError::RomFormat(_) => "rom_format",
+            RnesError::UnsupportedMapper(_) => "unsupported_mapper",
+            RnesError::MemoryAccess { .. } => "memory_access",
+            RnesError::MemoryRange { .. } => "memory_range",
+            RnesError::ReadOnlyMemory(_) => "read_only_memory",
+            RnesError::Cpu(_) => "cpu",
+            RnesError::InvalidOpcode { .. } => "invalid_opcode",
+            RnesError::Assembly { .. } => "assembly",
+            RnesError::Expression { .. } => "expression",
+            RnesError::Ppu(_) => "ppu",
+            RnesError::Apu(_) => "apu",
+            RnesError::Serialization(_) => "serialization",
+            RnesError::IncompatibleSaveState(_) => "incompatible_save_state",
+            RnesError::SaveStateVersionMismatch { .. } => "save_state_version_mismatch",
+            RnesError::SaveStateRomMismatch { .. } => "save_state_rom_mismatch",
+            RnesError::CorruptSaveState(_) => "corrupt_save_state",
+            RnesError::PrgRamSizeMismatch { .. } => "prg_ram_size_mismatch",
+            RnesError::UnsupportedFeature { .. } => "unsupported_feature",
+            RnesError::NoRomLoaded => "no_rom_loaded",
+            RnesError::NotFound(_) => "not_found",
+            RnesError::Netplay(_) => "netplay",
+            RnesError::Config(_) => "config",
+        }
+    }
+    
+    /// Check if this error means the data given as a ROM can't be played
+    pub fn is_rom_error(&self) -> bool {
+        matches!(self, RnesError::RomFormat(_) | RnesError::UnsupportedMapper(_))
+    }
+}
+
 /// Result type alias
 pub type RnesResult<T> = Result<T, RnesError>;
+
+/// Non-fatal problem noticed while loading or running a ROM
+///
+/// Emulation carries on; frontends can show these to the user.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RnesWarning {
+    #[error("Battery save is {actual} bytes but PRG RAM is {expected} bytes; loaded what fits")]
+    PrgRamSizeMismatch { expected: usize, actual: usize },
+    
+    #[error("{component} does not support {feature}")]
+    UnsupportedFeature { component: &'static str, feature: String },
+    
+    #[error("The game wrote to read-only {memory}")]
+    RomWrite { memory: &'static str },
+}
+
+/// Collects warnings until a frontend takes them
+///
+/// Each distinct warning is reported once until `clear`, so a game that
+/// repeats the same unsupported write doesn't flood the user.
+#[derive(Debug, Clone, Default)]
+pub struct WarningSink {
+    pending: Vec<RnesWarning>,
+    reported: Vec<RnesWarning>,
+}
+
+impl WarningSink {
+    /// Create empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Record a warning, unless the same one was already recorded
+    pub fn warn(&mut self, warning: RnesWarning) {
+        if self.reported.contains(&warning) {
+            return;
+        }
+        self.reported.push(warning.clone());
+        self.pending.push(warning);
+    }
+    
+    /// Take the warnings recorded since the last call
+    pub fn take(&mut self) -> Vec<RnesWarning> {
+        core::mem::take(&mut self.pending)
+    }
+    
+    /// Check if there are warnings waiting to be taken
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    
+    /// Forget all warnings, e.g. when another ROM is loaded
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.reported.clear();
+    }
+}
@@ -1,15 +1,37 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod error;
 pub mod types;
 pub mod constants;
 pub mod memory;
+pub mod hash;
+#[cfg(feature = "std")]
+pub mod storage;
+#[cfg(feature = "std")]
 pub mod save_system;
+#[cfg(feature = "std")]
 pub mod config;
+#[cfg(feature = "std")]
+pub mod annotations;
+#[cfg(feature = "std")]
+pub mod png;
 pub mod debugger;
+pub mod watch;
 
 pub use error::*;
 pub use types::*;
 pub use constants::*;
 pub use memory::MemoryAccess;
+pub use hash::*;
+#[cfg(feature = "std")]
+pub use storage::*;
+#[cfg(feature = "std")]
 pub use save_system::*;
+#[cfg(feature = "std")]
 pub use config::*;
+#[cfg(feature = "std")]
+pub use annotations::*;
 pub use debugger::*;
+pub use watch::*;
@@ -0,0 +1,189 @@
+//! Minimal PNG reader and writer for screenshots and golden frames
+//!
+//! Only 8-bit RGB and RGBA, non-interlaced images are supported, which covers
+//! everything the harness writes and what common image editors save.
+
+use std::io::{Read, Write};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::{crc32, RnesError, RnesResult};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+const COLOR_RGB: u8 = 2;
+const COLOR_RGBA: u8 = 6;
+
+/// Decoded image as RGBA8 pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Encode RGBA8 pixels as a PNG file
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> RnesResult<Vec<u8>> {
+    let stride = width as usize * 4;
+    if rgba.len() != stride * height as usize {
+        return Err(RnesError::Serialization(format!(
+            "PNG pixel data is {} bytes, expected {}x{} RGBA",
+            rgba.len(), width, height
+        )));
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, COLOR_RGBA, 0, 0, 0]);
+
+    // Every row uses filter type 0 (none)
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in rgba.chunks_exact(stride.max(1)) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    let data = encoder.finish()?;
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &data);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+/// Decode a PNG file to RGBA8 pixels
+pub fn decode_png(bytes: &[u8]) -> RnesResult<RgbaImage> {
+    let invalid = |message: &str| RnesError::Serialization(format!("Invalid PNG: {}", message));
+
+    if bytes.get(..8) != Some(&SIGNATURE[..]) {
+        return Err(invalid("bad signature"));
+    }
+
+    let mut header = None;
+    let mut data = Vec::new();
+    let mut offset = 8;
+    while offset + 12 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &bytes[offset + 4..offset + 8];
+        let body = bytes.get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| invalid("truncated chunk"))?;
+        match kind {
+            b"IHDR" => header = Some(body.to_vec()),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += length + 12;
+    }
+
+    let header = header.filter(|h| h.len() == 13).ok_or_else(|| invalid("missing IHDR"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match color_type {
+        COLOR_RGB => 3,
+        COLOR_RGBA => 4,
+        _ => return Err(invalid("only RGB and RGBA images are supported")),
+    };
+    if bit_depth != 8 || interlace != 0 {
+        return Err(invalid("only 8-bit non-interlaced images are supported"));
+    }
+
+    let mut raw = Vec::new();
+    ZlibDecoder::new(&data[..]).read_to_end(&mut raw)?;
+
+    let stride = width as usize * channels;
+    if raw.len() < (stride + 1) * height as usize {
+        return Err(invalid("image data is truncated"));
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    let mut previous = vec![0u8; stride];
+    let mut current = vec![0u8; stride];
+    for row in raw.chunks_exact(stride + 1).take(height as usize) {
+        current.copy_from_slice(&row[1..]);
+        unfilter(row[0], &mut current, &previous, channels)
+            .ok_or_else(|| invalid("unknown row filter"))?;
+        for pixel in current.chunks_exact(channels) {
+            pixels.extend_from_slice(&pixel[..3]);
+            pixels.push(if channels == 4 { pixel[3] } else { 255 });
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    Ok(RgbaImage { width, height, pixels })
+}
+
+/// Append one chunk (length, type, data, CRC)
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Undo a row filter in place, returning `None` for an unknown filter type
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Option<()> {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return None,
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+    Some(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_round_trip() {
+        let pixels: Vec<u8> = (0..3 * 2 * 4).map(|i| (i * 17) as u8).collect();
+        let png = encode_png(3, 2, &pixels).unwrap();
+        let image = decode_png(&png).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, pixels);
+    }
+
+    #[test]
+    fn test_png_unfilter_paeth_and_average() {
+        // Two RGB pixels per row; the second row predicts from the first
+        let previous = [10, 20, 30, 40, 50, 60];
+        let mut row = [1, 1, 1, 1, 1, 1];
+        unfilter(3, &mut row, &previous, 3).unwrap();
+        assert_eq!(row, [6, 11, 16, 24, 31, 39]);
+
+        let mut row = [1, 1, 1, 1, 1, 1];
+        unfilter(4, &mut row, &previous, 3).unwrap();
+        assert_eq!(row, [11, 21, 31, 41, 51, 61]);
+    }
+
+    #[test]
+    fn test_png_rejects_garbage() {
+        assert!(decode_png(b"not a png").is_err());
+    }
+}
@@ -1,98 +1,339 @@
-use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use bincode::Options;
 use serde::{Serialize, Deserialize};
-use crate::{Byte, RnesResult, RnesError};
+use serde::de::DeserializeOwned;
+use crate::{crc32, Byte, RnesResult, RnesError, StorageBackend, FileStorage, StorageConfig, RamInitPattern};
 
 /// Save system for NES emulator
 /// Handles battery backup saves and save states
 #[derive(Debug)]
 pub struct SaveSystem {
     save_dir: PathBuf,
+    storage: Box<dyn StorageBackend>,
+    per_rom_directory: bool,
+    battery_storage: Option<FileStorage>,
 }
 
 impl SaveSystem {
     /// Create new save system with default save directory
     pub fn new() -> Self {
-        let save_dir = PathBuf::from("saves");
-        Self { save_dir }
+        Self::with_save_dir("saves")
     }
     
     /// Create save system with custom save directory
     pub fn with_save_dir<P: AsRef<Path>>(save_dir: P) -> Self {
+        let save_dir = save_dir.as_ref().to_path_buf();
         Self {
-            save_dir: save_dir.as_ref().to_path_buf(),
+            storage: Box::new(FileStorage::new(&save_dir)),
+            save_dir,
+            per_rom_directory: false,
+            battery_storage: None,
         }
     }
     
+    /// Create save system with a custom storage backend
+    pub fn with_storage<S: StorageBackend + 'static>(storage: S) -> Self {
+        Self {
+            save_dir: PathBuf::new(),
+            storage: Box::new(storage),
+            per_rom_directory: false,
+            battery_storage: None,
+        }
+    }
+    
+    /// Create filesystem save system from configuration
+    pub fn from_config(config: &StorageConfig) -> Self {
+        let mut save_system = Self::with_save_dir(&config.save_dir);
+        save_system.per_rom_directory = config.per_rom_directory;
+        save_system.set_battery_dir(config.battery_dir.as_deref());
+        save_system
+    }
+    
+    /// Keep `.sav` battery files in a separate directory
+    pub fn with_battery_dir<P: AsRef<Path>>(mut self, battery_dir: P) -> Self {
+        self.set_battery_dir(Some(battery_dir.as_ref()));
+        self
+    }
+    
+    /// Set the directory for `.sav` battery files (`None` = main storage)
+    ///
+    /// Battery files in a separate directory are stored flat as
+    /// `<rom_name>.sav`, the layout FCEUX and Mesen use.
+    pub fn set_battery_dir(&mut self, battery_dir: Option<&Path>) {
+        self.battery_storage = battery_dir.map(FileStorage::new);
+    }
+    
+    /// Get the directory for `.sav` battery files, if set
+    pub fn battery_dir(&self) -> Option<&Path> {
+        self.battery_storage.as_ref().map(|storage| storage.root())
+    }
+    
+    /// Get storage backend used for battery files
+    fn battery_storage(&self) -> &dyn StorageBackend {
+        match self.battery_storage {
+            Some(ref storage) => storage,
+            None => self.storage.as_ref(),
+        }
+    }
+    
+    /// Store each ROM's saves in its own subdirectory
+    pub fn with_per_rom_directory(mut self, enabled: bool) -> Self {
+        self.per_rom_directory = enabled;
+        self
+    }
+    
+    /// Get storage backend
+    pub fn storage(&self) -> &dyn StorageBackend {
+        self.storage.as_ref()
+    }
+    
     /// Ensure save directory exists
     pub fn ensure_save_dir(&self) -> RnesResult<()> {
-        if !self.save_dir.exists() {
+        if !self.save_dir.as_os_str().is_empty() && !self.save_dir.exists() {
             std::fs::create_dir_all(&self.save_dir)?;
         }
         Ok(())
     }
     
+    /// Get storage key prefix for a ROM
+    fn rom_prefix(&self, rom_name: &str) -> String {
+        if self.per_rom_directory {
+            format!("{}/", rom_name)
+        } else {
+            String::new()
+        }
+    }
+    
+    /// Get battery backup storage key for a ROM
+    pub fn battery_key(&self, rom_name: &str) -> String {
+        if self.battery_storage.is_some() {
+            format!("{}.sav", rom_name)
+        } else {
+            format!("{}{}.sav", self.rom_prefix(rom_name), rom_name)
+        }
+    }
+    
+    /// Get save state storage key for a ROM
+    pub fn save_state_key(&self, rom_name: &str, slot: u8) -> String {
+        format!("{}{}.state{}", self.rom_prefix(rom_name), rom_name, slot)
+    }
+    
+    /// Get exit state storage key for a ROM
+    pub fn resume_state_key(&self, rom_name: &str) -> String {
+        format!("{}{}.resume", self.rom_prefix(rom_name), rom_name)
+    }
+    
+    /// Get fast-boot state storage key for a ROM
+    pub fn boot_state_key(&self, rom_name: &str) -> String {
+        format!("{}{}.boot", self.rom_prefix(rom_name), rom_name)
+    }
+    
     /// Get battery backup save path for a ROM
     pub fn get_battery_save_path(&self, rom_name: &str) -> PathBuf {
-        self.save_dir.join(format!("{}.sav", rom_name))
+        let dir = self.battery_dir().unwrap_or(&self.save_dir);
+        dir.join(self.battery_key(rom_name))
     }
     
     /// Get save state path for a ROM
     pub fn get_save_state_path(&self, rom_name: &str, slot: u8) -> PathBuf {
-        self.save_dir.join(format!("{}.state{}", rom_name, slot))
+        self.save_dir.join(self.save_state_key(rom_name, slot))
     }
     
     /// Save battery backup data
     pub fn save_battery_backup(&self, rom_name: &str, data: &[Byte]) -> RnesResult<()> {
-        self.ensure_save_dir()?;
-        
-        let save_path = self.get_battery_save_path(rom_name);
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&save_path)?;
-        
-        file.write_all(data)?;
-        tracing::info!("Battery backup saved to: {:?}", save_path);
+        let key = self.battery_key(rom_name);
+        self.battery_storage().write(&key, data)?;
+        tracing::info!("Battery backup saved to: {:?}", key);
         Ok(())
     }
     
     /// Load battery backup data
     pub fn load_battery_backup(&self, rom_name: &str) -> RnesResult<Vec<Byte>> {
-        let save_path = self.get_battery_save_path(rom_name);
+        let key = self.battery_key(rom_name);
         
-        if !save_path.exists() {
-            return Ok(Vec::new());
+        match self.battery_storage().read(&key)? {
+            Some(data) => {
+                tracing::info!("Battery backup loaded from: {:?}", key);
+                Ok(data)
+            }
+            None => Ok(Vec::new()),
         }
-        
-        let mut file = File::open(&save_path)?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
-        
-        tracing::info!("Battery backup loaded from: {:?}", save_path);
-        Ok(data)
     }
     
     /// Check if battery backup exists
     pub fn has_battery_backup(&self, rom_name: &str) -> bool {
-        self.get_battery_save_path(rom_name).exists()
+        self.battery_storage().exists(&self.battery_key(rom_name))
     }
     
     /// Delete battery backup
     pub fn delete_battery_backup(&self, rom_name: &str) -> RnesResult<()> {
-        let save_path = self.get_battery_save_path(rom_name);
+        let key = self.battery_key(rom_name);
         
-        if save_path.exists() {
-            std::fs::remove_file(&save_path)?;
-            tracing::info!("Battery backup deleted: {:?}", save_path);
+        let storage = self.battery_storage();
+        if storage.exists(&key) {
+            storage.delete(&key)?;
+            tracing::info!("Battery backup deleted: {:?}", key);
         }
         
         Ok(())
     }
 }
 
+impl SaveSystem {
+    /// Save the exit state written on shutdown
+    pub fn save_resume_state(&self, rom_name: &str, data: &[u8]) -> RnesResult<()> {
+        let key = self.resume_state_key(rom_name);
+        self.storage.write(&key, data)?;
+        tracing::info!("Exit state saved to: {:?}", key);
+        Ok(())
+    }
+    
+    /// Load the exit state, if one exists
+    pub fn load_resume_state(&self, rom_name: &str) -> RnesResult<Option<Vec<u8>>> {
+        self.storage.read(&self.resume_state_key(rom_name))
+    }
+    
+    /// Check if an exit state exists
+    pub fn has_resume_state(&self, rom_name: &str) -> bool {
+        self.storage.exists(&self.resume_state_key(rom_name))
+    }
+    
+    /// Delete the exit state
+    pub fn delete_resume_state(&self, rom_name: &str) -> RnesResult<()> {
+        self.storage.delete(&self.resume_state_key(rom_name))
+    }
+    
+    /// Save the cached post-boot state used by fast boot
+    pub fn save_boot_state(&self, rom_name: &str, data: &[u8]) -> RnesResult<()> {
+        let key = self.boot_state_key(rom_name);
+        self.storage.write(&key, data)?;
+        tracing::info!("Boot state cached to: {:?}", key);
+        Ok(())
+    }
+    
+    /// Load the cached post-boot state, if one exists
+    pub fn load_boot_state(&self, rom_name: &str) -> RnesResult<Option<Vec<u8>>> {
+        self.storage.read(&self.boot_state_key(rom_name))
+    }
+    
+    /// Check if a cached post-boot state exists
+    pub fn has_boot_state(&self, rom_name: &str) -> bool {
+        self.storage.exists(&self.boot_state_key(rom_name))
+    }
+    
+    /// Delete the cached post-boot state
+    pub fn delete_boot_state(&self, rom_name: &str) -> RnesResult<()> {
+        self.storage.delete(&self.boot_state_key(rom_name))
+    }
+    
+    /// List save state slots for a ROM, sorted by slot number
+    ///
+    /// Slots that cannot be decoded (e.g. from an older format) are skipped.
+    pub fn list_save_states(&self, rom_name: &str) -> RnesResult<Vec<SaveSlotInfo>> {
+        let prefix = format!("{}{}.state", self.rom_prefix(rom_name), rom_name);
+        let mut slots = Vec::new();
+        
+        for key in self.storage.list(&prefix)? {
+            let Ok(slot) = key[prefix.len()..].parse::<u8>() else {
+                continue;
+            };
+            let Some(data) = self.storage.read(&key)? else {
+                continue;
+            };
+            match SaveState::from_bytes(&data) {
+                Ok(state) => slots.push(SaveSlotInfo {
+                    slot,
+                    timestamp: state.timestamp,
+                    metadata: state.metadata,
+                }),
+                Err(e) => tracing::warn!("Skipping save state {:?}: {}", key, e),
+            }
+        }
+        
+        slots.sort_by_key(|info| info.slot);
+        Ok(slots)
+    }
+}
+
+impl Default for SaveSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Magic bytes at the start of every save state
+pub const SAVE_STATE_MAGIC: [u8; 4] = *b"RNSS";
+
+/// Current save state format version
+///
+/// Bump this whenever the serialized layout of `SaveState` changes.
+pub const SAVE_STATE_VERSION: u32 = 7;
+
+/// Save state header flag: payload is deflate-compressed
+const SAVE_STATE_FLAG_COMPRESSED: u16 = 0x0001;
+
+/// Size of the save state header in bytes
+const SAVE_STATE_HEADER_SIZE: usize = 24;
+
+/// Largest uncompressed payload accepted when loading
+///
+/// Real states are well under 100KB; the cap stops a corrupt header from
+/// requesting a huge allocation.
+pub const MAX_SAVE_STATE_PAYLOAD: usize = 16 * 1024 * 1024;
+
+/// Fixed-size header preceding the serialized save state
+///
+/// Layout (little-endian): magic[4], version u32, flags u16, reserved u16,
+/// rom_crc32 u32, payload_crc32 u32, payload_len u32. The payload CRC and
+/// length refer to the uncompressed payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveStateHeader {
+    pub version: u32,
+    pub flags: u16,
+    pub rom_crc32: u32,
+    pub payload_crc32: u32,
+    pub payload_len: u32,
+}
+
+impl SaveStateHeader {
+    /// Check if payload is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.flags & SAVE_STATE_FLAG_COMPRESSED != 0
+    }
+    
+    /// Encode header to bytes
+    pub fn to_bytes(&self) -> [u8; SAVE_STATE_HEADER_SIZE] {
+        let mut bytes = [0u8; SAVE_STATE_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&SAVE_STATE_MAGIC);
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.rom_crc32.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.payload_crc32.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes
+    }
+    
+    /// Decode header from the start of `data`
+    pub fn from_bytes(data: &[u8]) -> RnesResult<Self> {
+        if data.len() < SAVE_STATE_HEADER_SIZE || data[0..4] != SAVE_STATE_MAGIC {
+            return Err(RnesError::IncompatibleSaveState("Not an RNES save state".to_string()));
+        }
+        
+        let u32_at = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+        Ok(Self {
+            version: u32_at(4),
+            flags: u16::from_le_bytes([data[8], data[9]]),
+            rom_crc32: u32_at(12),
+            payload_crc32: u32_at(16),
+            payload_len: u32_at(20),
+        })
+    }
+}
+
 /// Save state data structure
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SaveState {
@@ -102,6 +343,10 @@ pub struct SaveState {
     pub timestamp: u64,
     /// ROM name
     pub rom_name: String,
+    /// CRC-32 of the ROM the state was taken from (0 = unknown)
+    pub rom_crc32: u32,
+    /// Slot metadata shown in save/load menus
+    pub metadata: SaveStateMetadata,
     /// CPU state
     pub cpu_state: CpuSaveState,
     /// PPU state
@@ -112,6 +357,41 @@ pub struct SaveState {
     pub memory_state: MemorySaveState,
     /// Mapper state
     pub mapper_state: MapperSaveState,
+    /// Controller port state
+    pub input_state: InputSaveState,
+    /// Power-on RAM pattern, applied again on the next power cycle
+    pub ram_init: RamInitPattern,
+    /// Seed for nondeterministic power-on state
+    pub seed: u64,
+}
+
+/// Save state metadata
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SaveStateMetadata {
+    /// Emulated play time in milliseconds
+    pub play_time_ms: u64,
+    /// PPU frame count when the state was taken
+    pub frame_count: u64,
+    /// Downscaled screenshot
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// Downscaled RGB24 screenshot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub width: u16,
+    pub height: u16,
+    /// Row-major RGB bytes (`width * height * 3`)
+    pub rgb: Vec<u8>,
+}
+
+/// Save state slot listing entry
+#[derive(Debug, Clone)]
+pub struct SaveSlotInfo {
+    pub slot: u8,
+    /// Unix timestamp when the state was saved
+    pub timestamp: u64,
+    pub metadata: SaveStateMetadata,
 }
 
 /// CPU save state
@@ -124,6 +404,9 @@ pub struct CpuSaveState {
     pub y: u8,
     pub status: u8,
     pub cycles: u64,
+    pub stall_cycles: u32,
+    pub nmi_pending: bool,
+    pub irq_pending: bool,
 }
 
 /// PPU save state
@@ -133,66 +416,18 @@ pub struct PpuSaveState {
     pub dot: u16,
     pub frame: u32,
     pub vblank: bool,
-    pub registers: PpuRegistersSaveState,
+    /// Registers, scroll and rendering pipeline state (an encoded `PpuState`)
+    pub data: Vec<u8>,
     pub oam: Vec<u8>, // OAM data
     pub palette_ram: Vec<u8>, // Palette RAM data
-    pub frame_buffer: Vec<u32>, // Pixel data as u32
-}
-
-/// PPU registers save state
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct PpuRegistersSaveState {
-    pub ppuctrl: u8,
-    pub ppumask: u8,
-    pub ppustatus: u8,
-    pub oamaddr: u8,
-    pub oamdata: u8,
-    pub ppuscroll: u8,
-    pub ppuaddr: u8,
-    pub ppudata: u8,
+    pub nametable_ram: Vec<u8>, // Nametable RAM data
 }
 
 /// APU save state
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ApuSaveState {
-    pub frame_counter: u8,
-    pub frame_counter_mode: u8,
-    pub frame_counter_cycles: u64,
-    pub channels: Vec<AudioChannelSaveState>,
-}
-
-/// Audio channel save state
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AudioChannelSaveState {
-    pub channel_type: u8,
-    pub enabled: bool,
-    pub volume: u8,
-    pub frequency: u16,
-    pub duty_cycle: u8,
-    pub envelope: EnvelopeSaveState,
-    pub sweep: SweepSaveState,
-    pub length_counter: u8,
-}
-
-/// Envelope save state
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct EnvelopeSaveState {
-    pub volume: u8,
-    pub decay_level: u8,
-    pub decay_counter: u8,
-    pub r#loop: bool,
-    pub constant_volume: bool,
-}
-
-/// Sweep save state
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct SweepSaveState {
-    pub enabled: bool,
-    pub period: u8,
-    pub shift: u8,
-    pub negate: bool,
-    pub reload: bool,
-    pub counter: u8,
+    /// Channel and frame counter state (an encoded `ApuState`)
+    pub data: Vec<u8>,
 }
 
 /// Memory save state
@@ -200,89 +435,187 @@ pub struct SweepSaveState {
 pub struct MemorySaveState {
     pub ram: Vec<u8>, // RAM data
     pub prg_ram: Vec<u8>,
+    pub chr_ram: Vec<u8>, // CHR RAM data (empty for CHR ROM boards)
 }
 
 /// Mapper save state
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct MapperSaveState {
     pub mapper_type: u8,
-    pub data: Vec<u8>, // Serialized mapper-specific data
+    pub data: Vec<u8>, // Bank and IRQ registers (an encoded `MapperState`)
+}
+
+/// Controller port save state
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InputSaveState {
+    pub controller_strobe: bool,
+    pub controller_shift: [u8; 2],
 }
 
 impl SaveState {
     /// Create new save state
     pub fn new(rom_name: String) -> Self {
         Self {
-            version: 1,
+            version: SAVE_STATE_VERSION,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
             rom_name,
+            rom_crc32: 0,
+            metadata: SaveStateMetadata::default(),
             cpu_state: CpuSaveState::default(),
             ppu_state: PpuSaveState::default(),
             apu_state: ApuSaveState::default(),
             memory_state: MemorySaveState::default(),
             mapper_state: MapperSaveState::default(),
+            input_state: InputSaveState::default(),
+            ram_init: RamInitPattern::default(),
+            seed: 0,
         }
     }
     
-    /// Save state to file
-    pub fn save_to_file(&self, save_system: &SaveSystem, slot: u8) -> RnesResult<()> {
-        save_system.ensure_save_dir()?;
-        
-        let save_path = save_system.get_save_state_path(&self.rom_name, slot);
-        let data = bincode::serialize(self)
+    /// Serialize save state to bytes (header + deflate-compressed payload)
+    pub fn to_bytes(&self) -> RnesResult<Vec<u8>> {
+        let payload = bincode::serialize(self)
             .map_err(|e| RnesError::Serialization(format!("Failed to serialize save state: {}", e)))?;
         
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&save_path)?;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&payload)?;
+        let compressed = encoder.finish()?;
+        
+        let header = SaveStateHeader {
+            version: SAVE_STATE_VERSION,
+            flags: SAVE_STATE_FLAG_COMPRESSED,
+            rom_crc32: self.rom_crc32,
+            payload_crc32: crc32(&payload),
+            payload_len: payload.len() as u32,
+        };
+        
+        let mut data = Vec::with_capacity(SAVE_STATE_HEADER_SIZE + compressed.len());
+        data.extend_from_slice(&header.to_bytes());
+        data.extend_from_slice(&compressed);
+        Ok(data)
+    }
+    
+    /// Deserialize save state from bytes, verifying version and checksum
+    pub fn from_bytes(data: &[u8]) -> RnesResult<Self> {
+        let header = SaveStateHeader::from_bytes(data)?;
+        
+        if header.version != SAVE_STATE_VERSION {
+            return Err(RnesError::SaveStateVersionMismatch {
+                found: header.version,
+                expected: SAVE_STATE_VERSION,
+            });
+        }
+        
+        if header.payload_len as usize > MAX_SAVE_STATE_PAYLOAD {
+            return Err(RnesError::CorruptSaveState(format!(
+                "payload of {} bytes exceeds the {} byte limit",
+                header.payload_len, MAX_SAVE_STATE_PAYLOAD
+            )));
+        }
+        
+        let body = &data[SAVE_STATE_HEADER_SIZE..];
+        let payload = if header.is_compressed() {
+            let mut payload = Vec::with_capacity(header.payload_len as usize);
+            // Never inflate past the advertised size
+            ZlibDecoder::new(body).take(header.payload_len as u64 + 1).read_to_end(&mut payload)
+                .map_err(|e| RnesError::CorruptSaveState(format!("failed to decompress: {}", e)))?;
+            payload
+        } else {
+            body.to_vec()
+        };
+        
+        if payload.len() != header.payload_len as usize || crc32(&payload) != header.payload_crc32 {
+            return Err(RnesError::CorruptSaveState("checksum mismatch".to_string()));
+        }
         
-        file.write_all(&data)?;
-        tracing::info!("Save state saved to: {:?}", save_path);
+        decode_bytes(&payload)
+            .map_err(|e| RnesError::Serialization(format!("Failed to deserialize save state: {}", e)))
+    }
+    
+    /// Encode a component's state (PPU, APU, mapper) for one of the `data` fields
+    pub fn encode_component<T: Serialize>(state: &T) -> RnesResult<Vec<u8>> {
+        bincode::serialize(state)
+            .map_err(|e| RnesError::Serialization(format!("Failed to serialize component state: {}", e)))
+    }
+    
+    /// Decode a component's state written by `encode_component`
+    pub fn decode_component<T: DeserializeOwned>(data: &[u8]) -> RnesResult<T> {
+        decode_bytes(data)
+            .map_err(|e| RnesError::CorruptSaveState(format!("invalid component state: {}", e)))
+    }
+    
+    /// Read only the header of serialized save state data
+    pub fn peek_header(data: &[u8]) -> RnesResult<SaveStateHeader> {
+        SaveStateHeader::from_bytes(data)
+    }
+    
+    /// Check that the save state belongs to the ROM with the given CRC-32
+    pub fn verify_rom(&self, rom_crc32: u32) -> RnesResult<()> {
+        if self.rom_crc32 != 0 && rom_crc32 != 0 && self.rom_crc32 != rom_crc32 {
+            return Err(RnesError::SaveStateRomMismatch {
+                state_crc32: self.rom_crc32,
+                rom_crc32,
+            });
+        }
+        Ok(())
+    }
+    
+    /// Save state to file
+    pub fn save_to_file(&self, save_system: &SaveSystem, slot: u8) -> RnesResult<()> {
+        let key = save_system.save_state_key(&self.rom_name, slot);
+        let data = self.to_bytes()?;
+        
+        save_system.storage().write(&key, &data)?;
+        tracing::info!("Save state saved to: {:?}", key);
         Ok(())
     }
     
     /// Load state from file
     pub fn load_from_file(save_system: &SaveSystem, rom_name: &str, slot: u8) -> RnesResult<Self> {
-        let save_path = save_system.get_save_state_path(rom_name, slot);
+        let key = save_system.save_state_key(rom_name, slot);
         
-        if !save_path.exists() {
-            return Err(RnesError::Serialization("Save state file not found".to_string()));
-        }
-        
-        let mut file = File::open(&save_path)?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+        let data = save_system.storage().read(&key)?
+            .ok_or_else(|| RnesError::NotFound(format!("save state {:?}", key)))?;
         
-        let save_state: SaveState = bincode::deserialize(&data)
-            .map_err(|e| RnesError::Serialization(format!("Failed to deserialize save state: {}", e)))?;
+        let save_state = SaveState::from_bytes(&data)?;
         
-        tracing::info!("Save state loaded from: {:?}", save_path);
+        tracing::info!("Save state loaded from: {:?}", key);
         Ok(save_state)
     }
     
     /// Check if save state exists
     pub fn exists(save_system: &SaveSystem, rom_name: &str, slot: u8) -> bool {
-        save_system.get_save_state_path(rom_name, slot).exists()
+        save_system.storage().exists(&save_system.save_state_key(rom_name, slot))
     }
     
     /// Delete save state
     pub fn delete(save_system: &SaveSystem, rom_name: &str, slot: u8) -> RnesResult<()> {
-        let save_path = save_system.get_save_state_path(rom_name, slot);
+        let key = save_system.save_state_key(rom_name, slot);
         
-        if save_path.exists() {
-            std::fs::remove_file(&save_path)?;
-            tracing::info!("Save state deleted: {:?}", save_path);
+        if save_system.storage().exists(&key) {
+            save_system.storage().delete(&key)?;
+            tracing::info!("Save state deleted: {:?}", key);
         }
         
         Ok(())
     }
 }
 
+/// Deserialize bincode data without letting length prefixes ask for more
+/// than `data` holds
+///
+/// Same encoding as `bincode::deserialize` otherwise.
+fn decode_bytes<T: DeserializeOwned>(data: &[u8]) -> bincode::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(data.len() as u64)
+        .deserialize(data)
+}
+
 // Default implementations
 impl Default for CpuSaveState {
     fn default() -> Self {
@@ -294,6 +627,9 @@ impl Default for CpuSaveState {
             y: 0,
             status: 0,
             cycles: 0,
+            stall_cycles: 0,
+            nmi_pending: false,
+            irq_pending: false,
         }
     }
 }
@@ -305,36 +641,10 @@ impl Default for PpuSaveState {
             dot: 0,
             frame: 0,
             vblank: false,
-            registers: PpuRegistersSaveState::default(),
+            data: Vec::new(),
             oam: vec![0; 256],
             palette_ram: vec![0; 32],
-            frame_buffer: Vec::new(),
-        }
-    }
-}
-
-impl Default for PpuRegistersSaveState {
-    fn default() -> Self {
-        Self {
-            ppuctrl: 0,
-            ppumask: 0,
-            ppustatus: 0,
-            oamaddr: 0,
-            oamdata: 0,
-            ppuscroll: 0,
-            ppuaddr: 0,
-            ppudata: 0,
-        }
-    }
-}
-
-impl Default for ApuSaveState {
-    fn default() -> Self {
-        Self {
-            frame_counter: 0,
-            frame_counter_mode: 0,
-            frame_counter_cycles: 0,
-            channels: Vec::new(),
+            nametable_ram: vec![0; 4096],
         }
     }
 }
@@ -344,15 +654,7 @@ impl Default for MemorySaveState {
         Self {
             ram: vec![0; 2048],
             prg_ram: Vec::new(),
-        }
-    }
-}
-
-impl Default for MapperSaveState {
-    fn default() -> Self {
-        Self {
-            mapper_type: 0,
-            data: Vec::new(),
+            chr_ram: Vec::new(),
         }
     }
 }
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::{RnesResult, RnesError};
+
+/// Key-value storage used by the save system
+///
+/// Keys are relative, `/`-separated names such as `game.sav` or
+/// `game/game.state1`. Backends decide how they map onto real storage.
+pub trait StorageBackend: std::fmt::Debug + Send + Sync {
+    /// Read data for a key, or `None` if it does not exist
+    fn read(&self, key: &str) -> RnesResult<Option<Vec<u8>>>;
+
+    /// Write data for a key, replacing any existing data
+    fn write(&self, key: &str, data: &[u8]) -> RnesResult<()>;
+
+    /// Check if a key exists
+    fn exists(&self, key: &str) -> bool;
+
+    /// Delete a key (no-op if it does not exist)
+    fn delete(&self, key: &str) -> RnesResult<()>;
+
+    /// List keys starting with `prefix`
+    fn list(&self, prefix: &str) -> RnesResult<Vec<String>>;
+}
+
+/// Write a file so readers see either the old or the new contents
+///
+/// Data goes to a temporary file beside `path` first, named after this
+/// process and thread, then is renamed over it. Emulators running side by
+/// side can save to the same directory without tearing each other's files.
+pub fn write_atomic(path: &Path, data: &[u8]) -> RnesResult<()> {
+    let name = path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let thread = format!("{:?}", std::thread::current().id());
+    let thread: String = thread.chars().filter(char::is_ascii_digit).collect();
+    let temp = path.with_file_name(format!(".{}.{}-{}{}", name, std::process::id(), thread, TEMP_SUFFIX));
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp)?;
+    let written = file.write_all(data).and_then(|_| file.sync_all());
+    drop(file);
+    if let Err(e) = written.and_then(|_| std::fs::rename(&temp, path)) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Suffix of `write_atomic` temporary files, which `FileStorage` never lists
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Filesystem storage rooted at a directory
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    /// Create storage rooted at `root`
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Get storage root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the file path for a key
+    pub fn path_for(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    fn collect_keys(&self, dir: &Path, keys: &mut Vec<String>) -> RnesResult<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_temp = path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| name.starts_with('.') && name.ends_with(TEMP_SUFFIX));
+            if path.is_dir() {
+                self.collect_keys(&path, keys)?;
+            } else if is_temp {
+                continue;
+            } else if let Ok(relative) = path.strip_prefix(&self.root) {
+                let key = relative.components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                keys.push(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn read(&self, key: &str) -> RnesResult<Option<Vec<u8>>> {
+        let path = self.path_for(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut file = File::open(&path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> RnesResult<()> {
+        let path = self.path_for(key);
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        write_atomic(&path, data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.path_for(key).exists()
+    }
+
+    fn delete(&self, key: &str) -> RnesResult<()> {
+        let path = self.path_for(key);
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> RnesResult<Vec<String>> {
+        let mut keys = Vec::new();
+        if self.root.exists() {
+            self.collect_keys(&self.root, &mut keys)?;
+        }
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// In-memory storage, useful for tests, WASM and sandboxed hosts
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    /// Create empty in-memory storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entries(&self) -> RnesResult<std::sync::MutexGuard<'_, HashMap<String, Vec<u8>>>> {
+        self.entries.lock()
+            .map_err(|_| RnesError::Serialization("Memory storage lock poisoned".to_string()))
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn read(&self, key: &str) -> RnesResult<Option<Vec<u8>>> {
+        Ok(self.entries()?.get(key).cloned())
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> RnesResult<()> {
+        self.entries()?.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.entries().map(|entries| entries.contains_key(key)).unwrap_or(false)
+    }
+
+    fn delete(&self, key: &str) -> RnesResult<()> {
+        self.entries()?.remove(key);
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> RnesResult<Vec<String>> {
+        let mut keys: Vec<String> = self.entries()?
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
@@ -62,6 +62,17 @@ impl Pixel {
 /// Audio sample
 pub type AudioSample = f32;
 
+/// Device plugged into a controller port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputDevice {
+    /// Standard controller
+    #[default]
+    Controller,
+    /// Zapper light gun
+    Zapper,
+}
+
 /// Controller button
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Button {
@@ -88,6 +99,65 @@ pub struct ControllerState {
     pub right: bool,
 }
 
+impl ControllerState {
+    /// Build controller state from the standard button byte
+    /// (bit 0 = A, 1 = B, 2 = Select, 3 = Start, 4 = Up, 5 = Down, 6 = Left, 7 = Right)
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            a: bits & 0x01 != 0,
+            b: bits & 0x02 != 0,
+            select: bits & 0x04 != 0,
+            start: bits & 0x08 != 0,
+            up: bits & 0x10 != 0,
+            down: bits & 0x20 != 0,
+            left: bits & 0x40 != 0,
+            right: bits & 0x80 != 0,
+        }
+    }
+
+    /// Set a single button
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        match button {
+            Button::A => self.a = pressed,
+            Button::B => self.b = pressed,
+            Button::Select => self.select = pressed,
+            Button::Start => self.start = pressed,
+            Button::Up => self.up = pressed,
+            Button::Down => self.down = pressed,
+            Button::Left => self.left = pressed,
+            Button::Right => self.right = pressed,
+        }
+    }
+
+    /// Check whether a button is pressed
+    pub fn is_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::A => self.a,
+            Button::B => self.b,
+            Button::Select => self.select,
+            Button::Start => self.start,
+            Button::Up => self.up,
+            Button::Down => self.down,
+            Button::Left => self.left,
+            Button::Right => self.right,
+        }
+    }
+
+    /// Get controller state as the standard button byte
+    pub fn bits(&self) -> u8 {
+        let mut value = 0;
+        if self.a { value |= 0x01; }
+        if self.b { value |= 0x02; }
+        if self.select { value |= 0x04; }
+        if self.start { value |= 0x08; }
+        if self.up { value |= 0x10; }
+        if self.down { value |= 0x20; }
+        if self.left { value |= 0x40; }
+        if self.right { value |= 0x80; }
+        value
+    }
+}
+
 impl Default for ControllerState {
     fn default() -> Self {
         Self {
@@ -103,6 +173,226 @@ impl Default for ControllerState {
     }
 }
 
+/// Power-on contents of RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RamInitPattern {
+    /// All $00
+    #[default]
+    Zeros,
+    /// All $FF
+    Ones,
+    /// FCEUX default: alternating runs of four $00 and four $FF bytes
+    Fceux,
+    /// Pseudo-random bytes from a seed, reproducible across runs
+    Random,
+}
+
+impl RamInitPattern {
+    /// Every pattern, in declaration order
+    pub const ALL: [RamInitPattern; 4] = [
+        RamInitPattern::Zeros,
+        RamInitPattern::Ones,
+        RamInitPattern::Fceux,
+        RamInitPattern::Random,
+    ];
+    
+    /// Name as written in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            RamInitPattern::Zeros => "zeros",
+            RamInitPattern::Ones => "ones",
+            RamInitPattern::Fceux => "fceux",
+            RamInitPattern::Random => "random",
+        }
+    }
+    
+    /// Fill `buffer` with this pattern (`seed` is only used by `Random`)
+    pub fn fill(&self, buffer: &mut [u8], seed: u64) {
+        match self {
+            RamInitPattern::Zeros => buffer.fill(0x00),
+            RamInitPattern::Ones => buffer.fill(0xFF),
+            RamInitPattern::Fceux => {
+                for (i, byte) in buffer.iter_mut().enumerate() {
+                    *byte = if i & 0x04 != 0 { 0xFF } else { 0x00 };
+                }
+            }
+            RamInitPattern::Random => {
+                // SplitMix64
+                let mut state = seed;
+                for chunk in buffer.chunks_mut(8) {
+                    state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                    z ^= z >> 31;
+                    chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+/// MMC3 scanline IRQ behavior, which differs between chip revisions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mmc3IrqRevision {
+    /// MMC3B/C ("new"): IRQ whenever the counter is 0 after a clock
+    #[default]
+    Sharp,
+    /// MMC3A ("old/alternate"): IRQ only when the counter becomes 0 by
+    /// decrementing or by an explicit reload
+    Nec,
+}
+
+/// What a game does while its window is unfocused or hidden
+///
+/// Ordered from least to most intrusive, so the stronger of two applicable
+/// settings wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundBehavior {
+    /// Carry on as if nothing happened
+    #[default]
+    KeepRunning,
+    /// Keep running with the sound off
+    Mute,
+    /// Stop until the window is back
+    Pause,
+}
+
+/// How the picture is scaled to fill the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    /// Largest whole-number multiple of 256x240 that fits, square pixels
+    Integer,
+    /// As large as fits, with the 8:7 pixel aspect ratio of a TV
+    #[default]
+    Fit,
+    /// Fill the whole window
+    Stretch,
+}
+
+/// Built-in CRT look applied on the GPU after the frame is uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrtPreset {
+    /// No CRT pass
+    #[default]
+    None,
+    /// Dark gaps between lines only
+    Scanlines,
+    /// Scanlines and a Trinitron-style aperture grille of RGB stripes
+    ApertureGrille,
+    /// Scanlines, a shadow mask, barrel curvature and darkened corners
+    Curved,
+}
+
+impl CrtPreset {
+    /// Every preset, in declaration order
+    pub const ALL: [CrtPreset; 4] = [
+        CrtPreset::None,
+        CrtPreset::Scanlines,
+        CrtPreset::ApertureGrille,
+        CrtPreset::Curved,
+    ];
+}
+
+/// Cartridge sound chip mixed on top of the APU
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpansionChip {
+    /// Konami VRC6: two pulses and a sawtooth
+    Vrc6,
+    /// Konami VRC7: six-channel OPLL FM
+    Vrc7,
+    /// Famicom Disk System wavetable channel
+    Fds,
+    /// Nintendo MMC5: two pulses and 8-bit PCM
+    Mmc5,
+    /// Namco 163: up to eight wavetable channels
+    Namco163,
+    /// Sunsoft 5B: three square channels with noise and envelope
+    Sunsoft5b,
+}
+
+impl ExpansionChip {
+    /// Number of chips, for per-chip tables
+    pub const COUNT: usize = 6;
+    
+    /// Every chip, in declaration order
+    pub const ALL: [ExpansionChip; Self::COUNT] = [
+        ExpansionChip::Vrc6,
+        ExpansionChip::Vrc7,
+        ExpansionChip::Fds,
+        ExpansionChip::Mmc5,
+        ExpansionChip::Namco163,
+        ExpansionChip::Sunsoft5b,
+    ];
+    
+    /// Position of the chip in `ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Console timing region
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    /// Frames per second for this region
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            Region::Ntsc => 60.0988,
+            Region::Pal | Region::Dendy => 50.0070,
+        }
+    }
+
+    /// CPU clock rate in Hz
+    pub fn cpu_clock_rate(&self) -> u32 {
+        match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        }
+    }
+
+    /// Guess region from ROM file name tags such as `(E)`, `(Europe)` or `(USA)`
+    pub fn from_filename(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        const PAL_TAGS: [&str; 7] = ["(e)", "(europe)", "(pal)", "(a)", "(australia)", "(g)", "(f)"];
+        const NTSC_TAGS: [&str; 6] = ["(u)", "(usa)", "(j)", "(japan)", "(ntsc)", "(ju)"];
+        const DENDY_TAGS: [&str; 1] = ["(dendy)"];
+
+        if DENDY_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Dendy)
+        } else if PAL_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Pal)
+        } else if NTSC_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Ntsc)
+        } else {
+            None
+        }
+    }
+}
+
+impl core::fmt::Display for Region {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Region::Ntsc => write!(f, "NTSC"),
+            Region::Pal => write!(f, "PAL"),
+            Region::Dendy => write!(f, "Dendy"),
+        }
+    }
+}
+
 /// Emulator state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmulatorState {
@@ -110,6 +400,10 @@ pub struct EmulatorState {
     pub ppu_scanline: Scanline,
     pub ppu_dot: Dot,
     pub frame_count: u64,
+    /// Frames that did not read the controllers ($4016/$4017)
+    pub lag_frames: u64,
+    /// Whether the last completed frame was a lag frame
+    pub lag_frame: bool,
 }
 
 impl Default for EmulatorState {
@@ -119,6 +413,8 @@ impl Default for EmulatorState {
             ppu_scanline: -1,
             ppu_dot: 0,
             frame_count: 0,
+            lag_frames: 0,
+            lag_frame: false,
         }
     }
 }
@@ -0,0 +1,308 @@
+//! Watch expressions for a debugger's variable panel
+//!
+//! An expression combines numbers and memory reads with C-like operators:
+//! `[addr]` reads the byte at a CPU address, numbers are decimal, `0x` or `$`
+//! hex, and `* / % + - << >> & ^ |` work as in C on 64-bit integers, with
+//! unary `-` and `~` and parentheses for grouping. Names from a RAM map
+//! stand for their address, so `[lives]` reads a game's lives counter.
+//! Arithmetic wraps and division by zero yields 0, so evaluation never fails.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use crate::{Byte, Word, RnesError, RnesResult};
+
+/// Binary operator, in C precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    And,
+    Xor,
+    Or,
+}
+
+impl BinaryOp {
+    /// Operators by spelling, two-character ones first so `<<` is not cut short
+    const SPELLINGS: [(&'static str, BinaryOp); 10] = [
+        ("<<", BinaryOp::Shl),
+        (">>", BinaryOp::Shr),
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Rem),
+        ("+", BinaryOp::Add),
+        ("-", BinaryOp::Sub),
+        ("&", BinaryOp::And),
+        ("^", BinaryOp::Xor),
+        ("|", BinaryOp::Or),
+    ];
+
+    /// Binding strength; higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 5,
+            BinaryOp::Add | BinaryOp::Sub => 4,
+            BinaryOp::Shl | BinaryOp::Shr => 3,
+            BinaryOp::And => 2,
+            BinaryOp::Xor => 1,
+            BinaryOp::Or => 0,
+        }
+    }
+
+    fn apply(self, left: i64, right: i64) -> i64 {
+        let shift = u32::try_from(right).ok().filter(|&shift| shift < 64);
+        match self {
+            BinaryOp::Mul => left.wrapping_mul(right),
+            BinaryOp::Div => left.checked_div(right).unwrap_or(0),
+            BinaryOp::Rem => left.checked_rem(right).unwrap_or(0),
+            BinaryOp::Add => left.wrapping_add(right),
+            BinaryOp::Sub => left.wrapping_sub(right),
+            BinaryOp::Shl => shift.map_or(0, |shift| left << shift),
+            BinaryOp::Shr => shift.map_or(0, |shift| left >> shift),
+            BinaryOp::And => left & right,
+            BinaryOp::Xor => left ^ right,
+            BinaryOp::Or => left | right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    /// Byte at the address the inner expression evaluates to
+    Read(Box<Node>),
+    Negate(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, peek: &dyn Fn(Word) -> Byte) -> i64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Read(addr) => peek(addr.evaluate(peek) as Word) as i64,
+            Node::Negate(inner) => inner.evaluate(peek).wrapping_neg(),
+            Node::Not(inner) => !inner.evaluate(peek),
+            Node::Binary(op, left, right) => op.apply(left.evaluate(peek), right.evaluate(peek)),
+        }
+    }
+}
+
+/// Parsed watch expression such as `[0x071C]*256+[0x071D]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchExpression {
+    source: String,
+    root: Node,
+}
+
+impl WatchExpression {
+    /// Parse an expression without names
+    pub fn parse(source: &str) -> RnesResult<Self> {
+        Self::parse_with_names(source, &|_| None)
+    }
+
+    /// Parse an expression, looking up the address of each name with `names`
+    pub fn parse_with_names(source: &str, names: &dyn Fn(&str) -> Option<Word>) -> RnesResult<Self> {
+        let mut parser = Parser { text: source.as_bytes(), pos: 0, names };
+        let root = parser.expression(0)?;
+        parser.skip_space();
+        if parser.pos < parser.text.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    /// Expression text as written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluate with `peek` reading CPU memory
+    pub fn evaluate(&self, peek: &dyn Fn(Word) -> Byte) -> i64 {
+        self.root.evaluate(peek)
+    }
+}
+
+/// Recursive descent parser with precedence climbing for binary operators
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    names: &'a dyn Fn(&str) -> Option<Word>,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> RnesError {
+        RnesError::Expression {
+            column: self.pos + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip spaces and consume `expected` if it comes next
+    fn eat(&mut self, expected: u8) -> bool {
+        self.skip_space();
+        let found = self.text.get(self.pos) == Some(&expected);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Binary operator at the current position, without consuming it
+    fn operator(&mut self) -> Option<(BinaryOp, usize)> {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        BinaryOp::SPELLINGS.iter()
+            .find(|(spelling, _)| rest.starts_with(spelling.as_bytes()))
+            .map(|&(spelling, op)| (op, spelling.len()))
+    }
+
+    /// Parse operators binding at least as tightly as `min_precedence`
+    fn expression(&mut self, min_precedence: u8) -> RnesResult<Node> {
+        let mut left = self.unary()?;
+        while let Some((op, len)) = self.operator() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.pos += len;
+            let right = self.expression(op.precedence() + 1)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> RnesResult<Node> {
+        if self.eat(b'-') {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat(b'~') {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(b'(') {
+            let inner = self.expression(0)?;
+            if !self.eat(b')') {
+                return Err(self.error("expected `)`"));
+            }
+            return Ok(inner);
+        }
+        if self.eat(b'[') {
+            let addr = self.expression(0)?;
+            if !self.eat(b']') {
+                return Err(self.error("expected `]`"));
+            }
+            return Ok(Node::Read(Box::new(addr)));
+        }
+        if self.text.get(self.pos).is_some_and(|&c| c.is_ascii_alphabetic() || c == b'_') {
+            return self.name();
+        }
+        self.number()
+    }
+
+    fn name(&mut self) -> RnesResult<Node> {
+        let start = self.pos;
+        while self.text.get(self.pos).is_some_and(|&c| c.is_ascii_alphanumeric() || c == b'_') {
+            self.pos += 1;
+        }
+        // Names are ASCII, so this slice is valid UTF-8
+        let name = core::str::from_utf8(&self.text[start..self.pos]).unwrap_or_default();
+        (self.names)(name)
+            .map(|addr| Node::Number(addr as i64))
+            .ok_or_else(|| RnesError::Expression {
+                column: start + 1,
+                message: alloc::format!("unknown name `{}`", name),
+            })
+    }
+
+    fn number(&mut self) -> RnesResult<Node> {
+        let radix = if self.eat(b'$') {
+            16
+        } else if self.text[self.pos..].starts_with(b"0x") || self.text[self.pos..].starts_with(b"0X") {
+            self.pos += 2;
+            16
+        } else {
+            10
+        };
+
+        let start = self.pos;
+        while self.text.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("expected a number, a name, `[`, `(`, `-` or `~`"));
+        }
+        // The digits are ASCII, so this slice is valid UTF-8
+        let digits = core::str::from_utf8(&self.text[start..self.pos]).unwrap_or_default();
+        i64::from_str_radix(digits, radix)
+            .map(Node::Number)
+            .map_err(|_| RnesError::Expression {
+                column: start + 1,
+                message: alloc::format!("invalid number `{}`", digits),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str) -> i64 {
+        let memory = |addr: Word| (addr & 0xFF) as Byte ^ 0x0F;
+        WatchExpression::parse(source).unwrap().evaluate(&memory)
+    }
+
+    #[test]
+    fn test_watch_expression_evaluation() {
+        assert_eq!(evaluate("42"), 42);
+        assert_eq!(evaluate("$10 + 0x10 + 0X10"), 48);
+        assert_eq!(evaluate("[0x075A]"), 0x55);
+        assert_eq!(evaluate("[0x071C]*256+[0x071D]"), 0x13 * 256 + 0x12);
+        assert_eq!(evaluate("[[0x0003] + 1]"), 0x02);
+        assert_eq!(evaluate("1 + 2 * 3 - 4"), 3);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9);
+        assert_eq!(evaluate("1 << 4 | 3 & 1"), 17);
+        assert_eq!(evaluate("-5 % 3 ^ ~0"), 1);
+        assert_eq!(evaluate("7 / 0 + 7 % 0"), 0);
+        assert_eq!(evaluate("1 << 64"), 0);
+    }
+
+    #[test]
+    fn test_watch_expression_errors() {
+        let column = |source: &str| match WatchExpression::parse(source) {
+            Err(RnesError::Expression { column, .. }) => column,
+            other => panic!("{:?} parsed as {:?}", source, other),
+        };
+        assert_eq!(column(""), 1);
+        assert_eq!(column("[0x10"), 6);
+        assert_eq!(column("(1 + 2"), 7);
+        assert_eq!(column("1 + "), 5);
+        assert_eq!(column("1 2"), 3);
+        assert_eq!(column("0x1G"), 3);
+        assert_eq!(column("99999999999999999999"), 1);
+        assert_eq!(WatchExpression::parse(" [0x10] ").unwrap().source(), "[0x10]");
+    }
+
+    #[test]
+    fn test_watch_expression_names() {
+        let names = |name: &str| (name == "lives").then_some(0x075A);
+        let memory = |addr: Word| (addr & 0xFF) as Byte;
+        let expression = WatchExpression::parse_with_names("[lives] + lives", &names).unwrap();
+        assert_eq!(expression.evaluate(&memory), 0x5A + 0x075A);
+        assert!(matches!(
+            WatchExpression::parse_with_names("[coins]", &names),
+            Err(RnesError::Expression { column: 2, .. })
+        ));
+    }
+}
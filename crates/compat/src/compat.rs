@@ -0,0 +1,374 @@
+//! Bulk ROM compatibility survey
+//!
+//! Every ROM in a directory is booted headlessly for a fixed number of
+//! frames and classified from its frame hashes: a picture that holds still
+//! for `stable_frames` frames boots, a single-colour final frame is a black
+//! screen, a jammed CPU or BRK loop is a hang, and errors, invalid opcodes,
+//! panics and early stops are crashes. ROMs run on a
+//! `ParallelRomRunner`, and the results are written as CSV or Markdown.
+
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use rnes_cartridge::Cartridge;
+use rnes_common::{crc32, Config, MemoryStorage, Pixel, RnesError, RnesResult, SaveSystem};
+use rnes_core::{CrashReason, Emulator};
+use crate::parallel::{ParallelRomRunner, RomOutcome};
+
+/// How a ROM fared in the survey
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompatStatus {
+    /// Shows a picture that held still for the stable frame count
+    Boots,
+    /// Shows a picture that never held still (attract demo or glitching)
+    Unstable,
+    /// The final frame is a single colour
+    BlackScreen,
+    /// Returned an error, hit an invalid opcode, panicked or stopped before the last frame
+    Crashed,
+    /// The CPU jammed or got stuck in a BRK loop
+    Hung,
+    /// The mapper is not emulated
+    UnsupportedMapper,
+    /// The file is not a valid iNES ROM
+    InvalidRom,
+    /// Exceeded the wall-clock timeout
+    Timeout,
+}
+
+impl CompatStatus {
+    /// Every status, in report order
+    pub const ALL: [CompatStatus; 8] = [
+        CompatStatus::Boots,
+        CompatStatus::Unstable,
+        CompatStatus::BlackScreen,
+        CompatStatus::Crashed,
+        CompatStatus::Hung,
+        CompatStatus::UnsupportedMapper,
+        CompatStatus::InvalidRom,
+        CompatStatus::Timeout,
+    ];
+}
+
+impl fmt::Display for CompatStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CompatStatus::Boots => "boots",
+            CompatStatus::Unstable => "unstable",
+            CompatStatus::BlackScreen => "black screen",
+            CompatStatus::Crashed => "crashed",
+            CompatStatus::Hung => "hung",
+            CompatStatus::UnsupportedMapper => "unsupported mapper",
+            CompatStatus::InvalidRom => "invalid ROM",
+            CompatStatus::Timeout => "timeout",
+        })
+    }
+}
+
+/// Survey result for one ROM
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompatEntry {
+    pub path: PathBuf,
+    pub status: CompatStatus,
+    /// Mapper number, if the header could be read
+    pub mapper: Option<u8>,
+    /// Frames emulated before the run ended
+    pub frames_run: u32,
+    /// First frame of the final run of identical frames
+    pub stable_since: Option<u32>,
+    /// Hash of the last frame
+    pub final_hash: Option<u32>,
+    /// Error or panic text
+    pub message: Option<String>,
+    /// Wall-clock time in seconds
+    pub time_secs: f64,
+}
+
+impl CompatEntry {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            status: CompatStatus::Boots,
+            mapper: None,
+            frames_run: 0,
+            stable_since: None,
+            final_hash: None,
+            message: None,
+            time_secs: 0.0,
+        }
+    }
+
+    fn failed(mut self, status: CompatStatus, message: impl Into<String>) -> Self {
+        self.status = status;
+        self.message = Some(message.into());
+        self
+    }
+
+    /// File name of the ROM, for reports
+    pub fn name(&self) -> String {
+        self.path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+}
+
+/// Survey results for a set of ROMs, in input order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompatReport {
+    pub entries: Vec<CompatEntry>,
+}
+
+impl CompatReport {
+    /// Count entries with the given status
+    pub fn count(&self, status: CompatStatus) -> usize {
+        self.entries.iter().filter(|entry| entry.status == status).count()
+    }
+
+    /// One-line summary, e.g. "12 ROMs: 9 boots, 2 black screen, 1 crashed"
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} ROMs", self.entries.len());
+        let mut separator = ": ";
+        for status in CompatStatus::ALL {
+            let count = self.count(status);
+            if count > 0 {
+                let _ = write!(summary, "{}{} {}", separator, count, status);
+                separator = ", ";
+            }
+        }
+        summary
+    }
+
+    /// Render as CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("rom,status,mapper,frames,stable_since,final_hash,time_secs,message\n");
+        for entry in &self.entries {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.3},{}",
+                csv_field(&entry.name()),
+                entry.status,
+                entry.mapper.map(|m| m.to_string()).unwrap_or_default(),
+                entry.frames_run,
+                entry.stable_since.map(|f| f.to_string()).unwrap_or_default(),
+                entry.final_hash.map(|h| format!("{:08X}", h)).unwrap_or_default(),
+                entry.time_secs,
+                csv_field(entry.message.as_deref().unwrap_or("")),
+            );
+        }
+        csv
+    }
+
+    /// Render as a Markdown table with the summary above it
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# Compatibility Report\n\n{}\n\n", self.summary());
+        md.push_str("| ROM | Status | Mapper | Frames | Stable since | Notes |\n");
+        md.push_str("|-----|--------|--------|--------|--------------|-------|\n");
+        for entry in &self.entries {
+            let mapper = entry.mapper.map(|m| rnes_mappers::describe(m).to_string()).unwrap_or_default();
+            let _ = writeln!(
+                md,
+                "| {} | {} | {} | {} | {} | {} |",
+                markdown_cell(&entry.name()),
+                entry.status,
+                markdown_cell(&mapper),
+                entry.frames_run,
+                entry.stable_since.map(|f| f.to_string()).unwrap_or_default(),
+                markdown_cell(entry.message.as_deref().unwrap_or("")),
+            );
+        }
+        md
+    }
+
+    /// Write the report, as Markdown for `.md` paths and CSV otherwise
+    pub fn write(&self, path: &Path) -> RnesResult<()> {
+        let contents = if path.extension().is_some_and(|ext| ext == "md") {
+            self.to_markdown()
+        } else {
+            self.to_csv()
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Find `.nes` files under `dir`, recursively, sorted by path
+pub fn find_roms(dir: &Path) -> RnesResult<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nes")) {
+                roms.push(path);
+            }
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
+
+/// Hash a frame's RGBA bytes, to spot a picture that holds still
+fn frame_hash(pixels: &[Pixel]) -> u32 {
+    let rgba: Vec<u8> = pixels.iter().flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a]).collect();
+    crc32(&rgba)
+}
+
+/// Boots ROMs headlessly and classifies the result
+pub struct CompatRunner {
+    frames: u32,
+    stable_frames: u32,
+    runner: ParallelRomRunner,
+}
+
+impl CompatRunner {
+    /// Create runner for 600 frames (10 s NTSC), stable after 60 frames
+    pub fn new() -> Self {
+        Self {
+            frames: 600,
+            stable_frames: 60,
+            runner: ParallelRomRunner::new().with_timeout(Duration::from_secs(60)),
+        }
+    }
+
+    /// Set the number of frames each ROM runs
+    pub fn with_frames(mut self, frames: u32) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    /// Set how many identical frames count as a stable picture
+    pub fn with_stable_frames(mut self, stable_frames: u32) -> Self {
+        self.stable_frames = stable_frames.max(1);
+        self
+    }
+
+    /// Set the number of ROMs run at once
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.runner = self.runner.with_threads(threads);
+        self
+    }
+
+    /// Set the wall-clock limit for each ROM
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.runner = self.runner.with_timeout(timeout);
+        self
+    }
+
+    /// Survey a single ROM on the calling thread
+    pub fn check_rom(&self, path: &Path) -> CompatEntry {
+        survey(path, self.frames, self.stable_frames)
+    }
+
+    /// Survey every ROM under `dir`
+    pub fn run_dir(&self, dir: &Path) -> RnesResult<CompatReport> {
+        Ok(self.run(&find_roms(dir)?))
+    }
+
+    /// Survey `roms` in parallel
+    pub fn run(&self, roms: &[PathBuf]) -> CompatReport {
+        let (frames, stable_frames) = (self.frames, self.stable_frames);
+        let runs = self.runner.run(roms, move |path| Ok(survey(path, frames, stable_frames)));
+        let entries = runs.into_iter()
+            .map(|run| {
+                let mut entry = match run.outcome {
+                    RomOutcome::Finished(Ok(entry)) => entry,
+                    RomOutcome::Finished(Err(e)) => CompatEntry::new(&run.path).failed(CompatStatus::Crashed, e.to_string()),
+                    RomOutcome::Panicked(message) => {
+                        CompatEntry::new(&run.path).failed(CompatStatus::Crashed, format!("panicked: {}", message))
+                    }
+                    RomOutcome::TimedOut => CompatEntry { status: CompatStatus::Timeout, ..CompatEntry::new(&run.path) },
+                };
+                entry.time_secs = run.elapsed.as_secs_f64();
+                entry
+            })
+            .collect();
+        CompatReport { entries }
+    }
+}
+
+impl Default for CompatRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn survey(path: &Path, frames: u32, stable_frames: u32) -> CompatEntry {
+    let entry = CompatEntry::new(path);
+    let cartridge = match Cartridge::from_file(path) {
+        Ok(cartridge) => cartridge,
+        Err(e) => return entry.failed(CompatStatus::InvalidRom, e.to_string()),
+    };
+    let mut entry = CompatEntry { mapper: Some(cartridge.mapper_number()), ..entry };
+
+    // Battery saves stay in memory so a survey never touches the player's saves
+    let mut config = Config::default();
+    config.general.auto_save_battery = false;
+    let mut emulator = Emulator::with_config(config)
+        .with_save_system(SaveSystem::with_storage(MemoryStorage::new()));
+    if let Err(e) = emulator.load_rom(cartridge) {
+        return match e {
+            RnesError::UnsupportedMapper(mapper) => {
+                entry.failed(CompatStatus::UnsupportedMapper, rnes_mappers::describe(mapper).to_string())
+            }
+            e => entry.failed(CompatStatus::Crashed, e.to_string()),
+        };
+    }
+    emulator.start();
+
+    let mut uniform = false;
+    for frame in 1..=frames {
+        if let Err(e) = emulator.run_frame() {
+            return entry.failed(CompatStatus::Crashed, format!("frame {}: {}", frame, e));
+        }
+        if let Some(crash) = emulator.crash() {
+            let status = match crash.reason {
+                CrashReason::InvalidOpcode { .. } => CompatStatus::Crashed,
+                CrashReason::Jam { .. } | CrashReason::BrkLoop => CompatStatus::Hung,
+            };
+            entry.frames_run = frame - 1;
+            return entry.failed(status, crash.to_string());
+        }
+        if !emulator.is_running() {
+            return entry.failed(CompatStatus::Crashed, format!("stopped after {} frames", frame - 1));
+        }
+        let Some(pixels) = emulator.get_ppu_frame_buffer() else {
+            return entry.failed(CompatStatus::Crashed, "no PPU frame buffer");
+        };
+
+        let hash = frame_hash(pixels);
+        if entry.final_hash != Some(hash) {
+            entry.stable_since = Some(frame);
+            uniform = pixels.iter().all(|pixel| *pixel == pixels[0]);
+        }
+        entry.final_hash = Some(hash);
+        entry.frames_run = frame;
+    }
+
+    let held = entry.stable_since.map_or(0, |since| frames + 1 - since);
+    entry.status = if uniform {
+        CompatStatus::BlackScreen
+    } else if held >= stable_frames {
+        CompatStatus::Boots
+    } else {
+        CompatStatus::Unstable
+    };
+    entry
+}
@@ -0,0 +1,10 @@
+//! ROM compatibility survey
+//!
+//! Boots whole directories of ROMs headlessly on a pool of threads. Shared
+//! by the native frontend's `compat` command and the test suite.
+
+pub mod compat;
+pub mod parallel;
+
+pub use compat::{find_roms, CompatEntry, CompatReport, CompatRunner, CompatStatus};
+pub use parallel::{ParallelRomRunner, RomOutcome, RomRun};
@@ -0,0 +1,192 @@
+//! Parallel ROM execution
+//!
+//! Each ROM runs on its own worker thread, with at most `threads` running at
+//! once. A panicking ROM is reported instead of aborting the batch, and a ROM
+//! that exceeds the wall-clock timeout is abandoned so it can't stall the
+//! rest. Abandoned threads keep running until their runner's own cycle limit
+//! stops them; their results are discarded.
+
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use rnes_common::RnesResult;
+
+/// How a single ROM run ended
+#[derive(Debug)]
+pub enum RomOutcome<R> {
+    /// The test function returned
+    Finished(RnesResult<R>),
+    /// The test function panicked
+    Panicked(String),
+    /// The ROM was still running when its timeout expired
+    TimedOut,
+}
+
+/// Result of one ROM in a parallel batch
+#[derive(Debug)]
+pub struct RomRun<R> {
+    pub path: PathBuf,
+    pub outcome: RomOutcome<R>,
+    pub elapsed: Duration,
+}
+
+impl<R> RomRun<R> {
+    /// File name of the ROM, for reports
+    pub fn name(&self) -> String {
+        self.path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+}
+
+/// Runs a test function over many ROMs on a pool of threads
+pub struct ParallelRomRunner {
+    threads: usize,
+    timeout: Duration,
+}
+
+impl ParallelRomRunner {
+    /// Create a runner using every available core and a 60 second timeout
+    pub fn new() -> Self {
+        Self {
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Set the number of ROMs run at once
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Set the wall-clock limit for each ROM
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run `test` on every ROM, returning results in input order
+    pub fn run<F, R>(&self, roms: &[PathBuf], test: F) -> Vec<RomRun<R>>
+    where
+        F: Fn(&Path) -> RnesResult<R> + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let test = Arc::new(test);
+        let (sender, receiver) = mpsc::channel();
+        let mut results: Vec<Option<RomRun<R>>> = roms.iter().map(|_| None).collect();
+        let mut pending = roms.iter().cloned().enumerate();
+        // Index -> start time of ROMs still running
+        let mut active: HashMap<usize, Instant> = HashMap::new();
+
+        loop {
+            while active.len() < self.threads {
+                let Some((index, path)) = pending.next() else {
+                    break;
+                };
+                let test = Arc::clone(&test);
+                let sender = sender.clone();
+                let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                active.insert(index, Instant::now());
+
+                let spawned = thread::Builder::new()
+                    .name(format!("rom-{}", name))
+                    .spawn(move || {
+                        let outcome = match panic::catch_unwind(AssertUnwindSafe(|| test(&path))) {
+                            Ok(result) => RomOutcome::Finished(result),
+                            Err(payload) => RomOutcome::Panicked(panic_message(payload.as_ref())),
+                        };
+                        // The receiver is gone if the batch already finished
+                        let _ = sender.send((index, outcome));
+                    });
+                if let Err(e) = spawned {
+                    active.remove(&index);
+                    results[index] = Some(RomRun {
+                        path: roms[index].clone(),
+                        outcome: RomOutcome::Panicked(format!("failed to spawn thread: {}", e)),
+                        elapsed: Duration::ZERO,
+                    });
+                }
+            }
+
+            let Some(oldest) = active.values().min().copied() else {
+                break;
+            };
+            let wait = (oldest + self.timeout).saturating_duration_since(Instant::now());
+
+            match receiver.recv_timeout(wait) {
+                Ok((index, outcome)) => {
+                    // Ignore late results from ROMs that already timed out
+                    if let Some(started) = active.remove(&index) {
+                        results[index] = Some(RomRun {
+                            path: roms[index].clone(),
+                            outcome,
+                            elapsed: started.elapsed(),
+                        });
+                    }
+                }
+                Err(_) => {
+                    let now = Instant::now();
+                    let expired: Vec<usize> = active.iter()
+                        .filter(|(_, started)| now.duration_since(**started) >= self.timeout)
+                        .map(|(index, _)| *index)
+                        .collect();
+                    for index in expired {
+                        let started = active.remove(&index).unwrap_or(now);
+                        tracing::warn!("ROM {} timed out", roms[index].display());
+                        results[index] = Some(RomRun {
+                            path: roms[index].clone(),
+                            outcome: RomOutcome::TimedOut,
+                            elapsed: now.duration_since(started),
+                        });
+                    }
+                }
+            }
+        }
+
+        results.into_iter().flatten().collect()
+    }
+}
+
+impl Default for ParallelRomRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roms(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_parallel_results_keep_input_order() {
+        let runner = ParallelRomRunner::new().with_threads(3);
+        let runs = runner.run(&roms(&["a.nes", "b.nes", "c.nes", "d.nes", "e.nes"]), |path| {
+            // Finish out of order
+            let delay = if path.ends_with("a.nes") { 30 } else { 1 };
+            thread::sleep(Duration::from_millis(delay));
+            Ok(path.display().to_string())
+        });
+        let names: Vec<String> = runs.iter().map(|run| run.name()).collect();
+        assert_eq!(names, ["a.nes", "b.nes", "c.nes", "d.nes", "e.nes"]);
+        assert!(runs.iter().all(|run| matches!(&run.outcome, RomOutcome::Finished(Ok(name)) if *name == run.path.display().to_string())));
+    }
+}
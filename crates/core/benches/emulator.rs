@@ -0,0 +1,103 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rnes_cartridge::Cartridge;
+use rnes_common::Config;
+use rnes_core::Emulator;
+
+const PRG_BANK_SIZE: usize = 16 * 1024;
+const CHR_BANK_SIZE: usize = 8 * 1024;
+
+/// Build an iNES image with `program` at $8000 and an optional IRQ handler
+fn build_rom(mapper: u8, prg_banks: u8, program: &[u8], irq_handler: Option<u16>) -> Vec<u8> {
+    let mut rom = vec![
+        0x4E, 0x45, 0x53, 0x1A,
+        prg_banks, 1,
+        (mapper & 0x0F) << 4, mapper & 0xF0,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    let prg_size = prg_banks as usize * PRG_BANK_SIZE;
+    let mut prg = vec![0xEA; prg_size];
+    prg[..program.len()].copy_from_slice(program);
+
+    // NMI -> $8000 (unused), RESET -> $8000, IRQ -> handler
+    let irq = irq_handler.unwrap_or(0x8000);
+    prg[prg_size - 6..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, irq as u8, (irq >> 8) as u8]);
+    rom.extend_from_slice(&prg);
+
+    // Non-empty pattern data so the PPU has something to fetch
+    let chr: Vec<u8> = (0..CHR_BANK_SIZE).map(|i| (i * 7) as u8).collect();
+    rom.extend_from_slice(&chr);
+    rom
+}
+
+/// Arithmetic loop with rendering disabled
+fn cpu_only_rom() -> Vec<u8> {
+    build_rom(0, 1, &[
+        0xA2, 0x00,             // LDX #$00
+        0xE8,                   // INX
+        0x8A,                   // TXA
+        0x69, 0x03,             // ADC #$03
+        0x95, 0x00,             // STA $00,X
+        0x4C, 0x02, 0x80,       // JMP $8002
+    ], None)
+}
+
+/// Background and sprite rendering enabled, idle CPU
+fn ppu_heavy_rom() -> Vec<u8> {
+    build_rom(0, 1, &[
+        0xA9, 0x1E,             // LDA #$1E
+        0x8D, 0x01, 0x20,       // STA $2001
+        0x4C, 0x05, 0x80,       // JMP $8005
+    ], None)
+}
+
+/// MMC3 scanline IRQ firing every 8 lines with rendering enabled
+fn mmc3_irq_rom() -> Vec<u8> {
+    build_rom(4, 2, &[
+        0xA9, 0x1E,             // LDA #$1E
+        0x8D, 0x01, 0x20,       // STA $2001
+        0xA9, 0x08,             // LDA #$08
+        0x8D, 0x00, 0xC0,       // STA $C000 (IRQ latch)
+        0x8D, 0x01, 0xC0,       // STA $C001 (IRQ reload)
+        0x8D, 0x01, 0xE0,       // STA $E001 (IRQ enable)
+        0x58,                   // CLI
+        0x4C, 0x11, 0x80,       // JMP $8011
+        // IRQ handler at $8014
+        0x8D, 0x00, 0xE0,       // STA $E000 (acknowledge)
+        0x8D, 0x01, 0xE0,       // STA $E001 (re-enable)
+        0x40,                   // RTI
+    ], Some(0x8014))
+}
+
+fn create_emulator(rom: &[u8]) -> Emulator {
+    let cartridge = Cartridge::from_bytes(rom).expect("valid benchmark ROM");
+    let mut emulator = Emulator::with_config(Config::default());
+    emulator.load_rom(cartridge).expect("benchmark ROM loads");
+    emulator.start();
+    emulator
+}
+
+fn bench_workload(c: &mut Criterion, name: &str, rom: Vec<u8>) {
+    let mut emulator = create_emulator(&rom);
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            emulator.run_frame().unwrap();
+            black_box(emulator.ppu().frame_count());
+        })
+    });
+}
+
+fn cpu_only(c: &mut Criterion) {
+    bench_workload(c, "frame_cpu_only", cpu_only_rom());
+}
+
+fn ppu_heavy(c: &mut Criterion) {
+    bench_workload(c, "frame_ppu_heavy", ppu_heavy_rom());
+}
+
+fn mmc3_irq_heavy(c: &mut Criterion) {
+    bench_workload(c, "frame_mmc3_irq_heavy", mmc3_irq_rom());
+}
+
+criterion_group!(benches, cpu_only, ppu_heavy, mmc3_irq_heavy);
+criterion_main!(benches);
@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+use rnes_common::{RnesError, RnesResult, Word};
+use crate::MemoryPeek;
+
+/// Memory reference size (rcheevos `0x` prefixes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemSize {
+    /// `0xH` - 8 bits
+    Bits8,
+    /// `0x` - 16 bits, little-endian
+    Bits16,
+    /// `0xX` - 32 bits, little-endian
+    Bits32,
+    /// `0xL` - low nibble
+    Lower4,
+    /// `0xU` - high nibble
+    Upper4,
+    /// `0xM`-`0xT` - single bit 0-7
+    Bit(u8),
+}
+
+impl MemSize {
+    fn read(&self, memory: &dyn MemoryPeek, addr: Word) -> u32 {
+        let mut bytes = [0u8; 4];
+        let len = match self {
+            MemSize::Bits16 => 2,
+            MemSize::Bits32 => 4,
+            _ => 1,
+        };
+        memory.peek_range(addr, &mut bytes[..len]);
+        let value = u32::from_le_bytes(bytes);
+        match self {
+            MemSize::Lower4 => value & 0x0F,
+            MemSize::Upper4 => (value >> 4) & 0x0F,
+            MemSize::Bit(bit) => (value >> bit) & 0x01,
+            _ => value,
+        }
+    }
+}
+
+/// Condition operand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// Constant value
+    Value(u32),
+    /// Current value of a memory reference
+    Mem { size: MemSize, addr: Word },
+    /// Value of a memory reference on the previous frame (`d` prefix)
+    Delta { size: MemSize, addr: Word, previous: u32 },
+}
+
+impl Operand {
+    fn parse(token: &str) -> RnesResult<Self> {
+        let invalid = || RnesError::Config(format!("Invalid achievement operand: {}", token));
+
+        let (delta, rest) = match token.strip_prefix('d') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+
+        let Some(mem) = rest.strip_prefix("0x") else {
+            if delta {
+                return Err(invalid());
+            }
+            // Plain values are decimal, `h` prefix is hex
+            let value = match rest.strip_prefix('h') {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => rest.parse(),
+            };
+            return value.map(Operand::Value).map_err(|_| invalid());
+        };
+
+        let (size, hex) = match mem.chars().next() {
+            Some('H') => (MemSize::Bits8, &mem[1..]),
+            Some('X') => (MemSize::Bits32, &mem[1..]),
+            Some('L') => (MemSize::Lower4, &mem[1..]),
+            Some('U') => (MemSize::Upper4, &mem[1..]),
+            Some(c @ 'M'..='T') => (MemSize::Bit(c as u8 - b'M'), &mem[1..]),
+            Some(' ') => (MemSize::Bits16, &mem[1..]),
+            Some(c) if c.is_ascii_hexdigit() => (MemSize::Bits16, mem),
+            _ => return Err(invalid()),
+        };
+        let addr = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(|addr| Word::try_from(addr).ok())
+            .ok_or_else(invalid)?;
+
+        Ok(if delta {
+            Operand::Delta { size, addr, previous: 0 }
+        } else {
+            Operand::Mem { size, addr }
+        })
+    }
+
+    fn value(&self, memory: &dyn MemoryPeek) -> u32 {
+        match *self {
+            Operand::Value(value) => value,
+            Operand::Mem { size, addr } => size.read(memory, addr),
+            Operand::Delta { previous, .. } => previous,
+        }
+    }
+
+    /// Remember this frame's value for next frame's delta
+    fn update_delta(&mut self, memory: &dyn MemoryPeek) {
+        if let Operand::Delta { size, addr, previous } = self {
+            *previous = size.read(memory, *addr);
+        }
+    }
+}
+
+/// Comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    fn apply(&self, left: u32, right: u32) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterEqual => left >= right,
+        }
+    }
+}
+
+/// Single `left <op> right` condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+}
+
+impl Condition {
+    /// Parse a condition such as `0xH0010=5` or `0x 0020>d0x 0020`
+    pub fn parse(text: &str) -> RnesResult<Self> {
+        // Two-character operators first so `<=` is not read as `<`
+        const OPERATORS: [(&str, Comparison); 7] = [
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessEqual),
+            (">=", Comparison::GreaterEqual),
+            ("==", Comparison::Equal),
+            ("=", Comparison::Equal),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ];
+
+        if text.contains(':') {
+            return Err(RnesError::Config(format!("Unsupported achievement condition flag: {}", text)));
+        }
+        for (operator, comparison) in OPERATORS {
+            if let Some((left, right)) = text.split_once(operator) {
+                return Ok(Self {
+                    left: Operand::parse(left)?,
+                    comparison,
+                    right: Operand::parse(right)?,
+                });
+            }
+        }
+        Err(RnesError::Config(format!("Invalid achievement condition: {}", text)))
+    }
+
+    fn test(&self, memory: &dyn MemoryPeek) -> bool {
+        self.comparison.apply(self.left.value(memory), self.right.value(memory))
+    }
+}
+
+/// Achievement defined by rcheevos-style logic
+///
+/// Supports the core subset of the rcheevos syntax: memory sizes
+/// `0xH`/`0x`/`0xX`/`0xL`/`0xU`/`0xM`-`0xT`, delta values (`d`), decimal
+/// and `h` hex constants, and conditions joined with `_` (all must hold).
+/// Flags, hit counts and alt groups are rejected at parse time.
+#[derive(Debug, Clone)]
+pub struct Achievement {
+    pub id: u32,
+    pub title: String,
+    conditions: Vec<Condition>,
+}
+
+impl Achievement {
+    /// Create achievement from its `MemAddr` logic string
+    pub fn new(id: u32, title: &str, logic: &str) -> RnesResult<Self> {
+        let conditions = logic.split('_')
+            .map(Condition::parse)
+            .collect::<RnesResult<Vec<_>>>()?;
+        Ok(Self {
+            id,
+            title: title.to_string(),
+            conditions,
+        })
+    }
+
+    /// Get parsed conditions
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+}
+
+/// Unlock event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unlock {
+    pub id: u32,
+    pub title: String,
+    pub frame: u64,
+}
+
+/// Receives unlocks, e.g. to submit them to a server
+pub trait UnlockReporter: Send {
+    fn report(&mut self, unlock: &Unlock);
+}
+
+/// Read-only achievement client
+///
+/// Evaluates achievements once per frame through `MemoryPeek`, so it can
+/// never change game state. Disabled until `set_enabled(true)`.
+#[derive(Default)]
+pub struct AchievementClient {
+    enabled: bool,
+    achievements: Vec<Achievement>,
+    unlocked: HashSet<u32>,
+    unlocks: Vec<Unlock>,
+    reporter: Option<Box<dyn UnlockReporter>>,
+}
+
+impl AchievementClient {
+    /// Create disabled client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set reporter called for every unlock
+    pub fn with_reporter(mut self, reporter: Box<dyn UnlockReporter>) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    /// Enable or disable evaluation
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Check if evaluation is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Add an achievement
+    pub fn add(&mut self, achievement: Achievement) {
+        self.achievements.push(achievement);
+    }
+
+    /// Check if an achievement is unlocked
+    pub fn is_unlocked(&self, id: u32) -> bool {
+        self.unlocked.contains(&id)
+    }
+
+    /// Take unlocks since the last call
+    pub fn take_unlocks(&mut self) -> Vec<Unlock> {
+        std::mem::take(&mut self.unlocks)
+    }
+
+    /// Evaluate all locked achievements for a completed frame
+    pub fn evaluate(&mut self, frame: u64, memory: &dyn MemoryPeek) {
+        if !self.enabled {
+            return;
+        }
+
+        for achievement in &mut self.achievements {
+            let locked = !self.unlocked.contains(&achievement.id);
+            if locked && achievement.conditions.iter().all(|condition| condition.test(memory)) {
+                let unlock = Unlock {
+                    id: achievement.id,
+                    title: achievement.title.clone(),
+                    frame,
+                };
+                tracing::info!("Achievement unlocked: {} ({})", unlock.title, unlock.id);
+                if let Some(ref mut reporter) = self.reporter {
+                    reporter.report(&unlock);
+                }
+                self.unlocked.insert(achievement.id);
+                self.unlocks.push(unlock);
+            }
+
+            for condition in &mut achievement.conditions {
+                condition.left.update_delta(memory);
+                condition.right.update_delta(memory);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for AchievementClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AchievementClient")
+            .field("enabled", &self.enabled)
+            .field("achievements", &self.achievements.len())
+            .field("unlocked", &self.unlocked)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ram([u8; 0x800]);
+
+    impl MemoryPeek for Ram {
+        fn peek(&self, addr: Word) -> u8 {
+            self.0[(addr & 0x07FF) as usize]
+        }
+    }
+
+    #[test]
+    fn test_condition_parsing() {
+        let condition = Condition::parse("0xH0010>=h1F").unwrap();
+        assert_eq!(condition.left, Operand::Mem { size: MemSize::Bits8, addr: 0x0010 });
+        assert_eq!(condition.comparison, Comparison::GreaterEqual);
+        assert_eq!(condition.right, Operand::Value(0x1F));
+
+        assert_eq!(Condition::parse("0x 0020=300").unwrap().left, Operand::Mem { size: MemSize::Bits16, addr: 0x20 });
+        assert_eq!(Condition::parse("0xO0001=1").unwrap().left, Operand::Mem { size: MemSize::Bit(2), addr: 1 });
+        assert!(Condition::parse("R:0xH0010=1").is_err());
+        assert!(Condition::parse("0xH0010").is_err());
+    }
+
+    #[test]
+    fn test_client_unlocks_once_when_enabled() {
+        let mut ram = Ram([0; 0x800]);
+        let mut client = AchievementClient::new();
+        // Level counter increased and is now 2
+        client.add(Achievement::new(7, "Level 2", "0xH0010=2_0xH0010>d0xH0010").unwrap());
+
+        ram.0[0x10] = 2;
+        client.evaluate(1, &ram);
+        assert!(!client.is_unlocked(7));
+
+        client.set_enabled(true);
+        ram.0[0x10] = 1;
+        client.evaluate(2, &ram);
+        ram.0[0x10] = 2;
+        client.evaluate(3, &ram);
+        client.evaluate(4, &ram);
+
+        let unlocks = client.take_unlocks();
+        assert_eq!(unlocks, vec![Unlock { id: 7, title: "Level 2".to_string(), frame: 3 }]);
+        assert!(client.is_unlocked(7));
+    }
+}
@@ -0,0 +1,44 @@
+use rnes_common::AudioSample;
+use crate::Bus;
+
+/// Callback receiving one fixed-size chunk of audio samples
+pub type AudioCallback = Box<dyn FnMut(&[AudioSample]) + Send>;
+
+/// Push-style audio delivery in chunks of a fixed size
+///
+/// As soon as the APU has buffered a whole chunk, the samples are copied
+/// into a reused buffer and handed to the callback, so an audio backend gets
+/// exactly one device buffer per call without any allocation.
+pub struct AudioSink {
+    callback: AudioCallback,
+    chunk: Vec<AudioSample>,
+}
+
+impl AudioSink {
+    /// Create a sink delivering `chunk_size` samples per call (at least 1)
+    pub fn new(chunk_size: usize, callback: AudioCallback) -> Self {
+        Self {
+            callback,
+            chunk: vec![0.0; chunk_size.max(1)],
+        }
+    }
+
+    /// Samples per chunk
+    pub fn chunk_size(&self) -> usize {
+        self.chunk.len()
+    }
+
+    /// Deliver every complete chunk buffered in the APU
+    pub(crate) fn deliver(&mut self, bus: &mut Bus) {
+        while bus.audio_available() >= self.chunk.len() {
+            bus.fill_audio(&mut self.chunk);
+            (self.callback)(&self.chunk);
+        }
+    }
+}
+
+impl std::fmt::Debug for AudioSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioSink").field("chunk_size", &self.chunk.len()).finish()
+    }
+}
@@ -1,10 +1,12 @@
-use rnes_common::{Byte, Word, RnesResult, RnesError, RAM_SIZE, MemoryAccess};
+use rnes_common::{Byte, Word, RnesResult, RAM_SIZE, MemoryAccess, RamInitPattern, IrqSource};
 use rnes_cpu6502::Cpu;
 use rnes_cartridge::Cartridge;
 use rnes_ppu::Ppu;
 use rnes_apu::Apu;
+use crate::{AccessKind, IrqController, TimelineEventKind};
 
 /// System bus
+#[derive(Clone)]
 pub struct Bus {
     pub cartridge: Option<Cartridge>,
     pub ppu: Option<Ppu>,
@@ -12,6 +14,35 @@ pub struct Bus {
     pub ram: [Byte; RAM_SIZE],
     pub controller1: rnes_common::ControllerState,
     pub controller2: rnes_common::ControllerState,
+    /// Set when the CPU reads $4016/$4017, cleared at each frame boundary
+    pub input_polled: std::cell::Cell<bool>,
+    /// Controller strobe ($4016 bit 0); while high the shift registers keep reloading
+    pub controller_strobe: bool,
+    /// Controller shift registers, next button in bit 0
+    controller_shift: [std::cell::Cell<Byte>; 2],
+    /// Devices plugged into ports 1 and 2
+    pub port_devices: [rnes_common::InputDevice; 2],
+    /// Controller 1 reads from port 2 and controller 2 from port 1
+    pub swap_ports: bool,
+    /// Zapper state, read through any port it is plugged into
+    pub zapper: crate::Zapper,
+    /// Overrides used when creating the mapper for an inserted cartridge
+    pub mapper_options: rnes_mappers::MapperOptions,
+    /// IRQ sources asserting the CPU's IRQ line
+    irq: IrqController,
+    /// Memory access heat map, recorded while a debugger has one started
+    pub(crate) heat_map: Option<std::cell::RefCell<crate::AccessHeatMap>>,
+    /// Mapper events collected for debugger breakpoints
+    pub(crate) mapper_watch: Option<crate::mapper_events::MapperWatch>,
+    /// Debug event timeline, recorded while a debugger has one started
+    pub(crate) timeline: Option<std::cell::RefCell<crate::EventTimeline>>,
+    /// PPU register write log, recorded while a debugger has one started
+    pub(crate) ppu_write_log: Option<crate::PpuWriteLog>,
+    /// Executed PRG ROM bytes, recorded while coverage is started
+    pub(crate) coverage: Option<crate::CodeCoverage>,
+    /// Bus accesses so far in the instruction being executed, one per CPU
+    /// cycle; `None` outside `step_cpu`
+    cpu_access_cycle: std::cell::Cell<Option<u32>>,
 }
 
 impl Bus {
@@ -24,6 +55,20 @@ impl Bus {
             ram: [0; RAM_SIZE],
             controller1: rnes_common::ControllerState::default(),
             controller2: rnes_common::ControllerState::default(),
+            input_polled: std::cell::Cell::new(false),
+            controller_strobe: false,
+            controller_shift: [std::cell::Cell::new(0), std::cell::Cell::new(0)],
+            port_devices: [rnes_common::InputDevice::Controller; 2],
+            swap_ports: false,
+            zapper: crate::Zapper::new(),
+            mapper_options: rnes_mappers::MapperOptions::default(),
+            irq: IrqController::new(),
+            heat_map: None,
+            mapper_watch: None,
+            timeline: None,
+            ppu_write_log: None,
+            coverage: None,
+            cpu_access_cycle: std::cell::Cell::new(None),
         }
     }
     
@@ -32,25 +77,70 @@ impl Bus {
         self.cartridge = Some(cartridge.clone());
         
         // Create mapper, PPU, and APU
-        let mapper = rnes_mappers::create_mapper(cartridge)?;
+        let mapper = rnes_mappers::create_mapper_with_options(cartridge, &self.mapper_options)?;
         let ppu = Ppu::new(mapper);
         let apu = Apu::new();
         
         self.ppu = Some(ppu);
         self.apu = Some(apu);
+        
+        // Bank counts are sized for the cartridge, so restart any recording
+        if let Some(window_frames) = self.heat_map().map(|heat_map| heat_map.window_frames()) {
+            self.start_heat_map(window_frames);
+        }
+        
         self.reset()?;
         Ok(())
     }
     
     /// Reset system
+    ///
+    /// RAM keeps its contents across a reset, as on hardware; use
+    /// `power_on` to establish the power-up contents.
     pub fn reset(&mut self) -> RnesResult<()> {
-        // Reset RAM
-        self.ram = [0; RAM_SIZE];
+        self.input_polled.set(false);
+        self.irq.clear();
         Ok(())
     }
     
+    /// Fill work RAM, OAM, palette RAM and cartridge RAM with their power-up contents
+    ///
+    /// Each memory gets its own stream derived from `seed`, so `Random`
+    /// is reproducible for a given seed.
+    pub fn power_on(&mut self, pattern: RamInitPattern, seed: u64) {
+        pattern.fill(&mut self.ram, seed);
+        
+        if let Some(ref mut ppu) = self.ppu {
+            let mut oam = [0u8; 256];
+            pattern.fill(&mut oam, seed ^ 0x4F41_4D00);
+            ppu.set_oam(&oam);
+            
+            let mut palette_ram = [0u8; 32];
+            pattern.fill(&mut palette_ram, seed ^ 0x5041_4C00);
+            // Palette entries are 6 bits wide
+            for entry in palette_ram.iter_mut() {
+                *entry &= 0x3F;
+            }
+            ppu.set_palette_ram(&palette_ram);
+            
+            let mut nametable_ram = [0u8; 4096];
+            pattern.fill(&mut nametable_ram, seed ^ 0x4E54_4100);
+            ppu.set_nametable_ram(&nametable_ram);
+            
+            if let Some(chr_ram) = ppu.mapper_mut().get_chr_ram_mut() {
+                pattern.fill(chr_ram, seed ^ 0x4348_5200);
+            }
+            
+            if let Some(prg_ram) = ppu.mapper_mut().get_prg_ram_mut() {
+                pattern.fill(prg_ram, seed ^ 0x5052_4700);
+            }
+        }
+    }
+    
     /// Read byte
     pub fn read_byte(&mut self, addr: Word) -> RnesResult<Byte> {
+        self.record_access(AccessKind::Read, addr);
+        self.next_access_cycle();
         match addr {
             // RAM (0x0000-0x1FFF)
             0x0000..=0x1FFF => {
@@ -60,6 +150,9 @@ impl Bus {
             
             // PPU registers (0x2000-0x2007)
             0x2000..=0x2007 => {
+                if addr == 0x2002 {
+                    self.record_timeline_event(TimelineEventKind::StatusRead);
+                }
                 if let Some(ref mut ppu) = self.ppu {
                     ppu.read_register(addr)
                 } else {
@@ -77,38 +170,16 @@ impl Bus {
             0x4000..=0x401F => {
                 match addr {
                     0x4015 => {
-                        // APU status
-                        if let Some(ref apu) = self.apu {
-                            apu.read_register(addr)
-                        } else {
-                            Ok(0)
-                        }
+                        // APU status, acknowledging the frame IRQ
+                        Ok(self.apu.as_ref().map_or(0, Apu::read_status))
                     }
                     0x4016 => {
-                        // Controller 1 state
-                        let mut value = 0;
-                        if self.controller1.a { value |= 0x01; }
-                        if self.controller1.b { value |= 0x02; }
-                        if self.controller1.select { value |= 0x04; }
-                        if self.controller1.start { value |= 0x08; }
-                        if self.controller1.up { value |= 0x10; }
-                        if self.controller1.down { value |= 0x20; }
-                        if self.controller1.left { value |= 0x40; }
-                        if self.controller1.right { value |= 0x80; }
-                        Ok(value)
+                        // Port 1 state
+                        Ok(self.read_port(0))
                     }
                     0x4017 => {
-                        // Controller 2 state
-                        let mut value = 0;
-                        if self.controller2.a { value |= 0x01; }
-                        if self.controller2.b { value |= 0x02; }
-                        if self.controller2.select { value |= 0x04; }
-                        if self.controller2.start { value |= 0x08; }
-                        if self.controller2.up { value |= 0x10; }
-                        if self.controller2.down { value |= 0x20; }
-                        if self.controller2.left { value |= 0x40; }
-                        if self.controller2.right { value |= 0x80; }
-                        Ok(value)
+                        // Port 2 state
+                        Ok(self.read_port(1))
                     }
                     _ => {
                         // APU registers
@@ -121,23 +192,22 @@ impl Bus {
                 }
             }
             
-            // Cartridge PRG ROM (0x8000-0xFFFF)
-            0x8000..=0xFFFF => {
-                if let Some(ref cartridge) = self.cartridge {
-                    let rom_addr = addr - 0x8000;
-                    cartridge.read_prg_rom(rom_addr)
+            // Cartridge space (0x4020-0xFFFF), decoded by the mapper
+            0x4020..=0xFFFF => {
+                if let Some(ref ppu) = self.ppu {
+                    ppu.mapper().read_prg(addr)
                 } else {
                     // Return 0 if no cartridge (for testing)
                     Ok(0)
                 }
             }
-            
-            _ => Err(RnesError::MemoryAccess { address: addr }),
         }
     }
     
     /// Write byte
     pub fn write_byte(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        self.record_access(AccessKind::Write, addr);
+        let cycle = self.next_access_cycle();
         match addr {
             // RAM (0x0000-0x1FFF)
             0x0000..=0x1FFF => {
@@ -148,8 +218,11 @@ impl Bus {
             
             // PPU registers (0x2000-0x2007)
             0x2000..=0x2007 => {
+                self.record_ppu_write(addr, value, cycle);
                 if let Some(ref mut ppu) = self.ppu {
-                    ppu.write_register(addr, value)
+                    // The PPU catches up after the instruction, so tell it
+                    // how far into the instruction this write happened
+                    ppu.write_register_delayed(addr, value, cycle * 3)
                 } else {
                     tracing::debug!("PPU write: 0x{:04X} = 0x{:02X}", addr, value);
                     Ok(())
@@ -168,6 +241,7 @@ impl Bus {
                 match addr {
                     0x4014 => {
                         // OAM DMA
+                        self.record_ppu_write(addr, value, cycle);
                         if let Some(ref mut ppu) = self.ppu {
                             ppu.start_oam_dma(value);
                         }
@@ -182,8 +256,8 @@ impl Bus {
                         }
                     }
                     0x4016 => {
-                        // Controller status register
-                        tracing::debug!("Controller status write: 0x{:02X}", value);
+                        // Controller strobe
+                        self.write_strobe(value);
                         Ok(())
                     }
                     0x4017 => {
@@ -206,23 +280,22 @@ impl Bus {
                 }
             }
             
-            // Cartridge PRG ROM (0x8000-0xFFFF)
-            0x8000..=0xFFFF => {
-                if let Some(ref mut cartridge) = self.cartridge {
-                    let rom_addr = addr - 0x8000;
-                    cartridge.write_prg_rom(rom_addr, value)
+            // Cartridge space (0x4020-0xFFFF), decoded by the mapper
+            0x4020..=0xFFFF => {
+                if self.mapper_watch.is_some() {
+                    self.write_mapper_watched(addr, value)
+                } else if let Some(ref mut ppu) = self.ppu {
+                    ppu.mapper_mut().write_prg(addr, value)
                 } else {
                     // Ignore write if no cartridge (for testing)
                     Ok(())
                 }
             }
-            
-            _ => Err(RnesError::MemoryAccess { address: addr }),
         }
     }
     
     /// Read word (little-endian)
-    pub fn read_word(&self, addr: Word) -> RnesResult<Word> {
+    pub fn read_word(&mut self, addr: Word) -> RnesResult<Word> {
         let low = self.read_byte(addr)? as Word;
         let high = self.read_byte(addr + 1)? as Word;
         Ok(low | (high << 8))
@@ -245,36 +318,175 @@ impl Bus {
         self.controller2 = state;
     }
     
+    /// Shift the next button out of the controller on `port` (0 or 1)
+    ///
+    /// While the strobe is high every read returns button A. Once it drops,
+    /// reads return A, B, Select, Start, Up, Down, Left, Right, then 1s.
+    pub(crate) fn read_controller(&self, port: usize) -> Byte {
+        let shift = &self.controller_shift[port];
+        if self.controller_strobe {
+            shift.set(self.port_controller(port).bits());
+        }
+        let bits = shift.get();
+        shift.set((bits >> 1) | 0x80);
+        bits & 0x01
+    }
+    
+    /// Write the controller strobe; the shift registers latch the buttons while it is high
+    fn write_strobe(&mut self, value: Byte) {
+        let strobe = value & 0x01 != 0;
+        if strobe || self.controller_strobe {
+            self.controller_shift[0].set(self.port_controller(0).bits());
+            self.controller_shift[1].set(self.port_controller(1).bits());
+        }
+        self.controller_strobe = strobe;
+    }
+    
+    /// Controller strobe and shift registers, for save states
+    pub(crate) fn input_state(&self) -> rnes_common::InputSaveState {
+        rnes_common::InputSaveState {
+            controller_strobe: self.controller_strobe,
+            controller_shift: [self.controller_shift[0].get(), self.controller_shift[1].get()],
+        }
+    }
+    
+    /// Restore the controller strobe and shift registers from a save state
+    pub(crate) fn load_input_state(&mut self, state: &rnes_common::InputSaveState) {
+        self.controller_strobe = state.controller_strobe;
+        self.controller_shift[0].set(state.controller_shift[0]);
+        self.controller_shift[1].set(state.controller_shift[1]);
+    }
+    
+    /// Count a bus access and return the CPU cycle it falls on within the
+    /// current instruction (0 outside `step_cpu`)
+    fn next_access_cycle(&self) -> u32 {
+        match self.cpu_access_cycle.get() {
+            Some(cycle) => {
+                self.cpu_access_cycle.set(Some(cycle + 1));
+                cycle
+            }
+            None => 0,
+        }
+    }
+    
     /// Execute one CPU cycle (requires CPU instance)
     pub fn step_cpu(&mut self, cpu: &mut Cpu) -> RnesResult<rnes_common::Cycles> {
-        let cycles = cpu.step(self)?;
+        self.record_access(AccessKind::Execute, cpu.pc);
+        self.record_coverage(cpu);
+        self.begin_mapper_watch(cpu.pc, cpu.cycles as u64);
+        self.begin_ppu_write_log(cpu.pc);
+        let irq_pending = cpu.irq_pending;
+        self.cpu_access_cycle.set(Some(0));
+        let result = cpu.step(self);
+        self.cpu_access_cycle.set(None);
+        let mut cycles = result?;
+        if irq_pending && !cpu.irq_pending {
+            self.record_timeline_event(TimelineEventKind::IrqAck);
+        }
+        
+        // OAM DMA halts the CPU while it copies a page into OAM
+        let dma_cycles = self.run_oam_dma(cpu.cycles)?;
+        cpu.cycles += dma_cycles;
+        cycles += dma_cycles;
         
         // Step PPU (3x CPU clock)
         if let Some(ref mut ppu) = self.ppu {
             for _ in 0..cycles * 3 {
                 ppu.step()?;
             }
+            // Writes the PPU didn't reach (e.g. during OAM DMA) still happen
+            // before the next instruction
+            ppu.flush_delayed_writes()?;
+            if ppu.take_nmi() {
+                cpu.request_nmi();
+                self.record_timeline_event(TimelineEventKind::Nmi);
+            }
+        }
+        if dma_cycles > 0 {
+            self.record_timeline_event(TimelineEventKind::OamDmaEnd);
         }
+        self.record_ppu_progress();
+        self.record_timeline_progress();
+        self.record_ppu_write_progress();
         
-        // Step APU (1x CPU clock)
+        // Step APU (1x CPU clock), paused with the PPU during overclock
+        // scanlines so audio pitch and frame counter timing are unaffected
+        let overclocking = self.ppu.as_ref().is_some_and(|ppu| ppu.in_overclock());
+        let expansion_audio = self.ppu.as_ref().and_then(|ppu| {
+            let mapper = ppu.mapper();
+            mapper.expansion_chip().map(|chip| (chip, mapper.expansion_audio()))
+        });
+        let dmc_fetches = self.apu.as_ref().map_or(0, Apu::dmc_fetches);
         if let Some(ref mut apu) = self.apu {
-            for _ in 0..cycles {
-                apu.step()?;
+            apu.set_expansion_audio(expansion_audio);
+            if !overclocking {
+                for _ in 0..cycles {
+                    apu.step()?;
+                }
+            }
+        }
+        if self.timeline_enabled() {
+            let fetched = self.apu.as_ref().map_or(0, Apu::dmc_fetches) - dmc_fetches;
+            for _ in 0..fetched {
+                self.record_timeline_event(TimelineEventKind::DmcFetch);
             }
         }
         
-        // Check mapper IRQ
+        // Clock mapper with the CPU (M2)
         if let Some(ref mut ppu) = self.ppu {
             let mapper = ppu.mapper_mut();
-            if mapper.irq_pending() {
-                cpu.request_irq();
-                mapper.clear_irq();
+            for _ in 0..cycles {
+                mapper.clock_cpu_cycle();
             }
         }
+        self.update_irq_line(cpu);
         
         Ok(cycles)
     }
     
+    /// Poll every IRQ source and drive the CPU's IRQ line from them
+    fn update_irq_line(&mut self, cpu: &mut Cpu) {
+        let (frame, dmc) = self.apu.as_ref()
+            .map_or((false, false), |apu| (apu.frame_irq_pending(), apu.dmc_irq_pending()));
+        let mapper = self.ppu.as_ref().is_some_and(|ppu| ppu.mapper().irq_pending());
+        
+        if self.irq.set(IrqSource::ApuFrame, frame) {
+            self.record_timeline_event(TimelineEventKind::FrameIrq);
+        }
+        if self.irq.set(IrqSource::Dmc, dmc) {
+            self.record_timeline_event(TimelineEventKind::DmcIrq);
+        }
+        if self.irq.set(IrqSource::Mapper, mapper) {
+            self.record_mapper_irq();
+            self.record_timeline_event(TimelineEventKind::MapperIrq);
+        }
+        cpu.irq_pending = self.irq.line();
+    }
+    
+    /// Get the IRQ sources asserting the CPU's IRQ line
+    pub fn irq(&self) -> &IrqController {
+        &self.irq
+    }
+    
+    /// Run an OAM DMA started by a $4014 write, returning the CPU cycles it took
+    ///
+    /// The page is copied in one go; the CPU is halted for 513 cycles, plus
+    /// one to align when the DMA starts on an odd cycle.
+    fn run_oam_dma(&mut self, cpu_cycles: rnes_common::Cycles) -> RnesResult<rnes_common::Cycles> {
+        let Some(addr) = self.ppu.as_ref().filter(|ppu| ppu.oam_dma_active()).map(Ppu::oam_dma_addr) else {
+            return Ok(0);
+        };
+        self.record_timeline_event(TimelineEventKind::OamDmaStart);
+        let mut page = [0; 256];
+        for (i, byte) in page.iter_mut().enumerate() {
+            *byte = self.read_byte(addr.wrapping_add(i as Word))?;
+        }
+        if let Some(ref mut ppu) = self.ppu {
+            ppu.complete_oam_dma(&page);
+        }
+        Ok(513 + cpu_cycles % 2)
+    }
+    
     /// Get PPU frame buffer
     pub fn get_ppu_frame_buffer(&self) -> Option<&[rnes_common::Pixel]> {
         self.ppu.as_ref().map(|ppu| ppu.frame_buffer())
@@ -330,11 +542,26 @@ impl Bus {
         self.ppu.as_mut().expect("PPU not initialized").mapper_mut()
     }
     
-    /// Get audio samples from APU
-    pub fn get_audio_samples(&mut self) -> Vec<rnes_common::AudioSample> {
-        self.apu.as_mut()
-            .map(|apu| apu.get_samples())
-            .unwrap_or_default()
+    /// Move buffered APU samples into `out`, returning how many were written
+    pub fn fill_audio(&mut self, out: &mut [rnes_common::AudioSample]) -> usize {
+        self.apu.as_mut().map_or(0, |apu| apu.fill_samples(out))
+    }
+    
+    /// Take all buffered APU samples, oldest first
+    pub fn drain_audio(&mut self) -> impl Iterator<Item = rnes_common::AudioSample> + '_ {
+        self.apu.iter_mut().flat_map(|apu| apu.drain_samples())
+    }
+    
+    /// Number of buffered APU samples
+    pub fn audio_available(&self) -> usize {
+        self.apu.as_ref().map_or(0, |apu| apu.samples_available())
+    }
+    
+    /// Discard buffered APU samples
+    pub fn clear_audio(&mut self) {
+        if let Some(ref mut apu) = self.apu {
+            apu.clear_samples();
+        }
     }
     
     /// Check if DMC IRQ is pending
@@ -343,49 +570,11 @@ impl Bus {
             .map(|apu| apu.dmc_irq_pending())
             .unwrap_or(false)
     }
-    
-    /// Clear DMC IRQ
-    pub fn clear_dmc_irq(&mut self) {
-        if let Some(ref mut apu) = self.apu {
-            apu.clear_dmc_irq();
-        }
-    }
 }
 
 impl MemoryAccess for Bus {
-    fn read_byte(&self, addr: Word) -> RnesResult<Byte> {
-        // For now, we'll need to restructure this to avoid the mutable requirement
-        // This is a temporary workaround
-        match addr {
-            0x0000..=0x1FFF => {
-                let ram_addr = (addr & 0x07FF) as usize;
-                Ok(self.ram[ram_addr])
-            }
-            0x2000..=0x2007 => {
-                // PPU registers - return 0 for now to avoid mutable issues
-                // In a real implementation, this would need to be handled differently
-                match addr {
-                    0x2002 => {
-                        // PPUSTATUS - return a value that indicates VBlank is not set
-                        Ok(0x00)
-                    }
-                    _ => Ok(0)
-                }
-            }
-            0x2008..=0x3FFF => {
-                let ram_addr = (addr & 0x07FF) as usize;
-                Ok(self.ram[ram_addr])
-            }
-            0x8000..=0xFFFF => {
-                if let Some(ref cartridge) = self.cartridge {
-                    let rom_addr = addr - 0x8000;
-                    cartridge.read_prg_rom(rom_addr)
-                } else {
-                    Ok(0)
-                }
-            }
-            _ => Err(RnesError::MemoryAccess { address: addr })
-        }
+    fn read_byte(&mut self, addr: Word) -> RnesResult<Byte> {
+        self.read_byte(addr)
     }
     
     fn write_byte(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
@@ -407,6 +596,11 @@ impl std::fmt::Debug for Bus {
             .field("ram", &"[...]")
             .field("controller1", &self.controller1)
             .field("controller2", &self.controller2)
+            .field("input_polled", &self.input_polled.get())
+            .field("heat_map", &self.heat_map.is_some())
+            .field("mapper_watch", &self.mapper_watch.is_some())
+            .field("timeline", &self.timeline.is_some())
+            .field("ppu_write_log", &self.ppu_write_log.is_some())
             .finish()
     }
 }
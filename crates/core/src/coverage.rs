@@ -0,0 +1,234 @@
+use std::ops::Range;
+use serde::{Deserialize, Serialize};
+use rnes_common::{RnesError, RnesResult, Word};
+use rnes_cpu6502::Cpu;
+use crate::{Bus, MemoryPeek, HEAT_MAP_PRG_BANK_SIZE};
+
+/// PRG ROM bank granularity of coverage reports, the same as the heat map's
+pub const COVERAGE_BANK_SIZE: usize = HEAT_MAP_PRG_BANK_SIZE;
+
+/// Executed bytes in one bank of PRG ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankCoverage {
+    pub bank: usize,
+    /// Bytes executed as opcodes or operands
+    pub executed: usize,
+    /// Size of the bank in bytes
+    pub size: usize,
+}
+
+/// Bitmap of the PRG ROM bytes executed during a run
+///
+/// Every byte of an executed instruction is marked, opcode and operands,
+/// by its offset in PRG ROM, so the map stays meaningful however the mapper
+/// banks it in. Coverage of separate runs can be merged, or diffed to find
+/// the code one play session reached and another didn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeCoverage {
+    prg_rom_size: usize,
+    bits: Vec<u8>,
+}
+
+impl CodeCoverage {
+    /// Create empty coverage of a `prg_rom_size` byte PRG ROM
+    pub fn new(prg_rom_size: usize) -> Self {
+        Self {
+            prg_rom_size,
+            bits: vec![0; prg_rom_size.div_ceil(8)],
+        }
+    }
+
+    /// Size of the covered PRG ROM in bytes
+    pub fn prg_rom_size(&self) -> usize {
+        self.prg_rom_size
+    }
+
+    /// Mark the byte at PRG ROM `offset` as executed
+    pub fn mark(&mut self, offset: usize) {
+        if offset < self.prg_rom_size {
+            self.bits[offset / 8] |= 1 << (offset % 8);
+        }
+    }
+
+    /// Check if the byte at PRG ROM `offset` was executed
+    pub fn is_executed(&self, offset: usize) -> bool {
+        offset < self.prg_rom_size && self.bits[offset / 8] & (1 << (offset % 8)) != 0
+    }
+
+    /// Number of executed bytes
+    pub fn executed(&self) -> usize {
+        self.bits.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    /// Executed bytes per `COVERAGE_BANK_SIZE` bank
+    pub fn banks(&self) -> Vec<BankCoverage> {
+        (0..self.prg_rom_size.div_ceil(COVERAGE_BANK_SIZE))
+            .map(|bank| {
+                let range = self.bank_range(bank);
+                BankCoverage {
+                    bank,
+                    executed: range.clone().filter(|&offset| self.is_executed(offset)).count(),
+                    size: range.len(),
+                }
+            })
+            .collect()
+    }
+
+    /// Runs of consecutive executed offsets, in order
+    pub fn ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for offset in 0..=self.prg_rom_size {
+            match (start, self.is_executed(offset)) {
+                (None, true) => start = Some(offset),
+                (Some(first), false) => {
+                    ranges.push(first..offset);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+
+    /// Add the bytes executed in `other`, a run of the same ROM
+    pub fn merge(&mut self, other: &CodeCoverage) -> RnesResult<()> {
+        self.check_same_rom(other)?;
+        for (bits, other) in self.bits.iter_mut().zip(&other.bits) {
+            *bits |= other;
+        }
+        Ok(())
+    }
+
+    /// Bytes executed here but not in `other`, a run of the same ROM
+    pub fn difference(&self, other: &CodeCoverage) -> RnesResult<CodeCoverage> {
+        self.check_same_rom(other)?;
+        let bits = self.bits.iter().zip(&other.bits).map(|(bits, other)| bits & !other).collect();
+        Ok(Self { prg_rom_size: self.prg_rom_size, bits })
+    }
+
+    /// Export as JSON, with a hex bitmap per bank
+    ///
+    /// Bit `n` of a bank's bitmap (least significant bit of each byte first)
+    /// is offset `n` within the bank.
+    pub fn to_json(&self) -> RnesResult<String> {
+        let report = CoverageReport {
+            prg_rom_size: self.prg_rom_size,
+            bank_size: COVERAGE_BANK_SIZE,
+            executed: self.executed(),
+            banks: self.banks().iter().map(|bank| BankReport {
+                bank: bank.bank,
+                executed: bank.executed,
+                bitmap: self.bank_bits(bank.bank).iter().map(|byte| format!("{:02x}", byte)).collect(),
+            }).collect(),
+        };
+        serde_json::to_string_pretty(&report).map_err(|e| RnesError::Serialization(e.to_string()))
+    }
+
+    /// Import coverage exported by `to_json`, e.g. to diff against a new run
+    pub fn from_json(json: &str) -> RnesResult<Self> {
+        let invalid = |message: String| RnesError::Serialization(format!("Invalid coverage: {}", message));
+        let report: CoverageReport = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        if report.bank_size != COVERAGE_BANK_SIZE {
+            return Err(invalid(format!("bank size {} is not {}", report.bank_size, COVERAGE_BANK_SIZE)));
+        }
+
+        let mut coverage = Self::new(report.prg_rom_size);
+        for bank in &report.banks {
+            let range = coverage.bank_range(bank.bank);
+            if range.is_empty() || bank.bitmap.len() != range.len().div_ceil(8) * 2 {
+                return Err(invalid(format!("bank {} does not fit the PRG ROM", bank.bank)));
+            }
+            for (i, pair) in bank.bitmap.as_bytes().chunks(2).enumerate() {
+                let hex = std::str::from_utf8(pair).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                let byte = hex.ok_or_else(|| invalid(format!("bank {} bitmap is not hex", bank.bank)))?;
+                coverage.bits[range.start / 8 + i] = byte;
+            }
+        }
+        Ok(coverage)
+    }
+
+    fn bank_range(&self, bank: usize) -> Range<usize> {
+        let start = (bank * COVERAGE_BANK_SIZE).min(self.prg_rom_size);
+        start..(start + COVERAGE_BANK_SIZE).min(self.prg_rom_size)
+    }
+
+    fn bank_bits(&self, bank: usize) -> &[u8] {
+        let range = self.bank_range(bank);
+        &self.bits[range.start / 8..range.end.div_ceil(8)]
+    }
+
+    fn check_same_rom(&self, other: &CodeCoverage) -> RnesResult<()> {
+        if self.prg_rom_size != other.prg_rom_size {
+            return Err(RnesError::Serialization(format!(
+                "Coverage of a {} byte PRG ROM can't be combined with one of {} bytes",
+                self.prg_rom_size, other.prg_rom_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// JSON layout of `CodeCoverage`
+#[derive(Serialize, Deserialize)]
+struct CoverageReport {
+    prg_rom_size: usize,
+    bank_size: usize,
+    executed: usize,
+    banks: Vec<BankReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BankReport {
+    bank: usize,
+    executed: usize,
+    bitmap: String,
+}
+
+impl Bus {
+    /// Start recording code coverage, replacing any previous recording
+    pub fn start_coverage(&mut self) {
+        let prg_rom_size = self.cartridge.as_ref().map_or(0, |c| c.prg_rom.len());
+        self.coverage = Some(CodeCoverage::new(prg_rom_size));
+    }
+
+    /// Stop recording and return the coverage
+    pub fn stop_coverage(&mut self) -> Option<CodeCoverage> {
+        self.coverage.take()
+    }
+
+    /// Get the coverage being recorded, if any
+    pub fn coverage(&self) -> Option<&CodeCoverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Mark the instruction `cpu` is about to run, if recording coverage
+    pub(crate) fn record_coverage(&mut self, cpu: &Cpu) {
+        if self.coverage.is_none() || cpu.jammed || cpu.stall_cycles > 0 {
+            return;
+        }
+        // A pending interrupt is taken first and its handler's first
+        // instruction runs in the same step
+        let pc = if cpu.nmi_pending {
+            self.peek_word(0xFFFA)
+        } else if cpu.irq_pending && !cpu.status.interrupts_disabled() {
+            self.peek_word(0xFFFE)
+        } else {
+            cpu.pc
+        };
+
+        let length = rnes_cpu6502::instruction_length(self.peek(pc));
+        let (Some(coverage), Some(ppu)) = (self.coverage.as_mut(), self.ppu.as_ref()) else {
+            return;
+        };
+        for addr in (0..length as Word).map(|i| pc.wrapping_add(i)) {
+            if let Some(offset) = ppu.mapper().prg_rom_offset(addr) {
+                coverage.mark(offset);
+            }
+        }
+    }
+
+    fn peek_word(&self, addr: Word) -> Word {
+        u16::from_le_bytes([self.peek(addr), self.peek(addr.wrapping_add(1))])
+    }
+}
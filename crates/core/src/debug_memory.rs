@@ -0,0 +1,218 @@
+use rnes_common::{Byte, Word, RnesError, RnesResult};
+use crate::{Bus, MemoryPeek};
+
+/// Memory a debugger's hex editor can view and edit
+///
+/// `Cpu` and `Ppu` are address spaces as seen by those chips; the other
+/// regions are the raw memories behind them, indexed from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryRegion {
+    /// CPU address space ($0000-$FFFF); registers read as 0 and cannot be written
+    Cpu,
+    /// PPU address space ($0000-$3FFF): pattern tables, nametables, palette
+    Ppu,
+    /// Work RAM (2KB)
+    WorkRam,
+    /// Nametable RAM (CIRAM, 4KB including four-screen RAM)
+    NametableRam,
+    /// Sprite OAM (256 bytes)
+    Oam,
+    /// Palette RAM (32 bytes)
+    Palette,
+    /// Cartridge PRG RAM
+    PrgRam,
+    /// Cartridge CHR RAM
+    ChrRam,
+    /// PRG ROM (read-only)
+    PrgRom,
+    /// CHR ROM (read-only)
+    ChrRom,
+}
+
+impl MemoryRegion {
+    /// All regions, in display order
+    pub const ALL: [MemoryRegion; 10] = [
+        MemoryRegion::Cpu,
+        MemoryRegion::Ppu,
+        MemoryRegion::WorkRam,
+        MemoryRegion::NametableRam,
+        MemoryRegion::Oam,
+        MemoryRegion::Palette,
+        MemoryRegion::PrgRam,
+        MemoryRegion::ChrRam,
+        MemoryRegion::PrgRom,
+        MemoryRegion::ChrRom,
+    ];
+
+    /// Get display name
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryRegion::Cpu => "CPU",
+            MemoryRegion::Ppu => "PPU",
+            MemoryRegion::WorkRam => "Work RAM",
+            MemoryRegion::NametableRam => "Nametable RAM",
+            MemoryRegion::Oam => "OAM",
+            MemoryRegion::Palette => "Palette",
+            MemoryRegion::PrgRam => "PRG RAM",
+            MemoryRegion::ChrRam => "CHR RAM",
+            MemoryRegion::PrgRom => "PRG ROM",
+            MemoryRegion::ChrRom => "CHR ROM",
+        }
+    }
+
+    /// Check if the region can be written
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, MemoryRegion::PrgRom | MemoryRegion::ChrRom)
+    }
+}
+
+impl std::fmt::Display for MemoryRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Bus {
+    /// Get the size of a memory region in bytes (0 if absent)
+    pub fn memory_size(&self, region: MemoryRegion) -> usize {
+        match region {
+            MemoryRegion::Cpu => 0x10000,
+            MemoryRegion::Ppu => 0x4000,
+            MemoryRegion::WorkRam => self.ram.len(),
+            MemoryRegion::NametableRam => self.ppu.as_ref().map_or(0, |ppu| ppu.nametable_ram().len()),
+            MemoryRegion::Oam => self.ppu.as_ref().map_or(0, |ppu| ppu.oam().len()),
+            MemoryRegion::Palette => self.ppu.as_ref().map_or(0, |ppu| ppu.palette_ram().len()),
+            MemoryRegion::PrgRam => self.ppu.as_ref()
+                .and_then(|ppu| ppu.mapper().get_prg_ram())
+                .map_or(0, |ram| ram.len()),
+            MemoryRegion::ChrRam => self.ppu.as_ref()
+                .and_then(|ppu| ppu.mapper().get_chr_ram())
+                .map_or(0, |ram| ram.len()),
+            MemoryRegion::PrgRom => self.cartridge.as_ref().map_or(0, |c| c.prg_rom.len()),
+            MemoryRegion::ChrRom => self.cartridge.as_ref()
+                .filter(|c| !c.chr_is_ram)
+                .map_or(0, |c| c.chr_rom.len()),
+        }
+    }
+
+    /// Read `len` bytes of a memory region without side effects
+    pub fn read_memory(&mut self, region: MemoryRegion, offset: usize, len: usize) -> RnesResult<Vec<Byte>> {
+        self.check_memory_range(region, offset, len)?;
+        let range = offset..offset + len;
+        let bytes = match region {
+            MemoryRegion::Cpu => {
+                let mut buffer = vec![0; len];
+                self.peek_range(offset as Word, &mut buffer);
+                buffer
+            }
+            MemoryRegion::Ppu => {
+                let ppu = self.ppu_mut();
+                range.map(|addr| ppu.peek_vram(addr as Word)).collect()
+            }
+            MemoryRegion::WorkRam => self.ram[range].to_vec(),
+            MemoryRegion::NametableRam => self.ppu().nametable_ram()[range].to_vec(),
+            MemoryRegion::Oam => self.ppu().oam()[range].to_vec(),
+            MemoryRegion::Palette => self.ppu().palette_ram()[range].to_vec(),
+            MemoryRegion::PrgRam => self.mapper().get_prg_ram().map(|ram| ram[range].to_vec()).unwrap_or_default(),
+            MemoryRegion::ChrRam => self.mapper().get_chr_ram().map(|ram| ram[range].to_vec()).unwrap_or_default(),
+            MemoryRegion::PrgRom => self.cartridge.as_ref().map(|c| c.prg_rom[range].to_vec()).unwrap_or_default(),
+            MemoryRegion::ChrRom => self.cartridge.as_ref().map(|c| c.chr_rom[range].to_vec()).unwrap_or_default(),
+        };
+        Ok(bytes)
+    }
+
+    /// Write bytes into a memory region without side effects
+    ///
+    /// CPU-space writes reach work RAM and cartridge RAM ($6000-$7FFF) only;
+    /// registers and ROM are rejected instead of triggering I/O or bank
+    /// switches.
+    pub fn write_memory(&mut self, region: MemoryRegion, offset: usize, data: &[Byte]) -> RnesResult<()> {
+        if !region.is_writable() {
+            return Err(RnesError::ReadOnlyMemory(region.to_string()));
+        }
+        self.check_memory_range(region, offset, data.len())?;
+        let range = offset..offset + data.len();
+        match region {
+            MemoryRegion::Cpu => {
+                for (addr, &value) in range.zip(data) {
+                    self.poke(addr as Word, value)?;
+                }
+            }
+            MemoryRegion::Ppu => {
+                let ppu = self.ppu_mut();
+                for (addr, &value) in range.zip(data) {
+                    ppu.poke_vram(addr as Word, value)?;
+                }
+            }
+            MemoryRegion::WorkRam => self.ram[range].copy_from_slice(data),
+            MemoryRegion::NametableRam => self.ppu_mut().nametable_ram_mut()[range].copy_from_slice(data),
+            MemoryRegion::Oam => self.ppu_mut().oam_mut()[range].copy_from_slice(data),
+            MemoryRegion::Palette => self.ppu_mut().palette_ram_mut()[range].copy_from_slice(data),
+            MemoryRegion::PrgRam => {
+                if let Some(ram) = self.mapper_mut().get_prg_ram_mut() {
+                    ram[range].copy_from_slice(data);
+                }
+            }
+            MemoryRegion::ChrRam => {
+                if let Some(ram) = self.mapper_mut().get_chr_ram_mut() {
+                    ram[range].copy_from_slice(data);
+                }
+                self.ppu_mut().clear_vram_cache();
+            }
+            MemoryRegion::PrgRom | MemoryRegion::ChrRom => unreachable!("read-only regions are rejected above"),
+        }
+        Ok(())
+    }
+
+    /// Patch code at a CPU address, e.g. from the debugger's assembler
+    ///
+    /// Unlike `write_memory`, addresses in $8000-$FFFF patch the PRG ROM
+    /// bank currently mapped there. Patches last until the ROM is reloaded
+    /// or the console is power cycled.
+    pub fn patch_code(&mut self, addr: Word, data: &[Byte]) -> RnesResult<()> {
+        for (i, &value) in data.iter().enumerate() {
+            let addr = addr.checked_add(i as Word).ok_or(RnesError::MemoryAccess { address: 0xFFFF })?;
+            if addr < 0x8000 {
+                self.poke(addr, value)?;
+                continue;
+            }
+            let offset = self.ppu.as_ref()
+                .and_then(|ppu| ppu.mapper().prg_rom_offset(addr))
+                .ok_or(RnesError::MemoryAccess { address: addr })?;
+            let rom = self.mapper_mut().prg_rom_mut()
+                .ok_or_else(|| RnesError::ReadOnlyMemory(MemoryRegion::PrgRom.to_string()))?;
+            rom[offset] = value;
+            // Keep the PRG ROM view in step with the mapper's copy
+            if let Some(ref mut cartridge) = self.cartridge {
+                cartridge.prg_rom[offset] = value;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write one CPU address without side effects
+    fn poke(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram[(addr & 0x07FF) as usize] = value;
+                Ok(())
+            }
+            // Cartridge RAM; mappers treat writes here as plain memory
+            0x6000..=0x7FFF if self.ppu.is_some() => self.mapper_mut().write_prg(addr, value),
+            _ => Err(RnesError::MemoryAccess { address: addr }),
+        }
+    }
+
+    fn check_memory_range(&self, region: MemoryRegion, offset: usize, len: usize) -> RnesResult<()> {
+        let size = self.memory_size(region);
+        let needs_ppu = !matches!(region, MemoryRegion::Cpu | MemoryRegion::WorkRam);
+        if (needs_ppu && self.ppu.is_none()) || offset.checked_add(len).is_none_or(|end| end > size) {
+            return Err(RnesError::MemoryRange {
+                region: region.to_string(),
+                offset,
+                len,
+            });
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
-use rnes_common::{RnesResult, EmulatorState, SaveSystem, SaveState, Config, Debugger, DebugInfo, CpuRegisters, StatusFlagsDebug, PpuDebugState, PpuRegistersDebug, MemoryAccess};
-use crate::Bus;
+use rnes_common::{RnesResult, RnesError, RamInitPattern, RnesWarning, WarningSink, RomId, EmulatorState, Region, SaveSystem, SaveState, SaveSlotInfo, Thumbnail, SCREEN_WIDTH, SCREEN_HEIGHT, Config, ExpansionAudioConfig, ExpansionChip, Debugger, InterruptEvent, InterruptKind, RamAnnotations, DebugInfo, CpuRegisters, StatusFlagsDebug, PpuDebugState, PpuRegistersDebug, InterruptDebugState};
+use crate::{AccessHeatMap, CodeCoverage, AudioSink, Bus, CrashReason, EventTimeline, PpuWriteLog, GameCrash, InputQueue, CycleProfiler, ProfiledStep, Watchdog, FrameHooks, MemoryPeek, MemoryRegion, Overlay, OverlayStatus, ReverseHistory, StemRecorder, TraceLogger};
 use rnes_cartridge::Cartridge;
 
 /// NES Emulator
@@ -11,10 +11,38 @@ pub struct Emulator {
     pub running: bool,
     pub save_system: SaveSystem,
     pub rom_name: Option<String>,
+    pub region: Region,
     pub config: Config,
     pub debugger: Debugger,
-    pub auto_save_timer: u32,
-    pub last_auto_save: std::time::Instant,
+    /// Time of the first battery RAM write not yet saved
+    pub battery_dirty_since: Option<std::time::Instant>,
+    /// Time the most recent battery RAM write was seen
+    pub last_battery_write: std::time::Instant,
+    pub hooks: FrameHooks,
+    pub overlay: Overlay,
+    /// Snapshots for reverse stepping, when enabled
+    pub reverse: Option<ReverseHistory>,
+    /// Instruction trace, when enabled
+    pub trace: Option<TraceLogger>,
+    /// Per-frame CPU budget, when enabled
+    pub profiler: Option<CycleProfiler>,
+    /// Per-channel WAV stems, while recording
+    pub stems: Option<StemRecorder>,
+    /// Chunked audio delivery, when a callback is registered
+    pub audio_sink: Option<AudioSink>,
+    /// Crash and hang detection
+    pub watchdog: Watchdog,
+    /// Non-fatal problems for the frontend to show
+    pub warnings: WarningSink,
+    /// Controller input waiting to be latched at a frame boundary
+    pub input: InputQueue,
+    /// No input or state load since power on, so the machine is in a plain boot
+    pub(crate) boot_pristine: bool,
+    /// RAM pattern and seed restored from a save state, used instead of the
+    /// config's for power cycles until another ROM is loaded
+    pub(crate) power_on_override: Option<(RamInitPattern, u64)>,
+    #[cfg(feature = "achievements")]
+    pub achievements: crate::AchievementClient,
 }
 
 impl Emulator {
@@ -26,12 +54,27 @@ impl Emulator {
             cpu: rnes_cpu6502::Cpu::new(),
             state: EmulatorState::default(),
             running: false,
-            save_system: SaveSystem::new(),
+            save_system: SaveSystem::from_config(&config.storage),
             rom_name: None,
+            region: Region::default(),
+            debugger: Debugger::from_config(&config.debug),
             config,
-            debugger: Debugger::new(),
-            auto_save_timer: 0,
-            last_auto_save: std::time::Instant::now(),
+            battery_dirty_since: None,
+            last_battery_write: std::time::Instant::now(),
+            hooks: FrameHooks::new(),
+            overlay: Overlay::new(),
+            reverse: None,
+            trace: None,
+            profiler: None,
+            stems: None,
+            audio_sink: None,
+            watchdog: Watchdog::new(),
+            warnings: WarningSink::new(),
+            input: InputQueue::new(),
+            boot_pristine: false,
+            power_on_override: None,
+            #[cfg(feature = "achievements")]
+            achievements: crate::AchievementClient::new(),
         }
     }
     
@@ -42,39 +85,257 @@ impl Emulator {
             cpu: rnes_cpu6502::Cpu::new(),
             state: EmulatorState::default(),
             running: false,
-            save_system: SaveSystem::new(),
+            save_system: SaveSystem::from_config(&config.storage),
             rom_name: None,
+            region: Region::default(),
+            debugger: Debugger::from_config(&config.debug),
             config,
-            debugger: Debugger::new(),
-            auto_save_timer: 0,
-            last_auto_save: std::time::Instant::now(),
+            battery_dirty_since: None,
+            last_battery_write: std::time::Instant::now(),
+            hooks: FrameHooks::new(),
+            overlay: Overlay::new(),
+            reverse: None,
+            trace: None,
+            profiler: None,
+            stems: None,
+            audio_sink: None,
+            watchdog: Watchdog::new(),
+            warnings: WarningSink::new(),
+            input: InputQueue::new(),
+            boot_pristine: false,
+            power_on_override: None,
+            #[cfg(feature = "achievements")]
+            achievements: crate::AchievementClient::new(),
         }
     }
     
+    /// Replace the save system, e.g. to use a custom storage backend
+    pub fn with_save_system(mut self, save_system: SaveSystem) -> Self {
+        self.save_system = save_system;
+        self
+    }
+    
+    /// Keep this instance's saves in their own directory
+    ///
+    /// Instances sharing a process each need one, or their battery saves
+    /// and save state slots for the same ROM will overwrite each other.
+    pub fn with_save_dir<P: AsRef<std::path::Path>>(mut self, save_dir: P) -> Self {
+        self.config.storage.save_dir = save_dir.as_ref().to_path_buf();
+        self.save_system = SaveSystem::from_config(&self.config.storage);
+        self
+    }
+    
     /// Load ROM
     pub fn load_rom(&mut self, cartridge: Cartridge) -> RnesResult<()> {
-        // Extract ROM name from cartridge
-        let rom_name = cartridge.header.magic.iter().map(|&b| b as char).collect::<String>();
+        // Save the outgoing game's battery RAM before it is replaced
+        self.flush_battery_backup()?;
+        self.battery_dirty_since = None;
+        
+        // Saves are named after the ROM file, like other emulators do
+        let rom_name = cartridge.file_stem()
+            .unwrap_or_else(|| cartridge.rom_id.to_string());
         self.rom_name = Some(rom_name.clone());
+        self.region = cartridge.region();
+        tracing::info!("ROM region: {}", self.region);
+        
+        let rom_dir = cartridge.file_path.as_ref()
+            .and_then(|path| path.parent())
+            .map(|dir| dir.to_path_buf());
+        if self.config.storage.sav_beside_rom {
+            let battery_dir = rom_dir.clone().or_else(|| self.config.storage.battery_dir.clone());
+            self.save_system.set_battery_dir(battery_dir.as_deref());
+        }
         
+        self.warnings.clear();
+        self.input.clear();
+        self.warn_unsupported_header_features(&cartridge);
+        
+        self.bus.mapper_options.mmc3_irq_revision = self.config.general.mmc3_irq_revision;
+        self.power_on_override = None;
         self.bus.insert_cartridge(cartridge)?;
+        self.power_on_hardware();
         
-        // Load battery backup if available
+        // Load battery backup if available, falling back to a .sav next to
+        // the ROM left by another emulator
         let mapper = self.bus.mapper_mut();
         if mapper.has_battery() {
-            if let Ok(data) = self.save_system.load_battery_backup(&rom_name) {
-                if !data.is_empty() {
-                    mapper.load_prg_ram(&data)?;
-                    tracing::info!("Loaded battery backup for ROM: {}", rom_name);
+            let mut data = self.save_system.load_battery_backup(&rom_name).unwrap_or_default();
+            if data.is_empty() {
+                if let Some(ref dir) = rom_dir {
+                    data = std::fs::read(dir.join(format!("{}.sav", rom_name))).unwrap_or_default();
+                }
+            }
+            if !data.is_empty() {
+                // Other emulators pad or trim saves; load what fits
+                let size = mapper.get_prg_ram().map_or(0, |ram| ram.len());
+                if data.len() != size {
+                    self.warnings.warn(RnesWarning::PrgRamSizeMismatch { expected: size, actual: data.len() });
+                    data.truncate(size);
                 }
+                mapper.load_prg_ram(&data)?;
+                tracing::info!("Loaded battery backup for ROM: {}", rom_name);
             }
         }
         
+        // A RAM map next to the ROM names the game's variables for the debugger
+        self.debugger.annotations = rom_dir
+            .map(|dir| dir.join(format!("{}.rammap", rom_name)))
+            .filter(|path| path.exists())
+            .and_then(|path| RamAnnotations::load_from_file(&path)
+                .inspect_err(|e| tracing::warn!("Failed to load RAM map {:?}: {}", path, e))
+                .ok())
+            .unwrap_or_default();
+        
         self.reset()?;
         Ok(())
     }
     
-    /// Reset emulator
+    /// Warn about header features that are loaded but not emulated
+    fn warn_unsupported_header_features(&mut self, cartridge: &Cartridge) {
+        let info = cartridge.info();
+        if info.trainer {
+            self.warnings.warn(RnesWarning::UnsupportedFeature {
+                component: "cartridge loader",
+                feature: "512-byte trainers".to_string(),
+            });
+        }
+        if let Some(nes2) = info.nes2 {
+            let console = match nes2.console_type {
+                1 => Some("Vs. System"),
+                2 => Some("PlayChoice-10"),
+                3 => Some("extended console types"),
+                _ => None,
+            };
+            if let Some(console) = console {
+                self.warnings.warn(RnesWarning::UnsupportedFeature {
+                    component: "emulator",
+                    feature: console.to_string(),
+                });
+            }
+        }
+    }
+    
+    /// Take warnings recorded since the last call, including the mapper's
+    ///
+    /// Frontends call this once per frame and show the result; emulation
+    /// continues regardless.
+    pub fn take_warnings(&mut self) -> Vec<RnesWarning> {
+        if self.bus.ppu.is_some() {
+            for warning in self.bus.mapper_mut().take_warnings() {
+                self.warnings.warn(warning);
+            }
+        }
+        self.warnings.take()
+    }
+    
+    /// Seed driving nondeterministic power-on state
+    ///
+    /// Save states carry it, so replaying from one powers on the same way.
+    pub fn seed(&self) -> u64 {
+        self.power_on_settings().1
+    }
+    
+    /// Set the seed; it takes effect at the next power on
+    pub fn set_seed(&mut self, seed: u64) {
+        self.config.general.ram_init_seed = seed;
+        if let Some((_, ref mut restored)) = self.power_on_override {
+            *restored = seed;
+        }
+    }
+    
+    /// RAM pattern and seed the next power on uses
+    ///
+    /// These come from the config, unless a save state for the loaded game
+    /// has been restored since it was loaded.
+    fn power_on_settings(&self) -> (RamInitPattern, u64) {
+        self.power_on_override
+            .unwrap_or((self.config.general.ram_init, self.config.general.ram_init_seed))
+    }
+    
+    /// Apply power-on RAM contents and configuration to freshly created hardware
+    fn power_on_hardware(&mut self) {
+        self.boot_pristine = true;
+        self.relatch_input();
+        self.bus.port_devices = self.config.input.port_devices;
+        self.bus.swap_ports = self.config.input.swap_ports;
+        let (ram_init, seed) = self.power_on_settings();
+        self.bus.power_on(ram_init, seed);
+        self.bus.apu_mut().set_master_volume(self.config.audio.master_volume);
+        self.bus.apu_mut().set_silence_ultrasonic_triangle(self.config.audio.silence_ultrasonic_triangle);
+        for chip in ExpansionChip::ALL {
+            let expansion = self.config.audio.expansion(chip);
+            self.bus.apu_mut().set_expansion_enabled(chip, expansion.enabled);
+            self.bus.apu_mut().set_expansion_gain(chip, expansion.gain);
+        }
+        let recording_stems = self.stems.is_some();
+        self.bus.apu_mut().set_visualizer_enabled(recording_stems);
+        self.bus.apu_mut().set_region(self.region);
+        let overclock = self.overclock_scanlines();
+        self.bus.ppu_mut().set_overclock_scanlines(overclock);
+        self.bus.ppu_mut().set_layer_visibility(self.debugger.layers);
+        self.bus.ppu_mut().set_region(self.region);
+        self.bus.ppu_mut().set_sprite_limit(!self.config.video.remove_sprite_limit);
+    }
+    
+    /// Press the console's reset button
+    ///
+    /// Work RAM, OAM, palette RAM, cartridge state and the frame timing are
+    /// kept. The CPU runs its 7-cycle reset sequence with registers intact,
+    /// the PPU clears its control registers and the APU is silenced.
+    pub fn soft_reset(&mut self) -> RnesResult<()> {
+        self.bus.reset()?;
+        if let Some(ref mut ppu) = self.bus.ppu {
+            ppu.reset();
+        }
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.reset();
+        }
+        self.cpu.soft_reset(&mut self.bus)?;
+        self.watchdog.clear();
+        self.debugger.clear_history();
+        self.debugger.break_next = self.debugger.pause_on_start;
+        if let Some(ref mut history) = self.reverse {
+            history.clear();
+        }
+        
+        tracing::info!("Soft reset");
+        Ok(())
+    }
+    
+    /// Turn the console off and on again
+    ///
+    /// The mapper, PPU and APU are rebuilt from the loaded cartridge and RAM
+    /// is refilled with the configured power-on pattern. Battery-backed RAM
+    /// survives, as it would on a real cartridge.
+    pub fn power_cycle(&mut self) -> RnesResult<()> {
+        let cartridge = self.bus.cartridge.clone()
+            .ok_or(RnesError::NoRomLoaded)?;
+        let mapper = self.bus.mapper();
+        let battery_ram = if mapper.has_battery() {
+            mapper.get_prg_ram().map(|ram| ram.to_vec())
+        } else {
+            None
+        };
+        
+        self.bus.insert_cartridge(cartridge)?;
+        self.power_on_hardware();
+        if let Some(data) = battery_ram {
+            self.bus.mapper_mut().load_prg_ram(&data)?;
+        }
+        
+        let running = self.running;
+        self.reset()?;
+        self.running = running;
+        
+        tracing::info!("Power cycle");
+        Ok(())
+    }
+    
+    /// Restart the loaded program from its reset vector, paused
+    ///
+    /// The CPU gets its power-up registers but memory and the PPU, APU and
+    /// mapper are left as they are. This is how a freshly loaded ROM starts;
+    /// use `soft_reset` or `power_cycle` for the console's buttons.
     pub fn reset(&mut self) -> RnesResult<()> {
         self.bus.reset()?;
         self.cpu.reset(&mut self.bus)?;
@@ -82,12 +343,22 @@ impl Emulator {
         // Note: Removed the 8-cycle delay as it was causing PC to be modified incorrectly
         
         self.state = EmulatorState::default();
+        if let Some(ref ppu) = self.bus.ppu {
+            self.state.frame_count = ppu.frame_count();
+        }
         self.running = false;
-        self.auto_save_timer = 0;
-        self.last_auto_save = std::time::Instant::now();
         
         // Clear debugger state
+        self.watchdog.clear();
         self.debugger.clear_history();
+        self.debugger.break_next = self.debugger.pause_on_start;
+        if let Some(ref mut history) = self.reverse {
+            history.clear();
+        }
+        self.hooks.reset();
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.clear();
+        }
         
         Ok(())
     }
@@ -106,32 +377,162 @@ impl Emulator {
             return Ok(0);
         }
         
-        let cycles = self.bus.step_cpu(&mut self.cpu)?;
+        self.record_reverse_snapshot();
+        self.execute_step(true)
+    }
+    
+    /// Execute one CPU step
+    ///
+    /// `live` is false while replaying for a reverse step, which skips
+    /// debugger events, frame hooks and auto-save.
+    pub(crate) fn execute_step(&mut self, live: bool) -> RnesResult<rnes_common::Cycles> {
+        self.bus.watch_mapper_events(live && !self.debugger.mapper_breaks.is_empty());
+        if live {
+            self.record_trace();
+        }
+        self.latch_input();
+        let (pc, sp, jammed, nmi_pending) = (self.cpu.pc, self.cpu.sp, self.cpu.jammed, self.cpu.nmi_pending);
+        let interrupt = self.upcoming_interrupt();
+        self.watchdog.record(pc);
+        let cycles = match self.bus.step_cpu(&mut self.cpu) {
+            Err(RnesError::InvalidOpcode { opcode, pc }) => {
+                self.game_crashed(CrashReason::InvalidOpcode { opcode }, pc);
+                return Ok(0);
+            }
+            result => result?,
+        };
         self.state.cpu_cycles += cycles;
+        if live && self.profiler.is_some() {
+            self.record_profile(ProfiledStep {
+                cycles,
+                sp,
+                sp_after: self.cpu.sp,
+                took_nmi: nmi_pending && !self.cpu.nmi_pending,
+                opcode: self.bus.peek(pc),
+            });
+        }
+        if self.cpu.jammed && !jammed {
+            let opcode = self.bus.peek(self.cpu.pc);
+            self.game_crashed(CrashReason::Jam { opcode }, self.cpu.pc);
+        } else if self.cpu.pc == pc && self.cpu.sp == sp.wrapping_sub(3) && self.bus.peek(pc) == 0x00 {
+            self.game_crashed(CrashReason::BrkLoop, pc);
+        }
+        self.advance_reverse_history();
+        
+        // Halt after the instruction that triggered a watched mapper event
+        for event in self.bus.take_mapper_events() {
+            if self.debugger.has_mapper_break(event.kind) {
+                self.running = false;
+                match event.write {
+                    Some(ref write) => tracing::info!(
+                        "Mapper {:?} on write 0x{:04X} = 0x{:02X} at 0x{:04X}",
+                        event.kind, write.address, write.value, write.pc
+                    ),
+                    None => tracing::info!(
+                        "Mapper {:?} at scanline {} dot {}",
+                        event.kind, event.scanline, event.dot
+                    ),
+                }
+                self.debugger.last_mapper_event = Some(event);
+            }
+        }
+        
+        // Halt after the step that took a watched interrupt
+        if let Some(kind) = interrupt.filter(|&kind| live && self.debugger.has_interrupt_break(kind)) {
+            self.interrupt_taken(kind, pc);
+        }
         
         // Update debug info after execution
-        self.update_debug_info();
+        if live {
+            self.update_debug_info();
+        }
         
         // Update PPU state
         if let Some(ref ppu) = self.bus.ppu {
             self.state.ppu_scanline = ppu.scanline();
             self.state.ppu_dot = ppu.dot();
+            
+            // Frames that never read the controllers are lag frames
+            let frame = ppu.frame_count();
+            if frame != self.state.frame_count {
+                self.state.frame_count = frame;
+                self.state.lag_frame = !self.bus.input_polled.get();
+                if self.state.lag_frame {
+                    self.state.lag_frames += 1;
+                }
+                self.bus.input_polled.set(false);
+                self.bus.zapper.end_frame();
+                if live {
+                    self.end_profiled_frame();
+                    self.record_stems()?;
+                }
+            }
+            
+            // Frame hooks run once per completed frame
+            if live && self.hooks.notify(frame, &self.bus) {
+                #[cfg(feature = "achievements")]
+                self.achievements.evaluate(frame, &self.bus);
+                self.update_watches();
+            }
         }
         
-        // Handle APU IRQ
-        if self.bus.dmc_irq_pending() {
-            self.cpu.request_irq();
-            self.bus.clear_dmc_irq();
+        if live {
+            if let Some(ref mut sink) = self.audio_sink {
+                sink.deliver(&mut self.bus);
+            }
         }
         
         // Handle auto-save
-        self.handle_auto_save()?;
+        if live {
+            self.handle_auto_save()?;
+        }
         
         Ok(cycles)
     }
     
+    /// Interrupt or BRK the next CPU step will take, if any
+    fn upcoming_interrupt(&self) -> Option<InterruptKind> {
+        let cpu = &self.cpu;
+        if cpu.jammed || cpu.stall_cycles > 0 {
+            None
+        } else if cpu.nmi_pending {
+            Some(InterruptKind::Nmi)
+        } else if cpu.irq_pending && !cpu.status.interrupts_disabled() {
+            Some(InterruptKind::Irq)
+        } else if self.bus.peek(cpu.pc) == 0x00 {
+            Some(InterruptKind::Brk)
+        } else {
+            None
+        }
+    }
+    
+    /// Stop on an interrupt the debugger breaks on
+    fn interrupt_taken(&mut self, kind: InterruptKind, pc: rnes_common::Word) {
+        let vector = kind.vector();
+        let target = u16::from_le_bytes([self.bus.peek(vector), self.bus.peek(vector + 1)]);
+        let (scanline, dot) = self.bus.ppu.as_ref().map_or((0, 0), |ppu| (ppu.scanline(), ppu.dot()));
+        tracing::info!("{:?} at 0x{:04X} to handler 0x{:04X}", kind, pc, target);
+        self.debugger.last_interrupt = Some(InterruptEvent { kind, pc, target, scanline, dot });
+        self.running = false;
+    }
+    
+    /// Stop on a crashed game, keeping what led up to it
+    fn game_crashed(&mut self, reason: CrashReason, pc: rnes_common::Word) {
+        let frame = self.state.frame_count;
+        let crash = self.watchdog.trip(reason, pc, frame);
+        tracing::warn!("Game crashed: {}", crash);
+        self.running = false;
+    }
+    
+    /// The crash that stopped the game, if any, for a "reset?" prompt
+    ///
+    /// Cleared by any reset and by loading a state.
+    pub fn crash(&self) -> Option<&GameCrash> {
+        self.watchdog.crash()
+    }
+    
     /// Update debug information
-    fn update_debug_info(&mut self) {
+    pub(crate) fn update_debug_info(&mut self) {
         let mut debug_info = DebugInfo::default();
         
         // CPU registers
@@ -153,6 +554,12 @@ impl Emulator {
             },
         };
         
+        debug_info.interrupts = InterruptDebugState {
+            nmi_pending: self.cpu.nmi_pending,
+            irq_line: self.bus.irq().line(),
+            irq_sources: self.bus.irq().sources(),
+        };
+        
         // Current instruction info (simplified)
         debug_info.current_pc = self.cpu.pc;
         debug_info.current_cycles = 0; // This will be updated by the debugger
@@ -184,15 +591,32 @@ impl Emulator {
     }
     
     /// Handle auto-save functionality
+    ///
+    /// Battery RAM is saved once the game has stopped writing to it for
+    /// `battery_flush_delay_ms`, or after `auto_save_interval` seconds if the
+    /// game keeps writing.
     fn handle_auto_save(&mut self) -> RnesResult<()> {
-        if !self.config.general.auto_save_battery || self.config.general.auto_save_interval == 0 {
+        if !self.config.general.auto_save_battery || self.bus.ppu.is_none() {
             return Ok(());
         }
         
-        let elapsed = self.last_auto_save.elapsed().as_secs() as u32;
-        if elapsed >= self.config.general.auto_save_interval {
-            self.save_battery_backup()?;
-            self.last_auto_save = std::time::Instant::now();
+        let mapper = self.bus.mapper_mut();
+        if mapper.prg_ram_dirty() {
+            mapper.clear_prg_ram_dirty();
+            let now = std::time::Instant::now();
+            self.last_battery_write = now;
+            self.battery_dirty_since.get_or_insert(now);
+        }
+        
+        let Some(dirty_since) = self.battery_dirty_since else {
+            return Ok(());
+        };
+        let delay = std::time::Duration::from_millis(self.config.general.battery_flush_delay_ms as u64);
+        let interval = self.config.general.auto_save_interval as u64;
+        let quiet = self.last_battery_write.elapsed() >= delay;
+        let overdue = interval != 0 && dirty_since.elapsed().as_secs() >= interval;
+        if quiet || overdue {
+            self.flush_battery_backup()?;
         }
         
         Ok(())
@@ -211,14 +635,51 @@ impl Emulator {
         Ok(())
     }
     
+    /// Run until the PPU completes the current frame
+    pub fn run_frame(&mut self) -> RnesResult<()> {
+        let start_frame = match self.bus.ppu {
+            Some(ref ppu) => ppu.frame_count(),
+            None => return Ok(()),
+        };
+        
+        // Bound the loop so a stalled PPU cannot hang the caller
+        let mut budget = rnes_common::CPU_CYCLES_PER_FRAME * 2;
+        while budget > 0 && self.ppu().frame_count() == start_frame {
+            let executed = self.step()?;
+            if executed == 0 {
+                break;
+            }
+            budget = budget.saturating_sub(executed);
+        }
+        Ok(())
+    }
+    
+    /// Run `frames` whole frames, returning how many completed
+    pub fn run_frames(&mut self, frames: u32) -> RnesResult<u32> {
+        for completed in 0..frames {
+            if !self.running {
+                return Ok(completed);
+            }
+            self.run_frame()?;
+        }
+        Ok(frames)
+    }
+    
     /// Start running
     pub fn start(&mut self) {
         self.running = true;
     }
     
     /// Stop running
+    ///
+    /// Unsaved battery RAM is written out when auto-save is enabled.
     pub fn stop(&mut self) {
         self.running = false;
+        if self.config.general.auto_save_battery {
+            if let Err(e) = self.flush_battery_backup() {
+                tracing::warn!("Failed to save battery backup: {}", e);
+            }
+        }
     }
     
     /// Check if running
@@ -241,6 +702,70 @@ impl Emulator {
         self.bus.get_ppu_frame_buffer()
     }
     
+    /// Scanlines that changed in the last frame, or `None` if it is unchanged
+    ///
+    /// Frontends can upload just these rows of their texture. While the
+    /// overlay is shown every line counts as dirty; upload the whole frame
+    /// once more after hiding it.
+    pub fn dirty_lines(&self) -> Option<std::ops::Range<usize>> {
+        if self.overlay.is_active() {
+            return Some(0..SCREEN_HEIGHT);
+        }
+        self.bus.ppu.as_ref()?.dirty_lines()
+    }
+    
+    /// Get the frame buffer with the overlay drawn on top
+    pub fn get_frame_buffer(&self) -> Option<Vec<rnes_common::Pixel>> {
+        let mut pixels = self.get_ppu_frame_buffer()?.to_vec();
+        if self.overlay.is_active() {
+            self.overlay.compose(&mut pixels, &self.overlay_status());
+        }
+        Some(pixels)
+    }
+    
+    /// Copy the frame buffer, with the overlay, into `out` as RGBA8 bytes
+    ///
+    /// `out` should hold `SCREEN_WIDTH * SCREEN_HEIGHT * 4` bytes; extra bytes
+    /// are left untouched.
+    pub fn write_frame_rgba(&self, out: &mut [u8]) {
+        let write = |out: &mut [u8], pixels: &[rnes_common::Pixel]| {
+            for (dst, pixel) in out.chunks_exact_mut(4).zip(pixels.iter()) {
+                dst.copy_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+            }
+        };
+        
+        if self.overlay.is_active() {
+            if let Some(pixels) = self.get_frame_buffer() {
+                write(out, &pixels);
+            }
+        } else if let Some(pixels) = self.get_ppu_frame_buffer() {
+            // No overlay: skip the intermediate copy
+            write(out, pixels);
+        }
+    }
+    
+    /// Encode the frame buffer, with the overlay, as a PNG image
+    pub fn screenshot_png(&self) -> RnesResult<Vec<u8>> {
+        if self.bus.ppu.is_none() {
+            return Err(RnesError::NoRomLoaded);
+        }
+        let mut rgba = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+        self.write_frame_rgba(&mut rgba);
+        rnes_common::png::encode_png(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &rgba)
+    }
+    
+    /// Gather values shown by the overlay's built-in widgets
+    pub(crate) fn overlay_status(&self) -> OverlayStatus {
+        OverlayStatus {
+            controller1: self.bus.controller1.clone(),
+            controller2: self.bus.controller2.clone(),
+            lag_frames: Some(self.state.lag_frames),
+            zapper_aim: self.bus.port_devices.contains(&rnes_common::InputDevice::Zapper)
+                .then_some(self.bus.zapper.aim)
+                .flatten(),
+        }
+    }
+    
     /// Check if PPU VBlank is active
     pub fn ppu_vblank(&self) -> bool {
         self.bus.ppu_vblank()
@@ -282,31 +807,48 @@ impl Emulator {
     }
     
     /// Set controller 1 state
+    ///
+    /// Takes effect immediately, even in the middle of a frame, and stays
+    /// held for later frames. Live input should use `set_input` instead.
     pub fn set_controller1(&mut self, state: rnes_common::ControllerState) {
+        self.boot_pristine &= state.bits() == 0;
+        self.input.set_held(0, state.bits());
         self.bus.set_controller1(state);
     }
     
     /// Set controller 2 state
+    ///
+    /// Takes effect immediately, like `set_controller1`.
     pub fn set_controller2(&mut self, state: rnes_common::ControllerState) {
+        self.boot_pristine &= state.bits() == 0;
+        self.input.set_held(1, state.bits());
         self.bus.set_controller2(state);
     }
     
     /// Handle keyboard input
+    ///
+    /// The change is latched when the next frame starts.
     pub fn handle_keyboard_input(&mut self, key: rnes_common::Button, pressed: bool) {
-        let mut controller1 = self.bus.controller1.clone();
-        
-        match key {
-            rnes_common::Button::A => controller1.a = pressed,
-            rnes_common::Button::B => controller1.b = pressed,
-            rnes_common::Button::Select => controller1.select = pressed,
-            rnes_common::Button::Start => controller1.start = pressed,
-            rnes_common::Button::Up => controller1.up = pressed,
-            rnes_common::Button::Down => controller1.down = pressed,
-            rnes_common::Button::Left => controller1.left = pressed,
-            rnes_common::Button::Right => controller1.right = pressed,
+        self.boot_pristine &= !pressed;
+        self.input.set_held_button(0, key, pressed);
+    }
+    
+    /// Handle a named key using the configured bindings
+    ///
+    /// Returns `true` if the key is bound to a button on either controller.
+    /// The change is latched when the next frame starts.
+    pub fn handle_key(&mut self, key: &str, pressed: bool) -> bool {
+        let mut handled = false;
+        if let Some(button) = self.config.input.controller1.button_for_key(key) {
+            self.input.set_held_button(0, button, pressed);
+            handled = true;
         }
-        
-        self.bus.set_controller1(controller1);
+        if let Some(button) = self.config.input.controller2.button_for_key(key) {
+            self.input.set_held_button(1, button, pressed);
+            handled = true;
+        }
+        self.boot_pristine &= !(handled && pressed);
+        handled
     }
     
     /// Get current controller 1 state
@@ -329,9 +871,38 @@ impl Emulator {
         &self.bus
     }
     
-    /// Get audio samples
-    pub fn get_audio_samples(&mut self) -> Vec<rnes_common::AudioSample> {
-        self.bus.get_audio_samples()
+    /// Move up to `out.len()` of the oldest audio samples into `out`,
+    /// returning how many were written
+    ///
+    /// Audio backends can ask for exactly one device buffer at a time.
+    pub fn fill_audio(&mut self, out: &mut [rnes_common::AudioSample]) -> usize {
+        self.bus.fill_audio(out)
+    }
+    
+    /// Take all buffered audio samples, oldest first
+    pub fn drain_audio(&mut self) -> impl Iterator<Item = rnes_common::AudioSample> + '_ {
+        self.bus.drain_audio()
+    }
+    
+    /// Number of buffered audio samples
+    pub fn audio_available(&self) -> usize {
+        self.bus.audio_available()
+    }
+    
+    /// Deliver audio in chunks of `chunk_size` samples as soon as each is complete
+    ///
+    /// Replaces any earlier callback. Samples handed to the callback are no
+    /// longer returned by `fill_audio`.
+    pub fn set_audio_callback<F>(&mut self, chunk_size: usize, callback: F)
+    where
+        F: FnMut(&[rnes_common::AudioSample]) + Send + 'static,
+    {
+        self.audio_sink = Some(AudioSink::new(chunk_size, Box::new(callback)));
+    }
+    
+    /// Stop delivering audio through the callback
+    pub fn clear_audio_callback(&mut self) {
+        self.audio_sink = None;
     }
     
     /// Get APU instance
@@ -344,6 +915,233 @@ impl Emulator {
         self.bus.apu_mut()
     }
     
+    /// Get effective overclock for the loaded ROM (per-game override first)
+    ///
+    /// Overrides are keyed by the ROM's CRC-32 or SHA-1 in hex.
+    pub fn overclock_scanlines(&self) -> u16 {
+        let overrides = &self.config.general.overclock_overrides;
+        self.rom_id()
+            .and_then(|id| overrides.get(&id.to_string()).or_else(|| overrides.get(&id.sha1_hex())).copied())
+            .unwrap_or(self.config.general.overclock_scanlines)
+    }
+    
+    /// Set extra scanlines per frame for all games
+    pub fn set_overclock_scanlines(&mut self, scanlines: u16) {
+        self.config.general.overclock_scanlines = scanlines;
+        let effective = self.overclock_scanlines();
+        if let Some(ref mut ppu) = self.bus.ppu {
+            ppu.set_overclock_scanlines(effective);
+        }
+    }
+    
+    /// Register a callback run once after every completed frame
+    ///
+    /// Callbacks get read-only memory access, e.g. for achievement logic.
+    pub fn add_frame_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(u64, &dyn MemoryPeek) + Send + 'static,
+    {
+        self.hooks.add(Box::new(callback));
+    }
+    
+    /// Read a CPU address without side effects
+    pub fn peek(&self, addr: rnes_common::Word) -> rnes_common::Byte {
+        self.bus.peek(addr)
+    }
+    
+    /// Read a block of CPU memory without side effects
+    pub fn peek_range(&self, addr: rnes_common::Word, buffer: &mut [rnes_common::Byte]) {
+        self.bus.peek_range(addr, buffer);
+    }
+    
+    /// Get the size of a memory region in bytes (0 if absent)
+    pub fn memory_size(&self, region: MemoryRegion) -> usize {
+        self.bus.memory_size(region)
+    }
+    
+    /// Read part of a memory region for a hex editor, without side effects
+    pub fn read_memory(&mut self, region: MemoryRegion, offset: usize, len: usize) -> RnesResult<Vec<rnes_common::Byte>> {
+        self.bus.read_memory(region, offset, len)
+    }
+    
+    /// Edit a memory region in place, while paused or running
+    pub fn write_memory(&mut self, region: MemoryRegion, offset: usize, data: &[rnes_common::Byte]) -> RnesResult<()> {
+        self.bus.write_memory(region, offset, data)
+    }
+    
+    /// Assemble 6502 source at a CPU address and patch it into memory
+    ///
+    /// Code in $8000-$FFFF is written into the mapped PRG ROM bank. Returns
+    /// the number of bytes written.
+    pub fn assemble_at(&mut self, addr: rnes_common::Word, source: &str) -> RnesResult<usize> {
+        let assembly = rnes_cpu6502::assemble(source, addr)?;
+        let mut written = 0;
+        for segment in &assembly.segments {
+            self.bus.patch_code(segment.origin, &segment.bytes)?;
+            written += segment.bytes.len();
+        }
+        Ok(written)
+    }
+    
+    /// Start recording a memory access heat map over `window_frames` frames (0 = until stopped)
+    pub fn start_heat_map(&mut self, window_frames: u32) {
+        self.bus.start_heat_map(window_frames);
+    }
+    
+    /// Stop recording and return the collected heat map
+    pub fn stop_heat_map(&mut self) -> Option<AccessHeatMap> {
+        self.bus.stop_heat_map()
+    }
+    
+    /// Get the heat map being recorded, if any
+    pub fn heat_map(&self) -> Option<std::cell::Ref<'_, AccessHeatMap>> {
+        self.bus.heat_map()
+    }
+    
+    /// Clear the heat map counts and restart its sampling window
+    pub fn clear_heat_map(&mut self) {
+        self.bus.clear_heat_map();
+    }
+    
+    /// Start recording which PRG ROM bytes are executed
+    pub fn start_coverage(&mut self) {
+        self.bus.start_coverage();
+    }
+    
+    /// Stop recording and return the code coverage
+    pub fn stop_coverage(&mut self) -> Option<CodeCoverage> {
+        self.bus.stop_coverage()
+    }
+    
+    /// Get the code coverage being recorded, if any
+    pub fn coverage(&self) -> Option<&CodeCoverage> {
+        self.bus.coverage()
+    }
+    
+    /// Start recording the per-frame event timeline (NMI, IRQs, DMA, $2002 reads)
+    pub fn start_timeline(&mut self) {
+        self.bus.start_timeline();
+    }
+    
+    /// Stop recording and return the event timeline
+    pub fn stop_timeline(&mut self) -> Option<EventTimeline> {
+        self.bus.stop_timeline()
+    }
+    
+    /// Get the event timeline being recorded, if any
+    pub fn timeline(&self) -> Option<std::cell::Ref<'_, EventTimeline>> {
+        self.bus.timeline()
+    }
+    
+    /// Start logging writes to $2000-$2007 and $4014 with their PC and PPU position
+    pub fn start_ppu_write_log(&mut self) {
+        self.bus.start_ppu_write_log();
+    }
+    
+    /// Stop logging and return the PPU write log
+    pub fn stop_ppu_write_log(&mut self) -> Option<PpuWriteLog> {
+        self.bus.stop_ppu_write_log()
+    }
+    
+    /// Get the PPU write log being recorded, if any
+    pub fn ppu_write_log(&self) -> Option<&PpuWriteLog> {
+        self.bus.ppu_write_log()
+    }
+    
+    /// Get RetroAchievements hash of the loaded ROM
+    pub fn ra_hash(&self) -> Option<String> {
+        self.bus.cartridge.as_ref().map(crate::ra_hash_cartridge)
+    }
+    
+    /// Get number of lag frames since reset
+    pub fn lag_frames(&self) -> u64 {
+        self.state.lag_frames
+    }
+    
+    /// Check if the last completed frame was a lag frame
+    pub fn is_lag_frame(&self) -> bool {
+        self.state.lag_frame
+    }
+    
+    /// Get timing region of the loaded ROM
+    pub fn region(&self) -> Region {
+        self.region
+    }
+    
+    /// Override the detected timing region
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_region(region);
+        }
+        if let Some(ref mut ppu) = self.bus.ppu {
+            ppu.set_region(region);
+        }
+    }
+    
+    /// Get effective frame rate for pacing (60.0988 NTSC, 50.007 PAL/Dendy)
+    pub fn frame_rate(&self) -> f64 {
+        self.region.frame_rate()
+    }
+    
+    /// Draw all sprites on a line (`true`) or keep the hardware's 8-sprite
+    /// limit
+    pub fn set_remove_sprite_limit(&mut self, remove: bool) {
+        self.config.video.remove_sprite_limit = remove;
+        if let Some(ref mut ppu) = self.bus.ppu {
+            ppu.set_sprite_limit(!remove);
+        }
+    }
+    
+    /// Check if sprites past the eighth on a line are drawn
+    pub fn sprite_limit_removed(&self) -> bool {
+        self.config.video.remove_sprite_limit
+    }
+    
+    /// Set master audio volume (0.0 - 1.0)
+    pub fn set_volume(&mut self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        self.config.audio.master_volume = volume;
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_master_volume(volume);
+        }
+    }
+    
+    /// Get master audio volume
+    pub fn volume(&self) -> f32 {
+        self.config.audio.master_volume
+    }
+    
+    /// Hold the triangle still instead of playing its ultrasonic periods
+    pub fn set_silence_ultrasonic_triangle(&mut self, silence: bool) {
+        self.config.audio.silence_ultrasonic_triangle = silence;
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_silence_ultrasonic_triangle(silence);
+        }
+    }
+    
+    /// Set the volume of a cartridge sound chip relative to its hardware level
+    pub fn set_expansion_gain(&mut self, chip: ExpansionChip, gain: f32) {
+        let gain = gain.max(0.0);
+        self.config.audio.expansion.entry(chip).or_default().gain = gain;
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_expansion_gain(chip, gain);
+        }
+    }
+    
+    /// Mute or unmute a cartridge sound chip
+    pub fn set_expansion_enabled(&mut self, chip: ExpansionChip, enabled: bool) {
+        self.config.audio.expansion.entry(chip).or_default().enabled = enabled;
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_expansion_enabled(chip, enabled);
+        }
+    }
+    
+    /// Current settings of a cartridge sound chip
+    pub fn expansion_audio(&self, chip: ExpansionChip) -> ExpansionAudioConfig {
+        self.config.audio.expansion(chip)
+    }
+    
     /// Save battery backup
     pub fn save_battery_backup(&self) -> RnesResult<()> {
         if let Some(ref rom_name) = self.rom_name {
@@ -357,93 +1155,389 @@ impl Emulator {
         Ok(())
     }
     
-    /// Save state to slot
-    pub fn save_state(&self, slot: u8) -> RnesResult<()> {
-        if let Some(ref rom_name) = self.rom_name {
-            let mut save_state = SaveState::new(rom_name.clone());
-            
-            // Save CPU state
-            save_state.cpu_state.pc = self.cpu.pc;
-            save_state.cpu_state.sp = self.cpu.sp;
-            save_state.cpu_state.a = self.cpu.a;
-            save_state.cpu_state.x = self.cpu.x;
-            save_state.cpu_state.y = self.cpu.y;
-            save_state.cpu_state.status = self.cpu.status.bits();
-            save_state.cpu_state.cycles = self.state.cpu_cycles as u64;
-            
-            // Save PPU state
-            if let Some(ref ppu) = self.bus.ppu {
-                save_state.ppu_state.scanline = ppu.scanline() as u16;
-                save_state.ppu_state.dot = ppu.dot() as u16;
-                save_state.ppu_state.frame = ppu.frame_count() as u32;
-                save_state.ppu_state.vblank = ppu.vblank();
-                save_state.ppu_state.oam = ppu.oam().to_vec();
-                save_state.ppu_state.palette_ram = ppu.palette_ram().to_vec();
-                
-                // Convert frame buffer to u32 for serialization
-                let frame_buffer = ppu.frame_buffer();
-                save_state.ppu_state.frame_buffer = frame_buffer.iter()
-                    .map(|pixel| pixel.to_u32())
-                    .collect();
-            }
+    /// Save battery backup if PRG RAM changed since it was last saved
+    ///
+    /// Returns whether anything was written.
+    pub fn flush_battery_backup(&mut self) -> RnesResult<bool> {
+        if self.bus.ppu.is_none() {
+            return Ok(false);
+        }
+        
+        let mapper = self.bus.mapper_mut();
+        let dirty = mapper.prg_ram_dirty() || self.battery_dirty_since.is_some();
+        if !dirty {
+            return Ok(false);
+        }
+        
+        mapper.clear_prg_ram_dirty();
+        self.save_battery_backup()?;
+        self.battery_dirty_since = None;
+        Ok(true)
+    }
+    
+    /// Capture the current machine state
+    ///
+    /// Fails with `RnesError::NoRomLoaded` when no ROM is loaded.
+    pub fn create_save_state(&self) -> RnesResult<SaveState> {
+        let rom_name = self.rom_name.as_ref().ok_or(RnesError::NoRomLoaded)?;
+        let mut save_state = SaveState::new(rom_name.clone());
+        save_state.rom_crc32 = self.bus.cartridge.as_ref().map(|c| c.crc32).unwrap_or(0);
+        
+        // Save CPU state
+        save_state.cpu_state.pc = self.cpu.pc;
+        save_state.cpu_state.sp = self.cpu.sp;
+        save_state.cpu_state.a = self.cpu.a;
+        save_state.cpu_state.x = self.cpu.x;
+        save_state.cpu_state.y = self.cpu.y;
+        save_state.cpu_state.status = self.cpu.status.bits();
+        save_state.cpu_state.cycles = self.state.cpu_cycles as u64;
+        save_state.cpu_state.stall_cycles = self.cpu.stall_cycles;
+        save_state.cpu_state.nmi_pending = self.cpu.nmi_pending;
+        save_state.cpu_state.irq_pending = self.cpu.irq_pending;
+        
+        // Save PPU state
+        if let Some(ref ppu) = self.bus.ppu {
+            save_state.ppu_state.scanline = ppu.scanline() as u16;
+            save_state.ppu_state.dot = ppu.dot() as u16;
+            save_state.ppu_state.frame = ppu.frame_count() as u32;
+            save_state.ppu_state.vblank = ppu.vblank();
+            save_state.ppu_state.oam = ppu.oam().to_vec();
+            save_state.ppu_state.palette_ram = ppu.palette_ram().to_vec();
+            save_state.ppu_state.nametable_ram = ppu.nametable_ram().to_vec();
+            save_state.ppu_state.data = SaveState::encode_component(&ppu.save_state())?;
+            // The frame buffer is render output and is rebuilt by the next frame
             
-            // Save memory state
-            save_state.memory_state.ram = self.bus.ram.to_vec();
+            save_state.metadata.frame_count = ppu.frame_count();
+            save_state.metadata.play_time_ms = (ppu.frame_count() as f64 * 1000.0 / self.frame_rate()) as u64;
+        }
+        
+        if let Some(ref apu) = self.bus.apu {
+            save_state.apu_state.data = SaveState::encode_component(&apu.save_state())?;
+        }
+        
+        // Save memory state
+        save_state.memory_state.ram = self.bus.ram.to_vec();
+        if self.bus.ppu.is_some() {
             let mapper = self.bus.mapper();
             if let Some(ram) = mapper.get_prg_ram() {
                 save_state.memory_state.prg_ram = ram.to_vec();
             }
-            
-            // Save mapper state
-            save_state.mapper_state.mapper_type = self.bus.cartridge.as_ref()
-                .map(|c| c.mapper_number())
-                .unwrap_or(0);
-            // Note: Mapper-specific state serialization would go here
-            
+            if let Some(ram) = mapper.get_chr_ram() {
+                save_state.memory_state.chr_ram = ram.to_vec();
+            }
+            save_state.mapper_state.data = SaveState::encode_component(&mapper.save_state())?;
+        }
+        save_state.mapper_state.mapper_type = self.bus.cartridge.as_ref()
+            .map(|c| c.mapper_number())
+            .unwrap_or(0);
+        save_state.input_state = self.bus.input_state();
+        
+        (save_state.ram_init, save_state.seed) = self.power_on_settings();
+        
+        Ok(save_state)
+    }
+    
+    /// Restore machine state from a save state
+    ///
+    /// Fails with `NoRomLoaded` when no ROM is loaded.
+    pub fn apply_save_state(&mut self, save_state: &SaveState) -> RnesResult<()> {
+        let (Some(cartridge), Some(_)) = (&self.bus.cartridge, &self.bus.ppu) else {
+            return Err(RnesError::NoRomLoaded);
+        };
+        save_state.verify_rom(cartridge.crc32)?;
+        // Decode and check every component before touching the machine, so
+        // a corrupt state leaves it as it was
+        let ppu_state: Option<rnes_ppu::PpuState> = decode_component(&save_state.ppu_state.data)?;
+        let apu_state: Option<rnes_apu::ApuState> = decode_component(&save_state.apu_state.data)?;
+        let mapper_state: Option<rnes_mappers::MapperState> = decode_component(&save_state.mapper_state.data)?;
+        if let Some(ref state) = ppu_state {
+            state.validate()?;
+        }
+        if let Some(ref state) = apu_state {
+            state.validate()?;
+        }
+        if let Some(ref state) = mapper_state {
+            state.check_board(self.bus.mapper())?;
+            state.validate()?;
+        }
+        // Checks the size before copying, so this is the last step that can fail
+        if !save_state.memory_state.prg_ram.is_empty() {
+            self.bus.mapper_mut().load_prg_ram(&save_state.memory_state.prg_ram)?;
+        }
+        
+        // Load CPU state
+        self.cpu.pc = save_state.cpu_state.pc;
+        self.cpu.sp = save_state.cpu_state.sp;
+        self.cpu.a = save_state.cpu_state.a;
+        self.cpu.x = save_state.cpu_state.x;
+        self.cpu.y = save_state.cpu_state.y;
+        self.cpu.status = rnes_cpu6502::StatusFlags::from_bits(save_state.cpu_state.status).unwrap_or_default();
+        self.cpu.stall_cycles = save_state.cpu_state.stall_cycles;
+        self.cpu.nmi_pending = save_state.cpu_state.nmi_pending;
+        self.cpu.irq_pending = save_state.cpu_state.irq_pending;
+        self.cpu.jammed = false;
+        self.state.cpu_cycles = save_state.cpu_state.cycles as u32;
+        self.watchdog.clear();
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.clear();
+        }
+        self.boot_pristine = false;
+        self.relatch_input();
+        
+        // Load PPU state
+        let ppu = self.bus.ppu_mut();
+        ppu.set_scanline(save_state.ppu_state.scanline as i32);
+        ppu.set_dot(save_state.ppu_state.dot as u32);
+        ppu.set_frame(save_state.ppu_state.frame);
+        ppu.set_vblank(save_state.ppu_state.vblank);
+        ppu.set_oam(&save_state.ppu_state.oam);
+        ppu.set_palette_ram(&save_state.ppu_state.palette_ram);
+        ppu.set_nametable_ram(&save_state.ppu_state.nametable_ram);
+        if let Some(ppu_state) = ppu_state {
+            ppu.load_state(ppu_state)?;
+        }
+        if let (Some(apu), Some(apu_state)) = (self.bus.apu.as_mut(), apu_state) {
+            apu.load_state(apu_state)?;
+        }
+        self.state.frame_count = save_state.ppu_state.frame as u64;
+        self.hooks.reset();
+        if let Some(ref mut history) = self.reverse {
+            history.clear();
+        }
+        
+        // Load memory state
+        if save_state.memory_state.ram.len() == self.bus.ram.len() {
+            self.bus.ram.copy_from_slice(&save_state.memory_state.ram);
+        }
+        self.bus.load_input_state(&save_state.input_state);
+        let mapper = self.bus.mapper_mut();
+        if let Some(mapper_state) = mapper_state {
+            mapper.load_state(mapper_state)?;
+        }
+        if let Some(chr_ram) = mapper.get_chr_ram_mut() {
+            if chr_ram.len() == save_state.memory_state.chr_ram.len() {
+                chr_ram.copy_from_slice(&save_state.memory_state.chr_ram);
+            }
+        }
+        
+        // Later power cycles must start from the same RAM as the original
+        // run's, but other games keep the configured settings
+        self.power_on_override = Some((save_state.ram_init, save_state.seed));
+        
+        Ok(())
+    }
+    
+    /// Export battery-backed PRG RAM
+    ///
+    /// Returns `None` if no ROM is loaded or the cartridge has no battery.
+    pub fn export_battery_ram(&self) -> Option<Vec<rnes_common::Byte>> {
+        self.bus.ppu.as_ref()?;
+        let mapper = self.bus.mapper();
+        if !mapper.has_battery() {
+            return None;
+        }
+        mapper.get_prg_ram().map(|ram| ram.to_vec())
+    }
+    
+    /// Import battery-backed PRG RAM, e.g. from an uploaded .sav file
+    pub fn import_battery_ram(&mut self, data: &[rnes_common::Byte]) -> RnesResult<()> {
+        if self.bus.ppu.is_none() {
+            return Err(RnesError::NoRomLoaded);
+        }
+        let mapper = self.bus.mapper_mut();
+        if !mapper.has_battery() {
+            return Err(RnesError::UnsupportedFeature {
+                component: "this cartridge",
+                feature: "battery-backed RAM".to_string(),
+            });
+        }
+        mapper.load_prg_ram(data)
+    }
+    
+    /// Take an in-memory snapshot for rewind or rollback
+    ///
+    /// Snapshots hold the whole machine state but skip the thumbnail and
+    /// compression; restore them with `restore_snapshot`.
+    pub fn snapshot(&self) -> RnesResult<SaveState> {
+        self.create_save_state()
+    }
+    
+    /// Restore an in-memory snapshot taken with `snapshot`
+    pub fn restore_snapshot(&mut self, snapshot: &SaveState) -> RnesResult<()> {
+        self.apply_save_state(snapshot)
+    }
+    
+    /// Save state to slot
+    pub fn save_state(&self, slot: u8) -> RnesResult<()> {
+        if self.rom_name.is_some() {
+            let mut save_state = self.create_save_state()?;
+            save_state.metadata.thumbnail = self.get_ppu_frame_buffer().map(create_thumbnail);
             save_state.save_to_file(&self.save_system, slot)?;
         }
         Ok(())
     }
     
+    /// List save state slots for the loaded ROM
+    pub fn list_save_states(&self) -> RnesResult<Vec<SaveSlotInfo>> {
+        match self.rom_name {
+            Some(ref rom_name) => self.save_system.list_save_states(rom_name),
+            None => Ok(Vec::new()),
+        }
+    }
+    
     /// Load state from slot
     pub fn load_state(&mut self, slot: u8) -> RnesResult<()> {
         if let Some(ref rom_name) = self.rom_name {
             let save_state = SaveState::load_from_file(&self.save_system, rom_name, slot)?;
-            
-            // Load CPU state
-            self.cpu.pc = save_state.cpu_state.pc;
-            self.cpu.sp = save_state.cpu_state.sp;
-            self.cpu.a = save_state.cpu_state.a;
-            self.cpu.x = save_state.cpu_state.x;
-            self.cpu.y = save_state.cpu_state.y;
-            self.cpu.status = rnes_cpu6502::StatusFlags::from_bits(save_state.cpu_state.status).unwrap_or_default();
-            self.state.cpu_cycles = save_state.cpu_state.cycles as u32;
-            
-            // Load PPU state
-            let ppu = self.bus.ppu_mut();
-            ppu.set_scanline(save_state.ppu_state.scanline as i32);
-            ppu.set_dot(save_state.ppu_state.dot as u32);
-            ppu.set_frame(save_state.ppu_state.frame);
-            ppu.set_vblank(save_state.ppu_state.vblank);
-            ppu.set_oam(save_state.ppu_state.oam);
-            ppu.set_palette_ram(save_state.ppu_state.palette_ram);
-                
-            // Convert frame buffer back from u32
-            let frame_buffer: Vec<rnes_common::Pixel> = save_state.ppu_state.frame_buffer.iter()
-                .map(|&pixel| rnes_common::Pixel::from_u32(pixel))
-                .collect();
-            ppu.set_frame_buffer(frame_buffer);
-            
-            // Load memory state
-            if save_state.memory_state.ram.len() == self.bus.ram.len() {
-                self.bus.ram.copy_from_slice(&save_state.memory_state.ram);
+            self.apply_save_state(&save_state)?;
+            tracing::info!("Loaded save state from slot {}", slot);
+        }
+        Ok(())
+    }
+    
+    /// Serialize the current machine state to an in-memory buffer
+    pub fn save_state_to_bytes(&self) -> RnesResult<Vec<u8>> {
+        self.create_save_state()?.to_bytes()
+    }
+    
+    /// Restore machine state from an in-memory buffer
+    pub fn load_state_from_bytes(&mut self, data: &[u8]) -> RnesResult<()> {
+        if self.rom_name.is_none() {
+            return Err(RnesError::NoRomLoaded);
+        }
+        let save_state = SaveState::from_bytes(data)?;
+        self.apply_save_state(&save_state)
+    }
+    
+    /// Get the content hash identifying the loaded ROM
+    pub fn rom_id(&self) -> Option<RomId> {
+        self.bus.cartridge.as_ref().map(|c| c.rom_id)
+    }
+    
+    /// Check if an exit state is available for the loaded ROM
+    pub fn has_resume_state(&self) -> bool {
+        self.rom_id()
+            .map(|id| self.save_system.has_resume_state(&id.to_string()))
+            .unwrap_or(false)
+    }
+    
+    /// Restore the state saved when the ROM was last closed
+    ///
+    /// Returns `Ok(false)` if auto-resume is disabled or no exit state exists.
+    pub fn resume_last_session(&mut self) -> RnesResult<bool> {
+        if !self.config.save_states.auto_resume {
+            return Ok(false);
+        }
+        let Some(rom_id) = self.rom_id().map(|id| id.to_string()) else {
+            return Ok(false);
+        };
+        let Some(data) = self.save_system.load_resume_state(&rom_id)? else {
+            return Ok(false);
+        };
+        
+        match SaveState::from_bytes(&data).and_then(|state| self.apply_save_state(&state)) {
+            Ok(()) => {
+                tracing::info!("Resumed last session for ROM {}", rom_id);
+                Ok(true)
             }
-            let mapper = self.bus.mapper_mut();
-            if !save_state.memory_state.prg_ram.is_empty() {
-                mapper.load_prg_ram(&save_state.memory_state.prg_ram)?;
+            Err(e) => {
+                // A stale or incompatible exit state should not block startup
+                tracing::warn!("Discarding exit state for ROM {}: {}", rom_id, e);
+                self.save_system.delete_resume_state(&rom_id)?;
+                Ok(false)
+            }
+        }
+    }
+    
+    /// Check if a fast-boot state is cached for the loaded ROM
+    pub fn has_boot_state(&self) -> bool {
+        self.rom_id()
+            .map(|id| self.save_system.has_boot_state(&id.to_string()))
+            .unwrap_or(false)
+    }
+    
+    /// Cache the current state as the loaded ROM's post-boot state
+    ///
+    /// Call once the game is past its license screens or disk loading. Only
+    /// a plain boot is cached: the state is refused after controller input or
+    /// a state load since the last power on.
+    pub fn cache_boot_state(&mut self) -> RnesResult<()> {
+        let rom_id = self.rom_id().ok_or(RnesError::NoRomLoaded)?.to_string();
+        if !self.boot_pristine {
+            return Err(RnesError::UnsupportedFeature {
+                component: "fast boot",
+                feature: "caching a state after input or a state load".to_string(),
+            });
+        }
+        let state = self.create_save_state()?;
+        self.save_system.save_boot_state(&rom_id, &state.to_bytes()?)
+    }
+    
+    /// Skip the boot by restoring the cached post-boot state
+    ///
+    /// Returns `Ok(false)` if fast boot is disabled, nothing is cached, or the
+    /// cache was made with other power-on settings. Battery RAM loaded with
+    /// the ROM is kept.
+    pub fn fast_boot(&mut self) -> RnesResult<bool> {
+        if !self.config.save_states.fast_boot {
+            return Ok(false);
+        }
+        let Some(rom_id) = self.rom_id().map(|id| id.to_string()) else {
+            return Ok(false);
+        };
+        let Some(data) = self.save_system.load_boot_state(&rom_id)? else {
+            return Ok(false);
+        };
+        
+        let state = match SaveState::from_bytes(&data) {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::warn!("Discarding boot state for ROM {}: {}", rom_id, e);
+                self.save_system.delete_boot_state(&rom_id)?;
+                return Ok(false);
+            }
+        };
+        if (state.ram_init, state.seed) != self.power_on_settings() {
+            tracing::debug!("Boot state for ROM {} was cached with other power-on settings", rom_id);
+            return Ok(false);
+        }
+        
+        let mapper = self.bus.mapper();
+        let battery_ram = if mapper.has_battery() {
+            mapper.get_prg_ram().map(|ram| ram.to_vec())
+        } else {
+            None
+        };
+        if let Err(e) = self.apply_save_state(&state) {
+            tracing::warn!("Discarding boot state for ROM {}: {}", rom_id, e);
+            self.save_system.delete_boot_state(&rom_id)?;
+            return Ok(false);
+        }
+        if let Some(data) = battery_ram {
+            self.bus.mapper_mut().load_prg_ram(&data)?;
+        }
+        self.boot_pristine = true;
+        
+        tracing::info!("Fast-booted ROM {}", rom_id);
+        Ok(true)
+    }
+    
+    /// Flush battery RAM and, if enabled, write the exit state
+    ///
+    /// Frontends should call this once before exiting.
+    pub fn shutdown(&mut self) -> RnesResult<()> {
+        self.running = false;
+        self.save_battery_backup()?;
+        self.battery_dirty_since = None;
+        if self.bus.ppu.is_some() {
+            self.bus.mapper_mut().clear_prg_ram_dirty();
+        }
+        
+        if self.config.save_states.auto_resume {
+            if let Some(rom_id) = self.rom_id() {
+                let state = self.create_save_state()?;
+                self.save_system.save_resume_state(&rom_id.to_string(), &state.to_bytes()?)?;
             }
-            
-            tracing::info!("Loaded save state from slot {}", slot);
         }
         Ok(())
     }
@@ -514,6 +1608,94 @@ impl Emulator {
         self.debugger.remove_breakpoint(address)
     }
     
+    /// Break when the mapper switches banks, raises an IRQ or changes mirroring
+    pub fn add_mapper_break(&mut self, kind: rnes_common::MapperEventKind) {
+        self.debugger.add_mapper_break(kind);
+    }
+    
+    /// Remove mapper event breakpoint
+    pub fn remove_mapper_break(&mut self, kind: rnes_common::MapperEventKind) -> bool {
+        self.debugger.remove_mapper_break(kind)
+    }
+    
+    /// Get the mapper event that last halted execution, with its triggering write
+    pub fn last_mapper_event(&self) -> Option<&rnes_common::MapperEvent> {
+        self.debugger.last_mapper_event.as_ref()
+    }
+    
+    /// Break when the CPU takes an NMI, IRQ or BRK
+    ///
+    /// Execution halts after the step that entered the handler.
+    pub fn add_interrupt_break(&mut self, kind: InterruptKind) {
+        self.debugger.add_interrupt_break(kind);
+    }
+    
+    /// Remove interrupt breakpoint
+    pub fn remove_interrupt_break(&mut self, kind: InterruptKind) -> bool {
+        self.debugger.remove_interrupt_break(kind)
+    }
+    
+    /// Get the interrupt that last halted execution, with its handler address
+    pub fn last_interrupt(&self) -> Option<&InterruptEvent> {
+        self.debugger.last_interrupt.as_ref()
+    }
+    
+    /// Halt at the reset vector before the first instruction after every reset
+    pub fn set_pause_on_start(&mut self, enabled: bool) {
+        self.debugger.pause_on_start = enabled;
+    }
+    
+    /// Hide background, sprites or nametables, or draw a tile grid
+    pub fn set_layer_visibility(&mut self, layers: rnes_common::LayerVisibility) {
+        self.debugger.layers = layers;
+        if let Some(ref mut ppu) = self.bus.ppu {
+            ppu.set_layer_visibility(layers);
+        }
+    }
+    
+    /// Get the layers currently drawn
+    pub fn layer_visibility(&self) -> rnes_common::LayerVisibility {
+        self.debugger.layers
+    }
+    
+    /// Load a RAM map naming the game's variables, replacing the current one
+    ///
+    /// A `<rom name>.rammap` next to the ROM is loaded automatically.
+    pub fn load_ram_annotations<P: AsRef<std::path::Path>>(&mut self, path: P) -> RnesResult<()> {
+        self.debugger.annotations = RamAnnotations::load_from_file(path)?;
+        Ok(())
+    }
+    
+    /// Get the loaded game's RAM map
+    pub fn ram_annotations(&self) -> &RamAnnotations {
+        &self.debugger.annotations
+    }
+    
+    /// Add a watch such as `scrollX = [0x071C]*256+[0x071D]`, evaluated after every frame
+    ///
+    /// The value is available from `watch_values()` right away.
+    pub fn add_watch(&mut self, definition: &str) -> RnesResult<()> {
+        self.debugger.add_watch(definition)?;
+        self.update_watches();
+        Ok(())
+    }
+    
+    /// Remove a watch by name
+    pub fn remove_watch(&mut self, name: &str) -> bool {
+        self.debugger.remove_watch(name)
+    }
+    
+    /// Get the value of every watch after the last completed frame, by name
+    pub fn watch_values(&self) -> &std::collections::BTreeMap<String, i64> {
+        &self.debugger.watch_values
+    }
+    
+    /// Evaluate the debugger's watches against current memory
+    fn update_watches(&mut self) {
+        let bus = &self.bus;
+        self.debugger.update_watches(&|addr| bus.peek(addr));
+    }
+    
     /// Enable step mode
     pub fn enable_step_mode(&mut self) {
         self.debugger.enable_step_mode();
@@ -537,17 +1719,9 @@ impl Emulator {
         Ok(())
     }
     
-    /// Get memory dump
+    /// Get memory dump, read without side effects
     pub fn get_memory_dump(&self, start: rnes_common::Word, length: usize) -> Vec<rnes_common::Byte> {
-        let mut data = Vec::new();
-        for addr in start..start + length as rnes_common::Word {
-            if let Ok(byte) = self.bus.read_byte(addr) {
-                data.push(byte);
-            } else {
-                data.push(0);
-            }
-        }
-        data
+        (start..start + length as rnes_common::Word).map(|addr| self.bus.peek(addr)).collect()
     }
 }
 
@@ -556,3 +1730,55 @@ impl Default for Emulator {
         Self::new()
     }
 }
+
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        // Don't lose battery RAM written since the last auto-save
+        if self.config.general.auto_save_battery {
+            if let Err(e) = self.flush_battery_backup() {
+                tracing::warn!("Failed to save battery backup: {}", e);
+            }
+        }
+    }
+}
+
+/// Thumbnail downscale factor (256x240 -> 64x60)
+const THUMBNAIL_SCALE: usize = 4;
+
+/// Decode a component's save state, or `None` if it was saved without one
+fn decode_component<T: serde::de::DeserializeOwned>(data: &[u8]) -> RnesResult<Option<T>> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+    SaveState::decode_component(data).map(Some)
+}
+
+/// Downscale a frame to a thumbnail by averaging each block of pixels
+fn create_thumbnail(frame: &[rnes_common::Pixel]) -> Thumbnail {
+    let width = SCREEN_WIDTH / THUMBNAIL_SCALE;
+    let height = SCREEN_HEIGHT / THUMBNAIL_SCALE;
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    
+    for ty in 0..height {
+        for tx in 0..width {
+            let mut sum = [0u32; 3];
+            for y in ty * THUMBNAIL_SCALE..(ty + 1) * THUMBNAIL_SCALE {
+                for x in tx * THUMBNAIL_SCALE..(tx + 1) * THUMBNAIL_SCALE {
+                    if let Some(pixel) = frame.get(y * SCREEN_WIDTH + x) {
+                        sum[0] += pixel.r as u32;
+                        sum[1] += pixel.g as u32;
+                        sum[2] += pixel.b as u32;
+                    }
+                }
+            }
+            let count = (THUMBNAIL_SCALE * THUMBNAIL_SCALE) as u32;
+            rgb.extend(sum.iter().map(|&channel| (channel / count) as u8));
+        }
+    }
+    
+    Thumbnail {
+        width: width as u16,
+        height: height as u16,
+        rgb,
+    }
+}
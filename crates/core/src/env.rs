@@ -0,0 +1,249 @@
+use rnes_common::{Byte, Config, ControllerState, MemoryStorage, RnesResult, SaveSystem, Word, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rnes_cartridge::Cartridge;
+use crate::Emulator;
+
+/// Observation returned by the environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObservationMode {
+    /// 256x240 RGB24 screen pixels
+    Pixels,
+    /// Copy of the 2KB work RAM
+    Ram,
+}
+
+/// Result of a single environment step
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub observation: Vec<Byte>,
+    pub reward: f32,
+    pub done: bool,
+    /// Frames elapsed since the last reset
+    pub frame: u64,
+}
+
+/// Reward function evaluated once per emulated frame
+pub trait RewardFn {
+    /// Called after every reset with the initial RAM contents
+    fn reset(&mut self, _ram: &[Byte]) {}
+
+    /// Compute the reward for the frame that just finished
+    fn reward(&mut self, ram: &[Byte]) -> f32;
+}
+
+impl<F> RewardFn for F
+where
+    F: FnMut(&[Byte]) -> f32,
+{
+    fn reward(&mut self, ram: &[Byte]) -> f32 {
+        self(ram)
+    }
+}
+
+/// Episode termination predicate, evaluated on work RAM after every frame
+pub type DoneFn = Box<dyn FnMut(&[Byte]) -> bool + Send>;
+
+/// Reward equal to the change of a RAM byte since the previous frame
+///
+/// Useful for score or progress counters stored in work RAM.
+#[derive(Debug, Clone)]
+pub struct RamDeltaReward {
+    address: Word,
+    scale: f32,
+    previous: Byte,
+}
+
+impl RamDeltaReward {
+    /// Create reward tracking the byte at `address` (0x0000-0x07FF)
+    pub fn new(address: Word) -> Self {
+        Self {
+            address: address & 0x07FF,
+            scale: 1.0,
+            previous: 0,
+        }
+    }
+
+    /// Multiply every delta by `scale`
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl RewardFn for RamDeltaReward {
+    fn reset(&mut self, ram: &[Byte]) {
+        self.previous = ram[self.address as usize];
+    }
+
+    fn reward(&mut self, ram: &[Byte]) -> f32 {
+        let current = ram[self.address as usize];
+        let delta = current as i32 - self.previous as i32;
+        self.previous = current;
+        delta as f32 * self.scale
+    }
+}
+
+/// Gym-style environment wrapping an emulator instance
+///
+/// Actions are controller 1 button bytes (see `ControllerState::from_bits`).
+/// Each step holds the action for `frame_skip` frames, summing the reward.
+pub struct RlEnv {
+    emulator: Emulator,
+    cartridge: Cartridge,
+    observation_mode: ObservationMode,
+    frame_skip: u32,
+    max_frames: Option<u64>,
+    reward_fn: Option<Box<dyn RewardFn + Send>>,
+    done_fn: Option<DoneFn>,
+    frame: u64,
+}
+
+impl RlEnv {
+    /// Create environment for a cartridge
+    ///
+    /// Battery RAM is kept in memory, so parallel environments never write
+    /// save files.
+    pub fn new(cartridge: Cartridge) -> RnesResult<Self> {
+        let mut config = Config::default();
+        config.general.auto_save_battery = false;
+        let mut env = Self {
+            emulator: Emulator::with_config(config)
+                .with_save_system(SaveSystem::with_storage(MemoryStorage::new())),
+            cartridge,
+            observation_mode: ObservationMode::Pixels,
+            frame_skip: 1,
+            max_frames: None,
+            reward_fn: None,
+            done_fn: None,
+            frame: 0,
+        };
+        env.reset()?;
+        Ok(env)
+    }
+
+    /// Set observation mode
+    pub fn with_observation_mode(mut self, mode: ObservationMode) -> Self {
+        self.observation_mode = mode;
+        self
+    }
+
+    /// Set number of frames each action is repeated for
+    pub fn with_frame_skip(mut self, frame_skip: u32) -> Self {
+        self.frame_skip = frame_skip.max(1);
+        self
+    }
+
+    /// End episodes after `max_frames` frames
+    pub fn with_max_frames(mut self, max_frames: u64) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    /// Set reward function
+    pub fn with_reward_fn<R: RewardFn + Send + 'static>(mut self, mut reward_fn: R) -> Self {
+        reward_fn.reset(&self.emulator.bus.ram);
+        self.reward_fn = Some(Box::new(reward_fn));
+        self
+    }
+
+    /// Set episode termination predicate
+    pub fn with_done_fn<F: FnMut(&[Byte]) -> bool + Send + 'static>(mut self, done_fn: F) -> Self {
+        self.done_fn = Some(Box::new(done_fn));
+        self
+    }
+
+    /// Restart the episode from power-on and return the first observation
+    pub fn reset(&mut self) -> RnesResult<Vec<Byte>> {
+        self.emulator.load_rom(self.cartridge.clone())?;
+        self.emulator.start();
+        self.frame = 0;
+
+        if let Some(ref mut reward_fn) = self.reward_fn {
+            reward_fn.reset(&self.emulator.bus.ram);
+        }
+
+        Ok(self.observe())
+    }
+
+    /// Apply an action and advance `frame_skip` frames
+    pub fn step(&mut self, action: Byte) -> RnesResult<StepResult> {
+        self.emulator.set_controller1(ControllerState::from_bits(action));
+
+        let mut reward = 0.0;
+        let mut done = false;
+        for _ in 0..self.frame_skip {
+            self.emulator.run_frame()?;
+            self.frame += 1;
+
+            let ram = &self.emulator.bus.ram;
+            if let Some(ref mut reward_fn) = self.reward_fn {
+                reward += reward_fn.reward(ram);
+            }
+            if let Some(ref mut done_fn) = self.done_fn {
+                done |= done_fn(ram);
+            }
+            if let Some(max_frames) = self.max_frames {
+                done |= self.frame >= max_frames;
+            }
+            // A stopped emulator (breakpoint or error) cannot make progress
+            done |= !self.emulator.is_running();
+
+            if done {
+                break;
+            }
+        }
+
+        Ok(StepResult {
+            observation: self.observe(),
+            reward,
+            done,
+            frame: self.frame,
+        })
+    }
+
+    /// Get the current observation
+    pub fn observe(&self) -> Vec<Byte> {
+        match self.observation_mode {
+            ObservationMode::Pixels => {
+                let mut pixels = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+                if let Some(frame_buffer) = self.emulator.get_ppu_frame_buffer() {
+                    for pixel in frame_buffer {
+                        pixels.extend_from_slice(&[pixel.r, pixel.g, pixel.b]);
+                    }
+                }
+                pixels
+            }
+            ObservationMode::Ram => self.emulator.bus.ram.to_vec(),
+        }
+    }
+
+    /// Get work RAM
+    pub fn ram(&self) -> &[Byte] {
+        &self.emulator.bus.ram
+    }
+
+    /// Frames elapsed since the last reset
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Get emulator instance
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Get mutable emulator instance
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+}
+
+impl std::fmt::Debug for RlEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RlEnv")
+            .field("observation_mode", &self.observation_mode)
+            .field("frame_skip", &self.frame_skip)
+            .field("max_frames", &self.max_frames)
+            .field("frame", &self.frame)
+            .finish()
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use rnes_common::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::Emulator;
+
+/// Bytes in one RGBA8 frame
+pub const FRAME_RGBA_BYTES: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 4;
+
+/// Double-buffered RGBA frames plus a frame counter, for handing frames
+/// from a worker that owns the `Emulator` to a thread that displays them
+///
+/// Both frames sit back to back in one allocation, so a web page can map
+/// them from wasm memory as a single `Uint8Array`. Frame `n` is written to
+/// buffer `n % 2` and the counter is bumped to `n` only afterwards (with
+/// release ordering), so a reader that loads the counter (`Atomics.load`
+/// on the page) and then reads buffer `counter % 2` sees a finished frame
+/// while the writer fills the other one.
+pub struct FrameExchange {
+    buffers: Vec<u8>,
+    frame: AtomicU32,
+}
+
+impl FrameExchange {
+    /// Create an exchange with two black frames and the counter at 0
+    pub fn new() -> Self {
+        Self {
+            buffers: vec![0; FRAME_RGBA_BYTES * 2],
+            frame: AtomicU32::new(0),
+        }
+    }
+
+    /// Write the emulator's current frame, with the overlay, into the back
+    /// buffer and publish it; returns the new frame number
+    pub fn publish(&mut self, emulator: &Emulator) -> u32 {
+        let frame = self.frame.load(Ordering::Relaxed).wrapping_add(1);
+        emulator.write_frame_rgba(self.buffer_mut(frame));
+        self.frame.store(frame, Ordering::Release);
+        frame
+    }
+
+    /// Number of the last published frame (0 before the first)
+    pub fn frame(&self) -> u32 {
+        self.frame.load(Ordering::Acquire)
+    }
+
+    /// The last published frame
+    pub fn front(&self) -> &[u8] {
+        self.buffer(self.frame())
+    }
+
+    /// Buffer holding frame `frame`
+    pub fn buffer(&self, frame: u32) -> &[u8] {
+        let start = (frame % 2) as usize * FRAME_RGBA_BYTES;
+        &self.buffers[start..start + FRAME_RGBA_BYTES]
+    }
+
+    fn buffer_mut(&mut self, frame: u32) -> &mut [u8] {
+        let start = (frame % 2) as usize * FRAME_RGBA_BYTES;
+        &mut self.buffers[start..start + FRAME_RGBA_BYTES]
+    }
+
+    /// Both buffers, back to back, for mapping into shared memory
+    pub fn buffers(&self) -> &[u8] {
+        &self.buffers
+    }
+
+    /// The frame counter, for mapping into shared memory
+    pub fn counter(&self) -> &AtomicU32 {
+        &self.frame
+    }
+}
+
+impl Default for FrameExchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for FrameExchange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameExchange").field("frame", &self.frame()).finish()
+    }
+}
@@ -0,0 +1,201 @@
+use std::cell::{Ref, RefCell};
+use rnes_common::{Word, Scanline};
+use crate::Bus;
+
+/// PRG ROM bank granularity used by the heat map (the smallest common mapper bank)
+pub const HEAT_MAP_PRG_BANK_SIZE: usize = 8 * 1024;
+
+/// CHR bank granularity used by the heat map (the smallest common mapper bank)
+pub const HEAT_MAP_CHR_BANK_SIZE: usize = 1024;
+
+/// Kind of CPU memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Access counts for one address or bank
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    pub reads: u32,
+    pub writes: u32,
+    pub executes: u32,
+}
+
+impl AccessCounts {
+    /// Total number of accesses of any kind
+    pub fn total(&self) -> u64 {
+        self.reads as u64 + self.writes as u64 + self.executes as u64
+    }
+
+    fn record(&mut self, kind: AccessKind) {
+        let count = match kind {
+            AccessKind::Read => &mut self.reads,
+            AccessKind::Write => &mut self.writes,
+            AccessKind::Execute => &mut self.executes,
+        };
+        *count = count.saturating_add(1);
+    }
+}
+
+/// Per-address and per-bank memory access counts over a sampling window
+///
+/// CPU addresses count reads, writes and executed opcodes (an opcode fetch
+/// counts as both a read and an execute). PRG banks count the accesses that
+/// landed in each 8KB bank of PRG ROM. CHR banks count, as reads, the
+/// visible rendered scanlines each 1KB bank of CHR was mapped for.
+#[derive(Debug, Clone)]
+pub struct AccessHeatMap {
+    cpu: Vec<AccessCounts>,
+    prg_banks: Vec<AccessCounts>,
+    chr_banks: Vec<AccessCounts>,
+    window_frames: u32,
+    frames: u32,
+    last_scanline: Option<Scanline>,
+    last_frame: Option<u64>,
+}
+
+impl AccessHeatMap {
+    /// Create an empty heat map sampling for `window_frames` frames (0 = until stopped)
+    pub fn new(prg_rom_size: usize, chr_size: usize, window_frames: u32) -> Self {
+        Self {
+            cpu: vec![AccessCounts::default(); 0x10000],
+            prg_banks: vec![AccessCounts::default(); prg_rom_size.div_ceil(HEAT_MAP_PRG_BANK_SIZE)],
+            chr_banks: vec![AccessCounts::default(); chr_size.div_ceil(HEAT_MAP_CHR_BANK_SIZE)],
+            window_frames,
+            frames: 0,
+            last_scanline: None,
+            last_frame: None,
+        }
+    }
+
+    /// Get access counts for a CPU address
+    pub fn counts(&self, addr: Word) -> AccessCounts {
+        self.cpu[addr as usize]
+    }
+
+    /// Get access counts for all 64K CPU addresses
+    pub fn cpu_counts(&self) -> &[AccessCounts] {
+        &self.cpu
+    }
+
+    /// Get access counts for each 8KB PRG ROM bank
+    pub fn prg_bank_counts(&self) -> &[AccessCounts] {
+        &self.prg_banks
+    }
+
+    /// Get access counts for each 1KB CHR bank
+    pub fn chr_bank_counts(&self) -> &[AccessCounts] {
+        &self.chr_banks
+    }
+
+    /// Number of frames sampled so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Length of the sampling window in frames (0 = until stopped)
+    pub fn window_frames(&self) -> u32 {
+        self.window_frames
+    }
+
+    /// Check if the sampling window has elapsed; counts no longer change
+    pub fn is_complete(&self) -> bool {
+        self.window_frames > 0 && self.frames >= self.window_frames
+    }
+
+    /// Clear all counts and restart the sampling window
+    pub fn clear(&mut self) {
+        self.cpu.fill(AccessCounts::default());
+        self.prg_banks.fill(AccessCounts::default());
+        self.chr_banks.fill(AccessCounts::default());
+        self.frames = 0;
+        self.last_scanline = None;
+        self.last_frame = None;
+    }
+
+    /// Record a CPU access, with the PRG ROM offset it resolved to if any
+    pub fn record(&mut self, kind: AccessKind, addr: Word, prg_offset: Option<usize>) {
+        if self.is_complete() {
+            return;
+        }
+        self.cpu[addr as usize].record(kind);
+        if let Some(bank) = prg_offset.and_then(|offset| self.prg_banks.get_mut(offset / HEAT_MAP_PRG_BANK_SIZE)) {
+            bank.record(kind);
+        }
+    }
+
+    /// Record that a CHR offset was mapped for one rendered scanline
+    fn record_chr(&mut self, chr_offset: usize) {
+        if let Some(bank) = self.chr_banks.get_mut(chr_offset / HEAT_MAP_CHR_BANK_SIZE) {
+            bank.record(AccessKind::Read);
+        }
+    }
+}
+
+impl Bus {
+    /// Start recording a memory access heat map, replacing any previous one
+    ///
+    /// Sampling stops after `window_frames` frames (0 = until stopped).
+    pub fn start_heat_map(&mut self, window_frames: u32) {
+        let prg_rom_size = self.cartridge.as_ref().map_or(0, |c| c.prg_rom.len());
+        let chr_size = self.cartridge.as_ref().map_or(0, |c| c.chr_rom.len());
+        self.heat_map = Some(RefCell::new(AccessHeatMap::new(prg_rom_size, chr_size, window_frames)));
+    }
+
+    /// Stop recording and return the collected heat map
+    pub fn stop_heat_map(&mut self) -> Option<AccessHeatMap> {
+        self.heat_map.take().map(RefCell::into_inner)
+    }
+
+    /// Get the heat map being recorded, if any
+    pub fn heat_map(&self) -> Option<Ref<'_, AccessHeatMap>> {
+        self.heat_map.as_ref().map(RefCell::borrow)
+    }
+
+    /// Clear the heat map counts and restart its sampling window
+    pub fn clear_heat_map(&mut self) {
+        if let Some(ref mut heat_map) = self.heat_map {
+            heat_map.get_mut().clear();
+        }
+    }
+
+    /// Record a CPU access in the heat map, if one is being recorded
+    pub(crate) fn record_access(&self, kind: AccessKind, addr: Word) {
+        if let Some(ref heat_map) = self.heat_map {
+            let prg_offset = self.ppu.as_ref().and_then(|ppu| ppu.mapper().prg_rom_offset(addr));
+            heat_map.borrow_mut().record(kind, addr, prg_offset);
+        }
+    }
+
+    /// Track CHR bank usage per rendered scanline and count frames
+    pub(crate) fn record_ppu_progress(&mut self) {
+        let (Some(heat_map), Some(ppu)) = (self.heat_map.as_mut(), self.ppu.as_ref()) else {
+            return;
+        };
+        let heat_map = heat_map.get_mut();
+        if heat_map.is_complete() {
+            return;
+        }
+
+        let scanline = ppu.scanline();
+        if heat_map.last_scanline != Some(scanline) {
+            heat_map.last_scanline = Some(scanline);
+            if (0..240).contains(&scanline) && ppu.rendering_enabled() {
+                for window in (0..0x2000).step_by(HEAT_MAP_CHR_BANK_SIZE) {
+                    if let Some(offset) = ppu.mapper().chr_offset(window as Word) {
+                        heat_map.record_chr(offset);
+                    }
+                }
+            }
+        }
+
+        let frame = ppu.frame_count();
+        if heat_map.last_frame.is_some_and(|last| last != frame) {
+            heat_map.frames += 1;
+        }
+        heat_map.last_frame = Some(frame);
+    }
+}
@@ -0,0 +1,141 @@
+use rnes_common::{md5, to_hex, Byte, Word};
+use rnes_cartridge::Cartridge;
+use crate::Bus;
+
+/// Side-effect-free view of CPU-visible memory
+///
+/// Addresses follow the CPU map, which is also the RetroAchievements NES
+/// memory map: $0000-$07FF work RAM (mirrored to $1FFF), $6000-$7FFF
+/// cartridge RAM and $8000-$FFFF PRG ROM. Registers read as 0 because
+/// reading them has side effects.
+pub trait MemoryPeek {
+    /// Read a byte without side effects
+    fn peek(&self, addr: Word) -> Byte;
+
+    /// Read consecutive bytes starting at `addr` (wrapping at $FFFF)
+    fn peek_range(&self, addr: Word, buffer: &mut [Byte]) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.peek(addr.wrapping_add(offset as Word));
+        }
+    }
+}
+
+impl MemoryPeek for Bus {
+    fn peek(&self, addr: Word) -> Byte {
+        match addr {
+            0x0000..=0x1FFF => self.ram[(addr & 0x07FF) as usize],
+            0x6000..=0xFFFF => self.ppu.as_ref()
+                .and_then(|ppu| ppu.mapper().read_prg(addr).ok())
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn peek_range(&self, addr: Word, buffer: &mut [Byte]) {
+        // Fast path for work RAM, which is what achievement logic reads most
+        let start = addr as usize;
+        if start + buffer.len() <= 0x0800 {
+            buffer.copy_from_slice(&self.ram[start..start + buffer.len()]);
+            return;
+        }
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.peek(addr.wrapping_add(offset as Word));
+        }
+    }
+}
+
+/// Callback run once after every completed frame
+pub type FrameCallback = Box<dyn FnMut(u64, &dyn MemoryPeek) + Send>;
+
+/// Frame callbacks for achievement logic, scripts and tools
+///
+/// Callbacks run exactly once per frame, right after the PPU finishes it
+/// (start of VBlank), no matter whether the frontend drives the emulator
+/// with `step()` or `run_frame()`.
+#[derive(Default)]
+pub struct FrameHooks {
+    callbacks: Vec<FrameCallback>,
+    last_frame: Option<u64>,
+}
+
+impl FrameHooks {
+    /// Create empty hook list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a frame callback
+    pub fn add(&mut self, callback: FrameCallback) {
+        self.callbacks.push(callback);
+    }
+
+    /// Remove all callbacks
+    pub fn clear(&mut self) {
+        self.callbacks.clear();
+    }
+
+    /// Get number of registered callbacks
+    pub fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Check if no callbacks are registered
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// Run callbacks if `frame` differs from the last frame seen
+    ///
+    /// Returns `true` when a new frame was reported.
+    pub fn notify(&mut self, frame: u64, memory: &dyn MemoryPeek) -> bool {
+        match self.last_frame.replace(frame) {
+            Some(last) if last != frame => {}
+            // First observation only establishes the baseline
+            _ => return false,
+        }
+        for callback in &mut self.callbacks {
+            callback(frame, memory);
+        }
+        true
+    }
+
+    /// Forget the last frame, e.g. after a reset or state load
+    pub fn reset(&mut self) {
+        self.last_frame = None;
+    }
+}
+
+impl std::fmt::Debug for FrameHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameHooks")
+            .field("callbacks", &self.callbacks.len())
+            .field("last_frame", &self.last_frame)
+            .finish()
+    }
+}
+
+/// RetroAchievements hash of an NES ROM file
+///
+/// Per the RA spec this is the MD5 of the file contents, skipping the
+/// 16-byte iNES header if present.
+pub fn ra_hash(data: &[u8]) -> String {
+    let body = if data.starts_with(b"NES\x1A") && data.len() >= 16 {
+        &data[16..]
+    } else {
+        data
+    };
+    to_hex(&md5(body))
+}
+
+/// RetroAchievements hash of a loaded cartridge
+///
+/// Matches `ra_hash` of the original file unless it had a trainer or
+/// trailing data, which the cartridge does not keep.
+pub fn ra_hash_cartridge(cartridge: &Cartridge) -> String {
+    let mut hasher = rnes_common::Md5::new();
+    hasher.update(&cartridge.prg_rom);
+    if cartridge.header.chr_rom_size > 0 {
+        hasher.update(&cartridge.chr_rom);
+    }
+    to_hex(&hasher.finish())
+}
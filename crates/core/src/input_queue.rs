@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use rnes_common::{Byte, Button, ControllerState};
+use crate::Emulator;
+
+/// Button bytes for controllers 1 and 2 on one frame
+pub type FrameInput = [Byte; 2];
+
+/// Controller input latched once per frame
+///
+/// Live play updates the held input whenever the host reports a change;
+/// movies and netplay queue the input of a given frame ahead of time. As each
+/// frame starts, the input queued for it, or else the held input, is latched
+/// onto the controllers, so a frame never sees input change halfway through
+/// and replays see exactly the input they recorded.
+#[derive(Debug, Clone, Default)]
+pub struct InputQueue {
+    held: FrameInput,
+    queued: BTreeMap<u64, FrameInput>,
+    latched_frame: Option<u64>,
+}
+
+impl InputQueue {
+    /// Create an empty queue with no buttons held
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Input latched on frames with nothing queued
+    pub fn held(&self) -> FrameInput {
+        self.held
+    }
+
+    /// Set the held buttons of controller `port` (0 or 1)
+    pub fn set_held(&mut self, port: usize, buttons: Byte) {
+        if let Some(slot) = self.held.get_mut(port) {
+            *slot = buttons;
+        }
+    }
+
+    /// Press or release one held button of controller `port`
+    pub fn set_held_button(&mut self, port: usize, button: Button, pressed: bool) {
+        if let Some(slot) = self.held.get_mut(port) {
+            let mut state = ControllerState::from_bits(*slot);
+            state.set_button(button, pressed);
+            *slot = state.bits();
+        }
+    }
+
+    /// Queue the input for PPU frame `frame`, replacing any queued before
+    pub fn queue(&mut self, frame: u64, input: FrameInput) {
+        self.queued.insert(frame, input);
+    }
+
+    /// Input queued for `frame`, if any
+    pub fn queued(&self, frame: u64) -> Option<FrameInput> {
+        self.queued.get(&frame).copied()
+    }
+
+    /// Number of frames with queued input
+    pub fn queued_len(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Drop all queued input and release the held buttons
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Input to latch as `frame` starts, or `None` if it already was
+    ///
+    /// Queued input for earlier frames can no longer apply and is dropped.
+    fn latch(&mut self, frame: u64) -> Option<FrameInput> {
+        if self.latched_frame == Some(frame) {
+            return None;
+        }
+        self.latched_frame = Some(frame);
+        self.queued = self.queued.split_off(&frame);
+        let input = self.queued.remove(&frame).unwrap_or(self.held);
+        Some(input)
+    }
+}
+
+impl Emulator {
+    /// Set the buttons of controller `port` (0 or 1) from the next frame on
+    ///
+    /// This is the input path for live play. Unlike `set_controller1`, the
+    /// change waits for the frame in progress to finish.
+    pub fn set_input(&mut self, port: usize, buttons: Byte) {
+        self.boot_pristine &= buttons == 0;
+        self.input.set_held(port, buttons);
+    }
+
+    /// Queue the input of both controllers for PPU frame `frame`
+    ///
+    /// Movies and netplay use this to apply recorded or confirmed input to
+    /// exactly the frame it belongs to. `next_input_frame` is the first frame
+    /// that can still take it.
+    pub fn queue_input(&mut self, frame: u64, input: FrameInput) {
+        self.boot_pristine &= input == [0, 0];
+        self.input.queue(frame, input);
+    }
+
+    /// PPU frame that input set or queued now is latched on
+    pub fn next_input_frame(&self) -> u64 {
+        let Some(ref ppu) = self.bus.ppu else {
+            return 0;
+        };
+        match self.input.latched_frame {
+            Some(frame) if frame == ppu.frame_count() => frame + 1,
+            _ => ppu.frame_count(),
+        }
+    }
+
+    /// Latch the frame's input onto the controllers if a new frame started
+    pub(crate) fn latch_input(&mut self) {
+        let Some(ref ppu) = self.bus.ppu else {
+            return;
+        };
+        if let Some(input) = self.input.latch(ppu.frame_count()) {
+            self.bus.set_controller1(ControllerState::from_bits(input[0]));
+            self.bus.set_controller2(ControllerState::from_bits(input[1]));
+        }
+    }
+
+    /// Latch input again when the next instruction runs, e.g. after a state load
+    pub(crate) fn relatch_input(&mut self) {
+        self.input.latched_frame = None;
+    }
+}
@@ -0,0 +1,58 @@
+use rnes_common::IrqSource;
+
+/// Level of the CPU's shared IRQ line
+///
+/// The APU frame counter, the DMC and the mapper each hold the line
+/// asserted until the game acknowledges them through their own registers:
+/// reading $4015 or setting the $4017 inhibit bit for the frame counter,
+/// writing $4015 for the DMC, and a mapper-specific write for the mapper.
+/// The bus polls every source after each CPU step, so the CPU sees the
+/// line as it would on hardware and keeps taking the IRQ while any source
+/// is asserted and the I flag is clear.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IrqController {
+    asserted: u8,
+}
+
+impl IrqController {
+    /// Create a controller with no source asserted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the level of one source, returning whether it was just asserted
+    pub fn set(&mut self, source: IrqSource, asserted: bool) -> bool {
+        let bit = Self::bit(source);
+        let rising = asserted && self.asserted & bit == 0;
+        if asserted {
+            self.asserted |= bit;
+        } else {
+            self.asserted &= !bit;
+        }
+        rising
+    }
+
+    /// Check if `source` is asserting the line
+    pub fn is_asserted(&self, source: IrqSource) -> bool {
+        self.asserted & Self::bit(source) != 0
+    }
+
+    /// Check if any source is asserting the line
+    pub fn line(&self) -> bool {
+        self.asserted != 0
+    }
+
+    /// Sources asserting the line
+    pub fn sources(&self) -> Vec<IrqSource> {
+        IrqSource::ALL.into_iter().filter(|&source| self.is_asserted(source)).collect()
+    }
+
+    /// Release every source
+    pub fn clear(&mut self) {
+        self.asserted = 0;
+    }
+
+    fn bit(source: IrqSource) -> u8 {
+        1 << source as u8
+    }
+}
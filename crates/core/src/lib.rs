@@ -1,8 +1,59 @@
 pub mod bus;
 pub mod emulator;
+pub mod env;
+pub mod pacer;
+pub mod session;
+pub mod rom_download;
+pub mod input_queue;
+pub mod zapper;
+pub mod hooks;
+pub mod overlay;
+pub mod debug_memory;
+pub mod heat_map;
+pub mod coverage;
+pub mod mapper_events;
+pub mod reverse;
+pub mod trace;
+pub mod profiler;
+pub mod stems;
+pub mod audio_sink;
+pub mod watchdog;
+pub mod interrupts;
+pub mod timeline;
+pub mod ppu_write_log;
+pub mod viewport;
+pub mod post_process;
+pub mod frame_exchange;
+#[cfg(feature = "achievements")]
+pub mod achievements;
 
 pub use bus::*;
 pub use emulator::*;
+pub use env::*;
+pub use pacer::*;
+pub use session::*;
+pub use rom_download::*;
+pub use input_queue::*;
+pub use zapper::*;
+pub use hooks::*;
+pub use overlay::*;
+pub use debug_memory::*;
+pub use heat_map::*;
+pub use coverage::*;
+pub use reverse::*;
+pub use trace::*;
+pub use profiler::*;
+pub use stems::*;
+pub use audio_sink::*;
+pub use watchdog::*;
+pub use interrupts::*;
+pub use timeline::*;
+pub use ppu_write_log::*;
+pub use viewport::*;
+pub use post_process::*;
+pub use frame_exchange::*;
+#[cfg(feature = "achievements")]
+pub use achievements::*;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,107 @@
+use rnes_common::{Byte, Word, MapperEvent, MapperEventKind};
+use rnes_common::debugger::MemoryAccess as MemoryAccessRecord;
+use rnes_cartridge::Mirroring;
+use rnes_ppu::Ppu;
+use crate::Bus;
+
+/// Bank and mirroring layout a mapper presents, compared around register writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MapperLayout {
+    prg: [Option<usize>; 4],
+    chr: [Option<usize>; 8],
+    mirroring: Mirroring,
+}
+
+impl MapperLayout {
+    fn capture(ppu: &Ppu) -> Self {
+        let mapper = ppu.mapper();
+        Self {
+            prg: std::array::from_fn(|i| mapper.prg_rom_offset(0x8000 + i as Word * 0x2000)),
+            chr: std::array::from_fn(|i| mapper.chr_offset(i as Word * 0x0400)),
+            mirroring: mapper.mirroring(),
+        }
+    }
+}
+
+/// Collects mapper events while the debugger has mapper breakpoints set
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MapperWatch {
+    pc: Word,
+    cycle: u64,
+    events: Vec<MapperEvent>,
+}
+
+impl Bus {
+    /// Start or stop collecting mapper events
+    pub fn watch_mapper_events(&mut self, enabled: bool) {
+        if enabled != self.mapper_watch.is_some() {
+            self.mapper_watch = enabled.then(MapperWatch::default);
+        }
+    }
+
+    /// Take the mapper events collected since the last call
+    pub fn take_mapper_events(&mut self) -> Vec<MapperEvent> {
+        self.mapper_watch.as_mut().map(|watch| std::mem::take(&mut watch.events)).unwrap_or_default()
+    }
+
+    /// Note the instruction about to execute, so events can name it
+    pub(crate) fn begin_mapper_watch(&mut self, pc: Word, cycle: u64) {
+        if let Some(ref mut watch) = self.mapper_watch {
+            watch.pc = pc;
+            watch.cycle = cycle;
+        }
+    }
+
+    /// Write a mapper register, recording any bank or mirroring change it causes
+    pub(crate) fn write_mapper_watched(&mut self, addr: Word, value: Byte) -> rnes_common::RnesResult<()> {
+        let Some(ppu) = self.ppu.as_mut() else {
+            return Ok(());
+        };
+        let Some(watch) = self.mapper_watch.as_mut() else {
+            return ppu.mapper_mut().write_prg(addr, value);
+        };
+
+        let before = MapperLayout::capture(ppu);
+        ppu.mapper_mut().write_prg(addr, value)?;
+        let after = MapperLayout::capture(ppu);
+        if before == after {
+            return Ok(());
+        }
+
+        let write = MemoryAccessRecord {
+            address: addr,
+            value,
+            is_write: true,
+            cycle: watch.cycle,
+            pc: watch.pc,
+        };
+        let changes = [
+            (before.prg != after.prg, MapperEventKind::PrgBankSwitch),
+            (before.chr != after.chr, MapperEventKind::ChrBankSwitch),
+            (before.mirroring != after.mirroring, MapperEventKind::MirroringChange),
+        ];
+        for (changed, kind) in changes {
+            if changed {
+                watch.events.push(MapperEvent {
+                    kind,
+                    write: Some(write.clone()),
+                    scanline: ppu.scanline(),
+                    dot: ppu.dot(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Record an IRQ asserted by the mapper
+    pub(crate) fn record_mapper_irq(&mut self) {
+        if let (Some(watch), Some(ppu)) = (self.mapper_watch.as_mut(), self.ppu.as_ref()) {
+            watch.events.push(MapperEvent {
+                kind: MapperEventKind::Irq,
+                write: None,
+                scanline: ppu.scanline(),
+                dot: ppu.dot(),
+            });
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use rnes_common::{ControllerState, Pixel, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::PacerStats;
+
+/// 3x5 font for ASCII 0x20-0x5F, three bits per row, top row first
+const FONT: [u16; 64] = [
+    0x0000, 0x2482, 0x5A00, 0x5F7D, 0x3C9E, 0x52A5, 0x2AAB, 0x2400,
+    0x1491, 0x4494, 0x0AA8, 0x05D0, 0x0014, 0x01C0, 0x0002, 0x12A4,
+    0x7B6F, 0x2C97, 0x73E7, 0x72CF, 0x5BC9, 0x79CF, 0x79EF, 0x7252,
+    0x7BEF, 0x7BCF, 0x0410, 0x0414, 0x1511, 0x0E38, 0x4454, 0x7282,
+    0x7BE7, 0x2BED, 0x6BAE, 0x3923, 0x6B6E, 0x79A7, 0x79A4, 0x396B,
+    0x5BED, 0x7497, 0x126A, 0x5BAD, 0x4927, 0x5FED, 0x6B6D, 0x2B6A,
+    0x6BA4, 0x2B73, 0x6BAD, 0x388E, 0x7492, 0x5B6F, 0x5B6A, 0x5BFD,
+    0x5AAD, 0x5A92, 0x72A7, 0x6926, 0x4889, 0x324B, 0x2A00, 0x0007,
+];
+
+/// Horizontal advance per character
+pub const CHAR_WIDTH: i32 = 4;
+/// Vertical advance per line
+pub const LINE_HEIGHT: i32 = 6;
+
+/// Translucent backdrop behind built-in widgets
+const BACKDROP: Pixel = Pixel { r: 0, g: 0, b: 0, a: 160 };
+const PRESSED: Pixel = Pixel::WHITE;
+const RELEASED: Pixel = Pixel { r: 96, g: 96, b: 96, a: 255 };
+
+/// Immediate-mode drawing on a pixel buffer
+///
+/// Coordinates are clipped, so callers can draw partly off-screen. Colors
+/// with alpha below 255 are blended over the existing pixels.
+#[derive(Debug)]
+pub struct Canvas<'a> {
+    pixels: &'a mut [Pixel],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Canvas<'a> {
+    /// Wrap a `width` x `height` pixel buffer
+    pub fn new(pixels: &'a mut [Pixel], width: usize, height: usize) -> Self {
+        debug_assert!(pixels.len() >= width * height);
+        Self { pixels, width, height }
+    }
+
+    /// Wrap a 256x240 NES frame
+    pub fn for_frame(pixels: &'a mut [Pixel]) -> Self {
+        Self::new(pixels, SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    /// Draw a single pixel
+    pub fn pixel(&mut self, x: i32, y: i32, color: Pixel) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height || color.a == 0 {
+            return;
+        }
+        let dst = &mut self.pixels[y as usize * self.width + x as usize];
+        *dst = blend(*dst, color);
+    }
+
+    /// Draw a rectangle outline
+    pub fn rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Pixel) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width as i32 - 1, y + height as i32 - 1);
+        for px in x..=right {
+            self.pixel(px, y, color);
+            if bottom != y {
+                self.pixel(px, bottom, color);
+            }
+        }
+        for py in y + 1..bottom {
+            self.pixel(x, py, color);
+            if right != x {
+                self.pixel(right, py, color);
+            }
+        }
+    }
+
+    /// Draw a filled rectangle
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Pixel) {
+        for py in y..y + height as i32 {
+            for px in x..x + width as i32 {
+                self.pixel(px, py, color);
+            }
+        }
+    }
+
+    /// Draw text with the built-in 3x5 font (`\n` starts a new line)
+    ///
+    /// Lowercase letters are drawn as uppercase; other unsupported
+    /// characters are drawn as `?`.
+    pub fn text(&mut self, x: i32, y: i32, text: &str, color: Pixel) {
+        let (mut cx, mut cy) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                cx = x;
+                cy += LINE_HEIGHT;
+                continue;
+            }
+            let glyph = glyph(c);
+            for row in 0..5 {
+                let bits = (glyph >> (12 - row * 3)) & 0x7;
+                for col in 0..3 {
+                    if bits & (0x4 >> col) != 0 {
+                        self.pixel(cx + col, cy + row, color);
+                    }
+                }
+            }
+            cx += CHAR_WIDTH;
+        }
+    }
+}
+
+/// Get size in pixels of `text` drawn with `Canvas::text`
+pub fn text_size(text: &str) -> (u32, u32) {
+    let lines = text.split('\n');
+    let (mut width, mut count) = (0, 0);
+    for line in lines {
+        width = width.max(line.chars().count() as u32 * CHAR_WIDTH as u32);
+        count += 1;
+    }
+    (width.saturating_sub(1), (count * LINE_HEIGHT as u32).saturating_sub(1))
+}
+
+fn glyph(c: char) -> u16 {
+    let c = c.to_ascii_uppercase();
+    match c as u32 {
+        code @ 0x20..=0x5F => FONT[(code - 0x20) as usize],
+        _ => FONT[('?' as u32 - 0x20) as usize],
+    }
+}
+
+fn blend(dst: Pixel, src: Pixel) -> Pixel {
+    if src.a == 255 {
+        return src;
+    }
+    let mix = |d: u8, s: u8| ((s as u32 * src.a as u32 + d as u32 * (255 - src.a as u32)) / 255) as u8;
+    Pixel {
+        r: mix(dst.r, src.r),
+        g: mix(dst.g, src.g),
+        b: mix(dst.b, src.b),
+        a: dst.a,
+    }
+}
+
+/// Queued overlay drawing command
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Pixel { x: i32, y: i32, color: Pixel },
+    Rect { x: i32, y: i32, width: u32, height: u32, color: Pixel, filled: bool },
+    Text { x: i32, y: i32, text: String, color: Pixel },
+}
+
+/// Live values shown by the built-in widgets
+#[derive(Debug, Clone, Default)]
+pub struct OverlayStatus {
+    pub controller1: ControllerState,
+    pub controller2: ControllerState,
+    /// Lag frames so far, if tracked
+    pub lag_frames: Option<u64>,
+    /// Where a plugged-in Zapper points, if on screen
+    pub zapper_aim: Option<(u16, u16)>,
+}
+
+/// Overlay composited on top of the frame before frontends get it
+///
+/// Built-in widgets (input display, FPS, lag counter, frame pacing, Zapper crosshair) are toggled with the
+/// `show_*` flags. Custom drawing from the debugger or scripts is queued
+/// with `pixel`, `rect`, `fill_rect` and `text` and stays until `clear`.
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    pub show_input: bool,
+    pub show_fps: bool,
+    pub show_lag: bool,
+    pub show_pacing: bool,
+    pub show_crosshair: bool,
+    fps: Option<f32>,
+    pacing: Option<PacerStats>,
+    commands: Vec<DrawCommand>,
+}
+
+impl Overlay {
+    /// Create empty overlay
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set measured frames per second (wall-clock time is up to the frontend)
+    pub fn set_fps(&mut self, fps: f32) {
+        self.fps = Some(fps);
+    }
+
+    /// Set frame pacing statistics from the frontend's pacer
+    pub fn set_pacer_stats(&mut self, stats: PacerStats) {
+        self.pacing = Some(stats);
+    }
+
+    /// Queue a pixel
+    pub fn pixel(&mut self, x: i32, y: i32, color: Pixel) {
+        self.commands.push(DrawCommand::Pixel { x, y, color });
+    }
+
+    /// Queue a rectangle outline
+    pub fn rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Pixel) {
+        self.commands.push(DrawCommand::Rect { x, y, width, height, color, filled: false });
+    }
+
+    /// Queue a filled rectangle
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Pixel) {
+        self.commands.push(DrawCommand::Rect { x, y, width, height, color, filled: true });
+    }
+
+    /// Queue text
+    pub fn text(&mut self, x: i32, y: i32, text: &str, color: Pixel) {
+        self.commands.push(DrawCommand::Text { x, y, text: text.to_string(), color });
+    }
+
+    /// Remove all queued commands
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Get queued commands
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    /// Check if composing would draw anything
+    pub fn is_active(&self) -> bool {
+        self.show_input || self.show_fps || self.show_lag || self.show_pacing || self.show_crosshair
+            || !self.commands.is_empty()
+    }
+
+    /// Draw the overlay onto a 256x240 frame
+    pub fn compose(&self, pixels: &mut [Pixel], status: &OverlayStatus) {
+        let mut canvas = Canvas::for_frame(pixels);
+
+        for command in &self.commands {
+            match command {
+                DrawCommand::Pixel { x, y, color } => canvas.pixel(*x, *y, *color),
+                DrawCommand::Rect { x, y, width, height, color, filled: false } => {
+                    canvas.rect(*x, *y, *width, *height, *color)
+                }
+                DrawCommand::Rect { x, y, width, height, color, filled: true } => {
+                    canvas.fill_rect(*x, *y, *width, *height, *color)
+                }
+                DrawCommand::Text { x, y, text, color } => canvas.text(*x, *y, text, *color),
+            }
+        }
+
+        // Counters stack down from the top-left corner
+        let mut y = 2;
+        if self.show_fps {
+            let text = match self.fps {
+                Some(fps) => format!("FPS {:.1}", fps),
+                None => "FPS --".to_string(),
+            };
+            label(&mut canvas, 2, y, &text);
+            y += LINE_HEIGHT + 2;
+        }
+        if self.show_lag {
+            if let Some(lag) = status.lag_frames {
+                label(&mut canvas, 2, y, &format!("LAG {}", lag));
+                y += LINE_HEIGHT + 2;
+            }
+        }
+        if self.show_pacing {
+            if let Some(stats) = self.pacing {
+                label(&mut canvas, 2, y, &format!("VSYNC {:.1}MS JIT {:.2}", stats.refresh_interval_ms, stats.jitter_ms));
+                y += LINE_HEIGHT + 2;
+                label(&mut canvas, 2, y, &format!("AUDIO X{:.4} REP {}", stats.audio_ratio, stats.frames_repeated));
+            }
+        }
+
+        if self.show_input {
+            let bottom = SCREEN_HEIGHT as i32 - LINE_HEIGHT - 3;
+            input_display(&mut canvas, 2, bottom - LINE_HEIGHT - 2, "1", &status.controller1);
+            input_display(&mut canvas, 2, bottom, "2", &status.controller2);
+        }
+
+        if self.show_crosshair {
+            if let Some((x, y)) = status.zapper_aim {
+                crosshair(&mut canvas, x as i32, y as i32);
+            }
+        }
+    }
+}
+
+/// Draw a crosshair with a gap in the middle, so the aimed pixel stays visible
+fn crosshair(canvas: &mut Canvas, x: i32, y: i32) {
+    for offset in 2..=5 {
+        for (dx, dy) in [(offset, 0), (-offset, 0), (0, offset), (0, -offset)] {
+            canvas.pixel(x + dx, y + dy, PRESSED);
+        }
+    }
+}
+
+/// Draw text on a translucent backdrop
+fn label(canvas: &mut Canvas, x: i32, y: i32, text: &str) {
+    let (width, height) = text_size(text);
+    canvas.fill_rect(x - 1, y - 1, width + 2, height + 2, BACKDROP);
+    canvas.text(x, y, text, Pixel::WHITE);
+}
+
+/// Draw one controller as `<^V> SS BA`, pressed buttons highlighted
+fn input_display(canvas: &mut Canvas, x: i32, y: i32, port: &str, state: &ControllerState) {
+    use rnes_common::Button;
+
+    const LAYOUT: [(Button, &str, i32); 8] = [
+        (Button::Left, "<", 2),
+        (Button::Up, "^", 3),
+        (Button::Down, "V", 4),
+        (Button::Right, ">", 5),
+        (Button::Select, "S", 7),
+        (Button::Start, "S", 8),
+        (Button::B, "B", 10),
+        (Button::A, "A", 11),
+    ];
+
+    let (width, height) = text_size("1 <^V> SS BA");
+    canvas.fill_rect(x - 1, y - 1, width + 2, height + 2, BACKDROP);
+    canvas.text(x, y, port, Pixel::WHITE);
+    for (button, text, column) in LAYOUT {
+        let color = if state.is_pressed(button) { PRESSED } else { RELEASED };
+        canvas.text(x + column * CHAR_WIDTH, y, text, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_clipping_and_blending() {
+        let mut pixels = vec![Pixel::BLACK; 4 * 4];
+        let mut canvas = Canvas::new(&mut pixels, 4, 4);
+        canvas.fill_rect(-2, -2, 10, 10, Pixel { r: 255, g: 255, b: 255, a: 128 });
+        canvas.pixel(0, 0, Pixel::new(255, 0, 0));
+        assert_eq!(pixels[0], Pixel::new(255, 0, 0));
+        assert_eq!(pixels[15].r, 128);
+    }
+
+    #[test]
+    fn test_text_rendering() {
+        let mut pixels = vec![Pixel::BLACK; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut canvas = Canvas::for_frame(&mut pixels);
+        canvas.text(0, 0, "1", Pixel::WHITE);
+
+        // "1" is .#. / ##. / .#. / .#. / ###
+        let lit = |x: usize, y: usize| pixels[y * SCREEN_WIDTH + x] == Pixel::WHITE;
+        assert!(lit(1, 0) && !lit(0, 0));
+        assert!(lit(0, 1) && lit(1, 1));
+        assert!(lit(0, 4) && lit(1, 4) && lit(2, 4));
+        assert_eq!(text_size("AB\nC"), (7, 11));
+    }
+
+    #[test]
+    fn test_overlay_only_draws_when_active() {
+        let mut overlay = Overlay::new();
+        assert!(!overlay.is_active());
+
+        overlay.show_input = true;
+        let mut pixels = vec![Pixel::BLACK; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let status = OverlayStatus {
+            controller1: ControllerState::from_bits(0x01),
+            ..OverlayStatus::default()
+        };
+        overlay.compose(&mut pixels, &status);
+        assert!(pixels.contains(&PRESSED));
+        assert!(pixels.contains(&RELEASED));
+    }
+
+    #[test]
+    fn test_crosshair_leaves_aimed_pixel_visible() {
+        let overlay = Overlay { show_crosshair: true, ..Overlay::new() };
+        let mut pixels = vec![Pixel::BLACK; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let status = OverlayStatus {
+            zapper_aim: Some((100, 50)),
+            ..OverlayStatus::default()
+        };
+        overlay.compose(&mut pixels, &status);
+        let at = |x: usize, y: usize| pixels[y * SCREEN_WIDTH + x];
+        assert_eq!(at(100, 50), Pixel::BLACK);
+        assert_eq!(at(103, 50), PRESSED);
+        assert_eq!(at(100, 45), PRESSED);
+    }
+}
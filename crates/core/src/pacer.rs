@@ -0,0 +1,249 @@
+/// NTSC frame rate (CPU clock / cycles per frame)
+pub const NTSC_FRAME_RATE: f64 = 60.0988;
+
+/// Converts host timestamps into a number of emulated frames to run
+///
+/// Timestamps are in milliseconds, matching `requestAnimationFrame` and
+/// `performance.now()`. When the host falls behind (e.g. a throttled
+/// background tab) the pacer catches up by at most `max_catch_up` frames per
+/// tick and drops the rest instead of fast-forwarding.
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    frame_duration: f64,
+    max_catch_up: u32,
+    accumulator: f64,
+    last_timestamp: Option<f64>,
+    dropped: u64,
+}
+
+impl FramePacer {
+    /// Create pacer for the NTSC frame rate
+    pub fn new() -> Self {
+        Self::with_frame_rate(NTSC_FRAME_RATE)
+    }
+
+    /// Create pacer for a console region's frame rate
+    pub fn for_region(region: rnes_common::Region) -> Self {
+        Self::with_frame_rate(region.frame_rate())
+    }
+
+    /// Create pacer for a custom frame rate
+    pub fn with_frame_rate(frame_rate: f64) -> Self {
+        Self {
+            frame_duration: 1000.0 / frame_rate,
+            max_catch_up: 4,
+            accumulator: 0.0,
+            last_timestamp: None,
+            dropped: 0,
+        }
+    }
+
+    /// Set the maximum number of frames run in a single tick
+    pub fn with_max_catch_up(mut self, frames: u32) -> Self {
+        self.max_catch_up = frames.max(1);
+        self
+    }
+
+    /// Get frame duration in milliseconds
+    pub fn frame_duration(&self) -> f64 {
+        self.frame_duration
+    }
+
+    /// Frames that fell due but were dropped to stay within `max_catch_up`
+    pub fn frames_dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Advance to `timestamp` (ms) and return how many frames should run
+    pub fn tick(&mut self, timestamp: f64) -> u32 {
+        let last = match self.last_timestamp.replace(timestamp) {
+            Some(last) => last,
+            None => {
+                // First tick after start/resume: show one frame right away
+                self.accumulator = 0.0;
+                return 1;
+            }
+        };
+
+        self.accumulator += (timestamp - last).max(0.0);
+
+        let mut frames = (self.accumulator / self.frame_duration) as u32;
+        self.accumulator -= frames as f64 * self.frame_duration;
+
+        if frames > self.max_catch_up {
+            self.dropped += (frames - self.max_catch_up) as u64;
+            frames = self.max_catch_up;
+            self.accumulator = 0.0;
+        }
+        frames
+    }
+
+    /// Forget the last timestamp, e.g. after pausing
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+        self.last_timestamp = None;
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Refresh rates closer than this (relative) to the frame rate run one
+/// emulated frame per refresh
+pub const REFRESH_LOCK_TOLERANCE: f64 = 0.01;
+
+/// Largest audio resampling correction applied for buffer fill (0.5%)
+pub const MAX_AUDIO_ADJUST: f64 = 0.005;
+
+/// Timing measured by `RefreshPacer`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PacerStats {
+    /// Emulated frames run
+    pub frames_run: u64,
+    /// Refreshes that showed the previous frame again
+    pub frames_repeated: u64,
+    /// Refreshes that ran more than one frame
+    pub frames_doubled: u64,
+    /// Smoothed time between refreshes (ms)
+    pub refresh_interval_ms: f64,
+    /// Smoothed deviation of that time from the display's period (ms)
+    pub jitter_ms: f64,
+    /// Output audio samples per emulated sample, last requested
+    pub audio_ratio: f64,
+}
+
+/// Schedules emulated frames against display refreshes (vsync)
+///
+/// When the display runs within `REFRESH_LOCK_TOLERANCE` of the console
+/// (60 Hz for NTSC), every refresh runs exactly one frame and audio is
+/// stretched by the small rate difference, so motion never stutters. Other
+/// combinations, such as 50 Hz PAL content on a 60 Hz display, run frames
+/// as they fall due and occasionally repeat one; audio then plays at its
+/// own rate. In both cases `audio_ratio` nudges the resampling rate to keep
+/// the audio queue at its target.
+#[derive(Debug, Clone)]
+pub struct RefreshPacer {
+    frame_rate: f64,
+    refresh_rate: f64,
+    locked: bool,
+    frames_owed: f64,
+    last_refresh: Option<f64>,
+    stats: PacerStats,
+}
+
+impl RefreshPacer {
+    /// Create pacer for `frame_rate` content on a `refresh_rate` display
+    pub fn new(frame_rate: f64, refresh_rate: f64) -> Self {
+        let locked = ((refresh_rate - frame_rate) / frame_rate).abs() <= REFRESH_LOCK_TOLERANCE;
+        Self {
+            frame_rate,
+            refresh_rate,
+            locked,
+            frames_owed: 0.0,
+            last_refresh: None,
+            stats: PacerStats {
+                refresh_interval_ms: 1000.0 / refresh_rate,
+                audio_ratio: 1.0,
+                ..PacerStats::default()
+            },
+        }
+    }
+
+    /// Create pacer for a console region on a `refresh_rate` display
+    pub fn for_region(region: rnes_common::Region, refresh_rate: f64) -> Self {
+        Self::new(region.frame_rate(), refresh_rate)
+    }
+
+    /// Check if every refresh runs exactly one frame
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Display refresh period in milliseconds
+    pub fn refresh_interval(&self) -> f64 {
+        1000.0 / self.refresh_rate
+    }
+
+    /// Report a refresh at `timestamp` (ms) and return how many frames to run
+    pub fn on_refresh(&mut self, timestamp: f64) -> u32 {
+        if let Some(last) = self.last_refresh.replace(timestamp) {
+            let interval = (timestamp - last).max(0.0);
+            let stats = &mut self.stats;
+            stats.refresh_interval_ms += (interval - stats.refresh_interval_ms) / 16.0;
+            let deviation = (interval - 1000.0 / self.refresh_rate).abs();
+            stats.jitter_ms += (deviation - stats.jitter_ms) / 16.0;
+        }
+
+        let frames = if self.locked {
+            1
+        } else {
+            self.frames_owed += self.frame_rate / self.refresh_rate;
+            let frames = self.frames_owed as u32;
+            self.frames_owed -= frames as f64;
+            frames
+        };
+
+        match frames {
+            0 => self.stats.frames_repeated += 1,
+            1 => {}
+            _ => self.stats.frames_doubled += 1,
+        }
+        self.stats.frames_run += frames as u64;
+        frames
+    }
+
+    /// Output samples to produce per emulated audio sample
+    ///
+    /// `queue_fill` is the queued audio relative to the target latency
+    /// (1.0 = on target). An emptying queue stretches the audio slightly,
+    /// a filling one squeezes it, by at most `MAX_AUDIO_ADJUST`.
+    pub fn audio_ratio(&mut self, queue_fill: f64) -> f64 {
+        let base = if self.locked { self.frame_rate / self.refresh_rate } else { 1.0 };
+        let adjust = ((1.0 - queue_fill) * MAX_AUDIO_ADJUST).clamp(-MAX_AUDIO_ADJUST, MAX_AUDIO_ADJUST);
+        self.stats.audio_ratio = base * (1.0 + adjust);
+        self.stats.audio_ratio
+    }
+
+    /// Get timing statistics
+    pub fn stats(&self) -> PacerStats {
+        self.stats
+    }
+
+    /// Forget the last refresh and owed frames, e.g. after pausing
+    pub fn reset(&mut self) {
+        self.frames_owed = 0.0;
+        self.last_refresh = None;
+    }
+}
+
+/// Resamples audio by a varying ratio with linear interpolation
+#[derive(Debug, Clone, Default)]
+pub struct AudioStretcher {
+    /// Position of the next output sample, relative to the previous input
+    position: f64,
+    previous: f32,
+}
+
+impl AudioStretcher {
+    /// Create stretcher
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resample `input`, appending about `input.len() * ratio` samples to `out`
+    pub fn process(&mut self, input: &[f32], ratio: f64, out: &mut Vec<f32>) {
+        let step = 1.0 / ratio.max(f64::EPSILON);
+        for &sample in input {
+            while self.position < 1.0 {
+                let t = self.position as f32;
+                out.push(self.previous + (sample - self.previous) * t);
+                self.position += step;
+            }
+            self.position -= 1.0;
+            self.previous = sample;
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use rnes_common::{CrtPreset, VideoConfig};
+
+/// Settings of the CRT shader pass, laid out as its uniform block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrtParams {
+    /// How dark the gaps between lines are (0.0 - 1.0)
+    pub scanline_intensity: f32,
+    /// How much the phosphor mask darkens off-color subpixels (0.0 - 1.0)
+    pub mask_strength: f32,
+    /// Mask layout: 0 aperture grille stripes, 1 shadow mask dots
+    pub mask_kind: u32,
+    /// Barrel distortion; 0.0 is flat
+    pub curvature: f32,
+    /// How much the corners are darkened (0.0 - 1.0)
+    pub vignette: f32,
+}
+
+impl CrtParams {
+    /// Settings of a built-in preset; scanline darkness comes from the config
+    ///
+    /// Returns `None` for `CrtPreset::None`.
+    pub fn for_preset(preset: CrtPreset, scanline_intensity: f32) -> Option<Self> {
+        let scanline_intensity = scanline_intensity.clamp(0.0, 1.0);
+        let flat = Self {
+            scanline_intensity,
+            mask_strength: 0.0,
+            mask_kind: 0,
+            curvature: 0.0,
+            vignette: 0.0,
+        };
+        match preset {
+            CrtPreset::None => None,
+            CrtPreset::Scanlines => Some(flat),
+            CrtPreset::ApertureGrille => Some(Self { mask_strength: 0.3, ..flat }),
+            CrtPreset::Curved => Some(Self {
+                mask_strength: 0.25,
+                mask_kind: 1,
+                curvature: 0.08,
+                vignette: 0.3,
+                ..flat
+            }),
+        }
+    }
+
+    /// Uniform block contents: the five settings, then the source and
+    /// output sizes in pixels
+    pub fn uniforms(&self, source: (u32, u32), output: (u32, u32)) -> [f32; 10] {
+        [
+            self.scanline_intensity,
+            self.mask_strength,
+            self.mask_kind as f32,
+            self.curvature,
+            self.vignette,
+            0.0,
+            source.0 as f32,
+            source.1 as f32,
+            output.0 as f32,
+            output.1 as f32,
+        ]
+    }
+}
+
+/// One stage between the emulated frame and the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostPass {
+    /// Software NTSC composite filter, run on the CPU before upload
+    Ntsc { strength: f32 },
+    /// CRT shader, run on the GPU after upload
+    Crt { preset: CrtPreset, params: CrtParams },
+}
+
+/// Passes `config` asks for, in the order they run
+///
+/// The NTSC filter works on the frame in memory, so it always comes before
+/// the CRT shader, which then draws its scanlines and mask over the
+/// filtered picture. `scanlines` without a preset picks
+/// `CrtPreset::Scanlines`.
+pub fn post_process_chain(config: &VideoConfig) -> Vec<PostPass> {
+    let mut passes = Vec::new();
+    if config.ntsc_filter {
+        passes.push(PostPass::Ntsc { strength: config.ntsc_strength.clamp(0.0, 1.0) });
+    }
+    let preset = match config.crt_preset {
+        CrtPreset::None if config.scanlines => CrtPreset::Scanlines,
+        preset => preset,
+    };
+    if let Some(params) = CrtParams::for_preset(preset, config.scanline_intensity) {
+        passes.push(PostPass::Crt { preset, params });
+    }
+    passes
+}
@@ -0,0 +1,124 @@
+use rnes_common::{Byte, Word, Scanline, Dot, DOTS_PER_SCANLINE, TOTAL_SCANLINES};
+use crate::Bus;
+
+/// CPU write to a PPU register ($2000-$2007) or to OAM DMA ($4014)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PpuWrite {
+    pub address: Word,
+    pub value: Byte,
+    /// Address of the instruction that wrote
+    pub pc: Word,
+    /// PPU position when the write lands
+    pub scanline: Scanline,
+    pub dot: Dot,
+}
+
+/// Per-frame log of PPU register writes
+///
+/// Writes are stamped with the dot the PPU sees them on, the cycle of the
+/// write within its instruction included, so scroll splits can be checked
+/// against the scanline they were meant for.
+#[derive(Debug, Clone, Default)]
+pub struct PpuWriteLog {
+    pc: Word,
+    frame: Option<u64>,
+    writes: Vec<PpuWrite>,
+    last_frame: Option<u64>,
+    last_writes: Vec<PpuWrite>,
+}
+
+impl PpuWriteLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes of the last completed frame, in order
+    pub fn last_frame(&self) -> &[PpuWrite] {
+        &self.last_writes
+    }
+
+    /// PPU frame number of the last completed frame, if one completed yet
+    pub fn last_frame_number(&self) -> Option<u64> {
+        self.last_frame
+    }
+
+    /// Writes so far in the frame in progress
+    pub fn current_frame(&self) -> &[PpuWrite] {
+        &self.writes
+    }
+
+    /// Forget all logged writes
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Move on to `frame`, keeping the writes of the frame that ended
+    fn begin_frame(&mut self, frame: u64) {
+        if self.frame == Some(frame) {
+            return;
+        }
+        if let Some(previous) = self.frame {
+            self.last_writes = std::mem::take(&mut self.writes);
+            self.last_frame = Some(previous);
+        }
+        self.frame = Some(frame);
+    }
+}
+
+impl Bus {
+    /// Start logging PPU register writes, replacing any previous log
+    pub fn start_ppu_write_log(&mut self) {
+        self.ppu_write_log = Some(PpuWriteLog::new());
+    }
+
+    /// Stop logging and return the log
+    pub fn stop_ppu_write_log(&mut self) -> Option<PpuWriteLog> {
+        self.ppu_write_log.take()
+    }
+
+    /// Get the PPU write log being recorded, if any
+    pub fn ppu_write_log(&self) -> Option<&PpuWriteLog> {
+        self.ppu_write_log.as_ref()
+    }
+
+    /// Note the instruction about to execute, so writes can name it
+    pub(crate) fn begin_ppu_write_log(&mut self, pc: Word) {
+        if let Some(ref mut log) = self.ppu_write_log {
+            log.pc = pc;
+        }
+    }
+
+    /// Log a PPU register write made `cycle` CPU cycles into the instruction
+    pub(crate) fn record_ppu_write(&mut self, address: Word, value: Byte, cycle: u32) {
+        let (Some(log), Some(ppu)) = (self.ppu_write_log.as_mut(), self.ppu.as_ref()) else {
+            return;
+        };
+
+        // The PPU is still at the start of the instruction
+        let dots = ppu.dot() + cycle * 3;
+        let mut scanline = ppu.scanline() + (dots / DOTS_PER_SCANLINE as Dot) as Scanline;
+        let mut frame = ppu.frame_count();
+        if scanline >= TOTAL_SCANLINES as Scanline - 1 {
+            scanline -= TOTAL_SCANLINES as Scanline;
+            frame += 1;
+        }
+
+        log.begin_frame(frame);
+        let pc = log.pc;
+        log.writes.push(PpuWrite {
+            address,
+            value,
+            pc,
+            scanline,
+            dot: dots % DOTS_PER_SCANLINE as Dot,
+        });
+    }
+
+    /// Close the log's frame once the PPU has finished it
+    pub(crate) fn record_ppu_write_progress(&mut self) {
+        if let (Some(log), Some(ppu)) = (self.ppu_write_log.as_mut(), self.ppu.as_ref()) {
+            log.begin_frame(ppu.frame_count());
+        }
+    }
+}
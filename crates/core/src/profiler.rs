@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+use rnes_common::{Byte, Cycles};
+use crate::Emulator;
+
+/// RTI opcode, which ends an NMI handler
+const RTI: Byte = 0x40;
+
+/// CPU time of one frame, split between the NMI handler and the main loop
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameProfile {
+    /// PPU frame number
+    pub frame: u64,
+    /// Cycles spent inside the NMI handler
+    pub nmi_cycles: u64,
+    /// Cycles spent everywhere else
+    pub main_cycles: u64,
+    /// Number of NMI handlers entered
+    pub nmi_count: u32,
+    /// An NMI handler was still running when vblank ended, so its PPU
+    /// updates may have landed on screen
+    pub vblank_overrun: bool,
+}
+
+impl FrameProfile {
+    /// Total CPU cycles of the frame
+    pub fn total_cycles(&self) -> u64 {
+        self.nmi_cycles + self.main_cycles
+    }
+}
+
+/// Measures per-frame CPU budget, like Mesen's profiler
+///
+/// The NMI handler is entered when the CPU takes an NMI and left when RTI
+/// brings the stack pointer back to where it was; an NMI arriving before the
+/// previous handler returned nests inside it. The newest `capacity` completed
+/// frames are kept.
+#[derive(Debug, Clone)]
+pub struct CycleProfiler {
+    capacity: usize,
+    current: Option<FrameProfile>,
+    frames: VecDeque<FrameProfile>,
+    /// Stack pointers to return to, innermost handler last
+    nmi_stack: Vec<Byte>,
+}
+
+impl CycleProfiler {
+    /// Create a profiler keeping `capacity` frames
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            current: None,
+            frames: VecDeque::new(),
+            nmi_stack: Vec::new(),
+        }
+    }
+
+    /// Completed frames, oldest first
+    pub fn frames(&self) -> &VecDeque<FrameProfile> {
+        &self.frames
+    }
+
+    /// The most recently completed frame
+    pub fn last_frame(&self) -> Option<&FrameProfile> {
+        self.frames.back()
+    }
+
+    /// Completed frames whose NMI handler overran vblank
+    pub fn overruns(&self) -> impl Iterator<Item = &FrameProfile> {
+        self.frames.iter().filter(|frame| frame.vblank_overrun)
+    }
+
+    /// Check if the CPU is inside an NMI handler
+    pub fn in_nmi(&self) -> bool {
+        !self.nmi_stack.is_empty()
+    }
+
+    /// Forget all frames, e.g. after a reset
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.frames.clear();
+        self.nmi_stack.clear();
+    }
+
+    /// Account for one CPU step in `frame`
+    fn record(&mut self, frame: u64, step: ProfiledStep, vblank: bool) {
+        let profile = self.current.get_or_insert(FrameProfile { frame, ..FrameProfile::default() });
+        if step.took_nmi {
+            self.nmi_stack.push(step.sp);
+            profile.nmi_count += 1;
+        }
+
+        if self.nmi_stack.is_empty() {
+            profile.main_cycles += step.cycles as u64;
+        } else {
+            profile.nmi_cycles += step.cycles as u64;
+        }
+
+        let returned = !step.took_nmi && step.opcode == RTI && self.nmi_stack.last() == Some(&step.sp_after);
+        if returned {
+            self.nmi_stack.pop();
+            if !vblank {
+                profile.vblank_overrun = true;
+            }
+        }
+    }
+
+    /// Close the current frame as the PPU starts the next one
+    fn end_frame(&mut self) {
+        let Some(mut profile) = self.current.take() else {
+            return;
+        };
+        // Vblank is over by the time the next frame starts
+        if self.in_nmi() {
+            profile.vblank_overrun = true;
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(profile);
+    }
+}
+
+/// What the profiler needs to know about one CPU step
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ProfiledStep {
+    pub cycles: Cycles,
+    /// Stack pointer before the step
+    pub sp: Byte,
+    /// Stack pointer after the step
+    pub sp_after: Byte,
+    /// The step entered the NMI handler
+    pub took_nmi: bool,
+    /// Opcode at PC before the step, run unless an interrupt was taken
+    pub opcode: Byte,
+}
+
+impl Emulator {
+    /// Start profiling CPU time per frame, keeping the newest `capacity` frames
+    pub fn enable_profiler(&mut self, capacity: usize) {
+        self.profiler = Some(CycleProfiler::new(capacity));
+    }
+
+    /// Stop profiling and drop the report
+    pub fn disable_profiler(&mut self) {
+        self.profiler = None;
+    }
+
+    /// Get the profiler, if enabled
+    pub fn profiler(&self) -> Option<&CycleProfiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Account for a CPU step in the profile
+    pub(crate) fn record_profile(&mut self, step: ProfiledStep) {
+        let (Some(profiler), Some(ppu)) = (self.profiler.as_mut(), self.bus.ppu.as_ref()) else {
+            return;
+        };
+        profiler.record(self.state.frame_count, step, ppu.vblank());
+    }
+
+    /// Close the profiled frame when the PPU starts a new one
+    pub(crate) fn end_profiled_frame(&mut self) {
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.end_frame();
+        }
+    }
+}
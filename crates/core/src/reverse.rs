@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use rnes_common::{EmulatorState, RnesResult};
+use rnes_cpu6502::Cpu;
+use crate::{Bus, Emulator};
+
+/// Machine state captured before an instruction
+#[derive(Debug, Clone)]
+struct ReverseSnapshot {
+    instruction: u64,
+    cpu: Cpu,
+    bus: Bus,
+    state: EmulatorState,
+}
+
+/// Periodic in-memory snapshots used to step backwards through execution
+///
+/// A snapshot is taken every `interval` instructions and the newest
+/// `capacity` are kept, so reverse steps can reach back about
+/// `interval * capacity` instructions.
+#[derive(Debug)]
+pub struct ReverseHistory {
+    interval: u64,
+    capacity: usize,
+    instructions: u64,
+    snapshots: VecDeque<ReverseSnapshot>,
+}
+
+impl ReverseHistory {
+    /// Create an empty history
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            instructions: 0,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Instructions executed since the history was enabled or cleared
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Number of snapshots held
+    pub fn snapshot_count(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Earliest instruction a reverse step can reach
+    pub fn earliest(&self) -> Option<u64> {
+        self.snapshots.front().map(|snapshot| snapshot.instruction)
+    }
+
+    /// Drop all snapshots, e.g. after a reset or state load
+    pub fn clear(&mut self) {
+        self.instructions = 0;
+        self.snapshots.clear();
+    }
+}
+
+impl Emulator {
+    /// Record snapshots so the debugger can step backwards
+    ///
+    /// A snapshot is taken every `interval` instructions and the newest
+    /// `capacity` are kept. Each snapshot holds a full copy of the machine.
+    pub fn enable_reverse_step(&mut self, interval: u64, capacity: usize) {
+        self.reverse = Some(ReverseHistory::new(interval, capacity));
+    }
+
+    /// Stop recording snapshots and free them
+    pub fn disable_reverse_step(&mut self) {
+        self.reverse = None;
+    }
+
+    /// Get the reverse step history, if enabled
+    pub fn reverse_history(&self) -> Option<&ReverseHistory> {
+        self.reverse.as_ref()
+    }
+
+    /// Take a snapshot if one is due before the next instruction
+    pub(crate) fn record_reverse_snapshot(&mut self) {
+        let Some(ref mut history) = self.reverse else {
+            return;
+        };
+        if history.instructions % history.interval != 0
+            || history.snapshots.back().is_some_and(|snapshot| snapshot.instruction == history.instructions)
+        {
+            return;
+        }
+
+        // The heat map is debugger bookkeeping, not machine state
+        let heat_map = self.bus.heat_map.take();
+        let bus = self.bus.clone();
+        self.bus.heat_map = heat_map;
+
+        history.snapshots.push_back(ReverseSnapshot {
+            instruction: history.instructions,
+            cpu: self.cpu.clone(),
+            bus,
+            state: self.state.clone(),
+        });
+        if history.snapshots.len() > history.capacity {
+            history.snapshots.pop_front();
+        }
+    }
+
+    /// Count an executed instruction
+    pub(crate) fn advance_reverse_history(&mut self) {
+        if let Some(ref mut history) = self.reverse {
+            history.instructions += 1;
+        }
+    }
+
+    /// Step back one instruction
+    ///
+    /// Restores the nearest earlier snapshot and re-executes up to the
+    /// instruction before the current one, leaving the emulator paused.
+    /// Returns `false` when reverse step is disabled or the previous
+    /// instruction is older than the oldest snapshot. Input is replayed as
+    /// it was when the snapshot was taken.
+    pub fn step_back(&mut self) -> RnesResult<bool> {
+        let Some(ref mut history) = self.reverse else {
+            return Ok(false);
+        };
+        let Some(target) = history.instructions.checked_sub(1) else {
+            return Ok(false);
+        };
+        history.snapshots.retain(|snapshot| snapshot.instruction <= target);
+        let Some(snapshot) = history.snapshots.back().cloned() else {
+            return Ok(false);
+        };
+        history.instructions = snapshot.instruction;
+
+        let heat_map = self.bus.heat_map.take();
+        self.bus = snapshot.bus;
+        self.cpu = snapshot.cpu;
+        self.state = snapshot.state;
+
+        while self.reverse.as_ref().is_some_and(|history| history.instructions < target) {
+            self.execute_step(false)?;
+        }
+
+        // Replayed audio was already played once
+        self.bus.clear_audio();
+        self.bus.heat_map = heat_map;
+        self.hooks.reset();
+        self.running = false;
+        self.update_debug_info();
+        Ok(true)
+    }
+}
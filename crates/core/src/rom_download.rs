@@ -0,0 +1,88 @@
+use rnes_cartridge::{Cartridge, InesHeader};
+use rnes_common::{RnesError, RnesResult};
+use crate::Session;
+
+/// Largest ROM accepted by `RomDownload`, well above any licensed game
+pub const MAX_ROM_SIZE: usize = 8 * 1024 * 1024;
+
+/// Size of the iNES header checked as soon as it arrives
+const HEADER_SIZE: usize = 16;
+
+/// ROM bytes arriving in chunks, e.g. from a streamed `fetch`
+///
+/// The header is validated as soon as it arrives, so a URL pointing at an
+/// HTML page or a zip fails on the first chunk instead of after the whole
+/// download.
+#[derive(Debug, Clone, Default)]
+pub struct RomDownload {
+    data: Vec<u8>,
+    expected: Option<usize>,
+}
+
+impl RomDownload {
+    /// Start a download of `expected` bytes, if the size is known
+    /// (`Content-Length`)
+    pub fn new(expected: Option<usize>) -> RnesResult<Self> {
+        if let Some(size) = expected {
+            check_size(size)?;
+        }
+        Ok(Self {
+            data: Vec::with_capacity(expected.unwrap_or(0)),
+            expected,
+        })
+    }
+
+    /// Append a chunk, failing once the data can't be a ROM
+    pub fn push(&mut self, chunk: &[u8]) -> RnesResult<()> {
+        let had_header = self.data.len() >= HEADER_SIZE;
+        check_size(self.data.len() + chunk.len())?;
+        self.data.extend_from_slice(chunk);
+        if !had_header && self.data.len() >= HEADER_SIZE {
+            InesHeader::from_bytes(&self.data)?;
+        }
+        Ok(())
+    }
+
+    /// Bytes received so far
+    pub fn received(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Fraction of the download received, when the size is known
+    pub fn progress(&self) -> Option<f32> {
+        match self.expected {
+            Some(0) => Some(1.0),
+            Some(size) => Some((self.data.len() as f32 / size as f32).min(1.0)),
+            None => None,
+        }
+    }
+
+    /// Parse the downloaded ROM
+    pub fn finish(self) -> RnesResult<Cartridge> {
+        Cartridge::from_bytes(&self.data)
+    }
+}
+
+fn check_size(size: usize) -> RnesResult<()> {
+    if size > MAX_ROM_SIZE {
+        return Err(RnesError::RomFormat(format!(
+            "ROM is {} bytes, larger than the {} byte limit",
+            size, MAX_ROM_SIZE
+        )));
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Load a ROM from raw file bytes, e.g. a dropped or downloaded file
+    ///
+    /// Invalid data fails with `RnesError::RomFormat`, before the running
+    /// game is touched, or `RnesError::UnsupportedMapper`; both are matched
+    /// by `RnesError::is_rom_error`. Saves are named after the ROM hash,
+    /// since there is no file path.
+    pub fn load_rom_bytes(&mut self, data: &[u8]) -> RnesResult<()> {
+        check_size(data.len())?;
+        let cartridge = Cartridge::from_bytes(data)?;
+        self.load_rom(cartridge)
+    }
+}
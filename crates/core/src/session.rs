@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+use rnes_cartridge::Cartridge;
+use rnes_common::{AudioSample, BackgroundBehavior, Byte, Config, Pixel, RnesResult, SaveState};
+use crate::{Emulator, FramePacer};
+
+/// Callback receiving each presented frame
+pub type VideoCallback = Box<dyn FnMut(&[Pixel]) + Send>;
+
+/// Milliseconds of `tick` timestamps each FPS measurement covers
+const FPS_WINDOW_MS: f64 = 1000.0;
+
+/// Performance counters of a session since its ROM was loaded
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionStats {
+    /// Emulated frames per second, measured over the last second of `tick`s
+    pub fps: f64,
+    /// Emulated frames run
+    pub frames_run: u64,
+    /// Frames handed to the video callback
+    pub frames_presented: u64,
+    /// Frames that fell due but were never run, because a tick would have
+    /// had to run more than the catch-up limit
+    pub frames_dropped: u64,
+    /// Times the host reported running out of audio
+    pub audio_underruns: u64,
+}
+
+/// In-memory snapshots taken every few frames for rewinding
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    interval: u32,
+    capacity: usize,
+    frames_since_snapshot: u32,
+    snapshots: VecDeque<SaveState>,
+}
+
+impl RewindBuffer {
+    /// Keep up to `capacity` snapshots, one every `interval` frames
+    pub fn new(interval: u32, capacity: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            frames_since_snapshot: 0,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Frames between snapshots
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Number of snapshots held
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Check if there is nothing to rewind to
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Forget all snapshots
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.frames_since_snapshot = 0;
+    }
+
+    /// Count a finished frame, taking a snapshot when one is due
+    fn frame_finished(&mut self, emulator: &Emulator) {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+        if let Ok(snapshot) = emulator.snapshot() {
+            if self.snapshots.len() == self.capacity {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back(snapshot);
+        }
+    }
+}
+
+/// A game being played, with everything a frontend needs around the emulator
+///
+/// `Session` owns the orchestration every frontend used to repeat: resuming
+/// or fast-booting after a ROM is loaded, pacing frames against host
+/// timestamps, latching controller input at frame boundaries, handing frames
+/// and audio to callbacks, rewind, and flushing saves on exit. Battery
+/// auto-save runs inside the emulator as frames are stepped.
+///
+/// Hosts with their own clock (a vsync-locked display, libretro's
+/// `retro_run`) call `run_frame` once per frame; others call `tick` with a
+/// timestamp and let the session decide how many frames are due.
+///
+/// Hosts report window focus and visibility with `set_focused` and
+/// `set_visible`; the session pauses or mutes as `on_focus_loss` and
+/// `on_hidden` in the config ask.
+pub struct Session {
+    emulator: Emulator,
+    pacer: FramePacer,
+    resume: bool,
+    paused: bool,
+    focused: bool,
+    visible: bool,
+    background: BackgroundBehavior,
+    video: Option<VideoCallback>,
+    rewind: Option<RewindBuffer>,
+    max_catch_up: u32,
+    stats: SessionStats,
+    fps_window: Option<(f64, u32)>,
+}
+
+impl Session {
+    /// Create a session with its own emulator
+    pub fn new(config: Config) -> Self {
+        Self::with_emulator(Emulator::with_config(config))
+    }
+
+    /// Create a session around an existing emulator
+    pub fn with_emulator(emulator: Emulator) -> Self {
+        Self {
+            pacer: FramePacer::for_region(emulator.region),
+            emulator,
+            resume: true,
+            paused: false,
+            focused: true,
+            visible: true,
+            background: BackgroundBehavior::KeepRunning,
+            video: None,
+            rewind: None,
+            max_catch_up: 4,
+            stats: SessionStats::default(),
+            fps_window: None,
+        }
+    }
+
+    /// Choose whether loading a ROM resumes the last session (default on)
+    ///
+    /// Resuming still requires `auto_resume` in the config.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Set how many frames a late `tick` may run before dropping the rest
+    /// (default 4)
+    ///
+    /// Only the last frame of a tick is presented, so raising this is a
+    /// frame skip for devices that can't render every frame.
+    pub fn with_max_catch_up(mut self, frames: u32) -> Self {
+        self.max_catch_up = frames.max(1);
+        self.pacer = self.new_pacer();
+        self
+    }
+
+    /// Load a ROM, then resume its last session or fast-boot it, and start
+    pub fn load_rom(&mut self, cartridge: Cartridge) -> RnesResult<()> {
+        self.emulator.load_rom(cartridge)?;
+        let resumed = self.resume && self.emulator.resume_last_session()?;
+        if !resumed {
+            self.emulator.fast_boot()?;
+        }
+
+        self.pacer = self.new_pacer();
+        self.stats = SessionStats::default();
+        self.fps_window = None;
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.clear();
+        }
+        self.paused = false;
+        // Loading powers on a fresh APU at the configured volume
+        if self.background == BackgroundBehavior::Mute {
+            self.set_muted(true);
+        }
+        self.emulator.start();
+        Ok(())
+    }
+
+    /// Set the buttons of controller `port` (0 or 1) from the next frame on
+    ///
+    /// Input is latched at frame boundaries, so a frame never sees half an
+    /// update.
+    pub fn set_input(&mut self, port: usize, buttons: Byte) {
+        self.emulator.set_input(port, buttons);
+    }
+
+    /// Buttons that will be applied to controller `port` at the next frame
+    pub fn input(&self, port: usize) -> Byte {
+        self.emulator.input.held().get(port).copied().unwrap_or(0)
+    }
+
+    /// Present every frame run by `tick` or `run_frame` to `callback`
+    ///
+    /// When a tick runs several frames to catch up, only the last one is
+    /// presented.
+    pub fn set_video_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&[Pixel]) + Send + 'static,
+    {
+        self.video = Some(Box::new(callback));
+    }
+
+    /// Deliver audio in chunks of `chunk_size` samples as soon as each is complete
+    pub fn set_audio_callback<F>(&mut self, chunk_size: usize, callback: F)
+    where
+        F: FnMut(&[AudioSample]) + Send + 'static,
+    {
+        self.emulator.set_audio_callback(chunk_size, callback);
+    }
+
+    /// Run the frames due at `timestamp` (ms), returning how many ran
+    pub fn tick(&mut self, timestamp: f64) -> RnesResult<u32> {
+        if self.is_halted() {
+            return Ok(0);
+        }
+        let frames = self.pacer.tick(timestamp);
+        for _ in 0..frames {
+            self.advance()?;
+        }
+        if frames > 0 {
+            self.present();
+        }
+        self.measure_fps(timestamp, frames);
+        Ok(frames)
+    }
+
+    /// Run and present exactly one frame, ignoring pacing
+    pub fn run_frame(&mut self) -> RnesResult<()> {
+        if self.is_halted() {
+            return Ok(());
+        }
+        self.advance()?;
+        self.present();
+        Ok(())
+    }
+
+    /// Pause emulation; `tick` and `run_frame` do nothing until resumed
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resume after `pause`, without catching up on the time spent paused
+    ///
+    /// A pause for focus loss or a hidden window stays until the host
+    /// reports the window back.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.restart_pacing();
+    }
+
+    /// Check if paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Report that the window gained (`true`) or lost keyboard focus
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.update_background();
+    }
+
+    /// Report that the window or browser tab was shown (`true`) or hidden,
+    /// e.g. minimized or switched away from
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        self.update_background();
+    }
+
+    /// What the session is doing because of focus or visibility, applying
+    /// the stronger setting when both the focus and the window are lost
+    pub fn background_behavior(&self) -> BackgroundBehavior {
+        self.background
+    }
+
+    /// Keep a snapshot every `interval` frames, up to `capacity`, for `rewind`
+    pub fn enable_rewind(&mut self, interval: u32, capacity: usize) {
+        self.rewind = Some(RewindBuffer::new(interval, capacity));
+    }
+
+    /// Stop keeping rewind snapshots
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    /// Get the rewind buffer, when rewind is enabled
+    pub fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    /// Go back to the most recent rewind snapshot
+    ///
+    /// Each call goes one snapshot further back. Snapshots do not hold the
+    /// picture, so the screen catches up with the next frame run. Returns
+    /// `Ok(false)` when rewind is disabled or there is nothing left.
+    pub fn rewind(&mut self) -> RnesResult<bool> {
+        let Some(rewind) = self.rewind.as_mut() else {
+            return Ok(false);
+        };
+        let Some(snapshot) = rewind.snapshots.pop_back() else {
+            return Ok(false);
+        };
+        rewind.frames_since_snapshot = 0;
+        self.emulator.restore_snapshot(&snapshot)?;
+        self.pacer.reset();
+        Ok(true)
+    }
+
+    /// Count an audio underrun, for hosts whose audio output reports them
+    pub fn report_audio_underrun(&mut self) {
+        self.stats.audio_underruns += 1;
+    }
+
+    /// Get performance counters, e.g. for a HUD or to enable frame skip
+    pub fn stats(&self) -> SessionStats {
+        SessionStats {
+            frames_dropped: self.pacer.frames_dropped(),
+            ..self.stats
+        }
+    }
+
+    /// Flush battery RAM and write the exit state
+    ///
+    /// Frontends should call this once before exiting.
+    pub fn shutdown(&mut self) -> RnesResult<()> {
+        self.emulator.shutdown()
+    }
+
+    /// Get the emulator
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Get the emulator mutably, e.g. for the debugger or save states
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    fn is_halted(&self) -> bool {
+        self.paused || self.background == BackgroundBehavior::Pause || !self.emulator.is_running()
+    }
+
+    fn restart_pacing(&mut self) {
+        self.pacer.reset();
+        self.fps_window = None;
+    }
+
+    fn update_background(&mut self) {
+        let general = &self.emulator.get_config().general;
+        let mut behavior = BackgroundBehavior::KeepRunning;
+        if !self.focused {
+            behavior = behavior.max(general.on_focus_loss);
+        }
+        if !self.visible {
+            behavior = behavior.max(general.on_hidden);
+        }
+
+        let previous = std::mem::replace(&mut self.background, behavior);
+        if previous == BackgroundBehavior::Mute || behavior == BackgroundBehavior::Mute {
+            self.set_muted(behavior == BackgroundBehavior::Mute);
+        }
+        // Time spent in the background is not caught up on
+        if previous == BackgroundBehavior::Pause && behavior != BackgroundBehavior::Pause {
+            self.restart_pacing();
+        }
+    }
+
+    /// Silence the APU without touching the configured volume
+    fn set_muted(&mut self, muted: bool) {
+        let volume = if muted { 0.0 } else { self.emulator.volume() };
+        if let Some(ref mut apu) = self.emulator.bus.apu {
+            apu.set_master_volume(volume);
+        }
+    }
+
+    fn new_pacer(&self) -> FramePacer {
+        FramePacer::for_region(self.emulator.region).with_max_catch_up(self.max_catch_up)
+    }
+
+    fn advance(&mut self) -> RnesResult<()> {
+        self.emulator.run_frame()?;
+        self.stats.frames_run += 1;
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.frame_finished(&self.emulator);
+        }
+        Ok(())
+    }
+
+    fn present(&mut self) {
+        if let (Some(video), Some(frame)) = (self.video.as_mut(), self.emulator.get_ppu_frame_buffer()) {
+            video(frame);
+            self.stats.frames_presented += 1;
+        }
+    }
+
+    fn measure_fps(&mut self, timestamp: f64, frames: u32) {
+        let (start, counted) = self.fps_window.get_or_insert((timestamp, 0));
+        *counted += frames;
+        let elapsed = timestamp - *start;
+        if elapsed >= FPS_WINDOW_MS {
+            self.stats.fps = *counted as f64 * 1000.0 / elapsed;
+            self.fps_window = Some((timestamp, 0));
+        }
+    }
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("paused", &self.paused)
+            .field("background", &self.background)
+            .field("rewind", &self.rewind)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use rnes_apu::CHANNEL_COUNT;
+use rnes_common::{RnesResult, AUDIO_SAMPLE_RATE};
+use crate::Emulator;
+
+/// File names of the APU channel stems, in `ChannelLevels` order
+pub const APU_STEM_NAMES: [&str; CHANNEL_COUNT] = ["pulse1", "pulse2", "triangle", "noise", "dmc"];
+
+const WAV_HEADER_LEN: u32 = 44;
+
+/// One mono 16-bit PCM WAV file being written
+#[derive(Debug)]
+struct WavWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: PathBuf, sample_rate: u32) -> RnesResult<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        // Sizes are patched in by `finish`
+        writer.write_all(&wav_header(sample_rate, 0))?;
+        Ok(Self { path, writer, data_len: 0 })
+    }
+
+    fn write_sample(&mut self, sample: i16) -> RnesResult<()> {
+        self.writer.write_all(&sample.to_le_bytes())?;
+        self.data_len = self.data_len.saturating_add(2);
+        Ok(())
+    }
+
+    fn finish(mut self, sample_rate: u32) -> RnesResult<PathBuf> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&wav_header(sample_rate, self.data_len))?;
+        self.writer.flush()?;
+        Ok(self.path)
+    }
+}
+
+/// RIFF header for mono 16-bit PCM with `data_len` bytes of samples
+fn wav_header(sample_rate: u32, data_len: u32) -> [u8; WAV_HEADER_LEN as usize] {
+    let mut header = [0; WAV_HEADER_LEN as usize];
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(WAV_HEADER_LEN - 8).saturating_add(data_len).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&1u16.to_le_bytes()); // PCM
+    header[22..24].copy_from_slice(&1u16.to_le_bytes()); // Mono
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * 2).to_le_bytes());
+    header[32..34].copy_from_slice(&2u16.to_le_bytes());
+    header[34..36].copy_from_slice(&16u16.to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+/// Writes each audio channel to its own WAV file
+///
+/// Channels are given by name, so expansion audio can add stems next to the
+/// APU's five. Levels are unipolar (0.0-1.0) and written unmixed, so silence
+/// is 0 and the stems can be summed or remixed freely.
+#[derive(Debug)]
+pub struct StemRecorder {
+    stems: Vec<WavWriter>,
+    sample_rate: u32,
+    samples: u64,
+}
+
+impl StemRecorder {
+    /// Create `<name>.wav` in `dir` for every channel name
+    pub fn create(dir: impl AsRef<Path>, names: &[&str], sample_rate: u32) -> RnesResult<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let stems = names.iter()
+            .map(|name| WavWriter::create(dir.join(format!("{}.wav", name)), sample_rate))
+            .collect::<RnesResult<Vec<_>>>()?;
+        Ok(Self { stems, sample_rate, samples: 0 })
+    }
+
+    /// Append one sample to every stem; extra levels are ignored
+    pub fn write(&mut self, levels: &[f32]) -> RnesResult<()> {
+        for (stem, level) in self.stems.iter_mut().zip(levels) {
+            stem.write_sample((level.clamp(0.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        self.samples += 1;
+        Ok(())
+    }
+
+    /// Number of samples written to each stem
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Finalize the headers and return the stem paths
+    pub fn finish(self) -> RnesResult<Vec<PathBuf>> {
+        let sample_rate = self.sample_rate;
+        self.stems.into_iter().map(|stem| stem.finish(sample_rate)).collect()
+    }
+}
+
+impl Emulator {
+    /// Start writing each APU channel to `<dir>/<channel>.wav`
+    ///
+    /// This takes over the APU's visualizer feed until recording stops.
+    pub fn start_stem_recording(&mut self, dir: impl AsRef<Path>) -> RnesResult<()> {
+        self.stems = Some(StemRecorder::create(dir, &APU_STEM_NAMES, AUDIO_SAMPLE_RATE)?);
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_visualizer_enabled(true);
+        }
+        Ok(())
+    }
+
+    /// Stop recording and return the finished stem files
+    pub fn stop_stem_recording(&mut self) -> RnesResult<Vec<PathBuf>> {
+        self.record_stems()?;
+        let Some(stems) = self.stems.take() else {
+            return Ok(Vec::new());
+        };
+        if let Some(ref mut apu) = self.bus.apu {
+            apu.set_visualizer_enabled(false);
+        }
+        stems.finish()
+    }
+
+    /// Check if channel stems are being recorded
+    pub fn is_recording_stems(&self) -> bool {
+        self.stems.is_some()
+    }
+
+    /// Move the channel levels produced since the last call into the stems
+    pub(crate) fn record_stems(&mut self) -> RnesResult<()> {
+        let (Some(stems), Some(apu)) = (self.stems.as_mut(), self.bus.apu.as_mut()) else {
+            return Ok(());
+        };
+        let mut levels = [[0.0; CHANNEL_COUNT]; 256];
+        loop {
+            let count = apu.drain_channel_levels(&mut levels);
+            for level in &levels[..count] {
+                stems.write(level)?;
+            }
+            if count < levels.len() {
+                return Ok(());
+            }
+        }
+    }
+}
+
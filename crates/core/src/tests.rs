@@ -25,6 +25,23 @@ fn test_bus_memory_access() {
     assert_eq!(bus.read_word(0x0001).unwrap(), 0x1234);
 }
 
+#[test]
+fn test_controller_strobe_and_shift() {
+    let mut bus = Bus::new();
+    bus.set_controller1(rnes_common::ControllerState::from_bits(0x09)); // A + Start
+    
+    // While strobed every read returns button A
+    bus.write_byte(0x4016, 0x01).unwrap();
+    assert_eq!(bus.read_byte(0x4016).unwrap(), 1);
+    assert_eq!(bus.read_byte(0x4016).unwrap(), 1);
+    
+    // Releasing the strobe shifts out A, B, Select, Start, ... then 1s
+    bus.write_byte(0x4016, 0x00).unwrap();
+    let bits: Vec<u8> = (0..10).map(|_| bus.read_byte(0x4016).unwrap()).collect();
+    assert_eq!(bits, vec![1, 0, 0, 1, 0, 0, 0, 0, 1, 1]);
+    assert_eq!(bus.read_byte(0x4017).unwrap(), 0);
+}
+
 #[test]
 fn test_emulator_creation() {
     let emulator = Emulator::new();
@@ -42,3 +59,2086 @@ fn test_emulator_control() {
     emulator.stop();
     assert!(!emulator.is_running());
 }
+
+/// Build a minimal NROM image running `INC $10; JMP $8000`
+fn create_test_rom() -> Vec<u8> {
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0xEA; 16 * 1024];
+    prg[..5].copy_from_slice(&[0xE6, 0x10, 0x4C, 0x00, 0x80]);
+    prg[0x3FFC] = 0x00;
+    prg[0x3FFD] = 0x80;
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 8 * 1024]);
+    rom
+}
+
+/// Assemble `source` at $C000 into an NROM image with its vectors set
+fn assemble_nrom(source: &str) -> Vec<u8> {
+    let assembly = rnes_cpu6502::assemble(source, 0xC000).unwrap();
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0xEA; 16 * 1024];
+    assembly.write_to(&mut prg, 0xC000).unwrap();
+    for (offset, label) in [(0x3FFA, "nmi"), (0x3FFC, "reset"), (0x3FFE, "irq")] {
+        let vector = assembly.label(label).unwrap_or(0xC000);
+        prg[offset..offset + 2].copy_from_slice(&vector.to_le_bytes());
+    }
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 8 * 1024]);
+    rom
+}
+
+#[test]
+fn test_cpu_takes_one_nmi_per_262_line_frame() {
+    let rom = assemble_nrom("
+        reset:  sei
+                ldx #$FF
+                txs
+                lda #$80
+                sta $2000
+        loop:   jmp loop
+        nmi:    inc $10
+                rti
+    ");
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&rom).unwrap()).unwrap();
+    emulator.start();
+    emulator.run_frames(2).unwrap();
+
+    let nmis = emulator.bus.ram[0x10];
+    let cycles = emulator.state.cpu_cycles;
+    emulator.run_frames(30).unwrap();
+    assert_eq!(emulator.bus.ram[0x10], nmis.wrapping_add(30));
+    // 262 lines of 341 dots is 29780.67 CPU cycles a frame
+    let expected = (30 * rnes_common::TOTAL_SCANLINES * rnes_common::DOTS_PER_SCANLINE / 3) as u32;
+    let elapsed = emulator.state.cpu_cycles - cycles;
+    assert!(elapsed.abs_diff(expected) < 8, "{} cycles, expected {}", elapsed, expected);
+}
+
+#[test]
+fn test_cpu_reads_sprite_overflow_from_status() {
+    // Nine sprites on the same line, then poll PPUSTATUS until overflow shows
+    let rom = assemble_nrom("
+        reset:  sei
+                ldx #$FF
+                txs
+        wait1:  bit $2002
+                bpl wait1
+                lda #$FF
+                ldx #$00
+        clear:  sta $0200,x
+                inx
+                bne clear
+                lda #$30
+        sprite: sta $0200,x
+                inx
+                cpx #36
+                bne sprite
+        wait2:  bit $2002
+                bpl wait2
+                lda #$02
+                sta $4014
+                lda #$18
+                sta $2001
+        poll:   lda $2002
+                and #$20
+                beq poll
+                sta $10
+        done:   jmp done
+    ");
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frames(4).unwrap();
+
+    assert_eq!(emulator.bus.ram[0x10], 0x20);
+}
+
+#[test]
+fn test_run_frame_advances_one_frame() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+
+    let start = emulator.ppu().frame_count();
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.ppu().frame_count(), start + 1);
+}
+
+#[test]
+fn test_save_state_bytes_round_trip() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    let data = emulator.save_state_to_bytes().unwrap();
+    let counter = emulator.bus.ram[0x10];
+    emulator.run_frame().unwrap();
+    assert_ne!(emulator.bus.ram[0x10], counter);
+
+    emulator.load_state_from_bytes(&data).unwrap();
+    assert_eq!(emulator.bus.ram[0x10], counter);
+}
+
+/// Build an MMC3 image that splits the screen with the scanline IRQ
+///
+/// The NMI picks the CHR bank for the top of the screen and arms the IRQ;
+/// the IRQ switches banks partway down, pokes the pulse channel and folds
+/// the main loop counter into the next bank choice, so a late or missed
+/// IRQ changes every frame after it.
+fn create_mmc3_rom() -> Vec<u8> {
+    let assembly = rnes_cpu6502::assemble("
+        .org $C000
+        reset:  sei
+                ldx #$FF
+                txs
+        wait1:  bit $2002
+                bpl wait1
+        wait2:  bit $2002
+                bpl wait2
+                lda #$20
+                sta $2006
+                lda #$00
+                sta $2006
+                ldx #$00
+                ldy #$04
+        fill:   txa
+                sta $2007
+                inx
+                bne fill
+                dey
+                bne fill
+                lda #$3F
+                sta $2006
+                lda #$00
+                sta $2006
+                ldx #$00
+        pal:    txa
+                sta $2007
+                inx
+                cpx #$20
+                bne pal
+                lda #$40
+                sta $4017
+                lda #$01
+                sta $4015
+                lda #$88
+                sta $2000
+                lda #$0A
+                sta $2001
+                cli
+        main:   inc $10
+                jmp main
+        nmi:    pha
+                lda #$00
+                sta $2005
+                sta $2005
+                sta $8000
+                lda $12
+                and #$06
+                sta $8001
+                lda #40
+                sta $C000
+                sta $C001
+                sta $E001
+                pla
+                rti
+        irq:    pha
+                sta $E000
+                inc $11
+                lda $10
+                clc
+                adc $12
+                sta $12
+                lda #$00
+                sta $8000
+                lda $11
+                and #$07
+                asl a
+                sta $8001
+                lda $11
+                sta $4002
+                lda #$BF
+                sta $4000
+                lda #$08
+                sta $4003
+                pla
+                rti
+                .org $FFFA
+                .word nmi, reset, irq
+    ", 0xC000).unwrap();
+
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 4, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0xEA; 32 * 1024];
+    assembly.write_to(&mut prg[0x4000..], 0xC000).unwrap();
+    rom.extend_from_slice(&prg);
+    rom.extend((0..32 * 1024).map(|i: usize| (((i >> 10) * 0x3B) ^ (i * 7)) as u8));
+    rom
+}
+
+#[test]
+fn test_save_state_resumes_mapper_rom_mid_frame() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_mmc3_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frames(4).unwrap();
+    // Stop partway down the screen with the scanline IRQ still armed
+    while emulator.ppu().scanline() != 20 {
+        emulator.step().unwrap();
+    }
+
+    let data = emulator.save_state_to_bytes().unwrap();
+    let snapshot = emulator.snapshot().unwrap();
+    let run = |emulator: &mut Emulator| {
+        emulator.run_frames(6).unwrap();
+        (emulator.get_ppu_frame_buffer().unwrap().to_vec(), emulator.bus.ram.to_vec(), emulator.cpu_status())
+    };
+    let expected = run(&mut emulator);
+    assert_ne!(emulator.bus.ram[0x11], 0, "the scanline IRQ should have fired");
+
+    emulator.load_state_from_bytes(&data).unwrap();
+    let resumed = run(&mut emulator);
+    assert!(resumed.0 == expected.0, "frame differs after loading a save state");
+    assert_eq!(resumed.1, expected.1);
+    assert_eq!(resumed.2, expected.2);
+
+    emulator.restore_snapshot(&snapshot).unwrap();
+    let rewound = run(&mut emulator);
+    assert!(rewound.0 == expected.0, "frame differs after restoring a snapshot");
+    assert_eq!(rewound.1, expected.1);
+    assert_eq!(rewound.2, expected.2);
+}
+
+#[test]
+fn test_apply_save_state_without_rom() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    let state = emulator.snapshot().unwrap();
+
+    let mut empty = Emulator::with_config(rnes_common::Config::default());
+    assert!(matches!(empty.apply_save_state(&state), Err(rnes_common::RnesError::NoRomLoaded)));
+    assert!(matches!(empty.restore_snapshot(&state), Err(rnes_common::RnesError::NoRomLoaded)));
+}
+
+#[test]
+fn test_failed_state_load_leaves_machine_unchanged() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_mmc3_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    let snapshot = emulator.snapshot().unwrap();
+    emulator.run_frames(3).unwrap();
+    let machine = |emulator: &Emulator| (emulator.bus.ram.to_vec(), emulator.cpu_status(), emulator.ppu().scanline());
+    let before = machine(&emulator);
+
+    let mut wrong_board = snapshot.clone();
+    wrong_board.mapper_state.data = rnes_common::SaveState::encode_component(&rnes_mappers::MapperState::Stateless).unwrap();
+    let mut oversized_ram = snapshot.clone();
+    oversized_ram.memory_state.prg_ram = vec![0; 1 << 20];
+    let mut garbage_ppu = snapshot.clone();
+    garbage_ppu.ppu_state.data = vec![0xFF; 8];
+    for corrupt in [wrong_board, oversized_ram, garbage_ppu] {
+        assert!(emulator.restore_snapshot(&corrupt).is_err());
+        assert_eq!(machine(&emulator), before);
+    }
+    emulator.restore_snapshot(&snapshot).unwrap();
+    assert_ne!(machine(&emulator), before);
+}
+
+#[test]
+fn test_rl_env_frame_skip_and_reward() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut env = RlEnv::new(cartridge).unwrap()
+        .with_observation_mode(ObservationMode::Ram)
+        .with_frame_skip(4)
+        .with_max_frames(8)
+        .with_reward_fn(|_ram: &[u8]| 1.0f32);
+
+    let observation = env.reset().unwrap();
+    assert_eq!(observation.len(), 0x800);
+
+    let result = env.step(0x01).unwrap();
+    assert_eq!(result.reward, 4.0);
+    assert_eq!(result.frame, 4);
+    assert!(!result.done);
+    assert!(env.emulator().get_controller1_state().a);
+
+    let result = env.step(0x00).unwrap();
+    assert!(result.done);
+    assert_eq!(result.frame, 8);
+
+    env.reset().unwrap();
+    assert_eq!(env.frame(), 0);
+}
+
+#[test]
+fn test_ram_delta_reward() {
+    let mut reward = RamDeltaReward::new(0x0010).with_scale(2.0);
+    let mut ram = [0u8; 0x800];
+    ram[0x10] = 5;
+    reward.reset(&ram);
+    ram[0x10] = 8;
+    assert_eq!(reward.reward(&ram), 6.0);
+    ram[0x10] = 7;
+    assert_eq!(reward.reward(&ram), -2.0);
+}
+
+#[test]
+fn test_set_volume() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+
+    emulator.set_volume(0.25);
+    assert_eq!(emulator.volume(), 0.25);
+    assert_eq!(emulator.apu().master_volume(), 0.25);
+
+    emulator.set_volume(3.0);
+    assert_eq!(emulator.apu().master_volume(), 1.0);
+}
+
+#[test]
+fn test_frame_pacer() {
+    let mut pacer = FramePacer::with_frame_rate(50.0).with_max_catch_up(3);
+    assert_eq!(pacer.tick(0.0), 1);
+    assert_eq!(pacer.tick(10.0), 0);
+    assert_eq!(pacer.tick(20.0), 1);
+    assert_eq!(pacer.tick(60.0), 2);
+
+    // Throttled tab: catch up by at most three frames
+    assert_eq!(pacer.tick(1060.0), 3);
+    assert_eq!(pacer.frames_dropped(), 47);
+    assert_eq!(pacer.tick(1080.0), 1);
+
+    pacer.reset();
+    assert_eq!(pacer.tick(5000.0), 1);
+}
+
+#[test]
+fn test_refresh_pacer() {
+    // NTSC on a 60 Hz display: one frame per refresh, audio stretched
+    let mut pacer = RefreshPacer::for_region(rnes_common::Region::Ntsc, 60.0);
+    assert!(pacer.is_locked());
+    for i in 0..10 {
+        assert_eq!(pacer.on_refresh(i as f64 * 1000.0 / 60.0), 1);
+    }
+    assert!((pacer.audio_ratio(1.0) - 60.0988 / 60.0).abs() < 1e-4);
+
+    // PAL on a 60 Hz display: five frames per six refreshes
+    let mut pacer = RefreshPacer::for_region(rnes_common::Region::Pal, 60.0);
+    assert!(!pacer.is_locked());
+    let frames: u32 = (0..600).map(|i| pacer.on_refresh(i as f64 * 1000.0 / 60.0)).sum();
+    assert!((499..=501).contains(&frames));
+    let stats = pacer.stats();
+    assert_eq!(stats.frames_run, frames as u64);
+    assert_eq!(stats.frames_repeated, 600 - frames as u64);
+    assert_eq!(stats.frames_doubled, 0);
+    assert!(stats.jitter_ms < 0.01);
+
+    // Queue fill correction is clamped
+    assert!(pacer.audio_ratio(0.5) > 1.0);
+    assert!(pacer.audio_ratio(1.5) < 1.0);
+    assert!((pacer.audio_ratio(-10.0) - (1.0 + MAX_AUDIO_ADJUST)).abs() < 1e-9);
+    assert_eq!(pacer.stats().audio_ratio, 1.0 + MAX_AUDIO_ADJUST);
+}
+
+#[test]
+fn test_viewport_scale_modes() {
+    use rnes_common::ScaleMode;
+
+    let integer = Viewport::new(ScaleMode::Integer, 1024, 768);
+    assert_eq!(integer, Viewport { x: 128, y: 24, width: 768, height: 720 });
+    assert!(integer.is_integer_scale());
+
+    // 8:7 pixels make the picture 3.2 * 292.6 wide at full height
+    let fit = Viewport::new(ScaleMode::Fit, 1024, 768);
+    assert_eq!(fit, Viewport { x: 44, y: 0, width: 936, height: 768 });
+    assert!(!fit.is_integer_scale());
+    assert_eq!(fit.prescale(), (3, 3));
+
+    let stretch = Viewport::new(ScaleMode::Stretch, 1024, 768);
+    assert_eq!(stretch, Viewport { x: 0, y: 0, width: 1024, height: 768 });
+
+    // A window smaller than the picture still shows it at 1x
+    assert_eq!(Viewport::new(ScaleMode::Integer, 200, 100), Viewport { x: 0, y: 0, width: 256, height: 240 });
+    assert_eq!(Viewport::new(ScaleMode::Fit, 200, 100).prescale(), (1, 1));
+
+    let config = rnes_common::VideoConfig::default();
+    assert_eq!(Viewport::from_config(&config, 1024, 768), fit);
+}
+
+#[test]
+fn test_post_process_chain() {
+    use rnes_common::{CrtPreset, VideoConfig};
+
+    let mut config = VideoConfig::default();
+    assert!(post_process_chain(&config).is_empty());
+
+    // The old scanlines switch still works without a preset
+    config.scanlines = true;
+    let scanlines = CrtParams::for_preset(CrtPreset::Scanlines, 0.3).unwrap();
+    assert_eq!(post_process_chain(&config), vec![PostPass::Crt { preset: CrtPreset::Scanlines, params: scanlines }]);
+
+    // NTSC runs on the CPU first, then the shader
+    config.crt_preset = CrtPreset::Curved;
+    config.ntsc_filter = true;
+    let passes = post_process_chain(&config);
+    assert_eq!(passes[0], PostPass::Ntsc { strength: 0.5 });
+    let PostPass::Crt { preset, params } = passes[1] else { panic!("expected CRT pass") };
+    assert_eq!(preset, CrtPreset::Curved);
+    assert!(params.curvature > 0.0 && params.mask_kind == 1);
+    assert_eq!(params.uniforms((256, 240), (1024, 960))[6..], [256.0, 240.0, 1024.0, 960.0]);
+
+    assert_eq!(CrtParams::for_preset(CrtPreset::None, 0.3), None);
+    for preset in CrtPreset::ALL.into_iter().skip(1) {
+        assert_eq!(CrtParams::for_preset(preset, 2.0).unwrap().scanline_intensity, 1.0);
+    }
+}
+
+#[test]
+fn test_audio_stretcher() {
+    let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.01).sin()).collect();
+    let mut stretcher = AudioStretcher::new();
+    let mut out = Vec::new();
+    stretcher.process(&input, 1.25, &mut out);
+    assert!((1249..=1251).contains(&out.len()));
+
+    out.clear();
+    stretcher.process(&input, 0.8, &mut out);
+    assert!((799..=801).contains(&out.len()));
+    assert!(out.iter().all(|s| (-1.0..=1.0).contains(s)));
+}
+
+#[test]
+fn test_handle_key_bindings() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+
+    // Keys change the held input, latched when the next frame starts
+    assert!(emulator.handle_key("z", true));
+    assert_eq!(emulator.input.held(), [0x01, 0x00]);
+    assert!(emulator.handle_key("Z", false));
+    assert_eq!(emulator.input.held(), [0x00, 0x00]);
+
+    emulator.get_config_mut().input.controller1.bind(rnes_common::Button::Start, "Space");
+    assert!(emulator.handle_key("Space", true));
+    assert_eq!(emulator.input.held(), [0x08, 0x00]);
+
+    assert!(emulator.handle_key("W", true));
+    assert_eq!(emulator.input.held(), [0x08, 0x10]);
+    assert!(!emulator.handle_key("F12", true));
+}
+
+#[test]
+fn test_battery_ram_export_import() {
+    // Same program on MMC1 with the battery flag set
+    let mut rom = create_test_rom();
+    rom[6] = 0x12;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    assert!(emulator.export_battery_ram().is_none());
+
+    emulator.load_rom(cartridge).unwrap();
+    let save = vec![0xA5; 16];
+    emulator.import_battery_ram(&save).unwrap();
+    let exported = emulator.export_battery_ram().unwrap();
+    assert_eq!(&exported[..16], &save[..]);
+
+    // Carts without a battery have nothing to export
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    emulator.load_rom(cartridge).unwrap();
+    assert!(emulator.export_battery_ram().is_none());
+    assert!(emulator.import_battery_ram(&save).is_err());
+}
+
+#[test]
+fn test_save_system_memory_storage() {
+    let save_system = rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new())
+        .with_per_rom_directory(true);
+    assert_eq!(save_system.battery_key("game"), "game/game.sav");
+
+    save_system.save_battery_backup("game", &[1, 2, 3]).unwrap();
+    assert!(save_system.has_battery_backup("game"));
+    assert_eq!(save_system.load_battery_backup("game").unwrap(), vec![1, 2, 3]);
+    assert!(save_system.load_battery_backup("other").unwrap().is_empty());
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default())
+        .with_save_system(save_system);
+    emulator.load_rom(cartridge).unwrap();
+    emulator.save_state(1).unwrap();
+    assert!(emulator.has_save_state(1));
+    emulator.load_state(1).unwrap();
+    emulator.delete_save_state(1).unwrap();
+    assert!(!emulator.has_save_state(1));
+
+    let keys = emulator.save_system.storage().list("game/").unwrap();
+    assert_eq!(keys, vec!["game/game.sav".to_string()]);
+}
+
+#[test]
+fn test_warnings_do_not_abort_loading() {
+    // MMC1 with a battery, and a save that is larger than its 8KB of PRG RAM
+    let mut rom = create_test_rom();
+    rom[6] = 0x12;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let save_system = rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new());
+    save_system.save_battery_backup(&cartridge.rom_id.to_string(), &vec![0x5A; 0x2400]).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default())
+        .with_save_system(save_system);
+
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.take_warnings(), vec![
+        rnes_common::RnesWarning::PrgRamSizeMismatch { expected: 0x2000, actual: 0x2400 },
+    ]);
+    assert!(emulator.take_warnings().is_empty());
+    assert_eq!(emulator.export_battery_ram().unwrap()[0x1FFF], 0x5A);
+
+    // Repeated writes to NROM's PRG ROM are reported once
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    emulator.load_rom(cartridge).unwrap();
+    emulator.bus.write_byte(0x8000, 0x01).unwrap();
+    emulator.bus.write_byte(0x8001, 0x02).unwrap();
+    assert_eq!(emulator.take_warnings(), vec![
+        rnes_common::RnesWarning::RomWrite { memory: "PRG ROM" },
+    ]);
+    emulator.bus.write_byte(0x8002, 0x03).unwrap();
+    assert!(emulator.take_warnings().is_empty());
+
+    assert!(matches!(
+        emulator.import_battery_ram(&[0; 16]),
+        Err(rnes_common::RnesError::UnsupportedFeature { .. })
+    ));
+}
+
+#[test]
+fn test_save_state_integrity_checks() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    let data = emulator.save_state_to_bytes().unwrap();
+    let header = rnes_common::SaveState::peek_header(&data).unwrap();
+    assert_eq!(header.version, rnes_common::SAVE_STATE_VERSION);
+    assert!(header.is_compressed());
+    assert_eq!(header.rom_crc32, emulator.bus.cartridge.as_ref().unwrap().crc32);
+
+    // Flipping a payload byte must be detected
+    let mut corrupted = data.clone();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0xFF;
+    assert!(matches!(
+        emulator.load_state_from_bytes(&corrupted),
+        Err(rnes_common::RnesError::CorruptSaveState(_))
+    ));
+
+    // Unsupported format versions are rejected
+    let mut future = data.clone();
+    future[4] = 0xFF;
+    assert!(matches!(
+        emulator.load_state_from_bytes(&future),
+        Err(rnes_common::RnesError::SaveStateVersionMismatch { expected: rnes_common::SAVE_STATE_VERSION, .. })
+    ));
+
+    // States from another ROM are rejected
+    let mut other_rom = create_test_rom();
+    other_rom[16 + 0x100] = 0x00;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&other_rom).unwrap();
+    emulator.load_rom(cartridge).unwrap();
+    assert!(matches!(
+        emulator.load_state_from_bytes(&data),
+        Err(rnes_common::RnesError::SaveStateRomMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_malformed_inputs_return_errors() {
+    // A header with no PRG ROM would leave mappers nothing to index
+    let mut no_prg = create_test_rom();
+    no_prg[4] = 0;
+    assert!(rnes_cartridge::Cartridge::from_bytes(&no_prg).is_err());
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    let data = emulator.save_state_to_bytes().unwrap();
+
+    // An oversized payload length is rejected before anything is allocated
+    let mut huge = data.clone();
+    huge[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(emulator.load_state_from_bytes(&huge).is_err());
+
+    // A valid header over garbage fails in the deserializer, not with a panic
+    let payload = vec![0xFF; 64];
+    let header = rnes_common::SaveStateHeader {
+        version: rnes_common::SAVE_STATE_VERSION,
+        flags: 0,
+        rom_crc32: 0,
+        payload_crc32: rnes_common::crc32(&payload),
+        payload_len: payload.len() as u32,
+    };
+    let mut garbage = header.to_bytes().to_vec();
+    garbage.extend_from_slice(&payload);
+    assert!(emulator.load_state_from_bytes(&garbage).is_err());
+
+    // Truncated states are rejected
+    assert!(emulator.load_state_from_bytes(&data[..data.len() / 2]).is_err());
+}
+
+#[test]
+fn test_snapshot_excludes_frame_buffer() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    let snapshot = emulator.snapshot().unwrap();
+    let counter = emulator.bus.ram[0x10];
+    let pc = emulator.cpu.pc;
+
+    emulator.run_frame().unwrap();
+    emulator.restore_snapshot(&snapshot).unwrap();
+    assert_eq!(emulator.bus.ram[0x10], counter);
+    assert_eq!(emulator.cpu.pc, pc);
+
+    // Serialized states no longer carry a 256x240 pixel buffer
+    let data = emulator.save_state_to_bytes().unwrap();
+    assert!(data.len() < rnes_common::SCREEN_WIDTH * rnes_common::SCREEN_HEIGHT);
+}
+
+#[test]
+fn test_auto_resume() {
+    let mut config = rnes_common::Config::default();
+    config.save_states.auto_resume = true;
+    let storage = rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new());
+    let mut emulator = Emulator::with_config(config).with_save_system(storage);
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    emulator.load_rom(cartridge.clone()).unwrap();
+    assert!(!emulator.resume_last_session().unwrap());
+
+    emulator.start();
+    emulator.run_frame().unwrap();
+    let counter = emulator.bus.ram[0x10];
+    emulator.shutdown().unwrap();
+    assert!(emulator.has_resume_state());
+
+    // Next launch: reload the ROM and pick up where we left off
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.bus.ram[0x10], 0);
+    assert!(emulator.resume_last_session().unwrap());
+    assert_eq!(emulator.bus.ram[0x10], counter);
+
+    // Disabled in config: nothing is restored
+    emulator.get_config_mut().save_states.auto_resume = false;
+    assert!(!emulator.resume_last_session().unwrap());
+}
+
+#[test]
+fn test_fast_boot_restores_cached_boot_state() {
+    let mut config = rnes_common::Config::default();
+    config.save_states.fast_boot = true;
+    let storage = rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new());
+    let mut emulator = Emulator::with_config(config).with_save_system(storage);
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    emulator.load_rom(cartridge.clone()).unwrap();
+    assert!(!emulator.fast_boot().unwrap());
+
+    emulator.start();
+    emulator.run_frames(2).unwrap();
+    let counter = emulator.bus.ram[0x10];
+    emulator.cache_boot_state().unwrap();
+    assert!(emulator.has_boot_state());
+
+    // Next launch starts past the boot
+    emulator.load_rom(cartridge.clone()).unwrap();
+    assert_eq!(emulator.bus.ram[0x10], 0);
+    assert!(emulator.fast_boot().unwrap());
+    assert_eq!(emulator.bus.ram[0x10], counter);
+
+    // Other power-on settings or the flag off: boot normally
+    emulator.load_rom(cartridge.clone()).unwrap();
+    emulator.set_seed(7);
+    assert!(!emulator.fast_boot().unwrap());
+    emulator.set_seed(0);
+    emulator.get_config_mut().save_states.fast_boot = false;
+    assert!(!emulator.fast_boot().unwrap());
+
+    // Input means the game is no longer just booting
+    emulator.load_rom(cartridge).unwrap();
+    emulator.set_controller1(rnes_common::ControllerState::from_bits(0x08));
+    assert!(emulator.cache_boot_state().is_err());
+}
+
+#[test]
+fn test_session_paces_latches_input_and_rewinds() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let mut session = Session::new(rnes_common::Config::default());
+    session.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    let presented = Arc::new(AtomicUsize::new(0));
+    let counter = presented.clone();
+    session.set_video_callback(move |frame| {
+        assert_eq!(frame.len(), rnes_common::SCREEN_WIDTH * rnes_common::SCREEN_HEIGHT);
+        counter.fetch_add(1, Ordering::Relaxed);
+    });
+
+    // Input waits for the next frame
+    session.set_input(0, 0x08);
+    assert_eq!(session.emulator().bus.controller1.bits(), 0);
+    assert_eq!(session.tick(0.0).unwrap(), 1);
+    assert_eq!(session.emulator().bus.controller1.bits(), 0x08);
+
+    // Two frames are due after 34ms; only the last is presented
+    assert_eq!(session.tick(34.0).unwrap(), 2);
+    assert_eq!(session.emulator().ppu().frame_count(), 3);
+    assert_eq!(presented.load(Ordering::Relaxed), 2);
+
+    session.pause();
+    assert_eq!(session.tick(1000.0).unwrap(), 0);
+    session.run_frame().unwrap();
+    assert_eq!(session.emulator().ppu().frame_count(), 3);
+    session.resume();
+
+    // Rewind walks back through the snapshots
+    session.enable_rewind(1, 2);
+    session.run_frame().unwrap();
+    let first = session.emulator().bus.ram[0x10];
+    session.run_frame().unwrap();
+    session.run_frame().unwrap();
+    assert_eq!(session.rewind_buffer().unwrap().len(), 2);
+    assert!(session.rewind().unwrap());
+    assert_eq!(session.emulator().ppu().frame_count(), 6);
+    assert!(session.rewind().unwrap());
+    assert_eq!(session.emulator().ppu().frame_count(), 5);
+    assert_ne!(session.emulator().bus.ram[0x10], first);
+    assert!(!session.rewind().unwrap());
+}
+
+#[test]
+fn test_input_latched_at_frame_boundaries() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    // Held input set in the middle of a frame waits for the next one
+    emulator.step().unwrap();
+    emulator.set_input(0, 0x01);
+    emulator.step().unwrap();
+    assert_eq!(emulator.get_controller1_state().bits(), 0x00);
+    assert_eq!(emulator.next_input_frame(), 2);
+    emulator.run_frame().unwrap();
+    emulator.step().unwrap();
+    assert_eq!(emulator.get_controller1_state().bits(), 0x01);
+
+    // Queued input applies to its frame only, then the held input returns
+    let frame = emulator.next_input_frame();
+    emulator.queue_input(frame, [0x80, 0x40]);
+    emulator.queue_input(frame + 5, [0x02, 0x00]);
+    emulator.run_frame().unwrap();
+    emulator.step().unwrap();
+    assert_eq!(emulator.get_controller1_state().bits(), 0x80);
+    assert_eq!(emulator.get_controller2_state().bits(), 0x40);
+    emulator.run_frame().unwrap();
+    emulator.step().unwrap();
+    assert_eq!(emulator.get_controller1_state().bits(), 0x01);
+    assert_eq!(emulator.get_controller2_state().bits(), 0x00);
+    assert_eq!(emulator.input.queued_len(), 1);
+}
+
+#[test]
+fn test_port_assignment_and_zapper() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    emulator.set_controller1(rnes_common::ControllerState::from_bits(0x01));
+    emulator.set_controller2(rnes_common::ControllerState::from_bits(0x02));
+    // With the strobe held high each port reads button A
+    emulator.bus.write_byte(0x4016, 0x01).unwrap();
+    assert_eq!(emulator.bus.read_byte(0x4016).unwrap(), 0x01);
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap(), 0x00);
+
+    emulator.set_swap_ports(true);
+    assert_eq!(emulator.bus.read_byte(0x4016).unwrap(), 0x00);
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap(), 0x01);
+    emulator.set_swap_ports(false);
+
+    // A Zapper reads dark until it sees a bright pixel the beam just drew
+    emulator.set_port_device(1, rnes_common::InputDevice::Zapper);
+    assert_eq!(emulator.config.input.port_devices[1], rnes_common::InputDevice::Zapper);
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap(), 0x08);
+    emulator.set_zapper_trigger(true);
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap(), 0x18);
+
+    emulator.start();
+    emulator.run_frame().unwrap();
+    while emulator.ppu().scanline() != 15 {
+        emulator.step().unwrap();
+    }
+    let mut frame = emulator.ppu().frame_buffer().to_vec();
+    frame[10 * rnes_common::SCREEN_WIDTH + 10] = rnes_common::Pixel::WHITE;
+    emulator.ppu_mut().set_frame_buffer(frame);
+    emulator.set_zapper_aim(Some((10, 10)));
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap(), 0x10);
+    emulator.set_zapper_aim(Some((10, 100)));
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap(), 0x18);
+
+    // Port 1 still has the controller
+    assert_eq!(emulator.bus.read_byte(0x4016).unwrap(), 0x01);
+}
+
+#[test]
+fn test_zapper_click_to_fire() {
+    assert_eq!(canvas_to_screen(0.0, 0.0, 512.0, 480.0), Some((0, 0)));
+    assert_eq!(canvas_to_screen(511.0, 479.0, 512.0, 480.0), Some((255, 239)));
+    assert_eq!(canvas_to_screen(200.0, 100.0, 768.0, 720.0), Some((66, 33)));
+    assert_eq!(canvas_to_screen(512.0, 10.0, 512.0, 480.0), None);
+    assert_eq!(canvas_to_screen(-1.0, 10.0, 512.0, 480.0), None);
+
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    emulator.set_port_device(1, rnes_common::InputDevice::Zapper);
+    emulator.start();
+
+    // A click holds the trigger for a few frames, then releases it
+    emulator.fire_zapper();
+    for _ in 0..ZAPPER_PULL_FRAMES {
+        assert_eq!(emulator.bus.read_byte(0x4017).unwrap() & 0x10, 0x10);
+        emulator.run_frame().unwrap();
+    }
+    assert_eq!(emulator.bus.read_byte(0x4017).unwrap() & 0x10, 0x00);
+
+    // The crosshair follows the aim only while a Zapper is plugged in
+    emulator.overlay.show_crosshair = true;
+    emulator.set_zapper_aim(canvas_to_screen(100.0, 100.0, 256.0, 240.0));
+    assert_eq!(emulator.zapper().aim, Some((100, 100)));
+    assert_eq!(emulator.overlay_status().zapper_aim, Some((100, 100)));
+    emulator.set_port_device(1, rnes_common::InputDevice::Controller);
+    assert_eq!(emulator.overlay_status().zapper_aim, None);
+}
+
+#[test]
+fn test_screenshot_png_and_state_export() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    assert!(matches!(emulator.screenshot_png(), Err(rnes_common::RnesError::NoRomLoaded)));
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    let image = rnes_common::png::decode_png(&emulator.screenshot_png().unwrap()).unwrap();
+    assert_eq!((image.width, image.height), (rnes_common::SCREEN_WIDTH as u32, rnes_common::SCREEN_HEIGHT as u32));
+    let mut rgba = vec![0; rnes_common::SCREEN_WIDTH * rnes_common::SCREEN_HEIGHT * 4];
+    emulator.write_frame_rgba(&mut rgba);
+    assert_eq!(image.pixels, rgba);
+
+    let exported = emulator.save_state_to_bytes().unwrap();
+    let counter = emulator.bus.read_byte(0x10).unwrap();
+    emulator.run_frame().unwrap();
+    assert_ne!(emulator.bus.read_byte(0x10).unwrap(), counter);
+    emulator.load_state_from_bytes(&exported).unwrap();
+    assert_eq!(emulator.bus.read_byte(0x10).unwrap(), counter);
+}
+
+#[test]
+fn test_frame_exchange_double_buffers() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    let mut exchange = FrameExchange::new();
+    assert_eq!(exchange.frame(), 0);
+    assert_eq!(exchange.buffers().len(), FRAME_RGBA_BYTES * 2);
+    let mut first = vec![0; FRAME_RGBA_BYTES];
+    emulator.write_frame_rgba(&mut first);
+    assert_eq!(exchange.publish(&emulator), 1);
+    assert_eq!(exchange.front(), &first[..]);
+
+    // The next frame goes to the other buffer and leaves the first intact
+    emulator.overlay.fill_rect(0, 0, 2, 1, rnes_common::Pixel::new(255, 0, 0));
+    assert_eq!(exchange.publish(&emulator), 2);
+    assert_eq!(exchange.frame(), 2);
+    assert_eq!(exchange.buffer(1), &first[..]);
+    assert_eq!(&exchange.front()[0..4], &[255, 0, 0, 255]);
+    assert_eq!(&exchange.buffers()[..FRAME_RGBA_BYTES], exchange.front());
+}
+
+#[test]
+fn test_rom_download_and_structured_errors() {
+    let rom = create_test_rom();
+    let mut download = RomDownload::new(Some(rom.len())).unwrap();
+    for chunk in rom.chunks(4096) {
+        download.push(chunk).unwrap();
+    }
+    assert_eq!(download.received(), rom.len());
+    assert_eq!(download.progress(), Some(1.0));
+    let mut session = Session::new(rnes_common::Config::default()).with_resume(false);
+    session.load_rom(download.finish().unwrap()).unwrap();
+    assert!(session.emulator().is_running());
+
+    // Not a ROM: rejected as soon as the header is in
+    let mut download = RomDownload::new(None).unwrap();
+    download.push(b"<!DOCTYPE").unwrap();
+    let error = download.push(b" html><html>").unwrap_err();
+    assert_eq!(error.kind(), "rom_format");
+    assert!(error.is_rom_error());
+    assert_eq!(download.progress(), None);
+
+    assert!(RomDownload::new(Some(MAX_ROM_SIZE + 1)).is_err());
+
+    // A bad drop leaves the running game alone
+    let error = session.load_rom_bytes(&rom[..100]).unwrap_err();
+    assert_eq!(error.kind(), "rom_format");
+    assert!(session.emulator().is_running());
+    session.load_rom_bytes(&rom).unwrap();
+    assert_eq!(rnes_common::RnesError::NoRomLoaded.kind(), "no_rom_loaded");
+    assert!(!rnes_common::RnesError::NoRomLoaded.is_rom_error());
+}
+
+#[test]
+fn test_session_stats() {
+    let mut session = Session::new(rnes_common::Config::default()).with_max_catch_up(2);
+    session.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    session.set_video_callback(|_| {});
+
+    // A second of on-time ticks measures the frame rate
+    let frame_ms = 1000.0 / 60.0988;
+    session.tick(0.0).unwrap();
+    for i in 1..=61 {
+        assert_eq!(session.tick(i as f64 * frame_ms + 1.0).unwrap(), 1);
+    }
+    let stats = session.stats();
+    assert!((stats.fps - 60.0988).abs() < 1.5, "fps {}", stats.fps);
+    assert_eq!(stats.frames_run, stats.frames_presented);
+    assert_eq!(stats.frames_dropped, 0);
+
+    // A stalled host runs two frames, presents one, and drops the rest
+    let before = session.stats();
+    assert_eq!(session.tick(61.0 * frame_ms + 100.0).unwrap(), 2);
+    let stats = session.stats();
+    assert_eq!(stats.frames_run, before.frames_run + 2);
+    assert_eq!(stats.frames_presented, before.frames_presented + 1);
+    assert!(stats.frames_dropped >= 4);
+
+    session.report_audio_underrun();
+    assert_eq!(session.stats().audio_underruns, 1);
+
+    // Counters start over with the next ROM
+    session.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    assert_eq!(session.stats(), SessionStats::default());
+}
+
+#[test]
+fn test_session_background_behavior() {
+    use rnes_common::BackgroundBehavior;
+
+    let mut config = rnes_common::Config::default();
+    config.general.on_focus_loss = BackgroundBehavior::Mute;
+    config.audio.master_volume = 0.8;
+    let mut session = Session::new(config);
+    session.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    let volume = |session: &Session| session.emulator().bus.apu().master_volume();
+
+    // Unfocused: still running, but silent
+    session.set_focused(false);
+    assert_eq!(session.background_behavior(), BackgroundBehavior::Mute);
+    assert_eq!(volume(&session), 0.0);
+    assert_eq!(session.emulator().volume(), 0.8);
+    session.run_frame().unwrap();
+    assert_eq!(session.stats().frames_run, 1);
+
+    // Hidden as well: the stronger setting, pause, wins
+    session.set_visible(false);
+    assert_eq!(session.background_behavior(), BackgroundBehavior::Pause);
+    session.run_frame().unwrap();
+    assert_eq!(session.tick(0.0).unwrap(), 0);
+    assert_eq!(session.stats().frames_run, 1);
+    assert!(!session.is_paused());
+
+    session.set_visible(true);
+    assert_eq!(session.background_behavior(), BackgroundBehavior::Mute);
+    session.set_focused(true);
+    assert_eq!(session.background_behavior(), BackgroundBehavior::KeepRunning);
+    assert_eq!(volume(&session), 0.8);
+
+    // A pause by the user outlasts the window coming back
+    session.pause();
+    session.set_visible(false);
+    session.set_visible(true);
+    session.run_frame().unwrap();
+    assert_eq!(session.stats().frames_run, 1);
+}
+
+/// ROM that enables NMI and spins, with an NMI handler running a delay
+/// loop `outer` times 255 iterations
+fn create_nmi_rom(outer: u8) -> Vec<u8> {
+    let mut rom = create_test_rom();
+    let prg = &mut rom[16..16 + 16 * 1024];
+    // LDA #$80; STA $2000; JMP *
+    prg[..8].copy_from_slice(&[0xA9, 0x80, 0x8D, 0x00, 0x20, 0x4C, 0x05, 0x80]);
+    // LDY #outer; LDX #$FF; DEX; BNE -3; DEY; BNE -8; RTI
+    prg[0x10..0x1B].copy_from_slice(&[0xA0, outer, 0xA2, 0xFF, 0xCA, 0xD0, 0xFD, 0x88, 0xD0, 0xF8, 0x40]);
+    prg[0x3FFA] = 0x10;
+    prg[0x3FFB] = 0x80;
+    rom
+}
+
+#[test]
+fn test_cycle_profiler_splits_nmi_and_flags_overruns() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_nmi_rom(1)).unwrap()).unwrap();
+    emulator.enable_profiler(4);
+    emulator.start();
+    for _ in 0..6 {
+        emulator.run_frame().unwrap();
+    }
+
+    let profiler = emulator.profiler().unwrap();
+    assert_eq!(profiler.frames().len(), 4);
+    let frame = *profiler.last_frame().unwrap();
+    assert_eq!(frame.nmi_count, 1);
+    assert!((1200..1400).contains(&frame.nmi_cycles), "{:?}", frame);
+    assert!((29_000..30_500).contains(&frame.total_cycles()), "{:?}", frame);
+    assert!(!frame.vblank_overrun);
+    assert!(!profiler.in_nmi());
+
+    // A handler longer than vblank is flagged
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_nmi_rom(16)).unwrap()).unwrap();
+    emulator.start();
+    for _ in 0..6 {
+        emulator.run_frame().unwrap();
+    }
+    let profiler = emulator.profiler().unwrap();
+    let frame = *profiler.last_frame().unwrap();
+    assert!(frame.vblank_overrun, "{:?}", frame);
+    assert!(frame.nmi_cycles > frame.main_cycles);
+    assert!(profiler.overruns().count() >= 2);
+}
+
+#[test]
+fn test_code_coverage_records_merges_and_diffs() {
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_nmi_rom(1)).unwrap()).unwrap();
+    emulator.start_coverage();
+    emulator.start();
+    for _ in 0..10 {
+        emulator.step().unwrap();
+    }
+
+    // Only the main loop runs before the first NMI
+    let boot = emulator.coverage().unwrap().clone();
+    assert_eq!(boot.ranges(), vec![0..8]);
+    for _ in 0..3 {
+        emulator.run_frame().unwrap();
+    }
+    let coverage = emulator.stop_coverage().unwrap();
+    assert!(emulator.coverage().is_none());
+    assert_eq!(coverage.ranges(), vec![0..8, 0x10..0x1B]);
+    assert_eq!(coverage.executed(), 19);
+    let banks = coverage.banks();
+    assert_eq!(banks.len(), 2);
+    assert_eq!((banks[0].executed, banks[0].size), (19, COVERAGE_BANK_SIZE));
+    assert_eq!(banks[1].executed, 0);
+
+    // The NMI handler is what the longer session added
+    let added = coverage.difference(&boot).unwrap();
+    assert_eq!(added.ranges(), vec![0x10..0x1B]);
+    let mut merged = boot.clone();
+    merged.merge(&added).unwrap();
+    assert_eq!(merged, coverage);
+    assert!(boot.difference(&CodeCoverage::new(1024)).is_err());
+}
+
+#[test]
+fn test_code_coverage_json_round_trip() {
+    let mut coverage = CodeCoverage::new(3 * COVERAGE_BANK_SIZE);
+    for offset in [0, 7, 8, COVERAGE_BANK_SIZE + 3, 3 * COVERAGE_BANK_SIZE - 1] {
+        coverage.mark(offset);
+    }
+    let json = coverage.to_json().unwrap();
+    assert_eq!(CodeCoverage::from_json(&json).unwrap(), coverage);
+    assert!(CodeCoverage::from_json("{}").is_err());
+}
+
+#[test]
+fn test_list_save_states_with_metadata() {
+    let storage = rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new());
+    let mut emulator = Emulator::with_config(rnes_common::Config::default()).with_save_system(storage);
+    assert!(emulator.list_save_states().unwrap().is_empty());
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+    emulator.save_state(3).unwrap();
+    emulator.run_frame().unwrap();
+    emulator.save_state(1).unwrap();
+
+    let slots = emulator.list_save_states().unwrap();
+    assert_eq!(slots.iter().map(|s| s.slot).collect::<Vec<_>>(), vec![1, 3]);
+    assert!(slots[0].metadata.frame_count > slots[1].metadata.frame_count);
+
+    let thumbnail = slots[0].metadata.thumbnail.as_ref().unwrap();
+    assert_eq!((thumbnail.width, thumbnail.height), (64, 60));
+    assert_eq!(thumbnail.rgb.len(), 64 * 60 * 3);
+}
+
+#[test]
+fn test_overclock_adds_cpu_time_per_frame() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge.clone()).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+    let start = emulator.get_state().cpu_cycles;
+    emulator.run_frame().unwrap();
+    let normal = emulator.get_state().cpu_cycles - start;
+
+    // Per-game override wins over the global setting
+    let mut config = rnes_common::Config::default();
+    config.general.overclock_scanlines = 10;
+    config.general.overclock_overrides.insert(format!("{:08X}", cartridge.crc32), 50);
+    let mut emulator = Emulator::with_config(config);
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.overclock_scanlines(), 50);
+    emulator.start();
+    emulator.run_frame().unwrap();
+    let start = emulator.get_state().cpu_cycles;
+    emulator.run_frame().unwrap();
+    let overclocked = emulator.get_state().cpu_cycles - start;
+
+    // 50 scanlines * 341 dots / 3 dots per CPU cycle
+    let extra = overclocked - normal;
+    assert!((5600..=5720).contains(&extra), "extra cycles: {}", extra);
+}
+
+#[test]
+fn test_region_detection() {
+    use rnes_common::Region;
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.region(), Region::Ntsc);
+    assert!((emulator.frame_rate() - 60.0988).abs() < 1e-6);
+
+    // NES 2.0 header with PAL timing in byte 12
+    let mut rom = create_test_rom();
+    rom[7] = 0x08;
+    rom[12] = 0x01;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.region(), Region::Pal);
+    assert!((emulator.frame_rate() - 50.007).abs() < 1e-6);
+    assert_eq!(emulator.bus.ppu().region(), Region::Pal);
+    emulator.set_region(Region::Ntsc);
+    assert_eq!(emulator.bus.ppu().region(), Region::Ntsc);
+
+    assert_eq!(Region::from_filename("Game (Europe).nes"), Some(Region::Pal));
+    assert_eq!(Region::from_filename("Game (U) [!].nes"), Some(Region::Ntsc));
+    assert_eq!(Region::from_filename("game.nes"), None);
+}
+
+#[test]
+fn test_sprite_limit_option() {
+    let mut config = rnes_common::Config::default();
+    config.video.remove_sprite_limit = true;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(config);
+    emulator.load_rom(cartridge).unwrap();
+    assert!(!emulator.bus.ppu().sprite_limit());
+
+    emulator.set_remove_sprite_limit(false);
+    assert!(emulator.bus.ppu().sprite_limit());
+    assert!(!emulator.sprite_limit_removed());
+}
+
+#[test]
+fn test_frame_callback_and_peek() {
+    use std::sync::{Arc, Mutex};
+
+    let rom = create_test_rom();
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.ra_hash(), Some(ra_hash(&rom)));
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorder = seen.clone();
+    emulator.add_frame_callback(move |frame, memory| {
+        recorder.lock().unwrap().push((frame, memory.peek(0x0010)));
+    });
+
+    emulator.start();
+    for _ in 0..3 {
+        emulator.run_frame().unwrap();
+    }
+
+    // One call per frame, seeing the counter the test ROM increments
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 3);
+    assert!(seen.windows(2).all(|pair| pair[1].0 == pair[0].0 + 1));
+    assert_eq!(seen[2].1, emulator.peek(0x0010));
+    assert_eq!(emulator.peek(0x0810), emulator.peek(0x0010));
+
+    // PRG ROM through the mapper: INC $10 at the reset vector
+    let mut code = [0; 2];
+    emulator.peek_range(0x8000, &mut code);
+    assert_eq!(code, [0xE6, 0x10]);
+}
+
+#[test]
+fn test_overlay_applies_to_frontend_frame() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+
+    let raw = emulator.get_ppu_frame_buffer().unwrap().to_vec();
+    emulator.overlay.fill_rect(0, 0, 2, 1, rnes_common::Pixel::new(255, 0, 0));
+
+    let mut rgba = vec![0; rnes_common::SCREEN_WIDTH * rnes_common::SCREEN_HEIGHT * 4];
+    emulator.write_frame_rgba(&mut rgba);
+    assert_eq!(&rgba[..8], &[255, 0, 0, 255, 255, 0, 0, 255]);
+
+    // The PPU output itself is untouched
+    assert_eq!(emulator.get_ppu_frame_buffer().unwrap(), &raw[..]);
+    emulator.overlay.clear();
+    assert_eq!(emulator.get_frame_buffer().unwrap(), raw);
+}
+
+#[test]
+fn test_lag_frame_counter() {
+    // The test ROM never reads the controllers: every frame lags
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frames(3).unwrap();
+    assert_eq!(emulator.lag_frames(), 3);
+    assert!(emulator.is_lag_frame());
+
+    // LDA $4016; JMP $8000 polls input every frame
+    let mut rom = create_test_rom();
+    rom[16..22].copy_from_slice(&[0xAD, 0x16, 0x40, 0x4C, 0x00, 0x80]);
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frames(3).unwrap();
+    assert_eq!(emulator.lag_frames(), 0);
+    assert!(!emulator.is_lag_frame());
+    assert_eq!(emulator.get_state().lag_frames, 0);
+}
+
+#[test]
+fn test_ram_init_patterns() {
+    let load = |pattern: rnes_common::RamInitPattern, seed: u64| {
+        let mut config = rnes_common::Config::default();
+        config.general.ram_init = pattern;
+        config.general.ram_init_seed = seed;
+        let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+        let mut emulator = Emulator::with_config(config);
+        emulator.load_rom(cartridge).unwrap();
+        emulator
+    };
+
+    assert!(load(rnes_common::RamInitPattern::Zeros, 0).bus.ram.iter().all(|&b| b == 0x00));
+    assert!(load(rnes_common::RamInitPattern::Ones, 0).bus.ram.iter().all(|&b| b == 0xFF));
+    assert_eq!(
+        &load(rnes_common::RamInitPattern::Fceux, 0).bus.ram[..16],
+        &[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]
+    );
+
+    // Seeded random contents are reproducible
+    let first = load(rnes_common::RamInitPattern::Random, 42);
+    let second = load(rnes_common::RamInitPattern::Random, 42);
+    let other = load(rnes_common::RamInitPattern::Random, 43);
+    assert_eq!(first.bus.ram, second.bus.ram);
+    assert_ne!(first.bus.ram, other.bus.ram);
+
+    // A reset keeps RAM contents
+    let mut emulator = first;
+    let ram = emulator.bus.ram;
+    emulator.reset().unwrap();
+    assert_eq!(emulator.bus.ram, ram);
+}
+
+#[test]
+fn test_save_state_carries_power_on_seed() {
+    let mut config = rnes_common::Config::default();
+    config.general.ram_init = rnes_common::RamInitPattern::Random;
+    config.general.ram_init_seed = 42;
+    let mut recorder = Emulator::with_config(config);
+    recorder.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    let save_state = recorder.create_save_state().unwrap();
+    assert_eq!((save_state.ram_init, save_state.seed), (rnes_common::RamInitPattern::Random, 42));
+
+    // A player with other settings powers on like the recording after loading the state
+    let mut player = Emulator::with_config(rnes_common::Config::default());
+    player.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    player.apply_save_state(&save_state).unwrap();
+    assert_eq!(player.seed(), 42);
+    player.power_cycle().unwrap();
+    recorder.power_cycle().unwrap();
+    assert_eq!(player.bus.ram, recorder.bus.ram);
+
+    player.set_seed(43);
+    player.power_cycle().unwrap();
+    assert_ne!(player.bus.ram, recorder.bus.ram);
+
+    // The restored settings stay with that game and never reach the config
+    assert_eq!(player.get_config().general.ram_init, rnes_common::RamInitPattern::default());
+    player.apply_save_state(&save_state).unwrap();
+    player.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_mmc3_rom()).unwrap()).unwrap();
+    assert_eq!(player.seed(), 43);
+    let mut fresh = Emulator::with_config(player.get_config().clone());
+    fresh.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_mmc3_rom()).unwrap()).unwrap();
+    assert_eq!(player.bus.ram, fresh.bus.ram);
+}
+
+#[test]
+fn test_soft_reset_vs_power_cycle() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frames(2).unwrap();
+    let counter = emulator.bus.ram[0x10];
+    assert_ne!(counter, 0);
+
+    // Soft reset keeps RAM and runs the 7-cycle reset sequence
+    emulator.soft_reset().unwrap();
+    assert_eq!(emulator.bus.ram[0x10], counter);
+    assert_eq!(emulator.cpu.sp, 0xFA);
+    assert_eq!(emulator.cpu.pc, 0x8000);
+    assert!(emulator.bus.ppu().in_reset());
+    assert!(emulator.is_running());
+    for _ in 0..7 {
+        assert_eq!(emulator.step().unwrap(), 1);
+        assert_eq!(emulator.cpu.pc, 0x8000);
+    }
+
+    // Power cycle clears RAM and restores power-up registers
+    emulator.power_cycle().unwrap();
+    assert_eq!(emulator.bus.ram[0x10], 0);
+    assert_eq!(emulator.cpu.sp, 0xFD);
+    assert!(!emulator.bus.ppu().in_reset());
+    assert!(emulator.is_running());
+}
+
+#[test]
+fn test_chr_ram_writes_and_save_state() {
+    // NROM with no CHR ROM: the board has 8KB of CHR RAM instead
+    let mut rom = create_test_rom();
+    rom[5] = 0;
+    rom.truncate(16 + 16 * 1024);
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    assert!(cartridge.chr_is_ram);
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+
+    // Write pattern data and a nametable byte through $2006/$2007
+    emulator.bus.write_byte(0x2006, 0x01).unwrap();
+    emulator.bus.write_byte(0x2006, 0x23).unwrap();
+    emulator.bus.write_byte(0x2007, 0xA5).unwrap();
+    emulator.bus.write_byte(0x2006, 0x20).unwrap();
+    emulator.bus.write_byte(0x2006, 0x45).unwrap();
+    emulator.bus.write_byte(0x2007, 0x5A).unwrap();
+    assert_eq!(emulator.bus.mapper_mut().read_chr(0x0123).unwrap(), 0xA5);
+    assert_eq!(emulator.bus.ppu().nametable_ram()[0x045], 0x5A);
+
+    let data = emulator.save_state_to_bytes().unwrap();
+    emulator.bus.mapper_mut().write_chr(0x0123, 0x00).unwrap();
+    emulator.bus.ppu_mut().set_nametable_ram(&[0; 4096]);
+
+    emulator.load_state_from_bytes(&data).unwrap();
+    assert_eq!(emulator.bus.mapper_mut().read_chr(0x0123).unwrap(), 0xA5);
+    assert_eq!(emulator.bus.ppu().nametable_ram()[0x045], 0x5A);
+}
+
+#[test]
+fn test_battery_ram_dirty_flush() {
+    let mut rom = create_test_rom();
+    rom[6] = 0x12;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let mut config = rnes_common::Config::default();
+    config.general.battery_flush_delay_ms = 0;
+    let mut emulator = Emulator::with_config(config)
+        .with_save_system(rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new()));
+    emulator.load_rom(cartridge).unwrap();
+    let rom_name = emulator.rom_name.clone().unwrap();
+
+    // Nothing is written until the game touches battery RAM
+    assert!(!emulator.flush_battery_backup().unwrap());
+    assert!(!emulator.save_system.has_battery_backup(&rom_name));
+
+    // Saved once writes stop
+    emulator.bus.write_byte(0x6000, 0x42).unwrap();
+    assert!(emulator.bus.mapper().prg_ram_dirty());
+    emulator.start();
+    emulator.step().unwrap();
+    assert!(!emulator.bus.mapper().prg_ram_dirty());
+    assert_eq!(emulator.save_system.load_battery_backup(&rom_name).unwrap()[0], 0x42);
+
+    // Writing the same value again does not mark the RAM dirty
+    emulator.bus.write_byte(0x6000, 0x42).unwrap();
+    assert!(!emulator.bus.mapper().prg_ram_dirty());
+
+    // stop() flushes pending writes regardless of the debounce delay
+    emulator.config.general.battery_flush_delay_ms = 60_000;
+    emulator.bus.write_byte(0x6001, 0x43).unwrap();
+    emulator.step().unwrap();
+    assert_eq!(emulator.save_system.load_battery_backup(&rom_name).unwrap()[1], 0x00);
+    emulator.stop();
+    assert_eq!(emulator.save_system.load_battery_backup(&rom_name).unwrap()[1], 0x43);
+    assert!(!emulator.flush_battery_backup().unwrap());
+}
+
+#[test]
+fn test_sav_named_after_rom_file() {
+    let dir = std::env::temp_dir().join(format!("rnes_sav_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut rom = create_test_rom();
+    rom[6] = 0x12;
+    let rom_path = dir.join("Some Game (USA).nes");
+    std::fs::write(&rom_path, &rom).unwrap();
+
+    // A .sav left next to the ROM by another emulator is picked up
+    let mut foreign_save = vec![0u8; 8192];
+    foreign_save[0] = 0x5A;
+    std::fs::write(dir.join("Some Game (USA).sav"), &foreign_save).unwrap();
+
+    let mut config = rnes_common::Config::default();
+    config.storage.save_dir = dir.join("saves");
+    config.storage.sav_beside_rom = true;
+    let mut emulator = Emulator::with_config(config);
+    emulator.load_rom(rnes_cartridge::Cartridge::from_file(&rom_path).unwrap()).unwrap();
+    assert_eq!(emulator.rom_name.as_deref(), Some("Some Game (USA)"));
+    assert_eq!(emulator.export_battery_ram().unwrap()[0], 0x5A);
+
+    // Raw PRG RAM is written back beside the ROM
+    emulator.bus.write_byte(0x6000, 0xA5).unwrap();
+    emulator.stop();
+    let written = std::fs::read(dir.join("Some Game (USA).sav")).unwrap();
+    assert_eq!(written.len(), 8192);
+    assert_eq!(written[0], 0xA5);
+    drop(emulator);
+
+    // ROMs loaded from memory fall back to the content checksum
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let crc = cartridge.crc32;
+    let mut emulator = Emulator::with_config(rnes_common::Config::default())
+        .with_save_system(rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new()));
+    emulator.load_rom(cartridge).unwrap();
+    assert_eq!(emulator.rom_name, Some(format!("{:08X}", crc)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_memory_editor_regions() {
+    let mut rom = create_test_rom();
+    rom[6] = 0x12;
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default())
+        .with_save_system(rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new()));
+    emulator.load_rom(cartridge).unwrap();
+
+    // CPU space writes land in work RAM mirrors and cartridge RAM
+    emulator.write_memory(MemoryRegion::Cpu, 0x0810, &[0x12, 0x34]).unwrap();
+    assert_eq!(emulator.read_memory(MemoryRegion::WorkRam, 0x10, 2).unwrap(), vec![0x12, 0x34]);
+    emulator.write_memory(MemoryRegion::Cpu, 0x6000, &[0x56]).unwrap();
+    assert_eq!(emulator.read_memory(MemoryRegion::PrgRam, 0, 1).unwrap(), vec![0x56]);
+
+    // Registers and ROM are not writable
+    assert!(emulator.write_memory(MemoryRegion::Cpu, 0x2000, &[0x80]).is_err());
+    assert!(emulator.write_memory(MemoryRegion::Cpu, 0x8000, &[0x00]).is_err());
+    assert!(emulator.write_memory(MemoryRegion::PrgRom, 0, &[0x00]).is_err());
+    assert_eq!(emulator.read_memory(MemoryRegion::PrgRom, 0, 2).unwrap(), vec![0xE6, 0x10]);
+    assert_eq!(emulator.read_memory(MemoryRegion::Cpu, 0x8000, 2).unwrap(), vec![0xE6, 0x10]);
+
+    // PPU space follows nametable mirroring and doesn't disturb $2007
+    emulator.write_memory(MemoryRegion::Ppu, 0x2005, &[0x77]).unwrap();
+    emulator.write_memory(MemoryRegion::Ppu, 0x3F01, &[0x21]).unwrap();
+    assert_eq!(emulator.read_memory(MemoryRegion::NametableRam, 0x005, 1).unwrap(), vec![0x77]);
+    assert_eq!(emulator.read_memory(MemoryRegion::Palette, 1, 1).unwrap(), vec![0x21]);
+    emulator.write_memory(MemoryRegion::Oam, 4, &[0x40, 0x01]).unwrap();
+    assert_eq!(emulator.bus.ppu().oam()[4..6], [0x40, 0x01]);
+
+    // Out of range accesses fail instead of truncating
+    let size = emulator.memory_size(MemoryRegion::Oam);
+    assert_eq!(size, 256);
+    assert!(emulator.read_memory(MemoryRegion::Oam, size - 1, 2).is_err());
+    assert!(emulator.read_memory(MemoryRegion::ChrRam, 0, 1).is_err());
+}
+
+#[test]
+fn test_heat_map_counts_accesses_and_banks() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.start_heat_map(2);
+
+    for _ in 0..3 {
+        emulator.run_frame().unwrap();
+    }
+
+    let heat_map = emulator.stop_heat_map().unwrap();
+    assert!(heat_map.is_complete());
+    assert_eq!(heat_map.frames(), 2);
+
+    // The loop executes INC $10 at $8000 and JMP $8000 at $8002
+    let inc = heat_map.counts(0x8000);
+    let jmp = heat_map.counts(0x8002);
+    assert!(inc.executes > 1000);
+    assert!(inc.executes.abs_diff(jmp.executes) <= 1);
+    assert_eq!(heat_map.counts(0x8001).executes, 0);
+    assert!(heat_map.counts(0x8001).reads >= inc.executes);
+    assert!(heat_map.counts(0x0010).reads >= inc.executes);
+    assert!(heat_map.counts(0x0010).writes >= inc.executes);
+    assert_eq!(heat_map.counts(0x0011).total(), 0);
+
+    // 16KB of PRG mirrored at $C000 is two 8KB banks; all code is in the first
+    let banks = heat_map.prg_bank_counts();
+    assert_eq!(banks.len(), 2);
+    assert_eq!(banks[0].executes, inc.executes + jmp.executes);
+    assert_eq!(banks[1].total(), 0);
+
+    // Rendering is off, so no CHR bank was in use
+    assert_eq!(heat_map.chr_bank_counts().len(), 8);
+    assert!(heat_map.chr_bank_counts().iter().all(|bank| bank.total() == 0));
+    assert!(emulator.heat_map().is_none());
+}
+
+#[test]
+fn test_mapper_bank_switch_breakpoint() {
+    // UxROM: LDA #$01; STA $8000; JMP * from the fixed bank at $C000
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, 0x20, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0xEA; 32 * 1024];
+    prg[0x4000..0x4008].copy_from_slice(&[0xA9, 0x01, 0x8D, 0x00, 0x80, 0x4C, 0x05, 0xC0]);
+    prg[0x7FFC] = 0x00;
+    prg[0x7FFD] = 0xC0;
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 8 * 1024]);
+
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&rom).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.add_mapper_break(rnes_common::MapperEventKind::MirroringChange);
+    emulator.add_mapper_break(rnes_common::MapperEventKind::PrgBankSwitch);
+    emulator.start();
+
+    for _ in 0..100 {
+        emulator.step().unwrap();
+        if !emulator.is_running() {
+            break;
+        }
+    }
+
+    // Execution halts right after the STA that switched banks
+    assert!(!emulator.is_running());
+    assert_eq!(emulator.cpu.pc, 0xC005);
+    let event = emulator.last_mapper_event().unwrap();
+    assert_eq!(event.kind, rnes_common::MapperEventKind::PrgBankSwitch);
+    let write = event.write.as_ref().unwrap();
+    assert_eq!((write.address, write.value, write.pc), (0x8000, 0x01, 0xC002));
+    assert_eq!(emulator.bus.mapper().prg_rom_offset(0x8000), Some(0x4000));
+}
+
+#[test]
+fn test_pause_on_start_and_interrupt_breaks() {
+    use rnes_common::InterruptKind;
+
+    let mut config = rnes_common::Config::default();
+    config.debug.pause_on_start = true;
+    config.debug.break_on_interrupts = vec![InterruptKind::Nmi];
+    let mut emulator = Emulator::with_config(config);
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_nmi_rom(1)).unwrap()).unwrap();
+    emulator.start();
+
+    // Halts at the reset vector before the first instruction
+    assert_eq!(emulator.step().unwrap(), 0);
+    assert!(!emulator.is_running());
+    assert_eq!((emulator.cpu.pc, emulator.state.cpu_cycles), (0x8000, 0));
+
+    // Then at the first NMI, reporting the handler
+    emulator.start();
+    emulator.run_frames(3).unwrap();
+    assert!(!emulator.is_running());
+    let event = emulator.last_interrupt().unwrap().clone();
+    assert_eq!((event.kind, event.target), (InterruptKind::Nmi, 0x8010));
+    assert!((0x8005..0x8008).contains(&event.pc), "{:?}", event);
+    assert_eq!(event.scanline, 241);
+
+    // BRK breaks on the instruction itself
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(create_crash_rom(&[0xEA, 0x00])).unwrap();
+    emulator.add_interrupt_break(InterruptKind::Brk);
+    emulator.start();
+    emulator.run_cycles(100).unwrap();
+    assert!(!emulator.is_running());
+    assert_eq!(emulator.cpu.pc, 0x8000);
+    let event = emulator.last_interrupt().unwrap();
+    assert_eq!((event.kind, event.pc, event.target), (InterruptKind::Brk, 0x8001, 0x8000));
+    assert!(emulator.remove_interrupt_break(InterruptKind::Brk));
+}
+
+/// NROM image running `main` at $8000 with an IRQ handler that counts in
+/// $20 and acknowledges the frame IRQ by reading $4015
+fn create_irq_rom(main: &[u8]) -> rnes_cartridge::Cartridge {
+    let mut rom = create_test_rom();
+    let prg = &mut rom[16..16 + 16 * 1024];
+    prg[..main.len()].copy_from_slice(main);
+    // INC $20; LDA $4015; RTI
+    prg[0x10..0x16].copy_from_slice(&[0xE6, 0x20, 0xAD, 0x15, 0x40, 0x40]);
+    prg[0x3FFE] = 0x10;
+    prg[0x3FFF] = 0x80;
+    rnes_cartridge::Cartridge::from_bytes(&rom).unwrap()
+}
+
+#[test]
+fn test_frame_irq_drives_irq_line() {
+    // SEI; JMP *: the frame IRQ asserts the line but is never taken
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(create_irq_rom(&[0x78, 0x4C, 0x01, 0x80])).unwrap();
+    emulator.start();
+    emulator.run_frames(2).unwrap();
+    assert_eq!(emulator.bus.irq().sources(), vec![rnes_common::IrqSource::ApuFrame]);
+    assert!(emulator.cpu.irq_pending);
+    let interrupts = &emulator.get_debugger().debug_info.interrupts;
+    assert!(interrupts.irq_line);
+    assert_eq!(interrupts.irq_sources, vec![rnes_common::IrqSource::ApuFrame]);
+    assert_eq!(emulator.peek(0x20), 0);
+
+    // Reading $4015 reports and releases it
+    assert_eq!(emulator.bus.read_byte(0x4015).unwrap() & 0x40, 0x40);
+    emulator.step().unwrap();
+    assert!(!emulator.bus.irq().line());
+    assert!(!emulator.cpu.irq_pending);
+
+    // CLI; JMP *: one IRQ per frame counter sequence, each acknowledged
+    emulator.load_rom(create_irq_rom(&[0x58, 0x4C, 0x01, 0x80])).unwrap();
+    emulator.start();
+    emulator.run_frames(4).unwrap();
+    assert!((3..=4).contains(&emulator.peek(0x20)), "{}", emulator.peek(0x20));
+}
+
+#[test]
+fn test_step_back_replays_to_previous_instruction() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.enable_reverse_step(16, 64);
+    emulator.start();
+
+    let mut history = Vec::new();
+    for _ in 0..100 {
+        history.push((emulator.cpu.pc, emulator.bus.ram[0x10], emulator.state.cpu_cycles));
+        emulator.step().unwrap();
+    }
+    assert_eq!(emulator.reverse_history().unwrap().instructions(), 100);
+
+    // Walk all the way back, one instruction at a time
+    for (index, &(pc, counter, cycles)) in history.iter().enumerate().rev() {
+        assert!(emulator.step_back().unwrap());
+        assert!(!emulator.is_running());
+        assert_eq!(emulator.reverse_history().unwrap().instructions(), index as u64);
+        assert_eq!((emulator.cpu.pc, emulator.bus.ram[0x10], emulator.state.cpu_cycles), (pc, counter, cycles));
+    }
+    assert!(!emulator.step_back().unwrap());
+
+    // Running forward again follows the same path
+    emulator.start();
+    for _ in 0..50 {
+        emulator.step().unwrap();
+    }
+    assert_eq!((emulator.cpu.pc, emulator.bus.ram[0x10]), (history[50].0, history[50].1));
+}
+
+#[test]
+fn test_assemble_at_patches_rom_and_ram() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+
+    // Replace INC $10 with INC $11 in the mapped ROM bank
+    assert_eq!(emulator.assemble_at(0x8000, "inc $11").unwrap(), 2);
+    assert_eq!(emulator.peek(0x8001), 0x11);
+    assert_eq!(emulator.read_memory(MemoryRegion::PrgRom, 0, 2).unwrap(), vec![0xE6, 0x11]);
+    let before = emulator.bus.ram[0x11];
+    emulator.start();
+    for _ in 0..10 {
+        emulator.step().unwrap();
+    }
+    assert_eq!(emulator.bus.ram[0x11], before.wrapping_add(5));
+
+    assert_eq!(emulator.assemble_at(0x0200, "lda #1\nrts").unwrap(), 3);
+    assert_eq!(emulator.bus.ram[0x200..0x203], [0xA9, 0x01, 0x60]);
+    assert!(emulator.assemble_at(0x2000, "nop").is_err());
+    assert!(emulator.assemble_at(0x8000, "bogus").is_err());
+}
+
+#[test]
+fn test_trace_logs_instructions_in_nestest_format() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.enable_trace(3);
+    emulator.start();
+    for _ in 0..4 {
+        emulator.step().unwrap();
+    }
+
+    let entries = emulator.trace().unwrap().entries();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries.iter().map(|entry| entry.pc).collect::<Vec<_>>(), vec![0x8002, 0x8000, 0x8002]);
+    assert_eq!(entries[1].bytes, vec![0xE6, 0x10]);
+    assert_eq!(entries[1].disassembly, "INC $10");
+    assert!(entries[1].cycles > entries[0].cycles);
+    for entry in entries {
+        assert_eq!(TraceEntry::parse(&entry.to_string()).as_ref(), Some(entry));
+    }
+
+    let line = "C72C  A2 00    *NOP $04 = 00                    A:5A X:00 Y:00 P:24 SP:FB PPU:  0,120 CYC:40";
+    let entry = TraceEntry::parse(line).unwrap();
+    assert_eq!((entry.pc, entry.bytes.clone(), entry.undocumented), (0xC72C, vec![0xA2, 0x00], true));
+    assert_eq!((entry.a, entry.p, entry.sp, entry.scanline, entry.dot, entry.cycles), (0x5A, 0x24, 0xFB, 0, 120, 40));
+    assert_eq!(entry.disassembly, "NOP $04 = 00");
+}
+
+#[test]
+fn test_stem_recording_writes_one_wav_per_channel() {
+    let dir = std::env::temp_dir().join(format!("rnes_stems_test_{}", std::process::id()));
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    for (addr, value) in [(0x4015, 0x01), (0x4000, 0xBF), (0x4002, 0xFD), (0x4003, 0x08)] {
+        emulator.apu_mut().write_register(addr, value).unwrap();
+    }
+
+    emulator.start_stem_recording(&dir).unwrap();
+    assert!(emulator.is_recording_stems());
+    emulator.start();
+    emulator.run_frames(3).unwrap();
+    let paths = emulator.stop_stem_recording().unwrap();
+    assert!(!emulator.is_recording_stems());
+
+    let names: Vec<String> = paths.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+    assert_eq!(names, ["pulse1.wav", "pulse2.wav", "triangle.wav", "noise.wav", "dmc.wav"]);
+    let wavs: Vec<Vec<u8>> = paths.iter().map(|path| std::fs::read(path).unwrap()).collect();
+    for wav in &wavs {
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        let data_len = u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize;
+        assert_eq!(data_len, wav.len() - 44);
+        // About three frames at 44.1 kHz
+        assert!((2000..2400).contains(&(data_len / 2)), "{} samples", data_len / 2);
+    }
+    let peak = |wav: &[u8]| wav[44..].chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]])).max().unwrap();
+    assert_eq!(peak(&wavs[0]), i16::MAX);
+    assert_eq!(peak(&wavs[1]), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Boot `rom` with `id` in PRG RAM, run it and save slot 1
+fn run_instance(emulator: &mut Emulator, rom: &[u8], id: u8) -> (u8, rnes_common::Cycles, Vec<rnes_common::Pixel>) {
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(rom).unwrap()).unwrap();
+    emulator.bus.write_byte(0x6000, id).unwrap();
+    emulator.start();
+    emulator.run_frames(30).unwrap();
+    emulator.save_state(1).unwrap();
+    emulator.stop();
+    (emulator.peek(0x10), emulator.get_state().cpu_cycles, emulator.get_frame_buffer().unwrap())
+}
+
+#[test]
+fn test_parallel_instances_are_independent() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Emulator>();
+    assert_send::<RlEnv>();
+
+    let dir = std::env::temp_dir().join(format!("rnes_parallel_test_{}", std::process::id()));
+    let mut rom = create_test_rom();
+    rom[6] = 0x12;
+
+    let mut reference = Emulator::with_config(rnes_common::Config::default())
+        .with_save_system(rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new()));
+    let expected = run_instance(&mut reference, &rom, 0);
+
+    // Emulators are built here and moved into their threads
+    let handles: Vec<_> = (1..=8u8).map(|id| {
+        let mut emulator = Emulator::with_config(rnes_common::Config::default())
+            .with_save_dir(dir.join(format!("instance{}", id)));
+        let rom = rom.clone();
+        std::thread::spawn(move || {
+            let result = run_instance(&mut emulator, &rom, id);
+            (emulator, result)
+        })
+    }).collect();
+
+    for (id, handle) in (1..=8u8).zip(handles) {
+        let (emulator, result) = handle.join().unwrap();
+        assert_eq!(result, expected, "instance {} diverged", id);
+        let rom_name = emulator.rom_name.clone().unwrap();
+        assert_eq!(emulator.save_system.load_battery_backup(&rom_name).unwrap()[0], id);
+        assert!(emulator.has_save_state(1));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_dirty_lines_track_changed_scanlines() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+
+    // Nothing was shown before the first frame
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.dirty_lines(), Some(0..240));
+
+    // Rendering is off, so the backdrop alone fills an unchanging screen
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.dirty_lines(), None);
+
+    // A new backdrop color changes every line; point VRAM away from the
+    // palette afterwards, as with rendering off the PPU shows the color there
+    for (addr, value) in [(0x2006, 0x3F), (0x2006, 0x00), (0x2007, 0x21), (0x2006, 0x00), (0x2006, 0x00)] {
+        emulator.bus.write_byte(addr, value).unwrap();
+    }
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.dirty_lines(), Some(0..240));
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.dirty_lines(), None);
+
+    emulator.overlay.text(0, 0, "PAUSED", rnes_common::Pixel::WHITE);
+    assert_eq!(emulator.dirty_lines(), Some(0..240));
+}
+
+#[test]
+fn test_audio_pull_and_chunked_callback() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+
+    // Pull exactly one buffer at a time
+    let available = emulator.audio_available();
+    assert!(available > 512);
+    let mut buffer = [1.0f32; 512];
+    assert_eq!(emulator.fill_audio(&mut buffer), 512);
+    assert_eq!(emulator.audio_available(), available - 512);
+    assert_eq!(emulator.drain_audio().count(), available - 512);
+    assert_eq!(emulator.fill_audio(&mut buffer), 0);
+
+    // The callback only ever sees whole chunks
+    let chunks = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received = chunks.clone();
+    emulator.set_audio_callback(256, move |samples| received.lock().unwrap().push(samples.len()));
+    emulator.run_frames(2).unwrap();
+    let chunks = chunks.lock().unwrap().clone();
+    assert!(chunks.len() >= 5);
+    assert!(chunks.iter().all(|&len| len == 256));
+    assert!(emulator.audio_available() < 256);
+
+    emulator.clear_audio_callback();
+    emulator.run_frame().unwrap();
+    assert!(emulator.audio_available() > 256);
+}
+
+/// Build an NROM image with `program` at $8000 and both vectors pointing there
+fn create_crash_rom(program: &[u8]) -> rnes_cartridge::Cartridge {
+    let mut rom = create_test_rom();
+    rom[16..16 + program.len()].copy_from_slice(program);
+    rom[16 + 0x3FFE] = 0x00;
+    rom[16 + 0x3FFF] = 0x80;
+    rnes_cartridge::Cartridge::from_bytes(&rom).unwrap()
+}
+
+#[test]
+fn test_watchdog_detects_crashes() {
+    // INC $10; INC $10; KIL
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(create_crash_rom(&[0xE6, 0x10, 0xE6, 0x10, 0x02])).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+    assert!(!emulator.is_running());
+    let crash = emulator.crash().unwrap().clone();
+    assert_eq!(crash.reason, CrashReason::Jam { opcode: 0x02 });
+    assert_eq!(crash.pc, 0x8004);
+    assert_eq!(crash.recent, vec![0x8000, 0x8002, 0x8004]);
+    assert_eq!(crash.to_string(), "CPU jammed by opcode $02 at $8004 in frame 0");
+
+    // The jammed CPU stays put while the PPU keeps going
+    emulator.start();
+    emulator.run_frames(2).unwrap();
+    assert_eq!(emulator.cpu.pc, 0x8004);
+    assert_eq!(emulator.bus.ram[0x10], 2);
+    assert!(emulator.get_state().frame_count >= 2);
+
+    // Reset clears the crash, and the game runs into it again
+    emulator.soft_reset().unwrap();
+    assert!(emulator.crash().is_none());
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.bus.ram[0x10], 4);
+    assert!(emulator.crash().is_some());
+
+    // BRK through a vector that points back at it
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(create_crash_rom(&[0x00])).unwrap();
+    emulator.start();
+    assert_eq!(emulator.step().unwrap(), 7);
+    assert_eq!(emulator.crash().unwrap().reason, CrashReason::BrkLoop);
+    assert_eq!(emulator.step().unwrap(), 0);
+
+    // Opcodes the core does not run stop the game instead of failing `step()`
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(create_crash_rom(&[0xEA, 0x03])).unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+    let crash = emulator.crash().unwrap();
+    assert_eq!((crash.reason, crash.pc), (CrashReason::InvalidOpcode { opcode: 0x03 }, 0x8001));
+    emulator.power_cycle().unwrap();
+    assert!(emulator.crash().is_none());
+}
+
+#[test]
+fn test_event_timeline_records_nmi_dma_and_status_reads() {
+    // LDA #$42; STA $0200; LDA #$80; STA $2000; loop: BIT $2002; JMP loop
+    // NMI at $8020: LDA #$02; STA $4014; RTI
+    let mut rom = create_test_rom();
+    let program = [0xA9, 0x42, 0x8D, 0x00, 0x02, 0xA9, 0x80, 0x8D, 0x00, 0x20, 0x2C, 0x02, 0x20, 0x4C, 0x0A, 0x80];
+    rom[16..16 + program.len()].copy_from_slice(&program);
+    rom[16 + 0x20..16 + 0x26].copy_from_slice(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0x40]);
+    rom[16 + 0x3FFA] = 0x20;
+    rom[16 + 0x3FFB] = 0x80;
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&rom).unwrap()).unwrap();
+    emulator.start();
+    emulator.start_timeline();
+    emulator.run_frames(3).unwrap();
+
+    // OAM DMA completes and the PPU keeps running
+    assert_eq!(emulator.ppu().oam()[0], 0x42);
+    assert!(!emulator.ppu().oam_dma_active());
+    assert!(emulator.ppu().frame_count() >= 3);
+
+    let timeline = emulator.timeline().unwrap();
+    assert!(timeline.last_frame_number().is_some());
+    let events = timeline.last_frame();
+    let find = |kind| events.iter().find(|event| event.kind == kind).copied().unwrap();
+    let nmi = find(TimelineEventKind::Nmi);
+    assert_eq!(nmi.scanline, 241);
+    let dma_start = find(TimelineEventKind::OamDmaStart);
+    let dma_end = find(TimelineEventKind::OamDmaEnd);
+    assert_eq!(dma_start.scanline, 241);
+    // 513 or 514 CPU cycles are 1539 or 1542 dots, four and a half scanlines
+    let dots = |event: TimelineEvent| event.scanline * 341 + event.dot as i32;
+    assert!((1539..1560).contains(&(dots(dma_end) - dots(dma_start))));
+    assert!(events.iter().filter(|event| event.kind == TimelineEventKind::StatusRead).count() > 1000);
+    assert!(!events.iter().any(|event| event.kind == TimelineEventKind::MapperIrq));
+    drop(timeline);
+
+    let timeline = emulator.stop_timeline().unwrap();
+    assert!(!timeline.last_frame().is_empty());
+    assert!(emulator.timeline().is_none());
+}
+
+#[test]
+fn test_ppu_write_log_stamps_writes() {
+    // LDA #$12; STA $2005; LDA #$34; STA $2005; JMP $8000
+    let program = [0xA9, 0x12, 0x8D, 0x05, 0x20, 0xA9, 0x34, 0x8D, 0x05, 0x20, 0x4C, 0x00, 0x80];
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(create_crash_rom(&program)).unwrap();
+    emulator.start();
+    emulator.start_ppu_write_log();
+    emulator.run_frames(2).unwrap();
+
+    let log = emulator.ppu_write_log().unwrap();
+    let writes = log.last_frame();
+    assert!(writes.len() > 1000);
+    assert!(writes.iter().all(|write| write.address == 0x2005));
+    let first = writes.iter().position(|write| write.pc == 0x8002).unwrap();
+    let (x, y) = (writes[first], writes[first + 1]);
+    assert_eq!((x.value, y.value, y.pc), (0x12, 0x34, 0x8007));
+
+    // Every pass through the loop spaces the writes the same way
+    let dots = |write: PpuWrite| write.scanline * 341 + write.dot as i32;
+    assert_eq!(dots(y) - dots(x), dots(writes[first + 3]) - dots(writes[first + 2]));
+    assert!(writes.windows(2).all(|pair| dots(pair[0]) < dots(pair[1])));
+
+    assert!(emulator.stop_ppu_write_log().is_some());
+    assert!(emulator.ppu_write_log().is_none());
+}
+
+#[test]
+fn test_watch_expressions_update_each_frame() {
+    let cartridge = rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap();
+    let mut emulator = Emulator::with_config(rnes_common::Config::default());
+    emulator.load_rom(cartridge).unwrap();
+    emulator.start();
+
+    emulator.add_watch("counter = [0x10]").unwrap();
+    emulator.add_watch("both = [$11] * 256 + [$10]").unwrap();
+    assert_eq!(emulator.watch_values()["counter"], 0);
+
+    emulator.bus.ram[0x11] = 0x12;
+    emulator.run_frame().unwrap();
+    let counter = emulator.bus.ram[0x10] as i64;
+    assert_eq!(emulator.watch_values()["counter"], counter);
+    assert_eq!(emulator.watch_values()["both"], 0x1200 + counter);
+
+    // Redefining a watch replaces it; bad definitions are rejected
+    emulator.add_watch("counter = [0x10] & 1").unwrap();
+    assert_eq!(emulator.watch_values()["counter"], counter & 1);
+    assert_eq!(emulator.get_debugger().watches.len(), 2);
+    assert!(emulator.add_watch("[0x10]").is_err());
+    assert!(emulator.add_watch(" = [0x10]").is_err());
+    assert!(matches!(emulator.add_watch("x = [0x10"), Err(rnes_common::RnesError::Expression { column: 10, .. })));
+
+    assert!(emulator.remove_watch("both"));
+    assert!(!emulator.remove_watch("both"));
+    assert_eq!(emulator.watch_values().keys().collect::<Vec<_>>(), vec!["counter"]);
+}
+
+#[test]
+fn test_ram_map_loaded_beside_rom() {
+    let dir = std::env::temp_dir().join(format!("rnes_rammap_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let rom_path = dir.join("Counter.nes");
+    std::fs::write(&rom_path, create_test_rom()).unwrap();
+    std::fs::write(dir.join("Counter.rammap"), "# Counter test ROM\n$0010  counter  Incremented by the main loop\n").unwrap();
+
+    let mut emulator = Emulator::with_config(rnes_common::Config::default())
+        .with_save_system(rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new()));
+    emulator.load_rom(rnes_cartridge::Cartridge::from_file(&rom_path).unwrap()).unwrap();
+    let counter = emulator.ram_annotations().get(0x0010).unwrap();
+    assert_eq!(counter.description, "Incremented by the main loop");
+
+    // Names work in watch expressions and label watchpoints
+    emulator.add_watch("twice = [counter] * 2").unwrap();
+    emulator.start();
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.watch_values()["twice"], emulator.bus.ram[0x10] as i64 * 2);
+    emulator.get_debugger_mut().add_watchpoint(0x0010);
+    assert_eq!(emulator.get_debugger().get_watchpoint_labels(), vec!["$0010 (counter)"]);
+
+    // The map belongs to the game it was loaded for
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&create_test_rom()).unwrap()).unwrap();
+    assert!(emulator.ram_annotations().is_empty());
+    assert!(emulator.add_watch("x = [counter]").is_err());
+    emulator.load_ram_annotations(dir.join("Counter.rammap")).unwrap();
+    assert!(emulator.add_watch("x = [counter]").is_ok());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,134 @@
+use std::cell::{Ref, RefCell};
+use rnes_common::{Scanline, Dot};
+use crate::Bus;
+
+/// Kind of event on the debug timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimelineEventKind {
+    /// The PPU asserted NMI at the start of vblank
+    Nmi,
+    /// The mapper asserted its IRQ
+    MapperIrq,
+    /// The DMC asserted its IRQ at the end of a sample
+    DmcIrq,
+    /// The APU frame counter asserted its IRQ
+    FrameIrq,
+    /// The CPU took a pending IRQ, acknowledging it
+    IrqAck,
+    /// A write to $4014 started OAM DMA
+    OamDmaStart,
+    /// OAM DMA finished and the CPU resumed
+    OamDmaEnd,
+    /// The DMC fetched a sample byte
+    DmcFetch,
+    /// The CPU read PPUSTATUS ($2002)
+    StatusRead,
+}
+
+/// Event on the debug timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineEvent {
+    pub kind: TimelineEventKind,
+    /// PPU position when the event was recorded
+    pub scanline: Scanline,
+    pub dot: Dot,
+}
+
+/// Per-frame timeline of interrupts, DMA and PPUSTATUS reads
+///
+/// Events are stamped when the emulator sees them: CPU reads, IRQ
+/// acknowledges and OAM DMA starts at the start of the instruction, the rest
+/// once the PPU has caught up with it. Stamps can therefore be off by up to
+/// one instruction, which is close enough to line events up with the picture.
+#[derive(Debug, Clone, Default)]
+pub struct EventTimeline {
+    frame: Option<u64>,
+    events: Vec<TimelineEvent>,
+    last_frame: Option<u64>,
+    last_events: Vec<TimelineEvent>,
+}
+
+impl EventTimeline {
+    /// Create an empty timeline
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events of the last completed frame, in order
+    pub fn last_frame(&self) -> &[TimelineEvent] {
+        &self.last_events
+    }
+
+    /// PPU frame number of the last completed frame, if one completed yet
+    pub fn last_frame_number(&self) -> Option<u64> {
+        self.last_frame
+    }
+
+    /// Events so far in the frame in progress
+    pub fn current_frame(&self) -> &[TimelineEvent] {
+        &self.events
+    }
+
+    /// Forget all recorded events
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Move on to `frame`, keeping the events of the frame that ended
+    fn begin_frame(&mut self, frame: u64) {
+        if self.frame == Some(frame) {
+            return;
+        }
+        if let Some(previous) = self.frame {
+            self.last_events = std::mem::take(&mut self.events);
+            self.last_frame = Some(previous);
+        }
+        self.frame = Some(frame);
+    }
+
+    fn record(&mut self, frame: u64, event: TimelineEvent) {
+        self.begin_frame(frame);
+        self.events.push(event);
+    }
+}
+
+impl Bus {
+    /// Start recording the event timeline, replacing any previous one
+    pub fn start_timeline(&mut self) {
+        self.timeline = Some(RefCell::new(EventTimeline::new()));
+    }
+
+    /// Stop recording and return the timeline
+    pub fn stop_timeline(&mut self) -> Option<EventTimeline> {
+        self.timeline.take().map(RefCell::into_inner)
+    }
+
+    /// Get the timeline being recorded, if any
+    pub fn timeline(&self) -> Option<Ref<'_, EventTimeline>> {
+        self.timeline.as_ref().map(RefCell::borrow)
+    }
+
+    /// Check if the event timeline is being recorded
+    pub(crate) fn timeline_enabled(&self) -> bool {
+        self.timeline.is_some()
+    }
+
+    /// Record an event at the current PPU position, if the timeline is being recorded
+    pub(crate) fn record_timeline_event(&self, kind: TimelineEventKind) {
+        if let (Some(timeline), Some(ppu)) = (self.timeline.as_ref(), self.ppu.as_ref()) {
+            let event = TimelineEvent {
+                kind,
+                scanline: ppu.scanline(),
+                dot: ppu.dot(),
+            };
+            timeline.borrow_mut().record(ppu.frame_count(), event);
+        }
+    }
+
+    /// Close the timeline's frame once the PPU has finished it
+    pub(crate) fn record_timeline_progress(&mut self) {
+        if let (Some(timeline), Some(ppu)) = (self.timeline.as_mut(), self.ppu.as_ref()) {
+            timeline.get_mut().begin_frame(ppu.frame_count());
+        }
+    }
+}
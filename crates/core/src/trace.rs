@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+use rnes_common::{Byte, RnesResult, Word};
+use crate::{Emulator, MemoryPeek};
+
+/// CPU state before one instruction, in the layout of nestest.log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: Word,
+    pub bytes: Vec<Byte>,
+    pub disassembly: String,
+    pub undocumented: bool,
+    pub a: Byte,
+    pub x: Byte,
+    pub y: Byte,
+    pub p: Byte,
+    pub sp: Byte,
+    pub scanline: i32,
+    pub dot: u32,
+    pub cycles: u64,
+}
+
+impl TraceEntry {
+    /// Parse a line in nestest.log format
+    ///
+    /// `C000  4C F5 C5  JMP $C5F5    A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+    pub fn parse(line: &str) -> Option<Self> {
+        let registers_at = line.find("A:")?;
+        let (left, registers) = line.split_at(registers_at);
+        let pc = Word::from_str_radix(left.get(0..4)?, 16).ok()?;
+        let bytes = left.get(6..14)?
+            .split_whitespace()
+            .map(|byte| Byte::from_str_radix(byte, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let undocumented = left.get(15..16) == Some("*");
+        let disassembly = left.get(16..).unwrap_or("").trim().to_string();
+
+        let field = |name: &str| -> Option<&str> {
+            let start = registers.find(name)? + name.len();
+            let rest = &registers[start..];
+            Some(rest.split(|c: char| c.is_whitespace() || c == ',').find(|s| !s.is_empty()).unwrap_or(""))
+        };
+        let hex = |name: &str| Byte::from_str_radix(field(name)?, 16).ok();
+
+        // PPU:  0, 21 puts a space after the comma; older logs have no PPU column
+        let (scanline, dot) = registers.split("PPU:").nth(1)
+            .and_then(|ppu| {
+                let (scanline, dot) = ppu.split_once(',')?;
+                Some((scanline.trim().parse().ok()?, dot.split_whitespace().next()?.parse().ok()?))
+            })
+            .unwrap_or((0, 0));
+
+        Some(Self {
+            pc,
+            bytes,
+            disassembly,
+            undocumented,
+            a: hex("A:")?,
+            x: hex("X:")?,
+            y: hex("Y:")?,
+            p: hex("P:")?,
+            sp: hex("SP:")?,
+            scanline,
+            dot,
+            cycles: field("CYC:")?.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        write!(
+            f,
+            "{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            self.pc,
+            bytes.join(" "),
+            if self.undocumented { '*' } else { ' ' },
+            self.disassembly,
+            self.a,
+            self.x,
+            self.y,
+            self.p,
+            self.sp,
+            self.scanline,
+            self.dot,
+            self.cycles,
+        )
+    }
+}
+
+/// Records the CPU state before each instruction
+///
+/// The newest `capacity` entries are kept.
+#[derive(Debug)]
+pub struct TraceLogger {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl TraceLogger {
+    /// Create an empty trace
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Get the recorded entries, oldest first
+    pub fn entries(&self) -> &VecDeque<TraceEntry> {
+        &self.entries
+    }
+
+    /// Remove and return the recorded entries
+    pub fn take(&mut self) -> Vec<TraceEntry> {
+        self.entries.drain(..).collect()
+    }
+
+    /// Write the entries as nestest.log lines
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> RnesResult<()> {
+        for entry in &self.entries {
+            writeln!(writer, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+impl Emulator {
+    /// Start logging every executed instruction, keeping the newest `capacity`
+    pub fn enable_trace(&mut self, capacity: usize) {
+        self.trace = Some(TraceLogger::new(capacity));
+    }
+
+    /// Stop logging and drop the trace
+    pub fn disable_trace(&mut self) {
+        self.trace = None;
+    }
+
+    /// Get the trace, if enabled
+    pub fn trace(&self) -> Option<&TraceLogger> {
+        self.trace.as_ref()
+    }
+
+    /// Get the trace mutably, e.g. to take its entries
+    pub fn trace_mut(&mut self) -> Option<&mut TraceLogger> {
+        self.trace.as_mut()
+    }
+
+    /// Record the instruction about to execute
+    pub(crate) fn record_trace(&mut self) {
+        let Some(ref mut trace) = self.trace else {
+            return;
+        };
+        // Cycles spent stalled (reset sequence, DMA) aren't instructions
+        if self.cpu.stall_cycles > 0 {
+            return;
+        }
+
+        let pc = self.cpu.pc;
+        let opcode = self.bus.peek(pc);
+        let mut bytes = vec![0; rnes_cpu6502::instruction_length(opcode)];
+        self.bus.peek_range(pc, &mut bytes);
+        let (scanline, dot) = self.bus.ppu.as_ref()
+            .map(|ppu| (ppu.scanline(), ppu.dot()))
+            .unwrap_or((0, 0));
+
+        trace.push(TraceEntry {
+            pc,
+            disassembly: rnes_cpu6502::disassemble(pc, &bytes),
+            undocumented: rnes_cpu6502::is_undocumented(opcode),
+            bytes,
+            a: self.cpu.a,
+            x: self.cpu.x,
+            y: self.cpu.y,
+            p: self.cpu.status.bits(),
+            sp: self.cpu.sp,
+            scanline,
+            dot,
+            cycles: self.cpu.cycles as u64,
+        });
+    }
+}
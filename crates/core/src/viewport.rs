@@ -0,0 +1,70 @@
+use rnes_common::{ScaleMode, VideoConfig, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Width of an NES pixel relative to its height on a TV
+pub const PIXEL_ASPECT_RATIO: f64 = 8.0 / 7.0;
+
+/// Where the picture lands in the window, in window pixels
+///
+/// Space left over around the picture is split evenly on both sides, so the
+/// picture stays centred behind black bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Place the 256x240 picture in a `window_width` x `window_height` window
+    ///
+    /// Integer mode falls back to 1x when the window is smaller than the
+    /// picture, cropping it on the sides that don't fit.
+    pub fn new(mode: ScaleMode, window_width: u32, window_height: u32) -> Self {
+        let (frame_width, frame_height) = (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+        let (width, height) = match mode {
+            ScaleMode::Integer => {
+                let scale = (window_width / frame_width).min(window_height / frame_height).max(1);
+                (frame_width * scale, frame_height * scale)
+            }
+            ScaleMode::Fit => {
+                let aspect_width = frame_width as f64 * PIXEL_ASPECT_RATIO;
+                let scale = (window_width as f64 / aspect_width).min(window_height as f64 / frame_height as f64);
+                let width = (aspect_width * scale).round() as u32;
+                let height = (frame_height as f64 * scale).round() as u32;
+                (width.clamp(1, window_width.max(1)), height.clamp(1, window_height.max(1)))
+            }
+            ScaleMode::Stretch => (window_width.max(1), window_height.max(1)),
+        };
+        Self {
+            x: window_width.saturating_sub(width) / 2,
+            y: window_height.saturating_sub(height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Place the picture as `config` asks
+    pub fn from_config(config: &VideoConfig, window_width: u32, window_height: u32) -> Self {
+        Self::new(config.scale_mode, window_width, window_height)
+    }
+
+    /// Whole-number factors for the nearest-neighbour pass of sharp-bilinear
+    /// scaling, horizontal and vertical
+    ///
+    /// The frame is first blown up by these with hard pixel edges; bilinear
+    /// filtering then only has to cover the fractional remainder, which
+    /// softens each edge by at most one window pixel.
+    pub fn prescale(&self) -> (u32, u32) {
+        (
+            (self.width / SCREEN_WIDTH as u32).max(1),
+            (self.height / SCREEN_HEIGHT as u32).max(1),
+        )
+    }
+
+    /// Check if every NES pixel covers a whole number of window pixels, so
+    /// nearest-neighbour sampling is exact and no filtering is needed
+    pub fn is_integer_scale(&self) -> bool {
+        self.width.is_multiple_of(SCREEN_WIDTH as u32) && self.height.is_multiple_of(SCREEN_HEIGHT as u32)
+    }
+}
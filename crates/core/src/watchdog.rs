@@ -0,0 +1,99 @@
+use std::fmt;
+use rnes_common::{Byte, Word};
+
+/// Instruction addresses kept for crash reports
+pub const CRASH_HISTORY_LEN: usize = 16;
+
+/// Why a game is considered crashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashReason {
+    /// The CPU executed a JAM (KIL) opcode and halted until reset
+    Jam { opcode: Byte },
+    /// An opcode the CPU core does not implement
+    InvalidOpcode { opcode: Byte },
+    /// BRK jumped back to itself through the IRQ/BRK vector
+    BrkLoop,
+}
+
+/// A crashed game, for a "game crashed — reset?" prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameCrash {
+    pub reason: CrashReason,
+    /// Address of the offending instruction
+    pub pc: Word,
+    /// Addresses of the instructions leading up to it, oldest first
+    pub recent: Vec<Word>,
+    /// Frame the crash happened in
+    pub frame: u64,
+}
+
+impl fmt::Display for GameCrash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            CrashReason::Jam { opcode } => write!(f, "CPU jammed by opcode ${:02X} at ${:04X}", opcode, self.pc)?,
+            CrashReason::InvalidOpcode { opcode } => write!(f, "invalid opcode ${:02X} at ${:04X}", opcode, self.pc)?,
+            CrashReason::BrkLoop => write!(f, "BRK loop at ${:04X}", self.pc)?,
+        }
+        write!(f, " in frame {}", self.frame)
+    }
+}
+
+/// Watches execution for crashed and hung games
+///
+/// The emulator records the address of every instruction it runs and trips
+/// the watchdog when the CPU jams, hits an opcode it cannot run, or BRK
+/// lands on itself. Tight `JMP *` loops are not crashes: games wait for NMI
+/// that way, and test ROMs park there when they finish.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    recent: [Word; CRASH_HISTORY_LEN],
+    next: usize,
+    len: usize,
+    crash: Option<GameCrash>,
+}
+
+impl Watchdog {
+    /// Create watchdog with an empty history
+    pub fn new() -> Self {
+        Self {
+            recent: [0; CRASH_HISTORY_LEN],
+            next: 0,
+            len: 0,
+            crash: None,
+        }
+    }
+
+    /// Record the address of the instruction about to run
+    pub(crate) fn record(&mut self, pc: Word) {
+        self.recent[self.next] = pc;
+        self.next = (self.next + 1) % CRASH_HISTORY_LEN;
+        self.len = (self.len + 1).min(CRASH_HISTORY_LEN);
+    }
+
+    /// Recorded addresses, oldest first
+    pub fn recent(&self) -> Vec<Word> {
+        let start = (self.next + CRASH_HISTORY_LEN - self.len) % CRASH_HISTORY_LEN;
+        (0..self.len).map(|i| self.recent[(start + i) % CRASH_HISTORY_LEN]).collect()
+    }
+
+    /// Mark the game as crashed
+    pub(crate) fn trip(&mut self, reason: CrashReason, pc: Word, frame: u64) -> &GameCrash {
+        self.crash.insert(GameCrash { reason, pc, recent: self.recent(), frame })
+    }
+
+    /// The crash that stopped the game, if any
+    pub fn crash(&self) -> Option<&GameCrash> {
+        self.crash.as_ref()
+    }
+
+    /// Forget the crash and the history, e.g. after a reset
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
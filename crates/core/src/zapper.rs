@@ -0,0 +1,146 @@
+use rnes_common::{Byte, InputDevice, Scanline, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{Bus, Emulator};
+
+/// Scanlines the photodiode keeps sensing a lit pixel after the beam draws it
+pub const ZAPPER_LIGHT_SCANLINES: Scanline = 20;
+
+/// Smallest `r + g + b` of a pixel bright enough for the photodiode
+pub const ZAPPER_LIGHT_THRESHOLD: u32 = 3 * 0xA0;
+
+/// Frames `Zapper::fire` holds the trigger, long enough for games to see it
+pub const ZAPPER_PULL_FRAMES: u8 = 3;
+
+/// Zapper light gun
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Zapper {
+    /// Screen pixel the gun points at, or `None` when aimed off screen
+    pub aim: Option<(u16, u16)>,
+    /// Trigger held down
+    pub trigger: bool,
+    /// Frames left of a pull started by `fire`
+    pull_frames: u8,
+}
+
+impl Zapper {
+    /// Create a Zapper aimed off screen with the trigger released
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pull the trigger for `ZAPPER_PULL_FRAMES` frames, e.g. on a click
+    ///
+    /// A click can be shorter than a frame, so holding `trigger` only while
+    /// the mouse button is down could be missed by the game.
+    pub fn fire(&mut self) {
+        self.pull_frames = ZAPPER_PULL_FRAMES;
+    }
+
+    /// Check if the trigger reads as pulled
+    pub fn is_pulled(&self) -> bool {
+        self.trigger || self.pull_frames > 0
+    }
+
+    /// Count down a pull started by `fire` as a frame ends
+    pub(crate) fn end_frame(&mut self) {
+        self.pull_frames = self.pull_frames.saturating_sub(1);
+    }
+}
+
+/// Map a point on a canvas showing the whole 256x240 picture to a screen pixel
+///
+/// `x` and `y` are relative to the canvas' top-left corner and `width` and
+/// `height` are its size, all in the same units (CSS pixels for mouse and
+/// touch events). Points outside the canvas give `None`, aiming off screen.
+pub fn canvas_to_screen(x: f64, y: f64, width: f64, height: f64) -> Option<(u16, u16)> {
+    if width <= 0.0 || height <= 0.0 || x < 0.0 || y < 0.0 || x >= width || y >= height {
+        return None;
+    }
+    let screen_x = (x * SCREEN_WIDTH as f64 / width) as u16;
+    let screen_y = (y * SCREEN_HEIGHT as f64 / height) as u16;
+    Some((screen_x, screen_y))
+}
+
+impl Bus {
+    /// Value read from controller port `port` (0 = $4016, 1 = $4017)
+    pub(crate) fn read_port(&self, port: usize) -> Byte {
+        self.input_polled.set(true);
+        match self.port_devices[port] {
+            InputDevice::Controller => self.read_controller(port),
+            InputDevice::Zapper => {
+                // Bit 3 reads 0 while light is sensed, bit 4 is the trigger
+                let dark = if self.zapper_senses_light() { 0x00 } else { 0x08 };
+                dark | if self.zapper.is_pulled() { 0x10 } else { 0x00 }
+            }
+        }
+    }
+
+    /// Controller connected to port `port`, after any port swap
+    pub(crate) fn port_controller(&self, port: usize) -> &rnes_common::ControllerState {
+        if port ^ self.swap_ports as usize == 0 {
+            &self.controller1
+        } else {
+            &self.controller2
+        }
+    }
+
+    /// Check if the pixel under the Zapper is bright and was drawn recently
+    fn zapper_senses_light(&self) -> bool {
+        let (Some((x, y)), Some(ppu)) = (self.zapper.aim, self.ppu.as_ref()) else {
+            return false;
+        };
+        let (x, y) = (x as usize, y as usize);
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            return false;
+        }
+
+        // The beam must have drawn the pixel, but not too long ago
+        let since = ppu.scanline() - y as Scanline;
+        let drawn = since > 0 || (since == 0 && ppu.dot() as usize > x + 1);
+        if !drawn || since >= ZAPPER_LIGHT_SCANLINES {
+            return false;
+        }
+        let pixel = ppu.frame_buffer()[y * SCREEN_WIDTH + x];
+        pixel.r as u32 + pixel.g as u32 + pixel.b as u32 >= ZAPPER_LIGHT_THRESHOLD
+    }
+}
+
+impl Emulator {
+    /// Plug `device` into controller port `port` (0 or 1)
+    pub fn set_port_device(&mut self, port: usize, device: InputDevice) {
+        if port < 2 {
+            self.config.input.port_devices[port] = device;
+            self.bus.port_devices[port] = device;
+        }
+    }
+
+    /// Device plugged into controller port `port` (0 or 1)
+    pub fn port_device(&self, port: usize) -> InputDevice {
+        self.bus.port_devices.get(port).copied().unwrap_or_default()
+    }
+
+    /// Connect controller 1 to port 2 and controller 2 to port 1, or undo it
+    pub fn set_swap_ports(&mut self, swap: bool) {
+        self.config.input.swap_ports = swap;
+        self.bus.swap_ports = swap;
+    }
+
+    /// Aim the Zapper at a screen pixel, or off screen with `None`
+    pub fn set_zapper_aim(&mut self, aim: Option<(u16, u16)>) {
+        self.bus.zapper.aim = aim;
+    }
+
+    /// Pull or release the Zapper trigger
+    pub fn set_zapper_trigger(&mut self, pulled: bool) {
+        self.bus.zapper.trigger = pulled;
+    }
+
+    /// Pull the Zapper trigger briefly, as for a mouse click or tap
+    pub fn fire_zapper(&mut self) {
+        self.bus.zapper.fire();
+    }
+
+    /// Get the Zapper state
+    pub fn zapper(&self) -> &Zapper {
+        &self.bus.zapper
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::string::ToString;
 use crate::cpu::Cpu;
 use rnes_common::{Byte, Word, RnesResult, MemoryAccess};
 
@@ -34,7 +35,7 @@ pub enum AddressingMode {
 
 impl AddressingMode {
     /// Get operand address
-    pub fn get_address<M: MemoryAccess>(&self, cpu: &Cpu, memory: &M) -> RnesResult<Word> {
+    pub fn get_address<M: MemoryAccess>(&self, cpu: &Cpu, memory: &mut M) -> RnesResult<Word> {
         match self {
             AddressingMode::Implied | AddressingMode::Accumulator => {
                 Err(rnes_common::RnesError::Cpu("Implied addressing mode has no address".to_string()))
@@ -90,7 +91,7 @@ impl AddressingMode {
     }
     
     /// Get operand value
-    pub fn get_operand<M: MemoryAccess>(&self, cpu: &Cpu, memory: &M) -> RnesResult<Byte> {
+    pub fn get_operand<M: MemoryAccess>(&self, cpu: &Cpu, memory: &mut M) -> RnesResult<Byte> {
         match self {
             AddressingMode::Implied | AddressingMode::Accumulator => {
                 Err(rnes_common::RnesError::Cpu("Implied addressing mode has no operand".to_string()))
@@ -122,7 +123,7 @@ impl AddressingMode {
     }
     
     /// Check if extra cycle is needed (page boundary crossing)
-    pub fn needs_extra_cycle<M: MemoryAccess>(&self, cpu: &Cpu, memory: &M) -> bool {
+    pub fn needs_extra_cycle<M: MemoryAccess>(&self, cpu: &Cpu, memory: &mut M) -> bool {
         match self {
             AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => {
                 let base_addr = memory.read_word(cpu.pc).unwrap_or(0);
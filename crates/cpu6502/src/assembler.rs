@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use rnes_common::{Byte, Word, RnesError, RnesResult};
+use crate::{AddressingMode, INSTRUCTIONS, get_instruction_length};
+
+/// Contiguous block of assembled code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub origin: Word,
+    pub bytes: Vec<Byte>,
+}
+
+/// Output of the assembler
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    /// Code blocks in source order; each `.org` starts a new one
+    pub segments: Vec<Segment>,
+    /// Label and constant values
+    pub labels: HashMap<String, Word>,
+}
+
+impl Assembly {
+    /// All bytes in source order, for code without `.org` gaps
+    pub fn bytes(&self) -> Vec<Byte> {
+        self.segments.iter().flat_map(|segment| segment.bytes.iter().copied()).collect()
+    }
+
+    /// Get the value of a label or constant
+    pub fn label(&self, name: &str) -> Option<Word> {
+        self.labels.get(name).copied()
+    }
+
+    /// Copy every segment into an image whose first byte is at CPU address `base`
+    ///
+    /// For example, a 32KB PRG ROM image has `base` $8000.
+    pub fn write_to(&self, image: &mut [Byte], base: Word) -> RnesResult<()> {
+        for segment in &self.segments {
+            let start = (segment.origin as usize).wrapping_sub(base as usize);
+            let end = start.checked_add(segment.bytes.len()).filter(|&end| segment.origin >= base && end <= image.len());
+            let Some(end) = end else {
+                return Err(RnesError::MemoryRange {
+                    region: format!("image at ${:04X}", base),
+                    offset: start,
+                    len: segment.bytes.len(),
+                });
+            };
+            image[start..end].copy_from_slice(&segment.bytes);
+        }
+        Ok(())
+    }
+}
+
+/// Assemble 6502 source into machine code
+///
+/// Code starts at `origin` until the first `.org`. The syntax is the common
+/// one shared by ca65 and asm6:
+///
+/// ```text
+/// PPUCTRL = $2000          ; constants
+/// reset:  sei              ; labels end with ':'
+///         lda #<table      ; < and > take the low and high byte
+///         sta PPUCTRL
+/// loop:   jmp loop
+/// table:  .byte $01, 2, %11
+///         .word reset, loop+3
+///         .org $FFFC
+///         .word reset
+/// ```
+///
+/// Numbers are decimal, `$hex` or `%binary`; `*` is the current address.
+/// Operands that fit in a byte use zero-page addressing when the mnemonic
+/// supports it and their value is known at that point in the source.
+pub fn assemble(source: &str, origin: Word) -> RnesResult<Assembly> {
+    let statements = source.lines()
+        .enumerate()
+        .map(|(index, line)| parse_line(line).map_err(|message| RnesError::Assembly { line: index + 1, message }))
+        .collect::<RnesResult<Vec<_>>>()?;
+
+    let mut assembler = Assembler {
+        labels: HashMap::new(),
+        pc: origin as i64,
+    };
+
+    // Pass 1: define labels and fix the size of every instruction
+    let mut opcodes = Vec::new();
+    for (index, line) in statements.iter().enumerate() {
+        for statement in line {
+            let opcode = assembler.define(statement).map_err(|message| RnesError::Assembly { line: index + 1, message })?;
+            opcodes.push(opcode);
+        }
+    }
+
+    // Pass 2: encode with every label known
+    assembler.pc = origin as i64;
+    let mut assembly = Assembly {
+        segments: vec![Segment { origin, bytes: Vec::new() }],
+        labels: HashMap::new(),
+    };
+    let mut opcodes = opcodes.into_iter();
+    for (index, line) in statements.iter().enumerate() {
+        for statement in line {
+            let opcode = opcodes.next().flatten();
+            assembler.encode(statement, opcode, &mut assembly.segments)
+                .map_err(|message| RnesError::Assembly { line: index + 1, message })?;
+        }
+    }
+
+    assembly.segments.retain(|segment| !segment.bytes.is_empty());
+    assembly.labels = assembler.labels.into_iter().map(|(name, value)| (name, value as Word)).collect();
+    Ok(assembly)
+}
+
+/// Find the opcode for a mnemonic and addressing mode, preferring official opcodes
+pub fn find_opcode(mnemonic: &str, mode: AddressingMode) -> Option<Byte> {
+    let candidates = || INSTRUCTIONS.iter()
+        .flatten()
+        .filter(move |instruction| instruction.name.eq_ignore_ascii_case(mnemonic) && instruction.addressing_mode == mode);
+    candidates().find(|instruction| !instruction.undocumented)
+        .or_else(|| candidates().next())
+        .map(|instruction| instruction.opcode)
+}
+
+/// Part of an expression's value to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Full,
+    Low,
+    High,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(i64),
+    Label(String),
+    Pc,
+}
+
+/// Sum of numbers and labels, optionally reduced to one byte
+#[derive(Debug, Clone)]
+struct Expr {
+    part: Part,
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    Direct(Expr),
+    IndexedX(Expr),
+    IndexedY(Expr),
+    Indirect(Expr),
+    IndirectX(Expr),
+    IndirectY(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Label(String),
+    Constant(String, Expr),
+    Org(Expr),
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+    Instruction(String, Operand),
+}
+
+struct Assembler {
+    labels: HashMap<String, i64>,
+    pc: i64,
+}
+
+impl Assembler {
+    fn eval(&self, expr: &Expr) -> Option<i64> {
+        let mut value = 0i64;
+        for (negative, term) in &expr.terms {
+            let term = match term {
+                Term::Number(number) => *number,
+                Term::Label(name) => *self.labels.get(name)?,
+                Term::Pc => self.pc,
+            };
+            value = if *negative { value - term } else { value + term };
+        }
+        Some(match expr.part {
+            Part::Full => value,
+            Part::Low => value & 0xFF,
+            Part::High => (value >> 8) & 0xFF,
+        })
+    }
+
+    fn eval_defined(&self, expr: &Expr) -> Result<i64, String> {
+        self.eval(expr).ok_or_else(|| {
+            let undefined = expr.terms.iter().find_map(|(_, term)| match term {
+                Term::Label(name) if !self.labels.contains_key(name) => Some(name.as_str()),
+                _ => None,
+            });
+            format!("undefined label '{}'", undefined.unwrap_or("?"))
+        })
+    }
+
+    fn define_label(&mut self, name: &str, value: i64) -> Result<(), String> {
+        if self.labels.insert(name.to_string(), value).is_some() {
+            return Err(format!("label '{}' defined twice", name));
+        }
+        Ok(())
+    }
+
+    /// Pass 1: record labels and advance the address; returns the chosen opcode
+    fn define(&mut self, statement: &Statement) -> Result<Option<Byte>, String> {
+        match statement {
+            Statement::Label(name) => self.define_label(name, self.pc)?,
+            Statement::Constant(name, expr) => {
+                let value = self.eval(expr)
+                    .ok_or_else(|| format!("constant '{}' must only use labels defined above it", name))?;
+                self.define_label(name, value)?;
+            }
+            Statement::Org(expr) => {
+                self.pc = self.eval(expr).ok_or(".org address must be known in advance")?;
+                check_range(self.pc, 0, 0xFFFF, ".org address")?;
+            }
+            Statement::Bytes(values) => self.pc += values.len() as i64,
+            Statement::Words(values) => self.pc += 2 * values.len() as i64,
+            Statement::Instruction(mnemonic, operand) => {
+                let opcode = self.select_opcode(mnemonic, operand)?;
+                self.pc += get_instruction_length(opcode) as i64;
+                return Ok(Some(opcode));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pick the addressing mode for an instruction, using zero page for known small operands
+    fn select_opcode(&self, mnemonic: &str, operand: &Operand) -> Result<Byte, String> {
+        let fits_zero_page = |expr: &Expr| self.eval(expr).is_some_and(|value| (0..=0xFF).contains(&value));
+        let modes: &[AddressingMode] = match operand {
+            Operand::None => &[AddressingMode::Implied, AddressingMode::Accumulator],
+            Operand::Accumulator => &[AddressingMode::Accumulator],
+            Operand::Immediate(_) => &[AddressingMode::Immediate],
+            Operand::Direct(expr) if fits_zero_page(expr) => &[AddressingMode::Relative, AddressingMode::ZeroPage, AddressingMode::Absolute],
+            Operand::Direct(_) => &[AddressingMode::Relative, AddressingMode::Absolute, AddressingMode::ZeroPage],
+            Operand::IndexedX(expr) if fits_zero_page(expr) => &[AddressingMode::ZeroPageX, AddressingMode::AbsoluteX],
+            Operand::IndexedX(_) => &[AddressingMode::AbsoluteX, AddressingMode::ZeroPageX],
+            Operand::IndexedY(expr) if fits_zero_page(expr) => &[AddressingMode::ZeroPageY, AddressingMode::AbsoluteY],
+            Operand::IndexedY(_) => &[AddressingMode::AbsoluteY, AddressingMode::ZeroPageY],
+            Operand::Indirect(_) => &[AddressingMode::Indirect],
+            Operand::IndirectX(_) => &[AddressingMode::IndirectX],
+            Operand::IndirectY(_) => &[AddressingMode::IndirectY],
+        };
+        modes.iter()
+            .find_map(|&mode| find_opcode(mnemonic, mode))
+            .ok_or_else(|| {
+                if INSTRUCTIONS.iter().flatten().any(|instruction| instruction.name.eq_ignore_ascii_case(mnemonic)) {
+                    format!("addressing mode not supported by {}", mnemonic.to_uppercase())
+                } else {
+                    format!("unknown mnemonic '{}'", mnemonic)
+                }
+            })
+    }
+
+    /// Pass 2: emit bytes for a statement
+    fn encode(&mut self, statement: &Statement, opcode: Option<Byte>, segments: &mut Vec<Segment>) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        match statement {
+            Statement::Label(_) | Statement::Constant(..) => return Ok(()),
+            Statement::Org(expr) => {
+                self.pc = self.eval_defined(expr)?;
+                segments.push(Segment { origin: self.pc as Word, bytes: Vec::new() });
+                return Ok(());
+            }
+            Statement::Bytes(values) => {
+                for value in values {
+                    let value = self.eval_defined(value)?;
+                    check_range(value, -0x80, 0xFF, "byte")?;
+                    bytes.push(value as Byte);
+                }
+            }
+            Statement::Words(values) => {
+                for value in values {
+                    let value = self.eval_defined(value)?;
+                    check_range(value, 0, 0xFFFF, "word")?;
+                    bytes.extend_from_slice(&(value as Word).to_le_bytes());
+                }
+            }
+            Statement::Instruction(_, operand) => {
+                let opcode = opcode.expect("opcode chosen in pass 1");
+                let mode = INSTRUCTIONS[opcode as usize].expect("opcode from instruction table").addressing_mode;
+                bytes.push(opcode);
+                let expr = match operand {
+                    Operand::None | Operand::Accumulator => None,
+                    Operand::Immediate(expr) | Operand::Direct(expr) | Operand::IndexedX(expr)
+                    | Operand::IndexedY(expr) | Operand::Indirect(expr) | Operand::IndirectX(expr)
+                    | Operand::IndirectY(expr) => Some(expr),
+                };
+                if let Some(expr) = expr {
+                    let value = self.eval_defined(expr)?;
+                    match get_instruction_length(opcode) {
+                        2 if mode == AddressingMode::Relative => {
+                            let offset = value - (self.pc + 2);
+                            check_range(offset, -0x80, 0x7F, "branch offset")?;
+                            bytes.push(offset as Byte);
+                        }
+                        2 if mode == AddressingMode::Immediate => {
+                            check_range(value, -0x80, 0xFF, "immediate value")?;
+                            bytes.push(value as Byte);
+                        }
+                        2 => {
+                            check_range(value, 0, 0xFF, "zero page address")?;
+                            bytes.push(value as Byte);
+                        }
+                        _ => {
+                            check_range(value, 0, 0xFFFF, "address")?;
+                            bytes.extend_from_slice(&(value as Word).to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+
+        self.pc += bytes.len() as i64;
+        if self.pc > 0x10000 {
+            return Err("code runs past $FFFF".to_string());
+        }
+        segments.last_mut().expect("assembly starts with a segment").bytes.extend(bytes);
+        Ok(())
+    }
+}
+
+fn check_range(value: i64, min: i64, max: i64, what: &str) -> Result<(), String> {
+    if value < min || value > max {
+        return Err(format!("{} out of range: {}", what, value));
+    }
+    Ok(())
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse one source line into statements (a label may precede an instruction)
+fn parse_line(line: &str) -> Result<Vec<Statement>, String> {
+    let mut rest = line.split(';').next().unwrap_or("").trim();
+    let mut statements = Vec::new();
+
+    if let Some((name, tail)) = rest.split_once(':') {
+        if is_identifier(name.trim()) {
+            statements.push(Statement::Label(name.trim().to_string()));
+            rest = tail.trim();
+        }
+    }
+    if rest.is_empty() {
+        return Ok(statements);
+    }
+
+    if let Some((name, value)) = rest.split_once('=') {
+        let name = name.trim();
+        if !is_identifier(name) {
+            return Err(format!("invalid constant name '{}'", name));
+        }
+        statements.push(Statement::Constant(name.to_string(), parse_expr(value)?));
+        return Ok(statements);
+    }
+
+    let (word, operand) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let operand = operand.trim();
+    let statement = match word.to_ascii_lowercase().as_str() {
+        ".org" => Statement::Org(parse_expr(operand)?),
+        ".byte" | ".db" => Statement::Bytes(parse_list(operand)?),
+        ".word" | ".dw" => Statement::Words(parse_list(operand)?),
+        directive if directive.starts_with('.') => return Err(format!("unknown directive '{}'", word)),
+        _ if is_identifier(word) => Statement::Instruction(word.to_string(), parse_operand(operand)?),
+        _ => return Err(format!("unexpected '{}'", word)),
+    };
+    statements.push(statement);
+    Ok(statements)
+}
+
+fn parse_list(text: &str) -> Result<Vec<Expr>, String> {
+    if text.is_empty() {
+        return Err("expected at least one value".to_string());
+    }
+    text.split(',').map(parse_expr).collect()
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let operand: String = text.split_whitespace().collect();
+    let upper = operand.to_ascii_uppercase();
+
+    if operand.is_empty() {
+        return Ok(Operand::None);
+    }
+    if upper == "A" {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(value) = operand.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_expr(value)?));
+    }
+    if let Some(inner) = operand.strip_prefix('(') {
+        if upper.ends_with(",X)") {
+            return Ok(Operand::IndirectX(parse_expr(&inner[..inner.len() - 3])?));
+        }
+        if upper.ends_with("),Y") {
+            return Ok(Operand::IndirectY(parse_expr(&inner[..inner.len() - 3])?));
+        }
+        if let Some(inner) = inner.strip_suffix(')') {
+            return Ok(Operand::Indirect(parse_expr(inner)?));
+        }
+        return Err(format!("malformed indirect operand '{}'", text));
+    }
+    if upper.ends_with(",X") {
+        return Ok(Operand::IndexedX(parse_expr(&operand[..operand.len() - 2])?));
+    }
+    if upper.ends_with(",Y") {
+        return Ok(Operand::IndexedY(parse_expr(&operand[..operand.len() - 2])?));
+    }
+    Ok(Operand::Direct(parse_expr(&operand)?))
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let text = text.trim();
+    let (part, text) = match text.as_bytes().first() {
+        Some(b'<') => (Part::Low, &text[1..]),
+        Some(b'>') => (Part::High, &text[1..]),
+        _ => (Part::Full, text),
+    };
+
+    let mut terms = Vec::new();
+    let mut negative = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        if (c == '+' || c == '-') && index > start {
+            terms.push((negative, parse_term(&text[start..index])?));
+            negative = c == '-';
+            start = index + 1;
+        } else if c == '-' && index == start && terms.is_empty() {
+            negative = true;
+            start = index + 1;
+        }
+    }
+    terms.push((negative, parse_term(&text[start..])?));
+    Ok(Expr { part, terms })
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    let text = text.trim();
+    let number = |digits: &str, radix| i64::from_str_radix(digits, radix)
+        .map(Term::Number)
+        .map_err(|_| format!("invalid number '{}'", text));
+    match text.chars().next() {
+        None => Err("missing value".to_string()),
+        Some('$') => number(&text[1..], 16),
+        Some('%') => number(&text[1..], 2),
+        Some('*') if text.len() == 1 => Ok(Term::Pc),
+        Some('\'') if text.len() == 3 && text.ends_with('\'') => Ok(Term::Number(text.as_bytes()[1] as i64)),
+        Some(c) if c.is_ascii_digit() => number(text, 10),
+        _ if is_identifier(text) => Ok(Term::Label(text.to_string())),
+        _ => Err(format!("invalid value '{}'", text)),
+    }
+}
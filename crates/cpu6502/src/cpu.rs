@@ -1,6 +1,8 @@
+use alloc::format;
+use alloc::string::String;
 use serde::{Deserialize, Serialize};
 use rnes_common::{Byte, Word, Cycles, RnesResult, MemoryAccess};
-use crate::{StatusFlags, execute_instruction, get_instruction_length};
+use crate::{StatusFlags, execute_instruction, get_instruction_length, is_jam_opcode};
 
 /// 6502 CPU implementation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +23,10 @@ pub struct Cpu {
     pub nmi_pending: bool,
     pub irq_pending: bool,
     pub reset_pending: bool,
+    
+    /// Halted by a JAM (KIL) opcode until the next reset
+    #[serde(default)]
+    pub jammed: bool,
 }
 
 impl Cpu {
@@ -38,6 +44,7 @@ impl Cpu {
             nmi_pending: false,
             irq_pending: false,
             reset_pending: false,
+            jammed: false,
         }
     }
     
@@ -53,6 +60,7 @@ impl Cpu {
         self.nmi_pending = false;
         self.irq_pending = false;
         self.reset_pending = false;
+        self.jammed = false;
         
         // Read reset vector
         let reset_vector = memory.read_word(0xFFFC)?;
@@ -68,8 +76,38 @@ impl Cpu {
         Ok(())
     }
     
+    /// Run the reset sequence triggered by the console's reset button
+    ///
+    /// Unlike `reset`, the registers keep their values: the sequence performs
+    /// three suppressed stack pushes (SP decreases by 3), sets the interrupt
+    /// disable flag and takes 7 cycles before the first instruction runs.
+    pub fn soft_reset<M: MemoryAccess>(&mut self, memory: &mut M) -> RnesResult<()> {
+        self.sp = self.sp.wrapping_sub(3);
+        self.status.set_interrupt_disable(true);
+        self.stall_cycles = 7;
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.reset_pending = false;
+        self.jammed = false;
+        
+        let reset_vector = memory.read_word(0xFFFC)?;
+        if reset_vector < 0x8000 {
+            self.pc = 0x8000;
+        } else {
+            self.pc = reset_vector;
+        }
+        
+        Ok(())
+    }
+    
     /// Execute one CPU cycle
     pub fn step<M: MemoryAccess>(&mut self, memory: &mut M) -> RnesResult<Cycles> {
+        // A jammed CPU ignores interrupts; the rest of the console keeps running
+        if self.jammed {
+            self.cycles += 1;
+            return Ok(1);
+        }
+        
         if self.stall_cycles > 0 {
             self.stall_cycles -= 1;
             self.cycles += 1;
@@ -85,12 +123,18 @@ impl Cpu {
         
         // Fetch and execute instruction
         let opcode = memory.read_byte(self.pc)?;
+        if is_jam_opcode(opcode) {
+            self.jammed = true;
+            self.cycles += 2;
+            return Ok(2);
+        }
         let instruction_cycles = execute_instruction(self, memory, opcode)?;
         
         // Update program counter based on instruction length
         let instruction_length = get_instruction_length(opcode);
         if !matches!(opcode, 
             0x4C | 0x6C | // JMP
+            0x00 | // BRK
             0x20 | // JSR
             0x40 | // RTI
             0x60 | // RTS
@@ -162,7 +206,7 @@ impl Cpu {
     }
     
     /// Pop byte from stack
-    pub fn pop_byte<M: MemoryAccess>(&mut self, memory: &M) -> RnesResult<Byte> {
+    pub fn pop_byte<M: MemoryAccess>(&mut self, memory: &mut M) -> RnesResult<Byte> {
         self.sp = self.sp.wrapping_add(1);
         memory.read_byte(0x0100 + self.sp as Word)
     }
@@ -175,7 +219,7 @@ impl Cpu {
     }
     
     /// Pop word from stack
-    pub fn pop_word<M: MemoryAccess>(&mut self, memory: &M) -> RnesResult<Word> {
+    pub fn pop_word<M: MemoryAccess>(&mut self, memory: &mut M) -> RnesResult<Word> {
         let low = self.pop_byte(memory)? as Word;
         let high = self.pop_byte(memory)? as Word;
         Ok(low | (high << 8))
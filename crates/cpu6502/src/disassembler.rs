@@ -0,0 +1,53 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use rnes_common::{Byte, Word};
+use crate::{AddressingMode, INSTRUCTIONS, get_instruction_length};
+
+/// Disassemble the instruction whose bytes start at `pc`
+///
+/// `bytes` holds the opcode followed by its operands; missing operand bytes
+/// read as zero. Unknown opcodes disassemble as `.byte $XX`.
+pub fn disassemble(pc: Word, bytes: &[Byte]) -> String {
+    let opcode = bytes.first().copied().unwrap_or(0);
+    let Some(instruction) = INSTRUCTIONS[opcode as usize] else {
+        return format!(".byte ${:02X}", opcode);
+    };
+
+    let low = bytes.get(1).copied().unwrap_or(0);
+    let high = bytes.get(2).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([low, high]);
+    let operand = match instruction.addressing_mode {
+        AddressingMode::Implied => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", low),
+        AddressingMode::ZeroPage => format!("${:02X}", low),
+        AddressingMode::ZeroPageX => format!("${:02X},X", low),
+        AddressingMode::ZeroPageY => format!("${:02X},Y", low),
+        AddressingMode::Relative => {
+            let target = pc.wrapping_add(2).wrapping_add(low as i8 as Word);
+            format!("${:04X}", target)
+        }
+        AddressingMode::Absolute => format!("${:04X}", word),
+        AddressingMode::AbsoluteX => format!("${:04X},X", word),
+        AddressingMode::AbsoluteY => format!("${:04X},Y", word),
+        AddressingMode::Indirect => format!("(${:04X})", word),
+        AddressingMode::IndirectX => format!("(${:02X},X)", low),
+        AddressingMode::IndirectY => format!("(${:02X}),Y", low),
+    };
+
+    if operand.is_empty() {
+        instruction.name.to_string()
+    } else {
+        format!("{} {}", instruction.name, operand)
+    }
+}
+
+/// Check if an opcode is undocumented (unknown opcodes count as undocumented)
+pub fn is_undocumented(opcode: Byte) -> bool {
+    INSTRUCTIONS[opcode as usize].is_none_or(|instruction| instruction.undocumented)
+}
+
+/// Length in bytes of the instruction starting with `opcode`
+pub fn instruction_length(opcode: Byte) -> usize {
+    get_instruction_length(opcode) as usize
+}
@@ -298,10 +298,15 @@ pub static INSTRUCTIONS: [Option<Instruction>; 256] = {
     table
 };
 
+/// Check for one of the twelve JAM (KIL) opcodes, which halt the CPU
+pub fn is_jam_opcode(opcode: Byte) -> bool {
+    matches!(opcode, 0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2)
+}
+
 /// Execute instruction
 pub fn execute_instruction<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, opcode: Byte) -> RnesResult<Cycles> {
     let instruction = INSTRUCTIONS[opcode as usize]
-        .ok_or_else(|| rnes_common::RnesError::Cpu(format!("Unknown instruction: 0x{:02X}", opcode)))?;
+        .ok_or(rnes_common::RnesError::InvalidOpcode { opcode, pc: cpu.pc })?;
     
     let cycles = match opcode {
         // ADC - Add with Carry
@@ -414,7 +419,7 @@ pub fn execute_instruction<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, opcod
         0x9A => txs(cpu)?,
         0x98 => tya(cpu)?,
         
-        _ => return Err(rnes_common::RnesError::Cpu(format!("Unimplemented instruction: 0x{:02X}", opcode))),
+        _ => return Err(rnes_common::RnesError::InvalidOpcode { opcode, pc: cpu.pc }),
     };
     
     // Check page boundary crossing for branch instructions
@@ -468,6 +473,8 @@ fn asl_mem<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode)
     let value = memory.read_byte(addr)?;
     let carry = (value & 0x80) != 0;
     let result = value << 1;
+    // Read-modify-write instructions first write back the unmodified value
+    memory.write_byte(addr, value)?;
     memory.write_byte(addr, result)?;
     cpu.status.set_czn(result, carry);
     Ok(5)
@@ -480,6 +487,7 @@ fn bcc<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -491,6 +499,7 @@ fn bcs<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -502,6 +511,7 @@ fn beq<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -525,6 +535,7 @@ fn bmi<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -536,6 +547,7 @@ fn bne<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -547,6 +559,7 @@ fn bpl<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -558,6 +571,7 @@ fn bvc<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -569,6 +583,7 @@ fn bvs<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
         cpu.pc = (cpu.pc as i16 + 2 + offset) as Word;
         Ok(3)
     } else {
+        cpu.pc = cpu.pc.wrapping_add(2);
         Ok(2)
     }
 }
@@ -578,8 +593,8 @@ fn brk<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, _mode: AddressingMode) ->
     // Set break flag
     cpu.status.set(StatusFlags::BREAK, true);
     
-    // Save state to stack
-    cpu.push_word(memory, cpu.pc + 1)?;
+    // Save state to stack, skipping the padding byte after BRK
+    cpu.push_word(memory, cpu.pc.wrapping_add(2))?;
     cpu.push_byte(memory, cpu.status.bits())?;
     
     // Set interrupt disable flag
@@ -652,6 +667,8 @@ fn dec<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode) ->
     let addr = mode.get_address(cpu, memory)?;
     let value = memory.read_byte(addr)?;
     let result = value.wrapping_sub(1);
+    // Read-modify-write instructions first write back the unmodified value
+    memory.write_byte(addr, value)?;
     memory.write_byte(addr, result)?;
     cpu.status.set_zn(result);
     Ok(5)
@@ -684,6 +701,8 @@ fn inc<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode) ->
     let addr = mode.get_address(cpu, memory)?;
     let value = memory.read_byte(addr)?;
     let result = value.wrapping_add(1);
+    // Read-modify-write instructions first write back the unmodified value
+    memory.write_byte(addr, value)?;
     memory.write_byte(addr, result)?;
     cpu.status.set_zn(result);
     Ok(5)
@@ -714,8 +733,8 @@ fn jmp<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode) ->
 fn jsr<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode) -> RnesResult<Cycles> {
     let addr = mode.get_address(cpu, memory)?;
     
-    // Push return address to stack (PC points to next instruction)
-    cpu.push_word(memory, cpu.pc + 1)?;
+    // Push return address to stack (last byte of the JSR instruction)
+    cpu.push_word(memory, cpu.pc.wrapping_add(2))?;
     
     // Jump to subroutine
     cpu.pc = addr;
@@ -760,6 +779,8 @@ fn lsr_mem<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode)
     let value = memory.read_byte(addr)?;
     let carry = (value & 0x01) != 0;
     let result = value >> 1;
+    // Read-modify-write instructions first write back the unmodified value
+    memory.write_byte(addr, value)?;
     memory.write_byte(addr, result)?;
     cpu.status.set_czn(result, carry);
     Ok(5)
@@ -822,6 +843,8 @@ fn rol_mem<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode)
     let old_carry = cpu.status.contains(StatusFlags::CARRY);
     let carry = (value & 0x80) != 0;
     let result = (value << 1) | if old_carry { 1 } else { 0 };
+    // Read-modify-write instructions first write back the unmodified value
+    memory.write_byte(addr, value)?;
     memory.write_byte(addr, result)?;
     cpu.status.set_czn(result, carry);
     Ok(5)
@@ -843,6 +866,8 @@ fn ror_mem<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M, mode: AddressingMode)
     let old_carry = cpu.status.contains(StatusFlags::CARRY);
     let carry = (value & 0x01) != 0;
     let result = (value >> 1) | if old_carry { 0x80 } else { 0 };
+    // Read-modify-write instructions first write back the unmodified value
+    memory.write_byte(addr, value)?;
     memory.write_byte(addr, result)?;
     cpu.status.set_czn(result, carry);
     Ok(5)
@@ -862,9 +887,9 @@ fn rti<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M) -> RnesResult<Cycles> {
 
 /// RTS - Return from Subroutine
 fn rts<M: MemoryAccess>(cpu: &mut Cpu, memory: &mut M) -> RnesResult<Cycles> {
-    // Pull program counter
+    // Pull program counter and step past the JSR operand
     let addr = cpu.pop_word(memory)?;
-    cpu.pc = addr;
+    cpu.pc = addr.wrapping_add(1);
     
     Ok(6)
 }
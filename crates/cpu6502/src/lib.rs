@@ -1,12 +1,22 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod cpu;
 pub mod instructions;
 pub mod addressing;
 pub mod flags;
+#[cfg(feature = "std")]
+pub mod assembler;
+pub mod disassembler;
 
 pub use cpu::*;
 pub use instructions::*;
 pub use addressing::*;
 pub use flags::*;
+#[cfg(feature = "std")]
+pub use assembler::*;
+pub use disassembler::*;
 
 #[cfg(test)]
 mod tests;
@@ -91,7 +91,7 @@ fn test_basic_program_execution() {
     }
     
     impl MemoryAccess for TestMemory {
-        fn read_byte(&self, addr: u16) -> RnesResult<Byte> {
+        fn read_byte(&mut self, addr: u16) -> RnesResult<Byte> {
             Ok(self.ram[addr as usize])
         }
         
@@ -100,7 +100,7 @@ fn test_basic_program_execution() {
             Ok(())
         }
         
-        fn read_word(&self, addr: u16) -> RnesResult<u16> {
+        fn read_word(&mut self, addr: u16) -> RnesResult<u16> {
             let low = self.read_byte(addr)? as u16;
             let high = self.read_byte(addr.wrapping_add(1))? as u16;
             Ok(low | (high << 8))
@@ -165,7 +165,7 @@ fn test_rom_loading_capability() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     impl MemoryAccess for TestMemory {
-        fn read_byte(&self, addr: u16) -> RnesResult<Byte> {
+        fn read_byte(&mut self, addr: u16) -> RnesResult<Byte> {
             Ok(self.ram[addr as usize])
         }
         
@@ -174,7 +174,7 @@ fn test_rom_loading_capability() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
         
-        fn read_word(&self, addr: u16) -> RnesResult<u16> {
+        fn read_word(&mut self, addr: u16) -> RnesResult<u16> {
             let low = self.read_byte(addr)? as u16;
             let high = self.read_byte(addr.wrapping_add(1))? as u16;
             Ok(low | (high << 8))
@@ -221,3 +221,136 @@ fn test_rom_loading_capability() -> Result<(), Box<dyn std::error::Error>> {
     println!("✅ ROM loading and basic execution test passed");
     Ok(())
 }
+
+#[test]
+fn test_assembler_encodes_addressing_modes() {
+    let source = "
+        PPUCTRL = $2000
+        counter = $10
+        start:  sei             ; implied
+                lda #$80        ; immediate
+                sta PPUCTRL     ; absolute
+                inc counter     ; zero page
+                lda counter,x
+                lda $0300,y
+                ldx counter,y
+                lda (counter,x)
+                sta (counter),y
+                asl
+                lsr a
+                jmp (vector)
+        loop:   bne loop
+                jmp start
+        vector: .word start, loop+1
+                .byte <vector, >vector, %101, -1
+    ";
+    let assembly = assemble(source, 0x8000).unwrap();
+    assert_eq!(assembly.bytes(), vec![
+        0x78,
+        0xA9, 0x80,
+        0x8D, 0x00, 0x20,
+        0xE6, 0x10,
+        0xB5, 0x10,
+        0xB9, 0x00, 0x03,
+        0xB6, 0x10,
+        0xA1, 0x10,
+        0x91, 0x10,
+        0x0A,
+        0x4A,
+        0x6C, 0x1D, 0x80,
+        0xD0, 0xFE,
+        0x4C, 0x00, 0x80,
+        0x00, 0x80, 0x19, 0x80,
+        0x1D, 0x80, 0x05, 0xFF,
+    ]);
+    assert_eq!(assembly.label("loop"), Some(0x8018));
+    assert_eq!(assembly.label("PPUCTRL"), Some(0x2000));
+}
+
+#[test]
+fn test_assembler_org_and_image() {
+    let source = "
+        reset:  jmp reset
+                .org $FFFC
+                .word reset, reset
+    ";
+    let assembly = assemble(source, 0xC000).unwrap();
+    assert_eq!(assembly.segments.len(), 2);
+    assert_eq!(assembly.segments[1], Segment { origin: 0xFFFC, bytes: vec![0x00, 0xC0, 0x00, 0xC0] });
+
+    let mut prg = vec![0xEA; 0x4000];
+    assembly.write_to(&mut prg, 0xC000).unwrap();
+    assert_eq!(prg[..3], [0x4C, 0x00, 0xC0]);
+    assert_eq!(prg[0x3FFC..], [0x00, 0xC0, 0x00, 0xC0]);
+    assert!(assembly.write_to(&mut prg, 0xE000).is_err());
+}
+
+#[test]
+fn test_assembler_errors_report_line() {
+    let error = |source: &str| match assemble(source, 0x8000) {
+        Err(rnes_common::RnesError::Assembly { line, message }) => (line, message),
+        other => panic!("expected assembly error, got {:?}", other),
+    };
+    assert_eq!(error("nop\nfoo #1").0, 2);
+    assert_eq!(error("jmp missing").1, "undefined label 'missing'");
+    assert!(error("ldx $1234,x").1.contains("not supported"));
+    assert!(error("a: nop\na: nop").1.contains("twice"));
+    assert!(error("beq far\n.org $9000\nfar: rts").1.contains("branch offset"));
+}
+
+#[test]
+fn test_disassembler_formats_operands() {
+    assert_eq!(disassemble(0xC000, &[0x4C, 0xF5, 0xC5]), "JMP $C5F5");
+    assert_eq!(disassemble(0xC000, &[0xA2, 0x00]), "LDX #$00");
+    assert_eq!(disassemble(0xC000, &[0xB1, 0x10]), "LDA ($10),Y");
+    assert_eq!(disassemble(0xC000, &[0x0A]), "ASL A");
+    assert_eq!(disassemble(0xC000, &[0xEA]), "NOP");
+    assert_eq!(disassemble(0xC010, &[0xD0, 0xFC]), "BNE $C00E");
+    assert_eq!(instruction_length(0x6C), 3);
+    assert!(!is_undocumented(0xA9));
+}
+
+#[test]
+fn test_branches_and_subroutines() {
+    use rnes_common::{Byte, MemoryAccess, RnesResult};
+
+    struct FlatMemory(Vec<Byte>);
+
+    impl MemoryAccess for FlatMemory {
+        fn read_byte(&mut self, addr: u16) -> RnesResult<Byte> {
+            Ok(self.0[addr as usize])
+        }
+
+        fn write_byte(&mut self, addr: u16, value: Byte) -> RnesResult<()> {
+            self.0[addr as usize] = value;
+            Ok(())
+        }
+    }
+
+    let source = "
+        start:  ldx #$03
+        loop:   dex
+                bne loop
+                jsr sub
+                sta $10
+        done:   jmp done
+        sub:    lda #$42
+                rts
+                .org $FFFC
+                .word start
+    ";
+    let assembly = assemble(source, 0x8000).unwrap();
+    let mut memory = FlatMemory(vec![0; 0x10000]);
+    assembly.write_to(&mut memory.0, 0).unwrap();
+
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut memory).unwrap();
+    for _ in 0..20 {
+        cpu.step(&mut memory).unwrap();
+    }
+    // The loop falls through once X hits zero and RTS lands after the JSR
+    assert_eq!(cpu.x, 0);
+    assert_eq!(Some(cpu.pc), assembly.label("done"));
+    assert_eq!(memory.read_byte(0x10).unwrap(), 0x42);
+    assert_eq!(cpu.sp, 0xFD);
+}
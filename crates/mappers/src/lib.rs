@@ -1,8 +1,44 @@
-use rnes_common::{Byte, Word, RnesResult};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod opll;
+
+pub use opll::Opll;
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use core::cell::Cell;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use rnes_common::{Byte, Word, Scanline, ExpansionChip, Mmc3IrqRevision, RnesResult, RnesWarning, WarningSink};
 use rnes_cartridge::Cartridge;
+use serde::{Deserialize, Serialize};
+
+/// What a PPU read is for, passed to `Mapper::ppu_read`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpuFetch {
+    /// $2007 read, or a debugger peek
+    Data,
+    /// Background nametable byte for tile `column` (0-33) of visible line `line`
+    ///
+    /// Columns 0 and 1 are prefetched at the end of the line before; the
+    /// pre-render line fetches with `line` -1.
+    Nametable { line: Scanline, column: u8 },
+    /// Background attribute byte for the tile of the last nametable fetch
+    Attribute,
+    /// Background pattern byte for the tile of the last nametable fetch
+    BackgroundPattern,
+    /// Sprite pattern byte, including the dummy fetches of empty slots
+    SpritePattern,
+}
 
 /// Mapper trait for different cartridge types
-pub trait Mapper {
+///
+/// Mappers are `Send` so a whole emulator can move to another thread.
+pub trait Mapper: Send {
     /// Read from PRG ROM/RAM
     fn read_prg(&self, addr: Word) -> RnesResult<Byte>;
     
@@ -18,17 +54,86 @@ pub trait Mapper {
     /// Get mirroring type
     fn mirroring(&self) -> rnes_cartridge::Mirroring;
     
-    /// Check if IRQ is pending
+    /// Check if the mapper is asserting the IRQ line
+    ///
+    /// The line stays asserted until the game acknowledges the IRQ through
+    /// a mapper register.
     fn irq_pending(&self) -> bool {
         false
     }
     
-    /// Clear IRQ
+    /// Acknowledge the IRQ as the game's acknowledging access would
     fn clear_irq(&mut self) {}
     
     /// Step mapper (for mappers with internal state)
     fn step(&mut self) {}
     
+    /// Called by the PPU when it starts a new scanline (-1 is the pre-render line)
+    fn notify_scanline(&mut self, _scanline: Scanline) {}
+    
+    /// Called by the bus once per CPU cycle (M2), for CPU-clocked IRQ counters
+    fn clock_cpu_cycle(&mut self) {}
+    
+    /// Called by the PPU whenever it drives a new address onto its bus
+    ///
+    /// Covers rendering fetches, $2007 accesses and $2006 writes, so mappers
+    /// can watch address lines (MMC3 A12, MMC2 latches) without decoding
+    /// `read_chr` calls.
+    fn ppu_address_changed(&mut self, _addr: Word) {}
+    
+    /// Supply a PPU read ($0000-$2FFF) from the mapper instead of CHR or CIRAM
+    ///
+    /// Called for every read with what it is for; `None` keeps the normal
+    /// path. Lets MMC5 answer nametable, attribute and pattern fetches from
+    /// ExRAM, fill mode and the split screen tile by tile.
+    fn ppu_read(&mut self, _addr: Word, _fetch: PpuFetch) -> Option<Byte> {
+        None
+    }
+    
+    /// Take a nametable write ($2000-$2FFF) that the mapper backs itself
+    ///
+    /// Returns `false` to store it in CIRAM.
+    fn ppu_write_nametable(&mut self, _addr: Word, _value: Byte) -> bool {
+        false
+    }
+    
+    /// CIRAM page (0 or 1) behind a nametable address, for layouts that
+    /// `mirroring()` can't express
+    fn ciram_page(&self, _addr: Word) -> Option<u8> {
+        None
+    }
+    
+    /// Called when the CPU writes PPUCTRL ($2000) or PPUMASK ($2001)
+    fn ppu_register_written(&mut self, _addr: Word, _value: Byte) {}
+    
+    /// Get the PRG ROM offset currently mapped at a CPU address, for debuggers
+    ///
+    /// Returns `None` outside $8000-$FFFF.
+    fn prg_rom_offset(&self, _addr: Word) -> Option<usize> {
+        None
+    }
+    
+    /// Get the CHR ROM/RAM offset currently mapped at a PPU address ($0000-$1FFF), for debuggers
+    fn chr_offset(&self, _addr: Word) -> Option<usize> {
+        None
+    }
+    
+    /// Clone the mapper with all of its bank and IRQ state, for reverse stepping
+    fn clone_mapper(&self) -> Box<dyn Mapper>;
+    
+    /// Borrow the bank, IRQ and sound chip state, for save states
+    fn save_state(&self) -> MapperState<'_>;
+    
+    /// Restore state taken with `save_state`
+    ///
+    /// Fails if the state was saved from a different kind of mapper.
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()>;
+    
+    /// Get mutable PRG ROM, for debugger code patches
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        None
+    }
+    
     /// Get PRG RAM for battery backup
     fn get_prg_ram(&self) -> Option<&[Byte]> {
         None
@@ -39,6 +144,24 @@ pub trait Mapper {
         None
     }
     
+    /// Get CHR RAM, or `None` if the cartridge uses CHR ROM
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        None
+    }
+    
+    /// Get mutable CHR RAM, or `None` if the cartridge uses CHR ROM
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        None
+    }
+    
+    /// Check if PRG RAM was written since the last `clear_prg_ram_dirty()`
+    fn prg_ram_dirty(&self) -> bool {
+        false
+    }
+    
+    /// Mark PRG RAM as flushed to the battery backup
+    fn clear_prg_ram_dirty(&mut self) {}
+    
     /// Load PRG RAM from battery backup
     fn load_prg_ram(&mut self, data: &[Byte]) -> RnesResult<()> {
         if let Some(ram) = self.get_prg_ram_mut() {
@@ -46,10 +169,13 @@ pub trait Mapper {
                 ram[..data.len()].copy_from_slice(data);
                 Ok(())
             } else {
-                Err(rnes_common::RnesError::Serialization("PRG RAM data too large".to_string()))
+                Err(rnes_common::RnesError::PrgRamSizeMismatch { expected: ram.len(), actual: data.len() })
             }
         } else {
-            Err(rnes_common::RnesError::Serialization("No PRG RAM available".to_string()))
+            Err(rnes_common::RnesError::UnsupportedFeature {
+                component: "this cartridge",
+                feature: "PRG RAM".to_string(),
+            })
         }
     }
     
@@ -57,6 +183,104 @@ pub trait Mapper {
     fn has_battery(&self) -> bool {
         false
     }
+    
+    /// Take warnings about unsupported features the game has used
+    fn take_warnings(&mut self) -> Vec<RnesWarning> {
+        Vec::new()
+    }
+    
+    /// Sound chip on the cartridge, if any
+    ///
+    /// The APU mixes `expansion_audio` at this chip's hardware level and
+    /// applies the user's gain and mute settings for it.
+    fn expansion_chip(&self) -> Option<ExpansionChip> {
+        None
+    }
+    
+    /// Current output of the sound chip, -1.0 to 1.0 of its full scale
+    ///
+    /// Chips are clocked from `clock_cpu_cycle`.
+    fn expansion_audio(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Register state of a mapper, one variant per board
+///
+/// Saving borrows the mapper and deserializing yields an owned copy. The
+/// cartridge and PRG RAM are left out: the ROM is already loaded and the
+/// RAM is saved on its own.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum MapperState<'a> {
+    /// Boards without registers
+    Stateless,
+    Mmc1(Cow<'a, Mmc1Mapper>),
+    Uxrom(Cow<'a, UxromMapper>),
+    Cnrom(Cow<'a, CnromMapper>),
+    Aorom(Cow<'a, AoromMapper>),
+    Mmc3(Cow<'a, Mmc3Mapper>),
+    Rambo1(Cow<'a, Rambo1Mapper>),
+    Vrc7(Cow<'a, Vrc7Mapper>),
+    Namco108(Cow<'a, Namco108Mapper>),
+    Mmc5(Cow<'a, Mmc5Mapper>),
+}
+
+impl MapperState<'_> {
+    /// Error for state saved from a different board
+    fn mismatch() -> rnes_common::RnesError {
+        rnes_common::RnesError::CorruptSaveState("mapper state is for a different board".to_string())
+    }
+    
+    /// Check that this state was saved from the same kind of board as `mapper`
+    pub fn check_board(&self, mapper: &dyn Mapper) -> RnesResult<()> {
+        if core::mem::discriminant(self) == core::mem::discriminant(&mapper.save_state()) {
+            Ok(())
+        } else {
+            Err(Self::mismatch())
+        }
+    }
+    
+    /// Check the registers a mapper indexes, shifts or counts with
+    ///
+    /// Deserialized states can hold values no board reaches, which would
+    /// make later reads panic.
+    pub fn validate(&self) -> RnesResult<()> {
+        let valid = match self {
+            MapperState::Mmc1(state) => state.shift_count < 5,
+            MapperState::Rambo1(state) => state.irq_prescaler < 4,
+            MapperState::Vrc7(state) => {
+                (-2..=VRC_IRQ_PRESCALER).contains(&state.irq_prescaler) && state.opll.is_valid()
+            }
+            MapperState::Mmc5(state) => {
+                state.exram.len() == 1024
+                    && state.prg_mode <= 3
+                    && state.chr_mode <= 3
+                    && state.exram_mode <= 3
+                    && state.tile_split_row < 30
+                    && state.tile_split_column < 32
+            }
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(rnes_common::RnesError::CorruptSaveState("mapper registers out of range".to_string()))
+        }
+    }
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.clone_mapper()
+    }
+}
+
+/// Value read from unmapped or disabled cartridge memory
+///
+/// The data bus keeps the last byte it carried, which for the usual
+/// absolute-addressed access is the high byte of the address.
+fn open_bus(addr: Word) -> Byte {
+    (addr >> 8) as Byte
 }
 
 /// NROM Mapper (Mapper 0)
@@ -65,13 +289,17 @@ pub trait Mapper {
 /// - PRG ROM: 0x8000-0xFFFF (32KB or 16KB mirrored)
 /// - CHR ROM/RAM: 0x0000-0x1FFF (8KB)
 /// - PRG RAM: 0x6000-0x7FFF (8KB, if present)
+#[derive(Clone, Serialize, Deserialize)]
 pub struct NromMapper {
+    #[serde(skip)]
     cartridge: Cartridge,
+    #[serde(skip)]
+    warnings: WarningSink,
 }
 
 impl NromMapper {
     pub fn new(cartridge: Cartridge) -> Self {
-        Self { cartridge }
+        Self { cartridge, warnings: WarningSink::new() }
     }
 }
 
@@ -99,7 +327,8 @@ impl Mapper for NromMapper {
             }
             0x8000..=0xFFFF => {
                 // PRG ROM is read-only
-                tracing::warn!("Attempting to write to PRG ROM: 0x{:04X} = 0x{:02X}", addr, value);
+                tracing::debug!("Ignoring write to PRG ROM: 0x{:04X} = 0x{:02X}", addr, value);
+                self.warnings.warn(RnesWarning::RomWrite { memory: "PRG ROM" });
                 Ok(())
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
@@ -114,9 +343,51 @@ impl Mapper for NromMapper {
         self.cartridge.write_chr(addr, value)
     }
     
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Stateless
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        match state {
+            MapperState::Stateless => Ok(()),
+            _ => Err(MapperState::mismatch()),
+        }
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some(((addr - 0x8000) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some((addr as usize) % self.cartridge.chr_rom.len())
+    }
+    
     fn mirroring(&self) -> rnes_cartridge::Mirroring {
         self.cartridge.mirroring()
     }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+    
+    fn take_warnings(&mut self) -> Vec<RnesWarning> {
+        self.warnings.take()
+    }
 }
 
 /// MMC1 Mapper (Mapper 1)
@@ -127,7 +398,11 @@ impl Mapper for NromMapper {
 /// - Battery backup support
 /// - Configurable mirroring
 /// - Serial shift register for register writes
+/// - SUROM/SXROM 512KB PRG and SOROM/SXROM banked PRG RAM
+/// - SEROM/SHROM fixed 32KB PRG (NES 2.0 submapper 5)
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Mmc1Mapper {
+    #[serde(skip)]
     cartridge: Cartridge,
     shift_register: u8,
     shift_count: u8,
@@ -135,12 +410,20 @@ pub struct Mmc1Mapper {
     chr_bank0: u8,
     chr_bank1: u8,
     prg_bank: u8,
+    #[serde(skip)]
     prg_ram: Vec<Byte>,
+    prg_ram_dirty: bool,
+    fixed_prg: bool,
+    
+    // CPU cycle tracking for the consecutive-write rule
+    cpu_cycle: u64,
+    last_write_cycle: Option<u64>,
 }
 
 impl Mmc1Mapper {
     pub fn new(cartridge: Cartridge) -> Self {
-        let prg_ram_size = if cartridge.has_battery() { 8192 } else { 8192 };
+        let prg_ram_size = cartridge.header.prg_ram_bytes().clamp(8192, 32768);
+        let fixed_prg = cartridge.submapper() == 5;
         Self {
             cartridge,
             shift_register: 0,
@@ -150,10 +433,23 @@ impl Mmc1Mapper {
             chr_bank1: 0,
             prg_bank: 0,
             prg_ram: vec![0; prg_ram_size],
+            prg_ram_dirty: false,
+            fixed_prg,
+            cpu_cycle: 0,
+            last_write_cycle: None,
         }
     }
     
     fn write_register(&mut self, addr: Word, value: Byte) {
+        // Writes on consecutive CPU cycles (the double write of read-modify-write
+        // instructions) are ignored after the first one
+        let consecutive = self.last_write_cycle
+            .is_some_and(|cycle| self.cpu_cycle.saturating_sub(cycle) < 2);
+        self.last_write_cycle = Some(self.cpu_cycle);
+        if consecutive {
+            return;
+        }
+        
         // MMC1 uses a serial shift register
         if value & 0x80 != 0 {
             // Reset shift register
@@ -175,7 +471,7 @@ impl Mmc1Mapper {
                 0 => self.control = self.shift_register & 0x1F,
                 1 => self.chr_bank0 = self.shift_register & 0x1F,
                 2 => self.chr_bank1 = self.shift_register & 0x1F,
-                3 => self.prg_bank = self.shift_register & 0x0F,
+                3 => self.prg_bank = self.shift_register & 0x1F,
                 _ => unreachable!(),
             }
             
@@ -185,40 +481,66 @@ impl Mmc1Mapper {
         }
     }
     
-    fn get_prg_bank(&self, addr: Word) -> Word {
+    /// Get the 256KB PRG half selected by CHR bank bit 4 on 512KB boards
+    fn prg_outer_bank(&self) -> usize {
+        if self.cartridge.prg_rom.len() > 256 * 1024 {
+            (self.chr_bank0 & 0x10) as usize
+        } else {
+            0
+        }
+    }
+    
+    /// Get the 16KB PRG ROM bank mapped at `addr`
+    fn get_prg_bank(&self, addr: Word) -> usize {
+        let upper = addr >= 0xC000;
+        if self.fixed_prg {
+            return upper as usize;
+        }
+        
         let bank_mode = (self.control >> 2) & 3;
-        let bank = self.prg_bank & 0x0F;
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let outer = self.prg_outer_bank();
         
         match bank_mode {
             0 | 1 => {
                 // 32KB mode
-                let bank_32k = (bank >> 1) * 2;
-                if addr < 0xC000 {
-                    bank_32k.into()
-                } else {
-                    (bank_32k + 1).into()
-                }
+                outer | (bank & 0x0E) | upper as usize
             }
             2 => {
                 // Fixed first, switchable last
-                if addr < 0xC000 {
-                    0
-                } else {
-                    bank.into()
-                }
+                if upper { outer | bank } else { outer }
             }
             3 => {
-                // Switchable first, fixed last
-                if addr < 0xC000 {
-                    bank.into()
+                // Switchable first, fixed last (of the current 256KB half)
+                if !upper {
+                    outer | bank
+                } else if self.cartridge.prg_rom.len() > 256 * 1024 {
+                    outer | 0x0F
                 } else {
-                    (self.cartridge.header.prg_rom_size - 1) as Word
+                    (self.cartridge.header.prg_rom_size as usize).saturating_sub(1)
                 }
             }
             _ => unreachable!(),
         }
     }
     
+    /// Check if PRG RAM is enabled (PRG bank bit 4 clear)
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+    
+    /// Get the offset of the selected 8KB PRG RAM bank
+    fn prg_ram_offset(&self) -> usize {
+        let bank = match self.prg_ram.len() {
+            // SOROM: CHR bank bit 3
+            16384 => ((self.chr_bank0 >> 3) & 1) as usize,
+            // SXROM: CHR bank bits 2-3
+            32768 => ((self.chr_bank0 >> 2) & 3) as usize,
+            _ => 0,
+        };
+        bank * 8192
+    }
+    
     fn get_chr_bank(&self, addr: Word) -> Word {
         let chr_mode = (self.control >> 4) & 1;
         
@@ -241,19 +563,18 @@ impl Mapper for Mmc1Mapper {
         match addr {
             0x6000..=0x7FFF => {
                 // PRG RAM
-                let ram_addr = (addr - 0x6000) as usize;
-                if ram_addr < self.prg_ram.len() {
-                    Ok(self.prg_ram[ram_addr])
-                } else {
-                    Err(rnes_common::RnesError::MemoryAccess { address: addr })
+                if !self.prg_ram_enabled() {
+                    return Ok(open_bus(addr));
                 }
+                let ram_addr = self.prg_ram_offset() + (addr - 0x6000) as usize;
+                Ok(self.prg_ram[ram_addr])
             }
             0x8000..=0xFFFF => {
                 // PRG ROM
                 let bank = self.get_prg_bank(addr);
-                let bank_offset = (bank as usize) * 16384;
-                let rom_addr = bank_offset + ((addr - 0x8000) as usize);
-                self.cartridge.read_prg_rom(rom_addr as Word)
+                let rom_addr = bank * 16384 + (addr & 0x3FFF) as usize;
+                let prg_rom = &self.cartridge.prg_rom;
+                Ok(prg_rom[rom_addr % prg_rom.len()])
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
         }
@@ -263,13 +584,14 @@ impl Mapper for Mmc1Mapper {
         match addr {
             0x6000..=0x7FFF => {
                 // PRG RAM
-                let ram_addr = (addr - 0x6000) as usize;
-                if ram_addr < self.prg_ram.len() {
-                    self.prg_ram[ram_addr] = value;
-                    Ok(())
-                } else {
-                    Err(rnes_common::RnesError::MemoryAccess { address: addr })
+                if self.prg_ram_enabled() {
+                    let ram_addr = self.prg_ram_offset() + (addr - 0x6000) as usize;
+                    if self.prg_ram[ram_addr] != value {
+                        self.prg_ram[ram_addr] = value;
+                        self.prg_ram_dirty = true;
+                    }
                 }
+                Ok(())
             }
             0x8000..=0xFFFF => {
                 // Register write
@@ -295,6 +617,42 @@ impl Mapper for Mmc1Mapper {
         self.cartridge.write_chr(chr_addr as Word, value)
     }
     
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Mmc1(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Mmc1(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self {
+            cartridge: core::mem::take(&mut self.cartridge),
+            prg_ram: core::mem::take(&mut self.prg_ram),
+            ..state.into_owned()
+        };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some((self.get_prg_bank(addr) * 16384 + (addr & 0x3FFF) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some((self.get_chr_bank(addr) as usize * 4096 + addr as usize) % self.cartridge.chr_rom.len())
+    }
+    
     fn mirroring(&self) -> rnes_cartridge::Mirroring {
         match self.control & 3 {
             0 => rnes_cartridge::Mirroring::SingleScreenA,
@@ -305,6 +663,18 @@ impl Mapper for Mmc1Mapper {
         }
     }
     
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+    
+    fn clock_cpu_cycle(&mut self) {
+        self.cpu_cycle = self.cpu_cycle.wrapping_add(1);
+    }
+    
     fn get_prg_ram(&self) -> Option<&[Byte]> {
         Some(&self.prg_ram)
     }
@@ -313,55 +683,66 @@ impl Mapper for Mmc1Mapper {
         Some(&mut self.prg_ram)
     }
     
+    fn prg_ram_dirty(&self) -> bool {
+        self.prg_ram_dirty
+    }
+    
+    fn clear_prg_ram_dirty(&mut self) {
+        self.prg_ram_dirty = false;
+    }
+    
     fn has_battery(&self) -> bool {
         self.cartridge.has_battery()
     }
 }
 
+/// Bank select mask covering every `bank_size` bank of `rom_len` bytes
+///
+/// Boards only wire up the bank lines their ROM needs, so larger selects
+/// wrap around. ROMs smaller than one bank get a mask of 0.
+pub fn bank_mask(rom_len: usize, bank_size: usize) -> usize {
+    rom_len.div_ceil(bank_size).max(1).next_power_of_two() - 1
+}
+
 /// UxROM Mapper (Mapper 2)
 /// 
 /// Features:
-/// - 16KB PRG ROM banks (switchable)
+/// - 16KB PRG ROM banks (switchable), up to 4MB on oversize boards
 /// - Fixed last 16KB of PRG ROM
 /// - 8KB CHR ROM/RAM
 /// - Simple bank switching
+#[derive(Clone, Serialize, Deserialize)]
 pub struct UxromMapper {
+    #[serde(skip)]
     cartridge: Cartridge,
     prg_bank: u8,
+    prg_bank_mask: u8,
 }
 
 impl UxromMapper {
     pub fn new(cartridge: Cartridge) -> Self {
+        // The whole register is used by 4MB oversize boards
+        let prg_bank_mask = bank_mask(cartridge.prg_rom.len(), 16384).min(0xFF) as u8;
         Self {
             cartridge,
             prg_bank: 0,
+            prg_bank_mask,
         }
     }
 }
 
 impl Mapper for UxromMapper {
     fn read_prg(&self, addr: Word) -> RnesResult<Byte> {
-        match addr {
-            0x8000..=0xBFFF => {
-                // Switchable 16KB bank
-                let bank_offset = (self.prg_bank as usize) * 16384;
-                let rom_addr = bank_offset + ((addr - 0x8000) as usize);
-                self.cartridge.read_prg_rom(rom_addr as Word)
-            }
-            0xC000..=0xFFFF => {
-                // Fixed last 16KB
-                let rom_addr = addr - 0x8000;
-                self.cartridge.read_prg_rom(rom_addr)
-            }
-            _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
-        }
+        self.prg_rom_offset(addr)
+            .map(|offset| self.cartridge.prg_rom[offset])
+            .ok_or(rnes_common::RnesError::MemoryAccess { address: addr })
     }
     
     fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
         match addr {
             0x8000..=0xFFFF => {
                 // Bank select
-                self.prg_bank = value & 0x0F;
+                self.prg_bank = value & self.prg_bank_mask;
                 Ok(())
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
@@ -376,29 +757,130 @@ impl Mapper for UxromMapper {
         self.cartridge.write_chr(addr, value)
     }
     
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Uxrom(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Uxrom(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self { cartridge: core::mem::take(&mut self.cartridge), ..state.into_owned() };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        let len = self.cartridge.prg_rom.len();
+        let offset = match addr {
+            0x8000..=0xBFFF => self.prg_bank as usize * 16384 + (addr - 0x8000) as usize,
+            // Fixed last 16KB
+            0xC000..=0xFFFF => len.saturating_sub(16384) + (addr - 0xC000) as usize,
+            _ => return None,
+        };
+        Some(offset % len)
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some((addr as usize) % self.cartridge.chr_rom.len())
+    }
+    
     fn mirroring(&self) -> rnes_cartridge::Mirroring {
         self.cartridge.mirroring()
     }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
 }
 
-/// CNROM Mapper (Mapper 3)
+/// CNROM Mapper (Mapper 3, and mapper 185 with copy protection)
 /// 
 /// Features:
 /// - 32KB PRG ROM (fixed)
 /// - 8KB CHR ROM banks (switchable)
 /// - Simple CHR bank switching
+/// - Mapper 185: a diode key in the bank register enables CHR ROM
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CnromMapper {
+    #[serde(skip)]
     cartridge: Cartridge,
     chr_bank: u8,
+    chr_bank_mask: u8,
+    protection: Option<ChrProtection>,
+    chr_enabled: bool,
+}
+
+/// How a mapper 185 board decides whether CHR ROM is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChrProtection {
+    /// Submapper 0: any value with either low bit set except $13, as
+    /// FCEUX guesses for ROMs without a submapper
+    Heuristic,
+    /// Submappers 4-7: the low two bits must equal the key
+    Key(u8),
+}
+
+impl ChrProtection {
+    /// Protection used by a mapper 185 submapper
+    pub fn from_submapper(submapper: u8) -> Self {
+        match submapper {
+            4..=7 => ChrProtection::Key(submapper - 4),
+            _ => ChrProtection::Heuristic,
+        }
+    }
+    
+    /// Check if a bank register value enables CHR ROM
+    pub fn enables(self, value: Byte) -> bool {
+        match self {
+            ChrProtection::Heuristic => value & 0x03 != 0 && value != 0x13,
+            ChrProtection::Key(key) => value & 0x03 == key,
+        }
+    }
 }
 
 impl CnromMapper {
     pub fn new(cartridge: Cartridge) -> Self {
+        // Boards decode only as many bank bits as their CHR ROM needs
+        let chr_bank_mask = bank_mask(cartridge.chr_rom.len(), 8192).min(0xFF) as u8;
         Self {
             cartridge,
             chr_bank: 0,
+            chr_bank_mask,
+            protection: None,
+            chr_enabled: true,
         }
     }
+    
+    /// Create a mapper 185 board, using the submapper to pick its key
+    ///
+    /// CHR ROM starts out disabled, as the register powers up as 0.
+    pub fn with_chr_protection(cartridge: Cartridge) -> Self {
+        let protection = ChrProtection::from_submapper(cartridge.submapper());
+        let mut mapper = Self::new(cartridge);
+        // The register only drives the diodes; there is a single 8KB bank
+        mapper.chr_bank_mask = 0;
+        mapper.protection = Some(protection);
+        mapper.chr_enabled = protection.enables(0);
+        mapper
+    }
+    
+    /// Check if CHR ROM currently responds to reads
+    pub fn chr_enabled(&self) -> bool {
+        self.chr_enabled
+    }
 }
 
 impl Mapper for CnromMapper {
@@ -416,8 +898,11 @@ impl Mapper for CnromMapper {
     fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
         match addr {
             0x8000..=0xFFFF => {
-                // CHR bank select
-                self.chr_bank = value & 0x03;
+                // CHR bank select, or the protection key on mapper 185
+                self.chr_bank = value & self.chr_bank_mask;
+                if let Some(protection) = self.protection {
+                    self.chr_enabled = protection.enables(value);
+                }
                 Ok(())
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
@@ -425,41 +910,94 @@ impl Mapper for CnromMapper {
     }
     
     fn read_chr(&mut self, addr: Word) -> RnesResult<Byte> {
-        let bank_offset = (self.chr_bank as usize) * 8192;
-        let chr_addr = bank_offset + (addr as usize);
-        self.cartridge.read_chr(chr_addr as Word)
+        if !self.chr_enabled {
+            // Nothing drives the PPU data bus, which floats high
+            return Ok(0xFF);
+        }
+        let offset = self.chr_bank as usize * 8192 + (addr & 0x1FFF) as usize;
+        Ok(self.cartridge.chr_rom[offset % self.cartridge.chr_rom.len()])
     }
     
     fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
-        // CNROM typically has CHR ROM, so writes are ignored
-        tracing::warn!("Attempting to write to CHR ROM: 0x{:04X} = 0x{:02X}", addr, value);
+        // CNROM typically has CHR ROM, where the cartridge ignores writes
+        if self.cartridge.chr_is_ram && self.chr_enabled {
+            let offset = self.chr_bank as usize * 8192 + (addr & 0x1FFF) as usize;
+            let len = self.cartridge.chr_rom.len();
+            self.cartridge.chr_rom[offset % len] = value;
+        }
         Ok(())
     }
     
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Cnrom(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Cnrom(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self { cartridge: core::mem::take(&mut self.cartridge), ..state.into_owned() };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some(((addr - 0x8000) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some((self.chr_bank as usize * 8192 + addr as usize) % self.cartridge.chr_rom.len())
+    }
+    
     fn mirroring(&self) -> rnes_cartridge::Mirroring {
         self.cartridge.mirroring()
     }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
 }
 
 /// AOROM Mapper (Mapper 7)
 /// 
 /// Features:
-/// - 32KB PRG ROM banks (switchable)
+/// - 32KB PRG ROM banks (switchable), up to 512KB
 /// - 8KB CHR ROM/RAM
 /// - Simple bank switching
 /// - Configurable mirroring
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AoromMapper {
+    #[serde(skip)]
     cartridge: Cartridge,
     prg_bank: u8,
+    prg_bank_mask: u8,
     mirroring: rnes_cartridge::Mirroring,
 }
 
 impl AoromMapper {
     pub fn new(cartridge: Cartridge) -> Self {
         let mirroring = cartridge.mirroring();
+        // Bit 4 selects the nametable, leaving bits 0-3 for the bank
+        let prg_bank_mask = bank_mask(cartridge.prg_rom.len(), 32768).min(0x0F) as u8;
         Self {
             cartridge,
             prg_bank: 0,
+            prg_bank_mask,
             mirroring,
         }
     }
@@ -467,22 +1005,16 @@ impl AoromMapper {
 
 impl Mapper for AoromMapper {
     fn read_prg(&self, addr: Word) -> RnesResult<Byte> {
-        match addr {
-            0x8000..=0xFFFF => {
-                // Switchable 32KB bank
-                let bank_offset = (self.prg_bank as usize) * 32768;
-                let rom_addr = bank_offset + ((addr - 0x8000) as usize);
-                self.cartridge.read_prg_rom(rom_addr as Word)
-            }
-            _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
-        }
+        self.prg_rom_offset(addr)
+            .map(|offset| self.cartridge.prg_rom[offset])
+            .ok_or(rnes_common::RnesError::MemoryAccess { address: addr })
     }
     
     fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
         match addr {
             0x8000..=0xFFFF => {
                 // Bank select and mirroring
-                self.prg_bank = value & 0x0F;
+                self.prg_bank = value & self.prg_bank_mask;
                 self.mirroring = if value & 0x10 != 0 {
                     rnes_cartridge::Mirroring::SingleScreenA
                 } else {
@@ -502,9 +1034,49 @@ impl Mapper for AoromMapper {
         self.cartridge.write_chr(addr, value)
     }
     
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Aorom(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Aorom(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self { cartridge: core::mem::take(&mut self.cartridge), ..state.into_owned() };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some((self.prg_bank as usize * 32768 + (addr - 0x8000) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some((addr as usize) % self.cartridge.chr_rom.len())
+    }
+    
     fn mirroring(&self) -> rnes_cartridge::Mirroring {
         self.mirroring
     }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
 }
 
 /// MMC3 Mapper (Mapper 4)
@@ -516,8 +1088,12 @@ impl Mapper for AoromMapper {
 /// - Battery backup support
 /// - Configurable mirroring
 /// - A12 clock detection for IRQ
+/// - Sharp (MMC3B/C) and NEC (MMC3A) IRQ revisions
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Mmc3Mapper {
+    #[serde(skip)]
     cartridge: Cartridge,
+    #[serde(skip)]
     prg_ram: Vec<Byte>,
     
     // Bank registers
@@ -527,36 +1103,106 @@ pub struct Mmc3Mapper {
     // Mirroring control
     mirroring: rnes_cartridge::Mirroring,
     
+    // PRG RAM protect ($A001)
+    prg_ram_enabled: bool,
+    prg_ram_write_protect: bool,
+    prg_ram_dirty: bool,
+    
     // IRQ counter
     irq_counter: u8,
     irq_latch: u8,
+    irq_reload: bool,
     irq_enabled: bool,
     irq_pending: bool,
+    irq_revision: Mmc3IrqRevision,
     
     // A12 clock detection
     last_a12: bool,
-    a12_rising_edge: bool,
+    a12_low_dots: u32,
+}
+
+/// PPU dots A12 must stay low before a rise clocks the IRQ counter
+///
+/// The MMC3 only counts a rise after A12 has been low for about three M2
+/// falling edges, which filters out the rapid toggling during sprite
+/// fetches with 8x16 sprites.
+const MMC3_A12_FILTER_DOTS: u32 = 9;
+
+/// 8KB PRG ROM bank mapped at a CPU address ($8000-$FFFF) by MMC3-style banking
+///
+/// $A000 is always R7 and $E000 the last bank. Bit 6 of the bank select
+/// swaps $8000 and $C000 between R6 and the second-last bank.
+fn mmc3_prg_bank(bank_select: u8, bank_data: &[u8; 8], prg_len: usize, addr: Word) -> usize {
+    let banks = (prg_len / 8192).max(1);
+    let second_last = banks.saturating_sub(2);
+    let swapped = bank_select & 0x40 != 0;
+    let bank = match (addr >> 13) & 0x03 {
+        0 if swapped => second_last,
+        0 => (bank_data[6] & 0x3F) as usize,
+        1 => (bank_data[7] & 0x3F) as usize,
+        2 if swapped => (bank_data[6] & 0x3F) as usize,
+        2 => second_last,
+        _ => banks - 1,
+    };
+    bank % banks
+}
+
+/// 1KB CHR bank register value mapped at a PPU address ($0000-$1FFF) by MMC3-style banking
+///
+/// R0 and R1 select 2KB banks (ignoring their low bit) for $0000-$0FFF
+/// and R2-R5 1KB banks for $1000-$1FFF. Bit 7 of the bank select
+/// inverts A12, swapping the two halves.
+fn mmc3_chr_bank(bank_select: u8, bank_data: &[u8; 8], addr: Word) -> u8 {
+    let addr = if bank_select & 0x80 != 0 { addr ^ 0x1000 } else { addr };
+    let slot = ((addr >> 10) & 0x07) as usize;
+    match slot {
+        0..=3 => (bank_data[slot / 2] & 0xFE) | (slot as u8 & 1),
+        _ => bank_data[slot - 2],
+    }
 }
 
 impl Mmc3Mapper {
     pub fn new(cartridge: Cartridge) -> Self {
         let prg_ram_size = if cartridge.has_battery() { 8192 } else { 8192 };
         let mirroring = cartridge.mirroring();
+        // NES 2.0 submapper 4 marks boards with the MMC3A
+        let irq_revision = if cartridge.submapper() == 4 {
+            Mmc3IrqRevision::Nec
+        } else {
+            Mmc3IrqRevision::Sharp
+        };
         Self {
             cartridge,
             prg_ram: vec![0; prg_ram_size],
             bank_select: 0,
             bank_data: [0; 8],
             mirroring,
+            // Enabled at power-up for games that never write $A001
+            prg_ram_enabled: true,
+            prg_ram_write_protect: false,
+            prg_ram_dirty: false,
             irq_counter: 0,
             irq_latch: 0,
+            irq_reload: false,
             irq_enabled: false,
             irq_pending: false,
+            irq_revision,
             last_a12: false,
-            a12_rising_edge: false,
+            a12_low_dots: MMC3_A12_FILTER_DOTS,
         }
     }
     
+    /// Use a specific IRQ revision instead of the one implied by the submapper
+    pub fn with_irq_revision(mut self, revision: Mmc3IrqRevision) -> Self {
+        self.irq_revision = revision;
+        self
+    }
+    
+    /// Get IRQ revision
+    pub fn irq_revision(&self) -> Mmc3IrqRevision {
+        self.irq_revision
+    }
+    
     fn write_bank_select(&mut self, value: Byte) {
         self.bank_select = value;
     }
@@ -574,12 +1220,19 @@ impl Mmc3Mapper {
         };
     }
     
+    fn write_prg_ram_protect(&mut self, value: Byte) {
+        self.prg_ram_enabled = value & 0x80 != 0;
+        self.prg_ram_write_protect = value & 0x40 != 0;
+    }
+    
     fn write_irq_latch(&mut self, value: Byte) {
         self.irq_latch = value;
     }
     
     fn write_irq_reload(&mut self, _value: Byte) {
-        self.irq_counter = self.irq_latch;
+        // The counter is reloaded from the latch on the next clock
+        self.irq_counter = 0;
+        self.irq_reload = true;
     }
     
     fn write_irq_disable(&mut self, _value: Byte) {
@@ -591,165 +1244,45 @@ impl Mmc3Mapper {
         self.irq_enabled = true;
     }
     
-    fn get_prg_bank(&self, addr: Word) -> Word {
-        let bank_mode = (self.bank_select >> 6) & 1;
-        let bank_num = (addr >> 13) & 1;
-        
-        match (bank_mode, bank_num) {
-            (0, 0) => {
-                // Fixed first bank, switchable second bank
-                if addr < 0xC000 {
-                    0 // Fixed first 8KB
-                } else {
-                    (self.bank_data[6] & 0x3F) as Word // Switchable 8KB
-                }
-            }
-            (0, 1) => {
-                // Switchable first bank, fixed last bank
-                if addr < 0xC000 {
-                    (self.bank_data[6] & 0x3F) as Word // Switchable 8KB
-                } else {
-                    (self.cartridge.header.prg_rom_size - 1) as Word // Fixed last 8KB
-                }
-            }
-            (1, 0) => {
-                // Switchable first bank, fixed last bank
-                if addr < 0xC000 {
-                    (self.bank_data[7] & 0x3F) as Word // Switchable 8KB
-                } else {
-                    (self.cartridge.header.prg_rom_size - 1) as Word // Fixed last 8KB
-                }
-            }
-            (1, 1) => {
-                // Fixed first bank, switchable second bank
-                if addr < 0xC000 {
-                    0 // Fixed first 8KB
-                } else {
-                    (self.bank_data[7] & 0x3F) as Word // Switchable 8KB
-                }
-            }
-            _ => unreachable!(),
-        }
+    fn get_prg_bank(&self, addr: Word) -> usize {
+        mmc3_prg_bank(self.bank_select, &self.bank_data, self.cartridge.prg_rom.len(), addr)
     }
     
-    fn get_chr_bank(&self, addr: Word) -> Word {
-        let chr_mode = (self.bank_select >> 7) & 1;
-        let bank_num = (addr >> 10) & 3;
-        
-        match (chr_mode, bank_num) {
-            (0, 0) => {
-                // 2KB mode for first bank
-                if addr < 0x0800 {
-                    (self.bank_data[0] & 0xFE) as Word // 2KB bank
-                } else if addr < 0x1000 {
-                    (self.bank_data[1] & 0xFE) as Word // 2KB bank
-                } else {
-                    (self.bank_data[2] as Word) * 2 // 1KB bank
-                }
-            }
-            (0, 1) => {
-                // 2KB mode for second bank
-                if addr < 0x0800 {
-                    (self.bank_data[2] as Word) * 2 // 1KB bank
-                } else if addr < 0x1000 {
-                    (self.bank_data[3] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[4] as Word) * 2 // 1KB bank
-                }
-            }
-            (0, 2) => {
-                // 2KB mode for third bank
-                if addr < 0x0800 {
-                    (self.bank_data[4] as Word) * 2 // 1KB bank
-                } else if addr < 0x1000 {
-                    (self.bank_data[5] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[0] & 0xFE) as Word // 2KB bank
-                }
-            }
-            (0, 3) => {
-                // 2KB mode for fourth bank
-                if addr < 0x0800 {
-                    (self.bank_data[6] as Word) * 2 // 1KB bank
-                } else if addr < 0x1000 {
-                    (self.bank_data[7] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[1] & 0xFE) as Word // 2KB bank
-                }
-            }
-            (1, 0) => {
-                // 1KB mode for first bank
-                if addr < 0x0400 {
-                    (self.bank_data[0] as Word) * 2 // 1KB bank
-                } else if addr < 0x0800 {
-                    (self.bank_data[1] as Word) * 2 // 1KB bank
-                } else if addr < 0x0C00 {
-                    (self.bank_data[2] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[3] as Word) * 2 // 1KB bank
-                }
-            }
-            (1, 1) => {
-                // 1KB mode for second bank
-                if addr < 0x0400 {
-                    (self.bank_data[4] as Word) * 2 // 1KB bank
-                } else if addr < 0x0800 {
-                    (self.bank_data[5] as Word) * 2 // 1KB bank
-                } else if addr < 0x0C00 {
-                    (self.bank_data[6] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[7] as Word) * 2 // 1KB bank
-                }
-            }
-            (1, 2) => {
-                // 1KB mode for third bank
-                if addr < 0x0400 {
-                    (self.bank_data[0] as Word) * 2 // 1KB bank
-                } else if addr < 0x0800 {
-                    (self.bank_data[1] as Word) * 2 // 1KB bank
-                } else if addr < 0x0C00 {
-                    (self.bank_data[2] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[3] as Word) * 2 // 1KB bank
-                }
-            }
-            (1, 3) => {
-                // 1KB mode for fourth bank
-                if addr < 0x0400 {
-                    (self.bank_data[4] as Word) * 2 // 1KB bank
-                } else if addr < 0x0800 {
-                    (self.bank_data[5] as Word) * 2 // 1KB bank
-                } else if addr < 0x0C00 {
-                    (self.bank_data[6] as Word) * 2 // 1KB bank
-                } else {
-                    (self.bank_data[7] as Word) * 2 // 1KB bank
-                }
-            }
-            _ => unreachable!(),
-        }
+    fn get_chr_bank(&self, addr: Word) -> usize {
+        mmc3_chr_bank(self.bank_select, &self.bank_data, addr) as usize
     }
     
     fn clock_irq_counter(&mut self) {
-        if self.irq_counter == 0 {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload;
+        if self.irq_counter == 0 || self.irq_reload {
             self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
         } else {
             self.irq_counter -= 1;
         }
         
-        if self.irq_counter == 0 && self.irq_enabled {
+        let fire = match self.irq_revision {
+            Mmc3IrqRevision::Sharp => self.irq_counter == 0,
+            // A latch of 0 only fires once per explicit reload
+            Mmc3IrqRevision::Nec => self.irq_counter == 0 && (previous != 0 || reloaded),
+        };
+        if fire && self.irq_enabled {
             self.irq_pending = true;
         }
     }
     
     fn detect_a12_rising_edge(&mut self, addr: Word) {
         let current_a12 = (addr & 0x1000) != 0;
-        self.a12_rising_edge = !self.last_a12 && current_a12;
-        self.last_a12 = current_a12;
         
-        // If we detected a rising edge, clock the IRQ counter
-        if self.a12_rising_edge {
+        // Only count rises after A12 has been low long enough
+        if current_a12 && !self.last_a12 && self.a12_low_dots >= MMC3_A12_FILTER_DOTS {
             self.clock_irq_counter();
         }
+        if !current_a12 && self.last_a12 {
+            self.a12_low_dots = 0;
+        }
+        self.last_a12 = current_a12;
     }
 }
 
@@ -758,6 +1291,9 @@ impl Mapper for Mmc3Mapper {
         match addr {
             0x6000..=0x7FFF => {
                 // PRG RAM
+                if !self.prg_ram_enabled {
+                    return Ok(open_bus(addr));
+                }
                 let ram_addr = (addr - 0x6000) as usize;
                 if ram_addr < self.prg_ram.len() {
                     Ok(self.prg_ram[ram_addr])
@@ -767,10 +1303,8 @@ impl Mapper for Mmc3Mapper {
             }
             0x8000..=0xFFFF => {
                 // PRG ROM
-                let bank = self.get_prg_bank(addr);
-                let bank_offset = (bank as usize) * 8192;
-                let rom_addr = bank_offset + ((addr & 0x1FFF) as usize);
-                self.cartridge.read_prg_rom(rom_addr as Word)
+                let offset = self.get_prg_bank(addr) * 8192 + (addr & 0x1FFF) as usize;
+                Ok(self.cartridge.prg_rom[offset % self.cartridge.prg_rom.len()])
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
         }
@@ -780,9 +1314,15 @@ impl Mapper for Mmc3Mapper {
         match addr {
             0x6000..=0x7FFF => {
                 // PRG RAM
+                if !self.prg_ram_enabled || self.prg_ram_write_protect {
+                    return Ok(());
+                }
                 let ram_addr = (addr - 0x6000) as usize;
                 if ram_addr < self.prg_ram.len() {
-                    self.prg_ram[ram_addr] = value;
+                    if self.prg_ram[ram_addr] != value {
+                        self.prg_ram[ram_addr] = value;
+                        self.prg_ram_dirty = true;
+                    }
                     Ok(())
                 } else {
                     Err(rnes_common::RnesError::MemoryAccess { address: addr })
@@ -804,7 +1344,7 @@ impl Mapper for Mmc3Mapper {
                     self.write_mirroring(value);
                 } else {
                     // Odd address: PRG RAM protect
-                    // Ignored for now
+                    self.write_prg_ram_protect(value);
                 }
                 Ok(())
             }
@@ -833,27 +1373,68 @@ impl Mapper for Mmc3Mapper {
     }
     
     fn read_chr(&mut self, addr: Word) -> RnesResult<Byte> {
-        // Detect A12 rising edge for IRQ counter
-        self.detect_a12_rising_edge(addr);
-        
-        let bank = self.get_chr_bank(addr);
-        let bank_offset = (bank as usize) * 1024;
-        let chr_addr = bank_offset + ((addr & 0x03FF) as usize);
-        self.cartridge.read_chr(chr_addr as Word)
+        let offset = self.get_chr_bank(addr) * 1024 + (addr & 0x03FF) as usize;
+        Ok(self.cartridge.chr_rom[offset % self.cartridge.chr_rom.len()])
     }
     
     fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
-        // MMC3 can have CHR RAM
-        let bank = self.get_chr_bank(addr);
-        let bank_offset = (bank as usize) * 1024;
-        let chr_addr = bank_offset + ((addr & 0x03FF) as usize);
-        self.cartridge.write_chr(chr_addr as Word, value)
+        // MMC3 can have CHR RAM, banked the same way; CHR ROM ignores writes
+        if self.cartridge.chr_is_ram {
+            let offset = self.get_chr_bank(addr) * 1024 + (addr & 0x03FF) as usize;
+            let len = self.cartridge.chr_rom.len();
+            self.cartridge.chr_rom[offset % len] = value;
+        }
+        Ok(())
+    }
+    
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Mmc3(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Mmc3(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self {
+            cartridge: core::mem::take(&mut self.cartridge),
+            prg_ram: core::mem::take(&mut self.prg_ram),
+            ..state.into_owned()
+        };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some((self.get_prg_bank(addr) * 8192 + (addr & 0x1FFF) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some((self.get_chr_bank(addr) * 1024 + (addr & 0x03FF) as usize) % self.cartridge.chr_rom.len())
     }
     
     fn mirroring(&self) -> rnes_cartridge::Mirroring {
         self.mirroring
     }
     
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+    
     fn irq_pending(&self) -> bool {
         self.irq_pending
     }
@@ -863,9 +1444,15 @@ impl Mapper for Mmc3Mapper {
     }
     
     fn step(&mut self) {
-        // MMC3 IRQ counter is clocked by A12 rising edge
-        // The counter is now handled in detect_a12_rising_edge
-        self.a12_rising_edge = false;
+        // Measure how long A12 has been low for the rise filter
+        if !self.last_a12 {
+            self.a12_low_dots = self.a12_low_dots.saturating_add(1);
+        }
+    }
+    
+    fn ppu_address_changed(&mut self, addr: Word) {
+        // Detect A12 rising edge for IRQ counter
+        self.detect_a12_rising_edge(addr);
     }
     
     fn get_prg_ram(&self) -> Option<&[Byte]> {
@@ -876,20 +1463,2000 @@ impl Mapper for Mmc3Mapper {
         Some(&mut self.prg_ram)
     }
     
+    fn prg_ram_dirty(&self) -> bool {
+        self.prg_ram_dirty
+    }
+    
+    fn clear_prg_ram_dirty(&mut self) {
+        self.prg_ram_dirty = false;
+    }
+    
     fn has_battery(&self) -> bool {
         self.cartridge.has_battery()
     }
 }
 
-/// Create mapper from cartridge
-pub fn create_mapper(cartridge: Cartridge) -> RnesResult<Box<dyn Mapper>> {
-    match cartridge.mapper_number() {
-        0 => Ok(Box::new(NromMapper::new(cartridge))),
-        1 => Ok(Box::new(Mmc1Mapper::new(cartridge))),
-        2 => Ok(Box::new(UxromMapper::new(cartridge))),
-        3 => Ok(Box::new(CnromMapper::new(cartridge))),
-        7 => Ok(Box::new(AoromMapper::new(cartridge))),
-        4 => Ok(Box::new(Mmc3Mapper::new(cartridge))),
-        mapper => Err(rnes_common::RnesError::UnsupportedMapper(mapper))
+/// Tengen RAMBO-1 Mapper (Mapper 64)
+///
+/// MMC3-like banking with extra registers:
+/// - R15 adds a third switchable 8KB PRG bank
+/// - Bit 5 of the bank select splits R0/R1 into 1KB banks, with R8/R9
+///   filling $0400/$0C00
+/// - IRQ counter clocked by scanlines (A12) or every 4 CPU cycles
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rambo1Mapper {
+    #[serde(skip)]
+    cartridge: Cartridge,
+    bank_select: u8,
+    bank_data: [u8; 16],
+    mirroring: rnes_cartridge::Mirroring,
+    
+    // IRQ counter
+    irq_counter: u8,
+    irq_latch: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    irq_cpu_mode: bool,
+    irq_prescaler: u8,
+    
+    // A12 clock detection
+    last_a12: bool,
+    a12_low_dots: u32,
+}
+
+impl Rambo1Mapper {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring();
+        Self {
+            cartridge,
+            bank_select: 0,
+            bank_data: [0; 16],
+            mirroring,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            irq_cpu_mode: false,
+            irq_prescaler: 0,
+            last_a12: false,
+            a12_low_dots: MMC3_A12_FILTER_DOTS,
+        }
+    }
+    
+    /// Check if the IRQ counter is clocked by CPU cycles instead of scanlines
+    pub fn irq_cpu_mode(&self) -> bool {
+        self.irq_cpu_mode
+    }
+    
+    /// 8KB PRG ROM bank mapped at a CPU address ($8000-$FFFF)
+    ///
+    /// Mode 0 maps R6, R7, R15 and the last bank; mode 1 (bit 6) maps
+    /// R15, R6, R7 and the last bank.
+    fn get_prg_bank(&self, addr: Word) -> usize {
+        let banks = (self.cartridge.prg_rom.len() / 8192).max(1);
+        let register = match ((addr >> 13) & 0x03, self.bank_select & 0x40 != 0) {
+            (3, _) => return banks - 1,
+            (0, false) | (1, true) => 6,
+            (1, false) | (2, true) => 7,
+            _ => 15,
+        };
+        self.bank_data[register] as usize % banks
+    }
+    
+    /// 1KB CHR bank mapped at a PPU address ($0000-$1FFF)
+    fn get_chr_bank(&self, addr: Word) -> usize {
+        let addr = if self.bank_select & 0x80 != 0 { addr ^ 0x1000 } else { addr };
+        let slot = ((addr >> 10) & 0x07) as usize;
+        let bank = match slot {
+            // 1KB mode: R0, R8, R1, R9
+            0..=3 if self.bank_select & 0x20 != 0 => self.bank_data[[0, 8, 1, 9][slot]],
+            0..=3 => (self.bank_data[slot / 2] & 0xFE) | (slot as u8 & 1),
+            _ => self.bank_data[slot - 2],
+        };
+        bank as usize
+    }
+    
+    fn chr_offset_of(&self, addr: Word) -> usize {
+        (self.get_chr_bank(addr) * 1024 + (addr & 0x03FF) as usize) % self.cartridge.chr_rom.len()
+    }
+    
+    /// Clock the IRQ counter
+    ///
+    /// After a $C001 write the counter reloads one higher than the latch
+    /// (two higher for latches above 1), so the first IRQ comes a line late.
+    fn clock_irq_counter(&mut self) {
+        if self.irq_reload {
+            self.irq_counter = self.irq_latch.wrapping_add(if self.irq_latch <= 1 { 1 } else { 2 });
+            self.irq_reload = false;
+        } else if self.irq_counter == 0 {
+            self.irq_counter = self.irq_latch.wrapping_add(1);
+        }
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Rambo1Mapper {
+    fn read_prg(&self, addr: Word) -> RnesResult<Byte> {
+        match addr {
+            // No PRG RAM
+            0x6000..=0x7FFF => Ok(open_bus(addr)),
+            _ => self.prg_rom_offset(addr)
+                .map(|offset| self.cartridge.prg_rom[offset])
+                .ok_or(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+    }
+    
+    fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        let odd = addr & 1 != 0;
+        match addr {
+            0x6000..=0x7FFF => {}
+            0x8000..=0x9FFF if odd => self.bank_data[(self.bank_select & 0x0F) as usize] = value,
+            0x8000..=0x9FFF => self.bank_select = value,
+            0xA000..=0xBFFF if odd => {}
+            0xA000..=0xBFFF => {
+                self.mirroring = if value & 0x01 != 0 {
+                    rnes_cartridge::Mirroring::Horizontal
+                } else {
+                    rnes_cartridge::Mirroring::Vertical
+                };
+            }
+            0xC000..=0xDFFF if odd => {
+                self.irq_cpu_mode = value & 0x01 != 0;
+                self.irq_prescaler = 0;
+                self.irq_reload = true;
+            }
+            0xC000..=0xDFFF => self.irq_latch = value,
+            0xE000..=0xFFFF if odd => self.irq_enabled = true,
+            0xE000..=0xFFFF => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            _ => return Err(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+        Ok(())
+    }
+    
+    fn read_chr(&mut self, addr: Word) -> RnesResult<Byte> {
+        Ok(self.cartridge.chr_rom[self.chr_offset_of(addr)])
+    }
+    
+    fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        if self.cartridge.chr_is_ram {
+            let offset = self.chr_offset_of(addr);
+            self.cartridge.chr_rom[offset] = value;
+        }
+        Ok(())
+    }
+    
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Rambo1(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Rambo1(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self { cartridge: core::mem::take(&mut self.cartridge), ..state.into_owned() };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some((self.get_prg_bank(addr) * 8192 + (addr & 0x1FFF) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some(self.chr_offset_of(addr))
+    }
+    
+    fn mirroring(&self) -> rnes_cartridge::Mirroring {
+        self.mirroring
+    }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+    
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+    
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+    
+    fn step(&mut self) {
+        if !self.last_a12 {
+            self.a12_low_dots = self.a12_low_dots.saturating_add(1);
+        }
+    }
+    
+    fn clock_cpu_cycle(&mut self) {
+        if self.irq_cpu_mode {
+            self.irq_prescaler = (self.irq_prescaler + 1) & 0x03;
+            if self.irq_prescaler == 0 {
+                self.clock_irq_counter();
+            }
+        }
+    }
+    
+    fn ppu_address_changed(&mut self, addr: Word) {
+        let current_a12 = (addr & 0x1000) != 0;
+        if current_a12 && !self.last_a12 && self.a12_low_dots >= MMC3_A12_FILTER_DOTS && !self.irq_cpu_mode {
+            self.clock_irq_counter();
+        }
+        if !current_a12 && self.last_a12 {
+            self.a12_low_dots = 0;
+        }
+        self.last_a12 = current_a12;
+    }
+}
+
+/// Konami VRC7 Mapper (Mapper 85)
+///
+/// Features:
+/// - Three 8KB PRG ROM banks (switchable) and a fixed last bank
+/// - Eight 1KB CHR banks
+/// - 8KB PRG RAM, battery backed on Lagrange Point
+/// - VRC-style IRQ counter clocked by scanlines or CPU cycles
+/// - OPLL FM expansion audio (six channels)
+///
+/// VRC7a (Lagrange Point) decodes its second registers with A4 and VRC7b
+/// (Tiny Toon Adventures 2) with A3; both are accepted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Vrc7Mapper {
+    #[serde(skip)]
+    cartridge: Cartridge,
+    #[serde(skip)]
+    prg_ram: Vec<Byte>,
+    prg_ram_enabled: bool,
+    prg_ram_dirty: bool,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    mirroring: rnes_cartridge::Mirroring,
+    
+    // IRQ counter
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_prescaler: i16,
+    irq_enabled: bool,
+    irq_enable_after_ack: bool,
+    irq_cycle_mode: bool,
+    irq_pending: bool,
+    
+    // Expansion audio, boxed so saved mapper states stay small
+    opll: Box<Opll>,
+    audio_silenced: bool,
+}
+
+/// PPU dots per scanline; the VRC IRQ prescaler counts them down 3 per CPU cycle
+const VRC_IRQ_PRESCALER: i16 = 341;
+
+impl Vrc7Mapper {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mirroring = cartridge.mirroring();
+        Self {
+            cartridge,
+            prg_ram: vec![0; 8192],
+            prg_ram_enabled: false,
+            prg_ram_dirty: false,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            mirroring,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_prescaler: VRC_IRQ_PRESCALER,
+            irq_enabled: false,
+            irq_enable_after_ack: false,
+            irq_cycle_mode: false,
+            irq_pending: false,
+            opll: Box::new(Opll::new()),
+            audio_silenced: false,
+        }
+    }
+    
+    /// Get the FM sound chip
+    pub fn opll(&self) -> &Opll {
+        &self.opll
+    }
+    
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+    
+    fn write_irq_control(&mut self, value: Byte) {
+        self.irq_enable_after_ack = value & 0x01 != 0;
+        self.irq_enabled = value & 0x02 != 0;
+        self.irq_cycle_mode = value & 0x04 != 0;
+        self.irq_pending = false;
+        if self.irq_enabled {
+            self.irq_counter = self.irq_latch;
+            self.irq_prescaler = VRC_IRQ_PRESCALER;
+        }
+    }
+    
+    fn write_control(&mut self, value: Byte) {
+        self.mirroring = match value & 0x03 {
+            0 => rnes_cartridge::Mirroring::Vertical,
+            1 => rnes_cartridge::Mirroring::Horizontal,
+            2 => rnes_cartridge::Mirroring::SingleScreenA,
+            _ => rnes_cartridge::Mirroring::SingleScreenB,
+        };
+        // Bit 6 holds the sound chip in reset
+        self.audio_silenced = value & 0x40 != 0;
+        if self.audio_silenced {
+            self.opll.reset();
+        }
+        self.prg_ram_enabled = value & 0x80 != 0;
+    }
+    
+    fn chr_offset_of(&self, addr: Word) -> usize {
+        let bank = self.chr_banks[((addr >> 10) & 0x07) as usize] as usize;
+        (bank * 1024 + (addr & 0x03FF) as usize) % self.cartridge.chr_rom.len()
+    }
+}
+
+impl Mapper for Vrc7Mapper {
+    fn read_prg(&self, addr: Word) -> RnesResult<Byte> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => Ok(self.prg_ram[(addr - 0x6000) as usize]),
+            0x6000..=0x7FFF => Ok(open_bus(addr)),
+            _ => self.prg_rom_offset(addr)
+                .map(|offset| self.cartridge.prg_rom[offset])
+                .ok_or(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+    }
+    
+    fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        let second = addr & 0x18 != 0;
+        match addr {
+            0x6000..=0x7FFF => {
+                let ram_addr = (addr - 0x6000) as usize;
+                if self.prg_ram_enabled && self.prg_ram[ram_addr] != value {
+                    self.prg_ram[ram_addr] = value;
+                    self.prg_ram_dirty = true;
+                }
+            }
+            0x8000..=0x8FFF => self.prg_banks[second as usize] = value & 0x3F,
+            0x9000..=0x9FFF => match addr & 0x30 {
+                0x10 => self.opll.write_address(value),
+                0x30 if !self.audio_silenced => self.opll.write_data(value),
+                0x00 | 0x20 if !second => self.prg_banks[2] = value & 0x3F,
+                _ => {}
+            },
+            0xA000..=0xDFFF => {
+                let register = (((addr - 0xA000) >> 12) * 2) as usize + second as usize;
+                self.chr_banks[register] = value;
+            }
+            0xE000..=0xEFFF if second => self.irq_latch = value,
+            0xE000..=0xEFFF => self.write_control(value),
+            0xF000..=0xFFFF if second => {
+                self.irq_pending = false;
+                self.irq_enabled = self.irq_enable_after_ack;
+            }
+            0xF000..=0xFFFF => self.write_irq_control(value),
+            _ => return Err(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+        Ok(())
+    }
+    
+    fn read_chr(&mut self, addr: Word) -> RnesResult<Byte> {
+        Ok(self.cartridge.chr_rom[self.chr_offset_of(addr)])
+    }
+    
+    fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        if self.cartridge.chr_is_ram {
+            let offset = self.chr_offset_of(addr);
+            self.cartridge.chr_rom[offset] = value;
+        }
+        Ok(())
+    }
+    
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Vrc7(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Vrc7(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self {
+            cartridge: core::mem::take(&mut self.cartridge),
+            prg_ram: core::mem::take(&mut self.prg_ram),
+            ..state.into_owned()
+        };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        let banks = (self.cartridge.prg_rom.len() / 8192).max(1);
+        let bank = match addr {
+            0x8000..=0xDFFF => self.prg_banks[((addr - 0x8000) >> 13) as usize] as usize % banks,
+            0xE000..=0xFFFF => banks - 1,
+            _ => return None,
+        };
+        Some((bank * 8192 + (addr & 0x1FFF) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some(self.chr_offset_of(addr))
+    }
+    
+    fn mirroring(&self) -> rnes_cartridge::Mirroring {
+        self.mirroring
+    }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+    
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+    
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+    
+    fn clock_cpu_cycle(&mut self) {
+        self.opll.clock();
+        if !self.irq_enabled {
+            return;
+        }
+        if self.irq_cycle_mode {
+            self.clock_irq_counter();
+        } else {
+            // Scanline mode: one clock per 341 PPU dots
+            self.irq_prescaler -= 3;
+            if self.irq_prescaler <= 0 {
+                self.irq_prescaler += VRC_IRQ_PRESCALER;
+                self.clock_irq_counter();
+            }
+        }
+    }
+    
+    fn get_prg_ram(&self) -> Option<&[Byte]> {
+        Some(&self.prg_ram)
+    }
+    
+    fn get_prg_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.prg_ram)
+    }
+    
+    fn prg_ram_dirty(&self) -> bool {
+        self.prg_ram_dirty
+    }
+    
+    fn clear_prg_ram_dirty(&mut self) {
+        self.prg_ram_dirty = false;
+    }
+    
+    fn has_battery(&self) -> bool {
+        self.cartridge.has_battery()
+    }
+    
+    fn expansion_chip(&self) -> Option<ExpansionChip> {
+        Some(ExpansionChip::Vrc7)
+    }
+    
+    fn expansion_audio(&self) -> f32 {
+        self.opll.output() / opll::OPLL_CHANNELS as f32
+    }
+}
+
+/// Board variant of the Namco 108 family
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Namco108Board {
+    /// Mapper 206 (DxROM, Namco 108): plain banking, hardwired mirroring
+    Namco108,
+    /// Mapper 88 (NAMCOT-3443): 128KB CHR, $0000-$0FFF from the first half and $1000-$1FFF from the second
+    Namcot3443,
+    /// Mapper 154 (NAMCOT-3453): as mapper 88, with bit 6 of every write selecting a one-screen nametable
+    Namcot3453,
+    /// Mapper 95 (NAMCOT-3425): bit 5 of R0/R1 drives CIRAM A10 for the upper/lower nametables
+    Namcot3425,
+}
+
+impl Namco108Board {
+    /// Board for an iNES mapper number, if it belongs to the family
+    pub fn from_mapper(mapper: u8) -> Option<Self> {
+        match mapper {
+            206 => Some(Self::Namco108),
+            88 => Some(Self::Namcot3443),
+            154 => Some(Self::Namcot3453),
+            95 => Some(Self::Namcot3425),
+            _ => None,
+        }
+    }
+}
+
+/// Namco 108 Mapper (Mapper 206, plus 88, 95 and 154)
+///
+/// The MMC3's predecessor, used by early Namco and Tengen releases:
+/// - $8000/$8001 bank select and data, decoded like MMC3 mode 0
+/// - 8KB PRG banks (R6/R7, 4 bits) with the last 16KB fixed
+/// - 2KB + 1KB CHR banks (6 bits)
+/// - No PRG RAM, IRQ or mode bits; registers at $A000-$FFFF are absent
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Namco108Mapper {
+    #[serde(skip)]
+    cartridge: Cartridge,
+    board: Namco108Board,
+    bank_select: u8,
+    bank_data: [u8; 8],
+    mirroring: rnes_cartridge::Mirroring,
+}
+
+impl Namco108Mapper {
+    pub fn new(cartridge: Cartridge, board: Namco108Board) -> Self {
+        let mirroring = match board {
+            Namco108Board::Namcot3453 | Namco108Board::Namcot3425 => rnes_cartridge::Mirroring::SingleScreenA,
+            _ => cartridge.mirroring(),
+        };
+        Self {
+            cartridge,
+            board,
+            bank_select: 0,
+            bank_data: [0; 8],
+            mirroring,
+        }
+    }
+    
+    /// Get board variant
+    pub fn board(&self) -> Namco108Board {
+        self.board
+    }
+    
+    fn write_bank_data(&mut self, value: Byte) {
+        let register = (self.bank_select & 0x07) as usize;
+        self.bank_data[register] = match register {
+            6 | 7 => value & 0x0F,
+            _ => value & 0x3F,
+        };
+        if self.board == Namco108Board::Namcot3425 && register < 2 {
+            self.update_nametables();
+        }
+    }
+    
+    /// Mapper 95 wires CHR A15 to CIRAM A10
+    ///
+    /// Nametable fetches at $2000-$27FF decode like CHR $0000-$07FF (R0)
+    /// and $2800-$2FFF like $0800-$0FFF (R1). Upper nametable B over
+    /// lower A has no `Mirroring` equivalent and falls back to horizontal.
+    fn update_nametables(&mut self) {
+        let upper = self.bank_data[0] & 0x20 != 0;
+        let lower = self.bank_data[1] & 0x20 != 0;
+        self.mirroring = match (upper, lower) {
+            (false, false) => rnes_cartridge::Mirroring::SingleScreenA,
+            (true, true) => rnes_cartridge::Mirroring::SingleScreenB,
+            _ => rnes_cartridge::Mirroring::Horizontal,
+        };
+    }
+    
+    fn get_prg_bank(&self, addr: Word) -> usize {
+        mmc3_prg_bank(0, &self.bank_data, self.cartridge.prg_rom.len(), addr)
+    }
+    
+    fn get_chr_bank(&self, addr: Word) -> usize {
+        let bank = mmc3_chr_bank(0, &self.bank_data, addr);
+        let bank = match self.board {
+            Namco108Board::Namco108 => bank,
+            // CHR A16 follows PPU A12
+            Namco108Board::Namcot3443 | Namco108Board::Namcot3453 => {
+                if addr & 0x1000 != 0 { bank | 0x40 } else { bank & 0x3F }
+            }
+            // Bit 5 went to the nametables
+            Namco108Board::Namcot3425 => bank & 0x1F,
+        };
+        bank as usize
+    }
+    
+    fn chr_offset_of(&self, addr: Word) -> usize {
+        (self.get_chr_bank(addr) * 1024 + (addr & 0x03FF) as usize) % self.cartridge.chr_rom.len()
+    }
+}
+
+impl Mapper for Namco108Mapper {
+    fn read_prg(&self, addr: Word) -> RnesResult<Byte> {
+        match addr {
+            // No PRG RAM
+            0x6000..=0x7FFF => Ok(open_bus(addr)),
+            _ => self.prg_rom_offset(addr)
+                .map(|offset| self.cartridge.prg_rom[offset])
+                .ok_or(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+    }
+    
+    fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        match addr {
+            0x6000..=0x7FFF => Ok(()),
+            0x8000..=0xFFFF => {
+                if self.board == Namco108Board::Namcot3453 {
+                    self.mirroring = if value & 0x40 != 0 {
+                        rnes_cartridge::Mirroring::SingleScreenB
+                    } else {
+                        rnes_cartridge::Mirroring::SingleScreenA
+                    };
+                }
+                match addr {
+                    0x8000..=0x9FFF if addr & 1 == 0 => self.bank_select = value & 0x07,
+                    0x8000..=0x9FFF => self.write_bank_data(value),
+                    _ => {}
+                }
+                Ok(())
+            }
+            _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
+        }
+    }
+    
+    fn read_chr(&mut self, addr: Word) -> RnesResult<Byte> {
+        Ok(self.cartridge.chr_rom[self.chr_offset_of(addr)])
+    }
+    
+    fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        if self.cartridge.chr_is_ram {
+            let offset = self.chr_offset_of(addr);
+            self.cartridge.chr_rom[offset] = value;
+        }
+        Ok(())
+    }
+    
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Namco108(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Namco108(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self { cartridge: core::mem::take(&mut self.cartridge), ..state.into_owned() };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        Some((self.get_prg_bank(addr) * 8192 + (addr & 0x1FFF) as usize) % self.cartridge.prg_rom.len())
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some(self.chr_offset_of(addr))
+    }
+    
+    fn mirroring(&self) -> rnes_cartridge::Mirroring {
+        self.mirroring
+    }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+}
+
+/// Where one MMC5 nametable quadrant comes from ($5105)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mmc5Nametable {
+    Ciram(u8),
+    ExRam,
+    Fill,
+}
+
+/// Nintendo MMC5 Mapper (Mapper 5)
+///
+/// Features:
+/// - Four PRG modes (one 32KB bank down to four 8KB banks), each bank
+///   below $E000 selecting ROM or up to 64KB of PRG RAM
+/// - Four CHR modes (8KB down to 1KB), with a separate background set
+///   while 8x16 sprites are enabled
+/// - 1KB ExRAM used as a nametable, extended attributes (per-tile 4KB CHR
+///   bank and palette) or CPU RAM
+/// - Nametable quadrants mapped to CIRAM A/B, ExRAM or the fill tile
+/// - Vertical split screen from ExRAM with its own scroll and CHR bank
+/// - Scanline IRQ and an 8x8 unsigned multiplier
+///
+/// The mapper follows rendering through `ppu_read`, and snoops $2000/$2001
+/// for the sprite size and rendering state. Expansion audio isn't emulated.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Mmc5Mapper {
+    #[serde(skip)]
+    cartridge: Cartridge,
+    #[serde(skip)]
+    prg_ram: Vec<Byte>,
+    prg_ram_dirty: bool,
+    prg_mode: u8,
+    chr_mode: u8,
+    prg_ram_protect: [u8; 2],
+    // $5113-$5117
+    prg_banks: [u8; 5],
+    // $5120-$512B, with the $5130 upper bits from when they were written
+    chr_banks: [u16; 12],
+    chr_upper: u8,
+    background_set_written_last: bool,
+    
+    exram: Vec<Byte>,
+    exram_mode: u8,
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    
+    // Vertical split
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+    
+    // Scanline IRQ; the status flag is cleared by reading $5204
+    irq_target: u8,
+    irq_enabled: bool,
+    irq_status: Cell<bool>,
+    in_frame: bool,
+    scanline: u8,
+    
+    multiplicand: u8,
+    multiplier: u8,
+    
+    // Snooped PPU state
+    large_sprites: bool,
+    
+    // Background tile being fetched, latched at its nametable fetch
+    tile_split: bool,
+    tile_attribute: u8,
+    tile_split_row: u8,
+    tile_split_column: u8,
+    tile_split_fine_y: u8,
+}
+
+impl Mmc5Mapper {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            cartridge,
+            prg_ram: vec![0; 65536],
+            prg_ram_dirty: false,
+            prg_mode: 3,
+            chr_mode: 3,
+            prg_ram_protect: [0; 2],
+            prg_banks: [0, 0, 0, 0, 0xFF],
+            chr_banks: [0; 12],
+            chr_upper: 0,
+            background_set_written_last: false,
+            exram: vec![0; 1024],
+            exram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            irq_target: 0,
+            irq_enabled: false,
+            irq_status: Cell::new(false),
+            in_frame: false,
+            scanline: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            large_sprites: false,
+            tile_split: false,
+            tile_attribute: 0,
+            tile_split_row: 0,
+            tile_split_column: 0,
+            tile_split_fine_y: 0,
+        }
+    }
+    
+    /// Get ExRAM
+    pub fn exram(&self) -> &[Byte] {
+        &self.exram
+    }
+    
+    /// Check if the PPU is rendering a visible line, as the MMC5 sees it
+    pub fn in_frame(&self) -> bool {
+        self.in_frame
+    }
+    
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect[0] & 0x03 == 0x02 && self.prg_ram_protect[1] & 0x03 == 0x01
+    }
+    
+    /// ROM flag and byte offset of a CPU address in $6000-$FFFF
+    fn prg_target(&self, addr: Word) -> (bool, usize) {
+        if addr < 0x8000 {
+            let bank = (self.prg_banks[0] & 0x07) as usize;
+            return (false, bank * 8192 + (addr & 0x1FFF) as usize);
+        }
+        // (register, bank size in 8KB units) for each PRG mode
+        let slot = ((addr - 0x8000) >> 13) as usize;
+        let (register, size) = match (self.prg_mode, slot) {
+            (0, _) => (4, 4),
+            (1, 0 | 1) => (2, 2),
+            (1, _) => (4, 2),
+            (2, 0 | 1) => (2, 2),
+            (2, 2) => (3, 1),
+            (2, _) => (4, 1),
+            (_, slot) => (slot + 1, 1),
+        };
+        let value = self.prg_banks[register];
+        let bank = (value & 0x7F & !(size - 1)) as usize | (slot & (size as usize - 1));
+        let rom = register == 4 || value & 0x80 != 0;
+        if rom {
+            let banks = (self.cartridge.prg_rom.len() / 8192).max(1);
+            (true, (bank % banks) * 8192 + (addr & 0x1FFF) as usize)
+        } else {
+            (false, (bank & 0x07) * 8192 + (addr & 0x1FFF) as usize)
+        }
+    }
+    
+    /// CHR offset of a pattern address in the sprite (A) or background (B) set
+    fn chr_offset_in(&self, addr: Word, background_set: bool) -> usize {
+        let register = match (self.chr_mode, background_set) {
+            (0, false) => 7,
+            (1, false) => ((addr >> 12) * 4 + 3) as usize,
+            (2, false) => ((addr >> 11) * 2 + 1) as usize,
+            (_, false) => (addr >> 10) as usize,
+            (0 | 1, true) => 11,
+            (2, true) => 8 + (((addr >> 11) & 0x01) * 2 + 1) as usize,
+            (_, true) => 8 + ((addr >> 10) & 0x03) as usize,
+        };
+        let size = 1024usize << (3 - self.chr_mode);
+        let offset = self.chr_banks[register] as usize * size + (addr as usize & (size - 1));
+        offset % self.cartridge.chr_rom.len()
+    }
+    
+    /// CHR offset outside background fetches: the sprite set with 8x16
+    /// sprites, otherwise whichever set was written last
+    fn chr_offset_of(&self, addr: Word) -> usize {
+        self.chr_offset_in(addr, self.background_set_written_last && !self.large_sprites)
+    }
+    
+    /// Offset into a 4KB CHR bank, for extended attributes and the split
+    fn chr_offset_4k(&self, bank: usize, addr: Word) -> usize {
+        (bank * 4096 + (addr & 0x0FFF) as usize) % self.cartridge.chr_rom.len()
+    }
+    
+    fn nametable_source(&self, addr: Word) -> Mmc5Nametable {
+        let quadrant = (addr >> 10) & 0x03;
+        match (self.nametable_mapping >> (quadrant * 2)) & 0x03 {
+            0 => Mmc5Nametable::Ciram(0),
+            1 => Mmc5Nametable::Ciram(1),
+            2 => Mmc5Nametable::ExRam,
+            _ => Mmc5Nametable::Fill,
+        }
+    }
+    
+    /// ExRAM as the PPU sees it: zeros unless it is a nametable or attributes
+    fn exram_for_ppu(&self, index: usize) -> Byte {
+        if self.exram_mode <= 1 { self.exram[index & 0x03FF] } else { 0 }
+    }
+    
+    /// Nametable or attribute byte from the $5105 mapping, `None` for CIRAM
+    fn mapped_nametable(&self, addr: Word) -> Option<Byte> {
+        match self.nametable_source(addr) {
+            Mmc5Nametable::Ciram(_) => None,
+            Mmc5Nametable::ExRam => Some(self.exram_for_ppu(addr as usize)),
+            Mmc5Nametable::Fill if addr & 0x03FF >= 0x03C0 => Some((self.fill_attribute & 0x03) * 0x55),
+            Mmc5Nametable::Fill => Some(self.fill_tile),
+        }
+    }
+    
+    /// Count a rendered line at its first tile fetch
+    fn detect_scanline(&mut self, line: Scanline) {
+        if line < 0 {
+            return;
+        }
+        if !self.in_frame {
+            self.in_frame = true;
+            self.scanline = 0;
+            return;
+        }
+        self.scanline = self.scanline.wrapping_add(1);
+        if self.scanline == self.irq_target && self.irq_target != 0 {
+            self.irq_status.set(true);
+        }
+    }
+    
+    /// Whether tile `column` of a line falls in the split region
+    fn in_split(&self, column: u8) -> bool {
+        if self.split_control & 0x80 == 0 || self.exram_mode > 1 {
+            return false;
+        }
+        let threshold = self.split_control & 0x1F;
+        if self.split_control & 0x40 != 0 { column >= threshold } else { column < threshold }
+    }
+    
+    fn fetch_nametable(&mut self, addr: Word, line: Scanline, column: u8) -> Option<Byte> {
+        if column == 2 {
+            self.detect_scanline(line);
+        }
+        
+        self.tile_split = line >= 0 && self.in_split(column);
+        if self.tile_split {
+            let y = (line as u16 + self.split_scroll as u16) % 240;
+            self.tile_split_row = (y / 8) as u8;
+            self.tile_split_column = column & 0x1F;
+            self.tile_split_fine_y = (y % 8) as u8;
+            let index = self.tile_split_row as usize * 32 + self.tile_split_column as usize;
+            return Some(self.exram[index]);
+        }
+        
+        if self.exram_mode == 1 {
+            self.tile_attribute = self.exram[(addr & 0x03FF) as usize];
+        }
+        self.mapped_nametable(addr)
+    }
+    
+    fn fetch_attribute(&self, addr: Word) -> Option<Byte> {
+        if self.tile_split {
+            let (row, column) = (self.tile_split_row as usize, self.tile_split_column as usize);
+            let byte = self.exram[0x03C0 + (row / 4) * 8 + column / 4];
+            let shift = ((row & 0x02) << 1) | (column & 0x02);
+            // Replicated so whichever quadrant the PPU picks gets the palette
+            return Some(((byte >> shift) & 0x03) * 0x55);
+        }
+        if self.exram_mode == 1 {
+            return Some((self.tile_attribute >> 6) * 0x55);
+        }
+        self.mapped_nametable(addr)
+    }
+    
+    fn fetch_background_pattern(&self, addr: Word) -> Byte {
+        let offset = if self.tile_split {
+            let addr = (addr & 0x0FF8) | self.tile_split_fine_y as Word;
+            self.chr_offset_4k(self.split_bank as usize, addr)
+        } else if self.exram_mode == 1 {
+            let bank = (self.tile_attribute & 0x3F) as usize | (self.chr_upper as usize) << 6;
+            self.chr_offset_4k(bank, addr)
+        } else if self.large_sprites {
+            self.chr_offset_in(addr, true)
+        } else {
+            self.chr_offset_of(addr)
+        };
+        self.cartridge.chr_rom[offset]
+    }
+    
+    fn write_register(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x5100 => self.prg_mode = value & 0x03,
+            0x5101 => self.chr_mode = value & 0x03,
+            0x5102 | 0x5103 => self.prg_ram_protect[(addr - 0x5102) as usize] = value,
+            0x5104 => self.exram_mode = value & 0x03,
+            0x5105 => self.nametable_mapping = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_attribute = value & 0x03,
+            0x5113..=0x5117 => self.prg_banks[(addr - 0x5113) as usize] = value,
+            0x5120..=0x512B => {
+                self.chr_banks[(addr - 0x5120) as usize] = value as u16 | (self.chr_upper as u16) << 8;
+                self.background_set_written_last = addr >= 0x5128;
+            }
+            0x5130 => self.chr_upper = value & 0x03,
+            0x5200 => self.split_control = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_bank = value,
+            0x5203 => self.irq_target = value,
+            0x5204 => {
+                self.irq_enabled = value & 0x80 != 0;
+            }
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            0x5C00..=0x5FFF if self.exram_mode != 3 => self.exram[(addr - 0x5C00) as usize] = value,
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mmc5Mapper {
+    fn read_prg(&self, addr: Word) -> RnesResult<Byte> {
+        let product = self.multiplicand as u16 * self.multiplier as u16;
+        match addr {
+            0x5204 => {
+                let status = (self.irq_status.replace(false) as Byte) << 7 | (self.in_frame as Byte) << 6;
+                Ok(status)
+            }
+            0x5205 => Ok(product as Byte),
+            0x5206 => Ok((product >> 8) as Byte),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => Ok(self.exram[(addr - 0x5C00) as usize]),
+            0x4020..=0x5FFF => Ok(open_bus(addr)),
+            0x6000..=0xFFFF => {
+                let (rom, offset) = self.prg_target(addr);
+                Ok(if rom { self.cartridge.prg_rom[offset] } else { self.prg_ram[offset] })
+            }
+            _ => Err(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+    }
+    
+    fn write_prg(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        match addr {
+            0x5000..=0x5FFF => self.write_register(addr, value),
+            0x6000..=0xFFFF => {
+                let (rom, offset) = self.prg_target(addr);
+                if !rom && self.prg_ram_writable() && self.prg_ram[offset] != value {
+                    self.prg_ram[offset] = value;
+                    self.prg_ram_dirty = true;
+                }
+            }
+            _ => return Err(rnes_common::RnesError::MemoryAccess { address: addr }),
+        }
+        Ok(())
+    }
+    
+    fn read_chr(&mut self, addr: Word) -> RnesResult<Byte> {
+        Ok(self.cartridge.chr_rom[self.chr_offset_of(addr)])
+    }
+    
+    fn write_chr(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        if self.cartridge.chr_is_ram {
+            let offset = self.chr_offset_of(addr);
+            self.cartridge.chr_rom[offset] = value;
+        }
+        Ok(())
+    }
+    
+    fn ppu_read(&mut self, addr: Word, fetch: PpuFetch) -> Option<Byte> {
+        match fetch {
+            PpuFetch::Nametable { line, column } => self.fetch_nametable(addr, line, column),
+            PpuFetch::Attribute => self.fetch_attribute(addr),
+            PpuFetch::BackgroundPattern => Some(self.fetch_background_pattern(addr)),
+            PpuFetch::Data if addr >= 0x2000 => self.mapped_nametable(addr),
+            // Sprites and CPU accesses go through `read_chr`
+            PpuFetch::Data | PpuFetch::SpritePattern => None,
+        }
+    }
+    
+    fn ppu_write_nametable(&mut self, addr: Word, value: Byte) -> bool {
+        match self.nametable_source(addr) {
+            Mmc5Nametable::Ciram(_) => false,
+            Mmc5Nametable::ExRam => {
+                if self.exram_mode <= 1 {
+                    self.exram[(addr & 0x03FF) as usize] = value;
+                }
+                true
+            }
+            Mmc5Nametable::Fill => true,
+        }
+    }
+    
+    fn ciram_page(&self, addr: Word) -> Option<u8> {
+        match self.nametable_source(addr) {
+            Mmc5Nametable::Ciram(page) => Some(page),
+            _ => Some(0),
+        }
+    }
+    
+    fn ppu_register_written(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x2000 => self.large_sprites = value & 0x20 != 0,
+            0x2001 if value & 0x18 == 0 => self.in_frame = false,
+            _ => {}
+        }
+    }
+    
+    fn notify_scanline(&mut self, scanline: Scanline) {
+        // The MMC5 sees no fetches after the last visible line
+        if scanline >= 240 {
+            self.in_frame = false;
+        }
+    }
+    
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+    
+    fn save_state(&self) -> MapperState<'_> {
+        MapperState::Mmc5(Cow::Borrowed(self))
+    }
+    
+    fn load_state(&mut self, state: MapperState<'_>) -> RnesResult<()> {
+        state.validate()?;
+        let MapperState::Mmc5(state) = state else {
+            return Err(MapperState::mismatch());
+        };
+        *self = Self {
+            cartridge: core::mem::take(&mut self.cartridge),
+            prg_ram: core::mem::take(&mut self.prg_ram),
+            ..state.into_owned()
+        };
+        Ok(())
+    }
+    
+    fn prg_rom_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.cartridge.prg_rom)
+    }
+    
+    fn prg_rom_offset(&self, addr: Word) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+        match self.prg_target(addr) {
+            (true, offset) => Some(offset),
+            (false, _) => None,
+        }
+    }
+    
+    fn chr_offset(&self, addr: Word) -> Option<usize> {
+        Some(self.chr_offset_of(addr))
+    }
+    
+    fn mirroring(&self) -> rnes_cartridge::Mirroring {
+        match self.nametable_mapping {
+            0x00 => rnes_cartridge::Mirroring::SingleScreenA,
+            0x55 => rnes_cartridge::Mirroring::SingleScreenB,
+            0x50 => rnes_cartridge::Mirroring::Horizontal,
+            _ => rnes_cartridge::Mirroring::Vertical,
+        }
+    }
+    
+    fn get_chr_ram(&self) -> Option<&[Byte]> {
+        self.cartridge.chr_ram()
+    }
+    
+    fn get_chr_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        self.cartridge.chr_ram_mut()
+    }
+    
+    fn irq_pending(&self) -> bool {
+        self.irq_enabled && self.irq_status.get()
+    }
+    
+    fn clear_irq(&mut self) {
+        self.irq_status.set(false);
+    }
+    
+    fn get_prg_ram(&self) -> Option<&[Byte]> {
+        Some(&self.prg_ram)
+    }
+    
+    fn get_prg_ram_mut(&mut self) -> Option<&mut [Byte]> {
+        Some(&mut self.prg_ram)
+    }
+    
+    fn prg_ram_dirty(&self) -> bool {
+        self.prg_ram_dirty
+    }
+    
+    fn clear_prg_ram_dirty(&mut self) {
+        self.prg_ram_dirty = false;
+    }
+    
+    fn has_battery(&self) -> bool {
+        self.cartridge.has_battery()
+    }
+}
+
+/// User overrides applied when creating a mapper
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapperOptions {
+    /// Force an MMC3 IRQ revision instead of deriving it from the submapper
+    pub mmc3_irq_revision: Option<Mmc3IrqRevision>,
+}
+
+/// Create mapper from cartridge
+pub fn create_mapper(cartridge: Cartridge) -> RnesResult<Box<dyn Mapper>> {
+    create_mapper_with_options(cartridge, &MapperOptions::default())
+}
+
+/// Create mapper from cartridge with user overrides
+pub fn create_mapper_with_options(cartridge: Cartridge, options: &MapperOptions) -> RnesResult<Box<dyn Mapper>> {
+    match cartridge.mapper_number() {
+        0 => Ok(Box::new(NromMapper::new(cartridge))),
+        1 => Ok(Box::new(Mmc1Mapper::new(cartridge))),
+        2 => {
+            validate_prg_banks(&cartridge, 16384)?;
+            Ok(Box::new(UxromMapper::new(cartridge)))
+        }
+        3 => Ok(Box::new(CnromMapper::new(cartridge))),
+        185 => Ok(Box::new(CnromMapper::with_chr_protection(cartridge))),
+        7 => {
+            validate_prg_banks(&cartridge, 32768)?;
+            Ok(Box::new(AoromMapper::new(cartridge)))
+        }
+        4 => {
+            let mut mapper = Mmc3Mapper::new(cartridge);
+            if let Some(revision) = options.mmc3_irq_revision {
+                mapper = mapper.with_irq_revision(revision);
+            }
+            Ok(Box::new(mapper))
+        }
+        5 => Ok(Box::new(Mmc5Mapper::new(cartridge))),
+        64 => Ok(Box::new(Rambo1Mapper::new(cartridge))),
+        85 => Ok(Box::new(Vrc7Mapper::new(cartridge))),
+        mapper => match Namco108Board::from_mapper(mapper) {
+            Some(board) => Ok(Box::new(Namco108Mapper::new(cartridge, board))),
+            None => Err(rnes_common::RnesError::UnsupportedMapper(mapper)),
+        }
+    }
+}
+
+/// Check that PRG ROM splits into whole `bank_size` banks
+///
+/// ROMs smaller than one bank are allowed and mirror through it.
+fn validate_prg_banks(cartridge: &Cartridge, bank_size: usize) -> RnesResult<()> {
+    let len = cartridge.prg_rom.len();
+    if len > bank_size && !len.is_multiple_of(bank_size) {
+        return Err(rnes_common::RnesError::RomFormat(format!(
+            "PRG ROM of {} bytes is not a whole number of {}KB banks",
+            len,
+            bank_size / 1024,
+        )));
+    }
+    Ok(())
+}
+
+/// How well a mapper is emulated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapperSupport {
+    /// Implemented and expected to run its games
+    Supported,
+    /// Implemented, but some boards or features are missing
+    Partial,
+    /// Not implemented; ROMs using it fail to load
+    Unsupported,
+}
+
+impl core::fmt::Display for MapperSupport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            MapperSupport::Supported => "supported",
+            MapperSupport::Partial => "partial",
+            MapperSupport::Unsupported => "unsupported",
+        })
+    }
+}
+
+/// Mappers that `create_mapper` can build; everything else is unsupported
+const MAPPER_SUPPORT: &[(u8, MapperSupport)] = &[
+    (0, MapperSupport::Supported),
+    (1, MapperSupport::Supported),
+    (2, MapperSupport::Supported),
+    (3, MapperSupport::Supported),
+    (4, MapperSupport::Supported),
+    (5, MapperSupport::Partial),
+    (7, MapperSupport::Supported),
+    (64, MapperSupport::Supported),
+    (85, MapperSupport::Supported),
+    (88, MapperSupport::Supported),
+    (95, MapperSupport::Supported),
+    (154, MapperSupport::Supported),
+    (185, MapperSupport::Supported),
+    (206, MapperSupport::Supported),
+];
+
+/// Name and support status of an iNES mapper number
+///
+/// Displays as e.g. "Mapper 69 (Sunsoft FME-7) — unsupported".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapperDescription {
+    pub number: u8,
+    pub name: Option<&'static str>,
+    pub support: MapperSupport,
+}
+
+impl core::fmt::Display for MapperDescription {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Mapper {}", self.number)?;
+        if let Some(name) = self.name {
+            write!(f, " ({})", name)?;
+        }
+        write!(f, " — {}", self.support)
+    }
+}
+
+/// Look up a mapper's name and support status
+pub fn describe(mapper: u8) -> MapperDescription {
+    let support = MAPPER_SUPPORT.iter()
+        .find(|(number, _)| *number == mapper)
+        .map_or(MapperSupport::Unsupported, |&(_, support)| support);
+    MapperDescription {
+        number: mapper,
+        name: rnes_cartridge::mapper_name(mapper),
+        support,
+    }
+}
+
+/// Every mapper that has a name or is emulated, in number order
+///
+/// This is the compatibility report shown by frontends and docs.
+pub fn known_mappers() -> impl Iterator<Item = MapperDescription> {
+    (0..=u8::MAX)
+        .map(describe)
+        .filter(|description| description.name.is_some() || description.support != MapperSupport::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a cartridge whose every 16KB PRG bank is filled with its index
+    fn cartridge(mapper: u8, prg_banks: u8) -> Cartridge {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, prg_banks, 1, mapper << 4, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..prg_banks {
+            rom.extend(core::iter::repeat_n(bank, 16384));
+        }
+        rom.extend_from_slice(&[0; 8192]);
+        Cartridge::from_bytes(&rom).unwrap()
+    }
+
+    #[test]
+    fn test_bank_mask() {
+        assert_eq!(bank_mask(16384, 16384), 0);
+        assert_eq!(bank_mask(8192, 16384), 0);
+        assert_eq!(bank_mask(128 * 1024, 16384), 0x07);
+        assert_eq!(bank_mask(512 * 1024, 16384), 0x1F);
+        assert_eq!(bank_mask(48 * 1024, 16384), 0x03);
+        assert_eq!(bank_mask(256 * 1024, 32768), 0x07);
+    }
+
+    #[test]
+    fn test_uxrom_oversize_banks() {
+        // 512KB: 32 banks, so bank selects need five bits
+        let mut mapper = UxromMapper::new(cartridge(2, 32));
+        assert_eq!(mapper.read_prg(0xC000).unwrap(), 31);
+        assert_eq!(mapper.read_prg(0xFFFF).unwrap(), 31);
+        mapper.write_prg(0x8000, 20).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 20);
+        assert_eq!(mapper.read_prg(0xBFFF).unwrap(), 20);
+        // Bits above the ROM size wrap around
+        mapper.write_prg(0x8000, 0xE5).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 5);
+        assert_eq!(mapper.prg_rom_offset(0x8000), Some(5 * 16384));
+    }
+
+    #[test]
+    fn test_uxrom_fixed_bank_is_last() {
+        let mut mapper = UxromMapper::new(cartridge(2, 8));
+        assert_eq!(mapper.read_prg(0xC000).unwrap(), 7);
+        mapper.write_prg(0x8000, 0x0F).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 7);
+
+        // A single bank appears in both windows
+        let mut mapper = UxromMapper::new(cartridge(2, 1));
+        mapper.write_prg(0x8000, 0x03).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 0);
+        assert_eq!(mapper.read_prg(0xC000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_aorom_banks_stay_in_range() {
+        // 256KB: eight 32KB banks
+        let mut mapper = AoromMapper::new(cartridge(7, 16));
+        mapper.write_prg(0x8000, 0x05).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 10);
+        assert_eq!(mapper.read_prg(0xFFFF).unwrap(), 11);
+        // Bit 3 is unused on 256KB boards; bit 4 only sets mirroring
+        mapper.write_prg(0x8000, 0x1E).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 12);
+
+        // An undersized 16KB ROM mirrors into both halves
+        let mut mapper = AoromMapper::new(cartridge(7, 1));
+        mapper.write_prg(0x8000, 0x07).unwrap();
+        assert_eq!(mapper.read_prg(0x8000).unwrap(), 0);
+        assert_eq!(mapper.read_prg(0xC000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_partial_prg_banks_rejected() {
+        assert!(matches!(
+            create_mapper(cartridge(7, 3)),
+            Err(rnes_common::RnesError::RomFormat(_))
+        ));
+        assert!(create_mapper(cartridge(7, 4)).is_ok());
+        assert!(create_mapper(cartridge(2, 3)).is_ok());
+    }
+    
+    /// MMC3 cartridge with 16 8KB PRG banks and 64 1KB CHR banks, each
+    /// filled with its index
+    fn mmc3_cartridge() -> Mmc3Mapper {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 8, 8, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..16u8 {
+            rom.extend(core::iter::repeat_n(bank, 8192));
+        }
+        for bank in 0..64u8 {
+            rom.extend(core::iter::repeat_n(bank, 1024));
+        }
+        Mmc3Mapper::new(Cartridge::from_bytes(&rom).unwrap())
+    }
+    
+    /// Bank visible in each 8KB PRG window
+    fn prg_windows(mapper: &dyn Mapper) -> [u8; 4] {
+        [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| mapper.read_prg(addr).unwrap())
+    }
+    
+    /// Bank visible in each 1KB CHR window
+    fn chr_windows(mapper: &mut dyn Mapper) -> [u8; 8] {
+        [0x0000, 0x0400, 0x0800, 0x0C00, 0x1000, 0x1400, 0x1800, 0x1C00].map(|addr| mapper.read_chr(addr).unwrap())
+    }
+    
+    /// Set R0-R7 through $8000/$8001, leaving `mode` in the bank select
+    fn set_banks(mapper: &mut dyn Mapper, mode: u8, banks: [u8; 8]) {
+        for (register, bank) in banks.into_iter().enumerate() {
+            mapper.write_prg(0x8000, mode | register as u8).unwrap();
+            mapper.write_prg(0x8001, bank).unwrap();
+        }
+    }
+    
+    #[test]
+    fn test_mmc3_prg_modes() {
+        let mut mapper = mmc3_cartridge();
+        set_banks(&mut mapper, 0x00, [0, 0, 0, 0, 0, 0, 3, 5]);
+        assert_eq!(prg_windows(&mapper), [3, 5, 14, 15]);
+        
+        // Mode 1 swaps $8000 and $C000; $A000 stays R7
+        set_banks(&mut mapper, 0x40, [0, 0, 0, 0, 0, 0, 3, 5]);
+        assert_eq!(prg_windows(&mapper), [14, 5, 3, 15]);
+        
+        // The top two bits of R6/R7 are ignored, and selects wrap to the ROM size
+        set_banks(&mut mapper, 0x00, [0, 0, 0, 0, 0, 0, 0xC4, 0x13]);
+        assert_eq!(prg_windows(&mapper), [4, 3, 14, 15]);
+        assert_eq!(mapper.prg_rom_offset(0xA000), Some(3 * 8192));
+    }
+    
+    #[test]
+    fn test_mmc3_chr_modes() {
+        let mut mapper = mmc3_cartridge();
+        
+        // 2KB banks ignore bit 0 of R0/R1
+        set_banks(&mut mapper, 0x00, [9, 12, 20, 21, 22, 23, 0, 1]);
+        assert_eq!(chr_windows(&mut mapper), [8, 9, 12, 13, 20, 21, 22, 23]);
+        
+        // A12 inversion moves the 2KB banks to $1000 and R2-R5 to $0000
+        set_banks(&mut mapper, 0x80, [9, 12, 20, 21, 22, 23, 0, 1]);
+        assert_eq!(chr_windows(&mut mapper), [20, 21, 22, 23, 8, 9, 12, 13]);
+        assert_eq!(mapper.chr_offset(0x1C00), Some(13 * 1024));
+        
+        // CHR and PRG modes are independent
+        set_banks(&mut mapper, 0xC0, [2, 4, 6, 7, 40, 63, 3, 5]);
+        assert_eq!(chr_windows(&mut mapper), [6, 7, 40, 63, 2, 3, 4, 5]);
+        assert_eq!(prg_windows(&mapper), [14, 5, 3, 15]);
+    }
+    
+    #[test]
+    fn test_mmc3_chr_ram_banking() {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.extend_from_slice(&[0; 32768]);
+        let mut mapper = Mmc3Mapper::new(Cartridge::from_bytes(&rom).unwrap());
+        
+        // 8KB of CHR RAM: bank 5 is the sixth KB, seen at $1400 without inversion
+        set_banks(&mut mapper, 0x00, [0, 2, 4, 5, 6, 7, 0, 1]);
+        mapper.write_chr(0x1400, 0xA5).unwrap();
+        mapper.write_prg(0x8000, 0x80).unwrap();
+        assert_eq!(mapper.read_chr(0x0400).unwrap(), 0xA5);
+        assert_eq!(mapper.get_chr_ram().unwrap()[5 * 1024], 0xA5);
+    }
+    
+    /// RAMBO-1 cartridge with 16 8KB PRG banks and 64 1KB CHR banks, each
+    /// filled with its index
+    fn rambo1_mapper() -> Rambo1Mapper {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 8, 8, 0x00, 0x40, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..16u8 {
+            rom.extend(core::iter::repeat_n(bank, 8192));
+        }
+        for bank in 0..64u8 {
+            rom.extend(core::iter::repeat_n(bank, 1024));
+        }
+        Rambo1Mapper::new(Cartridge::from_bytes(&rom).unwrap())
+    }
+    
+    #[test]
+    fn test_rambo1_banking() {
+        let mut mapper = rambo1_mapper();
+        set_banks(&mut mapper, 0x00, [9, 12, 20, 21, 22, 23, 3, 5]);
+        mapper.write_prg(0x8000, 0x0F).unwrap();
+        mapper.write_prg(0x8001, 7).unwrap();
+        assert_eq!(prg_windows(&mapper), [3, 5, 7, 15]);
+        assert_eq!(chr_windows(&mut mapper), [8, 9, 12, 13, 20, 21, 22, 23]);
+        
+        // PRG mode 1 rotates R15, R6, R7 through $8000-$DFFF
+        mapper.write_prg(0x8000, 0x40).unwrap();
+        assert_eq!(prg_windows(&mapper), [7, 3, 5, 15]);
+        
+        // 1KB mode interleaves R8/R9, and A12 inversion still applies
+        mapper.write_prg(0x8000, 0x08).unwrap();
+        mapper.write_prg(0x8001, 40).unwrap();
+        mapper.write_prg(0x8000, 0x09).unwrap();
+        mapper.write_prg(0x8001, 41).unwrap();
+        mapper.write_prg(0x8000, 0xA0).unwrap();
+        assert_eq!(chr_windows(&mut mapper), [20, 21, 22, 23, 9, 40, 12, 41]);
+        assert_eq!(mapper.chr_offset(0x1400), Some(40 * 1024));
+        
+        mapper.write_prg(0xA000, 0x01).unwrap();
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::Horizontal);
+    }
+    
+    #[test]
+    fn test_rambo1_cpu_cycle_irq() {
+        let mut mapper = rambo1_mapper();
+        mapper.write_prg(0xC000, 3).unwrap();
+        mapper.write_prg(0xC001, 0x01).unwrap();
+        mapper.write_prg(0xE001, 0x00).unwrap();
+        assert!(mapper.irq_cpu_mode());
+        
+        // Reloads to latch + 2, then counts down every 4 CPU cycles
+        for _ in 0..19 {
+            mapper.clock_cpu_cycle();
+        }
+        assert!(!mapper.irq_pending());
+        mapper.clock_cpu_cycle();
+        assert!(mapper.irq_pending());
+        
+        // Later periods are latch + 1 clocks
+        mapper.write_prg(0xE000, 0x00).unwrap();
+        mapper.write_prg(0xE001, 0x00).unwrap();
+        for _ in 0..15 {
+            mapper.clock_cpu_cycle();
+        }
+        assert!(!mapper.irq_pending());
+        mapper.clock_cpu_cycle();
+        assert!(mapper.irq_pending());
+        
+        // Scanline mode ignores CPU cycles and counts A12 rises
+        mapper.write_prg(0xE000, 0x00).unwrap();
+        mapper.write_prg(0xC000, 0).unwrap();
+        mapper.write_prg(0xC001, 0x00).unwrap();
+        mapper.write_prg(0xE001, 0x00).unwrap();
+        for _ in 0..16 {
+            mapper.clock_cpu_cycle();
+        }
+        assert!(!mapper.irq_pending());
+        mapper.ppu_address_changed(0x1000);
+        assert!(mapper.irq_pending());
+    }
+    
+    /// VRC7 cartridge with 16 8KB PRG banks and 64 1KB CHR banks, each
+    /// filled with its index
+    fn vrc7_mapper() -> Vrc7Mapper {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 8, 8, 0x50, 0x50, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..16u8 {
+            rom.extend(core::iter::repeat_n(bank, 8192));
+        }
+        for bank in 0..64u8 {
+            rom.extend(core::iter::repeat_n(bank, 1024));
+        }
+        Vrc7Mapper::new(Cartridge::from_bytes(&rom).unwrap())
+    }
+    
+    #[test]
+    fn test_vrc7_banking() {
+        let mut mapper = vrc7_mapper();
+        mapper.write_prg(0x8000, 3).unwrap();
+        mapper.write_prg(0x8010, 5).unwrap();
+        mapper.write_prg(0x9000, 7).unwrap();
+        assert_eq!(prg_windows(&mapper), [3, 5, 7, 15]);
+        
+        // VRC7b decodes its second registers with A3 instead of A4
+        mapper.write_prg(0x8008, 9).unwrap();
+        assert_eq!(mapper.read_prg(0xA000).unwrap(), 9);
+        for (register, addr) in [0xA000, 0xA010, 0xB000, 0xB008, 0xC000, 0xC010, 0xD000, 0xD018].into_iter().enumerate() {
+            mapper.write_prg(addr, 40 + register as u8).unwrap();
+        }
+        assert_eq!(chr_windows(&mut mapper), [40, 41, 42, 43, 44, 45, 46, 47]);
+        
+        // PRG RAM is off until $E000 bit 7 is set
+        assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x60);
+        mapper.write_prg(0xE000, 0x82).unwrap();
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::SingleScreenA);
+        mapper.write_prg(0x6000, 0x42).unwrap();
+        assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x42);
+        assert!(mapper.prg_ram_dirty());
+    }
+    
+    #[test]
+    fn test_vrc7_irq() {
+        let mut mapper = vrc7_mapper();
+        
+        // Cycle mode: counts up from the latch and fires when passing $FF
+        mapper.write_prg(0xE010, 0xFD).unwrap();
+        mapper.write_prg(0xF000, 0x06).unwrap();
+        mapper.clock_cpu_cycle();
+        mapper.clock_cpu_cycle();
+        assert!(!mapper.irq_pending());
+        mapper.clock_cpu_cycle();
+        assert!(mapper.irq_pending());
+        
+        // Acknowledging copies the A bit (clear here) into the enable
+        mapper.write_prg(0xF010, 0x00).unwrap();
+        for _ in 0..1000 {
+            mapper.clock_cpu_cycle();
+        }
+        assert!(!mapper.irq_pending());
+        
+        // Scanline mode: one clock per 341 PPU dots
+        mapper.write_prg(0xE010, 0xFF).unwrap();
+        mapper.write_prg(0xF000, 0x03).unwrap();
+        for _ in 0..113 {
+            mapper.clock_cpu_cycle();
+        }
+        assert!(!mapper.irq_pending());
+        mapper.clock_cpu_cycle();
+        assert!(mapper.irq_pending());
+    }
+    
+    #[test]
+    fn test_vrc7_expansion_audio() {
+        let mut mapper = vrc7_mapper();
+        assert_eq!(mapper.expansion_audio(), 0.0);
+        
+        // Instrument 3 at full volume on channel 0, keyed on
+        for (register, value) in [(0x30, 0x30), (0x10, 0x20), (0x20, 0x19)] {
+            mapper.write_prg(0x9010, register).unwrap();
+            mapper.write_prg(0x9030, value).unwrap();
+        }
+        let mut peak = 0.0f32;
+        for _ in 0..opll::OPLL_CYCLES_PER_SAMPLE * 1000 {
+            mapper.clock_cpu_cycle();
+            peak = peak.max(mapper.expansion_audio().abs());
+        }
+        assert!(peak > 0.01, "peak {}", peak);
+        assert!(peak <= 1.01 / opll::OPLL_CHANNELS as f32);
+        assert_eq!(mapper.expansion_chip(), Some(ExpansionChip::Vrc7));
+        
+        // $E000 bit 6 holds the sound chip in reset
+        mapper.write_prg(0xE000, 0x40).unwrap();
+        assert_eq!(mapper.expansion_audio(), 0.0);
+        mapper.write_prg(0x9010, 0x20).unwrap();
+        mapper.write_prg(0x9030, 0x19).unwrap();
+        for _ in 0..opll::OPLL_CYCLES_PER_SAMPLE * 100 {
+            mapper.clock_cpu_cycle();
+        }
+        assert_eq!(mapper.expansion_audio(), 0.0);
+    }
+    
+    /// Namco 108 family cartridge with 16 8KB PRG banks and 128 1KB CHR
+    /// banks, each filled with its index
+    fn namco108_mapper(mapper: u8) -> Namco108Mapper {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 8, 16, (mapper & 0x0F) << 4, mapper & 0xF0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..16u8 {
+            rom.extend(core::iter::repeat_n(bank, 8192));
+        }
+        for bank in 0..128u8 {
+            rom.extend(core::iter::repeat_n(bank, 1024));
+        }
+        Namco108Mapper::new(Cartridge::from_bytes(&rom).unwrap(), Namco108Board::from_mapper(mapper).unwrap())
+    }
+    
+    #[test]
+    fn test_namco108_banking() {
+        let mut mapper = namco108_mapper(206);
+        
+        // No mode bits: $C000 stays second-last and CHR is never inverted
+        set_banks(&mut mapper, 0xC0, [9, 12, 20, 21, 22, 0x7F, 0x13, 5]);
+        assert_eq!(prg_windows(&mapper), [3, 5, 14, 15]);
+        assert_eq!(chr_windows(&mut mapper), [8, 9, 12, 13, 20, 21, 22, 63]);
+        
+        // $A000-$FFFF has no registers and there is no IRQ
+        mapper.write_prg(0xA000, 0x01).unwrap();
+        mapper.write_prg(0xE001, 0x00).unwrap();
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::Horizontal);
+        assert!(!mapper.irq_pending());
+        assert!(create_mapper(namco108_mapper(206).cartridge).is_ok());
+    }
+    
+    #[test]
+    fn test_namcot_3443_and_3453() {
+        // $1000-$1FFF comes from the upper 64KB of CHR
+        let mut mapper = namco108_mapper(88);
+        set_banks(&mut mapper, 0x00, [9, 0x4C, 20, 21, 22, 23, 0, 1]);
+        assert_eq!(chr_windows(&mut mapper), [8, 9, 12, 13, 84, 85, 86, 87]);
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::Horizontal);
+        
+        // Mapper 154: bit 6 of any write picks the one-screen nametable
+        let mut mapper = namco108_mapper(154);
+        set_banks(&mut mapper, 0x00, [9, 12, 20, 21, 22, 23, 0, 1]);
+        assert_eq!(chr_windows(&mut mapper), [8, 9, 12, 13, 84, 85, 86, 87]);
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::SingleScreenA);
+        mapper.write_prg(0xC000, 0x40).unwrap();
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::SingleScreenB);
+        mapper.write_prg(0x8000, 0x06).unwrap();
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::SingleScreenA);
+    }
+    
+    #[test]
+    fn test_namcot_3425_nametables() {
+        let mut mapper = namco108_mapper(95);
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::SingleScreenA);
+        
+        // Bit 5 of R0/R1 selects nametables rather than CHR
+        set_banks(&mut mapper, 0x00, [0x22, 0x24, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::SingleScreenB);
+        assert_eq!(chr_windows(&mut mapper)[..4], [2, 3, 4, 5]);
+        set_banks(&mut mapper, 0x00, [0x02, 0x24, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(mapper.mirroring(), rnes_cartridge::Mirroring::Horizontal);
+    }
+    
+    /// CNROM-style cartridge with 32KB PRG and `chr_banks` 8KB CHR banks,
+    /// each filled with its index
+    fn cnrom_cartridge(mapper: u8, submapper: u8, chr_banks: u8) -> Cartridge {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, chr_banks, (mapper & 0x0F) << 4, mapper & 0xF0, submapper << 4, 0, 0, 0, 0, 0, 0, 0];
+        if submapper != 0 {
+            // NES 2.0, so the submapper is read
+            rom[7] |= 0x08;
+        }
+        rom.extend_from_slice(&[0; 32768]);
+        for bank in 0..chr_banks {
+            rom.extend(core::iter::repeat_n(bank + 1, 8192));
+        }
+        Cartridge::from_bytes(&rom).unwrap()
+    }
+    
+    #[test]
+    fn test_cnrom_chr_mask_follows_chr_size() {
+        // Two banks: only bit 0 selects
+        let mut mapper = CnromMapper::new(cnrom_cartridge(3, 0, 2));
+        mapper.write_prg(0x8000, 0x03).unwrap();
+        assert_eq!(mapper.read_chr(0x0000).unwrap(), 2);
+        mapper.write_prg(0x8000, 0x02).unwrap();
+        assert_eq!(mapper.read_chr(0x1FFF).unwrap(), 1);
+        
+        // 64KB oversize boards use more than two bits
+        let mut mapper = CnromMapper::new(cnrom_cartridge(3, 0, 8));
+        mapper.write_prg(0x8000, 0x06).unwrap();
+        assert_eq!(mapper.read_chr(0x0000).unwrap(), 7);
+        assert_eq!(mapper.chr_offset(0x0010), Some(6 * 8192 + 0x10));
+    }
+    
+    #[test]
+    fn test_mapper_185_chr_protection() {
+        // Submapper 0 guesses: low bits set enables, except $13
+        let mut mapper = CnromMapper::with_chr_protection(cnrom_cartridge(185, 0, 1));
+        assert!(!mapper.chr_enabled());
+        assert_eq!(mapper.read_chr(0x0000).unwrap(), 0xFF);
+        mapper.write_prg(0x8000, 0x21).unwrap();
+        assert_eq!(mapper.read_chr(0x0000).unwrap(), 1);
+        mapper.write_prg(0x8000, 0x13).unwrap();
+        assert_eq!(mapper.read_chr(0x0000).unwrap(), 0xFF);
+        mapper.write_prg(0x8000, 0x00).unwrap();
+        assert!(!mapper.chr_enabled());
+        
+        // Submapper 6 needs the key 2 in the low bits
+        let mut mapper = CnromMapper::with_chr_protection(cnrom_cartridge(185, 6, 1));
+        mapper.write_prg(0x8000, 0x01).unwrap();
+        assert!(!mapper.chr_enabled());
+        mapper.write_prg(0x8000, 0x0E).unwrap();
+        assert!(mapper.chr_enabled());
+        assert_eq!(mapper.read_chr(0x0123).unwrap(), 1);
+        
+        // Submapper 4 is enabled by the power-up value
+        let mapper = CnromMapper::with_chr_protection(cnrom_cartridge(185, 4, 1));
+        assert!(mapper.chr_enabled());
+        assert!(create_mapper(cnrom_cartridge(185, 0, 1)).is_ok());
+    }
+    
+    /// MMC5 cartridge with 32 8KB PRG banks and 256 1KB CHR banks, each
+    /// filled with its index
+    fn mmc5_mapper() -> Mmc5Mapper {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 16, 32, 0x50, 0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..32u8 {
+            rom.extend(core::iter::repeat_n(bank, 8192));
+        }
+        for bank in 0..=255u8 {
+            rom.extend(core::iter::repeat_n(bank, 1024));
+        }
+        Mmc5Mapper::new(Cartridge::from_bytes(&rom).unwrap())
+    }
+    
+    #[test]
+    fn test_mmc5_prg_banking() {
+        let mut mapper = mmc5_mapper();
+        for (addr, value) in [(0x5114, 0x81), (0x5115, 0x83), (0x5116, 0x85), (0x5117, 0x07)] {
+            mapper.write_prg(addr, value).unwrap();
+        }
+        assert_eq!(prg_windows(&mapper), [1, 3, 5, 7]);
+        mapper.write_prg(0x5100, 2).unwrap();
+        assert_eq!(prg_windows(&mapper), [2, 3, 5, 7]);
+        mapper.write_prg(0x5100, 1).unwrap();
+        assert_eq!(prg_windows(&mapper), [2, 3, 6, 7]);
+        mapper.write_prg(0x5100, 0).unwrap();
+        assert_eq!(prg_windows(&mapper), [4, 5, 6, 7]);
+        
+        // PRG RAM needs both protect registers unlocked
+        mapper.write_prg(0x5100, 3).unwrap();
+        mapper.write_prg(0x5113, 2).unwrap();
+        mapper.write_prg(0x6000, 0x42).unwrap();
+        assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x00);
+        mapper.write_prg(0x5102, 0x02).unwrap();
+        mapper.write_prg(0x5103, 0x01).unwrap();
+        mapper.write_prg(0x6000, 0x42).unwrap();
+        assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x42);
+        assert!(mapper.prg_ram_dirty());
+        
+        // A bank with bit 7 clear maps the same RAM into ROM space
+        mapper.write_prg(0x5115, 0x02).unwrap();
+        assert_eq!(mapper.read_prg(0xA000).unwrap(), 0x42);
+        assert_eq!(mapper.prg_rom_offset(0xA000), None);
+        assert_eq!(mapper.prg_rom_offset(0xE000), Some(7 * 8192));
+        
+        // 8x8 multiplier
+        mapper.write_prg(0x5205, 200).unwrap();
+        mapper.write_prg(0x5206, 100).unwrap();
+        assert_eq!(mapper.read_prg(0x5205).unwrap(), 0x20);
+        assert_eq!(mapper.read_prg(0x5206).unwrap(), 0x4E);
+    }
+    
+    #[test]
+    fn test_mmc5_chr_banking() {
+        let mut mapper = mmc5_mapper();
+        for register in 0..8 {
+            mapper.write_prg(0x5120 + register, 10 + register as u8).unwrap();
+        }
+        assert_eq!(chr_windows(&mut mapper), [10, 11, 12, 13, 14, 15, 16, 17]);
+        mapper.write_prg(0x5101, 1).unwrap();
+        assert_eq!(chr_windows(&mut mapper), [52, 53, 54, 55, 68, 69, 70, 71]);
+        
+        // With 8x8 sprites the last written set is used for everything
+        mapper.write_prg(0x5101, 3).unwrap();
+        for register in 0..4 {
+            mapper.write_prg(0x5128 + register, 20 + register as u8).unwrap();
+        }
+        assert_eq!(chr_windows(&mut mapper), [20, 21, 22, 23, 20, 21, 22, 23]);
+        
+        // 8x16 sprites fetch from set A and the background from set B
+        mapper.ppu_register_written(0x2000, 0x20);
+        assert_eq!(chr_windows(&mut mapper), [10, 11, 12, 13, 14, 15, 16, 17]);
+        assert_eq!(mapper.ppu_read(0x1800, PpuFetch::SpritePattern), None);
+        assert_eq!(mapper.ppu_read(0x1800, PpuFetch::BackgroundPattern), Some(22));
+    }
+    
+    #[test]
+    fn test_mmc5_nametables_and_extended_attributes() {
+        let mut mapper = mmc5_mapper();
+        
+        // CIRAM A, CIRAM B, ExRAM, fill
+        mapper.write_prg(0x5105, 0xE4).unwrap();
+        assert_eq!(mapper.ciram_page(0x2000), Some(0));
+        assert_eq!(mapper.ciram_page(0x2400), Some(1));
+        assert!(!mapper.ppu_write_nametable(0x2400, 0x11));
+        assert!(mapper.ppu_write_nametable(0x2805, 0x77));
+        assert_eq!(mapper.exram()[5], 0x77);
+        assert_eq!(mapper.ppu_read(0x2805, PpuFetch::Data), Some(0x77));
+        assert_eq!(mapper.ppu_read(0x2005, PpuFetch::Data), None);
+        
+        mapper.write_prg(0x5106, 0x33).unwrap();
+        mapper.write_prg(0x5107, 0x02).unwrap();
+        assert_eq!(mapper.ppu_read(0x2C10, PpuFetch::Data), Some(0x33));
+        assert_eq!(mapper.ppu_read(0x2FC0, PpuFetch::Data), Some(0xAA));
+        
+        // As CPU RAM, ExRAM reads back on $5C00 and as zeros on the PPU side
+        mapper.write_prg(0x5104, 2).unwrap();
+        mapper.write_prg(0x5C21, 0x83).unwrap();
+        assert_eq!(mapper.read_prg(0x5C21).unwrap(), 0x83);
+        assert_eq!(mapper.ppu_read(0x2821, PpuFetch::Data), Some(0x00));
+        mapper.write_prg(0x5104, 3).unwrap();
+        mapper.write_prg(0x5C21, 0x00).unwrap();
+        assert_eq!(mapper.read_prg(0x5C21).unwrap(), 0x83);
+        
+        // Extended attributes: palette 2 and 4KB CHR bank 3 for this tile
+        mapper.write_prg(0x5104, 1).unwrap();
+        assert_eq!(mapper.read_prg(0x5C21).unwrap(), 0x5C);
+        let fetch = PpuFetch::Nametable { line: 8, column: 3 };
+        assert_eq!(mapper.ppu_read(0x2021, fetch), None);
+        assert_eq!(mapper.ppu_read(0x23C0, PpuFetch::Attribute), Some(0xAA));
+        assert_eq!(mapper.ppu_read(0x0015, PpuFetch::BackgroundPattern), Some(12));
+    }
+    
+    #[test]
+    fn test_mmc5_split_screen() {
+        let mut mapper = mmc5_mapper();
+        mapper.write_prg(0x5104, 2).unwrap();
+        mapper.write_prg(0x5C41, 0x42).unwrap();
+        mapper.write_prg(0x5FC0, 0x30).unwrap();
+        mapper.write_prg(0x5104, 0).unwrap();
+        
+        // Left 4 tiles, scrolled down 16 lines, from 4KB CHR bank 5
+        mapper.write_prg(0x5200, 0x84).unwrap();
+        mapper.write_prg(0x5201, 16).unwrap();
+        mapper.write_prg(0x5202, 5).unwrap();
+        let fetch = PpuFetch::Nametable { line: 3, column: 1 };
+        assert_eq!(mapper.ppu_read(0x2001, fetch), Some(0x42));
+        assert_eq!(mapper.ppu_read(0x23C0, PpuFetch::Attribute), Some(0xFF));
+        // Fine Y comes from the split scroll, not the PPU's address
+        assert_eq!(mapper.ppu_read(0x0428, PpuFetch::BackgroundPattern), Some(21));
+        assert_eq!(mapper.ppu_read(0x2004, PpuFetch::Nametable { line: 3, column: 4 }), None);
+        
+        // Right side from tile 4 on
+        mapper.write_prg(0x5200, 0xC4).unwrap();
+        assert_eq!(mapper.ppu_read(0x2004, PpuFetch::Nametable { line: 3, column: 4 }), Some(0x00));
+        assert_eq!(mapper.ppu_read(0x2001, fetch), None);
+        
+        // No split while ExRAM is CPU RAM
+        mapper.write_prg(0x5104, 2).unwrap();
+        assert_eq!(mapper.ppu_read(0x2004, PpuFetch::Nametable { line: 3, column: 4 }), None);
+    }
+    
+    #[test]
+    fn test_mmc5_scanline_irq() {
+        let mut mapper = mmc5_mapper();
+        mapper.write_prg(0x5203, 3).unwrap();
+        mapper.write_prg(0x5204, 0x80).unwrap();
+        
+        let start_line = |mapper: &mut Mmc5Mapper, line: Scanline| {
+            mapper.ppu_read(0x2000, PpuFetch::Nametable { line, column: 2 });
+        };
+        start_line(&mut mapper, -1);
+        assert!(!mapper.in_frame());
+        for line in 0..3 {
+            start_line(&mut mapper, line);
+        }
+        assert!(mapper.in_frame());
+        assert!(!mapper.irq_pending());
+        start_line(&mut mapper, 3);
+        assert!(mapper.irq_pending());
+        
+        // Reading $5204 reports and acknowledges it
+        assert_eq!(mapper.read_prg(0x5204).unwrap(), 0xC0);
+        assert!(!mapper.irq_pending());
+        assert_eq!(mapper.read_prg(0x5204).unwrap(), 0x40);
+        
+        // The frame ends after the last visible line
+        mapper.notify_scanline(240);
+        assert_eq!(mapper.read_prg(0x5204).unwrap(), 0x00);
+    }
+    
+    #[test]
+    fn test_corrupt_mapper_state_is_rejected() {
+        let mut mapper = mmc5_mapper();
+        mapper.write_prg(0x5C00, 0x42).unwrap();
+        let mut corrupt = mapper.clone();
+        corrupt.exram.clear();
+        assert!(mapper.load_state(MapperState::Mmc5(Cow::Owned(corrupt))).is_err());
+        let mut corrupt = mapper.clone();
+        corrupt.tile_split_row = 255;
+        assert!(MapperState::Mmc5(Cow::Owned(corrupt)).validate().is_err());
+        
+        // The mapper is untouched and keeps working
+        mapper.write_prg(0x5104, 2).unwrap();
+        assert_eq!(mapper.read_prg(0x5C00).unwrap(), 0x42);
+        
+        let mut mmc1 = Mmc1Mapper::new(Cartridge::default());
+        assert!(mapper.load_state(mmc1.save_state()).is_err());
+        let mut corrupt = mmc1.clone();
+        corrupt.shift_count = 255;
+        assert!(mmc1.load_state(MapperState::Mmc1(Cow::Owned(corrupt))).is_err());
     }
 }
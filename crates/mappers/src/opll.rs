@@ -0,0 +1,519 @@
+//! VRC7 FM synthesizer
+//!
+//! The VRC7 carries a cut-down Yamaha YM2413 (OPLL): six two-operator FM
+//! channels, no rhythm mode, and its own set of 15 built-in instruments.
+//! This core follows the OPLL's structure (phase generator, envelope
+//! generator, key scaling, LFOs, feedback) in floating point rather than
+//! reproducing the chip's log-sin and exponent ROMs bit for bit.
+
+use serde::{Deserialize, Serialize};
+
+/// Number of FM channels on the VRC7
+pub const OPLL_CHANNELS: usize = 6;
+
+/// CPU cycles per OPLL sample (the 3.58 MHz chip clock divided by 72)
+pub const OPLL_CYCLES_PER_SAMPLE: u32 = 36;
+
+/// OPLL output rate in Hz
+const SAMPLE_RATE: f32 = 49716.0;
+
+/// Built-in VRC7 instruments 1-15 (instrument 0 is the custom patch)
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+/// Frequency multiplier for each MULT setting
+const MULTIPLIERS: [f32; 16] = [0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0];
+
+/// Key scale attenuation in dB at block 7 for the top four F-number bits
+const KSL_DB: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25,
+    36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25, 42.0,
+];
+
+/// Share of `KSL_DB` applied for KSL 0-3 (0, 1.5, 3 and 6 dB per octave)
+const KSL_SCALE: [f32; 4] = [0.0, 0.25, 0.5, 1.0];
+
+/// Modulator self-feedback in cycles of phase per unit of output, for FB 0-7
+const FEEDBACK: [f32; 8] = [0.0, 1.0 / 32.0, 1.0 / 16.0, 1.0 / 8.0, 0.25, 0.5, 1.0, 2.0];
+
+/// Carrier phase shift in cycles for a full-scale modulator (±4π)
+const MODULATION_DEPTH: f32 = 2.0;
+
+/// Attenuation at which an operator is silent
+const MAX_ATTENUATION: f32 = 48.0;
+
+/// Decay speed in dB per sample at rate index 4 (96 dB in 39.28 s)
+const DECAY_DB_PER_SAMPLE: f32 = 96.0 / (39.28 * SAMPLE_RATE);
+
+/// Attack time in samples at rate index 4 (2826 ms)
+const ATTACK_SAMPLES: f32 = 2.826 * SAMPLE_RATE;
+
+/// log2 of the fraction of attenuation left when an attack completes
+const ATTACK_LOG2_RANGE: f32 = -8.907;
+
+/// Amplitude LFO (3.7 Hz, up to 4.8 dB) and vibrato LFO (6.4 Hz, about ±14 cents)
+const AM_RATE: f32 = 3.7 / SAMPLE_RATE;
+const AM_DEPTH_DB: f32 = 4.8;
+const VIBRATO_RATE: f32 = 6.4 / SAMPLE_RATE;
+const VIBRATO_DEPTH: f32 = 0.008;
+
+/// Release rate used while a channel's sustain bit is set
+const SUSTAIN_RELEASE_RATE: u8 = 5;
+
+/// Release rate of percussive instruments after key-off
+const PERCUSSIVE_RELEASE_RATE: u8 = 7;
+
+/// Fractional part of `x`, always in [0, 1)
+fn fract(x: f32) -> f32 {
+    let whole = x as i32 as f32;
+    if whole > x { x - whole + 1.0 } else { x - whole }
+}
+
+/// sin(2πx) from a corrected parabola (no libm in `no_std`)
+fn sine(x: f32) -> f32 {
+    let t = fract(x + 0.5) - 0.5;
+    let y = 8.0 * t - 16.0 * t * t.abs();
+    0.225 * (y * y.abs() - y) + y
+}
+
+/// 2^x from a cubic fit of the fractional part
+fn exp2(x: f32) -> f32 {
+    if x < -126.0 {
+        return 0.0;
+    }
+    let mut whole = x as i32;
+    if whole as f32 > x {
+        whole -= 1;
+    }
+    let f = x - whole as f32;
+    let mantissa = 1.0 + f * (0.695_1 + f * (0.226_2 + f * 0.078_2));
+    mantissa * f32::from_bits(((whole.clamp(-126, 127) + 127) as u32) << 23)
+}
+
+/// Linear gain for an attenuation in dB
+fn db_to_gain(db: f32) -> f32 {
+    exp2(-db / 6.0206)
+}
+
+/// Phase of an operator's envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Off,
+}
+
+/// Settings for one operator, decoded from an instrument patch
+#[derive(Debug, Clone, Copy)]
+struct OperatorPatch {
+    am: bool,
+    vibrato: bool,
+    sustained: bool,
+    ksr: bool,
+    multiplier: f32,
+    ksl: u8,
+    rectified: bool,
+    attack: u8,
+    decay: u8,
+    sustain_level: u8,
+    release: u8,
+}
+
+impl OperatorPatch {
+    /// Decode operator `slot` (0 = modulator, 1 = carrier) of a patch
+    fn decode(patch: &[u8; 8], slot: usize) -> Self {
+        Self {
+            am: patch[slot] & 0x80 != 0,
+            vibrato: patch[slot] & 0x40 != 0,
+            sustained: patch[slot] & 0x20 != 0,
+            ksr: patch[slot] & 0x10 != 0,
+            multiplier: MULTIPLIERS[(patch[slot] & 0x0F) as usize],
+            ksl: patch[2 + slot] >> 6,
+            rectified: patch[3] & (0x08 << slot) != 0,
+            attack: patch[4 + slot] >> 4,
+            decay: patch[4 + slot] & 0x0F,
+            sustain_level: patch[6 + slot] >> 4,
+            release: patch[6 + slot] & 0x0F,
+        }
+    }
+}
+
+/// One FM operator: phase accumulator and envelope
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Operator {
+    phase: f32,
+    attenuation: f32,
+    state: EnvelopeState,
+    output: f32,
+    previous: f32,
+}
+
+impl Operator {
+    fn new() -> Self {
+        Self {
+            phase: 0.0,
+            attenuation: MAX_ATTENUATION,
+            state: EnvelopeState::Off,
+            output: 0.0,
+            previous: 0.0,
+        }
+    }
+
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.state = EnvelopeState::Attack;
+    }
+
+    fn key_off(&mut self) {
+        if self.state != EnvelopeState::Off {
+            self.state = EnvelopeState::Release;
+        }
+    }
+
+    /// Advance the envelope by one sample
+    fn step_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, release: u8) {
+        let rate_index = |rate: u8| -> u8 {
+            if rate == 0 {
+                0
+            } else {
+                let ksr = if patch.ksr { key_scale } else { key_scale >> 2 };
+                (rate * 4 + ksr).min(63)
+            }
+        };
+        let decay_step = |rate: u8| -> f32 {
+            let index = rate_index(rate);
+            if index < 4 { 0.0 } else { DECAY_DB_PER_SAMPLE * exp2(index as f32 / 4.0 - 1.0) }
+        };
+
+        match self.state {
+            EnvelopeState::Attack => {
+                let index = rate_index(patch.attack);
+                if index >= 60 {
+                    self.attenuation = 0.0;
+                } else if index >= 4 {
+                    let samples = ATTACK_SAMPLES / exp2(index as f32 / 4.0 - 1.0);
+                    self.attenuation *= exp2(ATTACK_LOG2_RANGE / samples);
+                }
+                if self.attenuation < 0.1 {
+                    self.attenuation = 0.0;
+                    self.state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                let sustain_level = patch.sustain_level as f32 * 3.0;
+                self.attenuation += decay_step(patch.decay);
+                if self.attenuation >= sustain_level {
+                    self.attenuation = sustain_level;
+                    self.state = EnvelopeState::Sustain;
+                }
+            }
+            EnvelopeState::Sustain => {
+                // Percussive instruments keep fading while the key is held
+                if !patch.sustained {
+                    self.attenuation += decay_step(patch.release);
+                }
+            }
+            EnvelopeState::Release => self.attenuation += decay_step(release),
+            EnvelopeState::Off => {}
+        }
+
+        if self.attenuation >= MAX_ATTENUATION {
+            self.attenuation = MAX_ATTENUATION;
+            if self.state != EnvelopeState::Attack {
+                self.state = EnvelopeState::Off;
+            }
+        }
+    }
+
+    /// Produce one sample with `modulation` cycles added to the phase
+    fn render(&mut self, patch: &OperatorPatch, increment: f32, modulation: f32, attenuation: f32) -> f32 {
+        self.previous = self.output;
+        let phase = fract(self.phase + modulation);
+        self.phase = fract(self.phase + increment * patch.multiplier);
+
+        let total = self.attenuation + attenuation;
+        self.output = if self.state == EnvelopeState::Off || total >= MAX_ATTENUATION {
+            0.0
+        } else if patch.rectified && phase >= 0.5 {
+            // Half-sine waveform: the negative half is cut off
+            0.0
+        } else {
+            sine(phase) * db_to_gain(total)
+        };
+        self.output
+    }
+}
+
+/// One two-operator channel
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+    modulator: Operator,
+    carrier: Operator,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            fnum: 0,
+            block: 0,
+            key_on: false,
+            sustain: false,
+            instrument: 0,
+            volume: 0,
+            modulator: Operator::new(),
+            carrier: Operator::new(),
+        }
+    }
+
+    /// Key scale rate input: block and the top F-number bit
+    fn key_scale(&self) -> u8 {
+        (self.block << 1) | (self.fnum >> 8) as u8
+    }
+
+    /// Key scale level attenuation before the KSL setting is applied
+    fn ksl_db(&self) -> f32 {
+        (KSL_DB[(self.fnum >> 5) as usize & 0x0F] - 6.0 * (7 - self.block) as f32).max(0.0)
+    }
+}
+
+/// VRC7 OPLL sound chip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Opll {
+    address: u8,
+    custom: [u8; 8],
+    channels: [Channel; OPLL_CHANNELS],
+    am_phase: f32,
+    vibrato_phase: f32,
+    divider: u32,
+    outputs: [f32; OPLL_CHANNELS],
+}
+
+impl Default for Opll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Opll {
+    pub fn new() -> Self {
+        Self {
+            address: 0,
+            custom: [0; 8],
+            channels: [Channel::new(); OPLL_CHANNELS],
+            am_phase: 0.0,
+            vibrato_phase: 0.0,
+            divider: 0,
+            outputs: [0.0; OPLL_CHANNELS],
+        }
+    }
+
+    /// Whether every register holds a value the chip can reach
+    pub(crate) fn is_valid(&self) -> bool {
+        self.divider < OPLL_CYCLES_PER_SAMPLE
+            && self.channels.iter().all(|channel| {
+                channel.fnum <= 0x1FF && channel.block <= 7 && channel.instrument <= 15 && channel.volume <= 15
+            })
+    }
+
+    /// Silence every channel and clear all registers
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Select the register for the next data write ($9010)
+    pub fn write_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    /// Write the selected register ($9030)
+    pub fn write_data(&mut self, value: u8) {
+        let register = self.address;
+        match register {
+            0x00..=0x07 => self.custom[register as usize] = value,
+            0x10..=0x15 | 0x20..=0x25 | 0x30..=0x35 => {
+                let channel = &mut self.channels[(register & 0x0F) as usize];
+                match register >> 4 {
+                    1 => channel.fnum = (channel.fnum & 0x100) | value as u16,
+                    2 => {
+                        channel.fnum = (channel.fnum & 0xFF) | ((value as u16 & 0x01) << 8);
+                        channel.block = (value >> 1) & 0x07;
+                        channel.sustain = value & 0x20 != 0;
+                        let key_on = value & 0x10 != 0;
+                        if key_on && !channel.key_on {
+                            channel.modulator.key_on();
+                            channel.carrier.key_on();
+                        } else if !key_on && channel.key_on {
+                            channel.modulator.key_off();
+                            channel.carrier.key_off();
+                        }
+                        channel.key_on = key_on;
+                    }
+                    _ => {
+                        channel.instrument = value >> 4;
+                        channel.volume = value & 0x0F;
+                    }
+                }
+            }
+            // Registers $0E-$0F (rhythm and test) are not connected on the VRC7
+            _ => {}
+        }
+    }
+
+    /// Advance by one CPU cycle, producing a new sample every 36 cycles
+    pub fn clock(&mut self) {
+        self.divider += 1;
+        if self.divider >= OPLL_CYCLES_PER_SAMPLE {
+            self.divider = 0;
+            self.generate_sample();
+        }
+    }
+
+    /// Latest output of each channel (-1.0 to 1.0)
+    pub fn channel_outputs(&self) -> [f32; OPLL_CHANNELS] {
+        self.outputs
+    }
+
+    /// Latest mixed output, the sum of all channels
+    pub fn output(&self) -> f32 {
+        self.outputs.iter().sum()
+    }
+
+    fn generate_sample(&mut self) {
+        self.am_phase = fract(self.am_phase + AM_RATE);
+        self.vibrato_phase = fract(self.vibrato_phase + VIBRATO_RATE);
+        let am_db = AM_DEPTH_DB * 0.5 * (1.0 + sine(self.am_phase));
+        let vibrato = 1.0 + VIBRATO_DEPTH * sine(self.vibrato_phase);
+
+        for (channel, output) in self.channels.iter_mut().zip(self.outputs.iter_mut()) {
+            let patch = if channel.instrument == 0 {
+                self.custom
+            } else {
+                PATCHES[channel.instrument as usize - 1]
+            };
+            let modulator_patch = OperatorPatch::decode(&patch, 0);
+            let carrier_patch = OperatorPatch::decode(&patch, 1);
+            let key_scale = channel.key_scale();
+            let ksl_db = channel.ksl_db();
+            let release = |operator: &OperatorPatch| {
+                if channel.sustain {
+                    SUSTAIN_RELEASE_RATE
+                } else if operator.sustained {
+                    operator.release
+                } else {
+                    PERCUSSIVE_RELEASE_RATE
+                }
+            };
+            let modulator_release = release(&modulator_patch);
+            let carrier_release = release(&carrier_patch);
+
+            channel.modulator.step_envelope(&modulator_patch, key_scale, modulator_release);
+            channel.carrier.step_envelope(&carrier_patch, key_scale, carrier_release);
+
+            // Cycles per sample: F-number × 2^block / 2^19
+            let increment = channel.fnum as f32 * (1u32 << channel.block) as f32 / 524_288.0;
+            let operator_increment = |operator: &OperatorPatch| {
+                if operator.vibrato { increment * vibrato } else { increment }
+            };
+            let operator_attenuation = |operator: &OperatorPatch, level_db: f32| {
+                level_db + ksl_db * KSL_SCALE[operator.ksl as usize] + if operator.am { am_db } else { 0.0 }
+            };
+
+            let feedback = FEEDBACK[(patch[3] & 0x07) as usize]
+                * (channel.modulator.output + channel.modulator.previous) * 0.5;
+            let modulator_level = (patch[2] & 0x3F) as f32 * 0.75;
+            let modulation = channel.modulator.render(
+                &modulator_patch,
+                operator_increment(&modulator_patch),
+                feedback,
+                operator_attenuation(&modulator_patch, modulator_level),
+            );
+            let carrier_level = channel.volume as f32 * 3.0;
+            *output = channel.carrier.render(
+                &carrier_patch,
+                operator_increment(&carrier_patch),
+                modulation * MODULATION_DEPTH,
+                operator_attenuation(&carrier_patch, carrier_level),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_math_helpers() {
+        for i in 0..64 {
+            let x = i as f32 / 64.0;
+            let expected = (2.0 * core::f32::consts::PI * x).sin();
+            assert!((sine(x) - expected).abs() < 0.002, "sine({})", x);
+        }
+        assert!((exp2(-1.0) - 0.5).abs() < 1e-4);
+        assert!((exp2(3.5) - 11.3137).abs() < 0.01);
+        assert!((db_to_gain(6.0206) - 0.5).abs() < 1e-3);
+        assert_eq!(db_to_gain(1000.0), 0.0);
+    }
+
+    /// Key on channel 0 with a built-in instrument at A4 (F-number 288, block 4)
+    fn play_note(opll: &mut Opll, instrument: u8) {
+        for (register, value) in [(0x30, instrument << 4), (0x10, 0x20), (0x20, 0x19)] {
+            opll.write_address(register);
+            opll.write_data(value);
+        }
+    }
+
+    #[test]
+    fn test_note_sounds_and_releases() {
+        let mut opll = Opll::new();
+        play_note(&mut opll, 3);
+
+        let mut peak = 0.0f32;
+        let mut crossings = 0;
+        let mut last = 0.0;
+        for _ in 0..OPLL_CYCLES_PER_SAMPLE * 4971 {
+            opll.clock();
+            let output = opll.output();
+            peak = peak.max(output.abs());
+            if last < 0.0 && output >= 0.0 {
+                crossings += 1;
+            }
+            last = output;
+        }
+        assert!(peak > 0.3, "peak {}", peak);
+        // About 44 fundamental cycles in a tenth of a second
+        assert!((40..=60).contains(&crossings), "crossings {}", crossings);
+        assert_eq!(opll.channel_outputs()[1], 0.0);
+
+        // Key off fades out
+        opll.write_address(0x20);
+        opll.write_data(0x08);
+        for _ in 0..OPLL_CYCLES_PER_SAMPLE * 49716 {
+            opll.clock();
+        }
+        assert!(opll.output().abs() < 0.01);
+    }
+}
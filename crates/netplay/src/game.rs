@@ -0,0 +1,54 @@
+use rnes_common::{RnesResult, SaveState};
+use rnes_core::Emulator;
+
+/// Deterministic game driven by netplay inputs
+pub trait NetplayGame {
+    /// Run one frame with one controller byte per player
+    ///
+    /// `resimulating` is set while replaying frames after a rollback, so
+    /// output that was already presented (audio, video) can be skipped.
+    fn advance(&mut self, inputs: &[u8], resimulating: bool) -> RnesResult<()>;
+}
+
+/// Game that a rollback session can rewind and resimulate
+pub trait RollbackGame: NetplayGame {
+    /// Saved state used to rewind
+    type State;
+
+    /// Capture the current state
+    fn save(&mut self) -> RnesResult<Self::State>;
+
+    /// Restore a state captured with `save`
+    fn load(&mut self, state: &Self::State) -> RnesResult<()>;
+}
+
+impl NetplayGame for Emulator {
+    fn advance(&mut self, inputs: &[u8], resimulating: bool) -> RnesResult<()> {
+        // Players 3 and 4 need Four Score support in the core and are ignored
+        let input = |player: usize| inputs.get(player).copied().unwrap_or(0);
+        let frame = self.next_input_frame();
+        self.queue_input(frame, [input(0), input(1)]);
+        if resimulating {
+            // Audio for these frames was already queued when they were predicted
+            let audio_sink = self.audio_sink.take();
+            let result = self.run_frame();
+            self.audio_sink = audio_sink;
+            self.bus.clear_audio();
+            result
+        } else {
+            self.run_frame()
+        }
+    }
+}
+
+impl RollbackGame for Emulator {
+    type State = SaveState;
+
+    fn save(&mut self) -> RnesResult<SaveState> {
+        self.snapshot()
+    }
+
+    fn load(&mut self, state: &SaveState) -> RnesResult<()> {
+        self.restore_snapshot(state)
+    }
+}
@@ -0,0 +1,96 @@
+/// Confirmed inputs of one player, indexed by frame
+///
+/// Inputs only ever arrive in order; frames older than the rollback window
+/// can be dropped with `discard_before`.
+#[derive(Debug, Clone, Default)]
+pub struct InputQueue {
+    base_frame: u32,
+    inputs: Vec<u8>,
+}
+
+impl InputQueue {
+    /// Create empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of frames with confirmed input (the next frame expected)
+    pub fn confirmed_frames(&self) -> u32 {
+        self.base_frame + self.inputs.len() as u32
+    }
+
+    /// Add inputs for consecutive frames starting at `start_frame`
+    ///
+    /// Frames already known are skipped; a gap means packets were lost and
+    /// the inputs are ignored until a packet covering the gap arrives.
+    /// Returns the number of newly confirmed frames.
+    pub fn add(&mut self, start_frame: u32, inputs: &[u8]) -> u32 {
+        let next = self.confirmed_frames();
+        if start_frame > next {
+            return 0;
+        }
+        let skip = (next - start_frame) as usize;
+        if skip >= inputs.len() {
+            return 0;
+        }
+        self.inputs.extend_from_slice(&inputs[skip..]);
+        (inputs.len() - skip) as u32
+    }
+
+    /// Push input for the next frame
+    pub fn push(&mut self, input: u8) {
+        self.inputs.push(input);
+    }
+
+    /// Get confirmed input for a frame
+    pub fn get(&self, frame: u32) -> Option<u8> {
+        frame.checked_sub(self.base_frame)
+            .and_then(|index| self.inputs.get(index as usize).copied())
+    }
+
+    /// Get inputs from `frame` up to the last confirmed frame
+    pub fn since(&self, frame: u32) -> &[u8] {
+        let start = frame.saturating_sub(self.base_frame) as usize;
+        &self.inputs[start.min(self.inputs.len())..]
+    }
+
+    /// Predict input for an unconfirmed frame (repeat the last known input)
+    pub fn predict(&self, frame: u32) -> u8 {
+        self.get(frame)
+            .or_else(|| self.inputs.last().copied())
+            .unwrap_or(0)
+    }
+
+    /// Drop inputs before `frame`, keeping at least the latest one for prediction
+    pub fn discard_before(&mut self, frame: u32) {
+        let limit = self.inputs.len().saturating_sub(1);
+        let count = (frame.saturating_sub(self.base_frame) as usize).min(limit);
+        self.inputs.drain(..count);
+        self.base_frame += count as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_queue_ordering_and_prediction() {
+        let mut queue = InputQueue::new();
+        assert_eq!(queue.predict(0), 0);
+
+        assert_eq!(queue.add(0, &[1, 2, 3]), 3);
+        // Overlapping resend only adds the new tail
+        assert_eq!(queue.add(1, &[2, 3, 4]), 1);
+        // Gap is ignored
+        assert_eq!(queue.add(6, &[9]), 0);
+        assert_eq!(queue.confirmed_frames(), 4);
+        assert_eq!(queue.get(3), Some(4));
+        assert_eq!(queue.predict(10), 4);
+
+        queue.discard_before(3);
+        assert_eq!(queue.get(2), None);
+        assert_eq!(queue.since(0), &[4]);
+        assert_eq!(queue.confirmed_frames(), 4);
+    }
+}
@@ -0,0 +1,18 @@
+pub mod protocol;
+pub mod transport;
+pub mod input;
+pub mod game;
+pub mod session;
+pub mod lockstep;
+pub mod spectator;
+
+pub use protocol::*;
+pub use transport::*;
+pub use input::*;
+pub use game::*;
+pub use session::*;
+pub use lockstep::*;
+pub use spectator::*;
+
+#[cfg(test)]
+mod test_game;
@@ -0,0 +1,293 @@
+use rnes_common::{RnesError, RnesResult, RomId};
+use crate::{
+    check_rom_id, decode_inputs, encode_inputs, AdvanceResult, ChatMessage, InputQueue, Message,
+    NetplayGame, NetplayStats, SpectatorHub, Transport,
+};
+
+/// Maximum number of players (NES Four Score)
+pub const MAX_PLAYERS: usize = 4;
+
+/// Lockstep session configuration
+#[derive(Debug, Clone)]
+pub struct LockstepConfig {
+    /// Local player index
+    pub local_player: usize,
+    /// Number of players in the session (2-4)
+    pub players: usize,
+    /// Frames of local input delay; should cover the round-trip time
+    pub input_delay: u32,
+    /// Loaded ROM; the session fails if a peer runs a different one
+    pub rom_id: Option<RomId>,
+}
+
+impl Default for LockstepConfig {
+    fn default() -> Self {
+        Self {
+            local_player: 0,
+            players: 2,
+            input_delay: 4,
+            rom_id: None,
+        }
+    }
+}
+
+/// Connection to another player
+#[derive(Debug)]
+struct Peer<T> {
+    player: usize,
+    transport: T,
+    /// Local inputs the peer has received
+    acked: u32,
+}
+
+/// Delay-based lockstep session
+///
+/// Every frame waits until all players' inputs for it have arrived, so no
+/// state is ever rolled back. Latency is hidden by a fixed input delay
+/// instead, which suits high-latency links and sessions with more than two
+/// players. Peers form a full mesh: one transport per remote player.
+#[derive(Debug)]
+pub struct LockstepSession<T: Transport> {
+    peers: Vec<Peer<T>>,
+    config: LockstepConfig,
+    frame: u32,
+    inputs: Vec<InputQueue>,
+    spectators: SpectatorHub<T>,
+    chat: Vec<ChatMessage>,
+    stats: NetplayStats,
+}
+
+impl<T: Transport> LockstepSession<T> {
+    /// Create session with one transport per remote player
+    pub fn new(peers: Vec<(usize, T)>, config: LockstepConfig) -> RnesResult<Self> {
+        if config.players < 2 || config.players > MAX_PLAYERS {
+            return Err(RnesError::Netplay(format!("Invalid player count: {}", config.players)));
+        }
+        if config.local_player >= config.players {
+            return Err(RnesError::Netplay(format!("Invalid local player: {}", config.local_player)));
+        }
+
+        let mut connected = vec![false; config.players];
+        connected[config.local_player] = true;
+        for &(player, _) in &peers {
+            if player >= config.players || connected[player] {
+                return Err(RnesError::Netplay(format!("Invalid or duplicate peer player: {}", player)));
+            }
+            connected[player] = true;
+        }
+        if connected.contains(&false) {
+            return Err(RnesError::Netplay("Every player needs a connection".to_string()));
+        }
+
+        // The first `input_delay` frames run with no input from anyone
+        let mut inputs = vec![InputQueue::new(); config.players];
+        for queue in &mut inputs {
+            for _ in 0..config.input_delay {
+                queue.push(0);
+            }
+        }
+
+        Ok(Self {
+            peers: peers.into_iter()
+                .map(|(player, transport)| Peer { player, transport, acked: 0 })
+                .collect(),
+            spectators: SpectatorHub::new(config.players),
+            config,
+            frame: 0,
+            inputs,
+            chat: Vec::new(),
+            stats: NetplayStats::default(),
+        })
+    }
+
+    /// Get next frame to simulate
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Get session statistics
+    pub fn stats(&self) -> NetplayStats {
+        NetplayStats {
+            frame: self.frame,
+            remote_confirmed: self.confirmed_frames(),
+            ..self.stats.clone()
+        }
+    }
+
+    /// Get session configuration
+    pub fn config(&self) -> &LockstepConfig {
+        &self.config
+    }
+
+    /// Add a spectator connection
+    pub fn add_spectator(&mut self, transport: T) {
+        self.spectators.add(transport);
+    }
+
+    /// Add local input, exchange inputs and run one frame once all inputs are in
+    ///
+    /// While waiting for other players, the local input of the waiting
+    /// frames is dropped; the frontend should simply call again next tick.
+    pub fn advance_frame<G: NetplayGame>(&mut self, game: &mut G, local_input: u8) -> RnesResult<AdvanceResult> {
+        self.poll()?;
+
+        let local = &mut self.inputs[self.config.local_player];
+        if local.confirmed_frames() <= self.frame + self.config.input_delay {
+            local.push(local_input);
+        }
+        self.send_inputs()?;
+
+        let result = if self.confirmed_frames() > self.frame {
+            let inputs: Vec<u8> = self.inputs.iter()
+                .map(|queue| queue.get(self.frame).unwrap_or(0))
+                .collect();
+            game.advance(&inputs, false)?;
+            self.spectators.push(&inputs);
+            self.frame += 1;
+            self.discard_confirmed();
+            AdvanceResult::Advanced
+        } else {
+            self.stats.stalled_frames += 1;
+            AdvanceResult::Stalled
+        };
+
+        self.spectators.update(&mut self.chat)?;
+        Ok(result)
+    }
+
+    /// Send a chat line to all players and spectators
+    pub fn send_chat(&mut self, text: &str) -> RnesResult<()> {
+        let message = ChatMessage::new(Some(self.config.local_player as u8), text);
+        let bytes = Message::Chat(message.clone()).to_bytes()?;
+        for peer in &mut self.peers {
+            peer.transport.send(&bytes)?;
+        }
+        self.spectators.broadcast_chat(&message)
+    }
+
+    /// Take chat lines received since the last call
+    pub fn take_chat(&mut self) -> Vec<ChatMessage> {
+        std::mem::take(&mut self.chat)
+    }
+
+    fn confirmed_frames(&self) -> u32 {
+        self.inputs.iter()
+            .map(|queue| queue.confirmed_frames())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn poll(&mut self) -> RnesResult<()> {
+        for peer in &mut self.peers {
+            while let Some(packet) = peer.transport.recv()? {
+                match Message::from_bytes(&packet) {
+                    Ok(Message::Hello { rom_id }) => check_rom_id(self.config.rom_id, rom_id)?,
+                    Ok(Message::Input { start_frame, ack_frame, runs }) => {
+                        self.inputs[peer.player].add(start_frame, &decode_inputs(&runs));
+                        peer.acked = peer.acked.max(ack_frame);
+                    }
+                    Ok(Message::Chat(message)) => {
+                        self.chat.push(ChatMessage::new(Some(peer.player as u8), &message.text));
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Dropping malformed netplay packet: {}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send_inputs(&mut self) -> RnesResult<()> {
+        let local = &self.inputs[self.config.local_player];
+        for peer in &mut self.peers {
+            if let (Some(rom_id), 0) = (self.config.rom_id, peer.acked) {
+                peer.transport.send(&Message::Hello { rom_id }.to_bytes()?)?;
+            }
+            let message = Message::Input {
+                start_frame: peer.acked,
+                ack_frame: self.inputs[peer.player].confirmed_frames(),
+                runs: encode_inputs(local.since(peer.acked)),
+            };
+            peer.transport.send(&message.to_bytes()?)?;
+        }
+        Ok(())
+    }
+
+    fn discard_confirmed(&mut self) {
+        let acked = self.peers.iter().map(|peer| peer.acked).min().unwrap_or(self.frame);
+        for (player, queue) in self.inputs.iter_mut().enumerate() {
+            if player == self.config.local_player {
+                queue.discard_before(acked.min(self.frame));
+            } else {
+                queue.discard_before(self.frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_game::CounterGame;
+    use crate::{LoopbackTransport, SpectatorSession};
+
+    #[test]
+    fn test_lockstep_four_players_with_spectator() {
+        // Full mesh between four players
+        let mut links: Vec<Vec<(usize, LoopbackTransport)>> = (0..MAX_PLAYERS).map(|_| Vec::new()).collect();
+        for a in 0..MAX_PLAYERS {
+            for b in a + 1..MAX_PLAYERS {
+                let (to_b, to_a) = LoopbackTransport::pair();
+                links[a].push((b, to_b));
+                links[b].push((a, to_a));
+            }
+        }
+        let mut sessions: Vec<_> = links.into_iter().enumerate()
+            .map(|(player, peers)| {
+                let config = LockstepConfig { local_player: player, players: MAX_PLAYERS, input_delay: 2, rom_id: None };
+                LockstepSession::new(peers, config).unwrap()
+            })
+            .collect();
+        let mut games: Vec<CounterGame> = (0..MAX_PLAYERS).map(|_| CounterGame::default()).collect();
+
+        let (host_link, spectator_link) = LoopbackTransport::pair();
+        sessions[0].add_spectator(host_link);
+        let mut spectator = SpectatorSession::new(spectator_link, MAX_PLAYERS).unwrap();
+        let mut spectator_game = CounterGame::default();
+
+        sessions[2].send_chat("hello").unwrap();
+        sessions[0].send_chat("gg").unwrap();
+
+        let mut host_values = vec![0];
+        for i in 0..100u32 {
+            for (player, (session, game)) in sessions.iter_mut().zip(&mut games).enumerate() {
+                session.advance_frame(game, (i as usize * (player + 1) % 5) as u8).unwrap();
+            }
+            host_values.push(games[0].value);
+            spectator.advance_frame(&mut spectator_game).unwrap();
+        }
+        while spectator.frames_behind() > 0 {
+            spectator.advance_frame(&mut spectator_game).unwrap();
+        }
+
+        let frames = games[0].frames;
+        assert_eq!(frames, 100);
+        for game in &games {
+            assert_eq!(game.frames, frames);
+            assert_eq!(game.value, games[0].value);
+        }
+        // Spectator replays exactly what the host ran
+        assert!(spectator_game.frames > 90);
+        assert_eq!(spectator_game.value, host_values[spectator_game.frames as usize]);
+
+        assert_eq!(sessions[0].take_chat(), vec![ChatMessage::new(Some(2), "hello")]);
+        assert_eq!(spectator.take_chat(), vec![ChatMessage::new(Some(0), "gg")]);
+    }
+
+    #[test]
+    fn test_lockstep_rejects_missing_peer() {
+        let (a, _b) = LoopbackTransport::pair();
+        let config = LockstepConfig { players: 3, ..LockstepConfig::default() };
+        assert!(LockstepSession::new(vec![(1, a)], config).is_err());
+    }
+}
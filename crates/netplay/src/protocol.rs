@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use rnes_common::{RnesError, RnesResult, RomId};
+
+/// Run of identical input bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputRun {
+    pub value: u8,
+    pub count: u16,
+}
+
+/// Netplay message exchanged between peers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Message {
+    /// Inputs for consecutive frames starting at `start_frame`
+    ///
+    /// Every packet resends all inputs the peer has not acknowledged yet, so
+    /// a lost packet is repaired by the next one. Inputs rarely change from
+    /// frame to frame, so they are run-length encoded.
+    Input {
+        start_frame: u32,
+        /// Number of the sender's remote inputs received so far
+        ack_frame: u32,
+        runs: Vec<InputRun>,
+    },
+    /// Confirmed inputs of every player, streamed to spectators
+    Spectate {
+        start_frame: u32,
+        /// Run-length encoded inputs, one list per player
+        players: Vec<Vec<InputRun>>,
+    },
+    /// Spectator acknowledgement of frames received so far
+    SpectateAck {
+        frames: u32,
+    },
+    /// Chat line
+    Chat(ChatMessage),
+    /// Identifies the loaded ROM; sent until the peer's first inputs arrive
+    Hello {
+        rom_id: RomId,
+    },
+}
+
+/// Maximum chat message length in bytes
+pub const MAX_CHAT_LENGTH: usize = 256;
+
+/// Chat line from a player or spectator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// Sending player, or `None` for a spectator
+    pub player: Option<u8>,
+    pub text: String,
+}
+
+impl ChatMessage {
+    /// Create chat message, truncating overly long text
+    pub fn new(player: Option<u8>, text: &str) -> Self {
+        let mut end = text.len().min(MAX_CHAT_LENGTH);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            player,
+            text: text[..end].to_string(),
+        }
+    }
+}
+
+impl Message {
+    /// Serialize message for sending
+    pub fn to_bytes(&self) -> RnesResult<Vec<u8>> {
+        bincode::serialize(self)
+            .map_err(|e| RnesError::Netplay(format!("Failed to encode message: {}", e)))
+    }
+
+    /// Deserialize a received message
+    pub fn from_bytes(data: &[u8]) -> RnesResult<Self> {
+        bincode::deserialize(data)
+            .map_err(|e| RnesError::Netplay(format!("Failed to decode message: {}", e)))
+    }
+}
+
+/// Fail if the peer's ROM differs from the local one
+///
+/// Sessions without a local ROM id accept any peer.
+pub fn check_rom_id(local: Option<RomId>, remote: RomId) -> RnesResult<()> {
+    match local {
+        Some(local) if local != remote => Err(RnesError::Netplay(format!(
+            "ROM mismatch: local {} ({}), peer {} ({})",
+            local, local.sha1_hex(), remote, remote.sha1_hex()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Run-length encode per-frame inputs
+pub fn encode_inputs(inputs: &[u8]) -> Vec<InputRun> {
+    let mut runs: Vec<InputRun> = Vec::new();
+    for &value in inputs {
+        match runs.last_mut() {
+            Some(run) if run.value == value && run.count < u16::MAX => run.count += 1,
+            _ => runs.push(InputRun { value, count: 1 }),
+        }
+    }
+    runs
+}
+
+/// Number of inputs `runs` expand to, without expanding them
+pub fn decoded_len(runs: &[InputRun]) -> usize {
+    runs.iter().map(|run| run.count as usize).sum()
+}
+
+/// Expand run-length encoded inputs
+pub fn decode_inputs(runs: &[InputRun]) -> Vec<u8> {
+    let mut inputs = Vec::new();
+    for run in runs {
+        inputs.extend(std::iter::repeat_n(run.value, run.count as usize));
+    }
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_run_length_round_trip() {
+        let inputs = [0, 0, 0, 0x01, 0x01, 0x80, 0, 0];
+        let runs = encode_inputs(&inputs);
+        assert_eq!(runs.len(), 4);
+        assert_eq!(decode_inputs(&runs), inputs);
+        assert_eq!(decoded_len(&runs), inputs.len());
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let message = Message::Input {
+            start_frame: 120,
+            ack_frame: 118,
+            runs: encode_inputs(&[0x08; 10]),
+        };
+        let bytes = message.to_bytes().unwrap();
+        assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+        assert!(Message::from_bytes(&[0xFF; 3]).is_err());
+    }
+
+    #[test]
+    fn test_check_rom_id() {
+        let rom = RomId::from_rom_data(&[0xEA; 16], &[]);
+        let other = RomId::from_rom_data(&[0x00; 16], &[]);
+        assert!(check_rom_id(Some(rom), rom).is_ok());
+        assert!(check_rom_id(None, other).is_ok());
+        assert!(check_rom_id(Some(rom), other).is_err());
+    }
+
+    #[test]
+    fn test_chat_message_truncation() {
+        let long = "é".repeat(MAX_CHAT_LENGTH);
+        let message = ChatMessage::new(Some(0), &long);
+        assert!(message.text.len() <= MAX_CHAT_LENGTH);
+        assert_eq!(ChatMessage::new(None, "gg").text, "gg");
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::VecDeque;
+use rnes_common::{RnesError, RnesResult, RomId};
+use crate::{check_rom_id, decode_inputs, decoded_len, encode_inputs, ChatMessage, InputQueue, Message, RollbackGame, SpectatorHub, Transport};
+
+/// Rollback session configuration
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// Local player index (0 = player 1, 1 = player 2)
+    pub local_player: usize,
+    /// Frames of local input delay, trading latency for fewer rollbacks
+    pub input_delay: u32,
+    /// Maximum frames to run ahead of the remote player before stalling
+    pub max_prediction: u32,
+    /// Loaded ROM; the session fails if the peer runs a different one
+    pub rom_id: Option<RomId>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            local_player: 0,
+            input_delay: 2,
+            max_prediction: 8,
+            rom_id: None,
+        }
+    }
+}
+
+/// Outcome of `RollbackSession::advance_frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvanceResult {
+    /// A new frame was simulated
+    Advanced,
+    /// Waiting for remote input; the frame was not run
+    Stalled,
+}
+
+/// Session statistics
+#[derive(Debug, Clone, Default)]
+pub struct NetplayStats {
+    /// Next frame to simulate
+    pub frame: u32,
+    /// Frames with confirmed remote input
+    pub remote_confirmed: u32,
+    /// Number of rollbacks performed
+    pub rollbacks: u32,
+    /// Total frames resimulated by rollbacks
+    pub resimulated_frames: u32,
+    /// Frames skipped waiting for the remote player
+    pub stalled_frames: u32,
+}
+
+/// Simulated frame that may still be rolled back
+#[derive(Debug)]
+struct FrameRecord<S> {
+    frame: u32,
+    /// State before the frame was run
+    state: S,
+    /// Remote input the frame was run with (possibly predicted)
+    remote_input: u8,
+}
+
+/// GGPO-style rollback session between two players
+///
+/// Each frame the local input is sent with all inputs the peer has not
+/// acknowledged yet. Missing remote inputs are predicted; when the real
+/// input arrives and differs, the game is rewound to the first mispredicted
+/// frame and resimulated up to the present.
+#[derive(Debug)]
+pub struct RollbackSession<T: Transport, S> {
+    transport: T,
+    config: SessionConfig,
+    frame: u32,
+    local: InputQueue,
+    remote: InputQueue,
+    /// Local frames acknowledged by the peer
+    remote_ack: u32,
+    history: VecDeque<FrameRecord<S>>,
+    spectators: SpectatorHub<T>,
+    chat: Vec<ChatMessage>,
+    stats: NetplayStats,
+}
+
+impl<T: Transport, S> RollbackSession<T, S> {
+    /// Create session over a connected transport
+    pub fn new(transport: T, config: SessionConfig) -> RnesResult<Self> {
+        if config.local_player > 1 {
+            return Err(RnesError::Netplay(format!("Invalid local player: {}", config.local_player)));
+        }
+
+        // The first `input_delay` frames run with no local input
+        let mut local = InputQueue::new();
+        for _ in 0..config.input_delay {
+            local.push(0);
+        }
+
+        Ok(Self {
+            transport,
+            config,
+            frame: 0,
+            local,
+            remote: InputQueue::new(),
+            remote_ack: 0,
+            history: VecDeque::new(),
+            spectators: SpectatorHub::new(2),
+            chat: Vec::new(),
+            stats: NetplayStats::default(),
+        })
+    }
+
+    /// Get next frame to simulate
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Get session statistics
+    pub fn stats(&self) -> NetplayStats {
+        NetplayStats {
+            frame: self.frame,
+            remote_confirmed: self.remote.confirmed_frames(),
+            ..self.stats.clone()
+        }
+    }
+
+    /// Get session configuration
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Add a spectator connection
+    ///
+    /// Spectators receive inputs once both players' inputs are confirmed, so
+    /// they never see mispredicted frames.
+    pub fn add_spectator(&mut self, transport: T) {
+        self.spectators.add(transport);
+    }
+
+    /// Send a chat line to the peer and spectators
+    pub fn send_chat(&mut self, text: &str) -> RnesResult<()> {
+        let message = ChatMessage::new(Some(self.config.local_player as u8), text);
+        self.transport.send(&Message::Chat(message.clone()).to_bytes()?)?;
+        self.spectators.broadcast_chat(&message)
+    }
+
+    /// Take chat lines received since the last call
+    pub fn take_chat(&mut self) -> Vec<ChatMessage> {
+        std::mem::take(&mut self.chat)
+    }
+
+    /// Add local input, exchange inputs with the peer and run one frame
+    pub fn advance_frame<G>(&mut self, game: &mut G, local_input: u8) -> RnesResult<AdvanceResult>
+    where
+        G: RollbackGame<State = S>,
+    {
+        let confirmed_before = self.remote.confirmed_frames();
+        self.poll()?;
+
+        if self.frame >= self.remote.confirmed_frames() + self.config.max_prediction {
+            self.stats.stalled_frames += 1;
+            self.send_inputs()?;
+            self.rollback_if_needed(game, confirmed_before)?;
+            self.discard_confirmed()?;
+            return Ok(AdvanceResult::Stalled);
+        }
+
+        self.local.push(local_input);
+        self.send_inputs()?;
+        self.rollback_if_needed(game, confirmed_before)?;
+
+        let remote_input = self.remote.predict(self.frame);
+        let state = game.save()?;
+        game.advance(&self.inputs_for(self.frame, remote_input), false)?;
+        self.history.push_back(FrameRecord { frame: self.frame, state, remote_input });
+        self.frame += 1;
+
+        self.discard_confirmed()?;
+        Ok(AdvanceResult::Advanced)
+    }
+
+    /// Receive pending packets from the peer
+    fn poll(&mut self) -> RnesResult<()> {
+        while let Some(packet) = self.transport.recv()? {
+            match Message::from_bytes(&packet) {
+                Ok(Message::Hello { rom_id }) => check_rom_id(self.config.rom_id, rom_id)?,
+                Ok(Message::Input { runs, .. }) if decoded_len(&runs) > self.max_unacked_inputs() => {
+                    tracing::warn!("Dropping netplay input packet with {} inputs", decoded_len(&runs));
+                }
+                Ok(Message::Input { start_frame, ack_frame, runs }) => {
+                    self.remote.add(start_frame, &decode_inputs(&runs));
+                    self.remote_ack = self.remote_ack.max(ack_frame);
+                }
+                Ok(Message::Chat(message)) => {
+                    let player = 1 - self.config.local_player as u8;
+                    self.chat.push(ChatMessage::new(Some(player), &message.text));
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Dropping malformed netplay packet: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Most inputs an honest peer can have unacknowledged
+    ///
+    /// Each side runs at most `max_prediction` frames past the other's
+    /// confirmed inputs plus its input delay, and acks lag by up to one such
+    /// window, so longer packets are dropped before they are expanded.
+    fn max_unacked_inputs(&self) -> usize {
+        2 * (self.config.max_prediction + self.config.input_delay) as usize
+    }
+
+    /// Send all local inputs the peer has not acknowledged
+    fn send_inputs(&mut self) -> RnesResult<()> {
+        if let (Some(rom_id), 0) = (self.config.rom_id, self.remote_ack) {
+            self.transport.send(&Message::Hello { rom_id }.to_bytes()?)?;
+        }
+        
+        let start_frame = self.remote_ack;
+        let message = Message::Input {
+            start_frame,
+            ack_frame: self.remote.confirmed_frames(),
+            runs: encode_inputs(self.local.since(start_frame)),
+        };
+        self.transport.send(&message.to_bytes()?)
+    }
+
+    /// Rewind and resimulate if newly confirmed inputs differ from predictions
+    fn rollback_if_needed<G>(&mut self, game: &mut G, confirmed_before: u32) -> RnesResult<()>
+    where
+        G: RollbackGame<State = S>,
+    {
+        let confirmed = self.remote.confirmed_frames();
+        let mispredicted = self.history.iter().position(|record| {
+            record.frame >= confirmed_before
+                && record.frame < confirmed
+                && self.remote.get(record.frame) != Some(record.remote_input)
+        });
+
+        let Some(index) = mispredicted else {
+            return Ok(());
+        };
+
+        game.load(&self.history[index].state)?;
+        let replayed = self.history.len() - index;
+        self.stats.rollbacks += 1;
+        self.stats.resimulated_frames += replayed as u32;
+        tracing::debug!("Rollback to frame {} ({} frames)", self.history[index].frame, replayed);
+
+        let mut history = std::mem::take(&mut self.history);
+        let result = history.iter_mut().skip(index).enumerate().try_for_each(|(i, record)| {
+            if i > 0 {
+                record.state = game.save()?;
+            }
+            record.remote_input = self.remote.predict(record.frame);
+            game.advance(&self.inputs_for(record.frame, record.remote_input), true)
+        });
+        self.history = history;
+        result
+    }
+
+    /// Stream confirmed frames to spectators, then drop history and inputs
+    /// that can no longer be rolled back
+    fn discard_confirmed(&mut self) -> RnesResult<()> {
+        let confirmed = self.remote.confirmed_frames();
+        let local_confirmed = self.local.confirmed_frames();
+        for frame in self.spectators.next_frame()..confirmed.min(local_confirmed) {
+            let inputs = self.inputs_for(frame, self.remote.get(frame).unwrap_or(0));
+            self.spectators.push(&inputs);
+        }
+        self.spectators.update(&mut self.chat)?;
+
+        while self.history.front().is_some_and(|record| record.frame < confirmed) {
+            self.history.pop_front();
+        }
+        self.remote.discard_before(confirmed);
+        // Local inputs are still needed to resimulate unconfirmed frames
+        self.local.discard_before(self.remote_ack.min(confirmed));
+        Ok(())
+    }
+
+    fn inputs_for(&self, frame: u32, remote_input: u8) -> [u8; 2] {
+        let local_input = self.local.get(frame).unwrap_or(0);
+        let mut inputs = [0; 2];
+        inputs[self.config.local_player] = local_input;
+        inputs[1 - self.config.local_player] = remote_input;
+        inputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_game::{input_emulator, CounterGame};
+    use crate::NetplayGame;
+    use crate::LoopbackTransport;
+
+    #[test]
+    fn test_rollback_sessions_converge() {
+        let (a, b) = LoopbackTransport::pair();
+        // No input delay, so remote inputs always arrive late
+        let config = SessionConfig { input_delay: 0, ..SessionConfig::default() };
+        let mut session_a = RollbackSession::new(a, config.clone()).unwrap();
+        let mut session_b = RollbackSession::new(b, SessionConfig { local_player: 1, ..config }).unwrap();
+        let mut game_a = CounterGame::default();
+        let mut game_b = CounterGame::default();
+
+        // Player 2 changes input often, so player 1 mispredicts
+        for i in 0..120u32 {
+            session_a.advance_frame(&mut game_a, (i / 10) as u8).unwrap();
+            session_b.advance_frame(&mut game_b, (i % 7) as u8).unwrap();
+        }
+        // Drain in-flight inputs with neutral input
+        for _ in 0..20 {
+            session_a.advance_frame(&mut game_a, 0).unwrap();
+            session_b.advance_frame(&mut game_b, 0).unwrap();
+        }
+
+        assert!(session_a.stats().rollbacks > 0);
+        assert_eq!(session_a.frame(), session_b.frame());
+        assert_eq!(game_a.frames, game_b.frames);
+        assert_eq!(game_a.value, game_b.value);
+    }
+
+    #[test]
+    fn test_emulator_rollback_matches_straight_run() {
+        let (a, b) = LoopbackTransport::pair();
+        let config = SessionConfig { input_delay: 0, ..SessionConfig::default() };
+        let mut session_a = RollbackSession::new(a, config.clone()).unwrap();
+        let mut session_b = RollbackSession::new(b, SessionConfig { local_player: 1, ..config }).unwrap();
+        let mut game_a = input_emulator();
+        let mut game_b = input_emulator();
+        let mut reference = input_emulator();
+
+        let inputs = |i: u32| if i < 40 { [(i / 5) as u8, (i % 7) as u8] } else { [0, 0] };
+        for i in 0..50u32 {
+            let [p1, p2] = inputs(i);
+            session_a.advance_frame(&mut game_a, p1).unwrap();
+            session_b.advance_frame(&mut game_b, p2).unwrap();
+            reference.advance(&[p1, p2], false).unwrap();
+        }
+
+        // Resimulated frames must land exactly where an uninterrupted run does
+        assert!(session_a.stats().rollbacks > 0);
+        for game in [&game_a, &game_b] {
+            assert_eq!(game.get_memory_dump(0, 0x800), reference.get_memory_dump(0, 0x800));
+            assert_eq!(game.get_frame_buffer(), reference.get_frame_buffer());
+            assert_eq!(game.cpu_status(), reference.cpu_status());
+        }
+    }
+
+    #[test]
+    fn test_session_rejects_different_rom() {
+        let (a, b) = LoopbackTransport::pair();
+        let rom = |byte| Some(RomId::from_rom_data(&[byte; 16], &[]));
+        let config = SessionConfig { rom_id: rom(1), ..SessionConfig::default() };
+        let mut session_a = RollbackSession::new(a, config).unwrap();
+        let config = SessionConfig { local_player: 1, rom_id: rom(2), ..SessionConfig::default() };
+        let mut session_b = RollbackSession::new(b, config).unwrap();
+        let mut game_a = CounterGame::default();
+        let mut game_b = CounterGame::default();
+
+        session_a.advance_frame(&mut game_a, 0).unwrap();
+        assert!(session_b.advance_frame(&mut game_b, 0).is_err());
+    }
+
+    #[test]
+    fn test_session_drops_oversized_input_packets() {
+        let (a, mut b) = LoopbackTransport::pair();
+        let mut session = RollbackSession::new(a, SessionConfig::default()).unwrap();
+        let mut game = CounterGame::default();
+
+        let runs = vec![crate::InputRun { value: 1, count: u16::MAX }; 8];
+        b.send(&Message::Input { start_frame: 0, ack_frame: 0, runs }.to_bytes().unwrap()).unwrap();
+        session.advance_frame(&mut game, 0).unwrap();
+        assert_eq!(session.stats().remote_confirmed, 0);
+
+        let runs = encode_inputs(&[1; 4]);
+        b.send(&Message::Input { start_frame: 0, ack_frame: 0, runs }.to_bytes().unwrap()).unwrap();
+        session.advance_frame(&mut game, 0).unwrap();
+        assert_eq!(session.stats().remote_confirmed, 4);
+    }
+
+    #[test]
+    fn test_session_stalls_without_peer() {
+        let (a, _b) = LoopbackTransport::pair();
+        let mut session = RollbackSession::new(a, SessionConfig::default()).unwrap();
+        let mut game = CounterGame::default();
+
+        let mut advanced = 0;
+        for _ in 0..20 {
+            if session.advance_frame(&mut game, 1).unwrap() == AdvanceResult::Advanced {
+                advanced += 1;
+            }
+        }
+        assert_eq!(advanced, session.config().max_prediction);
+        assert_eq!(session.stats().stalled_frames, 20 - advanced);
+    }
+}
@@ -0,0 +1,195 @@
+use rnes_common::{RnesError, RnesResult};
+use crate::{decode_inputs, encode_inputs, AdvanceResult, ChatMessage, InputQueue, Message, NetplayGame, Transport};
+
+/// Maximum frames sent to a spectator in one packet
+const MAX_SPECTATE_FRAMES: u32 = 60;
+
+/// Connected spectator
+#[derive(Debug)]
+struct SpectatorLink<T> {
+    transport: T,
+    /// Frames the spectator has received
+    acked: u32,
+}
+
+/// Host side of the spectator stream
+///
+/// Keeps every confirmed input since power-on so spectators that join late
+/// can replay the session from the start and catch up.
+#[derive(Debug)]
+pub struct SpectatorHub<T: Transport> {
+    spectators: Vec<SpectatorLink<T>>,
+    /// Confirmed inputs, one column per player
+    inputs: Vec<Vec<u8>>,
+}
+
+impl<T: Transport> SpectatorHub<T> {
+    /// Create hub for `players` controllers
+    pub fn new(players: usize) -> Self {
+        Self {
+            spectators: Vec::new(),
+            inputs: vec![Vec::new(); players],
+        }
+    }
+
+    /// Add a spectator connection
+    pub fn add(&mut self, transport: T) {
+        self.spectators.push(SpectatorLink { transport, acked: 0 });
+    }
+
+    /// Get number of spectators
+    pub fn len(&self) -> usize {
+        self.spectators.len()
+    }
+
+    /// Check if nobody is watching
+    pub fn is_empty(&self) -> bool {
+        self.spectators.is_empty()
+    }
+
+    /// Get next frame expected by `push`
+    pub fn next_frame(&self) -> u32 {
+        self.inputs.first().map_or(0, |column| column.len() as u32)
+    }
+
+    /// Record confirmed inputs for the next frame
+    pub fn push(&mut self, inputs: &[u8]) {
+        for (player, column) in self.inputs.iter_mut().enumerate() {
+            column.push(inputs.get(player).copied().unwrap_or(0));
+        }
+    }
+
+    /// Receive acknowledgements and chat, and send unacknowledged frames
+    pub fn update(&mut self, chat: &mut Vec<ChatMessage>) -> RnesResult<()> {
+        let next_frame = self.next_frame();
+        for spectator in &mut self.spectators {
+            while let Some(packet) = spectator.transport.recv()? {
+                match Message::from_bytes(&packet) {
+                    Ok(Message::SpectateAck { frames }) => {
+                        spectator.acked = spectator.acked.max(frames.min(next_frame));
+                    }
+                    Ok(Message::Chat(message)) => chat.push(ChatMessage::new(None, &message.text)),
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Dropping malformed spectator packet: {}", e),
+                }
+            }
+
+            if spectator.acked >= next_frame {
+                continue;
+            }
+            let start = spectator.acked as usize;
+            let end = next_frame.min(spectator.acked + MAX_SPECTATE_FRAMES) as usize;
+            let message = Message::Spectate {
+                start_frame: spectator.acked,
+                players: self.inputs.iter()
+                    .map(|column| encode_inputs(&column[start..end]))
+                    .collect(),
+            };
+            spectator.transport.send(&message.to_bytes()?)?;
+        }
+        Ok(())
+    }
+
+    /// Send a chat line to every spectator
+    pub fn broadcast_chat(&mut self, message: &ChatMessage) -> RnesResult<()> {
+        let bytes = Message::Chat(message.clone()).to_bytes()?;
+        for spectator in &mut self.spectators {
+            spectator.transport.send(&bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Watch a session by replaying the host's confirmed inputs
+#[derive(Debug)]
+pub struct SpectatorSession<T: Transport> {
+    transport: T,
+    frame: u32,
+    inputs: Vec<InputQueue>,
+    chat: Vec<ChatMessage>,
+}
+
+impl<T: Transport> SpectatorSession<T> {
+    /// Create spectator for a session with `players` controllers
+    pub fn new(transport: T, players: usize) -> RnesResult<Self> {
+        if players == 0 || players > crate::MAX_PLAYERS {
+            return Err(RnesError::Netplay(format!("Invalid player count: {}", players)));
+        }
+        Ok(Self {
+            transport,
+            frame: 0,
+            inputs: vec![InputQueue::new(); players],
+            chat: Vec::new(),
+        })
+    }
+
+    /// Get next frame to simulate
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Get number of received frames not simulated yet
+    ///
+    /// Frontends can run several frames per tick while this is large to
+    /// catch up with the live session.
+    pub fn frames_behind(&self) -> u32 {
+        self.confirmed_frames().saturating_sub(self.frame)
+    }
+
+    /// Run the next frame if its inputs have arrived
+    pub fn advance_frame<G: NetplayGame>(&mut self, game: &mut G) -> RnesResult<AdvanceResult> {
+        self.poll()?;
+
+        let result = if self.confirmed_frames() > self.frame {
+            let inputs: Vec<u8> = self.inputs.iter()
+                .map(|queue| queue.get(self.frame).unwrap_or(0))
+                .collect();
+            game.advance(&inputs, false)?;
+            self.frame += 1;
+            for queue in &mut self.inputs {
+                queue.discard_before(self.frame);
+            }
+            AdvanceResult::Advanced
+        } else {
+            AdvanceResult::Stalled
+        };
+
+        let ack = Message::SpectateAck { frames: self.confirmed_frames() };
+        self.transport.send(&ack.to_bytes()?)?;
+        Ok(result)
+    }
+
+    /// Send a chat line to the host
+    pub fn send_chat(&mut self, text: &str) -> RnesResult<()> {
+        let message = Message::Chat(ChatMessage::new(None, text));
+        self.transport.send(&message.to_bytes()?)
+    }
+
+    /// Take chat lines received since the last call
+    pub fn take_chat(&mut self) -> Vec<ChatMessage> {
+        std::mem::take(&mut self.chat)
+    }
+
+    fn confirmed_frames(&self) -> u32 {
+        self.inputs.iter()
+            .map(|queue| queue.confirmed_frames())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn poll(&mut self) -> RnesResult<()> {
+        while let Some(packet) = self.transport.recv()? {
+            match Message::from_bytes(&packet) {
+                Ok(Message::Spectate { start_frame, players }) if players.len() == self.inputs.len() => {
+                    for (queue, runs) in self.inputs.iter_mut().zip(&players) {
+                        queue.add(start_frame, &decode_inputs(runs));
+                    }
+                }
+                Ok(Message::Chat(message)) => self.chat.push(message),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Dropping malformed spectator packet: {}", e),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use rnes_common::RnesResult;
+use rnes_core::Emulator;
+use crate::{NetplayGame, RollbackGame};
+
+/// Order-sensitive toy game so any divergence changes the result
+#[derive(Debug, Default)]
+pub(crate) struct CounterGame {
+    pub value: u64,
+    pub frames: u32,
+}
+
+impl NetplayGame for CounterGame {
+    fn advance(&mut self, inputs: &[u8], _resimulating: bool) -> RnesResult<()> {
+        for &input in inputs {
+            self.value = self.value.wrapping_mul(31).wrapping_add(input as u64 + 1);
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+impl RollbackGame for CounterGame {
+    type State = (u64, u32);
+
+    fn save(&mut self) -> RnesResult<Self::State> {
+        Ok((self.value, self.frames))
+    }
+
+    fn load(&mut self, state: &Self::State) -> RnesResult<()> {
+        (self.value, self.frames) = *state;
+        Ok(())
+    }
+}
+
+/// Emulator running an NROM program whose picture and sound follow both controllers
+pub(crate) fn input_emulator() -> Emulator {
+    let assembly = rnes_cpu6502::assemble("
+        reset:  sei
+                ldx #$FF
+                txs
+                lda #$40
+                sta $4017
+                lda #$01
+                sta $4015
+        wait:   bit $2002
+                bpl wait
+                lda #$80
+                sta $2000
+                lda #$0A
+                sta $2001
+        main:   inc $20
+                jmp main
+        nmi:    pha
+                txa
+                pha
+                lda #$01
+                sta $4016
+                lda #$00
+                sta $4016
+                ldx #$08
+        read:   lda $4016
+                lsr a
+                rol $00
+                lda $4017
+                lsr a
+                rol $01
+                dex
+                bne read
+                lda $10
+                asl a
+                adc $00
+                eor $01
+                eor $20
+                sta $10
+                lda #$3F
+                sta $2006
+                lda #$00
+                sta $2006
+                lda $10
+                and #$3F
+                sta $2007
+                lda #$00
+                sta $2006
+                sta $2006
+                lda $10
+                sta $4002
+                lda #$B8
+                sta $4000
+                lda #$08
+                sta $4003
+                pla
+                tax
+                pla
+                rti
+    ", 0xC000).unwrap();
+    let mut prg = vec![0xEA; 16 * 1024];
+    assembly.write_to(&mut prg, 0xC000).unwrap();
+    for (offset, label) in [(0x3FFA, "nmi"), (0x3FFC, "reset"), (0x3FFE, "reset")] {
+        let vector = assembly.label(label).unwrap();
+        prg[offset..offset + 2].copy_from_slice(&vector.to_le_bytes());
+    }
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 8 * 1024]);
+
+    let mut emulator = Emulator::new();
+    emulator.load_rom(rnes_cartridge::Cartridge::from_bytes(&rom).unwrap()).unwrap();
+    emulator.start();
+    emulator
+}
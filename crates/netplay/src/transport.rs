@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use rnes_common::{RnesError, RnesResult};
+
+/// Largest datagram accepted from a peer
+const MAX_PACKET_SIZE: usize = 1500;
+
+/// Unreliable, non-blocking packet transport between two peers
+pub trait Transport {
+    /// Send a packet to the peer
+    fn send(&mut self, data: &[u8]) -> RnesResult<()>;
+
+    /// Receive the next pending packet, if any
+    fn recv(&mut self) -> RnesResult<Option<Vec<u8>>>;
+}
+
+/// UDP transport to a single peer
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpTransport {
+    /// Bind a local address and talk to `peer`
+    pub fn bind<A: ToSocketAddrs>(local: A, peer: SocketAddr) -> RnesResult<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, peer })
+    }
+
+    /// Get bound local address
+    pub fn local_addr(&self) -> RnesResult<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Get peer address
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, data: &[u8]) -> RnesResult<()> {
+        match self.socket.send_to(data, self.peer) {
+            Ok(_) => Ok(()),
+            // Dropped packets are repaired by the next send
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn recv(&mut self) -> RnesResult<Option<Vec<u8>>> {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == self.peer => return Ok(Some(buf[..len].to_vec())),
+                Ok((_, from)) => tracing::debug!("Ignoring packet from unknown peer {}", from),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                // ICMP port unreachable shows up here on some platforms while the peer starts
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => return Ok(None),
+                Err(e) => return Err(RnesError::Io(e)),
+            }
+        }
+    }
+}
+
+/// In-process transport, for tests and local two-player sessions
+#[derive(Debug, Clone)]
+pub struct LoopbackTransport {
+    incoming: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    outgoing: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+impl LoopbackTransport {
+    /// Create two connected endpoints
+    pub fn pair() -> (Self, Self) {
+        let a = Arc::new(Mutex::new(VecDeque::new()));
+        let b = Arc::new(Mutex::new(VecDeque::new()));
+        (
+            Self { incoming: a.clone(), outgoing: b.clone() },
+            Self { incoming: b, outgoing: a },
+        )
+    }
+
+    fn lock(queue: &Mutex<VecDeque<Vec<u8>>>) -> RnesResult<std::sync::MutexGuard<'_, VecDeque<Vec<u8>>>> {
+        queue.lock()
+            .map_err(|_| RnesError::Netplay("Loopback transport lock poisoned".to_string()))
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, data: &[u8]) -> RnesResult<()> {
+        Self::lock(&self.outgoing)?.push_back(data.to_vec());
+        Ok(())
+    }
+
+    fn recv(&mut self) -> RnesResult<Option<Vec<u8>>> {
+        Ok(Self::lock(&self.incoming)?.pop_front())
+    }
+}
@@ -1,9 +1,18 @@
-use rnes_common::{Byte, Word, RnesResult, Pixel, Scanline, Dot, SCREEN_WIDTH, SCREEN_HEIGHT, 
-                  TOTAL_SCANLINES, DOTS_PER_SCANLINE, VISIBLE_SCANLINES, NES_PALETTE};
-use rnes_mappers::Mapper;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use rnes_common::{Byte, Word, RnesError, RnesResult, Pixel, Scanline, Dot, SCREEN_WIDTH, SCREEN_HEIGHT, 
+                  TOTAL_SCANLINES, DOTS_PER_SCANLINE, VISIBLE_SCANLINES, NES_PALETTE, LayerVisibility, Region};
+use rnes_mappers::{Mapper, PpuFetch};
+use serde::{Deserialize, Serialize};
 
 /// Sprite data structure (4 bytes per sprite)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Sprite {
     pub y: Byte,        // Y position (top-left corner)
     pub tile_id: Byte,  // Tile index
@@ -82,7 +91,7 @@ impl Default for SpriteRenderingState {
 }
 
 /// PPU timing phases for optimization
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum PpuPhase {
     PreRender,
     Visible,
@@ -91,7 +100,7 @@ pub enum PpuPhase {
 }
 
 /// Background rendering pipeline state
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackgroundPipeline {
     pub nametable_latch: Byte,
     pub attribute_latch: Byte,
@@ -101,6 +110,9 @@ pub struct BackgroundPipeline {
     pub shift_low: Word,
     pub attr_shift_high: Word,
     pub attr_shift_low: Word,
+    pub nametable_select_latch: Byte, // Nametable (0-3) the latched tile came from
+    pub nametable_shift_high: Word,
+    pub nametable_shift_low: Word,
     pub fine_x: Byte,
     pub tile_counter: Byte,
     pub fetch_phase: u8, // 0-7 for each tile fetch cycle
@@ -117,6 +129,9 @@ impl Default for BackgroundPipeline {
             shift_low: 0,
             attr_shift_high: 0,
             attr_shift_low: 0,
+            nametable_select_latch: 0,
+            nametable_shift_high: 0,
+            nametable_shift_low: 0,
             fine_x: 0,
             tile_counter: 0,
             fetch_phase: 0,
@@ -125,14 +140,18 @@ impl Default for BackgroundPipeline {
 }
 
 /// Sprite rendering pipeline state
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpritePipeline {
     pub sprites_on_scanline: Vec<Sprite>,
-    pub sprite_patterns: Vec<[Byte; 8]>, // Pattern data for each sprite
+    pub sprite_patterns: Vec<[Byte; 8]>, // Color index (0-3) of each pixel, flips applied
+    pub sprite_zero_on_scanline: bool,   // First slot holds OAM sprite 0
     pub sprite_zero_hit: bool,
     pub sprite_overflow: bool,
     pub evaluation_phase: u8, // 0-63 for OAM evaluation
     pub rendering_phase: u8,  // 0-7 for sprite rendering
+    pub secondary_oam: Vec<Sprite>,  // Sprites found for the next line
+    pub secondary_sprite_zero: bool, // First secondary OAM slot holds OAM sprite 0
+    pub overflow_dot: Option<Dot>,   // Dot this line's evaluation sets the overflow flag
 }
 
 impl Default for SpritePipeline {
@@ -140,16 +159,20 @@ impl Default for SpritePipeline {
         Self {
             sprites_on_scanline: Vec::new(),
             sprite_patterns: Vec::new(),
+            sprite_zero_on_scanline: false,
             sprite_zero_hit: false,
             sprite_overflow: false,
             evaluation_phase: 0,
             rendering_phase: 0,
+            secondary_oam: Vec::new(),
+            secondary_sprite_zero: false,
+            overflow_dot: None,
         }
     }
 }
 
 /// Optimized PPU timing state
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PpuTimingState {
     pub phase: PpuPhase,
     pub scanline: Scanline,
@@ -178,10 +201,17 @@ pub struct PpuTimingState {
     
     // Memory access optimization
     pub last_vram_access: Word,
+    #[serde(skip, default = "empty_vram_cache")]
     pub vram_cache: [Byte; 256], // Small cache for frequently accessed VRAM
+    #[serde(skip, default = "empty_vram_cache")]
     pub cache_valid: [bool; 256],
 }
 
+/// Cache contents after a save state load, when nothing is cached
+fn empty_vram_cache<T: Copy + Default>() -> [T; 256] {
+    [T::default(); 256]
+}
+
 impl Default for PpuTimingState {
     fn default() -> Self {
         Self {
@@ -209,7 +239,7 @@ impl Default for PpuTimingState {
 }
 
 /// PPU registers
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PpuRegisters {
     pub ppuctrl: Byte,    // 0x2000
     pub ppumask: Byte,    // 0x2001
@@ -237,11 +267,13 @@ impl Default for PpuRegisters {
 }
 
 /// PPU implementation with timing optimization
+#[derive(Clone)]
 pub struct Ppu {
     registers: PpuRegisters,
     timing_state: PpuTimingState,
     oam: [Byte; 256],           // Object Attribute Memory
     palette_ram: [Byte; 32],    // Palette RAM
+    nametable_ram: [Byte; 4096], // Nametable RAM (2KB CIRAM, 4KB for four-screen)
     frame_buffer: Vec<Pixel>,   // Frame buffer
     mapper: Box<dyn Mapper>,
     
@@ -249,6 +281,132 @@ pub struct Ppu {
     oam_dma_active: bool,
     oam_dma_cycles: u16,
     oam_dma_addr: Word,
+    
+    // Overclocking: idle scanlines inserted after VBlank
+    overclock_scanlines: u16,
+    overclock_dots_remaining: u32,
+    
+    // Set by a soft reset until the end of the next VBlank
+    reset_latch: bool,
+    
+    // $2000/$2001 writes waiting for the PPU to reach their dot
+    delayed_writes: Vec<DelayedWrite>,
+    
+    // Set at the start of VBlank when PPUCTRL enables NMI
+    nmi_pending: bool,
+    
+    // Debug view: layers drawn to the frame buffer
+    layers: LayerVisibility,
+    
+    // Decides which PPUMASK emphasis bits tint red and green
+    region: Region,
+    
+    // Drop sprites past the eighth on a line, as the hardware does
+    sprite_limit: bool,
+    
+    // Lines (first, end) changed so far this frame, and in the last whole frame
+    drawing_dirty: Option<(usize, usize)>,
+    dirty_lines: Option<(usize, usize)>,
+}
+
+/// Internal PPU state for save states
+///
+/// Covers the registers, scroll and rendering pipelines, OAM DMA and
+/// writes still in flight. OAM, palette and nametable RAM are saved
+/// separately, and the frame buffer is rebuilt by the next frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PpuState {
+    registers: PpuRegisters,
+    timing_state: PpuTimingState,
+    oam_dma_active: bool,
+    oam_dma_cycles: u16,
+    oam_dma_addr: Word,
+    overclock_dots_remaining: u32,
+    reset_latch: bool,
+    delayed_writes: Vec<DelayedWrite>,
+    nmi_pending: bool,
+}
+
+impl PpuState {
+    /// Check the values the PPU indexes or shifts with
+    ///
+    /// Deserialized states can hold positions and sprite lists no frame
+    /// reaches, which would make rendering panic.
+    pub fn validate(&self) -> RnesResult<()> {
+        let timing = &self.timing_state;
+        let sprites = &timing.sprite_pipeline;
+        let valid = timing.dot < DOTS_PER_SCANLINE as Dot
+            && (-1..(TOTAL_SCANLINES - 1) as Scanline).contains(&timing.scanline)
+            && timing.x <= 7
+            && sprites.sprites_on_scanline.len() <= 64
+            && sprites.sprite_patterns.len() == sprites.sprites_on_scanline.len()
+            && sprites.secondary_oam.len() <= 64;
+        if valid {
+            Ok(())
+        } else {
+            Err(RnesError::CorruptSaveState("PPU state out of range".into()))
+        }
+    }
+}
+
+/// Register write that lands partway through a CPU instruction
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct DelayedWrite {
+    dots: u32,
+    addr: Word,
+    value: Byte,
+}
+
+/// Front-most opaque sprite pixel at a screen position
+#[derive(Debug, Clone, Copy)]
+struct SpritePixel {
+    color: Byte,
+    palette: Byte,
+    behind_background: bool,
+    sprite_zero: bool,
+}
+
+/// Maximum number of extra scanlines per frame
+pub const MAX_OVERCLOCK_SCANLINES: u16 = 200;
+
+/// Color of the debug tile grid overlay
+pub const GRID_COLOR: Pixel = Pixel { r: 255, g: 0, b: 255, a: 255 };
+
+/// Palette RAM index for a $3F00-$3FFF address
+///
+/// Entry 0 of each sprite palette ($3F10/$3F14/$3F18/$3F1C) is the same
+/// byte as the matching background entry ($3F00/$3F04/$3F08/$3F0C).
+fn palette_index(addr: Word) -> usize {
+    let index = (addr & 0x1F) as usize;
+    if index & 0x13 == 0x10 { index & 0x0F } else { index }
+}
+
+/// Color of a palette entry under the PPUMASK emphasis bits
+///
+/// Bit 7 emphasizes blue on every console, but bits 5 and 6 select red and
+/// green on NTSC and the other way round on PAL and Dendy. Emphasis darkens
+/// the channels that are not emphasized, or every channel once all three
+/// bits are set; the black $xE/$xF columns are left alone.
+pub fn palette_color(color_id: Byte, mask: Byte, region: Region) -> Pixel {
+    let color_id = color_id & 0x3F;
+    let pixel = Pixel::from_rgb(NES_PALETTE[color_id as usize]);
+    if mask & 0xE0 == 0 || color_id & 0x0E == 0x0E {
+        return pixel;
+    }
+    let (red_bit, green_bit) = match region {
+        Region::Ntsc => (0x20, 0x40),
+        Region::Pal | Region::Dendy => (0x40, 0x20),
+    };
+    // Roughly the 0.816 attenuation measured on hardware
+    let all = mask & 0xE0 == 0xE0;
+    let dim = |channel: u8, emphasized: bool| {
+        if emphasized && !all { channel } else { (channel as u16 * 209 / 256) as u8 }
+    };
+    Pixel::new(
+        dim(pixel.r, mask & red_bit != 0),
+        dim(pixel.g, mask & green_bit != 0),
+        dim(pixel.b, mask & 0x80 != 0),
+    )
 }
 
 impl Ppu {
@@ -258,14 +416,71 @@ impl Ppu {
             timing_state: PpuTimingState::default(),
             oam: [0; 256],
             palette_ram: [0; 32],
+            nametable_ram: [0; 4096],
             frame_buffer: vec![Pixel::BLACK; SCREEN_WIDTH * SCREEN_HEIGHT],
             mapper,
             oam_dma_active: false,
             oam_dma_cycles: 0,
             oam_dma_addr: 0,
+            overclock_scanlines: 0,
+            overclock_dots_remaining: 0,
+            reset_latch: false,
+            delayed_writes: Vec::new(),
+            nmi_pending: false,
+            layers: LayerVisibility::default(),
+            region: Region::Ntsc,
+            sprite_limit: true,
+            // Nothing has been shown yet, so the whole first frame is new
+            drawing_dirty: Some((0, SCREEN_HEIGHT)),
+            dirty_lines: None,
         }
     }
     
+    /// Reset PPU as the console's reset button does
+    ///
+    /// PPUCTRL, PPUMASK, the scroll registers and the write toggle are
+    /// cleared; OAM, palette RAM, the VRAM address and the frame timing are
+    /// kept. Writes to $2000, $2001, $2005 and $2006 are ignored until the
+    /// end of the next VBlank.
+    pub fn reset(&mut self) {
+        self.registers.ppuctrl = 0;
+        self.registers.ppumask = 0;
+        self.registers.ppuscroll = 0;
+        self.registers.ppudata = 0;
+        self.timing_state.t = 0;
+        self.timing_state.x = 0;
+        self.timing_state.w = false;
+        self.timing_state.rendering_enabled = false;
+        self.timing_state.background_enabled = false;
+        self.timing_state.sprites_enabled = false;
+        self.delayed_writes.clear();
+        self.nmi_pending = false;
+        self.reset_latch = true;
+    }
+    
+    /// Check if register writes are being ignored after a reset
+    pub fn in_reset(&self) -> bool {
+        self.reset_latch
+    }
+    
+    /// Set number of extra scanlines inserted after VBlank each frame
+    ///
+    /// During these scanlines the PPU is frozen while the CPU keeps running,
+    /// giving games more time per frame without moving NMI or rendering.
+    pub fn set_overclock_scanlines(&mut self, scanlines: u16) {
+        self.overclock_scanlines = scanlines.min(MAX_OVERCLOCK_SCANLINES);
+    }
+    
+    /// Get number of extra scanlines per frame
+    pub fn overclock_scanlines(&self) -> u16 {
+        self.overclock_scanlines
+    }
+    
+    /// Check if the PPU is currently paused for overclocking
+    pub fn in_overclock(&self) -> bool {
+        self.overclock_dots_remaining > 0
+    }
+    
     /// Start OAM DMA transfer
     pub fn start_oam_dma(&mut self, page: Byte) {
         self.oam_dma_active = true;
@@ -274,7 +489,7 @@ impl Ppu {
     }
     
     /// Step OAM DMA transfer
-    pub fn step_oam_dma(&mut self, memory: &dyn rnes_common::MemoryAccess) -> RnesResult<()> {
+    pub fn step_oam_dma(&mut self, memory: &mut dyn rnes_common::MemoryAccess) -> RnesResult<()> {
         if !self.oam_dma_active {
             return Ok(());
         }
@@ -300,11 +515,24 @@ impl Ppu {
         Ok(())
     }
     
+    /// Finish OAM DMA in one go with the 256 bytes of its source page
+    pub fn complete_oam_dma(&mut self, page: &[Byte; 256]) {
+        self.oam.copy_from_slice(page);
+        self.oam_dma_addr = self.oam_dma_addr.wrapping_add(256);
+        self.oam_dma_cycles = 513;
+        self.oam_dma_active = false;
+    }
+
     /// Check if OAM DMA is active
     pub fn oam_dma_active(&self) -> bool {
         self.oam_dma_active
     }
     
+    /// Take a pending NMI, clearing it
+    pub fn take_nmi(&mut self) -> bool {
+        core::mem::take(&mut self.nmi_pending)
+    }
+    
     /// Optimized PPU step with precise timing
     pub fn step(&mut self) -> RnesResult<()> {
         // Handle OAM DMA if active
@@ -312,6 +540,17 @@ impl Ppu {
             return Ok(());
         }
         
+        // Idle through overclock scanlines
+        if self.overclock_dots_remaining > 0 {
+            self.overclock_dots_remaining -= 1;
+            return Ok(());
+        }
+        
+        // Land mid-instruction register writes on their dot
+        if !self.delayed_writes.is_empty() {
+            self.step_delayed_writes()?;
+        }
+        
         // Update timing state
         self.update_timing_state();
         
@@ -329,6 +568,21 @@ impl Ppu {
         Ok(())
     }
     
+    /// Apply delayed writes that are due and count down the rest
+    fn step_delayed_writes(&mut self) -> RnesResult<()> {
+        let mut index = 0;
+        while index < self.delayed_writes.len() {
+            if self.delayed_writes[index].dots == 0 {
+                let write = self.delayed_writes.remove(index);
+                self.write_register(write.addr, write.value)?;
+            } else {
+                self.delayed_writes[index].dots -= 1;
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+    
     /// Update PPU timing state
     fn update_timing_state(&mut self) {
         // Update dot and scanline
@@ -337,18 +591,23 @@ impl Ppu {
             self.timing_state.dot = 0;
             self.timing_state.scanline += 1;
             
-            if self.timing_state.scanline >= TOTAL_SCANLINES as Scanline {
+            // Scanlines run -1 (pre-render) to 260
+            if self.timing_state.scanline >= (TOTAL_SCANLINES - 1) as Scanline {
                 self.timing_state.scanline = -1;
-                self.timing_state.frame_count += 1;
+                self.timing_state.frame_count = self.timing_state.frame_count.wrapping_add(1);
+                self.dirty_lines = self.drawing_dirty.take();
+                self.overclock_dots_remaining = self.overclock_scanlines as u32 * DOTS_PER_SCANLINE as u32;
             }
+            
+            self.mapper.notify_scanline(self.timing_state.scanline);
         }
         
         // Update phase based on scanline
-        self.timing_state.phase = if self.timing_state.scanline < VISIBLE_SCANLINES as Scanline {
+        self.timing_state.phase = if self.timing_state.scanline < 0 {
+            PpuPhase::PreRender
+        } else if self.timing_state.scanline < VISIBLE_SCANLINES as Scanline {
             PpuPhase::Visible
         } else if self.timing_state.scanline == VISIBLE_SCANLINES as Scanline {
-            PpuPhase::PreRender
-        } else if self.timing_state.scanline == (VISIBLE_SCANLINES + 1) as Scanline {
             PpuPhase::PostRender
         } else {
             PpuPhase::VBlank
@@ -364,20 +623,24 @@ impl Ppu {
     fn step_pre_render(&mut self) -> RnesResult<()> {
         let dot = self.timing_state.dot as usize;
         
-        // Clear VBlank flag at dot 1
+        // Clear VBlank, sprite 0 hit and overflow at dot 1
         if dot == 1 {
             self.timing_state.vblank = false;
-            self.registers.ppustatus &= !0x80;
-        }
-        
-        // Background rendering (same as visible scanlines)
-        if self.timing_state.background_enabled {
-            self.step_background_rendering()?;
+            self.timing_state.sprite_zero_hit = false;
+            self.timing_state.sprite_overflow = false;
+            self.timing_state.sprite_pipeline.sprite_zero_hit = false;
+            self.timing_state.sprite_pipeline.sprite_overflow = false;
+            self.registers.ppustatus &= !0xE0;
+            self.reset_latch = false;
         }
         
-        // Sprite evaluation for next scanline
-        if self.timing_state.sprites_enabled && dot >= 257 && dot <= 320 {
-            self.step_sprite_evaluation()?;
+        // Same fetches as a visible line, then reload the vertical scroll
+        if self.timing_state.rendering_enabled {
+            self.step_background_fetching()?;
+            self.step_sprite_fetching()?;
+            if (280..=304).contains(&dot) {
+                self.copy_vertical_scroll();
+            }
         }
         
         Ok(())
@@ -388,24 +651,28 @@ impl Ppu {
         let dot = self.timing_state.dot as usize;
         let scanline = self.timing_state.scanline as usize;
         
-        // Background rendering
-        if self.timing_state.background_enabled {
-            self.step_background_rendering()?;
-        }
-        
-        // Sprite evaluation (cycles 1-64)
-        if self.timing_state.sprites_enabled && dot >= 1 && dot <= 64 {
-            self.step_sprite_evaluation()?;
+        // Fetches and scroll updates run whenever either layer is enabled
+        if self.timing_state.rendering_enabled {
+            self.step_background_fetching()?;
         }
         
-        // Sprite rendering (cycles 65-256)
-        if self.timing_state.sprites_enabled && dot >= 65 && dot <= 256 {
-            self.step_sprite_rendering(scanline)?;
+        if (1..=256).contains(&dot) {
+            let mut color = self.output_pixel(dot - 1)?;
+            if let Some(grid) = self.layers.grid {
+                if (dot - 1).is_multiple_of(grid.size()) || scanline.is_multiple_of(grid.size()) {
+                    color = GRID_COLOR;
+                }
+            }
+            let index = scanline * SCREEN_WIDTH + dot - 1;
+            if self.frame_buffer[index] != color {
+                self.frame_buffer[index] = color;
+                self.mark_line_dirty(scanline);
+            }
         }
         
-        // Background tile fetching for next scanline (cycles 257-320)
-        if self.timing_state.background_enabled && dot >= 257 && dot <= 320 {
-            self.step_background_fetching()?;
+        // Sprites for the next line replace this line's once it is drawn
+        if self.timing_state.rendering_enabled {
+            self.step_sprite_fetching()?;
         }
         
         Ok(())
@@ -426,205 +693,397 @@ impl Ppu {
         if scanline == 241 && dot == 1 {
             self.timing_state.vblank = true;
             self.registers.ppustatus |= 0x80;
+            if self.registers.ppuctrl & 0x80 != 0 {
+                self.nmi_pending = true;
+            }
         }
         
         Ok(())
     }
     
-    /// Optimized background rendering step
-    fn step_background_rendering(&mut self) -> RnesResult<()> {
+    /// Background fetches, shift registers and scroll updates for one dot
+    ///
+    /// Dots 1-256 fetch the tiles for the current line and 321-336 prefetch
+    /// the first two tiles of the next. Coarse X advances after every tile,
+    /// fine Y at dot 256, and dot 257 reloads the horizontal scroll from `t`.
+    fn step_background_fetching(&mut self) -> RnesResult<()> {
         let dot = self.timing_state.dot as usize;
-        let scanline = self.timing_state.scanline as usize;
         
-        // Render pixel (cycles 1-256)
-        if dot >= 1 && dot <= 256 {
-            let x = (dot - 1) as usize;
-            
-            // Get pixel color from background
-            let color = self.get_background_pixel_optimized(x, scanline)?;
-            
-            // Write to frame buffer (only for visible scanlines)
-            if scanline < SCREEN_HEIGHT {
-                let pixel_index = scanline * SCREEN_WIDTH + x;
-                if pixel_index < self.frame_buffer.len() {
-                    self.frame_buffer[pixel_index] = color;
-                }
-            }
-        }
-        
-        // Background tile fetching (cycles 1-256)
-        if dot >= 1 && dot <= 256 {
-            self.step_background_fetching()?;
-        }
-        
-        // Shift background registers
-        if dot >= 1 && dot <= 256 {
+        if matches!(dot, 2..=257 | 322..=337) {
             self.shift_background_registers();
+            // The tile fetched over the previous 8 dots enters the shifters
+            if (dot - 1).is_multiple_of(8) {
+                self.load_background_registers();
+            }
         }
         
-        Ok(())
-    }
-    
-    /// Optimized background tile fetching
-    fn step_background_fetching(&mut self) -> RnesResult<()> {
-        let dot = self.timing_state.dot as usize;
-        let cycle = if dot >= 1 && dot <= 256 {
-            dot
-        } else if dot >= 257 && dot <= 320 {
-            dot - 256
-        } else {
-            return Ok(());
-        };
-        
-        // Tile fetching cycles (every 8 cycles)
-        if cycle >= 1 && cycle <= 256 {
-            let tile_cycle = (cycle - 1) % 8;
-            
-            match tile_cycle {
+        if matches!(dot, 1..=256 | 321..=336) {
+            match (dot - 1) % 8 {
                 0 => {
                     // Fetch nametable byte
                     let addr = self.get_nametable_address()?;
-                    self.timing_state.bg_pipeline.nametable_latch = self.read_vram_cached(addr)?;
+                    let fetch = self.nametable_fetch(dot);
+                    self.timing_state.bg_pipeline.nametable_latch = self.fetch_vram(addr, fetch)?;
+                    self.timing_state.bg_pipeline.nametable_select_latch = ((addr >> 10) & 0x03) as Byte;
                 }
                 2 => {
                     // Fetch attribute byte
                     let addr = self.get_attribute_address()?;
-                    let attr_byte = self.read_vram_cached(addr)?;
+                    let attr_byte = self.fetch_vram(addr, PpuFetch::Attribute)?;
                     let attr_shift = self.get_attribute_shift()?;
                     self.timing_state.bg_pipeline.attribute_latch = (attr_byte >> attr_shift) & 0x03;
                 }
                 4 => {
                     // Fetch pattern table low byte
                     let addr = self.get_pattern_address(false)?;
-                    self.timing_state.bg_pipeline.pattern_low_latch = self.read_vram_cached(addr)?;
+                    self.timing_state.bg_pipeline.pattern_low_latch = self.fetch_vram(addr, PpuFetch::BackgroundPattern)?;
                 }
                 6 => {
                     // Fetch pattern table high byte
                     let addr = self.get_pattern_address(true)?;
-                    self.timing_state.bg_pipeline.pattern_high_latch = self.read_vram_cached(addr)?;
-                }
-                7 => {
-                    // Load shift registers
-                    self.load_background_registers();
-                    
-                    // Increment X scroll
-                    self.increment_scroll_x();
+                    self.timing_state.bg_pipeline.pattern_high_latch = self.fetch_vram(addr, PpuFetch::BackgroundPattern)?;
                 }
+                7 => self.increment_scroll_x(),
                 _ => {}
             }
         }
         
+        match dot {
+            256 => self.increment_scroll_y(),
+            257 => self.copy_horizontal_scroll(),
+            _ => {}
+        }
+        
         Ok(())
     }
     
-    /// Optimized sprite evaluation
-    fn step_sprite_evaluation(&mut self) -> RnesResult<()> {
-        let dot = self.timing_state.dot as usize;
-        
-        // Sprite evaluation happens in cycles 1-64
-        if dot >= 1 && dot <= 64 {
-            let sprite_index = (dot - 1) / 2; // 2 cycles per sprite
-            
-            if sprite_index < 64 {
-                let oam_addr = sprite_index * 4;
-                let sprite_y = self.oam[oam_addr];
-                let sprite_tile_id = self.oam[oam_addr + 1];
-                let sprite_attributes = self.oam[oam_addr + 2];
-                let sprite_x = self.oam[oam_addr + 3];
-                
-                let sprite_height = if self.registers.ppuctrl & 0x20 != 0 { 16 } else { 8 };
-                let scanline = self.timing_state.scanline as usize;
-                
-                // Check if sprite is visible on this scanline
-                if sprite_y < 240 && sprite_y + sprite_height > scanline as Byte && sprite_y <= scanline as Byte {
-                    let sprite = Sprite {
-                        y: sprite_y,
-                        tile_id: sprite_tile_id,
-                        attributes: sprite_attributes,
-                        x: sprite_x,
-                    };
-                    
-                    self.timing_state.sprite_pipeline.sprites_on_scanline.push(sprite);
-                    
-                    // Check for sprite zero hit
-                    if sprite_index == 0 {
-                        self.timing_state.sprite_pipeline.sprite_zero_hit = true;
-                    }
-                    
-                    // Check for sprite overflow (more than 8 sprites on scanline)
-                    if self.timing_state.sprite_pipeline.sprites_on_scanline.len() > 8 {
-                        self.timing_state.sprite_pipeline.sprite_overflow = true;
-                    }
-                }
+    /// Line and tile column a background nametable fetch at `dot` is for
+    ///
+    /// Dots 321-336 prefetch columns 0 and 1 of the next line; dots 1-256
+    /// fetch columns 2-33 of the current one.
+    fn nametable_fetch(&self, dot: usize) -> PpuFetch {
+        let scanline = self.timing_state.scanline;
+        if dot >= 321 {
+            PpuFetch::Nametable { line: scanline + 1, column: ((dot - 321) / 8) as u8 }
+        } else {
+            PpuFetch::Nametable { line: scanline, column: ((dot - 1) / 8 + 2) as u8 }
+        }
+    }
+    
+    /// Sprite evaluation and pattern fetches for the next line
+    ///
+    /// Secondary OAM is cleared at dot 1 and filled by the evaluation at
+    /// dot 65. At dot 257 it replaces the sprites of the line just drawn,
+    /// then each of the eight slots fetches its two pattern bytes over 8
+    /// dots up to dot 320. Empty slots fetch tile $FF like the hardware, so
+    /// mappers watching A12 see the same accesses.
+    fn step_sprite_fetching(&mut self) -> RnesResult<()> {
+        let dot = self.timing_state.dot;
+        match dot {
+            1 => {
+                let pipeline = &mut self.timing_state.sprite_pipeline;
+                pipeline.secondary_oam.clear();
+                pipeline.secondary_sprite_zero = false;
+                pipeline.overflow_dot = None;
             }
+            65 => self.evaluate_sprites(),
+            _ => {}
         }
+        if self.timing_state.sprite_pipeline.overflow_dot == Some(dot) {
+            self.timing_state.sprite_pipeline.sprite_overflow = true;
+            self.timing_state.sprite_overflow = true;
+            self.registers.ppustatus |= 0x20;
+        }
+        
+        let dot = dot as usize;
+        if !(257..=320).contains(&dot) {
+            return Ok(());
+        }
+        if dot == 257 {
+            let pipeline = &mut self.timing_state.sprite_pipeline;
+            pipeline.sprites_on_scanline.clear();
+            pipeline.sprites_on_scanline.append(&mut pipeline.secondary_oam);
+            pipeline.sprite_zero_on_scanline = core::mem::take(&mut pipeline.secondary_sprite_zero);
+            pipeline.sprite_patterns.clear();
+            pipeline.sprite_patterns.resize(pipeline.sprites_on_scanline.len(), [0; 8]);
+        }
+        if dot == 320 {
+            self.load_extra_sprite_patterns()?;
+        }
+        
+        let slot = (dot - 257) / 8;
+        let plane = match (dot - 257) % 8 {
+            4 => 0,
+            6 => 1,
+            _ => return Ok(()),
+        };
+        
+        let sprite = self.timing_state.sprite_pipeline.sprites_on_scanline.get(slot).copied();
+        let Some(sprite) = sprite else {
+            let addr = self.sprite_pattern_address(0xFF, 0, plane == 1);
+            self.fetch_vram(addr, PpuFetch::SpritePattern)?;
+            return Ok(());
+        };
+        
+        let addr = self.sprite_row_address(sprite, plane == 1);
+        let bits = self.fetch_vram(addr, PpuFetch::SpritePattern)?;
+        self.load_sprite_pattern(slot, sprite, plane, bits);
         
         Ok(())
     }
     
-    /// Optimized sprite rendering
-    fn step_sprite_rendering(&mut self, scanline: usize) -> RnesResult<()> {
-        // Render sprites in order of Y position (simplified for timing optimization)
-        let sprites_to_render = self.timing_state.sprite_pipeline.sprites_on_scanline.clone();
-        for sprite in sprites_to_render {
-            self.render_sprite(sprite, scanline)?;
+    /// Fetch the patterns of the sprites past the eighth, kept when the
+    /// sprite limit is off
+    ///
+    /// The hardware has no fetch slots for them, so they are read without
+    /// the address changes mappers watch for.
+    fn load_extra_sprite_patterns(&mut self) -> RnesResult<()> {
+        for slot in 8..self.timing_state.sprite_pipeline.sprites_on_scanline.len() {
+            let sprite = self.timing_state.sprite_pipeline.sprites_on_scanline[slot];
+            for plane in 0..2 {
+                let addr = self.sprite_row_address(sprite, plane == 1);
+                let bits = match self.mapper.ppu_read(addr, PpuFetch::SpritePattern) {
+                    Some(value) => value,
+                    None => self.mapper.read_chr(addr)?,
+                };
+                self.load_sprite_pattern(slot, sprite, plane, bits);
+            }
         }
-        
         Ok(())
     }
     
-    /// Get optimized background pixel color
-    fn get_background_pixel_optimized(&self, x: usize, _scanline: usize) -> RnesResult<Pixel> {
-        // Calculate fine X position
-        let fine_x = (x + self.timing_state.bg_pipeline.fine_x as usize) % 8;
+    /// Pattern address of the row of `sprite` on the current line
+    fn sprite_row_address(&self, sprite: Sprite, high: bool) -> Word {
+        // Masked so a sprite list from a corrupt state can't underflow
+        let mut row = (self.timing_state.scanline - sprite.y as Scanline) as Word & (self.sprite_height() - 1);
+        if sprite.flip_vertical() {
+            row = self.sprite_height() - 1 - row;
+        }
+        self.sprite_pattern_address(sprite.tile_id, row, high)
+    }
+    
+    /// Merge one fetched bit plane into the pixels of a sprite slot
+    fn load_sprite_pattern(&mut self, slot: usize, sprite: Sprite, plane: u8, bits: Byte) {
+        let pattern = &mut self.timing_state.sprite_pipeline.sprite_patterns[slot];
+        for (pixel, color) in pattern.iter_mut().enumerate() {
+            let shift = if sprite.flip_horizontal() { pixel } else { 7 - pixel };
+            *color |= ((bits >> shift) & 1) << plane;
+        }
+    }
+    
+    /// Copy the first eight sprites in OAM that cover the next line into
+    /// secondary OAM
+    ///
+    /// OAM Y is one less than the sprite's top line, so a sprite that covers
+    /// the current line relative to Y is drawn on the next one. Nothing is
+    /// drawn on line 0.
+    ///
+    /// The hardware spends dots 65-256 on this: 2 dots to check each sprite's
+    /// Y and 6 more to copy one in range. After the eighth it keeps looking
+    /// for a ninth, but a hardware bug advances the byte index along with the
+    /// sprite index, so tile numbers, attributes and X positions get compared
+    /// as Y. The overflow flag is raised on the dot that check succeeds, which
+    /// is why it can be missed or set spuriously like on a real console.
+    fn evaluate_sprites(&mut self) {
+        let scanline = self.timing_state.scanline;
+        if scanline < 0 {
+            return;
+        }
+        let height = self.sprite_height() as Scanline;
+        let in_range = |y: Byte| (0..height).contains(&(scanline - y as Scanline));
+        let pipeline = &mut self.timing_state.sprite_pipeline;
         
-        // Get pixel data from shift registers
-        let bit0 = (self.timing_state.bg_pipeline.shift_low >> (15 - fine_x)) & 1;
-        let bit1 = (self.timing_state.bg_pipeline.shift_high >> (15 - fine_x)) & 1;
-        let attr_bit0 = (self.timing_state.bg_pipeline.attr_shift_low >> (7 - fine_x)) & 1;
-        let attr_bit1 = (self.timing_state.bg_pipeline.attr_shift_high >> (7 - fine_x)) & 1;
+        let mut dot: Dot = 65;
+        let mut n = 0;
+        while n < 64 && pipeline.secondary_oam.len() < 8 {
+            let entry = &self.oam[n * 4..n * 4 + 4];
+            if in_range(entry[0]) {
+                if n == 0 {
+                    pipeline.secondary_sprite_zero = true;
+                }
+                pipeline.secondary_oam.push(Sprite {
+                    y: entry[0],
+                    tile_id: entry[1],
+                    attributes: entry[2],
+                    x: entry[3],
+                });
+                dot += 8;
+            } else {
+                dot += 2;
+            }
+            n += 1;
+        }
+        let last_copied = n;
         
-        let palette_index = (attr_bit1 << 1) | attr_bit0;
-        let color_index = (bit1 << 1) | bit0;
+        // Overflow search, with the byte index m drifting with each miss
+        let mut m = 0;
+        while n < 64 && pipeline.secondary_oam.len() == 8 {
+            if in_range(self.oam[n * 4 + m]) {
+                pipeline.overflow_dot = Some(dot + 1);
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 3;
+            dot += 2;
+        }
         
-        if color_index == 0 {
-            // Background color (universal)
-            Ok(Pixel::from_rgb(NES_PALETTE[0]))
+        // Without the limit, every later sprite in range is kept too, in
+        // OAM order so front-to-back priority still holds
+        if !self.sprite_limit {
+            for entry in self.oam[last_copied * 4..].chunks_exact(4) {
+                if in_range(entry[0]) {
+                    pipeline.secondary_oam.push(Sprite {
+                        y: entry[0],
+                        tile_id: entry[1],
+                        attributes: entry[2],
+                        x: entry[3],
+                    });
+                }
+            }
+        }
+    }
+    
+    /// Sprite height in pixels from PPUCTRL
+    fn sprite_height(&self) -> Word {
+        if self.registers.ppuctrl & 0x20 != 0 { 16 } else { 8 }
+    }
+    
+    /// Pattern address of one row of a sprite tile
+    ///
+    /// 8x8 sprites use the table selected by PPUCTRL bit 3; 8x16 sprites take
+    /// the table from bit 0 of the tile number and use an even/odd tile pair.
+    fn sprite_pattern_address(&self, tile_id: Byte, row: Word, high: bool) -> Word {
+        let (table, tile) = if self.sprite_height() == 16 {
+            ((tile_id as Word & 0x01) * 0x1000, (tile_id as Word & 0xFE) + row / 8)
+        } else {
+            ((self.registers.ppuctrl as Word & 0x08) << 9, tile_id as Word)
+        };
+        let plane = if high { 8 } else { 0 };
+        table + (tile << 4) + (row & 0x07) + plane
+    }
+    
+    /// Front-most opaque sprite pixel at `x` on the current line
+    fn sprite_pixel(&self, x: usize) -> Option<SpritePixel> {
+        let pipeline = &self.timing_state.sprite_pipeline;
+        pipeline.sprites_on_scanline.iter()
+            .zip(&pipeline.sprite_patterns)
+            .enumerate()
+            .find_map(|(slot, (sprite, pattern))| {
+                let offset = x.checked_sub(sprite.x as usize).filter(|&offset| offset < 8)?;
+                let color = pattern[offset];
+                (color != 0).then_some(SpritePixel {
+                    color,
+                    palette: sprite.palette(),
+                    behind_background: sprite.behind_background(),
+                    sprite_zero: slot == 0 && pipeline.sprite_zero_on_scanline,
+                })
+            })
+    }
+    
+    /// Combine the background and sprite pixels at `x` on the current line
+    ///
+    /// Transparency is decided on color indices, before palette lookup. An
+    /// opaque sprite pixel wins unless its priority bit puts it behind an
+    /// opaque background pixel; where both are transparent the backdrop
+    /// shows. Sprite 0 over an opaque background pixel sets the hit flag.
+    fn output_pixel(&mut self, x: usize) -> RnesResult<Pixel> {
+        let mask = self.registers.ppumask;
+        let (bg_color, bg_palette) = if self.timing_state.background_enabled && (x >= 8 || mask & 0x02 != 0) {
+            self.background_pixel()
         } else {
-            // Get color from palette
-            let palette_addr = 0x3F00 + (palette_index << 2) + color_index;
-            let color_id = self.read_palette_ram(palette_addr)?;
-            Ok(Pixel::from_rgb(NES_PALETTE[color_id as usize]))
+            (0, 0)
+        };
+        let sprite = if self.timing_state.sprites_enabled && (x >= 8 || mask & 0x04 != 0) {
+            self.sprite_pixel(x)
+        } else {
+            None
+        };
+        
+        if let Some(sprite) = sprite {
+            if sprite.sprite_zero && bg_color != 0 && x != 255 {
+                self.timing_state.sprite_zero_hit = true;
+                self.timing_state.sprite_pipeline.sprite_zero_hit = true;
+                self.registers.ppustatus |= 0x40;
+            }
         }
+        
+        // Hidden layers drop out only after the hit check above
+        let bg_color = if self.layers.background && self.layers.nametables[self.background_nametable()] {
+            bg_color
+        } else {
+            0
+        };
+        let sprite = sprite.filter(|_| self.layers.sprites);
+        
+        let palette_addr = match sprite {
+            Some(sprite) if bg_color == 0 || !sprite.behind_background => {
+                0x3F10 + ((sprite.palette as Word) << 2) + sprite.color as Word
+            }
+            _ if bg_color != 0 => 0x3F00 + ((bg_palette as Word) << 2) + bg_color as Word,
+            _ => return Ok(self.backdrop_pixel()),
+        };
+        let color_id = self.read_palette_ram(palette_addr)?;
+        Ok(palette_color(color_id, mask, self.region))
+    }
+    
+    /// Background color index (0-3) and palette (0-3) at the current dot,
+    /// selected by fine X
+    fn background_pixel(&self) -> (Byte, Byte) {
+        let pipeline = &self.timing_state.bg_pipeline;
+        let bit = 0x8000 >> self.timing_state.x;
+        let color = ((pipeline.shift_high & bit != 0) as Byte) << 1 | (pipeline.shift_low & bit != 0) as Byte;
+        let palette = ((pipeline.attr_shift_high & bit != 0) as Byte) << 1 | (pipeline.attr_shift_low & bit != 0) as Byte;
+        (color, palette)
+    }
+    
+    /// Nametable (0-3) the background pixel at the current dot came from
+    fn background_nametable(&self) -> usize {
+        let pipeline = &self.timing_state.bg_pipeline;
+        let bit = 0x8000 >> self.timing_state.x;
+        ((pipeline.nametable_shift_high & bit != 0) as usize) << 1 | (pipeline.nametable_shift_low & bit != 0) as usize
+    }
+    
+    /// Universal background color from $3F00, shown where both layers are
+    /// transparent
+    ///
+    /// While rendering is off and `v` points into palette RAM, the PPU shows
+    /// that entry instead.
+    fn backdrop_pixel(&self) -> Pixel {
+        let v = self.timing_state.v & 0x3FFF;
+        let addr = if !self.timing_state.rendering_enabled && v >= 0x3F00 { v } else { 0x3F00 };
+        palette_color(self.palette_ram[palette_index(addr)], self.registers.ppumask, self.region)
     }
     
-    /// Load background shift registers
+    /// Load the fetched tile into the low byte of the shift registers
     fn load_background_registers(&mut self) {
-        // Shift existing data
-        self.timing_state.bg_pipeline.shift_high = 
-            (self.timing_state.bg_pipeline.shift_high << 8) | 
-            (self.timing_state.bg_pipeline.pattern_high_latch as Word);
-        self.timing_state.bg_pipeline.shift_low = 
-            (self.timing_state.bg_pipeline.shift_low << 8) | 
-            (self.timing_state.bg_pipeline.pattern_low_latch as Word);
+        let pipeline = &mut self.timing_state.bg_pipeline;
+        pipeline.shift_low = (pipeline.shift_low & 0xFF00) | pipeline.pattern_low_latch as Word;
+        pipeline.shift_high = (pipeline.shift_high & 0xFF00) | pipeline.pattern_high_latch as Word;
         
-        // Load attribute data
-        let attr_bit0 = (self.timing_state.bg_pipeline.attribute_latch & 0x01) as Word;
-        let attr_bit1 = (self.timing_state.bg_pipeline.attribute_latch & 0x02) as Word >> 1;
+        // Attribute bits are expanded so they shift in step with the pattern
+        let attr_low = if pipeline.attribute_latch & 0x01 != 0 { 0xFF } else { 0x00 };
+        let attr_high = if pipeline.attribute_latch & 0x02 != 0 { 0xFF } else { 0x00 };
+        pipeline.attr_shift_low = (pipeline.attr_shift_low & 0xFF00) | attr_low;
+        pipeline.attr_shift_high = (pipeline.attr_shift_high & 0xFF00) | attr_high;
         
-        self.timing_state.bg_pipeline.attr_shift_high = 
-            (self.timing_state.bg_pipeline.attr_shift_high << 8) | (attr_bit1 << 7);
-        self.timing_state.bg_pipeline.attr_shift_low = 
-            (self.timing_state.bg_pipeline.attr_shift_low << 8) | (attr_bit0 << 7);
+        let nt_low = if pipeline.nametable_select_latch & 0x01 != 0 { 0xFF } else { 0x00 };
+        let nt_high = if pipeline.nametable_select_latch & 0x02 != 0 { 0xFF } else { 0x00 };
+        pipeline.nametable_shift_low = (pipeline.nametable_shift_low & 0xFF00) | nt_low;
+        pipeline.nametable_shift_high = (pipeline.nametable_shift_high & 0xFF00) | nt_high;
     }
     
-    /// Shift background registers
+    /// Shift background registers one pixel
     fn shift_background_registers(&mut self) {
-        // Shift registers are already shifted during loading
-        // This is a placeholder for any additional shifting logic
+        let pipeline = &mut self.timing_state.bg_pipeline;
+        pipeline.shift_low <<= 1;
+        pipeline.shift_high <<= 1;
+        pipeline.attr_shift_low <<= 1;
+        pipeline.attr_shift_high <<= 1;
+        pipeline.nametable_shift_low <<= 1;
+        pipeline.nametable_shift_high <<= 1;
     }
     
-    /// Increment scroll X
+    /// Increment coarse X in `v`, wrapping into the next horizontal nametable
     fn increment_scroll_x(&mut self) {
         if (self.timing_state.v & 0x001F) == 31 {
             self.timing_state.v &= !0x001F;
@@ -634,87 +1093,83 @@ impl Ppu {
         }
     }
     
-
-    
-    /// Render a single sprite (legacy method for compatibility)
-    fn render_sprite(&mut self, sprite: Sprite, scanline: usize) -> RnesResult<()> {
-        let sprite_y_pos = sprite.y as usize;
-        let sprite_height = if self.registers.ppuctrl & 0x20 != 0 { 16 } else { 8 };
-        
-        // Check if sprite is visible on this scanline
-        if sprite_y_pos > scanline || sprite_y_pos + sprite_height as usize <= scanline {
-            return Ok(());
+    /// Increment fine Y in `v`, carrying into coarse Y and the vertical nametable
+    fn increment_scroll_y(&mut self) {
+        let v = &mut self.timing_state.v;
+        if *v & 0x7000 != 0x7000 {
+            *v += 0x1000;
+            return;
         }
         
-        let sprite_x_pos = sprite.x as usize;
-        let sprite_y_offset = scanline - sprite_y_pos;
-        
-        // Get sprite pattern data
-        let pattern_table = if self.registers.ppuctrl & 0x10 != 0 { 0x1000 } else { 0x0000 };
-        let tile_addr = pattern_table + (sprite.tile_id as Word) * 16;
-        
-        // Read pattern data for the sprite row
-        let pattern_low = self.read_vram(tile_addr + sprite_y_offset as Word)?;
-        let pattern_high = self.read_vram(tile_addr + sprite_y_offset as Word + 8)?;
-        
-        // Render sprite pixels
-        for pixel_x in 0..8 {
-            let screen_x = sprite_x_pos + pixel_x;
-            if screen_x >= SCREEN_WIDTH {
-                continue;
-            }
-            
-            // Get pixel data from pattern
-            let pattern_x = if sprite.flip_horizontal() { 7 - pixel_x } else { pixel_x };
-            let low_bit = (pattern_low >> (7 - pattern_x)) & 1;
-            let high_bit = (pattern_high >> (7 - pattern_x)) & 1;
-            let color_index = (high_bit << 1) | low_bit;
-            
-            // Skip transparent pixels
-            if color_index == 0 {
-                continue;
-            }
-            
-            // Get sprite color
-            let palette_base = 0x3F10 + (sprite.palette() as Word) * 4;
-            let color_id = self.read_palette_ram(palette_base + color_index as Word)?;
-            let color = Pixel::from_rgb(NES_PALETTE[color_id as usize]);
-            
-            // Write to frame buffer
-            let pixel_index = scanline * SCREEN_WIDTH + screen_x;
-            if pixel_index < self.frame_buffer.len() {
-                // Check sprite-to-background priority
-                let bg_pixel = self.frame_buffer[pixel_index];
-                if !sprite.behind_background() || bg_pixel == Pixel::BLACK {
-                    self.frame_buffer[pixel_index] = color;
-                }
+        *v &= !0x7000;
+        let coarse_y = (*v & 0x03E0) >> 5;
+        let coarse_y = match coarse_y {
+            29 => {
+                *v ^= 0x0800; // Toggle nametable
+                0
             }
+            // Rows 30 and 31 hold attributes; they wrap without switching
+            31 => 0,
+            _ => coarse_y + 1,
+        };
+        *v = (*v & !0x03E0) | (coarse_y << 5);
+    }
+    
+    /// Copy coarse X and the horizontal nametable bit from `t` to `v`
+    fn copy_horizontal_scroll(&mut self) {
+        self.timing_state.v = (self.timing_state.v & !0x041F) | (self.timing_state.t & 0x041F);
+    }
+    
+    /// Copy fine Y, coarse Y and the vertical nametable bit from `t` to `v`
+    fn copy_vertical_scroll(&mut self) {
+        self.timing_state.v = (self.timing_state.v & !0x7BE0) | (self.timing_state.t & 0x7BE0);
+    }
+    
+    /// Check if the PPU is fetching for a rendered line, where $2007
+    /// accesses disturb `v` instead of incrementing it normally
+    fn in_rendering_fetch(&self) -> bool {
+        self.timing_state.rendering_enabled && self.timing_state.scanline < VISIBLE_SCANLINES as Scanline
+    }
+    
+    /// Advance `v` after a $2007 access
+    fn increment_vram_address(&mut self) {
+        if self.in_rendering_fetch() {
+            // The access clocks both scroll counters at once
+            self.increment_scroll_x();
+            self.increment_scroll_y();
+        } else {
+            let step = if self.registers.ppuctrl & 0x04 != 0 { 32 } else { 1 };
+            self.timing_state.v = self.timing_state.v.wrapping_add(step) & 0x7FFF;
         }
-        
-        Ok(())
     }
     
-    /// Read from VRAM
+    /// Read from VRAM for $2007
     fn read_vram(&mut self, addr: Word) -> RnesResult<Byte> {
+        self.fetch_vram(addr, PpuFetch::Data)
+    }
+    
+    /// Read from VRAM, letting the mapper supply the byte
+    fn fetch_vram(&mut self, addr: Word, fetch: PpuFetch) -> RnesResult<Byte> {
+        self.mapper.ppu_address_changed(addr);
+        if addr < 0x3F00 {
+            if let Some(value) = self.mapper.ppu_read(addr, fetch) {
+                return Ok(value);
+            }
+        }
+        
         match addr {
             0x0000..=0x1FFF => {
-                // Pattern tables - detect A12 for MMC3 IRQ
+                // Pattern tables
                 self.mapper.read_chr(addr)
             }
             0x2000..=0x3EFF => {
                 // Nametables (with mirroring)
                 let mirrored_addr = self.mirror_nametable_address(addr);
-                self.mapper.read_chr(mirrored_addr)
+                Ok(self.nametable_ram[(mirrored_addr & 0x0FFF) as usize])
             }
-            0x3F00..=0x3F1F => {
-                // Palette RAM
-                let palette_addr = addr & 0x1F;
-                Ok(self.palette_ram[palette_addr as usize])
-            }
-            0x3F20..=0x3FFF => {
-                // Palette RAM mirror
-                let palette_addr = addr & 0x1F;
-                Ok(self.palette_ram[palette_addr as usize])
+            0x3F00..=0x3FFF => {
+                // Palette RAM and its mirrors
+                self.read_palette_ram(addr)
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
         }
@@ -725,6 +1180,10 @@ impl Ppu {
         let base = addr & 0x2C00;
         let offset = addr & 0x03FF;
         
+        if let Some(page) = self.mapper.ciram_page(addr) {
+            return 0x2000 | (page as Word & 0x01) << 10 | offset;
+        }
+        
         let mirrored_base = match self.mapper.mirroring() {
             rnes_cartridge::Mirroring::Horizontal => {
                 if base == 0x2400 || base == 0x2C00 {
@@ -750,23 +1209,83 @@ impl Ppu {
     
     /// Read from palette RAM
     fn read_palette_ram(&self, addr: Word) -> RnesResult<Byte> {
-        let index = (addr & 0x1F) as usize;
-        if index < self.palette_ram.len() {
-            Ok(self.palette_ram[index])
-        } else {
-            Err(rnes_common::RnesError::MemoryAccess { address: addr })
-        }
+        Ok(self.palette_ram[palette_index(addr)])
     }
     
     /// Write to palette RAM
     fn write_palette_ram(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
-        let index = (addr & 0x1F) as usize;
-        if index < self.palette_ram.len() {
-            self.palette_ram[index] = value;
-            Ok(())
-        } else {
-            Err(rnes_common::RnesError::MemoryAccess { address: addr })
+        self.palette_ram[palette_index(addr)] = value;
+        Ok(())
+    }
+    
+    /// Read PPU memory ($0000-$3FFF) for debuggers
+    ///
+    /// Unlike $2007 reads this leaves the read buffer, `v` and the mapper's
+    /// view of the address bus untouched.
+    pub fn peek_vram(&mut self, addr: Word) -> Byte {
+        let addr = addr & 0x3FFF;
+        if addr < 0x3F00 {
+            if let Some(value) = self.mapper.ppu_read(addr, PpuFetch::Data) {
+                return value;
+            }
+        }
+        match addr {
+            0x0000..=0x1FFF => self.mapper.read_chr(addr).unwrap_or(0),
+            0x2000..=0x3EFF => {
+                let mirrored_addr = self.mirror_nametable_address(addr);
+                self.nametable_ram[(mirrored_addr & 0x0FFF) as usize]
+            }
+            _ => self.read_palette_ram(addr).unwrap_or(0),
+        }
+    }
+    
+    /// Write PPU memory ($0000-$3FFF) for debuggers, without side effects
+    pub fn poke_vram(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => self.mapper.write_chr(addr, value)?,
+            0x2000..=0x3EFF if self.mapper.ppu_write_nametable(addr, value) => {}
+            0x2000..=0x3EFF => {
+                let mirrored_addr = self.mirror_nametable_address(addr);
+                self.nametable_ram[(mirrored_addr & 0x0FFF) as usize] = value;
+            }
+            _ => self.write_palette_ram(addr, value)?,
         }
+        self.clear_vram_cache();
+        Ok(())
+    }
+    
+    /// Choose which layers are drawn, for inspecting a frame
+    pub fn set_layer_visibility(&mut self, layers: LayerVisibility) {
+        self.layers = layers;
+    }
+    
+    /// Get the layers currently drawn
+    pub fn layer_visibility(&self) -> LayerVisibility {
+        self.layers
+    }
+    
+    /// Select the region whose emphasis bit order is used
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+    
+    /// Keep the hardware's 8 sprites per line, or draw them all without
+    /// flicker
+    ///
+    /// Evaluation timing and the overflow flag are the same either way.
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.sprite_limit = enabled;
+    }
+    
+    /// Check if sprites past the eighth on a line are dropped
+    pub fn sprite_limit(&self) -> bool {
+        self.sprite_limit
+    }
+    
+    /// Get the region whose emphasis bit order is used
+    pub fn region(&self) -> Region {
+        self.region
     }
     
     /// Get frame buffer
@@ -774,6 +1293,22 @@ impl Ppu {
         &self.frame_buffer
     }
     
+    /// Scanlines of the last completed frame that differ from the frame before
+    ///
+    /// `None` means the frame is identical, so a frontend can skip the
+    /// texture upload; otherwise only these rows need uploading.
+    pub fn dirty_lines(&self) -> Option<Range<usize>> {
+        self.dirty_lines.map(|(first, end)| first..end)
+    }
+    
+    /// Note that a line of the frame being drawn changed
+    fn mark_line_dirty(&mut self, line: usize) {
+        self.drawing_dirty = Some(match self.drawing_dirty {
+            Some((first, end)) => (first.min(line), end.max(line + 1)),
+            None => (line, line + 1),
+        });
+    }
+    
     /// Get OAM data
     pub fn oam(&self) -> &[Byte] {
         &self.oam
@@ -840,16 +1375,44 @@ impl Ppu {
     }
     
     /// Set OAM data
-    pub fn set_oam(&mut self, oam: Vec<u8>) {
+    pub fn set_oam(&mut self, oam: &[u8]) {
         if oam.len() == self.oam.len() {
-            self.oam.copy_from_slice(&oam);
+            self.oam.copy_from_slice(oam);
         }
     }
     
     /// Set palette RAM data
-    pub fn set_palette_ram(&mut self, palette_ram: Vec<u8>) {
+    pub fn set_palette_ram(&mut self, palette_ram: &[u8]) {
         if palette_ram.len() == self.palette_ram.len() {
-            self.palette_ram.copy_from_slice(&palette_ram);
+            self.palette_ram.copy_from_slice(palette_ram);
+        }
+    }
+    
+    /// Get nametable RAM
+    pub fn nametable_ram(&self) -> &[Byte] {
+        &self.nametable_ram
+    }
+    
+    /// Get mutable OAM data
+    pub fn oam_mut(&mut self) -> &mut [Byte] {
+        &mut self.oam
+    }
+    
+    /// Get mutable palette RAM data
+    pub fn palette_ram_mut(&mut self) -> &mut [Byte] {
+        &mut self.palette_ram
+    }
+    
+    /// Get mutable nametable RAM
+    pub fn nametable_ram_mut(&mut self) -> &mut [Byte] {
+        self.clear_vram_cache();
+        &mut self.nametable_ram
+    }
+    
+    /// Set nametable RAM data
+    pub fn set_nametable_ram(&mut self, nametable_ram: &[u8]) {
+        if nametable_ram.len() == self.nametable_ram.len() {
+            self.nametable_ram.copy_from_slice(nametable_ram);
         }
     }
     
@@ -857,9 +1420,42 @@ impl Ppu {
     pub fn set_frame_buffer(&mut self, frame_buffer: Vec<Pixel>) {
         if frame_buffer.len() == self.frame_buffer.len() {
             self.frame_buffer = frame_buffer;
+            self.drawing_dirty = Some((0, SCREEN_HEIGHT));
+            self.dirty_lines = Some((0, SCREEN_HEIGHT));
         }
     }
     
+    /// Capture the internal state for a save state
+    pub fn save_state(&self) -> PpuState {
+        PpuState {
+            registers: self.registers,
+            timing_state: self.timing_state.clone(),
+            oam_dma_active: self.oam_dma_active,
+            oam_dma_cycles: self.oam_dma_cycles,
+            oam_dma_addr: self.oam_dma_addr,
+            overclock_dots_remaining: self.overclock_dots_remaining,
+            reset_latch: self.reset_latch,
+            delayed_writes: self.delayed_writes.clone(),
+            nmi_pending: self.nmi_pending,
+        }
+    }
+    
+    /// Restore internal state taken with `save_state`
+    pub fn load_state(&mut self, state: PpuState) -> RnesResult<()> {
+        state.validate()?;
+        self.registers = state.registers;
+        self.timing_state = state.timing_state;
+        self.oam_dma_active = state.oam_dma_active;
+        self.oam_dma_cycles = state.oam_dma_cycles;
+        self.oam_dma_addr = state.oam_dma_addr;
+        self.overclock_dots_remaining = state.overclock_dots_remaining;
+        self.reset_latch = state.reset_latch;
+        self.delayed_writes = state.delayed_writes;
+        self.nmi_pending = state.nmi_pending;
+        self.drawing_dirty = Some((0, SCREEN_HEIGHT));
+        Ok(())
+    }
+    
     /// Get mapper instance
     pub fn mapper(&self) -> &dyn rnes_mappers::Mapper {
         self.mapper.as_ref()
@@ -897,15 +1493,8 @@ impl Ppu {
             }
             0x2007 => {
                 // PPUDATA
-                let value = self.read_vram(self.timing_state.v)?;
-                
-                // Auto-increment address
-                if self.registers.ppuctrl & 0x04 != 0 {
-                    self.timing_state.v += 32;
-                } else {
-                    self.timing_state.v += 1;
-                }
-                
+                let value = self.read_vram(self.timing_state.v & 0x3FFF)?;
+                self.increment_vram_address();
                 Ok(value)
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
@@ -914,11 +1503,19 @@ impl Ppu {
     
     /// Write PPU register
     pub fn write_register(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        if self.reset_latch && matches!(addr, 0x2000 | 0x2001 | 0x2005 | 0x2006) {
+            return Ok(());
+        }
+        
         match addr {
             0x2000 => {
-                // PPUCTRL
+                // PPUCTRL; enabling NMI during VBlank raises one immediately
+                if value & 0x80 != 0 && self.registers.ppuctrl & 0x80 == 0 && self.registers.ppustatus & 0x80 != 0 {
+                    self.nmi_pending = true;
+                }
                 self.registers.ppuctrl = value;
                 self.timing_state.t = (self.timing_state.t & 0xF3FF) | ((value as Word & 0x03) << 10);
+                self.mapper.ppu_register_written(addr, value);
                 Ok(())
             }
             0x2001 => {
@@ -928,6 +1525,7 @@ impl Ppu {
                 self.timing_state.rendering_enabled = self.registers.ppumask & 0x18 != 0;
                 self.timing_state.background_enabled = self.registers.ppumask & 0x08 != 0;
                 self.timing_state.sprites_enabled = self.registers.ppumask & 0x10 != 0;
+                self.mapper.ppu_register_written(addr, value);
                 Ok(())
             }
             0x2002 => {
@@ -970,37 +1568,59 @@ impl Ppu {
                     // Second write: low byte
                     self.timing_state.t = (self.timing_state.t & 0xFF00) | value as Word;
                     self.timing_state.v = self.timing_state.t;
+                    self.mapper.ppu_address_changed(self.timing_state.v);
                 }
                 self.timing_state.w = !self.timing_state.w;
                 Ok(())
             }
             0x2007 => {
                 // PPUDATA
-                self.write_vram(self.timing_state.v, value)?;
-                
-                // Auto-increment address
-                if self.registers.ppuctrl & 0x04 != 0 {
-                    self.timing_state.v += 32;
-                } else {
-                    self.timing_state.v += 1;
-                }
+                self.write_vram(self.timing_state.v & 0x3FFF, value)?;
+                self.increment_vram_address();
                 Ok(())
             }
             _ => Err(rnes_common::RnesError::MemoryAccess { address: addr })
         }
     }
     
+    /// Write a PPU register `dots` PPU cycles from now
+    ///
+    /// The CPU runs a whole instruction before the PPU catches up, so a
+    /// write made on the instruction's last cycle would otherwise land
+    /// several dots early. $2000 and $2001 are held until the PPU reaches the
+    /// right dot, which keeps mid-scanline rendering and pattern table
+    /// switches in place; other registers are written immediately.
+    pub fn write_register_delayed(&mut self, addr: Word, value: Byte, dots: u32) -> RnesResult<()> {
+        if dots == 0 || !matches!(addr, 0x2000 | 0x2001) {
+            return self.write_register(addr, value);
+        }
+        self.delayed_writes.push(DelayedWrite { dots, addr, value });
+        Ok(())
+    }
+    
+    /// Apply any delayed register writes still waiting
+    pub fn flush_delayed_writes(&mut self) -> RnesResult<()> {
+        for write in core::mem::take(&mut self.delayed_writes) {
+            self.write_register(write.addr, write.value)?;
+        }
+        Ok(())
+    }
+    
     /// Write to VRAM
     fn write_vram(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        self.mapper.ppu_address_changed(addr);
+        
         match addr {
             0x0000..=0x1FFF => {
                 // Pattern tables
                 self.mapper.write_chr(addr, value)
             }
+            0x2000..=0x3EFF if self.mapper.ppu_write_nametable(addr, value) => Ok(()),
             0x2000..=0x3EFF => {
                 // Nametables (with mirroring)
                 let mirrored_addr = self.mirror_nametable_address(addr);
-                self.mapper.write_chr(mirrored_addr, value)
+                self.nametable_ram[(mirrored_addr & 0x0FFF) as usize] = value;
+                Ok(())
             }
             0x3F00..=0x3F1F => {
                 // Palette RAM
@@ -1014,26 +1634,6 @@ impl Ppu {
         }
     }
     
-    /// Cached VRAM read for optimization
-    fn read_vram_cached(&mut self, addr: Word) -> RnesResult<Byte> {
-        // Check cache first
-        let cache_index = (addr & 0xFF) as usize;
-        if self.timing_state.cache_valid[cache_index] && 
-           self.timing_state.vram_cache[cache_index] != 0 {
-            return Ok(self.timing_state.vram_cache[cache_index]);
-        }
-        
-        // Read from VRAM
-        let value = self.read_vram(addr)?;
-        
-        // Update cache
-        self.timing_state.vram_cache[cache_index] = value;
-        self.timing_state.cache_valid[cache_index] = true;
-        self.timing_state.last_vram_access = addr;
-        
-        Ok(value)
-    }
-    
     /// Get nametable address
     fn get_nametable_address(&self) -> RnesResult<Word> {
         let base = 0x2000 + (self.timing_state.v & 0x0C00);
@@ -1132,6 +1732,7 @@ impl Ppu {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rnes_common::TileGrid;
     use rnes_mappers::NromMapper;
     use rnes_cartridge::Cartridge;
     
@@ -1219,6 +1820,563 @@ mod tests {
         assert!(ppu.oam_dma_active());
         assert_eq!(ppu.oam_dma_addr(), 0x0200);
     }
+    
+    #[test]
+    fn test_reset_ignores_writes_until_vblank_ends() {
+        let mut test_data = vec![
+            0x4E, 0x45, 0x53, 0x1A, // iNES magic
+            0x01, 0x01,             // 16KB PRG, 8KB CHR
+            0x00, 0x00,             // Mapper 0, horizontal mirroring
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
+        ];
+        test_data.extend(vec![0; 16384]);
+        test_data.extend(vec![0; 8192]);
+        
+        let cartridge = Cartridge::from_bytes(&test_data).unwrap();
+        let mut ppu = Ppu::new(Box::new(NromMapper::new(cartridge)));
+        
+        ppu.write_register(0x2000, 0x80).unwrap();
+        ppu.write_register(0x2003, 0x10).unwrap();
+        ppu.write_register(0x2004, 0xAB).unwrap();
+        ppu.reset();
+        
+        // Control registers are cleared, OAM survives
+        assert_eq!(ppu.registers().ppuctrl, 0);
+        assert_eq!(ppu.oam()[0x10], 0xAB);
+        assert!(ppu.in_reset());
+        
+        ppu.write_register(0x2000, 0x80).unwrap();
+        assert_eq!(ppu.registers().ppuctrl, 0);
+        
+        let mut steps = 0;
+        while ppu.in_reset() {
+            ppu.step().unwrap();
+            steps += 1;
+            assert!(steps <= 2 * DOTS_PER_SCANLINE * TOTAL_SCANLINES);
+        }
+        ppu.write_register(0x2000, 0x80).unwrap();
+        assert_eq!(ppu.registers().ppuctrl, 0x80);
+    }
+    
+    #[test]
+    fn test_delayed_mask_write_lands_on_its_dot() {
+        let mut test_data = vec![
+            0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        test_data.extend(vec![0; 16384]);
+        test_data.extend(vec![0xFF; 8192]); // Every background pixel uses color 3
+        
+        let cartridge = Cartridge::from_bytes(&test_data).unwrap();
+        let mut ppu = Ppu::new(Box::new(NromMapper::new(cartridge)));
+        ppu.palette_ram_mut()[0] = 0x30;
+        ppu.palette_ram_mut()[3] = 0x16;
+        ppu.write_register(0x2001, 0x08).unwrap();
+        
+        // Run through the pre-render line and 100 dots of scanline 0
+        for _ in 0..DOTS_PER_SCANLINE + 100 {
+            ppu.step().unwrap();
+        }
+        assert_eq!((ppu.scanline(), ppu.dot()), (0, 100));
+        
+        ppu.write_register_delayed(0x2001, 0x00, 6).unwrap();
+        for _ in 0..6 {
+            ppu.step().unwrap();
+        }
+        assert_eq!(ppu.registers().ppumask, 0x08);
+        ppu.step().unwrap();
+        assert_eq!(ppu.registers().ppumask, 0x00);
+        
+        let backdrop = Pixel::from_rgb(NES_PALETTE[0x30]);
+        assert_eq!(ppu.frame_buffer()[105], Pixel::from_rgb(NES_PALETTE[0x16]));
+        assert_eq!(ppu.frame_buffer()[106], backdrop);
+        
+        // Other registers are never delayed
+        ppu.write_register_delayed(0x2003, 0x40, 6).unwrap();
+        assert_eq!(ppu.registers().oamaddr, 0x40);
+        ppu.write_register_delayed(0x2000, 0x10, 6).unwrap();
+        ppu.flush_delayed_writes().unwrap();
+        assert_eq!(ppu.registers().ppuctrl, 0x10);
+    }
+    
+    #[test]
+    fn test_scroll_register_writes() {
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        
+        ppu.write_register(0x2000, 0x00).unwrap();
+        ppu.write_register(0x2005, 0x7D).unwrap();
+        assert_eq!((ppu.state().t, ppu.state().x), (0x000F, 0x05));
+        ppu.write_register(0x2005, 0x5E).unwrap();
+        assert_eq!(ppu.state().t, 0x616F);
+        
+        // The first $2006 write clears bit 14, so fine Y loses its top bit
+        ppu.write_register(0x2006, 0x3D).unwrap();
+        assert_eq!(ppu.state().t, 0x3D6F);
+        assert_eq!(ppu.state().v, 0x0000);
+        ppu.write_register(0x2006, 0xF0).unwrap();
+        assert_eq!((ppu.state().t, ppu.state().v), (0x3DF0, 0x3DF0));
+        
+        // A lone $2005 write only changes coarse/fine X
+        ppu.write_register(0x2005, 0x08).unwrap();
+        assert_eq!((ppu.state().t, ppu.state().x, ppu.state().v), (0x3DE1, 0x00, 0x3DF0));
+    }
+    
+    #[test]
+    fn test_scroll_increments() {
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        
+        // Fine Y 7 of row 29 carries into the other vertical nametable
+        ppu.timing_state.v = 0x73A0;
+        ppu.increment_scroll_y();
+        assert_eq!(ppu.timing_state.v, 0x0800);
+        
+        // Row 31 is in the attribute table and wraps without switching
+        ppu.timing_state.v = 0x73E0;
+        ppu.increment_scroll_y();
+        assert_eq!(ppu.timing_state.v, 0x0000);
+        
+        ppu.timing_state.v = 0x001F;
+        ppu.increment_scroll_x();
+        assert_eq!(ppu.timing_state.v, 0x0400);
+        
+        // Dot 257 takes the horizontal bits from t, the pre-render line the vertical ones
+        ppu.timing_state.t = 0x7FFF;
+        ppu.timing_state.v = 0x0000;
+        ppu.copy_horizontal_scroll();
+        assert_eq!(ppu.timing_state.v, 0x041F);
+        ppu.copy_vertical_scroll();
+        assert_eq!(ppu.timing_state.v, 0x7FFF);
+    }
+    
+    #[test]
+    fn test_vblank_raises_nmi() {
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        ppu.write_register(0x2000, 0x80).unwrap();
+        
+        let mut steps = 0;
+        while !ppu.vblank() {
+            ppu.step().unwrap();
+            steps += 1;
+        }
+        // Pre-render line plus 241 lines, then dot 1
+        assert_eq!(steps, 242 * DOTS_PER_SCANLINE + 1);
+        assert!(ppu.take_nmi());
+        assert!(!ppu.take_nmi());
+        
+        // Enabling NMI during VBlank raises one straight away
+        ppu.write_register(0x2000, 0x00).unwrap();
+        ppu.write_register(0x2000, 0x80).unwrap();
+        assert!(ppu.take_nmi());
+        
+        // A frame is 262 lines
+        for _ in 0..TOTAL_SCANLINES * DOTS_PER_SCANLINE {
+            ppu.step().unwrap();
+        }
+        assert!(ppu.vblank());
+        assert_eq!(ppu.frame_count(), 1);
+    }
+    
+    #[test]
+    fn test_nmi_needs_ppuctrl_and_frames_are_262_lines() {
+        // Steps from here to the start of the next VBlank
+        fn next_vblank(ppu: &mut Ppu) -> usize {
+            let mut steps = 0;
+            while ppu.vblank() {
+                ppu.step().unwrap();
+                steps += 1;
+            }
+            while !ppu.vblank() {
+                ppu.step().unwrap();
+                steps += 1;
+            }
+            steps
+        }
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        
+        // VBlank without PPUCTRL bit 7 raises nothing
+        next_vblank(&mut ppu);
+        assert!(!ppu.take_nmi());
+        
+        // Enabling NMI outside VBlank waits for the next one
+        while ppu.vblank() {
+            ppu.step().unwrap();
+        }
+        ppu.write_register(0x2000, 0x80).unwrap();
+        assert!(!ppu.take_nmi());
+        next_vblank(&mut ppu);
+        assert!(ppu.take_nmi());
+        
+        // With rendering off every frame is 262 lines of 341 dots, one NMI each
+        let frame = ppu.frame_count();
+        for _ in 0..3 {
+            assert_eq!(next_vblank(&mut ppu), TOTAL_SCANLINES * DOTS_PER_SCANLINE);
+            assert!(ppu.take_nmi());
+            assert!(!ppu.take_nmi());
+        }
+        assert_eq!(ppu.frame_count(), frame + 3);
+    }
+    
+    #[test]
+    fn test_sprite_priority_mux() {
+        let mut test_data = vec![
+            0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        test_data.extend(vec![0; 16384]);
+        let mut chr = vec![0; 8192];
+        chr[16..24].fill(0xFF); // Tile 1: color 1
+        chr[32..48].fill(0xFF); // Tile 2: color 3
+        test_data.extend(chr);
+        
+        let cartridge = Cartridge::from_bytes(&test_data).unwrap();
+        let mut ppu = Ppu::new(Box::new(NromMapper::new(cartridge)));
+        ppu.poke_vram(0x2001, 0x01).unwrap(); // Opaque background at x 8-15
+        for (addr, color) in [(0x3F00, 0x0F), (0x3F01, 0x16), (0x3F13, 0x2A), (0x3F17, 0x12)] {
+            ppu.poke_vram(addr, color).unwrap();
+        }
+        // Sprite 0 behind the background at x 4-11, sprite 1 in front at x 10-17
+        ppu.oam_mut()[0..8].copy_from_slice(&[0x00, 0x02, 0x20, 0x04, 0x00, 0x02, 0x01, 0x0A]);
+        ppu.write_register(0x2001, 0x1E).unwrap();
+        
+        for _ in 0..3 * DOTS_PER_SCANLINE {
+            ppu.step().unwrap();
+        }
+        let line = |y: usize| &ppu.frame_buffer()[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+        let color = |id: usize| Pixel::from_rgb(NES_PALETTE[id]);
+        
+        // Sprites with OAM Y 0 start on line 1
+        assert_eq!(line(0)[4], color(0x0F));
+        assert_eq!(line(0)[8], color(0x16));
+        
+        let expected = [
+            (3, 0x0F), // Backdrop
+            (4, 0x2A), // Behind-background sprite over a transparent background
+            (8, 0x16), // ... and under an opaque one
+            (10, 0x16), // Sprite 0 is front-most, so its priority hides sprite 1 too
+            (12, 0x12), // Sprite 1 over the background
+            (17, 0x12),
+            (18, 0x0F),
+        ];
+        for (x, id) in expected {
+            assert_eq!(line(1)[x], color(id), "x = {}", x);
+        }
+        assert_ne!(ppu.registers().ppustatus & 0x40, 0);
+        
+        // Hit and overflow flags clear on the pre-render line
+        while ppu.scanline() != -1 || ppu.dot() < 1 {
+            ppu.step().unwrap();
+        }
+        assert_eq!(ppu.registers().ppustatus & 0x40, 0);
+    }
+    
+    #[test]
+    fn test_sprite_overflow_timing_and_m_bug() {
+        let overflow_dot = |sprites: &[(usize, [Byte; 4])]| {
+            let mut test_data = vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ];
+            test_data.extend(vec![0; 16384 + 8192]);
+            let cartridge = Cartridge::from_bytes(&test_data).unwrap();
+            let mut ppu = Ppu::new(Box::new(NromMapper::new(cartridge)));
+            ppu.oam_mut().fill(0xF0);
+            for &(index, entry) in sprites {
+                ppu.oam_mut()[index * 4..index * 4 + 4].copy_from_slice(&entry);
+            }
+            ppu.write_register(0x2001, 0x18).unwrap();
+            
+            while ppu.scanline() != 10 || ppu.dot() != 0 {
+                ppu.step().unwrap();
+            }
+            assert_eq!(ppu.registers().ppustatus & 0x20, 0);
+            while ppu.scanline() == 10 {
+                ppu.step().unwrap();
+                if ppu.registers().ppustatus & 0x20 != 0 {
+                    let dot = ppu.dot();
+                    // Stays set until the pre-render line
+                    while ppu.scanline() != -1 || ppu.dot() < 1 {
+                        assert_ne!(ppu.registers().ppustatus & 0x20, 0);
+                        ppu.step().unwrap();
+                    }
+                    assert_eq!(ppu.registers().ppustatus & 0x20, 0);
+                    return Some(dot);
+                }
+            }
+            None
+        };
+        let on_line = [10, 0, 0, 0];
+        let eight: Vec<_> = (0..8).map(|index| (index, on_line)).collect();
+        
+        // The ninth sprite is found after 8 copies of 8 dots
+        let mut nine = eight.clone();
+        nine.push((8, on_line));
+        assert_eq!(overflow_dot(&nine), Some(130));
+        assert_eq!(overflow_dot(&eight), None);
+        
+        // After a miss the next sprite's tile number is compared as Y...
+        let mut false_positive = eight.clone();
+        false_positive.push((9, [0xF0, 10, 0xF0, 0xF0]));
+        assert_eq!(overflow_dot(&false_positive), Some(132));
+        
+        // ... so a real ninth sprite after a miss can go unnoticed
+        let mut missed = eight.clone();
+        missed.push((9, [10, 0xF0, 0xF0, 0xF0]));
+        assert_eq!(overflow_dot(&missed), None);
+    }
+    
+    #[test]
+    fn test_sprite_limit() {
+        let render = |sprite_limit: bool| {
+            let mut test_data = vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ];
+            test_data.extend(vec![0; 16384]);
+            let mut chr = vec![0; 8192];
+            chr[16..24].fill(0xFF); // Tile 1: color 1
+            test_data.extend(chr);
+            
+            let cartridge = Cartridge::from_bytes(&test_data).unwrap();
+            let mut ppu = Ppu::new(Box::new(NromMapper::new(cartridge)));
+            ppu.set_sprite_limit(sprite_limit);
+            for (addr, color) in [(0x3F00, 0x0F), (0x3F11, 0x16), (0x3F15, 0x2A)] {
+                ppu.poke_vram(addr, color).unwrap();
+            }
+            // Ten sprites side by side, and an eleventh under the tenth
+            ppu.oam_mut().fill(0xF0);
+            for index in 0..10 {
+                ppu.oam_mut()[index * 4..index * 4 + 4].copy_from_slice(&[0x00, 0x01, 0x00, index as Byte * 8]);
+            }
+            ppu.oam_mut()[40..44].copy_from_slice(&[0x00, 0x01, 0x01, 72]);
+            ppu.write_register(0x2001, 0x14).unwrap();
+            
+            for _ in 0..3 * DOTS_PER_SCANLINE {
+                ppu.step().unwrap();
+            }
+            assert_ne!(ppu.registers().ppustatus & 0x20, 0);
+            ppu.frame_buffer()[SCREEN_WIDTH..2 * SCREEN_WIDTH].to_vec()
+        };
+        let color = |id: usize| Pixel::from_rgb(NES_PALETTE[id]);
+        
+        let limited = render(true);
+        assert_eq!(limited[56], color(0x16));
+        assert_eq!(limited[64], color(0x0F));
+        assert_eq!(limited[72], color(0x0F));
+        
+        let unlimited = render(false);
+        assert_eq!(unlimited[64], color(0x16));
+        // The tenth sprite is still in front of the eleventh
+        assert_eq!(unlimited[72], color(0x16));
+        assert_eq!(unlimited[80], color(0x0F));
+    }
+    
+    #[test]
+    fn test_layer_visibility() {
+        let render = |layers: LayerVisibility| {
+            let mut test_data = vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ];
+            test_data.extend(vec![0; 16384]);
+            let mut chr = vec![0; 8192];
+            chr[16..24].fill(0xFF); // Tile 1: color 1
+            test_data.extend(chr);
+            
+            let cartridge = Cartridge::from_bytes(&test_data).unwrap();
+            let mut ppu = Ppu::new(Box::new(NromMapper::new(cartridge)));
+            ppu.set_layer_visibility(layers);
+            ppu.poke_vram(0x2001, 0x01).unwrap(); // Opaque background at x 8-15
+            for (addr, color) in [(0x3F00, 0x0F), (0x3F01, 0x16), (0x3F11, 0x2A)] {
+                ppu.poke_vram(addr, color).unwrap();
+            }
+            // Sprite 0 at x 4-11 on line 1
+            ppu.oam_mut()[0..4].copy_from_slice(&[0x00, 0x01, 0x00, 0x04]);
+            ppu.write_register(0x2001, 0x1E).unwrap();
+            
+            for _ in 0..3 * DOTS_PER_SCANLINE {
+                ppu.step().unwrap();
+            }
+            (ppu.frame_buffer()[SCREEN_WIDTH..2 * SCREEN_WIDTH].to_vec(), ppu.registers().ppustatus & 0x40)
+        };
+        let color = |id: usize| Pixel::from_rgb(NES_PALETTE[id]);
+        
+        let (line, hit) = render(LayerVisibility::default());
+        assert_eq!((line[4], line[12], line[16], hit), (color(0x2A), color(0x16), color(0x0F), 0x40));
+        
+        // Hiding a layer leaves sprite-0 hits alone
+        let (line, hit) = render(LayerVisibility { background: false, ..LayerVisibility::default() });
+        assert_eq!((line[4], line[12], hit), (color(0x2A), color(0x0F), 0x40));
+        let (line, _) = render(LayerVisibility { sprites: false, ..LayerVisibility::default() });
+        assert_eq!((line[4], line[12]), (color(0x0F), color(0x16)));
+        let (line, _) = render(LayerVisibility { nametables: [false, true, true, true], ..LayerVisibility::default() });
+        assert_eq!(line[12], color(0x0F));
+        let (line, _) = render(LayerVisibility { nametables: [true, false, true, true], ..LayerVisibility::default() });
+        assert_eq!(line[12], color(0x16));
+        
+        let (line, _) = render(LayerVisibility { grid: Some(TileGrid::Tile8), ..LayerVisibility::default() });
+        assert_eq!((line[0], line[8], line[16]), (GRID_COLOR, GRID_COLOR, GRID_COLOR));
+        assert_eq!((line[4], line[12]), (color(0x2A), color(0x16)));
+    }
+    
+    #[test]
+    fn test_palette_mirrors_and_backdrop() {
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        let write = |ppu: &mut Ppu, addr: Word, value: Byte| {
+            ppu.write_register(0x2006, (addr >> 8) as Byte).unwrap();
+            ppu.write_register(0x2006, addr as Byte).unwrap();
+            ppu.write_register(0x2007, value).unwrap();
+        };
+        
+        write(&mut ppu, 0x3F10, 0x21);
+        write(&mut ppu, 0x3F04, 0x05);
+        write(&mut ppu, 0x3F11, 0x16);
+        write(&mut ppu, 0x3F01, 0x2A);
+        assert_eq!(ppu.peek_vram(0x3F00), 0x21);
+        assert_eq!(ppu.peek_vram(0x3F14), 0x05);
+        assert_eq!(ppu.peek_vram(0x3F3C), ppu.peek_vram(0x3F0C));
+        assert_eq!((ppu.peek_vram(0x3F01), ppu.peek_vram(0x3F11)), (0x2A, 0x16));
+        assert_eq!(ppu.palette_ram()[0x10], 0x00);
+        
+        // Rendering is off and v is $3F02, so that entry is the backdrop
+        write(&mut ppu, 0x3F02, 0x30);
+        ppu.write_register(0x2006, 0x3F).unwrap();
+        ppu.write_register(0x2006, 0x02).unwrap();
+        for _ in 0..2 * DOTS_PER_SCANLINE {
+            ppu.step().unwrap();
+        }
+        assert_eq!(ppu.frame_buffer()[0], Pixel::from_rgb(NES_PALETTE[0x30]));
+        
+        // Otherwise it is $3F00, written through its $3F10 mirror
+        ppu.write_register(0x2006, 0x00).unwrap();
+        ppu.write_register(0x2006, 0x00).unwrap();
+        for _ in 0..TOTAL_SCANLINES * DOTS_PER_SCANLINE {
+            ppu.step().unwrap();
+        }
+        assert_eq!(ppu.frame_buffer()[0], Pixel::from_rgb(NES_PALETTE[0x21]));
+    }
+    
+    #[test]
+    fn test_emphasis_by_region() {
+        let white = Pixel::from_rgb(NES_PALETTE[0x30]);
+        let dim = |channel: u8| (channel as u16 * 209 / 256) as u8;
+        for region in [Region::Ntsc, Region::Pal, Region::Dendy] {
+            assert_eq!(palette_color(0x30, 0x1E, region), white);
+            // Blue is bit 7 everywhere
+            assert_eq!(palette_color(0x30, 0x80, region), Pixel::new(dim(white.r), dim(white.g), white.b));
+            // All three bits darken everything, the black columns never change
+            assert_eq!(palette_color(0x30, 0xE0, region), Pixel::new(dim(white.r), dim(white.g), dim(white.b)));
+            assert_eq!(palette_color(0x0F, 0xE0, region), Pixel::from_rgb(NES_PALETTE[0x0F]));
+            assert_eq!(palette_color(0x2E, 0x80, region), Pixel::from_rgb(NES_PALETTE[0x2E]));
+        }
+        
+        let red = Pixel::new(white.r, dim(white.g), dim(white.b));
+        let green = Pixel::new(dim(white.r), white.g, dim(white.b));
+        assert_eq!(palette_color(0x30, 0x20, Region::Ntsc), red);
+        assert_eq!(palette_color(0x30, 0x40, Region::Ntsc), green);
+        assert_eq!(palette_color(0x30, 0x20, Region::Pal), green);
+        assert_eq!(palette_color(0x30, 0x40, Region::Pal), red);
+        assert_eq!(palette_color(0x30, 0x20, Region::Dendy), green);
+        assert_eq!(palette_color(0x70, 0x40, Region::Dendy), red);
+        
+        // The backdrop picks up the emphasis in the PPU's region
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        ppu.set_region(Region::Pal);
+        ppu.write_register(0x2006, 0x3F).unwrap();
+        ppu.write_register(0x2006, 0x00).unwrap();
+        ppu.write_register(0x2007, 0x30).unwrap();
+        ppu.write_register(0x2006, 0x00).unwrap();
+        ppu.write_register(0x2006, 0x00).unwrap();
+        ppu.write_register(0x2001, 0x20).unwrap();
+        for _ in 0..TOTAL_SCANLINES * DOTS_PER_SCANLINE {
+            ppu.step().unwrap();
+        }
+        assert_eq!(ppu.frame_buffer()[0], green);
+    }
+    
+    /// Mapper that records the PPU callbacks it receives
+    #[derive(Default, Clone)]
+    struct ProbeMapper {
+        scanlines: std::sync::Arc<std::sync::Mutex<Vec<Scanline>>>,
+        addresses: std::sync::Arc<std::sync::Mutex<Vec<Word>>>,
+    }
+    
+    impl Mapper for ProbeMapper {
+        fn read_prg(&self, _addr: Word) -> RnesResult<Byte> {
+            Ok(0)
+        }
+        
+        fn write_prg(&mut self, _addr: Word, _value: Byte) -> RnesResult<()> {
+            Ok(())
+        }
+        
+        fn read_chr(&mut self, _addr: Word) -> RnesResult<Byte> {
+            Ok(0)
+        }
+        
+        fn write_chr(&mut self, _addr: Word, _value: Byte) -> RnesResult<()> {
+            Ok(())
+        }
+        
+        fn mirroring(&self) -> rnes_cartridge::Mirroring {
+            rnes_cartridge::Mirroring::Horizontal
+        }
+        
+        fn clone_mapper(&self) -> Box<dyn Mapper> {
+            Box::new(self.clone())
+        }
+        
+        fn save_state(&self) -> rnes_mappers::MapperState<'_> {
+            rnes_mappers::MapperState::Stateless
+        }
+        
+        fn load_state(&mut self, _state: rnes_mappers::MapperState<'_>) -> RnesResult<()> {
+            Ok(())
+        }
+        
+        fn notify_scanline(&mut self, scanline: Scanline) {
+            self.scanlines.lock().unwrap().push(scanline);
+        }
+        
+        fn ppu_address_changed(&mut self, addr: Word) {
+            self.addresses.lock().unwrap().push(addr);
+        }
+    }
+    
+    #[test]
+    fn test_mapper_hooks() {
+        let mapper = ProbeMapper::default();
+        let scanlines = mapper.scanlines.clone();
+        let addresses = mapper.addresses.clone();
+        let mut ppu = Ppu::new(Box::new(mapper));
+        
+        // $2006 puts the new address on the bus, $2007 accesses it
+        ppu.write_register(0x2006, 0x10).unwrap();
+        ppu.write_register(0x2006, 0x20).unwrap();
+        ppu.write_register(0x2007, 0x55).unwrap();
+        assert_eq!(*addresses.lock().unwrap(), vec![0x1020, 0x1020]);
+        
+        for _ in 0..DOTS_PER_SCANLINE * 2 {
+            ppu.step().unwrap();
+        }
+        assert_eq!(*scanlines.lock().unwrap(), vec![0, 1]);
+    }
+    
+    #[test]
+    fn test_load_state_rejects_out_of_range_values() {
+        let mut ppu = Ppu::new(Box::new(ProbeMapper::default()));
+        let state = ppu.save_state();
+        ppu.load_state(state.clone()).unwrap();
+        
+        let mut bad_dot = state.clone();
+        bad_dot.timing_state.dot = DOTS_PER_SCANLINE as Dot;
+        let mut bad_scanline = state.clone();
+        bad_scanline.timing_state.scanline = 1000;
+        let mut bad_sprites = state.clone();
+        bad_sprites.timing_state.sprite_pipeline.sprites_on_scanline.push(Sprite::new());
+        for corrupt in [bad_dot, bad_scanline, bad_sprites] {
+            assert!(ppu.load_state(corrupt).is_err());
+        }
+    }
 }
 
 // Timing optimization types are already public
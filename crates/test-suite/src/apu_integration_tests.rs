@@ -1,5 +1,10 @@
 use std::path::PathBuf;
+use std::time::Duration;
 use rnes_test_suite::apu_test_runner::{ApuTestRunner, ApuTestResult};
+use rnes_test_suite::report::{TestSuiteReport, ToTestCase};
+use rnes_test_suite::parallel::{ParallelRomRunner, RomOutcome};
+use rnes_test_suite::{ApuScript, AudioGoldenRunner, AudioGoldenResult};
+use rnes_apu::{Apu, CHANNEL_COUNT, VISUALIZER_CAPACITY};
 
 /// Test APU basic functionality
 #[test]
@@ -13,7 +18,211 @@ fn test_apu_basic_functionality() {
     println!("✅ APU basic functionality test passed");
 }
 
+/// Compare scripted APU output with the stored audio fingerprints
+///
+/// Run with `RNES_UPDATE_GOLDEN=1` to regenerate the references.
+#[test]
+fn test_apu_audio_goldens() {
+    let runner = AudioGoldenRunner::new("../../tests/golden/audio");
+    let cases = [
+        // 50% duty pulse at constant volume
+        ("pulse_tone", ApuScript::new()
+            .write(0x4015, 0x01)
+            .write(0x4000, 0xBF)
+            .write(0x4002, 0xFD)
+            .write(0x4003, 0x08)),
+        // Pulse with a decaying envelope
+        ("pulse_envelope", ApuScript::new()
+            .write(0x4015, 0x02)
+            .write(0x4004, 0x83)
+            .write(0x4006, 0x7F)
+            .write(0x4007, 0x09)),
+        // Upward sweep
+        ("pulse_sweep", ApuScript::new()
+            .write(0x4015, 0x01)
+            .write(0x4000, 0x7F)
+            .write(0x4001, 0x92)
+            .write(0x4002, 0x00)
+            .write(0x4003, 0x0A)),
+        ("triangle_tone", ApuScript::new()
+            .write(0x4015, 0x04)
+            .write(0x4008, 0xFF)
+            .write(0x400A, 0xFD)
+            .write(0x400B, 0x08)),
+        // Noise burst that is silenced halfway through
+        ("noise_burst", ApuScript::new()
+            .write(0x4015, 0x08)
+            .write(0x400C, 0x3F)
+            .write(0x400E, 0x04)
+            .write(0x400F, 0x08)
+            .wait_frames(5)
+            .write(0x4015, 0x00)),
+    ];
+
+    let mut failures = Vec::new();
+    for (name, script) in cases {
+        match runner.check(name, &script, 10) {
+            Ok(AudioGoldenResult::Matched) => println!("  ✅ {} matches", name),
+            Ok(AudioGoldenResult::Updated) => println!("  📝 {} reference updated", name),
+            Ok(AudioGoldenResult::Missing) => {
+                println!("  ⚠️  {} has no reference, run with RNES_UPDATE_GOLDEN=1", name);
+            }
+            Ok(AudioGoldenResult::Mismatch { window, reason }) => {
+                println!("  ❌ {}: window {}: {}", name, window, reason);
+                failures.push(name);
+            }
+            Err(e) => {
+                println!("  💥 {}: {}", name, e);
+                failures.push(name);
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "Audio golden mismatches: {:?}", failures);
+}
+
 /// Test Blargg's APU test suite
+/// Per-channel levels and decoded registers for visualizers
+#[test]
+fn test_apu_visualizer_feed() {
+    let mut apu = Apu::new();
+    apu.set_visualizer_enabled(true);
+    apu.write_register(0x4015, 0x01).unwrap();
+    apu.write_register(0x4000, 0xBC).unwrap(); // 50% duty, constant volume 12
+    apu.write_register(0x4002, 0xFD).unwrap();
+    apu.write_register(0x4003, 0x08).unwrap(); // Period $0FD, length 254
+    
+    // About one NTSC frame
+    for _ in 0..29_780 {
+        apu.step().unwrap();
+    }
+    
+    let mut levels = [[0.0; CHANNEL_COUNT]; 1024];
+    let count = apu.drain_channel_levels(&mut levels);
+    assert!((730..=740).contains(&count), "{} samples", count);
+    assert!(levels[..count].iter().any(|level| level[0] == 12.0 / 15.0));
+    assert!(levels[..count].iter().any(|level| level[0] == 0.0));
+    assert!(levels[..count].iter().all(|level| level[1..].iter().all(|&other| other == 0.0)));
+    assert_eq!(apu.drain_channel_levels(&mut levels), 0);
+    
+    let pulse = apu.snapshot().pulse1;
+    assert!(pulse.enabled);
+    assert_eq!((pulse.period, pulse.volume, pulse.duty), (0x0FD, 12, 2));
+    assert!(pulse.length_remaining > 0);
+    let pitch = pulse.frequency(1_789_773).unwrap();
+    assert!((pitch - 440.0).abs() < 1.0, "{} Hz", pitch);
+    assert_eq!(apu.snapshot().triangle.frequency(1_789_773), None);
+    
+    apu.set_visualizer_enabled(false);
+    apu.step().unwrap();
+    assert_eq!(apu.drain_channel_levels(&mut levels), 0);
+    println!("✅ APU visualizer feed works");
+}
+
+/// $4015 DMC enable, status bit and sample-finished IRQ
+#[test]
+fn test_dmc_status_and_irq() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4012, 0x00).unwrap();
+    apu.write_register(0x4013, 0x01).unwrap(); // 17 bytes
+    apu.write_register(0x4010, 0x8F).unwrap(); // IRQ on, fastest rate
+    
+    apu.write_register(0x4015, 0x10).unwrap();
+    assert_eq!(apu.read_register(0x4015).unwrap() & 0x10, 0x10);
+    assert_eq!(apu.snapshot().dmc.length_remaining, 17);
+    for _ in 0..2000 {
+        apu.step().unwrap();
+    }
+    let remaining = apu.snapshot().dmc.length_remaining;
+    assert!(remaining > 0 && remaining < 17, "{} bytes left", remaining);
+    
+    // Setting the bit again while playing does not restart the sample
+    apu.write_register(0x4015, 0x10).unwrap();
+    assert_eq!(apu.snapshot().dmc.length_remaining, remaining);
+    
+    // Clearing it stops the sample without raising an IRQ
+    apu.write_register(0x4015, 0x00).unwrap();
+    assert_eq!(apu.read_register(0x4015).unwrap() & 0x10, 0);
+    for _ in 0..10_000 {
+        apu.step().unwrap();
+    }
+    assert!(!apu.dmc_irq_pending());
+    assert!(!apu.take_dmc_sample_finished());
+    
+    // A finished sample restarts and raises the IRQ when it ends
+    apu.write_register(0x4015, 0x10).unwrap();
+    assert_eq!(apu.snapshot().dmc.length_remaining, 17);
+    for _ in 0..10_000 {
+        apu.step().unwrap();
+    }
+    assert_eq!(apu.read_register(0x4015).unwrap() & 0x90, 0x80);
+    assert!(apu.take_dmc_sample_finished());
+    assert!(!apu.take_dmc_sample_finished());
+    
+    // $4015 writes acknowledge the IRQ
+    apu.write_register(0x4015, 0x00).unwrap();
+    assert!(!apu.dmc_irq_pending());
+    
+    // Looping samples never finish
+    apu.write_register(0x4010, 0xCF).unwrap();
+    apu.write_register(0x4015, 0x10).unwrap();
+    for _ in 0..10_000 {
+        apu.step().unwrap();
+    }
+    assert_eq!(apu.read_register(0x4015).unwrap() & 0x90, 0x10);
+    assert!(!apu.take_dmc_sample_finished());
+    println!("✅ DMC $4015 handling works");
+}
+
+/// Triangle ultrasonic silencing, halting and phase on $400B writes
+#[test]
+fn test_triangle_ultrasonic_and_phase() {
+    let start = |silence: bool, period_low: u8, linear: u8| {
+        let mut apu = Apu::new();
+        apu.set_silence_ultrasonic_triangle(silence);
+        apu.set_visualizer_enabled(true);
+        apu.write_register(0x4015, 0x04).unwrap();
+        apu.write_register(0x4008, linear).unwrap();
+        apu.write_register(0x400A, period_low).unwrap();
+        apu.write_register(0x400B, 0x08).unwrap();
+        apu
+    };
+    let triangle_levels = |apu: &mut Apu, cycles: usize| {
+        for _ in 0..cycles {
+            apu.step().unwrap();
+        }
+        let mut levels = [[0.0; CHANNEL_COUNT]; VISUALIZER_CAPACITY];
+        let count = apu.drain_channel_levels(&mut levels);
+        levels[..count].iter().map(|level| level[2]).collect::<Vec<f32>>()
+    };
+    
+    // The linear counter first loads at cycle 14914, so look after it
+    let held = triangle_levels(&mut start(true, 0x01, 0xFF), 20_000);
+    assert!(held[200..].windows(2).all(|pair| pair[0] == pair[1]));
+    let ultrasonic = triangle_levels(&mut start(false, 0x01, 0xFF), 20_000);
+    assert!(ultrasonic[200..].windows(2).any(|pair| pair[0] != pair[1]));
+    
+    // Rewriting $400B keeps the sequencer where it is
+    let mut apu = start(true, 0xFD, 0xFF);
+    triangle_levels(&mut apu, 10_000);
+    while apu.snapshot().triangle.output == 1.0 {
+        apu.step().unwrap();
+    }
+    let before = apu.snapshot().triangle.output;
+    apu.write_register(0x400B, 0x08).unwrap();
+    assert_eq!(apu.snapshot().triangle.output, before);
+    
+    // The linear counter loads at cycle 14914 and runs out at 29828;
+    // after that the output holds its last level
+    let mut apu = start(true, 0xFD, 0x01);
+    triangle_levels(&mut apu, 29_828);
+    let halted = apu.snapshot().triangle.output;
+    assert!(halted > 0.0);
+    let after = triangle_levels(&mut apu, 10_000);
+    assert!(after.iter().all(|&level| level == halted));
+    println!("✅ Triangle ultrasonic silencing and phase work");
+}
+
 #[test]
 #[ignore] // Requires test ROMs to be downloaded
 fn test_blargg_apu_suite() {
@@ -85,55 +294,75 @@ fn test_blargg_apu_suite() {
         "50.irq_timing40.nes",
     ];
     
+    let mut report = TestSuiteReport::new("blargg_apu_suite");
+    let mut rom_paths = Vec::new();
     for test_file in test_files {
         let rom_path = apu_test_dir.join(test_file);
         
         if rom_path.exists() {
-            println!("🧪 Running APU test: {}", test_file);
-            
+            rom_paths.push(rom_path);
+        } else {
+            report.record_skipped(test_file, "not found");
+            println!("⚠️  Test file not found: {}", test_file);
+        }
+    }
+    
+    // The ROMs are independent, so spread them across all cores
+    let runs = ParallelRomRunner::new()
+        .with_timeout(Duration::from_secs(120))
+        .run(&rom_paths, |rom_path| {
             let mut runner = ApuTestRunner::new()
                 .with_max_cycles(1000000)
                 .with_audio_check(true)
                 .with_min_audio_samples(500);
-            
-            match runner.load_rom(&rom_path) {
-                Ok(_) => {
-                    match runner.run_test() {
-                        Ok(result) => {
-                            match result {
-                                ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
-                                    if status == 0 {
-                                        println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
-                                                status, cycles, audio_samples, avg_amplitude);
-                                    } else {
-                                        println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
-                                                status, cycles, audio_samples, avg_amplitude);
-                                    }
-                                }
-                                ApuTestResult::Timeout { cycles, audio_samples } => {
-                                    println!("  ⏰ TIMEOUT ({} cycles, {} samples)", cycles, audio_samples);
-                                }
-                                ApuTestResult::InfiniteLoop { cycles, pc, audio_samples } => {
-                                    println!("  🔄 INFINITE LOOP at PC=0x{:04X} ({} cycles, {} samples)", pc, cycles, audio_samples);
-                                }
-                                ApuTestResult::Error { error, cycles } => {
-                                    println!("  💥 ERROR: {} ({} cycles)", error, cycles);
-                                }
+            runner.load_rom(rom_path)?;
+            runner.run_test()
+        });
+    
+    for run in runs {
+        let test_file = run.name();
+        report.push(run.to_test_case(&test_file));
+        println!("🧪 APU test: {}", test_file);
+        
+        match run.outcome {
+            RomOutcome::Finished(Ok(result)) => {
+                match result {
+                    ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
+                        if status == 0 {
+                            println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
+                                    status, cycles, audio_samples, avg_amplitude);
+                        } else {
+                            println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
+                                    status, cycles, audio_samples, avg_amplitude);
+                            if let Some(message) = message {
+                                println!("     {}", message.trim());
                             }
                         }
-                        Err(e) => {
-                            println!("  💥 ERROR: {}", e);
-                        }
                     }
-                }
-                Err(e) => {
-                    println!("  📁 LOAD ERROR: {}", e);
+                    ApuTestResult::Timeout { cycles, audio_samples } => {
+                        println!("  ⏰ TIMEOUT ({} cycles, {} samples)", cycles, audio_samples);
+                    }
+                    ApuTestResult::InfiniteLoop { cycles, pc, audio_samples } => {
+                        println!("  🔄 INFINITE LOOP at PC=0x{:04X} ({} cycles, {} samples)", pc, cycles, audio_samples);
+                    }
+                    ApuTestResult::Error { error, cycles } => {
+                        println!("  💥 ERROR: {} ({} cycles)", error, cycles);
+                    }
                 }
             }
-        } else {
-            println!("⚠️  Test file not found: {}", test_file);
+            RomOutcome::Finished(Err(e)) => {
+                println!("  💥 ERROR: {}", e);
+            }
+            RomOutcome::Panicked(message) => {
+                println!("  💥 PANIC: {}", message);
+            }
+            RomOutcome::TimedOut => {
+                println!("  ⏰ WALL-CLOCK TIMEOUT ({:.1}s)", run.elapsed.as_secs_f64());
+            }
         }
     }
+    
+    report.finish();
 }
 
 /// Test APU frame counter functionality
@@ -162,13 +391,16 @@ fn test_apu_frame_counter() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
                                 if status == 0 {
                                     println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             _ => {
@@ -216,13 +448,16 @@ fn test_apu_length_counter() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
                                 if status == 0 {
                                     println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             _ => {
@@ -270,13 +505,16 @@ fn test_apu_reset() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
                                 if status == 0 {
                                     println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             _ => {
@@ -324,13 +562,16 @@ fn test_apu_irq() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
                                 if status == 0 {
                                     println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             _ => {
@@ -378,13 +619,16 @@ fn test_apu_sweep() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
                                 if status == 0 {
                                     println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             _ => {
@@ -432,13 +676,16 @@ fn test_apu_envelope() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                            ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, message } => {
                                 if status == 0 {
                                     println!("  ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                             status, cycles, audio_samples, avg_amplitude);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             _ => {
@@ -3,6 +3,7 @@ use rnes_cartridge::Cartridge;
 use rnes_common::{RnesResult, AudioSample};
 use std::path::Path;
 use std::time::Duration;
+use crate::blargg::{BlarggMonitor, BlarggPoll};
 
 /// APU test result
 #[derive(Debug, Clone)]
@@ -13,6 +14,8 @@ pub enum ApuTestResult {
         cycles: u32,
         audio_samples: usize,
         avg_amplitude: f32,
+        /// Result text from ROMs using the blargg protocol
+        message: Option<String>,
     },
     /// Test timed out
     Timeout {
@@ -100,6 +103,7 @@ impl ApuTestRunner {
         let mut last_pc = 0u16;
         let mut pc_repeat_count = 0;
         const PC_REPEAT_THRESHOLD: u32 = 1000;
+        let mut blargg = BlarggMonitor::new();
 
         // Start emulator
         self.emulator.start();
@@ -120,6 +124,30 @@ impl ApuTestRunner {
             }
             cycles += step_cycles;
 
+            // Collect audio samples
+            if self.check_audio {
+                audio_samples.extend(self.emulator.drain_audio());
+            }
+
+            // Tests using the blargg protocol report their own status
+            match blargg.poll(&self.emulator.bus, cycles as u64) {
+                BlarggPoll::Done { status, message } => {
+                    return Ok(ApuTestResult::Completed {
+                        status,
+                        cycles,
+                        audio_samples: audio_samples.len(),
+                        avg_amplitude: self.calculate_average_amplitude(&audio_samples),
+                        message: Some(message),
+                    });
+                }
+                BlarggPoll::Reset => {
+                    self.emulator.soft_reset()?;
+                    continue;
+                }
+                BlarggPoll::Running => continue,
+                BlarggPoll::Absent => {}
+            }
+
             // Check for infinite loop
             let current_pc = self.emulator.cpu().pc;
             if current_pc == last_pc {
@@ -136,12 +164,6 @@ impl ApuTestRunner {
                 last_pc = current_pc;
             }
 
-            // Collect audio samples
-            if self.check_audio {
-                let samples = self.emulator.get_audio_samples();
-                audio_samples.extend(samples);
-            }
-
             // Check if test completed (look for specific patterns in memory)
             if self.check_test_completion() {
                 let status = self.get_test_status();
@@ -152,6 +174,7 @@ impl ApuTestRunner {
                     cycles,
                     audio_samples: audio_samples.len(),
                     avg_amplitude,
+                    message: None,
                 });
             }
         }
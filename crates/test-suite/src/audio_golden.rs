@@ -0,0 +1,350 @@
+//! Audio golden-sample regression harness
+//!
+//! The APU is driven directly by scripted register writes, with no ROM or
+//! CPU involved. The rendered audio is reduced to a fingerprint (RMS level
+//! and dominant frequency per window) and compared with a stored JSON
+//! reference. `RNES_UPDATE_GOLDEN=1` regenerates the references, as for
+//! golden frames.
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use rnes_apu::Apu;
+use rnes_common::{AudioSample, Byte, RnesError, RnesResult, Word, AUDIO_SAMPLE_RATE, CPU_CYCLES_PER_FRAME};
+use crate::golden::GOLDEN_UPDATE_ENV;
+
+/// Samples per fingerprint window (about 23ms at 44.1 kHz)
+pub const FINGERPRINT_WINDOW: usize = 1024;
+
+/// A timed sequence of APU register writes
+#[derive(Debug, Clone, Default)]
+pub struct ApuScript {
+    /// (CPU cycle, register, value), in cycle order
+    writes: Vec<(u64, Word, Byte)>,
+    cursor: u64,
+}
+
+impl ApuScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a register at the current point in the script
+    pub fn write(mut self, addr: Word, value: Byte) -> Self {
+        self.writes.push((self.cursor, addr, value));
+        self
+    }
+
+    /// Advance the script by `cycles` CPU cycles
+    pub fn wait_cycles(mut self, cycles: u64) -> Self {
+        self.cursor += cycles;
+        self
+    }
+
+    /// Advance the script by `frames` NTSC frames
+    pub fn wait_frames(self, frames: u64) -> Self {
+        self.wait_cycles(frames * CPU_CYCLES_PER_FRAME as u64)
+    }
+
+    /// Render `frames` frames of audio
+    pub fn render(&self, frames: u32) -> RnesResult<Vec<AudioSample>> {
+        let mut apu = Apu::new();
+        let mut samples = Vec::new();
+        let mut writes = self.writes.iter().peekable();
+
+        for cycle in 0..frames as u64 * CPU_CYCLES_PER_FRAME as u64 {
+            while let Some(&(_, addr, value)) = writes.next_if(|(at, _, _)| *at <= cycle) {
+                apu.write_register(addr, value)?;
+            }
+            apu.step()?;
+            // The APU only buffers a few thousand samples
+            if cycle % CPU_CYCLES_PER_FRAME as u64 == 0 {
+                samples.extend(apu.drain_samples());
+            }
+        }
+        samples.extend(apu.drain_samples());
+        Ok(samples)
+    }
+}
+
+/// Compact description of how a piece of audio sounds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioFingerprint {
+    pub sample_rate: u32,
+    pub window: usize,
+    /// RMS level of each window
+    pub rms: Vec<f32>,
+    /// Strongest non-DC frequency of each window in Hz (0 for silence)
+    pub peaks: Vec<f32>,
+}
+
+impl AudioFingerprint {
+    /// Fingerprint samples in windows of `FINGERPRINT_WINDOW`
+    pub fn from_samples(samples: &[AudioSample], sample_rate: u32) -> Self {
+        let mut rms = Vec::new();
+        let mut peaks = Vec::new();
+        for window in samples.chunks_exact(FINGERPRINT_WINDOW) {
+            let level = (window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32).sqrt();
+            rms.push(level);
+            peaks.push(dominant_frequency(window, sample_rate));
+        }
+        Self { sample_rate, window: FINGERPRINT_WINDOW, rms, peaks }
+    }
+
+    /// Width of one spectrum bin in Hz
+    pub fn bin_width(&self) -> f32 {
+        self.sample_rate as f32 / self.window as f32
+    }
+}
+
+/// How far a fingerprint may drift from its reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioTolerance {
+    /// Allowed RMS difference per window, relative to the louder of the two
+    pub rms_ratio: f32,
+    /// Allowed peak frequency difference, in spectrum bins
+    pub frequency_bins: f32,
+}
+
+impl Default for AudioTolerance {
+    fn default() -> Self {
+        Self { rms_ratio: 0.05, frequency_bins: 1.0 }
+    }
+}
+
+/// Outcome of checking a script against its reference
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioGoldenResult {
+    Matched,
+    Updated,
+    Missing,
+    /// The first window that differs and why
+    Mismatch { window: usize, reason: String },
+}
+
+impl AudioGoldenResult {
+    /// Check if the audio matched or the reference was regenerated
+    pub fn is_pass(&self) -> bool {
+        matches!(self, AudioGoldenResult::Matched | AudioGoldenResult::Updated)
+    }
+}
+
+/// Compare a fingerprint with a reference, returning the first difference
+pub fn compare_fingerprints(
+    expected: &AudioFingerprint,
+    actual: &AudioFingerprint,
+    tolerance: AudioTolerance,
+) -> AudioGoldenResult {
+    if expected.rms.len() != actual.rms.len() {
+        return AudioGoldenResult::Mismatch {
+            window: expected.rms.len().min(actual.rms.len()),
+            reason: format!("expected {} windows, got {}", expected.rms.len(), actual.rms.len()),
+        };
+    }
+
+    let max_shift = tolerance.frequency_bins * expected.bin_width();
+    for (window, (want, got)) in expected.rms.iter().zip(&actual.rms).enumerate() {
+        // The small floor keeps near-silent windows from failing on rounding
+        if (want - got).abs() > tolerance.rms_ratio * want.max(*got) + 1e-6 {
+            return AudioGoldenResult::Mismatch {
+                window,
+                reason: format!("RMS expected {:.4}, got {:.4}", want, got),
+            };
+        }
+        let (want_peak, got_peak) = (expected.peaks[window], actual.peaks[window]);
+        if (want_peak - got_peak).abs() > max_shift {
+            return AudioGoldenResult::Mismatch {
+                window,
+                reason: format!("peak expected {:.1} Hz, got {:.1} Hz", want_peak, got_peak),
+            };
+        }
+    }
+    AudioGoldenResult::Matched
+}
+
+/// Renders APU scripts and compares them with stored fingerprints
+pub struct AudioGoldenRunner {
+    golden_dir: PathBuf,
+    tolerance: AudioTolerance,
+    update: bool,
+}
+
+impl AudioGoldenRunner {
+    /// Create a runner that keeps references in `golden_dir`
+    pub fn new(golden_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            golden_dir: golden_dir.into(),
+            tolerance: AudioTolerance::default(),
+            update: std::env::var(GOLDEN_UPDATE_ENV).is_ok_and(|value| value != "0"),
+        }
+    }
+
+    /// Set how much the audio may differ from its reference
+    pub fn with_tolerance(mut self, tolerance: AudioTolerance) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Write references instead of comparing against them
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Path of the reference fingerprint for `name`
+    pub fn golden_path(&self, name: &str) -> PathBuf {
+        self.golden_dir.join(format!("{}.json", name))
+    }
+
+    /// Render `script` for `frames` frames and check it against `name`
+    pub fn check(&self, name: &str, script: &ApuScript, frames: u32) -> RnesResult<AudioGoldenResult> {
+        let samples = script.render(frames)?;
+        self.check_fingerprint(name, &AudioFingerprint::from_samples(&samples, AUDIO_SAMPLE_RATE))
+    }
+
+    /// Check a fingerprint against the reference for `name`
+    pub fn check_fingerprint(&self, name: &str, actual: &AudioFingerprint) -> RnesResult<AudioGoldenResult> {
+        let path = self.golden_path(name);
+        if self.update {
+            fs::create_dir_all(&self.golden_dir)?;
+            let json = serde_json::to_string_pretty(actual)
+                .map_err(|e| RnesError::Serialization(e.to_string()))?;
+            fs::write(&path, json)?;
+            tracing::info!("Updated audio golden {}", path.display());
+            return Ok(AudioGoldenResult::Updated);
+        }
+
+        if !path.exists() {
+            return Ok(AudioGoldenResult::Missing);
+        }
+        let expected: AudioFingerprint = serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| RnesError::Serialization(format!("{}: {}", path.display(), e)))?;
+        Ok(compare_fingerprints(&expected, actual, self.tolerance))
+    }
+}
+
+/// Strongest non-DC frequency in `window`, which must be a power of two long
+fn dominant_frequency(window: &[f32], sample_rate: u32) -> f32 {
+    let n = window.len();
+    let mean = window.iter().sum::<f32>() / n as f32;
+    // Hann window to keep leakage from hiding the peak
+    let mut re: Vec<f32> = window.iter().enumerate()
+        .map(|(i, s)| (s - mean) * (0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    let (bin, power) = (1..n / 2)
+        .map(|k| (k, re[k] * re[k] + im[k] * im[k]))
+        .fold((0, 0.0f32), |best, bin| if bin.1 > best.1 { bin } else { best });
+    if power < 1e-6 {
+        0.0
+    } else {
+        bin as f32 * sample_rate as f32 / n as f32
+    }
+}
+
+/// In-place radix-2 FFT
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / AUDIO_SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_fingerprint_finds_sine_peak() {
+        let fingerprint = AudioFingerprint::from_samples(&sine(1000.0, 0.5, FINGERPRINT_WINDOW * 3), AUDIO_SAMPLE_RATE);
+        assert_eq!(fingerprint.rms.len(), 3);
+        for (rms, peak) in fingerprint.rms.iter().zip(&fingerprint.peaks) {
+            assert!((rms - 0.5 / 2f32.sqrt()).abs() < 0.01, "rms {}", rms);
+            assert!((peak - 1000.0).abs() <= fingerprint.bin_width(), "peak {}", peak);
+        }
+
+        let silence = AudioFingerprint::from_samples(&[0.0; FINGERPRINT_WINDOW], AUDIO_SAMPLE_RATE);
+        assert_eq!(silence.peaks, [0.0]);
+    }
+
+    #[test]
+    fn test_compare_fingerprints_reports_first_difference() {
+        let expected = AudioFingerprint::from_samples(&sine(440.0, 0.3, FINGERPRINT_WINDOW * 2), AUDIO_SAMPLE_RATE);
+        let mut samples = sine(440.0, 0.3, FINGERPRINT_WINDOW);
+        samples.extend(sine(880.0, 0.3, FINGERPRINT_WINDOW));
+        let actual = AudioFingerprint::from_samples(&samples, AUDIO_SAMPLE_RATE);
+
+        let tolerance = AudioTolerance::default();
+        assert_eq!(compare_fingerprints(&expected, &expected, tolerance), AudioGoldenResult::Matched);
+        match compare_fingerprints(&expected, &actual, tolerance) {
+            AudioGoldenResult::Mismatch { window, reason } => {
+                assert_eq!(window, 1);
+                assert!(reason.starts_with("peak"), "{}", reason);
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_script_render_and_golden_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rnes_audio_golden_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let script = ApuScript::new()
+            .write(0x4015, 0x01)
+            .write(0x4000, 0xBF)
+            .write(0x4002, 0xFD)
+            .write(0x4003, 0x08)
+            .wait_frames(3)
+            .write(0x4015, 0x00);
+
+        let samples = script.render(6).unwrap();
+        assert!(samples.len() >= FINGERPRINT_WINDOW * 4);
+        let fingerprint = AudioFingerprint::from_samples(&samples, AUDIO_SAMPLE_RATE);
+        assert!(fingerprint.rms[0] > 0.0);
+        assert_eq!(*fingerprint.rms.last().unwrap(), 0.0);
+
+        let runner = AudioGoldenRunner::new(&dir).with_update(true);
+        assert_eq!(runner.check("pulse", &script, 6).unwrap(), AudioGoldenResult::Updated);
+        let runner = runner.with_update(false);
+        assert_eq!(runner.check("pulse", &script, 6).unwrap(), AudioGoldenResult::Matched);
+        assert_eq!(runner.check("other", &script, 6).unwrap(), AudioGoldenResult::Missing);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,210 @@
+//! Result protocol used by blargg's test ROMs
+//!
+//! Once $6001-$6003 hold the signature `DE B0 61`, $6000 reports the test
+//! status and a zero-terminated result text starts at $6004.
+//!
+//! Older ROMs such as `sprite_hit_tests_2005.10.05` and
+//! `sprite_overflow_tests` predate this and only leave a result code at $F8.
+
+use rnes_common::{Byte, Word};
+use rnes_core::MemoryPeek;
+
+/// Address of the status byte
+pub const BLARGG_STATUS_ADDR: Word = 0x6000;
+
+/// Signature at $6001-$6003 marking the status byte as valid
+pub const BLARGG_SIGNATURE: [Byte; 3] = [0xDE, 0xB0, 0x61];
+
+/// Start of the zero-terminated result text
+pub const BLARGG_TEXT_ADDR: Word = 0x6004;
+
+/// CPU cycles to wait before honouring a reset request (about 100ms)
+pub const BLARGG_RESET_DELAY_CYCLES: u64 = 180_000;
+
+/// Status reported at $6000
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlarggStatus {
+    /// Test is still running ($80)
+    Running,
+    /// Test wants the reset button pressed ($81)
+    ResetRequested,
+    /// Test finished with a result code (0 is a pass)
+    Done(u8),
+}
+
+impl BlarggStatus {
+    /// Read the status, or `None` if the signature hasn't been written
+    pub fn read<M: MemoryPeek + ?Sized>(memory: &M) -> Option<Self> {
+        let mut signature = [0; 3];
+        memory.peek_range(BLARGG_STATUS_ADDR + 1, &mut signature);
+        if signature != BLARGG_SIGNATURE {
+            return None;
+        }
+
+        Some(match memory.peek(BLARGG_STATUS_ADDR) {
+            0x80 => BlarggStatus::Running,
+            0x81 => BlarggStatus::ResetRequested,
+            code => BlarggStatus::Done(code),
+        })
+    }
+}
+
+/// Read the result text written at $6004
+pub fn read_blargg_text<M: MemoryPeek + ?Sized>(memory: &M) -> String {
+    let bytes: Vec<Byte> = (BLARGG_TEXT_ADDR..=0x7FFF)
+        .map(|addr| memory.peek(addr))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
+}
+
+/// Zero-page byte holding an older test's result: 1 is a pass, any other
+/// value the number of the failed test
+pub const BLARGG_LEGACY_RESULT_ADDR: Word = 0x00F8;
+
+/// Read the result of an older test ROM once it has finished
+///
+/// These ROMs end in a `JMP` to itself, so the result is only read while
+/// the instruction at `pc` is one; before that $F8 may hold anything.
+/// Returns 0 for a pass and the failed test's number otherwise.
+pub fn read_legacy_result<M: MemoryPeek + ?Sized>(memory: &M, pc: Word) -> Option<u8> {
+    let [low, high] = pc.to_le_bytes();
+    let mut instruction = [0; 3];
+    memory.peek_range(pc, &mut instruction);
+    if instruction != [0x4C, low, high] {
+        return None;
+    }
+    match memory.peek(BLARGG_LEGACY_RESULT_ADDR) {
+        0 => None,
+        1 => Some(0),
+        code => Some(code),
+    }
+}
+
+/// What a test runner should do after polling the protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlarggPoll {
+    /// The ROM doesn't use the protocol (yet)
+    Absent,
+    /// Keep running
+    Running,
+    /// Reset the console now
+    Reset,
+    /// Test finished
+    Done { status: u8, message: String },
+}
+
+/// Follows the protocol across a test run, timing reset requests
+#[derive(Debug, Default)]
+pub struct BlarggMonitor {
+    reset_requested_at: Option<u64>,
+    reset_issued: bool,
+}
+
+impl BlarggMonitor {
+    /// Create a monitor for a new test run
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the protocol after an instruction, `cycles` into the run
+    pub fn poll<M: MemoryPeek + ?Sized>(&mut self, memory: &M, cycles: u64) -> BlarggPoll {
+        let Some(status) = BlarggStatus::read(memory) else {
+            return BlarggPoll::Absent;
+        };
+
+        match status {
+            BlarggStatus::Running => {
+                self.reset_requested_at = None;
+                self.reset_issued = false;
+                BlarggPoll::Running
+            }
+            // The status stays at $81 until the ROM has restarted
+            BlarggStatus::ResetRequested if self.reset_issued => BlarggPoll::Running,
+            BlarggStatus::ResetRequested => {
+                let requested_at = *self.reset_requested_at.get_or_insert(cycles);
+                if cycles.saturating_sub(requested_at) >= BLARGG_RESET_DELAY_CYCLES {
+                    self.reset_requested_at = None;
+                    self.reset_issued = true;
+                    BlarggPoll::Reset
+                } else {
+                    BlarggPoll::Running
+                }
+            }
+            BlarggStatus::Done(status) => BlarggPoll::Done {
+                status,
+                message: read_blargg_text(memory),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ram(Vec<Byte>);
+
+    impl MemoryPeek for Ram {
+        fn peek(&self, addr: Word) -> Byte {
+            self.0[addr as usize]
+        }
+    }
+
+    fn ram_with_status(status: Byte, text: &str) -> Ram {
+        let mut ram = vec![0; 0x10000];
+        ram[0x6000] = status;
+        ram[0x6001..0x6004].copy_from_slice(&BLARGG_SIGNATURE);
+        ram[0x6004..0x6004 + text.len()].copy_from_slice(text.as_bytes());
+        Ram(ram)
+    }
+
+    #[test]
+    fn test_status_requires_signature() {
+        let mut ram = ram_with_status(0x00, "");
+        assert_eq!(BlarggStatus::read(&ram), Some(BlarggStatus::Done(0)));
+
+        ram.0[0x6002] = 0;
+        assert_eq!(BlarggStatus::read(&ram), None);
+        assert_eq!(BlarggMonitor::new().poll(&ram, 0), BlarggPoll::Absent);
+    }
+
+    #[test]
+    fn test_done_reports_text() {
+        let ram = ram_with_status(0x03, "\n01-basics\n\nFailed #3\n");
+        assert_eq!(
+            BlarggMonitor::new().poll(&ram, 0),
+            BlarggPoll::Done { status: 3, message: "\n01-basics\n\nFailed #3".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_legacy_result_read_at_final_loop() {
+        let mut ram = Ram(vec![0; 0x10000]);
+        ram.0[0xE010..0xE013].copy_from_slice(&[0x4C, 0x10, 0xE0]);
+        assert_eq!(read_legacy_result(&ram, 0xE010), None);
+
+        ram.0[BLARGG_LEGACY_RESULT_ADDR as usize] = 1;
+        assert_eq!(read_legacy_result(&ram, 0xE010), Some(0));
+        assert_eq!(read_legacy_result(&ram, 0xE011), None);
+        ram.0[BLARGG_LEGACY_RESULT_ADDR as usize] = 4;
+        assert_eq!(read_legacy_result(&ram, 0xE010), Some(4));
+    }
+
+    #[test]
+    fn test_reset_request_is_delayed_and_issued_once() {
+        let mut ram = ram_with_status(0x81, "");
+        let mut monitor = BlarggMonitor::new();
+
+        assert_eq!(monitor.poll(&ram, 1000), BlarggPoll::Running);
+        assert_eq!(monitor.poll(&ram, 1000 + BLARGG_RESET_DELAY_CYCLES - 1), BlarggPoll::Running);
+        assert_eq!(monitor.poll(&ram, 1000 + BLARGG_RESET_DELAY_CYCLES), BlarggPoll::Reset);
+        assert_eq!(monitor.poll(&ram, 1000 + 3 * BLARGG_RESET_DELAY_CYCLES), BlarggPoll::Running);
+
+        ram.0[0x6000] = 0x80;
+        assert_eq!(monitor.poll(&ram, 1000 + 3 * BLARGG_RESET_DELAY_CYCLES), BlarggPoll::Running);
+        ram.0[0x6000] = 0x81;
+        assert_eq!(monitor.poll(&ram, 1000 + 3 * BLARGG_RESET_DELAY_CYCLES), BlarggPoll::Running);
+        assert_eq!(monitor.poll(&ram, 1000 + 4 * BLARGG_RESET_DELAY_CYCLES), BlarggPoll::Reset);
+    }
+}
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 use rnes_test_suite::cpu_test_runner::{CpuTestRunner, TestResult};
+use rnes_test_suite::report::TestSuiteReport;
+use rnes_test_suite::{NestestRunner, SingleStepTestRunner};
 
 /// Test CPU basic functionality
 #[test]
@@ -36,6 +38,7 @@ fn test_blargg_cpu_suite() {
         "official.nes",
     ];
     
+    let mut report = TestSuiteReport::new("blargg_cpu_suite");
     for test_file in test_files {
         let rom_path = cpu_test_dir.join(test_file);
         
@@ -47,14 +50,19 @@ fn test_blargg_cpu_suite() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
-                                TestResult::Completed { status, cycles, .. } => {
+                                TestResult::Completed { status, cycles, message, .. } => {
                                     if status == 0 {
                                         println!("  ✅ PASS ({} cycles)", cycles);
                                     } else {
                                         println!("  ❌ FAIL (status: {}, {} cycles)", status, cycles);
+                                        if let Some(message) = message {
+                                            println!("     {}", message.trim());
+                                        }
                                     }
                                 }
                                 TestResult::Timeout { cycles } => {
@@ -71,13 +79,17 @@ fn test_blargg_cpu_suite() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test CPU dummy reads
@@ -105,11 +117,14 @@ fn test_cpu_dummy_reads() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            TestResult::Completed { status, cycles, .. } => {
+                            TestResult::Completed { status, cycles, message, .. } => {
                                 if status == 0 {
                                     println!("  ✅ PASS ({} cycles)", cycles);
                                 } else {
                                     println!("  ❌ FAIL (status: {}, {} cycles)", status, cycles);
+                                    if let Some(message) = message {
+                                        println!("     {}", message.trim());
+                                    }
                                 }
                             }
                             TestResult::Timeout { cycles } => {
@@ -150,6 +165,7 @@ fn test_branch_timing() {
         "3.Forward_Branch.nes",
     ];
     
+    let mut report = TestSuiteReport::new("branch_timing");
     for test_file in test_files {
         let rom_path = branch_timing_dir.join(test_file);
         
@@ -161,14 +177,19 @@ fn test_branch_timing() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
-                                TestResult::Completed { status, cycles, .. } => {
+                                TestResult::Completed { status, cycles, message, .. } => {
                                     if status == 0 {
                                         println!("  ✅ PASS ({} cycles)", cycles);
                                     } else {
                                         println!("  ❌ FAIL (status: {}, {} cycles)", status, cycles);
+                                        if let Some(message) = message {
+                                            println!("     {}", message.trim());
+                                        }
                                     }
                                 }
                                 TestResult::Timeout { cycles } => {
@@ -185,11 +206,75 @@ fn test_branch_timing() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         }
     }
+    
+    report.finish();
+}
+
+/// Test every opcode against the SingleStepTests JSON vectors
+#[test]
+#[ignore] // Requires the ProcessorTests repository in tests/roms
+fn test_single_step_conformance() {
+    let runner = SingleStepTestRunner::new();
+    
+    if !runner.tests_dir().exists() {
+        println!("⚠️  SingleStepTests not found, see docs/TESTING_GUIDE.md");
+        return;
+    }
+    
+    match runner.run_all() {
+        Ok(reports) => {
+            let mut failed_opcodes = 0;
+            for report in &reports {
+                if report.is_success() {
+                    continue;
+                }
+                failed_opcodes += 1;
+                println!("  ❌ ${:02X}: {}/{} passed", report.opcode, report.passed, report.total);
+                for failure in &report.failures {
+                    println!("     {}: {}", failure.test, failure.mismatches.join(", "));
+                }
+            }
+            println!("🧪 {} opcodes tested, {} with failures", reports.len(), failed_opcodes);
+        }
+        Err(e) => {
+            println!("  💥 ERROR: {}", e);
+        }
+    }
+}
+
+/// Compare our trace of nestest.nes against the canonical nestest.log
+#[test]
+#[ignore] // Requires test ROMs to be downloaded
+fn test_nestest_log() {
+    let nestest_dir = PathBuf::from("../../tests/roms/nes-test-roms/other");
+    let rom_path = nestest_dir.join("nestest.nes");
+    let log_path = nestest_dir.join("nestest.log");
+    
+    if !rom_path.exists() || !log_path.exists() {
+        println!("⚠️  nestest.nes/nestest.log not found, please run: ./scripts/download_cpu_test_roms.sh");
+        return;
+    }
+    
+    match NestestRunner::new().run(&rom_path, &log_path) {
+        Ok(report) => {
+            println!("🧪 nestest: {}/{} lines matched", report.lines_matched, report.lines_total);
+            println!("  Official result: ${:02X}, unofficial result: ${:02X}", report.official_result, report.unofficial_result);
+            if let Some(divergence) = report.divergence {
+                println!("  ❌ {}", divergence);
+            } else {
+                println!("  ✅ PASS");
+            }
+        }
+        Err(e) => {
+            println!("  💥 ERROR: {}", e);
+        }
+    }
 }
 
 /// Convenience function to run all CPU tests
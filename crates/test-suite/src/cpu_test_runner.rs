@@ -2,6 +2,8 @@ use std::fs;
 use std::path::Path;
 use rnes_cpu6502::Cpu;
 use rnes_common::{Byte, MemoryAccess, RnesResult};
+use rnes_core::MemoryPeek;
+use crate::blargg::{BlarggMonitor, BlarggPoll};
 
 /// Simple test memory implementation
 struct TestMemory {
@@ -58,7 +60,7 @@ impl TestMemory {
 }
 
 impl MemoryAccess for TestMemory {
-    fn read_byte(&self, addr: u16) -> RnesResult<Byte> {
+    fn read_byte(&mut self, addr: u16) -> RnesResult<Byte> {
         Ok(self.ram[addr as usize])
     }
     
@@ -67,7 +69,7 @@ impl MemoryAccess for TestMemory {
         Ok(())
     }
     
-    fn read_word(&self, addr: u16) -> RnesResult<u16> {
+    fn read_word(&mut self, addr: u16) -> RnesResult<u16> {
         let low = self.read_byte(addr)? as u16;
         let high = self.read_byte(addr.wrapping_add(1))? as u16;
         Ok(low | (high << 8))
@@ -80,6 +82,12 @@ impl MemoryAccess for TestMemory {
     }
 }
 
+impl MemoryPeek for TestMemory {
+    fn peek(&self, addr: u16) -> Byte {
+        self.ram[addr as usize]
+    }
+}
+
 /// CPU test runner
 pub struct CpuTestRunner {
     cpu: Cpu,
@@ -113,12 +121,31 @@ impl CpuTestRunner {
         let mut cycles = 0;
         let mut last_pc = self.cpu.pc;
         let mut stall_count = 0;
+        let mut blargg = BlarggMonitor::new();
         
         // Run test until maximum cycles or infinite loop detected
         while cycles < self.max_cycles {
             let step_cycles = self.cpu.step(&mut self.memory)?;
             cycles += step_cycles as u64;
             
+            // Tests using the blargg protocol report their own status
+            match blargg.poll(&self.memory, cycles) {
+                BlarggPoll::Done { status, message } => {
+                    return Ok(TestResult::Completed {
+                        cycles,
+                        status,
+                        cpu_state: self.cpu.clone(),
+                        message: Some(message),
+                    });
+                }
+                BlarggPoll::Reset => {
+                    self.cpu.soft_reset(&mut self.memory)?;
+                    continue;
+                }
+                BlarggPoll::Running => continue,
+                BlarggPoll::Absent => {}
+            }
+            
             // Detect infinite loop
             if self.cpu.pc == last_pc {
                 stall_count += 1;
@@ -140,6 +167,7 @@ impl CpuTestRunner {
                         cycles,
                         status,
                         cpu_state: self.cpu.clone(),
+                        message: None,
                     });
                 }
             }
@@ -159,6 +187,8 @@ pub enum TestResult {
         cycles: u64,
         status: Byte,
         cpu_state: Cpu,
+        /// Result text from ROMs using the blargg protocol
+        message: Option<String>,
     },
     Timeout {
         cycles: u64,
@@ -0,0 +1,279 @@
+//! Golden-frame screenshot regression harness
+//!
+//! A ROM is run to a fixed frame and the frame buffer is compared with a
+//! stored PNG. Set `RNES_UPDATE_GOLDEN=1` to write the current frames as the
+//! new goldens instead of comparing.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use rnes_cartridge::Cartridge;
+use rnes_common::{crc32, Config, Pixel, RnesError, RnesResult, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rnes_core::Emulator;
+use crate::png::{decode_png, encode_png};
+
+/// Environment variable that switches the harness to regenerating goldens
+pub const GOLDEN_UPDATE_ENV: &str = "RNES_UPDATE_GOLDEN";
+
+/// How far a frame may drift from its golden and still pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameTolerance {
+    /// Largest per-channel difference that still counts as the same pixel
+    pub max_channel_delta: u8,
+    /// Number of pixels allowed to exceed `max_channel_delta`
+    pub max_differing_pixels: usize,
+}
+
+impl FrameTolerance {
+    /// Require an exact match
+    pub fn exact() -> Self {
+        Self::default()
+    }
+}
+
+/// Pixel-level comparison of two frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameDiff {
+    /// Pixels where any channel differs by more than the tolerance
+    pub differing_pixels: usize,
+    /// Largest channel difference seen anywhere
+    pub max_channel_delta: u8,
+}
+
+/// Outcome of checking one frame against its golden
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoldenResult {
+    /// The frame is within tolerance
+    Matched { hash: u32, diff: FrameDiff },
+    /// The golden was written from the current frame
+    Updated { hash: u32 },
+    /// No golden exists yet
+    Missing { hash: u32 },
+    /// The frame differs; the actual frame and a diff mask were saved
+    Mismatch { hash: u32, diff: FrameDiff, actual_path: PathBuf, diff_path: PathBuf },
+}
+
+impl GoldenResult {
+    /// Check if the frame matched or the golden was regenerated
+    pub fn is_pass(&self) -> bool {
+        matches!(self, GoldenResult::Matched { .. } | GoldenResult::Updated { .. })
+    }
+}
+
+/// Hash a frame buffer, for a quick exact-match check
+pub fn frame_hash(pixels: &[Pixel]) -> u32 {
+    crc32(&frame_to_rgba(pixels))
+}
+
+/// Compare two RGBA8 frames of the same size
+pub fn diff_frames(expected: &[u8], actual: &[u8], max_channel_delta: u8) -> FrameDiff {
+    let mut diff = FrameDiff { differing_pixels: 0, max_channel_delta: 0 };
+    for (want, got) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let delta = want.iter().zip(got).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+        diff.max_channel_delta = diff.max_channel_delta.max(delta);
+        if delta > max_channel_delta {
+            diff.differing_pixels += 1;
+        }
+    }
+    diff
+}
+
+/// Runs ROMs to a frame and compares the picture with golden PNGs
+pub struct GoldenFrameRunner {
+    golden_dir: PathBuf,
+    tolerance: FrameTolerance,
+    update: bool,
+}
+
+impl GoldenFrameRunner {
+    /// Create a runner that keeps goldens in `golden_dir`
+    ///
+    /// Update mode starts from the `RNES_UPDATE_GOLDEN` environment variable.
+    pub fn new(golden_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            golden_dir: golden_dir.into(),
+            tolerance: FrameTolerance::exact(),
+            update: std::env::var(GOLDEN_UPDATE_ENV).is_ok_and(|value| value != "0"),
+        }
+    }
+
+    /// Set how much a frame may differ from its golden
+    pub fn with_tolerance(mut self, tolerance: FrameTolerance) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Write goldens instead of comparing against them
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Path of the golden PNG for `name`
+    pub fn golden_path(&self, name: &str) -> PathBuf {
+        self.golden_dir.join(format!("{}.png", name))
+    }
+
+    /// Run the ROM at `rom_path` for `frames` frames and check the last one
+    pub fn run_rom(&self, name: &str, rom_path: &Path, frames: u32) -> RnesResult<GoldenResult> {
+        let mut emulator = Emulator::with_config(Config::default());
+        emulator.load_rom(Cartridge::from_file(rom_path)?)?;
+        self.run_emulator(name, &mut emulator, frames)
+    }
+
+    /// Run a loaded emulator for `frames` frames and check the last one
+    pub fn run_emulator(&self, name: &str, emulator: &mut Emulator, frames: u32) -> RnesResult<GoldenResult> {
+        emulator.start();
+        let completed = emulator.run_frames(frames)?;
+        emulator.stop();
+        if completed < frames {
+            return Err(RnesError::Ppu(format!(
+                "{} stopped after {} of {} frames", name, completed, frames
+            )));
+        }
+
+        let pixels = emulator.get_ppu_frame_buffer()
+            .ok_or_else(|| RnesError::Ppu("No PPU frame buffer".to_string()))?;
+        self.check_frame(name, pixels)
+    }
+
+    /// Compare a frame buffer with the golden for `name`
+    pub fn check_frame(&self, name: &str, pixels: &[Pixel]) -> RnesResult<GoldenResult> {
+        let actual = frame_to_rgba(pixels);
+        let hash = crc32(&actual);
+        let golden_path = self.golden_path(name);
+
+        if self.update {
+            fs::create_dir_all(&self.golden_dir)?;
+            fs::write(&golden_path, encode_png(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &actual)?)?;
+            tracing::info!("Updated golden frame {}", golden_path.display());
+            return Ok(GoldenResult::Updated { hash });
+        }
+
+        if !golden_path.exists() {
+            return Ok(GoldenResult::Missing { hash });
+        }
+
+        let golden = decode_png(&fs::read(&golden_path)?)?;
+        if (golden.width as usize, golden.height as usize) != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+            return Err(RnesError::Serialization(format!(
+                "Golden {} is {}x{}, expected {}x{}",
+                golden_path.display(), golden.width, golden.height, SCREEN_WIDTH, SCREEN_HEIGHT
+            )));
+        }
+
+        let diff = diff_frames(&golden.pixels, &actual, self.tolerance.max_channel_delta);
+        if diff.differing_pixels <= self.tolerance.max_differing_pixels {
+            return Ok(GoldenResult::Matched { hash, diff });
+        }
+
+        // Keep the evidence next to the golden for inspection
+        let actual_path = self.golden_dir.join(format!("{}.actual.png", name));
+        let diff_path = self.golden_dir.join(format!("{}.diff.png", name));
+        fs::write(&actual_path, encode_png(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &actual)?)?;
+        let mask = diff_mask(&golden.pixels, &actual, self.tolerance.max_channel_delta);
+        fs::write(&diff_path, encode_png(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &mask)?)?;
+
+        Ok(GoldenResult::Mismatch { hash, diff, actual_path, diff_path })
+    }
+}
+
+/// Flatten pixels to RGBA8 bytes
+fn frame_to_rgba(pixels: &[Pixel]) -> Vec<u8> {
+    pixels.iter().flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a]).collect()
+}
+
+/// Red where pixels differ, dimmed golden elsewhere
+fn diff_mask(expected: &[u8], actual: &[u8], max_channel_delta: u8) -> Vec<u8> {
+    expected.chunks_exact(4).zip(actual.chunks_exact(4))
+        .flat_map(|(want, got)| {
+            let differs = want.iter().zip(got).any(|(a, b)| a.abs_diff(*b) > max_channel_delta);
+            if differs {
+                [255, 0, 0, 255]
+            } else {
+                [want[0] / 4, want[1] / 4, want[2] / 4, 255]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestRomBuilder;
+
+    fn temp_golden_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rnes_golden_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn solid_frame(pixel: Pixel) -> Vec<Pixel> {
+        vec![pixel; SCREEN_WIDTH * SCREEN_HEIGHT]
+    }
+
+    #[test]
+    fn test_golden_update_then_match() {
+        let dir = temp_golden_dir("match");
+        let frame = solid_frame(Pixel::new(10, 20, 30));
+
+        let result = GoldenFrameRunner::new(&dir).check_frame("solid", &frame).unwrap();
+        assert!(matches!(result, GoldenResult::Missing { .. }));
+
+        let runner = GoldenFrameRunner::new(&dir).with_update(true);
+        assert!(runner.check_frame("solid", &frame).unwrap().is_pass());
+
+        let result = GoldenFrameRunner::new(&dir).with_update(false).check_frame("solid", &frame).unwrap();
+        assert_eq!(result, GoldenResult::Matched {
+            hash: frame_hash(&frame),
+            diff: FrameDiff { differing_pixels: 0, max_channel_delta: 0 },
+        });
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_golden_tolerance_and_mismatch() {
+        let dir = temp_golden_dir("tolerance");
+        let frame = solid_frame(Pixel::new(10, 20, 30));
+        GoldenFrameRunner::new(&dir).with_update(true).check_frame("solid", &frame).unwrap();
+
+        let mut changed = frame.clone();
+        changed[0] = Pixel::new(12, 20, 30);
+        changed[1] = Pixel::new(200, 20, 30);
+
+        let lenient = GoldenFrameRunner::new(&dir)
+            .with_update(false)
+            .with_tolerance(FrameTolerance { max_channel_delta: 2, max_differing_pixels: 1 });
+        assert!(lenient.check_frame("solid", &changed).unwrap().is_pass());
+
+        let strict = GoldenFrameRunner::new(&dir).with_update(false);
+        match strict.check_frame("solid", &changed).unwrap() {
+            GoldenResult::Mismatch { diff, actual_path, diff_path, .. } => {
+                assert_eq!(diff, FrameDiff { differing_pixels: 2, max_channel_delta: 190 });
+                assert!(actual_path.exists() && diff_path.exists());
+                let mask = decode_png(&fs::read(diff_path).unwrap()).unwrap();
+                assert_eq!(&mask.pixels[..4], &[255, 0, 0, 255]);
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_golden_run_emulator() {
+        let dir = temp_golden_dir("emulator");
+        let load = || {
+            let mut emulator = Emulator::with_config(Config::default());
+            let cartridge = TestRomBuilder::new()
+                .with_program("reset: jmp reset")
+                .build_cartridge();
+            emulator.load_rom(cartridge).unwrap();
+            emulator
+        };
+
+        let runner = GoldenFrameRunner::new(&dir).with_update(true);
+        runner.run_emulator("blank", &mut load(), 3).unwrap();
+        let result = runner.with_update(false).run_emulator("blank", &mut load(), 3).unwrap();
+        assert!(matches!(result, GoldenResult::Matched { .. }), "{:?}", result);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
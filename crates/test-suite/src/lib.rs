@@ -2,18 +2,37 @@
 //! 
 //! This crate contains all integration tests and testing utilities
 
+pub mod audio_golden;
+pub mod blargg;
+pub mod golden;
+pub use rnes_common::png;
+pub mod report;
 pub mod cpu_test_runner;
 pub mod ppu_test_runner;
 pub mod apu_test_runner;
 pub mod m4_test_runner;
 pub mod m5_test_runner;
 pub mod m6_integration_tests;
+pub mod movie;
+pub mod nestest;
+pub mod parallel;
+pub mod single_step_runner;
+pub mod rom_builder;
 
 pub use cpu_test_runner::CpuTestRunner;
-pub use ppu_test_runner::{PpuTestRunner, PpuTestResult};
+pub use ppu_test_runner::{PpuTestRunner, PpuTestResult, Rect};
 pub use apu_test_runner::{ApuTestRunner, ApuTestResult};
 pub use m4_test_runner::{M4TestRunner, M4TestResult};
 pub use m5_test_runner::{M5TestRunner, M5TestResult};
 pub use m6_integration_tests::M6TestRunner;
+pub use rom_builder::TestRomBuilder;
+pub use single_step_runner::{SingleStepTestRunner, OpcodeReport};
+pub use nestest::{NestestRunner, NestestReport};
+pub use golden::{GoldenFrameRunner, GoldenResult, FrameTolerance};
+pub use audio_golden::{ApuScript, AudioGoldenRunner, AudioGoldenResult, AudioTolerance};
+pub use report::{TestSuiteReport, TestCaseReport, TestStatus};
+pub use parallel::{run_to_report, ParallelRomRunner, RomOutcome, RomRun};
+pub use movie::{Movie, MovieRunner, MovieReport, MovieCase};
+pub use rnes_compat::{CompatEntry, CompatReport, CompatRunner, CompatStatus};
 
 // Test modules are now separate test targets
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use rnes_test_suite::ppu_test_runner::{PpuTestRunner, PpuTestResult};
+use rnes_test_suite::report::TestSuiteReport;
 
 /// Test sprite hit functionality using Blargg's sprite hit test suite
 #[test]
@@ -28,6 +29,7 @@ fn test_sprite_hit_functionality() {
         "11.edge_timing.nes",
     ];
     
+    let mut report = TestSuiteReport::new("sprite_hit_functionality");
     for test_file in test_files {
         let rom_path = sprite_hit_dir.join(test_file);
         
@@ -40,7 +42,9 @@ fn test_sprite_hit_functionality() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 PpuTestResult::Completed { cycles, frames, .. } => {
@@ -60,13 +64,17 @@ fn test_sprite_hit_functionality() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test sprite overflow functionality using Blargg's sprite overflow test suite
@@ -90,24 +98,32 @@ fn test_sprite_overflow_functionality() {
         "5.Emulator.nes",
     ];
     
+    let mut report = TestSuiteReport::new("sprite_overflow_functionality");
     for test_file in test_files {
         let rom_path = sprite_overflow_dir.join(test_file);
         
         if rom_path.exists() {
             println!("🧪 Running sprite overflow test: {}", test_file);
             
+            // These ROMs predate the $6000 protocol and report at $F8
             let mut runner = PpuTestRunner::new()
                 .with_max_cycles(2000000)
-                .with_max_frames(1000);
+                .with_max_frames(1000)
+                .with_legacy_result(true);
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
-                                PpuTestResult::Completed { cycles, frames, .. } => {
+                                PpuTestResult::Completed { status: 0, cycles, frames, .. } => {
                                     println!("  ✅ PASS ({} cycles, {} frames)", cycles, frames);
                                 }
+                                PpuTestResult::Completed { status, .. } => {
+                                    println!("  ❌ FAIL (test #{})", status);
+                                }
                                 PpuTestResult::Timeout { cycles, frames } => {
                                     println!("  ⏰ TIMEOUT ({} cycles, {} frames)", cycles, frames);
                                 }
@@ -122,13 +138,18 @@ fn test_sprite_overflow_functionality() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
+    assert!(report.is_success(), "{}", report.summary());
 }
 
 /// Test OAM functionality using Blargg's OAM stress test
@@ -1,9 +1,11 @@
 use std::path::PathBuf;
 use rnes_test_suite::cpu_test_runner::{CpuTestRunner, TestResult};
+use rnes_test_suite::report::TestSuiteReport;
 use rnes_test_suite::m4_test_runner::{M4TestRunner, M4TestResult};
+use rnes_test_suite::rom_builder::{TestRomBuilder, checkerboard_chr};
 use rnes_core::Emulator;
 use rnes_cartridge::Cartridge;
-use rnes_common::SaveSystem;
+use rnes_common::{MemoryStorage, SaveSystem};
 
 /// Test M4: Common Mappers functionality
 #[test]
@@ -24,7 +26,8 @@ fn test_save_system_basic() {
     println!("🧪 Testing Save System Basic Functionality");
     
     // Test save system creation
-    let save_system = SaveSystem::new();
+    let save_dir = std::env::temp_dir().join(format!("rnes_save_system_{}", std::process::id()));
+    let save_system = SaveSystem::with_save_dir(&save_dir);
     assert!(save_system.ensure_save_dir().is_ok());
     println!("✅ Save system creation test passed");
     
@@ -53,6 +56,7 @@ fn test_save_system_basic() {
     
     // Clean up
     let _ = save_system.delete_battery_backup(rom_name);
+    let _ = std::fs::remove_dir_all(&save_dir);
     println!("✅ Battery backup cleanup passed");
 }
 
@@ -64,7 +68,7 @@ fn test_save_state_functionality() {
     // Create test ROM with battery backup
     let rom_data = create_test_rom_with_battery(1);
     let cartridge = Cartridge::from_bytes(&rom_data).unwrap();
-    let mut emulator = Emulator::new();
+    let mut emulator = Emulator::new().with_save_system(SaveSystem::with_storage(MemoryStorage::new()));
     
     // Load ROM
     assert!(emulator.load_rom(cartridge).is_ok());
@@ -130,7 +134,9 @@ fn test_mmc1_mapper_with_save_system() {
     // Create MMC1 ROM with battery backup
     let rom_data = create_test_rom_with_battery(1);
     let cartridge = Cartridge::from_bytes(&rom_data).unwrap();
-    let mut emulator = Emulator::new();
+    // Both emulators share a temporary save directory
+    let save_dir = std::env::temp_dir().join(format!("rnes_mmc1_battery_{}", std::process::id()));
+    let mut emulator = Emulator::new().with_save_system(SaveSystem::with_save_dir(&save_dir));
     
     // Load ROM
     assert!(emulator.load_rom(cartridge).is_ok());
@@ -158,7 +164,7 @@ fn test_mmc1_mapper_with_save_system() {
     
     // Create new emulator instance to test loading
     let cartridge2 = Cartridge::from_bytes(&rom_data).unwrap();
-    let mut emulator2 = Emulator::new();
+    let mut emulator2 = Emulator::new().with_save_system(SaveSystem::with_save_dir(&save_dir));
     assert!(emulator2.load_rom(cartridge2).is_ok());
     
     // Check if battery backup was loaded
@@ -170,6 +176,7 @@ fn test_mmc1_mapper_with_save_system() {
             println!("⚠️  MMC1 battery backup data mismatch");
         }
     }
+    let _ = std::fs::remove_dir_all(&save_dir);
     
     println!("✅ MMC1 mapper with save system test completed");
 }
@@ -199,6 +206,7 @@ fn test_mmc1_mapper() {
         "mmc1_basic.nes",
     ];
     
+    let mut report = TestSuiteReport::new("mmc1_mapper");
     for test_file in test_files {
         let rom_path = mmc1_test_dir.join(test_file);
         
@@ -210,7 +218,9 @@ fn test_mmc1_mapper() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 TestResult::Completed { status, cycles, .. } => {
@@ -234,13 +244,17 @@ fn test_mmc1_mapper() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test UxROM Mapper (Mapper 2)
@@ -260,6 +274,7 @@ fn test_uxrom_mapper() {
         "uxrom_basic.nes",
     ];
     
+    let mut report = TestSuiteReport::new("uxrom_mapper");
     for test_file in test_files {
         let rom_path = uxrom_test_dir.join(test_file);
         
@@ -271,7 +286,9 @@ fn test_uxrom_mapper() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 TestResult::Completed { status, cycles, .. } => {
@@ -295,13 +312,17 @@ fn test_uxrom_mapper() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test CNROM Mapper (Mapper 3)
@@ -321,6 +342,7 @@ fn test_cnrom_mapper() {
         "cnrom_basic.nes",
     ];
     
+    let mut report = TestSuiteReport::new("cnrom_mapper");
     for test_file in test_files {
         let rom_path = cnrom_test_dir.join(test_file);
         
@@ -332,7 +354,9 @@ fn test_cnrom_mapper() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 TestResult::Completed { status, cycles, .. } => {
@@ -356,13 +380,17 @@ fn test_cnrom_mapper() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test AOROM Mapper (Mapper 7)
@@ -382,6 +410,7 @@ fn test_aorom_mapper() {
         "aorom_basic.nes",
     ];
     
+    let mut report = TestSuiteReport::new("aorom_mapper");
     for test_file in test_files {
         let rom_path = aorom_test_dir.join(test_file);
         
@@ -393,7 +422,9 @@ fn test_aorom_mapper() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 TestResult::Completed { status, cycles, .. } => {
@@ -417,13 +448,17 @@ fn test_aorom_mapper() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test mapper creation and basic functionality
@@ -475,6 +510,298 @@ fn test_mapper_creation() {
     println!("✅ Unsupported mapper error test passed");
 }
 
+/// Test the mapper registry agrees with what can actually be created
+#[test]
+fn test_mapper_registry() {
+    use rnes_mappers::{create_mapper, describe, known_mappers, MapperSupport};
+
+    for description in known_mappers() {
+        let cartridge = Cartridge::from_bytes(&create_test_rom(description.number)).unwrap();
+        let created = create_mapper(cartridge).is_ok();
+        assert_eq!(created, description.support != MapperSupport::Unsupported, "{}", description);
+    }
+
+    assert_eq!(describe(4).to_string(), "Mapper 4 (MMC3) — supported");
+    assert_eq!(describe(85).to_string(), "Mapper 85 (VRC7) — supported");
+    assert_eq!(describe(69).to_string(), "Mapper 69 (Sunsoft FME-7) — unsupported");
+    assert_eq!(describe(99).to_string(), "Mapper 99 — unsupported");
+    assert!(known_mappers().any(|description| description.number == 69));
+    println!("✅ Mapper registry test passed");
+}
+
+/// Survey boot status of every downloaded test ROM
+#[test]
+#[ignore] // Requires test ROMs to be downloaded
+fn test_compat_survey() {
+    use rnes_test_suite::{CompatRunner, CompatStatus};
+    use rnes_test_suite::report::TEST_REPORT_DIR_ENV;
+
+    let test_roms_dir = PathBuf::from("../../tests/roms/nes-test-roms");
+    if !test_roms_dir.exists() {
+        println!("⚠️  Test ROMs not found, please run: ./scripts/download_all_test_roms.sh");
+        return;
+    }
+
+    let report = CompatRunner::new().with_frames(300).run_dir(&test_roms_dir).unwrap();
+    println!("📊 {}", report.summary());
+    if let Some(dir) = std::env::var_os(TEST_REPORT_DIR_ENV).map(PathBuf::from) {
+        report.write(&dir.join("compat.md")).unwrap();
+        report.write(&dir.join("compat.csv")).unwrap();
+    }
+    assert_eq!(report.count(CompatStatus::InvalidRom), 0);
+}
+
+/// Test compat survey classification of picture, blank, jammed and bad ROMs
+#[test]
+fn test_compat_classification() {
+    use std::fs;
+    use rnes_test_suite::{CompatRunner, CompatStatus};
+
+    let dir = std::env::temp_dir().join(format!("rnes_compat_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+
+    // Checkerboard tile 0 across the whole background
+    let picture = TestRomBuilder::new()
+        .with_chr(&checkerboard_chr())
+        .with_program("
+            reset:  lda #$3F
+                    sta $2006
+                    lda #$00
+                    sta $2006
+                    lda #$0F
+                    sta $2007
+                    lda #$16
+                    sta $2007
+                    lda #$2A
+                    sta $2007
+                    lda #$30
+                    sta $2007
+                    lda #$0A
+                    sta $2001
+            loop:   jmp loop
+        ")
+        .build();
+    let blank = TestRomBuilder::new().with_program("loop: jmp loop").build();
+    let fme7 = TestRomBuilder::new().with_mapper(69).with_program("loop: jmp loop").build();
+    fs::write(dir.join("a_picture.nes"), picture).unwrap();
+    fs::write(dir.join("b_blank.nes"), blank).unwrap();
+    fs::write(dir.join("nested").join("c_fme7.nes"), fme7).unwrap();
+    fs::write(dir.join("d_garbage.nes"), b"not a rom").unwrap();
+    let jam = TestRomBuilder::new().with_program("reset: .byte $02").build();
+    fs::write(dir.join("e_jam.nes"), jam).unwrap();
+    fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+    let report = CompatRunner::new()
+        .with_frames(30)
+        .with_stable_frames(10)
+        .with_threads(2)
+        .run_dir(&dir)
+        .unwrap();
+    let statuses: Vec<_> = report.entries.iter().map(|entry| (entry.name(), entry.status)).collect();
+    assert_eq!(statuses, vec![
+        ("a_picture.nes".to_string(), CompatStatus::Boots),
+        ("b_blank.nes".to_string(), CompatStatus::BlackScreen),
+        ("d_garbage.nes".to_string(), CompatStatus::InvalidRom),
+        ("e_jam.nes".to_string(), CompatStatus::Hung),
+        ("c_fme7.nes".to_string(), CompatStatus::UnsupportedMapper),
+    ]);
+    assert_eq!(report.entries[0].frames_run, 30);
+    assert_eq!(report.entries[3].message.as_deref(), Some("CPU jammed by opcode $02 at $8000 in frame 0"));
+    assert_eq!(report.entries[4].mapper, Some(69));
+    assert_eq!(report.summary(), "5 ROMs: 1 boots, 1 black screen, 1 hung, 1 unsupported mapper, 1 invalid ROM");
+
+    let csv = report.to_csv();
+    assert_eq!(csv.lines().count(), 6);
+    assert!(csv.contains("c_fme7.nes,unsupported mapper,69,0,,,"));
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("| c_fme7.nes | unsupported mapper | Mapper 69 (Sunsoft FME-7) — unsupported |"));
+
+    report.write(&dir.join("report.md")).unwrap();
+    assert!(fs::read_to_string(dir.join("report.md")).unwrap().starts_with("# Compatibility Report"));
+    let _ = fs::remove_dir_all(&dir);
+    println!("✅ Compat classification test passed");
+}
+
+/// Test the ROM info report for iNES and NES 2.0 headers
+#[test]
+fn test_cartridge_info() {
+    use rnes_cartridge::Mirroring;
+
+    let cartridge = TestRomBuilder::new()
+        .with_mapper(4)
+        .with_prg_banks(2)
+        .with_mirroring(Mirroring::Vertical)
+        .with_battery(true)
+        .with_chr(&checkerboard_chr())
+        .with_program("loop: jmp loop")
+        .build_cartridge();
+    let info = cartridge.info();
+    assert_eq!(info.mapper, 4);
+    assert_eq!(info.mapper_name, Some("MMC3"));
+    assert_eq!(info.prg_rom_size, 32 * 1024);
+    assert_eq!(info.chr_rom_size, 8 * 1024);
+    assert_eq!(info.chr_ram_size, 0);
+    assert_eq!(info.mirroring, Mirroring::Vertical);
+    assert!(info.battery);
+    assert!(!info.trainer);
+    assert!(info.nes2.is_none());
+    assert_eq!(info.crc32, cartridge.rom_id.crc32);
+    assert_eq!(info.sha1, cartridge.rom_id.sha1_hex());
+
+    let text = info.to_string();
+    assert!(text.contains("Mapper:     4 (MMC3)"));
+    assert!(text.contains("Format:     iNES"));
+    assert!(text.contains("PRG RAM:    8 KB (battery)"));
+    assert!(text.contains(&format!("{:08X}", info.crc32)));
+
+    // NES 2.0: submapper 1, 8KB PRG NVRAM, 8KB CHR RAM, PAL
+    let mut rom = TestRomBuilder::new()
+        .with_mapper(4)
+        .with_chr_banks(0)
+        .with_program("loop: jmp loop")
+        .build();
+    rom[7] |= 0x08;
+    rom[8] = 0x10;
+    rom[10] = 0x70;
+    rom[11] = 0x07;
+    rom[12] = 0x01;
+    let info = Cartridge::from_bytes(&rom).unwrap().info();
+    let nes2 = info.nes2.unwrap();
+    assert_eq!(nes2.submapper, 1);
+    assert_eq!(nes2.prg_ram_size, 0);
+    assert_eq!(nes2.prg_nvram_size, 8 * 1024);
+    assert_eq!(nes2.chr_ram_size, 8 * 1024);
+    assert_eq!(info.chr_rom_size, 0);
+    assert_eq!(info.chr_ram_size, 8 * 1024);
+    assert_eq!(info.region, rnes_common::Region::Pal);
+    assert!(info.to_string().contains("Mapper:     4 (MMC3), submapper 1"));
+
+    assert_eq!(rnes_cartridge::mapper_name(99), None);
+    println!("✅ Cartridge info test passed");
+}
+
+/// Test MMC1 consecutive-write rule and 512KB PRG banking (SUROM)
+#[test]
+fn test_mmc1_consecutive_writes_and_surom() {
+    use rnes_mappers::{create_mapper, Mapper};
+    
+    // Serial write of a 5-bit register value, with CPU cycles in between
+    fn mmc1_write(mapper: &mut dyn Mapper, addr: u16, value: u8) {
+        for bit in 0..5 {
+            mapper.write_prg(addr, (value >> bit) & 1).unwrap();
+            mapper.clock_cpu_cycle();
+            mapper.clock_cpu_cycle();
+        }
+    }
+    
+    // 512KB PRG, each 16KB bank filled with its bank number
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 32, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    for bank in 0..32u8 {
+        rom.extend(std::iter::repeat_n(bank, 16384));
+    }
+    let mut mapper = create_mapper(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+    
+    // Power-up: last bank of the first 256KB half is fixed at $C000
+    assert_eq!(mapper.read_prg(0xC000).unwrap(), 15);
+    
+    // CHR bank 0 bit 4 selects the upper 256KB half
+    mmc1_write(mapper.as_mut(), 0xA000, 0x10);
+    mmc1_write(mapper.as_mut(), 0xE000, 0x02);
+    assert_eq!(mapper.read_prg(0x8000).unwrap(), 18);
+    assert_eq!(mapper.read_prg(0xC000).unwrap(), 31);
+    
+    // The second write of a back-to-back pair is ignored
+    for bit in 0..5 {
+        mapper.write_prg(0xE000, (0x05 >> bit) & 1).unwrap();
+        mapper.write_prg(0xE000, 1).unwrap();
+        mapper.clock_cpu_cycle();
+        mapper.clock_cpu_cycle();
+    }
+    assert_eq!(mapper.read_prg(0x8000).unwrap(), 21);
+}
+
+/// Test PRG RAM enable and write protect on MMC1 and MMC3
+#[test]
+fn test_prg_ram_protect() {
+    use rnes_mappers::create_mapper;
+    
+    // MMC3: $A001 bit 7 enables, bit 6 write-protects
+    let mut mapper = create_mapper(Cartridge::from_bytes(&create_test_rom(4)).unwrap()).unwrap();
+    mapper.write_prg(0x6000, 0x42).unwrap();
+    assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x42);
+    
+    mapper.write_prg(0xA001, 0xC0).unwrap();
+    mapper.write_prg(0x6000, 0x55).unwrap();
+    assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x42);
+    
+    // Disabled RAM reads open bus and ignores writes
+    mapper.write_prg(0xA001, 0x00).unwrap();
+    assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x60);
+    mapper.write_prg(0x6000, 0x55).unwrap();
+    mapper.write_prg(0xA001, 0x80).unwrap();
+    assert_eq!(mapper.read_prg(0x6000).unwrap(), 0x42);
+    
+    // MMC1: PRG bank bit 4 disables RAM
+    let mut mapper = create_mapper(Cartridge::from_bytes(&create_test_rom(1)).unwrap()).unwrap();
+    mapper.write_prg(0x7000, 0x24).unwrap();
+    for bit in 0..5 {
+        mapper.write_prg(0xE000, (0x10 >> bit) & 1).unwrap();
+        mapper.clock_cpu_cycle();
+        mapper.clock_cpu_cycle();
+    }
+    assert_eq!(mapper.read_prg(0x7000).unwrap(), 0x70);
+    mapper.write_prg(0x7000, 0x99).unwrap();
+    for _ in 0..5 {
+        mapper.write_prg(0xE000, 0).unwrap();
+        mapper.clock_cpu_cycle();
+        mapper.clock_cpu_cycle();
+    }
+    assert_eq!(mapper.read_prg(0x7000).unwrap(), 0x24);
+}
+
+/// Test MMC3 A12 filtering and IRQ revisions
+#[test]
+fn test_mmc3_irq_revisions() {
+    use rnes_common::Mmc3IrqRevision;
+    use rnes_mappers::{create_mapper_with_options, Mapper, MapperOptions};
+    
+    // Hold A12 low for `low_dots` PPU dots, then raise it
+    fn a12_pulse(mapper: &mut dyn Mapper, low_dots: usize) {
+        mapper.ppu_address_changed(0x0000);
+        for _ in 0..low_dots {
+            mapper.step();
+        }
+        mapper.ppu_address_changed(0x1000);
+    }
+    
+    for revision in [Mmc3IrqRevision::Sharp, Mmc3IrqRevision::Nec] {
+        let cartridge = Cartridge::from_bytes(&create_test_rom(4)).unwrap();
+        let options = MapperOptions { mmc3_irq_revision: Some(revision) };
+        let mut mapper = create_mapper_with_options(cartridge, &options).unwrap();
+        mapper.ppu_address_changed(0x1000);
+        
+        // Latch 0, reload, enable
+        mapper.write_prg(0xC000, 0).unwrap();
+        mapper.write_prg(0xC001, 0).unwrap();
+        mapper.write_prg(0xE001, 0).unwrap();
+        
+        // Rapid A12 toggles are filtered out
+        a12_pulse(mapper.as_mut(), 2);
+        a12_pulse(mapper.as_mut(), 2);
+        assert!(!mapper.irq_pending());
+        
+        // The reload after $C001 fires on both revisions
+        a12_pulse(mapper.as_mut(), 20);
+        assert!(mapper.irq_pending());
+        mapper.clear_irq();
+        
+        // Reloading a latch of 0 again only fires on the Sharp revision
+        a12_pulse(mapper.as_mut(), 20);
+        assert_eq!(mapper.irq_pending(), revision == Mmc3IrqRevision::Sharp);
+    }
+}
+
 /// Test M4 integration with all components
 #[test]
 fn test_m4_integration() {
@@ -489,7 +816,7 @@ fn test_m4_integration() {
         // Create ROM with battery backup
         let rom_data = create_test_rom_with_battery(mapper_num);
         let cartridge = Cartridge::from_bytes(&rom_data).unwrap();
-        let mut emulator = Emulator::new();
+        let mut emulator = Emulator::new().with_save_system(SaveSystem::with_storage(MemoryStorage::new()));
         
         // Load ROM
         assert!(emulator.load_rom(cartridge).is_ok());
@@ -569,117 +896,23 @@ fn test_m4_with_test_runner() {
 
 /// Create a test ROM with specified mapper number
 fn create_test_rom(mapper_number: u8) -> Vec<u8> {
-    let mut rom = Vec::new();
-    
-    // iNES header
-    rom.extend_from_slice(&[
-        0x4E, 0x45, 0x53, 0x1A, // iNES magic
-        0x01, 0x01,             // 16KB PRG, 8KB CHR
-        (mapper_number & 0x0F) << 4, (mapper_number & 0xF0), // Mapper number, horizontal mirroring
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-    ]);
-    
-    // PRG ROM (16KB) - Simple program
-    let mut prg_rom = vec![0; 16384];
-    
-    // Simple program that just loops
-    prg_rom[0] = 0x4C; // JMP absolute
-    prg_rom[1] = 0x00; // Low byte
-    prg_rom[2] = 0x80; // High byte (infinite loop)
-    
-    // Reset vector points to our program
-    prg_rom[0x3FFC] = 0x00; // Reset vector low
-    prg_rom[0x3FFD] = 0x80; // Reset vector high
-    
-    rom.extend_from_slice(&prg_rom);
-    
-    // CHR ROM (8KB) - Simple pattern data
-    let mut chr_rom = vec![0; 8192];
-    
-    // Create a simple pattern (8x8 pixel tile)
-    for tile in 0..16 {
-        let base = tile * 16;
-        // Create a simple checkerboard pattern
-        chr_rom[base + 0] = 0xAA; // 10101010
-        chr_rom[base + 1] = 0x55; // 01010101
-        chr_rom[base + 2] = 0xAA;
-        chr_rom[base + 3] = 0x55;
-        chr_rom[base + 4] = 0xAA;
-        chr_rom[base + 5] = 0x55;
-        chr_rom[base + 6] = 0xAA;
-        chr_rom[base + 7] = 0x55;
-        chr_rom[base + 8] = 0xAA;
-        chr_rom[base + 9] = 0x55;
-        chr_rom[base + 10] = 0xAA;
-        chr_rom[base + 11] = 0x55;
-        chr_rom[base + 12] = 0xAA;
-        chr_rom[base + 13] = 0x55;
-        chr_rom[base + 14] = 0xAA;
-        chr_rom[base + 15] = 0x55;
-    }
-    
-    rom.extend_from_slice(&chr_rom);
-    
-    rom
+    TestRomBuilder::new()
+        .with_mapper(mapper_number)
+        .with_chr(&checkerboard_chr())
+        .with_program("loop: jmp loop")
+        .build()
 }
 
 /// Create a test ROM with battery backup for specified mapper
 fn create_test_rom_with_battery(mapper_number: u8) -> Vec<u8> {
-    let mut rom = Vec::new();
-    
-    // iNES header with battery backup flag
-    rom.extend_from_slice(&[
-        0x4E, 0x45, 0x53, 0x1A, // iNES magic
-        0x01, 0x01,             // 16KB PRG, 8KB CHR
-        (mapper_number & 0x0F) << 4 | 0x02, (mapper_number & 0xF0), // Mapper number, battery backup, horizontal mirroring
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-    ]);
-    
-    // PRG ROM (16KB) - Simple program
-    let mut prg_rom = vec![0; 16384];
-    
-    // Simple program that just loops
-    prg_rom[0] = 0xA9; // LDA immediate
-    prg_rom[1] = 0x42; // Value 0x42
-    prg_rom[2] = 0x8D; // STA absolute
-    prg_rom[3] = 0x00; // Low byte
-    prg_rom[4] = 0x60; // High byte (PRG RAM)
-    prg_rom[5] = 0x4C; // JMP absolute
-    prg_rom[6] = 0x00; // Low byte
-    prg_rom[7] = 0x80; // High byte (infinite loop)
-    
-    // Reset vector points to our program
-    prg_rom[0x3FFC] = 0x00; // Reset vector low
-    prg_rom[0x3FFD] = 0x80; // Reset vector high
-    
-    rom.extend_from_slice(&prg_rom);
-    
-    // CHR ROM (8KB) - Simple pattern data
-    let mut chr_rom = vec![0; 8192];
-    
-    // Create a simple pattern (8x8 pixel tile)
-    for tile in 0..16 {
-        let base = tile * 16;
-        // Create a simple checkerboard pattern
-        chr_rom[base + 0] = 0xAA; // 10101010
-        chr_rom[base + 1] = 0x55; // 01010101
-        chr_rom[base + 2] = 0xAA;
-        chr_rom[base + 3] = 0x55;
-        chr_rom[base + 4] = 0xAA;
-        chr_rom[base + 5] = 0x55;
-        chr_rom[base + 6] = 0xAA;
-        chr_rom[base + 7] = 0x55;
-        chr_rom[base + 8] = 0xAA;
-        chr_rom[base + 9] = 0x55;
-        chr_rom[base + 10] = 0xAA;
-        chr_rom[base + 11] = 0x55;
-        chr_rom[base + 12] = 0xAA;
-        chr_rom[base + 13] = 0x55;
-        chr_rom[base + 14] = 0xAA;
-        chr_rom[base + 15] = 0x55;
-    }
-    
-    rom.extend_from_slice(&chr_rom);
-    
-    rom
+    TestRomBuilder::new()
+        .with_mapper(mapper_number)
+        .with_battery(true)
+        .with_chr(&checkerboard_chr())
+        .with_program("
+            reset: lda #$42
+                   sta $6000
+                   jmp reset
+        ")
+        .build()
 }
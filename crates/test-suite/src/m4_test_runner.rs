@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 use rnes_core::Emulator;
 use rnes_cartridge::Cartridge;
-use rnes_common::{RnesResult, RnesError};
+use rnes_common::{MemoryStorage, RnesResult, RnesError, SaveSystem};
 
 /// Result of M4 test execution
 #[derive(Debug)]
@@ -80,7 +80,8 @@ impl M4TestRunner {
             .map_err(|e| RnesError::RomFormat(format!("Failed to read ROM file: {}", e)))?;
         
         let cartridge = Cartridge::from_bytes(&rom_data)?;
-        self.emulator = Some(Emulator::new());
+        // Keep test saves out of the working directory
+        self.emulator = Some(Emulator::new().with_save_system(SaveSystem::with_storage(MemoryStorage::new())));
         let emulator = self.emulator.as_mut().unwrap();
         emulator.load_rom(cartridge)?;
         
@@ -274,6 +275,7 @@ impl Default for M4TestRunner {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rom_builder::{TestRomBuilder, checkerboard_chr};
     
     #[test]
     fn test_basic_rom_loading() {
@@ -287,7 +289,7 @@ mod tests {
         let cartridge = Cartridge::from_bytes(&rom_data).unwrap();
         
         // Set up the runner's emulator
-        runner.emulator = Some(Emulator::new());
+        runner.emulator = Some(Emulator::new().with_save_system(SaveSystem::with_storage(MemoryStorage::new())));
         let emulator = runner.emulator.as_mut().unwrap();
         assert!(emulator.load_rom(cartridge).is_ok());
         emulator.rom_name = Some("test_m4_runner_internal".to_string());
@@ -300,57 +302,10 @@ mod tests {
     }
     
     fn create_test_rom(mapper_number: u8) -> Vec<u8> {
-        let mut rom = Vec::new();
-        
-        // iNES header
-        rom.extend_from_slice(&[
-            0x4E, 0x45, 0x53, 0x1A, // iNES magic
-            0x01, 0x01,             // 16KB PRG, 8KB CHR
-            (mapper_number & 0x0F) << 4, (mapper_number & 0xF0), // Mapper number, horizontal mirroring
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-        ]);
-        
-        // PRG ROM (16KB) - Simple program
-        let mut prg_rom = vec![0; 16384];
-        
-        // Simple program that just loops
-        prg_rom[0] = 0x4C; // JMP absolute
-        prg_rom[1] = 0x00; // Low byte
-        prg_rom[2] = 0x80; // High byte (infinite loop)
-        
-        // Reset vector points to our program
-        prg_rom[0x3FFC] = 0x00; // Reset vector low
-        prg_rom[0x3FFD] = 0x80; // Reset vector high
-        
-        rom.extend_from_slice(&prg_rom);
-        
-        // CHR ROM (8KB) - Simple pattern data
-        let mut chr_rom = vec![0; 8192];
-        
-        // Create a simple pattern (8x8 pixel tile)
-        for tile in 0..16 {
-            let base = tile * 16;
-            // Create a simple checkerboard pattern
-            chr_rom[base + 0] = 0xAA; // 10101010
-            chr_rom[base + 1] = 0x55; // 01010101
-            chr_rom[base + 2] = 0xAA;
-            chr_rom[base + 3] = 0x55;
-            chr_rom[base + 4] = 0xAA;
-            chr_rom[base + 5] = 0x55;
-            chr_rom[base + 6] = 0xAA;
-            chr_rom[base + 7] = 0x55;
-            chr_rom[base + 8] = 0xAA;
-            chr_rom[base + 9] = 0x55;
-            chr_rom[base + 10] = 0xAA;
-            chr_rom[base + 11] = 0x55;
-            chr_rom[base + 12] = 0xAA;
-            chr_rom[base + 13] = 0x55;
-            chr_rom[base + 14] = 0xAA;
-            chr_rom[base + 15] = 0x55;
-        }
-        
-        rom.extend_from_slice(&chr_rom);
-        
-        rom
+        TestRomBuilder::new()
+            .with_mapper(mapper_number)
+            .with_chr(&checkerboard_chr())
+            .with_program("loop: jmp loop")
+            .build()
     }
 }
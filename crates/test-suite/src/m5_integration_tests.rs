@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use rnes_test_suite::m5_test_runner::M5TestRunner;
+use rnes_test_suite::report::TestSuiteReport;
 use rnes_core::Emulator;
 use rnes_cartridge::Cartridge;
 
@@ -267,6 +268,7 @@ fn test_mmc3_with_real_roms() {
     
     // Test MMC3 basic functionality ROMs
     let mmc3_test_dir = test_roms_dir.join("mmc3_test");
+    let mut report = TestSuiteReport::new("mmc3_real_roms");
     if mmc3_test_dir.exists() {
         println!("📂 Testing MMC3 Basic Functionality ROMs");
         
@@ -288,7 +290,9 @@ fn test_mmc3_with_real_roms() {
                     .with_max_cycles(300000)
                     .with_test_roms_dir(test_roms_dir.clone());
                 
-                match runner.run_test_with_rom_file(&rom_path, test_file) {
+                let result = runner.run_test_with_rom_file(&rom_path, test_file);
+                report.record(test_file, &result);
+                match result {
                     Ok(result) => {
                         println!("  ✅ PASS ({} cycles, IRQ: {})", result.cycles_executed, result.irq_count);
                     }
@@ -297,6 +301,7 @@ fn test_mmc3_with_real_roms() {
                     }
                 }
             } else {
+                report.record_skipped(test_file, "not found");
                 println!("⚠️  Test file not found: {}", test_file);
             }
         }
@@ -328,7 +333,9 @@ fn test_mmc3_with_real_roms() {
                     .with_max_cycles(300000)
                     .with_test_roms_dir(test_roms_dir.clone());
                 
-                match runner.run_test_with_rom_file(&rom_path, test_file) {
+                let result = runner.run_test_with_rom_file(&rom_path, test_file);
+                report.record(test_file, &result);
+                match result {
                     Ok(result) => {
                         println!("  ✅ PASS ({} cycles, IRQ: {})", result.cycles_executed, result.irq_count);
                     }
@@ -337,6 +344,7 @@ fn test_mmc3_with_real_roms() {
                     }
                 }
             } else {
+                report.record_skipped(test_file, "not found");
                 println!("⚠️  Test file not found: {}", test_file);
             }
         }
@@ -344,5 +352,6 @@ fn test_mmc3_with_real_roms() {
         println!("⚠️  MMC3 IRQ test directory not found: {:?}", mmc3_irq_test_dir);
     }
     
+    report.finish();
     println!("✅ MMC3 real ROM test completed");
 }
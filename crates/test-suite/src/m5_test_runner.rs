@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 use rnes_core::Emulator;
-use rnes_cartridge::Cartridge;
+use rnes_cartridge::{Cartridge, Mirroring};
 use rnes_common::RnesResult;
+use crate::rom_builder::TestRomBuilder;
 
 /// M5 test result
 #[derive(Debug, Clone)]
@@ -288,58 +289,93 @@ impl M5TestRunner {
     
     /// Create a simple test ROM with MMC3 mapper
     fn create_test_rom_with_mmc3(&self) -> Vec<u8> {
-        // Create a minimal NES ROM with MMC3 mapper
-        let mut rom_data = Vec::new();
-        
-        // NES header
-        rom_data.extend_from_slice(b"NES\x1A"); // Magic number
-        rom_data.push(1); // PRG ROM size (16KB)
-        rom_data.push(1); // CHR ROM size (8KB)
-        rom_data.push(0x44); // Flags 6: Mapper 4 (MMC3), vertical mirroring, battery
-        rom_data.push(0x00); // Flags 7: Mapper 4 (MMC3), NES 2.0 not set
-        rom_data.push(0x00); // PRG RAM size
-        rom_data.push(0x00); // TV system
-        rom_data.push(0x00); // TV system, PRG RAM presence
-        rom_data.push(0x00); // Timing
-        rom_data.push(0x00); // System type
-        rom_data.push(0x00); // Mapper, submapper
-        rom_data.push(0x00); // PRG ROM upper bits
-        rom_data.push(0x00); // CHR ROM upper bits
-        rom_data.push(0x00); // PRG RAM shift
-        rom_data.push(0x00); // CHR RAM shift
-        rom_data.push(0x00); // Country
-        rom_data.push(0x00); // Developer
-        rom_data.push(0x00); // Version
-        rom_data.push(0x00); // Reserved
-        
-        // PRG ROM (16KB)
-        for i in 0..16384 {
-            rom_data.push((i & 0xFF) as u8);
-        }
-        
-        // CHR ROM (8KB)
-        for i in 0..8192 {
-            rom_data.push((i & 0xFF) as u8);
-        }
-        
-        rom_data
+        self.mmc3_rom_builder()
+            .with_program("
+                reset: sei
+                       cld
+                       ldx #$FF
+                       txs
+                loop:  jmp loop
+            ")
+            .build()
     }
     
     /// Create a test ROM that exercises bank switching
     fn create_test_rom_with_bank_switching(&self) -> Vec<u8> {
-        // Similar to basic ROM but with specific bank switching test code
-        self.create_test_rom_with_mmc3()
+        // Cycle R6 through every 8KB PRG bank and R0 through the CHR banks
+        self.mmc3_rom_builder()
+            .with_program("
+                reset: sei
+                       ldx #$00
+                loop:  lda #$06
+                       sta $8000
+                       stx $8001
+                       lda #$00
+                       sta $8000
+                       txa
+                       asl a
+                       sta $8001
+                       inx
+                       cpx #$08
+                       bne loop
+                       ldx #$00
+                       jmp loop
+            ")
+            .build()
     }
     
     /// Create a test ROM that exercises scanline IRQ
     fn create_test_rom_with_scanline_irq(&self) -> Vec<u8> {
-        // Similar to basic ROM but with specific IRQ test code
-        self.create_test_rom_with_mmc3()
+        // Enable rendering so A12 clocks the counter, then count IRQs in $00
+        self.mmc3_rom_builder()
+            .with_program("
+                reset: sei
+                       lda #$00
+                       sta $00
+                       lda #$08
+                       sta $2000
+                       lda #$18
+                       sta $2001
+                       lda #$20
+                       sta $C000
+                       sta $C001
+                       sta $E001
+                       cli
+                loop:  jmp loop
+
+                irq:   pha
+                       sta $E000
+                       sta $E001
+                       inc $00
+                       pla
+                       rti
+            ")
+            .build()
     }
     
     /// Create a test ROM that exercises mirroring control
     fn create_test_rom_with_mirroring(&self) -> Vec<u8> {
-        // Similar to basic ROM but with specific mirroring test code
-        self.create_test_rom_with_mmc3()
+        // Toggle $A000 between vertical and horizontal mirroring
+        self.mmc3_rom_builder()
+            .with_program("
+                reset: sei
+                loop:  lda #$00
+                       sta $A000
+                       lda #$01
+                       sta $A000
+                       jmp loop
+            ")
+            .build()
+    }
+    
+    /// MMC3 cartridge with 64KB PRG, 8KB CHR and battery-backed PRG RAM
+    fn mmc3_rom_builder(&self) -> TestRomBuilder {
+        let chr: Vec<u8> = (0..8192).map(|i| (i & 0xFF) as u8).collect();
+        TestRomBuilder::new()
+            .with_mapper(4)
+            .with_prg_banks(4)
+            .with_mirroring(Mirroring::Vertical)
+            .with_battery(true)
+            .with_chr(&chr)
     }
 }
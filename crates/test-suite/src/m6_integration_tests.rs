@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 use rnes_core::Emulator;
 use rnes_cartridge::Cartridge;
-use rnes_common::{Config, RnesResult};
+use rnes_common::{Config, MemoryStorage, RnesResult, SaveSystem};
+use crate::rom_builder::TestRomBuilder;
 
 /// Test result enum
 #[derive(Debug)]
@@ -12,6 +13,11 @@ pub enum TestResult {
     Error,
 }
 
+/// Save system that keeps test saves out of the working directory
+fn memory_saves() -> SaveSystem {
+    SaveSystem::with_storage(MemoryStorage::new())
+}
+
 /// Test runner for M6 features
 pub struct M6TestRunner {
     emulator: Emulator,
@@ -20,12 +26,12 @@ pub struct M6TestRunner {
 impl M6TestRunner {
     pub fn new() -> Self {
         Self {
-            emulator: Emulator::new(),
+            emulator: Emulator::new().with_save_system(memory_saves()),
         }
     }
     
     pub fn with_config(mut self, config: Config) -> Self {
-        self.emulator = Emulator::with_config(config);
+        self.emulator = Emulator::with_config(config).with_save_system(memory_saves());
         self
     }
     
@@ -246,8 +252,8 @@ impl M6TestRunner {
         let rom_name = self.emulator.rom_name.as_ref().map_or("Unknown", |v| v);
         println!("  Loaded ROM: {}", rom_name);
         
-        // Reset the emulator to ensure proper initialization
-        let _ = self.emulator.reset();
+        // Start from power-on state
+        let _ = self.emulator.power_cycle();
         
         // Check reset vector
         let reset_vector_low = self.emulator.bus.read_byte(0xFFFC)?;
@@ -458,191 +464,43 @@ impl M6TestRunner {
     }
     
     fn create_debug_test_rom(&self) -> Vec<u8> {
-        let mut rom = Vec::new();
-        
-        // iNES header
-        rom.extend_from_slice(&[
-            0x4E, 0x45, 0x53, 0x1A, // iNES magic
-            0x01, 0x01,             // 16KB PRG, 8KB CHR
-            0x00, 0x00,             // Mapper 0, horizontal mirroring
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-        ]);
-        
-        // PRG ROM (16KB) - Simple program for debugging
-        let mut prg_rom = vec![0; 16384];
-        
-        // Program: LDA #$42, STA $00, LDA #$84, STA $01, JMP $8000
-        prg_rom[0] = 0xA9; // LDA immediate
-        prg_rom[1] = 0x42; // Value 0x42
-        prg_rom[2] = 0x85; // STA zero page
-        prg_rom[3] = 0x00; // Address 0x00
-        prg_rom[4] = 0xA9; // LDA immediate
-        prg_rom[5] = 0x84; // Value 0x84
-        prg_rom[6] = 0x85; // STA zero page
-        prg_rom[7] = 0x01; // Address 0x01
-        prg_rom[8] = 0x4C; // JMP absolute
-        prg_rom[9] = 0x00; // Low byte
-        prg_rom[10] = 0x80; // High byte (infinite loop)
-        
-        // Add more instructions to make the program longer
-        prg_rom[11] = 0xA9; // LDA immediate
-        prg_rom[12] = 0x55; // Value 0x55
-        prg_rom[13] = 0x85; // STA zero page
-        prg_rom[14] = 0x02; // Address 0x02
-        prg_rom[15] = 0xA9; // LDA immediate
-        prg_rom[16] = 0xAA; // Value 0xAA
-        prg_rom[17] = 0x85; // STA zero page
-        prg_rom[18] = 0x03; // Address 0x03
-        prg_rom[19] = 0x4C; // JMP absolute
-        prg_rom[20] = 0x00; // Low byte
-        prg_rom[21] = 0x80; // High byte (infinite loop)
-        
-        // Reset vector
-        prg_rom[0x3FFC] = 0x00; // Reset vector low
-        prg_rom[0x3FFD] = 0x80; // Reset vector high
-        
-        rom.extend_from_slice(&prg_rom);
-        
-        // CHR ROM (8KB) - Empty
-        rom.extend(vec![0; 8192]);
-        
-        rom
+        TestRomBuilder::new()
+            .with_program("
+                reset: lda #$42
+                       sta $00
+                       lda #$84
+                       sta $01
+                       jmp reset
+            ")
+            .build()
     }
     
     fn create_save_state_test_rom(&self) -> Vec<u8> {
-        let mut rom = Vec::new();
-        
-        // iNES header
-        rom.extend_from_slice(&[
-            0x4E, 0x45, 0x53, 0x1A, // iNES magic
-            0x01, 0x01,             // 16KB PRG, 8KB CHR
-            0x00, 0x00,             // Mapper 0, horizontal mirroring
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-        ]);
-        
-        // PRG ROM (16KB) - Program that modifies registers
-        let mut prg_rom = vec![0; 16384];
-        
-        // Program: A simple program without loops
-        prg_rom[0] = 0xA9; // LDA immediate
-        prg_rom[1] = 0x42; // Value 0x42
-        prg_rom[2] = 0xAA; // TAX
-        prg_rom[3] = 0xA9; // LDA immediate
-        prg_rom[4] = 0x84; // Value 0x84
-        prg_rom[5] = 0xA8; // TAY
-        prg_rom[6] = 0xA9; // LDA immediate
-        prg_rom[7] = 0x55; // Value 0x55
-        prg_rom[8] = 0x85; // STA zero page
-        prg_rom[9] = 0x00; // Address 0x00
-        prg_rom[10] = 0xA9; // LDA immediate
-        prg_rom[11] = 0xAA; // Value 0xAA
-        prg_rom[12] = 0x85; // STA zero page
-        prg_rom[13] = 0x01; // Address 0x01
-        prg_rom[14] = 0xA9; // LDA immediate
-        prg_rom[15] = 0x33; // Value 0x33
-        prg_rom[16] = 0x85; // STA zero page
-        prg_rom[17] = 0x02; // Address 0x02
-        prg_rom[18] = 0xA9; // LDA immediate
-        prg_rom[19] = 0x44; // Value 0x44
-        prg_rom[20] = 0x85; // STA zero page
-        prg_rom[21] = 0x03; // Address 0x03
-        prg_rom[22] = 0xA9; // LDA immediate
-        prg_rom[23] = 0x66; // Value 0x66
-        prg_rom[24] = 0x85; // STA zero page
-        prg_rom[25] = 0x04; // Address 0x04
-        prg_rom[26] = 0xA9; // LDA immediate
-        prg_rom[27] = 0x77; // Value 0x77
-        prg_rom[28] = 0x85; // STA zero page
-        prg_rom[29] = 0x05; // Address 0x05
-        prg_rom[30] = 0xEA; // NOP (instead of JMP)
-        prg_rom[31] = 0xEA; // NOP
-        prg_rom[32] = 0xEA; // NOP
-        
-        // Add more instructions to make the program longer
-        prg_rom[9] = 0xA9; // LDA immediate
-        prg_rom[10] = 0x55; // Value 0x55
-        prg_rom[11] = 0x85; // STA zero page
-        prg_rom[12] = 0x00; // Address 0x00
-        prg_rom[13] = 0xA9; // LDA immediate
-        prg_rom[14] = 0xAA; // Value 0xAA
-        prg_rom[15] = 0x85; // STA zero page
-        prg_rom[16] = 0x01; // Address 0x01
-        prg_rom[17] = 0x4C; // JMP absolute
-        prg_rom[18] = 0x00; // Low byte
-        prg_rom[19] = 0x80; // High byte (infinite loop)
-        
-        // Add even more instructions
-        prg_rom[20] = 0xA9; // LDA immediate
-        prg_rom[21] = 0x33; // Value 0x33
-        prg_rom[22] = 0x85; // STA zero page
-        prg_rom[23] = 0x02; // Address 0x02
-        prg_rom[24] = 0xA9; // LDA immediate
-        prg_rom[25] = 0x44; // Value 0x44
-        prg_rom[26] = 0x85; // STA zero page
-        prg_rom[27] = 0x03; // Address 0x03
-        prg_rom[28] = 0x4C; // JMP absolute
-        prg_rom[29] = 0x00; // Low byte
-        prg_rom[30] = 0x80; // High byte (infinite loop)
-        
-        // Add more instructions to make the program longer
-        prg_rom[9] = 0xA9; // LDA immediate
-        prg_rom[10] = 0x55; // Value 0x55
-        prg_rom[11] = 0x85; // STA zero page
-        prg_rom[12] = 0x00; // Address 0x00
-        prg_rom[13] = 0xA9; // LDA immediate
-        prg_rom[14] = 0xAA; // Value 0xAA
-        prg_rom[15] = 0x85; // STA zero page
-        prg_rom[16] = 0x01; // Address 0x01
-        prg_rom[17] = 0x4C; // JMP absolute
-        prg_rom[18] = 0x00; // Low byte
-        prg_rom[19] = 0x80; // High byte (infinite loop)
-        
-        // Reset vector
-        prg_rom[0x3FFC] = 0x00; // Reset vector low
-        prg_rom[0x3FFD] = 0x80; // Reset vector high
-        
-        rom.extend_from_slice(&prg_rom);
-        
-        // CHR ROM (8KB) - Empty
-        rom.extend(vec![0; 8192]);
-        
-        rom
+        // Program that modifies registers and zero page
+        TestRomBuilder::new()
+            .with_program("
+                reset: lda #$42
+                       tax
+                       lda #$84
+                       tay
+                       lda #$55
+                       sta $00
+                       lda #$AA
+                       sta $01
+                       jmp reset
+            ")
+            .build()
     }
     
     fn create_auto_save_test_rom(&self) -> Vec<u8> {
-        let mut rom = Vec::new();
-        
-        // iNES header with battery backup
-        rom.extend_from_slice(&[
-            0x4E, 0x45, 0x53, 0x1A, // iNES magic
-            0x01, 0x01,             // 16KB PRG, 8KB CHR
-            0x02, 0x00,             // Mapper 0, vertical mirroring, battery backup
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding
-        ]);
-        
-        // PRG ROM (16KB) - Simple program
-        let mut prg_rom = vec![0; 16384];
-        
-        // Program: LDA #$42, STA $6000, JMP $8000
-        prg_rom[0] = 0xA9; // LDA immediate
-        prg_rom[1] = 0x42; // Value 0x42
-        prg_rom[2] = 0x8D; // STA absolute
-        prg_rom[3] = 0x00; // Low byte
-        prg_rom[4] = 0x60; // High byte (PRG RAM)
-        prg_rom[5] = 0x4C; // JMP absolute
-        prg_rom[6] = 0x00; // Low byte
-        prg_rom[7] = 0x80; // High byte (infinite loop)
-        
-        // Reset vector
-        prg_rom[0x3FFC] = 0x00; // Reset vector low
-        prg_rom[0x3FFD] = 0x80; // Reset vector high
-        
-        rom.extend_from_slice(&prg_rom);
-        
-        // CHR ROM (8KB) - Empty
-        rom.extend(vec![0; 8192]);
-        
-        rom
+        TestRomBuilder::new()
+            .with_battery(true)
+            .with_program("
+                reset: lda #$42
+                       sta $6000
+                       jmp reset
+            ")
+            .build()
     }
 }
 
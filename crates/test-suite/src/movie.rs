@@ -0,0 +1,437 @@
+//! Movie (TAS) playback verification
+//!
+//! A movie is a list of per-frame controller inputs. Playing one back
+//! headlessly and checking the final frame hash and RAM turns a whole game
+//! run into a regression test. FCEUX `.fm2` text movies and a simple native
+//! text format are supported.
+//!
+//! The native format has one frame per line: the controller 1 and 2 button
+//! bytes in hex, optionally followed by `reset`. Lines starting with `#` are
+//! comments. `ram_init` and `seed` lines record the power-on RAM pattern and
+//! seed, so the movie replays bit-exact whatever the player's configuration.
+//!
+//! ```text
+//! # rnes movie
+//! ram_init random
+//! seed 42
+//! 00 00
+//! 08 00
+//! 00 00 reset
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use rnes_cartridge::Cartridge;
+use rnes_common::{Byte, Config, RamInitPattern, RnesError, RnesResult, Word};
+use rnes_core::Emulator;
+use crate::golden;
+use crate::report::{TestCaseReport, TestStatus, ToTestCase};
+
+/// Inputs for one frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MovieFrame {
+    /// Button bytes for controllers 1 and 2 (see `ControllerState::from_bits`)
+    pub buttons: [Byte; 2],
+    /// Press reset before this frame
+    pub reset: bool,
+    /// Power cycle before this frame
+    pub power: bool,
+}
+
+/// A recorded input sequence
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Movie {
+    pub frames: Vec<MovieFrame>,
+    /// Header fields from the movie file, e.g. `romFilename`
+    pub header: BTreeMap<String, String>,
+}
+
+/// Header fields the native format keeps
+const NATIVE_HEADER_KEYS: [&str; 2] = ["ram_init", "seed"];
+
+impl Movie {
+    /// Load a movie, choosing the format from the extension
+    pub fn load(path: &Path) -> RnesResult<Self> {
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("fm2")) {
+            Self::parse_fm2(&text)
+        } else {
+            Self::parse_native(&text)
+        }
+    }
+
+    /// Parse an FCEUX text movie
+    pub fn parse_fm2(text: &str) -> RnesResult<Self> {
+        let mut movie = Movie::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if let Some(input) = line.strip_prefix('|') {
+                movie.frames.push(parse_fm2_frame(input).ok_or_else(|| {
+                    RnesError::Serialization(format!("fm2 line {}: malformed input", index + 1))
+                })?);
+            } else if let Some((key, value)) = line.split_once(' ') {
+                movie.header.insert(key.to_string(), value.trim().to_string());
+            }
+        }
+
+        if movie.header.get("binary").is_some_and(|value| value != "0") {
+            return Err(RnesError::UnsupportedFeature {
+                component: "fm2 playback",
+                feature: "binary movies".to_string(),
+            });
+        }
+        if movie.header.get("savestate").is_some_and(|value| !value.is_empty()) {
+            return Err(RnesError::UnsupportedFeature {
+                component: "fm2 playback",
+                feature: "movies starting from a savestate".to_string(),
+            });
+        }
+        Ok(movie)
+    }
+
+    /// Parse the native text format
+    pub fn parse_native(text: &str) -> RnesResult<Self> {
+        let mut movie = Movie::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once(char::is_whitespace) {
+                if NATIVE_HEADER_KEYS.contains(&key) {
+                    movie.header.insert(key.to_string(), value.trim().to_string());
+                    continue;
+                }
+            }
+            let malformed = || RnesError::Serialization(format!("Movie line {}: expected two hex bytes", index + 1));
+            let mut fields = line.split_whitespace();
+            let mut byte = || fields.next().and_then(|field| Byte::from_str_radix(field, 16).ok()).ok_or_else(malformed);
+            let buttons = [byte()?, byte()?];
+            let reset = fields.next() == Some("reset");
+            movie.frames.push(MovieFrame { buttons, reset, power: false });
+        }
+        Ok(movie)
+    }
+
+    /// Write the movie in the native format
+    pub fn to_native(&self) -> String {
+        let mut text = String::from("# rnes movie\n");
+        for key in NATIVE_HEADER_KEYS {
+            if let Some(value) = self.header.get(key) {
+                text.push_str(&format!("{} {}\n", key, value));
+            }
+        }
+        for frame in &self.frames {
+            text.push_str(&format!("{:02X} {:02X}", frame.buttons[0], frame.buttons[1]));
+            if frame.reset {
+                text.push_str(" reset");
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Power-on RAM pattern and seed the movie was recorded with, if stored
+    pub fn power_on(&self) -> RnesResult<Option<(RamInitPattern, u64)>> {
+        let Some(pattern) = self.header.get("ram_init") else {
+            return Ok(None);
+        };
+        let pattern = RamInitPattern::ALL.into_iter()
+            .find(|candidate| candidate.name() == pattern)
+            .ok_or_else(|| RnesError::Serialization(format!("Unknown movie RAM pattern {}", pattern)))?;
+        let seed = match self.header.get("seed") {
+            Some(seed) => seed.parse()
+                .map_err(|_| RnesError::Serialization(format!("Invalid movie seed {}", seed)))?,
+            None => 0,
+        };
+        Ok(Some((pattern, seed)))
+    }
+
+    /// Record the power-on RAM pattern and seed in the header
+    pub fn set_power_on(&mut self, pattern: RamInitPattern, seed: u64) {
+        self.header.insert("ram_init".to_string(), pattern.name().to_string());
+        self.header.insert("seed".to_string(), seed.to_string());
+    }
+}
+
+/// Parse `cmd|RLDUTSBA|RLDUTSBA|` after the leading pipe
+fn parse_fm2_frame(input: &str) -> Option<MovieFrame> {
+    let mut fields = input.split('|');
+    let commands: u8 = fields.next()?.trim().parse().ok()?;
+    let mut frame = MovieFrame {
+        reset: commands & 0x01 != 0,
+        power: commands & 0x02 != 0,
+        ..MovieFrame::default()
+    };
+    for buttons in frame.buttons.iter_mut() {
+        let Some(field) = fields.next() else {
+            break;
+        };
+        // Columns run Right, Left, Down, Up, Start, Select, B, A: bit 7 down to 0
+        for (column, c) in field.chars().take(8).enumerate() {
+            if c != '.' && c != ' ' {
+                *buttons |= 0x80 >> column;
+            }
+        }
+    }
+    Some(frame)
+}
+
+/// Result of playing a movie
+#[derive(Debug, Clone)]
+pub struct MovieReport {
+    pub frames_played: usize,
+    /// CRC-32 of the final frame buffer
+    pub frame_hash: u32,
+    /// Whether the expected frame hash matched, if one was given
+    pub frame_hash_matched: Option<bool>,
+    /// RAM checks that failed: (address, expected, actual)
+    pub ram_mismatches: Vec<(Word, Byte, Byte)>,
+}
+
+impl MovieReport {
+    /// Check if every expectation held
+    pub fn is_success(&self) -> bool {
+        self.frame_hash_matched != Some(false) && self.ram_mismatches.is_empty()
+    }
+}
+
+impl ToTestCase for MovieReport {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        let mut problems: Vec<String> = self.ram_mismatches.iter()
+            .map(|(addr, expected, actual)| format!("${:04X}: expected {:02X}, got {:02X}", addr, expected, actual))
+            .collect();
+        if self.frame_hash_matched == Some(false) {
+            problems.insert(0, format!("frame hash {:08X} differs", self.frame_hash));
+        }
+        TestCaseReport {
+            name: name.to_string(),
+            status: if self.is_success() { TestStatus::Passed } else { TestStatus::Failed },
+            cycles: None,
+            time_secs: 0.0,
+            message: (!problems.is_empty()).then(|| problems.join("; ")),
+        }
+    }
+}
+
+/// Plays a movie headlessly and checks the end state
+pub struct MovieRunner {
+    movie: Movie,
+    extra_frames: u32,
+    expected_frame_hash: Option<u32>,
+    expected_ram: Vec<(Word, Byte)>,
+}
+
+impl MovieRunner {
+    pub fn new(movie: Movie) -> Self {
+        Self {
+            movie,
+            extra_frames: 0,
+            expected_frame_hash: None,
+            expected_ram: Vec::new(),
+        }
+    }
+
+    /// Keep running with no input for `frames` frames after the movie ends
+    pub fn with_extra_frames(mut self, frames: u32) -> Self {
+        self.extra_frames = frames;
+        self
+    }
+
+    /// Expect the final frame buffer to hash to `hash`
+    pub fn with_expected_frame_hash(mut self, hash: u32) -> Self {
+        self.expected_frame_hash = Some(hash);
+        self
+    }
+
+    /// Expect CPU memory at `addr` to hold `value` at the end
+    pub fn with_expected_ram(mut self, addr: Word, value: Byte) -> Self {
+        self.expected_ram.push((addr, value));
+        self
+    }
+
+    /// Play the movie on the ROM at `rom_path`
+    pub fn run(&self, rom_path: &Path) -> RnesResult<MovieReport> {
+        let mut config = Config::default();
+        if let Some((pattern, seed)) = self.movie.power_on()? {
+            config.general.ram_init = pattern;
+            config.general.ram_init_seed = seed;
+        }
+        let mut emulator = Emulator::with_config(config);
+        emulator.load_rom(Cartridge::from_file(rom_path)?)?;
+        self.run_emulator(&mut emulator)
+    }
+
+    /// Play the movie on a loaded emulator
+    ///
+    /// The emulator has already powered on, so the movie's RAM pattern and
+    /// seed only apply from its first power cycle.
+    pub fn run_emulator(&self, emulator: &mut Emulator) -> RnesResult<MovieReport> {
+        if let Some((pattern, seed)) = self.movie.power_on()? {
+            emulator.config.general.ram_init = pattern;
+            emulator.set_seed(seed);
+        }
+        emulator.start();
+        let mut frames_played = 0;
+        for frame in &self.movie.frames {
+            if frame.power {
+                emulator.power_cycle()?;
+            } else if frame.reset {
+                emulator.soft_reset()?;
+            }
+            let next_frame = emulator.next_input_frame();
+            emulator.queue_input(next_frame, frame.buttons);
+            emulator.run_frame()?;
+            frames_played += 1;
+        }
+
+        emulator.set_input(0, 0);
+        emulator.set_input(1, 0);
+        emulator.run_frames(self.extra_frames)?;
+        emulator.stop();
+
+        let frame_hash = emulator.get_ppu_frame_buffer().map(golden::frame_hash).unwrap_or(0);
+        let ram_mismatches = self.expected_ram.iter()
+            .map(|&(addr, expected)| (addr, expected, emulator.peek(addr)))
+            .filter(|&(_, expected, actual)| expected != actual)
+            .collect();
+
+        Ok(MovieReport {
+            frames_played,
+            frame_hash,
+            frame_hash_matched: self.expected_frame_hash.map(|hash| hash == frame_hash),
+            ram_mismatches,
+        })
+    }
+}
+
+/// A movie test described by a JSON manifest
+///
+/// Paths are relative to the manifest. RAM addresses are hex strings:
+///
+/// ```json
+/// { "rom": "smb.nes", "movie": "smb-any.fm2", "frame_hash": 305419896, "ram": { "075F": 7 } }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct MovieCase {
+    pub rom: PathBuf,
+    pub movie: PathBuf,
+    #[serde(default)]
+    pub extra_frames: u32,
+    pub frame_hash: Option<u32>,
+    #[serde(default)]
+    pub ram: BTreeMap<String, Byte>,
+}
+
+impl MovieCase {
+    /// Load a manifest and resolve its paths
+    pub fn load(manifest: &Path) -> RnesResult<Self> {
+        let mut case: MovieCase = serde_json::from_slice(&fs::read(manifest)?)
+            .map_err(|e| RnesError::Serialization(format!("{}: {}", manifest.display(), e)))?;
+        let base = manifest.parent().unwrap_or(Path::new("."));
+        case.rom = base.join(&case.rom);
+        case.movie = base.join(&case.movie);
+        Ok(case)
+    }
+
+    /// Build the runner for this case
+    pub fn runner(&self) -> RnesResult<MovieRunner> {
+        let mut runner = MovieRunner::new(Movie::load(&self.movie)?).with_extra_frames(self.extra_frames);
+        if let Some(hash) = self.frame_hash {
+            runner = runner.with_expected_frame_hash(hash);
+        }
+        for (addr, &value) in &self.ram {
+            let addr = Word::from_str_radix(addr.trim_start_matches("0x").trim_start_matches('$'), 16)
+                .map_err(|_| RnesError::Serialization(format!("Invalid RAM address {}", addr)))?;
+            runner = runner.with_expected_ram(addr, value);
+        }
+        Ok(runner)
+    }
+
+    /// Play the movie and check the expectations
+    pub fn run(&self) -> RnesResult<MovieReport> {
+        self.runner()?.run(&self.rom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestRomBuilder;
+
+    #[test]
+    fn test_parse_fm2() {
+        let movie = Movie::parse_fm2("\
+version 3
+romFilename smb
+binary 0
+|0|........|........||
+|0|....T...|........||
+|1|R......A|.L......||
+").unwrap();
+        assert_eq!(movie.header.get("romFilename").map(String::as_str), Some("smb"));
+        assert_eq!(movie.frames.len(), 3);
+        assert_eq!(movie.frames[1].buttons, [0x08, 0x00]);
+        assert_eq!(movie.frames[2], MovieFrame { buttons: [0x81, 0x40], reset: true, power: false });
+        assert!(Movie::parse_fm2("binary 1\n").is_err());
+    }
+
+    #[test]
+    fn test_native_round_trip() {
+        let movie = Movie::parse_native("# test\n00 00\n08 00\n01 02 reset\n").unwrap();
+        assert_eq!(movie.frames[2], MovieFrame { buttons: [0x01, 0x02], reset: true, power: false });
+        assert_eq!(Movie::parse_native(&movie.to_native()).unwrap(), movie);
+        assert!(Movie::parse_native("zz 00\n").is_err());
+    }
+
+    #[test]
+    fn test_native_power_on_header() {
+        let movie = Movie::parse_native("# test\nram_init random\nseed 42\n00 00\n").unwrap();
+        assert_eq!(movie.power_on().unwrap(), Some((RamInitPattern::Random, 42)));
+        assert_eq!(movie.frames.len(), 1);
+        assert_eq!(Movie::parse_native(&movie.to_native()).unwrap(), movie);
+
+        let mut recorded = Movie::default();
+        assert_eq!(recorded.power_on().unwrap(), None);
+        recorded.set_power_on(RamInitPattern::Fceux, 7);
+        assert!(recorded.to_native().contains("ram_init fceux\nseed 7\n"));
+        assert!(Movie::parse_native("ram_init noise\n").unwrap().power_on().is_err());
+    }
+
+    #[test]
+    fn test_movie_playback_checks_ram() {
+        // Copy controller 1 into $10 every pass (A ends up in bit 7)
+        let cartridge = TestRomBuilder::new()
+            .with_program("
+                reset: lda #$01
+                       sta $4016
+                       lda #$00
+                       sta $4016
+                       ldx #$08
+                loop:  lda $4016
+                       lsr a
+                       rol $11
+                       dex
+                       bne loop
+                       lda $11
+                       sta $10
+                       jmp reset
+            ")
+            .build_cartridge();
+        let movie = Movie::parse_native("00 00\n00 00\n08 00\n08 00\n").unwrap();
+
+        let mut emulator = Emulator::with_config(Config::default());
+        emulator.load_rom(cartridge).unwrap();
+        let report = MovieRunner::new(movie)
+            .with_expected_ram(0x0010, 0x10)
+            .with_expected_ram(0x0010, 0x20)
+            .run_emulator(&mut emulator)
+            .unwrap();
+
+        assert_eq!(report.frames_played, 4);
+        assert!(!report.is_success());
+        assert_eq!(report.ram_mismatches, vec![(0x0010, 0x20, 0x10)]);
+    }
+}
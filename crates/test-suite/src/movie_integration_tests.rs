@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::PathBuf;
+use rnes_test_suite::movie::MovieCase;
+use rnes_test_suite::report::TestSuiteReport;
+
+/// Play every movie described by a manifest in tests/movies
+#[test]
+#[ignore] // Requires user-supplied ROMs and movies
+fn test_movie_playback() {
+    let movies_dir = PathBuf::from("../../tests/movies");
+    let Ok(entries) = fs::read_dir(&movies_dir) else {
+        println!("⚠️  No movie manifests found in {}", movies_dir.display());
+        return;
+    };
+
+    let mut manifests: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    manifests.sort();
+
+    let mut report = TestSuiteReport::new("movie_playback");
+    let mut failures = Vec::new();
+    for manifest in manifests {
+        let name = manifest.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        println!("🎬 Playing movie: {}", name);
+
+        let case = match MovieCase::load(&manifest) {
+            Ok(case) => case,
+            Err(e) => {
+                report.record_error(&name, &e);
+                println!("  📁 LOAD ERROR: {}", e);
+                failures.push(name);
+                continue;
+            }
+        };
+        if !case.rom.exists() {
+            report.record_skipped(&name, "ROM not found");
+            println!("⚠️  ROM not found: {}", case.rom.display());
+            continue;
+        }
+
+        let result = case.run();
+        report.record(&name, &result);
+        match result {
+            Ok(result) if result.is_success() => {
+                println!("  ✅ PASS ({} frames, hash {:08X})", result.frames_played, result.frame_hash);
+            }
+            Ok(result) => {
+                println!("  ❌ FAIL ({} frames, hash {:08X})", result.frames_played, result.frame_hash);
+                if result.frame_hash_matched == Some(false) {
+                    println!("     Final frame hash differs");
+                }
+                for (addr, expected, actual) in &result.ram_mismatches {
+                    println!("     ${:04X}: expected {:02X}, got {:02X}", addr, expected, actual);
+                }
+                failures.push(name);
+            }
+            Err(e) => {
+                println!("  💥 ERROR: {}", e);
+                failures.push(name);
+            }
+        }
+    }
+
+    report.finish();
+    assert!(failures.is_empty(), "Movie failures: {:?}", failures);
+}
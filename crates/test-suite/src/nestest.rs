@@ -0,0 +1,274 @@
+//! Golden-log comparison against nestest.log
+//!
+//! nestest.nes run from $C000 (automation mode) exercises every official and
+//! most unofficial opcodes without a PPU. Comparing our trace to the
+//! canonical log line by line pinpoints the first instruction that goes wrong.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use rnes_core::{Emulator, TraceEntry};
+use rnes_cartridge::Cartridge;
+use rnes_common::{Byte, Config, RnesError, RnesResult};
+
+/// Entry point used when nestest runs without a PPU
+pub const NESTEST_AUTOMATION_PC: u16 = 0xC000;
+
+/// The first line where our trace differs from the log
+#[derive(Debug, Clone)]
+pub struct NestestDivergence {
+    /// Line number in the log (1-based)
+    pub line: usize,
+    pub expected: TraceEntry,
+    pub actual: TraceEntry,
+    /// Fields that differ, e.g. `A: expected 5A, got 00`
+    pub fields: Vec<String>,
+    /// Our trace lines leading up to the divergence
+    pub context: Vec<TraceEntry>,
+}
+
+impl fmt::Display for NestestDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Divergence at line {}: {}", self.line, self.fields.join(", "))?;
+        for entry in &self.context {
+            writeln!(f, "           {}", entry)?;
+        }
+        writeln!(f, "  expected {}", self.expected)?;
+        write!(f, "  actual   {}", self.actual)
+    }
+}
+
+/// Result of a nestest run
+#[derive(Debug, Clone)]
+pub struct NestestReport {
+    /// Lines that matched before the run stopped
+    pub lines_matched: usize,
+    /// Lines in the log
+    pub lines_total: usize,
+    pub divergence: Option<NestestDivergence>,
+    /// Error code for official opcodes, stored at $02 (0 is a pass)
+    pub official_result: Byte,
+    /// Error code for unofficial opcodes, stored at $03 (0 is a pass)
+    pub unofficial_result: Byte,
+}
+
+impl NestestReport {
+    /// Check if the whole log matched
+    pub fn is_success(&self) -> bool {
+        self.divergence.is_none() && self.lines_matched == self.lines_total
+    }
+}
+
+/// Runs nestest.nes and diffs the trace against nestest.log
+pub struct NestestRunner {
+    check_cycles: bool,
+    check_ppu: bool,
+    context_lines: usize,
+}
+
+impl NestestRunner {
+    pub fn new() -> Self {
+        Self {
+            check_cycles: true,
+            check_ppu: false,
+            context_lines: 5,
+        }
+    }
+
+    /// Compare the CYC column
+    pub fn with_cycle_check(mut self, enabled: bool) -> Self {
+        self.check_cycles = enabled;
+        self
+    }
+
+    /// Compare the PPU scanline and dot columns
+    pub fn with_ppu_check(mut self, enabled: bool) -> Self {
+        self.check_ppu = enabled;
+        self
+    }
+
+    /// Set how many preceding trace lines a divergence report shows
+    pub fn with_context_lines(mut self, lines: usize) -> Self {
+        self.context_lines = lines;
+        self
+    }
+
+    /// Run the ROM at `rom_path` against the log at `log_path`
+    pub fn run(&self, rom_path: &Path, log_path: &Path) -> RnesResult<NestestReport> {
+        let log = fs::read_to_string(log_path)?;
+        let expected = log.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| TraceEntry::parse(line).ok_or_else(|| {
+                RnesError::Serialization(format!("nestest.log line {} is malformed", index + 1))
+            }))
+            .collect::<RnesResult<Vec<_>>>()?;
+
+        let mut emulator = Emulator::with_config(Config::default());
+        emulator.load_rom(Cartridge::from_file(rom_path)?)?;
+        self.compare(&mut emulator, &expected)
+    }
+
+    /// Run a loaded emulator from $C000 against parsed log lines
+    pub fn compare(&self, emulator: &mut Emulator, expected: &[TraceEntry]) -> RnesResult<NestestReport> {
+        emulator.cpu.pc = NESTEST_AUTOMATION_PC;
+        emulator.enable_trace(1);
+        emulator.start();
+
+        let mut report = NestestReport {
+            lines_matched: 0,
+            lines_total: expected.len(),
+            divergence: None,
+            official_result: 0,
+            unofficial_result: 0,
+        };
+        // nestest.log starts after the 7-cycle reset sequence
+        let mut cycle_offset = None;
+        let mut context = VecDeque::with_capacity(self.context_lines + 1);
+
+        for (index, want) in expected.iter().enumerate() {
+            let Some(got) = self.next_instruction(emulator)? else {
+                break;
+            };
+            let offset = *cycle_offset.get_or_insert(want.cycles as i64 - got.cycles as i64);
+
+            let fields = self.differences(want, &got, offset);
+            if !fields.is_empty() {
+                report.divergence = Some(NestestDivergence {
+                    line: index + 1,
+                    expected: want.clone(),
+                    actual: got,
+                    fields,
+                    context: context.into_iter().collect(),
+                });
+                break;
+            }
+            report.lines_matched += 1;
+
+            context.push_back(got);
+            if context.len() > self.context_lines {
+                context.pop_front();
+            }
+        }
+
+        emulator.stop();
+        emulator.disable_trace();
+        report.official_result = emulator.peek(0x0002);
+        report.unofficial_result = emulator.peek(0x0003);
+        Ok(report)
+    }
+
+    /// Step until the next instruction is traced, or `None` if the emulator stopped
+    fn next_instruction(&self, emulator: &mut Emulator) -> RnesResult<Option<TraceEntry>> {
+        loop {
+            if emulator.step()? == 0 {
+                return Ok(None);
+            }
+            if let Some(entry) = emulator.trace_mut().and_then(|trace| trace.take().pop()) {
+                return Ok(Some(entry));
+            }
+        }
+    }
+
+    /// List the fields of `got` that don't match `want`
+    fn differences(&self, want: &TraceEntry, got: &TraceEntry, cycle_offset: i64) -> Vec<String> {
+        let mut fields = Vec::new();
+        if want.pc != got.pc {
+            fields.push(format!("PC: expected {:04X}, got {:04X}", want.pc, got.pc));
+        }
+        if want.bytes != got.bytes {
+            fields.push(format!("bytes: expected {:02X?}, got {:02X?}", want.bytes, got.bytes));
+        }
+        let registers = [
+            ("A", want.a, got.a),
+            ("X", want.x, got.x),
+            ("Y", want.y, got.y),
+            ("P", want.p, got.p),
+            ("SP", want.sp, got.sp),
+        ];
+        for (name, want, got) in registers {
+            if want != got {
+                fields.push(format!("{}: expected {:02X}, got {:02X}", name, want, got));
+            }
+        }
+        if self.check_ppu && (want.scanline, want.dot) != (got.scanline, got.dot) {
+            fields.push(format!(
+                "PPU: expected {},{}, got {},{}",
+                want.scanline, want.dot, got.scanline, got.dot
+            ));
+        }
+        let got_cycles = got.cycles as i64 + cycle_offset;
+        if self.check_cycles && want.cycles as i64 != got_cycles {
+            fields.push(format!("CYC: expected {}, got {}", want.cycles, got_cycles));
+        }
+        fields
+    }
+}
+
+impl Default for NestestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestRomBuilder;
+
+    fn load_countdown() -> Emulator {
+        let cartridge = TestRomBuilder::new()
+            .with_program("
+                       .org $C000
+                start: ldx #$03
+                loop:  dex
+                       bne loop
+                       jmp start
+            ")
+            .build_cartridge();
+        let mut emulator = Emulator::with_config(Config::default());
+        emulator.load_rom(cartridge).unwrap();
+        emulator
+    }
+
+    fn golden_trace(lines: usize) -> Vec<TraceEntry> {
+        let mut emulator = load_countdown();
+        emulator.cpu.pc = NESTEST_AUTOMATION_PC;
+        emulator.enable_trace(lines);
+        emulator.start();
+        while emulator.trace().unwrap().entries().len() < lines {
+            emulator.step().unwrap();
+        }
+        // Round-trip through the log format, as a real log would be read
+        emulator.trace_mut().unwrap().take().iter()
+            .map(|entry| TraceEntry::parse(&entry.to_string()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_nestest_matching_trace() {
+        let expected = golden_trace(12);
+        let report = NestestRunner::new().compare(&mut load_countdown(), &expected).unwrap();
+        assert!(report.is_success(), "{:?}", report.divergence);
+        assert_eq!(report.lines_matched, 12);
+    }
+
+    #[test]
+    fn test_nestest_reports_first_divergence() {
+        let mut expected = golden_trace(12);
+        expected[6].x = 0x7F;
+        expected[9].a = 0x7F;
+
+        let report = NestestRunner::new()
+            .with_context_lines(2)
+            .compare(&mut load_countdown(), &expected)
+            .unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!(report.lines_matched, 6);
+        assert_eq!(divergence.line, 7);
+        assert_eq!(divergence.fields, vec![format!("X: expected 7F, got {:02X}", divergence.actual.x)]);
+        assert_eq!(divergence.context.len(), 2);
+        assert_eq!(divergence.context[1].pc, expected[5].pc);
+    }
+}
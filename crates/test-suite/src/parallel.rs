@@ -0,0 +1,79 @@
+//! Parallel ROM runs collected into test reports
+//!
+//! The runner itself lives in `rnes-compat`, which the native frontend
+//! shares; this module turns its results into `TestSuiteReport`s.
+
+use std::path::{Path, PathBuf};
+use rnes_common::RnesResult;
+use crate::report::{TestCaseReport, TestStatus, TestSuiteReport, ToTestCase};
+
+pub use rnes_compat::parallel::{ParallelRomRunner, RomOutcome, RomRun};
+
+impl<R: ToTestCase> ToTestCase for RomRun<R> {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        let mut case = match &self.outcome {
+            RomOutcome::Finished(result) => result.to_test_case(name),
+            RomOutcome::Panicked(message) => TestCaseReport {
+                name: name.to_string(),
+                status: TestStatus::Error,
+                cycles: None,
+                time_secs: 0.0,
+                message: Some(format!("panicked: {}", message)),
+            },
+            RomOutcome::TimedOut => TestCaseReport {
+                name: name.to_string(),
+                status: TestStatus::Timeout,
+                cycles: None,
+                time_secs: 0.0,
+                message: Some(format!("wall-clock timeout after {:.1}s", self.elapsed.as_secs_f64())),
+            },
+        };
+        case.time_secs = self.elapsed.as_secs_f64();
+        case
+    }
+}
+
+/// Run `test` on every ROM and collect the results in a report
+pub fn run_to_report<F, R>(runner: &ParallelRomRunner, suite: &str, roms: &[PathBuf], test: F) -> TestSuiteReport
+where
+    F: Fn(&Path) -> RnesResult<R> + Send + Sync + 'static,
+    R: ToTestCase + Send + 'static,
+{
+    let mut report = TestSuiteReport::new(suite);
+    for run in runner.run(roms, test) {
+        report.push(run.to_test_case(&run.name()));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use crate::m6_integration_tests::TestResult;
+
+    fn roms(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_parallel_isolates_panics_and_timeouts() {
+        let runner = ParallelRomRunner::new()
+            .with_threads(2)
+            .with_timeout(Duration::from_millis(100));
+        let report = run_to_report(&runner, "isolation", &roms(&["ok.nes", "panic.nes", "hang.nes", "ok2.nes"]), |path| {
+            if path.ends_with("panic.nes") {
+                panic!("bad opcode");
+            }
+            if path.ends_with("hang.nes") {
+                thread::sleep(Duration::from_secs(2));
+            }
+            Ok(TestResult::Passed)
+        });
+
+        let statuses: Vec<TestStatus> = report.cases.iter().map(|case| case.status).collect();
+        assert_eq!(statuses, [TestStatus::Passed, TestStatus::Error, TestStatus::Timeout, TestStatus::Passed]);
+        assert_eq!(report.cases[1].message.as_deref(), Some("panicked: bad opcode"));
+    }
+}
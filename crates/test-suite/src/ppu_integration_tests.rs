@@ -1,5 +1,11 @@
 use std::path::PathBuf;
-use rnes_test_suite::ppu_test_runner::{PpuTestRunner, PpuTestResult};
+use rnes_test_suite::ppu_test_runner::{PpuTestRunner, PpuTestResult, Rect};
+use rnes_test_suite::report::TestSuiteReport;
+use rnes_test_suite::{GoldenFrameRunner, GoldenResult, FrameTolerance};
+use rnes_test_suite::rom_builder::{status_bar_split_rom, STATUS_BAR_SPLIT_LINE};
+use rnes_cartridge::Cartridge;
+use rnes_common::{Config, Pixel, NES_PALETTE, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rnes_core::Emulator;
 
 /// Test PPU basic functionality
 #[test]
@@ -40,6 +46,7 @@ fn test_blargg_ppu_suite() {
         "power_up_palette.nes",
     ];
     
+    let mut report = TestSuiteReport::new("blargg_ppu_suite");
     for test_file in test_files {
         let rom_path = blargg_ppu_dir.join(test_file);
         
@@ -52,7 +59,9 @@ fn test_blargg_ppu_suite() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 PpuTestResult::Completed { cycles, frames, .. } => {
@@ -72,13 +81,17 @@ fn test_blargg_ppu_suite() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test PPU VBL NMI functionality
@@ -201,6 +214,7 @@ fn test_full_palette() {
         "flowing_palette.nes",
     ];
     
+    let mut report = TestSuiteReport::new("full_palette");
     for test_file in test_files {
         let rom_path = full_palette_dir.join(test_file);
         
@@ -214,10 +228,12 @@ fn test_full_palette() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
-                                PpuTestResult::Completed { cycles, frames, final_frame, status: _ } => {
+                                PpuTestResult::Completed { cycles, frames, final_frame, .. } => {
                                     println!("  ✅ PASS ({} cycles, {} frames)", cycles, frames);
                                     
                                     // Analyze frame output for palette correctness
@@ -239,13 +255,17 @@ fn test_full_palette() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test NROM mapper specific functionality
@@ -265,6 +285,7 @@ fn test_nrom_mapper() {
         "fail368.nes",
     ];
     
+    let mut report = TestSuiteReport::new("nrom_mapper");
     for test_file in test_files {
         let rom_path = nrom_dir.join(test_file);
         
@@ -277,7 +298,9 @@ fn test_nrom_mapper() {
             
             match runner.load_rom(&rom_path) {
                 Ok(_) => {
-                    match runner.run_test() {
+                    let result = runner.run_test();
+                    report.record(test_file, &result);
+                    match result {
                         Ok(result) => {
                             match result {
                                 PpuTestResult::Completed { cycles, frames, .. } => {
@@ -297,13 +320,17 @@ fn test_nrom_mapper() {
                     }
                 }
                 Err(e) => {
+                    report.record_error(test_file, &e);
                     println!("  📁 LOAD ERROR: {}", e);
                 }
             }
         } else {
+            report.record_skipped(test_file, "not found");
             println!("⚠️  Test file not found: {}", test_file);
         }
     }
+    
+    report.finish();
 }
 
 /// Test background scrolling functionality
@@ -333,7 +360,7 @@ fn test_background_scrolling() {
                 match runner.run_test() {
                     Ok(result) => {
                         match result {
-                            PpuTestResult::Completed { cycles, frames, final_frame, status: _ } => {
+                            PpuTestResult::Completed { cycles, frames, final_frame, .. } => {
                                 println!("  ✅ PASS ({} cycles, {} frames)", cycles, frames);
                                 
                                 // Analyze frame output for scrolling correctness
@@ -363,6 +390,112 @@ fn test_background_scrolling() {
     }
 }
 
+/// Mid-frame $2006/$2005 writes split the screen into a scrolled playfield
+/// and a fixed status bar
+#[test]
+fn test_status_bar_split() {
+    let mut emulator = Emulator::with_config(Config::default());
+    emulator.load_rom(Cartridge::from_bytes(&status_bar_split_rom()).unwrap()).unwrap();
+    emulator.start();
+    assert_eq!(emulator.run_frames(4).unwrap(), 4);
+    
+    let frame = emulator.get_ppu_frame_buffer().unwrap();
+    let pixel = |x: usize, y: usize| frame[y * SCREEN_WIDTH + x];
+    let red = Pixel::from_rgb(NES_PALETTE[0x16]);
+    let green = Pixel::from_rgb(NES_PALETTE[0x2A]);
+    
+    // Playfield stripes start at column 0 but are scrolled 4 pixels
+    for y in 8..STATUS_BAR_SPLIT_LINE - 24 {
+        assert_eq!(pixel(0, y), red, "playfield row {}", y);
+        assert_eq!(pixel(3, y), red, "playfield row {}", y);
+        assert_ne!(pixel(4, y), red, "playfield row {}", y);
+        assert_ne!(pixel(11, y), red, "playfield row {}", y);
+        assert_eq!(pixel(12, y), red, "playfield row {}", y);
+    }
+    
+    // Status bar rows come from nametable 2 with no scroll
+    for y in STATUS_BAR_SPLIT_LINE + 16..SCREEN_HEIGHT {
+        assert_eq!(pixel(0, y), green, "status bar row {}", y);
+        assert_eq!(pixel(7, y), green, "status bar row {}", y);
+        assert_ne!(pixel(8, y), green, "status bar row {}", y);
+        assert_eq!(pixel(16, y), green, "status bar row {}", y);
+    }
+    println!("✅ Status bar split rendered");
+}
+
+/// Frame analysis helpers on a known picture
+#[test]
+fn test_frame_analysis_helpers() {
+    let mut runner = PpuTestRunner::new();
+    runner.load_cartridge(Cartridge::from_bytes(&status_bar_split_rom()).unwrap()).unwrap();
+    let frames = runner.wait_for_stable_frame(2, 20).unwrap();
+    assert!(frames.is_some_and(|frames| frames <= 20), "picture never settled");
+    
+    let red = Pixel::from_rgb(NES_PALETTE[0x16]);
+    let green = Pixel::from_rgb(NES_PALETTE[0x2A]);
+    assert_eq!(runner.pixel_at(0, 100), Some(red));
+    assert_eq!(runner.pixel_at(0, SCREEN_HEIGHT - 1), Some(green));
+    assert_eq!(runner.pixel_at(SCREEN_WIDTH, 0), None);
+    
+    assert_eq!(runner.region_average_color(Rect::new(0, 8, 4, 100)), Some(red));
+    assert_eq!(runner.region_average_color(Rect::new(0, STATUS_BAR_SPLIT_LINE + 16, 8, 8)), Some(green));
+    // Clipped at the right edge
+    assert!(runner.region_average_color(Rect::new(SCREEN_WIDTH - 4, 8, 100, 8)).is_some());
+    assert_eq!(runner.region_average_color(Rect::new(SCREEN_WIDTH, 0, 8, 8)), None);
+    
+    let drawn = runner.count_non_background_pixels();
+    assert!(drawn > 0 && drawn < SCREEN_WIDTH * SCREEN_HEIGHT, "{} pixels drawn", drawn);
+    println!("✅ Frame analysis helpers work");
+}
+
+/// Compare rendered frames with the stored golden screenshots
+///
+/// Run with `RNES_UPDATE_GOLDEN=1` to regenerate the goldens.
+#[test]
+#[ignore] // Requires test ROMs to be downloaded
+fn test_golden_frames() {
+    let test_roms_dir = PathBuf::from("../../tests/roms/nes-test-roms");
+    if !test_roms_dir.exists() {
+        println!("⚠️  Test ROMs not found, please run: ./scripts/download_all_test_roms.sh");
+        return;
+    }
+
+    let runner = GoldenFrameRunner::new("../../tests/golden")
+        .with_tolerance(FrameTolerance { max_channel_delta: 0, max_differing_pixels: 0 });
+    let cases = [
+        ("nestest_menu", "other/nestest.nes", 30),
+        ("full_palette", "full_palette/full_palette.nes", 60),
+        ("scroll", "scrolltest/scroll.nes", 60),
+    ];
+
+    let mut failures = Vec::new();
+    for (name, rom, frames) in cases {
+        let rom_path = test_roms_dir.join(rom);
+        if !rom_path.exists() {
+            println!("⚠️  Test file not found: {}", rom);
+            continue;
+        }
+
+        match runner.run_rom(name, &rom_path, frames) {
+            Ok(GoldenResult::Matched { hash, .. }) => println!("  ✅ {} matches ({:08X})", name, hash),
+            Ok(GoldenResult::Updated { hash }) => println!("  📝 {} golden updated ({:08X})", name, hash),
+            Ok(GoldenResult::Missing { hash }) => {
+                println!("  ⚠️  {} has no golden ({:08X}), run with RNES_UPDATE_GOLDEN=1", name, hash);
+            }
+            Ok(GoldenResult::Mismatch { diff, diff_path, .. }) => {
+                println!("  ❌ {}: {} pixels differ, see {}", name, diff.differing_pixels, diff_path.display());
+                failures.push(name);
+            }
+            Err(e) => {
+                println!("  💥 {}: {}", name, e);
+                failures.push(name);
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "Golden frame mismatches: {:?}", failures);
+}
+
 /// Analyze palette output for correctness
 fn analyze_palette_output(frame: &[rnes_common::Pixel]) {
     // Count unique colors in the frame
@@ -1,7 +1,24 @@
 use std::path::Path;
 use rnes_core::Emulator;
 use rnes_cartridge::Cartridge;
-use rnes_common::{RnesResult, Pixel};
+use rnes_common::{RnesResult, Pixel, NES_PALETTE, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::blargg::{read_legacy_result, BlarggMonitor, BlarggPoll};
+use crate::golden::frame_hash;
+
+/// Rectangle of screen pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+}
 
 /// PPU test result
 #[derive(Debug)]
@@ -12,6 +29,8 @@ pub enum PpuTestResult {
         cycles: u64,
         frames: u64,
         final_frame: Vec<Pixel>,
+        /// Result text from ROMs using the blargg protocol
+        message: Option<String>,
     },
     /// Test timed out
     Timeout { 
@@ -32,6 +51,7 @@ pub struct PpuTestRunner {
     max_cycles: u64,
     max_frames: u64,
     check_frame_output: bool,
+    legacy_result: bool,
 }
 
 impl PpuTestRunner {
@@ -41,6 +61,7 @@ impl PpuTestRunner {
             max_cycles: 1000000, // Default maximum 1 million cycles
             max_frames: 1000,    // Default maximum 1000 frames
             check_frame_output: false,
+            legacy_result: false,
         }
     }
     
@@ -59,20 +80,33 @@ impl PpuTestRunner {
         self
     }
     
+    /// Take the result from $F8, as older blargg ROMs report it, instead of
+    /// guessing completion from the PPU state
+    pub fn with_legacy_result(mut self, legacy: bool) -> Self {
+        self.legacy_result = legacy;
+        self
+    }
+    
     /// Load ROM into the emulator
     pub fn load_rom(&mut self, rom_path: &Path) -> RnesResult<()> {
         let cartridge = Cartridge::from_file(rom_path)?;
         self.emulator.load_rom(cartridge)
     }
     
+    /// Load an in-memory cartridge, e.g. one from `TestRomBuilder`
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) -> RnesResult<()> {
+        self.emulator.load_rom(cartridge)
+    }
+    
     /// Run PPU test
     pub fn run_test(&mut self) -> RnesResult<PpuTestResult> {
         let mut cycles = 0;
         let mut frames = 0;
         let mut last_frame_count = 0;
+        let mut blargg = BlarggMonitor::new();
         
-        // Reset emulator
-        let _ = self.emulator.reset();
+        // Start from power-on state
+        let _ = self.emulator.power_cycle();
         
         // Start emulator
         self.emulator.start();
@@ -102,19 +136,43 @@ impl PpuTestRunner {
                 last_frame_count = current_frame_count;
             }
             
-            // Check for test completion (common patterns in PPU test ROMs)
-            if self.check_test_completion() {
-                let final_frame = if self.check_frame_output {
-                    self.emulator.ppu().frame_buffer().to_vec()
-                } else {
-                    vec![]
-                };
-                
+            // Tests using the blargg protocol report their own status
+            match blargg.poll(&self.emulator.bus, cycles) {
+                BlarggPoll::Done { status, message } => {
+                    return Ok(PpuTestResult::Completed {
+                        status,
+                        cycles,
+                        frames,
+                        final_frame: self.final_frame(),
+                        message: Some(message),
+                    });
+                }
+                BlarggPoll::Reset => {
+                    self.emulator.soft_reset()?;
+                    continue;
+                }
+                BlarggPoll::Running => continue,
+                BlarggPoll::Absent => {}
+            }
+            
+            if self.legacy_result {
+                if let Some(status) = read_legacy_result(&self.emulator.bus, self.emulator.cpu().pc) {
+                    return Ok(PpuTestResult::Completed {
+                        status,
+                        cycles,
+                        frames,
+                        final_frame: self.final_frame(),
+                        message: None,
+                    });
+                }
+            } else if self.check_test_completion() {
+                // Completion guessed from common patterns in PPU test ROMs
                 return Ok(PpuTestResult::Completed {
                     status: 0, // Assume success if test completed
                     cycles,
                     frames,
-                    final_frame,
+                    final_frame: self.final_frame(),
+                    message: None,
                 });
             }
             
@@ -129,20 +187,24 @@ impl PpuTestRunner {
         }
         
         // If we get here, the test completed normally
-        let final_frame = if self.check_frame_output {
-            self.emulator.ppu().frame_buffer().to_vec()
-        } else {
-            vec![]
-        };
-        
         Ok(PpuTestResult::Completed {
             status: 0,
             cycles,
             frames,
-            final_frame,
+            final_frame: self.final_frame(),
+            message: None,
         })
     }
     
+    /// Copy the frame buffer if frame output checking is enabled
+    fn final_frame(&self) -> Vec<Pixel> {
+        if self.check_frame_output {
+            self.emulator.ppu().frame_buffer().to_vec()
+        } else {
+            vec![]
+        }
+    }
+    
     /// Check if PPU test has completed
     fn check_test_completion(&self) -> bool {
         // Common completion patterns for PPU test ROMs:
@@ -171,6 +233,64 @@ impl PpuTestRunner {
         self.emulator.ppu().frame_buffer()
     }
     
+    /// Get the pixel at a screen position, or `None` off screen
+    pub fn pixel_at(&self, x: usize, y: usize) -> Option<Pixel> {
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            return None;
+        }
+        self.frame_buffer().get(y * SCREEN_WIDTH + x).copied()
+    }
+    
+    /// Average color of a region, clipped to the screen
+    ///
+    /// Returns `None` if no part of the region is on screen.
+    pub fn region_average_color(&self, rect: Rect) -> Option<Pixel> {
+        let frame = self.frame_buffer();
+        let right = (rect.x + rect.width).min(SCREEN_WIDTH);
+        let bottom = (rect.y + rect.height).min(SCREEN_HEIGHT);
+        let mut sums = [0u64; 3];
+        let mut count = 0u64;
+        for y in rect.y..bottom {
+            for pixel in &frame[y * SCREEN_WIDTH + rect.x.min(right)..y * SCREEN_WIDTH + right] {
+                sums[0] += pixel.r as u64;
+                sums[1] += pixel.g as u64;
+                sums[2] += pixel.b as u64;
+                count += 1;
+            }
+        }
+        (count > 0).then(|| Pixel::new((sums[0] / count) as u8, (sums[1] / count) as u8, (sums[2] / count) as u8))
+    }
+    
+    /// Count pixels that differ from the universal background color at $3F00
+    pub fn count_non_background_pixels(&self) -> usize {
+        let backdrop = self.emulator.ppu().palette_ram()[0] & 0x3F;
+        let backdrop = Pixel::from_rgb(NES_PALETTE[backdrop as usize]);
+        self.frame_buffer().iter().filter(|pixel| **pixel != backdrop).count()
+    }
+    
+    /// Run whole frames until the picture stops changing
+    ///
+    /// Returns the number of frames run once `stable_frames` frames in a row
+    /// matched the one before, or `None` if that didn't happen within
+    /// `max_frames`.
+    pub fn wait_for_stable_frame(&mut self, stable_frames: u32, max_frames: u32) -> RnesResult<Option<u32>> {
+        self.emulator.start();
+        let mut last_hash = frame_hash(self.frame_buffer());
+        let mut unchanged = 0;
+        for frame in 1..=max_frames {
+            if self.emulator.run_frames(1)? == 0 {
+                break;
+            }
+            let hash = frame_hash(self.frame_buffer());
+            unchanged = if hash == last_hash { unchanged + 1 } else { 0 };
+            last_hash = hash;
+            if unchanged >= stable_frames {
+                return Ok(Some(frame));
+            }
+        }
+        Ok(None)
+    }
+    
     /// Get PPU registers
     pub fn ppu_registers(&self) -> &rnes_ppu::PpuRegisters {
         self.emulator.ppu().registers()
@@ -0,0 +1,392 @@
+//! Machine-readable test reports
+//!
+//! Batch tests record each ROM's result in a `TestSuiteReport`. When the
+//! `RNES_TEST_REPORT_DIR` environment variable is set, `finish` writes the
+//! report there as `<suite>.json` and `<suite>.xml` (JUnit).
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use rnes_common::{RnesError, RnesResult};
+use crate::apu_test_runner::ApuTestResult;
+use crate::cpu_test_runner::TestResult as CpuTestResult;
+use crate::m4_test_runner::M4TestResult;
+use crate::m5_test_runner::M5TestResult;
+use crate::m6_integration_tests::TestResult as M6TestResult;
+use crate::ppu_test_runner::PpuTestResult;
+
+/// Environment variable naming the directory reports are written to
+pub const TEST_REPORT_DIR_ENV: &str = "RNES_TEST_REPORT_DIR";
+
+/// Outcome of one test case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Timeout,
+    Error,
+    Skipped,
+}
+
+/// One test case in a report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestCaseReport {
+    pub name: String,
+    pub status: TestStatus,
+    /// CPU cycles executed, when the runner tracks them
+    pub cycles: Option<u64>,
+    /// Wall-clock time in seconds
+    pub time_secs: f64,
+    /// Failure text or other details
+    pub message: Option<String>,
+}
+
+/// Convert a runner result into a report case
+pub trait ToTestCase {
+    fn to_test_case(&self, name: &str) -> TestCaseReport;
+}
+
+fn case(name: &str, status: TestStatus, cycles: Option<u64>, message: Option<String>) -> TestCaseReport {
+    TestCaseReport {
+        name: name.to_string(),
+        status,
+        cycles,
+        time_secs: 0.0,
+        message,
+    }
+}
+
+/// Status and message for a ROM that reported a result code
+fn completed(status: u8, message: &Option<String>) -> (TestStatus, Option<String>) {
+    if status == 0 {
+        (TestStatus::Passed, message.as_ref().map(|m| m.trim().to_string()))
+    } else {
+        let text = match message {
+            Some(message) => format!("status {}: {}", status, message.trim()),
+            None => format!("status {}", status),
+        };
+        (TestStatus::Failed, Some(text))
+    }
+}
+
+fn infinite_loop(pc: u16) -> Option<String> {
+    Some(format!("infinite loop at PC=0x{:04X}", pc))
+}
+
+impl ToTestCase for CpuTestResult {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        match self {
+            CpuTestResult::Completed { cycles, status, message, .. } => {
+                let (status, message) = completed(*status, message);
+                case(name, status, Some(*cycles), message)
+            }
+            CpuTestResult::Timeout { cycles } => case(name, TestStatus::Timeout, Some(*cycles), None),
+            CpuTestResult::InfiniteLoop { cycles, pc } => {
+                case(name, TestStatus::Failed, Some(*cycles), infinite_loop(*pc))
+            }
+        }
+    }
+}
+
+impl ToTestCase for ApuTestResult {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        match self {
+            ApuTestResult::Completed { status, cycles, message, .. } => {
+                let (status, message) = completed(*status, message);
+                case(name, status, Some(*cycles as u64), message)
+            }
+            ApuTestResult::Timeout { cycles, .. } => case(name, TestStatus::Timeout, Some(*cycles as u64), None),
+            ApuTestResult::InfiniteLoop { cycles, pc, .. } => {
+                case(name, TestStatus::Failed, Some(*cycles as u64), infinite_loop(*pc))
+            }
+            ApuTestResult::Error { error, cycles } => {
+                case(name, TestStatus::Error, Some(*cycles as u64), Some(error.clone()))
+            }
+        }
+    }
+}
+
+impl ToTestCase for PpuTestResult {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        match self {
+            PpuTestResult::Completed { status, cycles, message, .. } => {
+                let (status, message) = completed(*status, message);
+                case(name, status, Some(*cycles), message)
+            }
+            PpuTestResult::Timeout { cycles, .. } => case(name, TestStatus::Timeout, Some(*cycles), None),
+            PpuTestResult::InfiniteLoop { cycles, pc, .. } => {
+                case(name, TestStatus::Failed, Some(*cycles), infinite_loop(*pc))
+            }
+        }
+    }
+}
+
+impl ToTestCase for M4TestResult {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        match self {
+            M4TestResult::Completed { status, cycles, .. } => {
+                let (status, message) = completed(*status, &None);
+                case(name, status, Some(*cycles as u64), message)
+            }
+            M4TestResult::Timeout { cycles } => case(name, TestStatus::Timeout, Some(*cycles as u64), None),
+            M4TestResult::InfiniteLoop { cycles, pc } => {
+                case(name, TestStatus::Failed, Some(*cycles as u64), infinite_loop(*pc))
+            }
+            M4TestResult::Error { error, cycles } => {
+                case(name, TestStatus::Error, Some(*cycles as u64), Some(error.clone()))
+            }
+        }
+    }
+}
+
+impl ToTestCase for M5TestResult {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        let status = if self.passed { TestStatus::Passed } else { TestStatus::Failed };
+        case(name, status, Some(self.cycles_executed), self.error_message.clone())
+    }
+}
+
+impl ToTestCase for M6TestResult {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        let status = match self {
+            M6TestResult::Passed => TestStatus::Passed,
+            M6TestResult::Failed => TestStatus::Failed,
+            M6TestResult::Timeout => TestStatus::Timeout,
+            M6TestResult::Error => TestStatus::Error,
+        };
+        case(name, status, None, None)
+    }
+}
+
+impl<T: ToTestCase> ToTestCase for RnesResult<T> {
+    fn to_test_case(&self, name: &str) -> TestCaseReport {
+        match self {
+            Ok(result) => result.to_test_case(name),
+            Err(e) => case(name, TestStatus::Error, None, Some(e.to_string())),
+        }
+    }
+}
+
+/// Results of one batch of tests
+#[derive(Debug, Clone, Serialize)]
+pub struct TestSuiteReport {
+    pub name: String,
+    /// Seconds since the Unix epoch when the suite started
+    pub timestamp: u64,
+    pub cases: Vec<TestCaseReport>,
+    #[serde(skip)]
+    last_record: Instant,
+}
+
+impl TestSuiteReport {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            cases: Vec::new(),
+            last_record: Instant::now(),
+        }
+    }
+
+    /// Record a runner result
+    ///
+    /// The case's time is measured from the previous record, so record each
+    /// result right after its test runs.
+    pub fn record<R: ToTestCase>(&mut self, name: &str, result: &R) {
+        let case = result.to_test_case(name);
+        self.push(case);
+    }
+
+    /// Record an error that kept a test from running, such as a load failure
+    pub fn record_error(&mut self, name: &str, error: &RnesError) {
+        self.push(case(name, TestStatus::Error, None, Some(error.to_string())));
+    }
+
+    /// Record a test that was not run
+    pub fn record_skipped(&mut self, name: &str, reason: &str) {
+        self.push(case(name, TestStatus::Skipped, None, Some(reason.to_string())));
+    }
+
+    /// Add a case as is
+    pub fn push(&mut self, mut case: TestCaseReport) {
+        let now = Instant::now();
+        if case.time_secs == 0.0 {
+            case.time_secs = now.duration_since(self.last_record).as_secs_f64();
+        }
+        self.last_record = now;
+        self.cases.push(case);
+    }
+
+    /// Count the cases with `status`
+    pub fn count(&self, status: TestStatus) -> usize {
+        self.cases.iter().filter(|case| case.status == status).count()
+    }
+
+    /// Check if no case failed, timed out or errored
+    pub fn is_success(&self) -> bool {
+        self.cases.iter().all(|case| matches!(case.status, TestStatus::Passed | TestStatus::Skipped))
+    }
+
+    /// One-line summary of the counts
+    pub fn summary(&self) -> String {
+        format!(
+            "{}: {} passed, {} failed, {} timed out, {} errors, {} skipped",
+            self.name,
+            self.count(TestStatus::Passed),
+            self.count(TestStatus::Failed),
+            self.count(TestStatus::Timeout),
+            self.count(TestStatus::Error),
+            self.count(TestStatus::Skipped),
+        )
+    }
+
+    /// Serialize the report as JSON
+    pub fn to_json(&self) -> RnesResult<String> {
+        serde_json::to_string_pretty(self).map_err(|e| RnesError::Serialization(e.to_string()))
+    }
+
+    /// Serialize the report as JUnit XML
+    pub fn to_junit_xml(&self) -> String {
+        let total_time: f64 = self.cases.iter().map(|case| case.time_secs).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            xml_escape(&self.name),
+            self.cases.len(),
+            self.count(TestStatus::Failed) + self.count(TestStatus::Timeout),
+            self.count(TestStatus::Error),
+            self.count(TestStatus::Skipped),
+            total_time,
+        );
+        for case in &self.cases {
+            let _ = write!(
+                xml,
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                xml_escape(&case.name),
+                xml_escape(&self.name),
+                case.time_secs,
+            );
+            let message = xml_escape(case.message.as_deref().unwrap_or(""));
+            let cycles = case.cycles.map(|c| format!(" ({} cycles)", c)).unwrap_or_default();
+            let _ = match case.status {
+                TestStatus::Passed => writeln!(xml, "/>"),
+                TestStatus::Failed => writeln!(
+                    xml, ">\n    <failure message=\"{}\">{}{}</failure>\n  </testcase>", message, message, cycles
+                ),
+                TestStatus::Timeout => writeln!(
+                    xml, ">\n    <failure message=\"timeout\">timeout{}</failure>\n  </testcase>", cycles
+                ),
+                TestStatus::Error => writeln!(
+                    xml, ">\n    <error message=\"{}\">{}</error>\n  </testcase>", message, message
+                ),
+                TestStatus::Skipped => writeln!(
+                    xml, ">\n    <skipped message=\"{}\"/>\n  </testcase>", message
+                ),
+            };
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+
+    /// Write the report to `path`, as JUnit XML for `.xml` and JSON otherwise
+    pub fn write(&self, path: &Path) -> RnesResult<()> {
+        let contents = if path.extension().is_some_and(|ext| ext == "xml") {
+            self.to_junit_xml()
+        } else {
+            self.to_json()?
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Write `<name>.json` and `<name>.xml` into `dir`
+    pub fn write_to_dir(&self, dir: &Path) -> RnesResult<()> {
+        fs::create_dir_all(dir)?;
+        self.write(&dir.join(format!("{}.json", self.name)))?;
+        self.write(&dir.join(format!("{}.xml", self.name)))
+    }
+
+    /// Print the summary and write the report if `RNES_TEST_REPORT_DIR` is set
+    pub fn finish(&self) {
+        println!("📋 {}", self.summary());
+        let Some(dir) = std::env::var_os(TEST_REPORT_DIR_ENV).map(PathBuf::from) else {
+            return;
+        };
+        match self.write_to_dir(&dir) {
+            Ok(()) => println!("📄 Report written to {}", dir.display()),
+            Err(e) => println!("⚠️  Failed to write report: {}", e),
+        }
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_report() -> TestSuiteReport {
+        let mut report = TestSuiteReport::new("blargg_cpu");
+        report.record("pass.nes", &CpuTestResult::Timeout { cycles: 10 });
+        report.record("loop.nes", &M6TestResult::Passed);
+        let failed: RnesResult<M5TestResult> = Err(RnesError::RomFormat("bad <header>".to_string()));
+        report.record("bad.nes", &failed);
+        report.record_skipped("missing.nes", "not found");
+        report
+    }
+
+    #[test]
+    fn test_report_counts_and_json() {
+        let report = sample_report();
+        assert_eq!(report.count(TestStatus::Timeout), 1);
+        assert_eq!(report.count(TestStatus::Passed), 1);
+        assert_eq!(report.count(TestStatus::Error), 1);
+        assert!(!report.is_success());
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["name"], "blargg_cpu");
+        assert_eq!(json["cases"][0]["status"], "timeout");
+        assert_eq!(json["cases"][0]["cycles"], 10);
+        assert_eq!(json["cases"][3]["status"], "skipped");
+    }
+
+    #[test]
+    fn test_report_junit_xml() {
+        let xml = sample_report().to_junit_xml();
+        assert!(xml.contains("<testsuite name=\"blargg_cpu\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\""));
+        assert!(xml.contains("<testcase name=\"loop.nes\" classname=\"blargg_cpu\""));
+        assert!(xml.contains("&lt;header&gt;"));
+        assert!(xml.trim_end().ends_with("</testsuite>"));
+    }
+
+    #[test]
+    fn test_completed_status_messages() {
+        let result = CpuTestResult::Completed {
+            cycles: 5,
+            status: 3,
+            cpu_state: rnes_cpu6502::Cpu::new(),
+            message: Some("Failed #3\n".to_string()),
+        };
+        let case = result.to_test_case("official.nes");
+        assert_eq!(case.status, TestStatus::Failed);
+        assert_eq!(case.message.as_deref(), Some("status 3: Failed #3"));
+    }
+}
@@ -0,0 +1,384 @@
+use rnes_cartridge::{Cartridge, Mirroring};
+use rnes_common::{RnesError, RnesResult, Word};
+
+/// Builder for synthetic iNES test ROMs
+///
+/// The program is assembled with `rnes_cpu6502::assemble`, starting at
+/// $8000 unless it uses `.org`. Code at $8000-$BFFF lands in the first 16KB
+/// of PRG ROM and code at $C000-$FFFF in the last 16KB, which is how NROM,
+/// UxROM, MMC1 and MMC3 map them at power-on.
+///
+/// The reset vector points at the `reset` label if the program has one,
+/// otherwise at $8000; the `nmi` and `irq` labels set the other vectors.
+///
+/// ```ignore
+/// let rom = TestRomBuilder::new()
+///     .with_mapper(4)
+///     .with_battery(true)
+///     .with_program("
+///         reset: lda #$42
+///                sta $6000
+///         loop:  jmp loop
+///     ")
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestRomBuilder {
+    mapper: u8,
+    prg_banks: u8,
+    chr_banks: u8,
+    mirroring: Mirroring,
+    battery: bool,
+    prg_fill: u8,
+    program: String,
+    prg_patches: Vec<(usize, Vec<u8>)>,
+    chr: Vec<u8>,
+    reset_vector: Option<Word>,
+    nmi_vector: Option<Word>,
+    irq_vector: Option<Word>,
+}
+
+impl TestRomBuilder {
+    /// Create a builder for an NROM cartridge with 16KB PRG and 8KB CHR
+    pub fn new() -> Self {
+        Self {
+            mapper: 0,
+            prg_banks: 1,
+            chr_banks: 1,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            prg_fill: 0x00,
+            program: String::new(),
+            prg_patches: Vec::new(),
+            chr: Vec::new(),
+            reset_vector: None,
+            nmi_vector: None,
+            irq_vector: None,
+        }
+    }
+
+    /// Set the iNES mapper number
+    pub fn with_mapper(mut self, mapper: u8) -> Self {
+        self.mapper = mapper;
+        self
+    }
+
+    /// Set PRG ROM size in 16KB banks
+    pub fn with_prg_banks(mut self, banks: u8) -> Self {
+        self.prg_banks = banks.max(1);
+        self
+    }
+
+    /// Set CHR ROM size in 8KB banks (0 for CHR RAM)
+    pub fn with_chr_banks(mut self, banks: u8) -> Self {
+        self.chr_banks = banks;
+        self
+    }
+
+    /// Set nametable mirroring (horizontal, vertical or four-screen)
+    pub fn with_mirroring(mut self, mirroring: Mirroring) -> Self {
+        self.mirroring = mirroring;
+        self
+    }
+
+    /// Mark the cartridge as having battery-backed PRG RAM
+    pub fn with_battery(mut self, battery: bool) -> Self {
+        self.battery = battery;
+        self
+    }
+
+    /// Set the byte unused PRG ROM is filled with
+    pub fn with_prg_fill(mut self, value: u8) -> Self {
+        self.prg_fill = value;
+        self
+    }
+
+    /// Set the program source
+    pub fn with_program(mut self, source: &str) -> Self {
+        self.program = source.to_string();
+        self
+    }
+
+    /// Copy raw bytes into PRG ROM at a file offset
+    pub fn with_prg_data(mut self, offset: usize, data: &[u8]) -> Self {
+        self.prg_patches.push((offset, data.to_vec()));
+        self
+    }
+
+    /// Set CHR ROM contents; shorter data is padded with zeros
+    pub fn with_chr(mut self, data: &[u8]) -> Self {
+        self.chr = data.to_vec();
+        self
+    }
+
+    /// Set the reset vector
+    pub fn with_reset_vector(mut self, addr: Word) -> Self {
+        self.reset_vector = Some(addr);
+        self
+    }
+
+    /// Set the NMI vector
+    pub fn with_nmi_vector(mut self, addr: Word) -> Self {
+        self.nmi_vector = Some(addr);
+        self
+    }
+
+    /// Set the IRQ/BRK vector
+    pub fn with_irq_vector(mut self, addr: Word) -> Self {
+        self.irq_vector = Some(addr);
+        self
+    }
+
+    /// Build the iNES image, reporting assembly errors
+    pub fn try_build(&self) -> RnesResult<Vec<u8>> {
+        let prg_size = self.prg_banks as usize * 16 * 1024;
+        let mut prg = vec![self.prg_fill; prg_size];
+        let prg_offset = |addr: Word| match addr {
+            0x8000..=0xBFFF => Ok((addr - 0x8000) as usize),
+            0xC000..=0xFFFF => Ok(prg_size - 0x4000 + (addr - 0xC000) as usize),
+            _ => Err(RnesError::MemoryAccess { address: addr }),
+        };
+
+        let assembly = rnes_cpu6502::assemble(&self.program, 0x8000)?;
+        for segment in &assembly.segments {
+            for (i, &byte) in segment.bytes.iter().enumerate() {
+                prg[prg_offset(segment.origin.wrapping_add(i as Word))?] = byte;
+            }
+        }
+        for (offset, data) in &self.prg_patches {
+            prg[*offset..*offset + data.len()].copy_from_slice(data);
+        }
+
+        let vectors = [
+            (0xFFFA, self.nmi_vector.or(assembly.label("nmi"))),
+            (0xFFFC, self.reset_vector.or(assembly.label("reset")).or(Some(0x8000))),
+            (0xFFFE, self.irq_vector.or(assembly.label("irq"))),
+        ];
+        for (addr, vector) in vectors {
+            if let Some(vector) = vector {
+                let offset = prg_offset(addr)?;
+                prg[offset..offset + 2].copy_from_slice(&vector.to_le_bytes());
+            }
+        }
+
+        let mut flags6 = (self.mapper & 0x0F) << 4;
+        if self.battery {
+            flags6 |= 0x02;
+        }
+        match self.mirroring {
+            Mirroring::Vertical => flags6 |= 0x01,
+            Mirroring::FourScreen => flags6 |= 0x08,
+            _ => {}
+        }
+
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, self.prg_banks, self.chr_banks, flags6, self.mapper & 0xF0];
+        rom.resize(16, 0);
+        rom.extend_from_slice(&prg);
+
+        let mut chr = self.chr.clone();
+        chr.resize(self.chr_banks as usize * 8 * 1024, 0);
+        rom.extend_from_slice(&chr);
+        Ok(rom)
+    }
+
+    /// Build the iNES image
+    ///
+    /// Panics if the program does not assemble.
+    pub fn build(&self) -> Vec<u8> {
+        self.try_build().unwrap_or_else(|e| panic!("test ROM failed to build: {}", e))
+    }
+
+    /// Build and load the image as a cartridge
+    pub fn build_cartridge(&self) -> Cartridge {
+        Cartridge::from_bytes(&self.build()).expect("test ROM should parse")
+    }
+}
+
+impl Default for TestRomBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CHR data with a checkerboard in the first 16 tiles
+pub fn checkerboard_chr() -> Vec<u8> {
+    let mut chr = vec![0; 16 * 16];
+    for (i, byte) in chr.iter_mut().enumerate() {
+        *byte = if i % 2 == 0 { 0xAA } else { 0x55 };
+    }
+    chr
+}
+
+/// Scanline the status bar split in `status_bar_split_rom` lands near
+pub const STATUS_BAR_SPLIT_LINE: usize = 194;
+
+/// NROM image drawing an SMB3-style status bar split
+///
+/// The playfield in nametable 0 is 8-pixel stripes of color 1 ($16, red)
+/// on even tile columns, scrolled 4 pixels left. The NMI handler sets that
+/// scroll with $2005/$2000, then waits until about `STATUS_BAR_SPLIT_LINE`
+/// and switches to nametable 2 with the $2006/$2005/$2005/$2006 sequence,
+/// which loads `v` immediately. The status bar there is unscrolled stripes
+/// of color 2 ($2A, green), so rows below the split start with 8 green
+/// pixels.
+pub fn status_bar_split_rom() -> Vec<u8> {
+    let mut chr = vec![0; 8192];
+    chr[16..24].fill(0xFF); // Tile 1: color 1
+    chr[40..48].fill(0xFF); // Tile 2: color 2
+
+    TestRomBuilder::new()
+        .with_mirroring(Mirroring::Horizontal)
+        .with_chr(&chr)
+        .with_program("
+            reset:  sei
+                    ldx #$FF
+                    txs
+                    lda #$3F
+                    sta $2006
+                    lda #$00
+                    sta $2006
+                    lda #$0F
+                    sta $2007
+                    lda #$16
+                    sta $2007
+                    lda #$2A
+                    sta $2007
+                    lda #$30
+                    sta $2007
+
+                    ; Playfield: tile 1 in even columns
+                    lda #$20
+                    sta $2006
+                    lda #$00
+                    sta $2006
+                    ldy #$04
+                    ldx #$00
+            field:  txa
+                    and #$01
+                    eor #$01
+                    sta $2007
+                    inx
+                    bne field
+                    dey
+                    bne field
+
+                    ; Status bar: tile 2 in even columns
+                    lda #$28
+                    sta $2006
+                    lda #$00
+                    sta $2006
+                    ldy #$04
+            bar:    txa
+                    and #$01
+                    eor #$01
+                    asl a
+                    sta $2007
+                    inx
+                    bne bar
+                    dey
+                    bne bar
+
+                    ; Both attribute tables select palette 0
+                    lda #$23
+                    sta $2006
+                    lda #$C0
+                    sta $2006
+                    jsr clear
+                    lda #$2B
+                    sta $2006
+                    lda #$C0
+                    sta $2006
+                    jsr clear
+
+                    lda #$80
+                    sta $2000
+                    lda #$0A
+                    sta $2001
+            loop:   jmp loop
+
+            clear:  lda #$00
+                    ldx #$40
+            attr:   sta $2007
+                    dex
+                    bne attr
+                    rts
+
+            nmi:    lda #$04
+                    sta $2005
+                    lda #$00
+                    sta $2005
+                    lda #$80
+                    sta $2000
+
+                    ; About 24500 cycles, from line 241 to the split
+                    ldx #19
+            wait:   ldy #$00
+            spin:   dey
+                    bne spin
+                    dex
+                    bne wait
+
+                    ; Land the writes in horizontal blank so the split is stable
+                    ldy #13
+            hblank: dey
+                    bne hblank
+
+                    lda #$08
+                    sta $2006
+                    lda #$00
+                    sta $2005
+                    sta $2005
+                    sta $2006
+                    rti
+        ")
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_header_and_vectors() {
+        let rom = TestRomBuilder::new()
+            .with_mapper(0x14)
+            .with_prg_banks(2)
+            .with_chr_banks(0)
+            .with_mirroring(Mirroring::Vertical)
+            .with_battery(true)
+            .with_program("
+                reset: cli
+                loop:  jmp loop
+                nmi:   rti
+                       .org $C000
+                irq:   rti
+            ")
+            .build();
+
+        assert_eq!(&rom[0..8], &[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x43, 0x10]);
+        assert_eq!(rom.len(), 16 + 32 * 1024);
+
+        let prg = &rom[16..];
+        assert_eq!(&prg[0..5], &[0x58, 0x4C, 0x01, 0x80, 0x40]);
+        assert_eq!(prg[0x4000], 0x40);
+        assert_eq!(&prg[0x7FFA..], &[0x04, 0x80, 0x00, 0x80, 0x00, 0xC0]);
+    }
+
+    #[test]
+    fn test_builder_loads_as_cartridge() {
+        let cartridge = TestRomBuilder::new()
+            .with_chr(&checkerboard_chr())
+            .with_program("loop: jmp loop")
+            .build_cartridge();
+
+        assert_eq!(cartridge.mapper_number(), 0);
+        assert_eq!(cartridge.chr_rom[1], 0x55);
+        assert_eq!(&cartridge.prg_rom[0x3FFC..0x3FFE], &[0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_builder_reports_assembly_errors() {
+        let result = TestRomBuilder::new().with_program("lda ($10").try_build();
+        assert!(matches!(result, Err(RnesError::Assembly { line: 1, .. })));
+    }
+}
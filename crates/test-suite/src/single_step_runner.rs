@@ -0,0 +1,323 @@
+//! Runner for Tom Harte's SingleStepTests CPU test vectors
+//!
+//! Each opcode has a JSON file (`a9.json` etc.) holding thousands of tests.
+//! A test gives the CPU registers and memory before one instruction, the
+//! expected state after it, and the bus activity of every cycle.
+
+use std::cell::RefCell;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use rnes_cpu6502::{Cpu, StatusFlags};
+use rnes_common::{Byte, MemoryAccess, RnesError, RnesResult, Word};
+
+/// Kind of bus cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BusCycleKind {
+    Read,
+    Write,
+}
+
+/// One cycle of bus activity: address, value and direction
+pub type BusCycle = (Word, Byte, BusCycleKind);
+
+/// CPU registers and memory contents
+#[derive(Debug, Clone, Deserialize)]
+pub struct SingleStepState {
+    pub pc: Word,
+    pub s: Byte,
+    pub a: Byte,
+    pub x: Byte,
+    pub y: Byte,
+    pub p: Byte,
+    pub ram: Vec<(Word, Byte)>,
+}
+
+/// A single test vector
+#[derive(Debug, Clone, Deserialize)]
+pub struct SingleStepTest {
+    pub name: String,
+    pub initial: SingleStepState,
+    #[serde(rename = "final")]
+    pub expected: SingleStepState,
+    pub cycles: Vec<BusCycle>,
+}
+
+/// A test that didn't match, with a line per difference
+#[derive(Debug, Clone)]
+pub struct SingleStepFailure {
+    pub test: String,
+    pub mismatches: Vec<String>,
+}
+
+/// Results for one opcode
+#[derive(Debug, Clone)]
+pub struct OpcodeReport {
+    pub opcode: Byte,
+    pub total: usize,
+    pub passed: usize,
+    /// The first failures, up to the runner's limit
+    pub failures: Vec<SingleStepFailure>,
+}
+
+impl OpcodeReport {
+    /// Check if every test passed
+    pub fn is_success(&self) -> bool {
+        self.passed == self.total
+    }
+}
+
+/// Flat 64KB memory that logs every access
+struct SingleStepMemory {
+    ram: Vec<Byte>,
+    cycles: RefCell<Vec<BusCycle>>,
+}
+
+impl SingleStepMemory {
+    fn new(state: &SingleStepState) -> Self {
+        let mut ram = vec![0; 0x10000];
+        for &(addr, value) in &state.ram {
+            ram[addr as usize] = value;
+        }
+        Self {
+            ram,
+            cycles: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl MemoryAccess for SingleStepMemory {
+    fn read_byte(&mut self, addr: Word) -> RnesResult<Byte> {
+        let value = self.ram[addr as usize];
+        self.cycles.borrow_mut().push((addr, value, BusCycleKind::Read));
+        Ok(value)
+    }
+
+    fn write_byte(&mut self, addr: Word, value: Byte) -> RnesResult<()> {
+        self.ram[addr as usize] = value;
+        self.cycles.borrow_mut().push((addr, value, BusCycleKind::Write));
+        Ok(())
+    }
+}
+
+/// SingleStepTests conformance runner
+pub struct SingleStepTestRunner {
+    tests_dir: PathBuf,
+    check_cycles: bool,
+    check_bus: bool,
+    max_failures: usize,
+}
+
+impl SingleStepTestRunner {
+    pub fn new() -> Self {
+        // The NES variant of the tests, which has no decimal mode
+        let possible_paths = [
+            PathBuf::from("tests/roms/ProcessorTests/nes6502/v1"),
+            PathBuf::from("../tests/roms/ProcessorTests/nes6502/v1"),
+            PathBuf::from("../../tests/roms/ProcessorTests/nes6502/v1"),
+        ];
+
+        let tests_dir = possible_paths.into_iter()
+            .find(|path| path.exists())
+            .unwrap_or_else(|| PathBuf::from("tests/roms/ProcessorTests/nes6502/v1"));
+
+        Self {
+            tests_dir,
+            check_cycles: true,
+            check_bus: false,
+            max_failures: 5,
+        }
+    }
+
+    /// Set the directory holding the per-opcode JSON files
+    pub fn with_tests_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.tests_dir = dir.into();
+        self
+    }
+
+    /// Compare the instruction's cycle count
+    pub fn with_cycle_check(mut self, enabled: bool) -> Self {
+        self.check_cycles = enabled;
+        self
+    }
+
+    /// Compare bus activity cycle by cycle, including dummy reads and writes
+    pub fn with_bus_check(mut self, enabled: bool) -> Self {
+        self.check_bus = enabled;
+        self
+    }
+
+    /// Set how many failures are kept per opcode
+    pub fn with_max_failures(mut self, max_failures: usize) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+
+    /// Get the test vector directory
+    pub fn tests_dir(&self) -> &Path {
+        &self.tests_dir
+    }
+
+    /// Run every opcode that has a test file
+    pub fn run_all(&self) -> RnesResult<Vec<OpcodeReport>> {
+        let mut reports = Vec::new();
+        for opcode in 0..=0xFF {
+            if let Some(report) = self.run_opcode(opcode)? {
+                reports.push(report);
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Run the tests for one opcode, or `None` if its file is missing
+    pub fn run_opcode(&self, opcode: Byte) -> RnesResult<Option<OpcodeReport>> {
+        let path = self.tests_dir.join(format!("{:02x}.json", opcode));
+        if !path.exists() {
+            return Ok(None);
+        }
+        self.run_file(&path, opcode).map(Some)
+    }
+
+    /// Run the tests in a JSON file
+    pub fn run_file(&self, path: &Path, opcode: Byte) -> RnesResult<OpcodeReport> {
+        let data = fs::read(path)?;
+        let tests: Vec<SingleStepTest> = serde_json::from_slice(&data)
+            .map_err(|e| RnesError::Serialization(format!("{}: {}", path.display(), e)))?;
+        Ok(self.run_tests(opcode, &tests))
+    }
+
+    /// Run a set of tests for one opcode
+    pub fn run_tests(&self, opcode: Byte, tests: &[SingleStepTest]) -> OpcodeReport {
+        let mut report = OpcodeReport {
+            opcode,
+            total: tests.len(),
+            passed: 0,
+            failures: Vec::new(),
+        };
+
+        for test in tests {
+            let mismatches = self.run_test(test);
+            if mismatches.is_empty() {
+                report.passed += 1;
+            } else if report.failures.len() < self.max_failures {
+                report.failures.push(SingleStepFailure {
+                    test: test.name.clone(),
+                    mismatches,
+                });
+            }
+        }
+        report
+    }
+
+    /// Run one test, returning its mismatches (empty on success)
+    pub fn run_test(&self, test: &SingleStepTest) -> Vec<String> {
+        let initial = &test.initial;
+        let mut cpu = Cpu::new();
+        cpu.pc = initial.pc;
+        cpu.sp = initial.s;
+        cpu.a = initial.a;
+        cpu.x = initial.x;
+        cpu.y = initial.y;
+        cpu.status = StatusFlags::from_bits_retain(initial.p);
+        let mut memory = SingleStepMemory::new(initial);
+
+        // Address arithmetic that overflows is a CPU bug, not a runner failure
+        let result = panic::catch_unwind(AssertUnwindSafe(|| cpu.step(&mut memory)));
+        let cycles = match result {
+            Ok(Ok(cycles)) => cycles,
+            Ok(Err(e)) => return vec![format!("error: {}", e)],
+            Err(_) => return vec!["panicked".to_string()],
+        };
+
+        let expected = &test.expected;
+        let mut mismatches = Vec::new();
+        let registers = [
+            ("PC", expected.pc, cpu.pc),
+            ("S", expected.s as Word, cpu.sp as Word),
+            ("A", expected.a as Word, cpu.a as Word),
+            ("X", expected.x as Word, cpu.x as Word),
+            ("Y", expected.y as Word, cpu.y as Word),
+            ("P", expected.p as Word, cpu.status.bits() as Word),
+        ];
+        for (name, want, got) in registers {
+            if want != got {
+                mismatches.push(format!("{}: expected ${:02X}, got ${:02X}", name, want, got));
+            }
+        }
+
+        for &(addr, want) in &expected.ram {
+            let got = memory.ram[addr as usize];
+            if want != got {
+                mismatches.push(format!("${:04X}: expected ${:02X}, got ${:02X}", addr, want, got));
+            }
+        }
+
+        if self.check_cycles && cycles as usize != test.cycles.len() {
+            mismatches.push(format!("cycles: expected {}, got {}", test.cycles.len(), cycles));
+        }
+
+        if self.check_bus {
+            let actual = memory.cycles.borrow();
+            let first_difference = test.cycles.iter()
+                .zip(actual.iter())
+                .position(|(want, got)| want != got)
+                .or_else(|| (test.cycles.len() != actual.len()).then_some(test.cycles.len().min(actual.len())));
+            if let Some(index) = first_difference {
+                mismatches.push(format!(
+                    "bus cycle {}: expected {:?}, got {:?}",
+                    index,
+                    test.cycles.get(index),
+                    actual.get(index),
+                ));
+            }
+        }
+
+        mismatches
+    }
+}
+
+impl Default for SingleStepTestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LDA_IMMEDIATE: &str = r#"[{
+        "name": "a9 42 00",
+        "initial": {"pc": 32768, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                    "ram": [[32768, 169], [32769, 66]]},
+        "final": {"pc": 32770, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36,
+                  "ram": [[32768, 169], [32769, 66]]},
+        "cycles": [[32768, 169, "read"], [32769, 66, "read"]]
+    }]"#;
+
+    #[test]
+    fn test_single_step_vector_passes() {
+        let tests: Vec<SingleStepTest> = serde_json::from_str(LDA_IMMEDIATE).unwrap();
+        let runner = SingleStepTestRunner::new().with_bus_check(true);
+
+        let report = runner.run_tests(0xA9, &tests);
+        assert!(report.is_success(), "{:?}", report.failures);
+    }
+
+    #[test]
+    fn test_single_step_reports_mismatches() {
+        let mut tests: Vec<SingleStepTest> = serde_json::from_str(LDA_IMMEDIATE).unwrap();
+        tests[0].expected.a = 0x43;
+        tests[0].cycles.push((0x8002, 0x00, BusCycleKind::Read));
+        let runner = SingleStepTestRunner::new().with_bus_check(true);
+
+        let mismatches = runner.run_test(&tests[0]);
+        assert_eq!(mismatches, vec![
+            "A: expected $43, got $42".to_string(),
+            "cycles: expected 3, got 2".to_string(),
+            "bus cycle 2: expected Some((32770, 0, Read)), got None".to_string(),
+        ]);
+    }
+}
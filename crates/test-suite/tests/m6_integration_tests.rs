@@ -126,7 +126,7 @@ fn test_m6_save_system_features() {
     println!("Testing M6 save system features...");
     
     // Test save system creation
-    let save_system = rnes_common::SaveSystem::new();
+    let save_system = rnes_common::SaveSystem::with_storage(rnes_common::MemoryStorage::new());
     
     // Test battery backup detection (this should work even without ROMs)
     let rom_name = "test_rom".to_string();
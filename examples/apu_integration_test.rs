@@ -84,7 +84,7 @@ fn test_blargg_apu_suite(test_roms_dir: &PathBuf) -> Result<(), Box<dyn std::err
                     match runner.run_test() {
                         Ok(result) => {
                             match result {
-                                ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                                ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, .. } => {
                                     if status == 0 {
                                         println!("    ✅ PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                                 status, cycles, audio_samples, avg_amplitude);
@@ -160,7 +160,7 @@ fn test_apu_components(test_roms_dir: &PathBuf) -> Result<(), Box<dyn std::error
                         match runner.run_test() {
                             Ok(result) => {
                                 match result {
-                                    ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude } => {
+                                    ApuTestResult::Completed { status, cycles, audio_samples, avg_amplitude, .. } => {
                                         if status == 0 {
                                             println!("    ✅ {} PASS (status: {}, {} cycles, {} samples, avg_amp: {:.4})", 
                                                     component_name, status, cycles, audio_samples, avg_amplitude);
@@ -41,7 +41,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     match runner.run_test() {
                         Ok(result) => {
                             match result {
-                                PpuTestResult::Completed { cycles, frames, final_frame, status: _ } => {
+                                PpuTestResult::Completed { cycles, frames, final_frame, .. } => {
                                     println!("  ✅ Test completed ({} cycles, {} frames)", cycles, frames);
                                     
                                     if !final_frame.is_empty() {
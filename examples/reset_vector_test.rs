@@ -29,7 +29,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     impl MemoryAccess for TestMemory {
-        fn read_byte(&self, addr: u16) -> rnes_common::RnesResult<rnes_common::Byte> {
+        fn read_byte(&mut self, addr: u16) -> rnes_common::RnesResult<rnes_common::Byte> {
             if addr == 0xFFFC {
                 Ok(self.reset_vector as u8)
             } else if addr == 0xFFFD {
@@ -43,7 +43,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
         
-        fn read_word(&self, addr: u16) -> rnes_common::RnesResult<u16> {
+        fn read_word(&mut self, addr: u16) -> rnes_common::RnesResult<u16> {
             if addr == 0xFFFC {
                 Ok(self.reset_vector)
             } else {
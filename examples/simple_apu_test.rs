@@ -77,7 +77,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         emulator.run_cycles(29780)?;
         
         // Get audio samples
-        let samples = emulator.get_audio_samples();
+        let samples: Vec<f32> = emulator.drain_audio().collect();
         if !samples.is_empty() {
             total_samples += samples.len();
             println!("Frame {}: Generated {} audio samples", frame_count, samples.len());
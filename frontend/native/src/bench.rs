@@ -0,0 +1,39 @@
+use anyhow::Result;
+use rnes_cartridge::Cartridge;
+use rnes_common::Config;
+use rnes_core::Emulator;
+use std::time::Instant;
+
+/// Run a ROM headless for `frames` frames and report emulated FPS
+pub fn run(rom_path: &str, frames: u64) -> Result<()> {
+    let cartridge = Cartridge::from_file(rom_path)?;
+
+    // Use default settings so results don't depend on the user's config
+    let mut emulator = Emulator::with_config(Config::default());
+    emulator.load_rom(cartridge)?;
+    emulator.start();
+
+    let start = Instant::now();
+    let mut completed = 0;
+    while completed < frames {
+        emulator.run_frame()?;
+        if !emulator.is_running() {
+            break;
+        }
+        completed += 1;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let fps = if elapsed > 0.0 { completed as f64 / elapsed } else { 0.0 };
+    println!("ROM:        {}", rom_path);
+    println!("Region:     {}", emulator.region());
+    println!("Frames:     {}", completed);
+    println!("Time:       {:.3}s", elapsed);
+    println!("FPS:        {:.1}", fps);
+    println!("Speed:      {:.1}x realtime", fps / emulator.frame_rate());
+
+    if completed < frames {
+        println!("Emulator stopped after {} of {} frames", completed, frames);
+    }
+    Ok(())
+}
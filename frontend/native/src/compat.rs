@@ -0,0 +1,26 @@
+use anyhow::Result;
+use rnes_compat::CompatRunner;
+use std::path::Path;
+
+/// Boot every ROM under `rom_dir` for `frames` frames and report the results
+///
+/// The report goes to `output` as Markdown (`.md`) or CSV (anything else).
+pub fn run(rom_dir: &str, frames: u32, output: Option<&str>) -> Result<()> {
+    let report = CompatRunner::new()
+        .with_frames(frames)
+        .run_dir(Path::new(rom_dir))?;
+
+    for entry in &report.entries {
+        match &entry.message {
+            Some(message) => println!("{:<40} {} ({})", entry.name(), entry.status, message),
+            None => println!("{:<40} {}", entry.name(), entry.status),
+        }
+    }
+    println!("{}", report.summary());
+
+    if let Some(output) = output {
+        report.write(Path::new(output))?;
+        println!("Report written to {}", output);
+    }
+    Ok(())
+}
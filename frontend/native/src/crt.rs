@@ -0,0 +1,113 @@
+use anyhow::{anyhow, bail, Context, Result};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, ShaderStage};
+use rnes_common::VideoConfig;
+use rnes_core::{post_process_chain, PostPass};
+use std::borrow::Cow;
+use tracing::info;
+
+/// Built-in CRT shader, covering every `CrtPreset`
+pub const CRT_SHADER: &str = include_str!("shaders/crt.wgsl");
+
+/// Entry points a CRT shader must define
+const ENTRY_POINTS: [(&str, ShaderStage); 2] = [
+    ("vs_main", ShaderStage::Vertex),
+    ("fs_main", ShaderStage::Fragment),
+];
+
+/// Size in bytes of the uniform block filled from `CrtParams::uniforms`
+const UNIFORM_SIZE: u32 = 10 * 4;
+
+/// WGSL source of the CRT pass: the configured file, or the built-in shader
+///
+/// Custom shaders get the same bindings and uniform block as the built-in
+/// one, and are parsed and validated before use.
+pub fn shader_source(config: &VideoConfig) -> Result<Cow<'static, str>> {
+    let Some(path) = &config.crt_shader else {
+        return Ok(Cow::Borrowed(CRT_SHADER));
+    };
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read CRT shader {}", path.display()))?;
+    validate(&source).with_context(|| format!("Invalid CRT shader {}", path.display()))?;
+    Ok(Cow::Owned(source))
+}
+
+/// Parse and validate WGSL, then check the entry points and uniform block
+fn validate(source: &str) -> Result<()> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|error| anyhow!(error.emit_to_string(source)))?;
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|error| anyhow!(error.emit_to_string(source)))?;
+    for (name, stage) in ENTRY_POINTS {
+        if !module.entry_points.iter().any(|entry| entry.name == name && entry.stage == stage) {
+            bail!("No {:?} entry point `{}`", stage, name);
+        }
+    }
+    let Some((_, uniform)) = module.global_variables.iter().find(|(_, var)| var.space == AddressSpace::Uniform) else {
+        bail!("No uniform block");
+    };
+    let size = module.types[uniform.ty].inner.size(module.to_ctx());
+    if size != UNIFORM_SIZE {
+        bail!("Uniform block is {} bytes, expected {}", size, UNIFORM_SIZE);
+    }
+    Ok(())
+}
+
+/// Log the post-process passes the configuration sets up
+pub fn describe(config: &VideoConfig) -> Result<()> {
+    let passes = post_process_chain(config);
+    if passes.is_empty() {
+        return Ok(());
+    }
+    let mut names = Vec::new();
+    for pass in &passes {
+        match pass {
+            PostPass::Ntsc { strength } => names.push(format!("NTSC x{:.2}", strength)),
+            PostPass::Crt { preset, .. } => {
+                shader_source(config)?;
+                let shader = match &config.crt_shader {
+                    Some(path) => path.display().to_string(),
+                    None => "built-in".to_string(),
+                };
+                names.push(format!("CRT {:?} ({})", preset, shader));
+            }
+        }
+    }
+    info!("Post-process: {}", names.join(" -> "));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rnes_common::CrtPreset;
+    use rnes_core::CrtParams;
+
+    #[test]
+    fn test_builtin_shader_validates() {
+        validate(CRT_SHADER).unwrap();
+    }
+
+    #[test]
+    fn test_every_preset_uses_valid_shader() {
+        for preset in CrtPreset::ALL {
+            let config = VideoConfig { crt_preset: preset, ..VideoConfig::default() };
+            validate(&shader_source(&config).unwrap()).unwrap();
+            if let Some(params) = CrtParams::for_preset(preset, config.scanline_intensity) {
+                let uniforms = params.uniforms((256, 240), (1024, 960));
+                assert_eq!(std::mem::size_of_val(&uniforms) as u32, UNIFORM_SIZE);
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_shader_is_validated() {
+        let path = std::env::temp_dir().join(format!("rnes-crt-{}.wgsl", std::process::id()));
+        std::fs::write(&path, CRT_SHADER.replace("crt.vignette", "crt.vignete")).unwrap();
+        let config = VideoConfig { crt_shader: Some(path.clone()), ..VideoConfig::default() };
+        let result = shader_source(&config);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,16 +1,76 @@
+mod bench;
+mod compat;
+mod crt;
+mod pacing;
+
 use anyhow::Result;
-use clap::Parser;
-use rnes_core::Emulator;
+use clap::{Parser, Subcommand};
+use rnes_core::{Emulator, Session, Viewport};
 use rnes_cartridge::Cartridge;
+use rnes_common::RnesError;
 use tracing::{info, error};
 
 #[derive(Parser)]
 #[command(name = "rnes")]
 #[command(about = "NES emulator written in Rust")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// ROM file to load
     #[arg(value_name = "ROM_FILE")]
     rom_file: Option<String>,
+
+    /// Start fresh instead of resuming the last session
+    #[arg(long)]
+    no_resume: bool,
+
+    /// Display refresh rate in Hz to pace emulation against
+    #[arg(long, default_value_t = 60.0)]
+    refresh_rate: f64,
+
+    /// Number of display refreshes to run the ROM for
+    #[arg(long, default_value_t = 600)]
+    refreshes: u64,
+
+    /// Show frame pacing statistics in the overlay
+    #[arg(long)]
+    show_pacing: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a ROM headless and report emulation speed
+    Bench {
+        /// ROM file to benchmark
+        #[arg(value_name = "ROM_FILE")]
+        rom_file: String,
+
+        /// Number of frames to emulate
+        #[arg(long, default_value_t = 3600)]
+        frames: u64,
+    },
+    /// Print a ROM's header, sizes and hashes
+    Info {
+        /// ROM file to inspect
+        #[arg(value_name = "ROM_FILE")]
+        rom_file: String,
+    },
+    /// Boot every ROM in a directory and write a compatibility report
+    Compat {
+        /// Directory searched recursively for .nes files
+        #[arg(value_name = "ROM_DIR")]
+        rom_dir: String,
+
+        /// Number of frames to run each ROM
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+
+        /// Report file; Markdown for .md, CSV otherwise
+        #[arg(long)]
+        output: Option<String>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -21,9 +81,24 @@ fn main() -> Result<()> {
     
     let args = Args::parse();
     
+    match args.command {
+        Some(Command::Bench { rom_file, frames }) => return bench::run(&rom_file, frames),
+        Some(Command::Info { rom_file }) => {
+            println!("ROM:        {}", rom_file);
+            let info = Cartridge::from_file(&rom_file)?.info();
+            println!("{}", info);
+            println!("Support:    {}", rnes_mappers::describe(info.mapper).support);
+            return Ok(());
+        }
+        Some(Command::Compat { rom_dir, frames, output }) => {
+            return compat::run(&rom_dir, frames, output.as_deref());
+        }
+        None => {}
+    }
+    
     info!("RNES - NES Emulator Starting");
     
-    let mut emulator = Emulator::new();
+    let mut session = Session::with_emulator(Emulator::new()).with_resume(!args.no_resume);
     
     let has_rom = args.rom_file.is_some();
     
@@ -31,8 +106,14 @@ fn main() -> Result<()> {
         info!("Loading ROM: {}", rom_path);
         match Cartridge::from_file(&rom_path) {
             Ok(cartridge) => {
-                if let Err(e) = emulator.load_rom(cartridge) {
-                    error!("Failed to load ROM: {}", e);
+                // Resumes the last session or fast-boots, as configured
+                if let Err(e) = session.load_rom(cartridge) {
+                    match e {
+                        RnesError::UnsupportedMapper(mapper) => {
+                            error!("Failed to load ROM: {}", rnes_mappers::describe(mapper));
+                        }
+                        _ => error!("Failed to load ROM: {}", e),
+                    }
                     return Err(e.into());
                 }
                 info!("ROM loaded successfully");
@@ -46,6 +127,8 @@ fn main() -> Result<()> {
         info!("No ROM file specified, starting debug mode");
     }
     
+    let emulator = session.emulator_mut();
+    
     // Simple test loop
     emulator.start();
     
@@ -86,16 +169,38 @@ fn main() -> Result<()> {
             info!("Memory[0x00] = 0x{:02X}", emulator.bus.read_byte(0x0000).unwrap());
         }
     } else {
-        // Run ROM
-        for i in 0..1000 {
-            if let Err(e) = emulator.step() {
-                error!("Emulator error: {}", e);
-                break;
-            }
-            
-            if i % 100 == 0 {
-                info!("CPU status: {}", emulator.cpu_status());
-            }
+        // Run ROM paced to the display
+        emulator.overlay.show_pacing = args.show_pacing;
+        let video = &emulator.get_config().video;
+        let viewport = Viewport::from_config(video, video.window_width, video.window_height);
+        let (prescale_x, prescale_y) = viewport.prescale();
+        info!(
+            "Viewport {}x{} at ({}, {}) in a {}x{} window, {:?} scaling, {}",
+            viewport.width,
+            viewport.height,
+            viewport.x,
+            viewport.y,
+            video.window_width,
+            video.window_height,
+            video.scale_mode,
+            if viewport.is_integer_scale() {
+                "nearest".to_string()
+            } else if video.sharp_bilinear {
+                format!("sharp-bilinear from {}x/{}x", prescale_x, prescale_y)
+            } else {
+                "bilinear".to_string()
+            },
+        );
+        crt::describe(video)?;
+        if let Err(e) = pacing::run(emulator, args.refresh_rate, args.refreshes) {
+            error!("Emulator error: {}", e);
+        }
+        info!("CPU status: {}", emulator.cpu_status());
+    }
+    
+    if has_rom {
+        if let Err(e) = emulator.shutdown() {
+            error!("Failed to save on exit: {}", e);
         }
     }
     
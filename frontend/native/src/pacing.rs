@@ -0,0 +1,104 @@
+use anyhow::Result;
+use rnes_common::AUDIO_SAMPLE_RATE;
+use rnes_core::{AudioStretcher, Emulator, RefreshPacer};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Time left before a deadline that is spun instead of slept, covering the
+/// OS scheduler's wake-up latency
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+/// Audio kept queued ahead of playback
+const AUDIO_TARGET_LATENCY: Duration = Duration::from_millis(50);
+
+/// Waits for refresh deadlines by sleeping, then spinning the last stretch
+#[derive(Debug)]
+pub struct FrameClock {
+    start: Instant,
+    interval: Duration,
+    next: Instant,
+}
+
+impl FrameClock {
+    /// Create clock ticking every `interval`, starting now
+    pub fn new(interval: Duration) -> Self {
+        let start = Instant::now();
+        Self { start, interval, next: start + interval }
+    }
+
+    /// Block until the next refresh and return its time in ms since start
+    pub fn wait(&mut self) -> f64 {
+        let now = Instant::now();
+        if let Some(remaining) = self.next.checked_duration_since(now) {
+            if remaining > SPIN_MARGIN {
+                std::thread::sleep(remaining - SPIN_MARGIN);
+            }
+            while Instant::now() < self.next {
+                std::hint::spin_loop();
+            }
+        } else if now - self.next > self.interval {
+            // Missed more than a whole refresh; don't try to catch up
+            self.next = now;
+        }
+        let timestamp = self.next.duration_since(self.start).as_secs_f64() * 1000.0;
+        self.next += self.interval;
+        timestamp
+    }
+}
+
+/// Run the loaded ROM for `refreshes` display refreshes at `refresh_rate`
+///
+/// Audio goes through an `AudioStretcher` into a queue drained at the
+/// output rate, standing in for the audio device until there is one.
+pub fn run(emulator: &mut Emulator, refresh_rate: f64, refreshes: u64) -> Result<()> {
+    let mut pacer = RefreshPacer::for_region(emulator.region(), refresh_rate);
+    let mut clock = FrameClock::new(Duration::from_secs_f64(pacer.refresh_interval() / 1000.0));
+    let mut stretcher = AudioStretcher::new();
+    let mut queue = Vec::new();
+    let mut audio = Vec::new();
+    let target_samples = AUDIO_TARGET_LATENCY.as_secs_f64() * AUDIO_SAMPLE_RATE as f64;
+    let consumed_per_refresh = AUDIO_SAMPLE_RATE as f64 / refresh_rate;
+    let mut consumed = 0.0;
+
+    info!(
+        "Pacing {:.3} fps to a {:.2} Hz display ({})",
+        emulator.frame_rate(),
+        refresh_rate,
+        if pacer.is_locked() { "locked" } else { "free-running" },
+    );
+
+    for refresh in 0..refreshes {
+        let timestamp = clock.wait();
+        let frames = pacer.on_refresh(timestamp);
+        if emulator.run_frames(frames)? < frames {
+            break;
+        }
+
+        let ratio = pacer.audio_ratio(queue.len() as f64 / target_samples);
+        audio.clear();
+        audio.extend(emulator.drain_audio());
+        stretcher.process(&audio, ratio, &mut queue);
+        consumed += consumed_per_refresh;
+        let drained = (consumed as usize).min(queue.len());
+        consumed -= drained as f64;
+        queue.drain(..drained);
+
+        for warning in emulator.take_warnings() {
+            warn!("{}", warning);
+        }
+        emulator.overlay.set_pacer_stats(pacer.stats());
+        if refresh % 600 == 0 {
+            let stats = pacer.stats();
+            info!(
+                "Frames {} (repeated {}, doubled {}), refresh {:.2}ms, jitter {:.2}ms, audio x{:.4}",
+                stats.frames_run,
+                stats.frames_repeated,
+                stats.frames_doubled,
+                stats.refresh_interval_ms,
+                stats.jitter_ms,
+                stats.audio_ratio,
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+// CRT post-process pass: scanlines, phosphor mask, curvature and vignette
+//
+// Drawn as one full-screen triangle over the viewport. The uniform block
+// matches `CrtParams::uniforms` in rnes-core.
+
+struct Crt {
+    scanline_intensity: f32,
+    mask_strength: f32,
+    mask_kind: f32,
+    curvature: f32,
+    vignette: f32,
+    _pad: f32,
+    source_size: vec2<f32>,
+    output_size: vec2<f32>,
+}
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var frame_sampler: sampler;
+@group(0) @binding(2) var<uniform> crt: Crt;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Bend the picture outwards from the centre like the glass of a tube
+fn barrel(uv: vec2<f32>) -> vec2<f32> {
+    let centered = uv * 2.0 - 1.0;
+    let bent = centered * (1.0 + crt.curvature * dot(centered, centered));
+    return bent * 0.5 + 0.5;
+}
+
+// Darken subpixels of the other two colors in each phosphor triad
+fn phosphor_mask(position: vec2<f32>) -> vec3<f32> {
+    var column = u32(position.x) % 3u;
+    if (crt.mask_kind > 0.5) {
+        // Shadow mask: triads shift by half a triad every other row
+        column = (u32(position.x) + (u32(position.y) & 1u) * 2u) % 3u;
+    }
+    var mask = vec3<f32>(1.0 - crt.mask_strength);
+    mask[column] = 1.0;
+    return mask;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = barrel(in.uv);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    var color = textureSample(frame, frame_sampler, uv).rgb;
+
+    // Each NES line is brightest in its middle and darkest at its edges
+    let line = fract(uv.y * crt.source_size.y);
+    let beam = 1.0 - crt.scanline_intensity * pow(abs(line - 0.5) * 2.0, 2.0);
+    color *= beam;
+
+    color *= phosphor_mask(in.position.xy);
+
+    let edge = uv * (1.0 - uv);
+    let corner = clamp(pow(edge.x * edge.y * 16.0, 0.25), 0.0, 1.0);
+    color *= mix(1.0, corner, crt.vignette);
+
+    return vec4<f32>(color, 1.0);
+}
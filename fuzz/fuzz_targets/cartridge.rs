@@ -0,0 +1,21 @@
+//! Parse arbitrary bytes as an iNES/NES 2.0 ROM and run it briefly
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rnes_cartridge::Cartridge;
+use rnes_common::Config;
+use rnes_core::Emulator;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(cartridge) = Cartridge::from_bytes(data) else {
+        return;
+    };
+
+    let mut emulator = Emulator::with_config(Config::default());
+    if emulator.load_rom(cartridge).is_err() {
+        return;
+    }
+    emulator.start();
+    let _ = emulator.run_frames(2);
+});
@@ -0,0 +1,51 @@
+//! Drive mapper registers with arbitrary writes
+//!
+//! The input is a 16-byte header followed by (address high, address low,
+//! value) triples. PRG and CHR data are filled in so every header parses.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rnes_cartridge::Cartridge;
+use rnes_mappers::create_mapper;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 16 {
+        return;
+    }
+    let (header, writes) = data.split_at(16);
+
+    let mut rom = header.to_vec();
+    rom[0..4].copy_from_slice(b"NES\x1A");
+    // Keep ROMs small so iterations stay fast
+    rom[4] = (rom[4] % 8).max(1);
+    rom[5] %= 8;
+    let trainer = if rom[6] & 0x04 != 0 { 512 } else { 0 };
+    let size = trainer + rom[4] as usize * 16384 + rom[5] as usize * 8192;
+    rom.extend((0..size).map(|i| i as u8));
+
+    let Ok(cartridge) = Cartridge::from_bytes(&rom) else {
+        return;
+    };
+    let Ok(mut mapper) = create_mapper(cartridge) else {
+        return;
+    };
+
+    for write in writes.chunks_exact(3) {
+        let addr = u16::from_be_bytes([write[0], write[1]]);
+        if addr >= 0x4020 {
+            let _ = mapper.write_prg(addr, write[2]);
+        } else {
+            let _ = mapper.write_chr(addr & 0x1FFF, write[2]);
+        }
+        mapper.clock_cpu_cycle();
+        mapper.notify_scanline((write[2] as i32) % 262);
+
+        for addr in (0x6000..=0xFFFF).step_by(0x3FF) {
+            let _ = mapper.read_prg(addr);
+        }
+        for addr in (0x0000..0x2000).step_by(0x3FF) {
+            let _ = mapper.read_chr(addr);
+        }
+    }
+});
@@ -0,0 +1,66 @@
+//! Load arbitrary bytes as a save state
+//!
+//! The first byte picks the board, so the fuzzer reaches every mapper's
+//! state decoder. The rest is tried both as-is and with a valid header
+//! wrapped around it, so the fuzzer reaches the deserializer without
+//! having to guess the magic, version and checksum.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rnes_cartridge::Cartridge;
+use rnes_common::{crc32, Config, MemoryStorage, SaveStateHeader, SaveSystem, SAVE_STATE_VERSION};
+use rnes_core::Emulator;
+
+/// One mapper number per board with its own save state
+const MAPPERS: [u8; 10] = [0, 1, 2, 3, 4, 5, 7, 64, 85, 206];
+
+/// 32KB PRG, 8KB CHR ROM with battery RAM that loops forever
+///
+/// Every 8KB PRG bank starts with `JMP $8000` and ends with vectors
+/// pointing there, so any bank layout the state selects still runs.
+fn test_rom(mapper: u8) -> Vec<u8> {
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, (mapper << 4) | 0x02, mapper & 0xF0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0xEA; 32768];
+    for bank in prg.chunks_exact_mut(8192) {
+        bank[0..3].copy_from_slice(&[0x4C, 0x00, 0x80]);
+        bank[0x1FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+    }
+    rom.extend(prg);
+    rom.extend(vec![0; 8192]);
+    rom
+}
+
+/// Prefix `payload` with a valid uncompressed header
+fn wrap(payload: &[u8]) -> Vec<u8> {
+    let header = SaveStateHeader {
+        version: SAVE_STATE_VERSION,
+        flags: 0,
+        rom_crc32: 0,
+        payload_crc32: crc32(payload),
+        payload_len: payload.len() as u32,
+    };
+    let mut state = header.to_bytes().to_vec();
+    state.extend_from_slice(payload);
+    state
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&board, data)) = data.split_first() else {
+        return;
+    };
+    let mapper = MAPPERS[board as usize % MAPPERS.len()];
+
+    let mut config = Config::default();
+    config.general.auto_save_battery = false;
+    let mut emulator = Emulator::with_config(config)
+        .with_save_system(SaveSystem::with_storage(MemoryStorage::new()));
+    emulator.load_rom(Cartridge::from_bytes(&test_rom(mapper)).unwrap()).unwrap();
+
+    for state in [data.to_vec(), wrap(data)] {
+        if emulator.load_state_from_bytes(&state).is_ok() {
+            emulator.start();
+            let _ = emulator.run_frame();
+        }
+    }
+});
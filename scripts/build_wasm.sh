@@ -0,0 +1,54 @@
+#!/bin/bash
+
+# Build the web variants of a crate with the wasm-release profile
+#
+#   scalar   - baseline wasm32
+#   simd     - compiled with +simd128; there are no hand-written SIMD paths
+#   threads  - +simd128 and shared memory (needs nightly); only built with
+#              --threads, and nothing in core spawns threads yet
+#
+# Only the build is covered here. There is no loader that picks a variant,
+# and core still uses std::time::Instant, which panics on
+# wasm32-unknown-unknown; see docs/WEB_FRONTEND.md.
+#
+# Usage: scripts/build_wasm.sh [-p PACKAGE] [--threads]
+
+set -e
+
+PACKAGE="rnes-capi"
+THREADS=0
+TARGET="wasm32-unknown-unknown"
+OUT_DIR="target/wasm"
+
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -p) PACKAGE="$2"; shift 2 ;;
+        --threads) THREADS=1; shift ;;
+        *) echo "Unknown argument: $1"; exit 1 ;;
+    esac
+done
+
+build() {
+    local variant="$1"
+    local flags="$2"
+    shift 2
+    echo "📦 Building $PACKAGE ($variant)..."
+    RUSTFLAGS="$flags" cargo "$@" build -p "$PACKAGE" --target "$TARGET" --profile wasm-release \
+        --target-dir "target/wasm-$variant"
+    local wasm=("target/wasm-$variant/$TARGET/wasm-release/"*.wasm)
+    if [ ! -e "${wasm[0]}" ]; then
+        echo "❌ No .wasm produced for $variant; $PACKAGE needs a cdylib crate type" >&2
+        exit 1
+    fi
+    mkdir -p "$OUT_DIR/$variant"
+    cp "${wasm[@]}" "$OUT_DIR/$variant/"
+}
+
+build scalar ""
+build simd "-C target-feature=+simd128"
+if [ "$THREADS" = 1 ]; then
+    build threads "-C target-feature=+simd128,+atomics,+bulk-memory,+mutable-globals" \
+        +nightly -Z build-std=panic_abort,std
+fi
+
+echo "✅ Web builds written to $OUT_DIR"